@@ -43,7 +43,7 @@ impl Engine {
                     sprite_renderer: SpriteRenderer::new(
                         &renderer.gpu,
                         renderer.config().view_formats[1].into(),
-                        renderer.depth_texture().format(),
+                        renderer.depth_format(),
                     ),
                     window,
                     renderer,
@@ -117,13 +117,15 @@ impl Engine {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: self.renderer.depth_texture_view(),
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
+                depth_stencil_attachment: self.renderer.depth_texture_view().map(|view| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }
                 }),
                 ..Default::default()
             });
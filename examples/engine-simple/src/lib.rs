@@ -1,7 +1,6 @@
 use std::sync::Arc;
 
 pub use bytemuck::Zeroable;
-use frenderer::{EventPhase, Logger};
 pub use frenderer::{
     bitfont::BitFont,
     clock::Clock,
@@ -11,6 +10,7 @@ pub use frenderer::{
     sprites::{Camera2D as Camera, SheetRegion, Transform},
     wgpu, Renderer,
 };
+use frenderer::{EventPhase, Logger};
 use std::cell::OnceCell;
 pub trait Game: Sized + 'static {
     fn new(engine: &mut Engine) -> Self;
@@ -37,14 +37,13 @@ pub fn run<G: Game>(
     elp.run(move |event, target| {
         if let winit::event::Event::Resumed = event {
             if let Some(builder) = builder.take() {
-                let instance = Arc::new(wgpu::Instance::default());
                 let window = Arc::new(builder.build(target).unwrap());
                 frenderer::prepare_window(&window);
-                let surface = instance.create_surface(Arc::clone(&window)).unwrap();
                 let init = Arc::clone(&init);
                 let window = Arc::clone(&window);
-                let instance = Arc::clone(&instance);
                 let fut = async move {
+                    let instance = frenderer::WGPU::detect_instance().await;
+                    let surface = instance.create_surface(Arc::clone(&window)).unwrap();
                     let renderer =
                         Renderer::with_surface(1024, 768, 1024, 768, instance, Some(surface))
                             .await
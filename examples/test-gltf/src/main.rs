@@ -230,6 +230,7 @@ fn load_gltf_single_textured(
             submeshes: vec![frenderer::meshes::SubmeshEntry {
                 vertex_base: 0,
                 indices: 0..vert_count as u32,
+                layer: None,
             }],
         }],
     )
@@ -288,6 +289,7 @@ fn load_gltf_flat(frend: &mut frenderer::Renderer, asset: &Gltf, instance_count:
             entry.submeshes.push(frenderer::meshes::SubmeshData {
                 indices: idx_old_len as u32..(indices.len() as u32),
                 vertex_base,
+                layer: None,
             })
         }
         assert!(!entry.submeshes.is_empty());
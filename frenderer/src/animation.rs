@@ -0,0 +1,226 @@
+//! Sprite sheet animations: sequences of [`SheetRegion`]s, each shown
+//! for a while.
+//!
+//! A [`SpriteAnimation`] holds the frames and what happens after the
+//! last one; an [`AnimationPlayer`] plays one back.  Each frame, call
+//! [`AnimationPlayer::tick`] with the time that has passed and write
+//! the region it returns into the sprite's slot, e.g. in
+//! [`crate::Renderer::sprites_mut`].
+
+use crate::sprites::SheetRegion;
+
+/// What an animation does after its last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Stop on the last frame.
+    Once,
+    /// Start over from the first frame.
+    #[default]
+    Loop,
+    /// Play backwards to the first frame, then forwards again, and so on.
+    PingPong,
+}
+
+/// One frame of a [`SpriteAnimation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationFrame {
+    pub region: SheetRegion,
+    /// How long the frame is shown, in seconds.
+    pub duration: f32,
+}
+
+/// A sequence of frames and what to do after the last one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteAnimation {
+    frames: Vec<AnimationFrame>,
+    loop_mode: LoopMode,
+}
+
+impl SpriteAnimation {
+    /// Creates an animation from the given frames.  Panics if there
+    /// are no frames or some frame's duration isn't positive.
+    pub fn new(frames: Vec<AnimationFrame>, loop_mode: LoopMode) -> Self {
+        assert!(!frames.is_empty(), "An animation needs at least one frame");
+        assert!(
+            frames.iter().all(|frame| frame.duration > 0.0),
+            "Animation frames must last longer than 0 seconds"
+        );
+        Self { frames, loop_mode }
+    }
+    /// Creates an animation showing each region for `frame_duration` seconds.
+    pub fn uniform(
+        regions: impl IntoIterator<Item = SheetRegion>,
+        frame_duration: f32,
+        loop_mode: LoopMode,
+    ) -> Self {
+        Self::new(
+            regions
+                .into_iter()
+                .map(|region| AnimationFrame {
+                    region,
+                    duration: frame_duration,
+                })
+                .collect(),
+            loop_mode,
+        )
+    }
+    /// Creates an animation of `count` frames laid out left to right
+    /// in a row of the sprite sheet, starting with `first` and each
+    /// as wide as it, each shown for `frame_duration` seconds.
+    pub fn strip(
+        first: SheetRegion,
+        count: usize,
+        frame_duration: f32,
+        loop_mode: LoopMode,
+    ) -> Self {
+        let step = first.w.unsigned_abs();
+        Self::uniform(
+            (0..count as u16).map(|i| SheetRegion {
+                x: first.x + i * step,
+                ..first
+            }),
+            frame_duration,
+            loop_mode,
+        )
+    }
+    /// Returns the frames.
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+    /// Returns what the animation does after its last frame.
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+    /// Changes what the animation does after its last frame.
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) {
+        self.loop_mode = loop_mode;
+    }
+    // The frame indices of one pass through the animation; ping-pong
+    // animations don't repeat their first and last frames on the way back
+    fn pass_frames(&self) -> impl Iterator<Item = usize> {
+        let count = self.frames.len();
+        let back = if self.loop_mode == LoopMode::PingPong {
+            1..count.saturating_sub(1)
+        } else {
+            0..0
+        };
+        (0..count).chain(back.rev())
+    }
+    /// Returns how long one pass through the animation takes, in
+    /// seconds (there and back for [`LoopMode::PingPong`]).
+    pub fn duration(&self) -> f32 {
+        self.pass_frames().map(|i| self.frames[i].duration).sum()
+    }
+    /// Returns the index of the frame shown `time` seconds after the
+    /// animation starts.
+    pub fn frame_at(&self, time: f32) -> usize {
+        let duration = self.duration();
+        if self.loop_mode == LoopMode::Once && time >= duration {
+            return self.frames.len() - 1;
+        }
+        let mut time = time.max(0.0) % duration;
+        let mut last = 0;
+        for i in self.pass_frames() {
+            if time < self.frames[i].duration {
+                return i;
+            }
+            time -= self.frames[i].duration;
+            last = i;
+        }
+        // Only reachable through rounding error at the very end
+        last
+    }
+    /// Returns true if a [`LoopMode::Once`] animation has reached its
+    /// end `time` seconds after starting.  Looping animations never end.
+    pub fn finished_at(&self, time: f32) -> bool {
+        self.loop_mode == LoopMode::Once && time >= self.duration()
+    }
+}
+
+/// Plays back a [`SpriteAnimation`].
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    animation: SpriteAnimation,
+    time: f32,
+    speed: f32,
+    paused: bool,
+}
+
+impl AnimationPlayer {
+    /// Creates a player at the start of the given animation.
+    pub fn new(animation: SpriteAnimation) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+    /// Returns the animation being played.
+    pub fn animation(&self) -> &SpriteAnimation {
+        &self.animation
+    }
+    /// Switches to the given animation from its start, unless it's
+    /// already the one being played; so it's fine to call this every
+    /// frame with e.g. the animation for the character's current state.
+    pub fn play(&mut self, animation: &SpriteAnimation) {
+        if self.animation != *animation {
+            self.animation = animation.clone();
+            self.time = 0.0;
+        }
+    }
+    /// Goes back to the start of the animation.
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+    /// Pauses or resumes playback; a paused player's [`Self::tick`]
+    /// doesn't advance.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+    /// Returns true if playback is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+    /// Sets how fast the animation plays, as a multiple of its frame
+    /// durations (1.0 at first).  Negative speeds are treated as 0.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+    /// Returns how fast the animation plays.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    /// Returns how far into the animation playback is, in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+    /// Jumps to the given time into the animation, in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.max(0.0);
+    }
+    /// Returns the index of the frame being shown.
+    pub fn frame(&self) -> usize {
+        self.animation.frame_at(self.time)
+    }
+    /// Returns the region of the frame being shown.
+    pub fn region(&self) -> SheetRegion {
+        self.animation.frames[self.frame()].region
+    }
+    /// Returns true if a [`LoopMode::Once`] animation has reached its end.
+    pub fn finished(&self) -> bool {
+        self.animation.finished_at(self.time)
+    }
+    /// Advances playback by `dt` seconds (scaled by the speed) unless
+    /// it's paused, and returns the region of the frame being shown.
+    pub fn tick(&mut self, dt: f32) -> SheetRegion {
+        if !self.paused {
+            self.time += dt * self.speed;
+            // Keep looping animations' time from growing without bound
+            if self.animation.loop_mode != LoopMode::Once {
+                self.time %= self.animation.duration();
+            }
+        }
+        self.region()
+    }
+}
@@ -0,0 +1,273 @@
+//! Packing many separate images into the layers of one array
+//! texture, so sprites drawn from them can share a sprite group.
+//!
+//! [`Atlas::pack`] takes RGBA8 images of any sizes and places them on
+//! shelves (rows as tall as their tallest image), tallest images
+//! first, starting a new layer whenever one fills up.  Each image
+//! gets a [`SheetRegion`] covering exactly its pixels, in the order
+//! the images were given.  Filtering or mipmapping can blend in
+//! texels from next to a region, so images can be separated by
+//! transparent `padding` and surrounded by `extrude` copies of their
+//! edge pixels (see [`AtlasOptions`]).
+//!
+//! ```no_run
+//! # fn load(frend: &frenderer::Renderer, player: &[u8], coin: &[u8]) -> Result<(), frenderer::atlas::AtlasError> {
+//! use frenderer::atlas::{Atlas, AtlasImage, AtlasOptions};
+//! let atlas = Atlas::pack(
+//!     &[AtlasImage::new(16, 24, player), AtlasImage::new(8, 8, coin)],
+//!     &AtlasOptions::default(),
+//! )?;
+//! let texture = atlas.create_texture(frend, frenderer::wgpu::TextureFormat::Rgba8UnormSrgb, Some("sprites"));
+//! let [player_region, coin_region] = [atlas.region(0), atlas.region(1)];
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::sprites::SheetRegion;
+
+/// An RGBA8 image to pack into an [`Atlas`], `width` texels wide and
+/// `height` rows tall.
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    /// Four bytes per texel, row by row from the top
+    pub pixels: &'a [u8],
+}
+
+impl<'a> AtlasImage<'a> {
+    /// Creates an [`AtlasImage`] with the given size and pixels.
+    pub fn new(width: u32, height: u32, pixels: &'a [u8]) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// How an [`Atlas`] is laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasOptions {
+    /// The width of each layer in texels
+    pub width: u32,
+    /// The height of each layer in texels
+    pub height: u32,
+    /// Transparent texels left around each image (after extrusion)
+    pub padding: u32,
+    /// How many times each image's edge texels are repeated outwards,
+    /// so that filtering at its edges doesn't blend in transparency
+    pub extrude: u32,
+}
+
+impl Default for AtlasOptions {
+    /// 1024 by 1024 layers with one texel of padding and no extrusion
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            padding: 1,
+            extrude: 0,
+        }
+    }
+}
+
+/// An error packing an [`Atlas`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtlasError {
+    /// The image with the given index doesn't fit in a layer along
+    /// with its padding and extrusion
+    TooLarge { image: usize },
+    /// The image with the given index doesn't have four bytes for
+    /// each of its texels
+    WrongSize { image: usize },
+    /// Layers are too big for their texels to be addressed by a [`SheetRegion`]
+    LayerTooLarge,
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::TooLarge { image } => {
+                write!(f, "Atlas image {image} doesn't fit in a layer")
+            }
+            AtlasError::WrongSize { image } => write!(
+                f,
+                "Atlas image {image} doesn't have four bytes for each texel"
+            ),
+            AtlasError::LayerTooLarge => {
+                write!(f, "Atlas layers can be at most 32767 texels on a side")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+// A row of images across a layer
+struct Shelf {
+    layer: usize,
+    y: u32,
+    height: u32,
+    // Where the next image goes
+    x: u32,
+}
+
+/// Images packed into the layers of an array texture.
+#[derive(Clone, Debug)]
+pub struct Atlas {
+    width: u32,
+    height: u32,
+    layers: u32,
+    data: Vec<u8>,
+    regions: Vec<SheetRegion>,
+}
+
+impl Atlas {
+    /// Packs `images` into as many layers as they need.
+    pub fn pack(images: &[AtlasImage], options: &AtlasOptions) -> Result<Self, AtlasError> {
+        let &AtlasOptions {
+            width,
+            height,
+            padding,
+            extrude,
+        } = options;
+        if width > i16::MAX as u32 || height > i16::MAX as u32 {
+            return Err(AtlasError::LayerTooLarge);
+        }
+        let border = padding + extrude;
+        for (i, image) in images.iter().enumerate() {
+            if image.pixels.len() != image.width as usize * image.height as usize * 4 {
+                return Err(AtlasError::WrongSize { image: i });
+            }
+            if image.width + 2 * border > width || image.height + 2 * border > height {
+                return Err(AtlasError::TooLarge { image: i });
+            }
+        }
+        let mut order: Vec<usize> = (0..images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((images[i].height, images[i].width)));
+        let mut shelves: Vec<Shelf> = vec![];
+        // The bottom of the last shelf of each layer
+        let mut layer_bottoms: Vec<u32> = vec![];
+        let mut regions = vec![SheetRegion::ZERO; images.len()];
+        let mut placed = Vec::with_capacity(images.len());
+        for i in order {
+            let (cell_w, cell_h) = (images[i].width + 2 * border, images[i].height + 2 * border);
+            let shelf = match shelves
+                .iter()
+                .position(|shelf| shelf.height >= cell_h && shelf.x + cell_w <= width)
+            {
+                Some(shelf) => shelf,
+                None => {
+                    let layer = layer_bottoms
+                        .iter()
+                        .position(|&bottom| bottom + cell_h <= height)
+                        .unwrap_or_else(|| {
+                            layer_bottoms.push(0);
+                            layer_bottoms.len() - 1
+                        });
+                    shelves.push(Shelf {
+                        layer,
+                        y: layer_bottoms[layer],
+                        height: cell_h,
+                        x: 0,
+                    });
+                    layer_bottoms[layer] += cell_h;
+                    shelves.len() - 1
+                }
+            };
+            let shelf = &mut shelves[shelf];
+            let (x, y) = (shelf.x + border, shelf.y + border);
+            shelf.x += cell_w;
+            regions[i] = SheetRegion::new(
+                shelf.layer as u16,
+                x as u16,
+                y as u16,
+                0,
+                images[i].width as i16,
+                images[i].height as i16,
+            );
+            placed.push(i);
+        }
+        let layers = layer_bottoms.len().max(1) as u32;
+        let mut atlas = Self {
+            width,
+            height,
+            layers,
+            data: vec![0; width as usize * height as usize * 4 * layers as usize],
+            regions,
+        };
+        for i in placed {
+            atlas.blit(&images[i], atlas.regions[i], extrude);
+        }
+        Ok(atlas)
+    }
+    // Copies an image into its region, repeating its edges `extrude` times
+    fn blit(&mut self, image: &AtlasImage, region: SheetRegion, extrude: u32) {
+        let stride = self.width as usize * 4;
+        let layer = region.sheet as usize * stride * self.height as usize;
+        let (x, y) = (region.x as usize, region.y as usize);
+        let (w, h) = (image.width as usize, image.height as usize);
+        let e = extrude as usize;
+        if w == 0 || h == 0 {
+            return;
+        }
+        for row in 0..h {
+            let start = layer + (y + row) * stride + x * 4;
+            let src = &image.pixels[row * w * 4..(row + 1) * w * 4];
+            self.data[start..start + w * 4].copy_from_slice(src);
+            for i in 1..=e {
+                self.data[start - i * 4..start - (i - 1) * 4].copy_from_slice(&src[..4]);
+                let end = start + (w - 1 + i) * 4;
+                self.data[end..end + 4].copy_from_slice(&src[(w - 1) * 4..]);
+            }
+        }
+        // The extruded rows include the extruded corners
+        let (left, right) = (x * 4 - e * 4, (x + w + e) * 4);
+        for i in 1..=e {
+            for (from, to) in [(y, y - i), (y + h - 1, y + h - 1 + i)] {
+                let from = layer + from * stride;
+                let to = layer + to * stride;
+                self.data.copy_within(from + left..from + right, to + left);
+            }
+        }
+    }
+    /// Returns the region covering the image with the given index.
+    pub fn region(&self, image: usize) -> SheetRegion {
+        self.regions[image]
+    }
+    /// Returns the regions covering each image, in the order they were given.
+    pub fn regions(&self) -> &[SheetRegion] {
+        &self.regions
+    }
+    /// Returns the width and height of each layer in texels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    /// Returns how many layers the images were packed into.
+    pub fn layer_count(&self) -> u32 {
+        self.layers
+    }
+    /// Returns the RGBA8 texels of every layer, one after another.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    /// Creates an array texture holding the atlas's layers (see
+    /// [`crate::Renderer::create_array_texture_dense`]).  The format
+    /// should have four bytes per texel, such as
+    /// [`wgpu::TextureFormat::Rgba8UnormSrgb`].
+    pub fn create_texture(
+        &self,
+        renderer: &crate::Renderer,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        renderer.create_array_texture_dense(
+            &self.data,
+            self.layers,
+            format,
+            (self.width, self.height),
+            label,
+        )
+    }
+}
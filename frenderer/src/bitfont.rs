@@ -77,6 +77,38 @@ impl<B: RangeBounds<char>> BitFont<B> {
             region,
         }
     }
+    /// Returns true if the font has a glyph for `c`.
+    pub fn contains(&self, c: char) -> bool {
+        self.chars.contains(&c)
+    }
+    /// Returns the width of each character divided by its height.
+    pub fn char_aspect(&self) -> f32 {
+        self.char_w as f32 / self.char_h as f32
+    }
+    /// Returns the sheet region of the glyph for character `c`, drawn at the given depth.
+    /// Panics if `c` is not within the font's character range.
+    pub fn char_region(&self, c: char, depth: u16) -> SheetRegion {
+        let start_char: u32 = match self.chars.start_bound() {
+            std::ops::Bound::Included(&c) => u32::from(c),
+            std::ops::Bound::Excluded(&c) => u32::from(c) + 1,
+            _ => unreachable!(),
+        };
+        if !self.chars.contains(&c) {
+            panic!("Drawing outside of font character range");
+        }
+        let chars_per_row = self.region.w as u16 / (self.char_w + self.padding_x);
+        let chara = u32::from(c) - start_char;
+        let which_row = chara / chars_per_row as u32;
+        let which_col = chara % chars_per_row as u32;
+        SheetRegion::new(
+            self.region.sheet,
+            self.region.x + (which_col as u16) * (self.char_w + self.padding_x),
+            self.region.y + (which_row as u16) * (self.char_h + self.padding_y),
+            depth,
+            self.char_w as i16,
+            self.char_h as i16,
+        )
+    }
     /// Draws the given `text` as a single line of characters of height `char_height`.
     /// The given position is the top-left corner of the rendered string.
     /// Panics if any character in text is not within the font's character range.
@@ -90,16 +122,9 @@ impl<B: RangeBounds<char>> BitFont<B> {
         depth: u16,
         char_height: f32,
     ) -> ([f32; 2], usize) {
-        let start_char: u32 = match self.chars.start_bound() {
-            std::ops::Bound::Included(&c) => u32::from(c),
-            std::ops::Bound::Excluded(&c) => u32::from(c) + 1,
-            _ => unreachable!(),
-        };
         trfs[0..text.len()].fill(Transform::ZERO);
         uvs[0..text.len()].fill(SheetRegion::ZERO);
-        let chars_per_row = self.region.w as u16 / (self.char_w + self.padding_x);
-        let aspect = self.char_w as f32 / self.char_h as f32;
-        let char_width = aspect * char_height;
+        let char_width = self.char_aspect() * char_height;
         screen_pos[0] += char_width / 2.0;
         screen_pos[1] -= char_height / 2.0;
         let mut used = 0;
@@ -108,9 +133,7 @@ impl<B: RangeBounds<char>> BitFont<B> {
             if chara.is_whitespace() {
                 screen_pos[0] += char_width;
             }
-            if !self.chars.contains(&chara) {
-                panic!("Drawing outside of font character range");
-            }
+            *uv = self.char_region(chara, depth);
             *trf = Transform {
                 w: char_width as u16,
                 h: char_height as u16,
@@ -118,17 +141,6 @@ impl<B: RangeBounds<char>> BitFont<B> {
                 y: screen_pos[1],
                 rot: 0.0,
             };
-            let chara = u32::from(chara) - start_char;
-            let which_row = chara / chars_per_row as u32;
-            let which_col = chara % chars_per_row as u32;
-            *uv = SheetRegion::new(
-                self.region.sheet,
-                self.region.x + (which_col as u16) * (self.char_w + self.padding_x),
-                self.region.y + (which_row as u16) * (self.char_h + self.padding_y),
-                depth,
-                self.char_w as i16,
-                self.char_h as i16,
-            );
             used += 1;
             screen_pos[0] += char_width;
         }
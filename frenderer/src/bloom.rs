@@ -0,0 +1,482 @@
+//! Bloom, which makes bright parts of the image bleed light into
+//! their surroundings (see [`crate::postprocess::Bloom`]).
+//!
+//! Bloom runs as part of [`crate::postprocess::PostProcess`], but
+//! unlike its other effects it isn't a single fullscreen pass.  The
+//! bright parts of the image, plus anything drawn into a glow texture
+//! (see [`GlowPass`]), are copied into a chain of half-resolution,
+//! quarter-resolution, etc. textures in a floating point format so
+//! that brightness isn't clamped along the way.  Each level is
+//! blurred as it's downsampled, and then the levels are upsampled and
+//! added back together and the result is added onto the scene.
+
+use std::borrow::Cow;
+
+use crate::{capture, WGPU};
+
+/// Owns a glow texture and the depth texture used while drawing into
+/// it.  The built-in renderer draws sprites' emissive layers into one
+/// (see [`crate::sprites::SpriteRenderer::render_emissive`]) while
+/// bloom is on, so that emissive sprites glow however bright they are.
+pub struct GlowPass {
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    depth_texture: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl GlowPass {
+    /// Creates a [`GlowPass`] with `width` by `height` glow and depth
+    /// textures.  `format` should be the color format of the
+    /// renderers drawing into it, and `depth_format` their depth
+    /// format (`None` means no depth testing).
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        let (texture, texture_view) = create_texture(
+            gpu,
+            "bloom:glow",
+            (width, height),
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let depth_texture = depth_format.into().map(|format| {
+            create_texture(
+                gpu,
+                "bloom:glow_depth",
+                (width, height),
+                format,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        });
+        Self {
+            texture,
+            texture_view,
+            depth_texture,
+        }
+    }
+    /// Re-creates the glow and depth textures at a new size.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        let format = self.texture.format();
+        let depth_format = self.depth_format();
+        *self = Self::new(gpu, width, height, format, depth_format);
+    }
+    /// Changes the depth format used while drawing glows, re-creating the depth texture.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        let size = self.texture.size();
+        self.depth_texture = depth_format.map(|format| {
+            create_texture(
+                gpu,
+                "bloom:glow_depth",
+                (size.width, size.height),
+                format,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        });
+    }
+    /// Returns the depth format used while drawing glows, if any.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_texture.as_ref().map(|(t, _)| t.format())
+    }
+    /// Returns the glow texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Begins a render pass which clears the glow texture to black
+    /// and clears the depth texture.
+    pub fn begin_render_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("bloom:glow");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("bloom:glow"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth_texture.as_ref().map(|(_, view)| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+fn create_texture(
+    gpu: &WGPU,
+    label: &str,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// The uniform parameters shared by all of bloom's passes; see `bloom.wgsl`.
+pub(crate) type BloomParams = [[f32; 4]; 1];
+
+/// The pipelines and textures bloom runs with.
+pub(crate) struct BloomChain {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params_buf: wgpu::Buffer,
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+    level_format: wgpu::TextureFormat,
+    // A black texture read when there's no glow texture
+    placeholder: wgpu::TextureView,
+    // Each level is half the size of the one before it
+    levels: Vec<wgpu::TextureView>,
+    // Element i reads level i to make level i + 1
+    down_bind_groups: Vec<wgpu::BindGroup>,
+    // Element i reads level i + 1 to add it onto level i
+    up_bind_groups: Vec<wgpu::BindGroup>,
+    // Index 0 reads the color texture, index 1 the scratch texture
+    prefilter_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl BloomChain {
+    /// Creates the pipelines for compositing onto textures of
+    /// `color_format`; the chain has no levels until it's resized.
+    pub(crate) fn new(gpu: &WGPU, color_format: wgpu::TextureFormat) -> Self {
+        let level_format = Self::level_format(gpu, color_format);
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("bloom:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("bloom.wgsl"))),
+            });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("bloom:bgl"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                    BloomParams,
+                                >(
+                                )
+                                    as u64),
+                            },
+                            count: None,
+                        },
+                        texture_entry(1),
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        texture_entry(3),
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("bloom:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let pipeline = |label, entry_point, target: wgpu::ColorTargetState| {
+            gpu.device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point,
+                        targets: &[Some(target)],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        };
+        let prefilter = pipeline("bloom:prefilter", "fs_prefilter", level_format.into());
+        let downsample = pipeline("bloom:downsample", "fs_downsample", level_format.into());
+        // Each level is added onto the one above it
+        let upsample = pipeline(
+            "bloom:upsample",
+            "fs_upsample",
+            wgpu::ColorTargetState {
+                format: level_format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+        );
+        let composite = pipeline("bloom:composite", "fs_composite", color_format.into());
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom:sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buf = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloom:params"),
+            size: std::mem::size_of::<BloomParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (_, placeholder) = create_texture(
+            gpu,
+            "bloom:placeholder_glow",
+            (1, 1),
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        Self {
+            bind_group_layout,
+            sampler,
+            params_buf,
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            level_format,
+            placeholder,
+            levels: vec![],
+            down_bind_groups: vec![],
+            up_bind_groups: vec![],
+            prefilter_bind_groups: vec![],
+            composite_bind_groups: vec![],
+        }
+    }
+    /// Levels are kept in half floats where the adapter can render to
+    /// and filter them, so bright colors aren't clamped.
+    fn level_format(gpu: &WGPU, color_format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        let features = gpu
+            .adapter()
+            .get_texture_format_features(wgpu::TextureFormat::Rgba16Float);
+        if features
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && features
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+        {
+            wgpu::TextureFormat::Rgba16Float
+        } else {
+            color_format
+        }
+    }
+    /// Returns how many levels the chain currently has.
+    pub(crate) fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+    /// Drops the chain's textures, so it draws nothing until it's resized.
+    pub(crate) fn clear_levels(&mut self) {
+        self.levels.clear();
+        self.down_bind_groups.clear();
+        self.up_bind_groups.clear();
+        self.prefilter_bind_groups.clear();
+        self.composite_bind_groups.clear();
+    }
+    /// Re-creates the chain's textures for a `size` scene with up to
+    /// `levels` levels, reading the scene from `inputs` and extra glow
+    /// from `glow`.  Levels stop once they'd be smaller than a pixel.
+    pub(crate) fn resize(
+        &mut self,
+        gpu: &WGPU,
+        (width, height): (u32, u32),
+        levels: u32,
+        inputs: [&wgpu::TextureView; 2],
+        glow: Option<&wgpu::TextureView>,
+    ) {
+        let glow = glow.unwrap_or(&self.placeholder);
+        self.levels.clear();
+        let (mut w, mut h) = (width / 2, height / 2);
+        while self.levels.len() < levels.max(1) as usize && w > 0 && h > 0 {
+            let (_, view) = create_texture(
+                gpu,
+                "bloom:level",
+                (w, h),
+                self.level_format,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            );
+            self.levels.push(view);
+            (w, h) = (w / 2, h / 2);
+        }
+        self.prefilter_bind_groups = inputs
+            .iter()
+            .map(|input| self.bind_group(gpu, input, glow))
+            .collect();
+        self.composite_bind_groups = match self.levels.first() {
+            Some(first) => inputs
+                .iter()
+                .map(|input| self.bind_group(gpu, first, input))
+                .collect(),
+            None => vec![],
+        };
+        let count = self.levels.len().saturating_sub(1);
+        self.down_bind_groups = (0..count)
+            .map(|i| self.bind_group(gpu, &self.levels[i], &self.placeholder))
+            .collect();
+        self.up_bind_groups = (0..count)
+            .map(|i| self.bind_group(gpu, &self.levels[i + 1], &self.placeholder))
+            .collect();
+    }
+    fn bind_group(
+        &self,
+        gpu: &WGPU,
+        source: &wgpu::TextureView,
+        aux: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom:bg"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(aux),
+                },
+            ],
+        })
+    }
+    /// Uploads new parameters.
+    pub(crate) fn set_params(&self, gpu: &WGPU, params: &BloomParams) {
+        gpu.queue()
+            .write_buffer(&self.params_buf, 0, bytemuck::cast_slice(params));
+    }
+    /// Blooms the scene in the color texture (if `input` is 0) or the
+    /// scratch texture (if it's 1), writing the result into `target`.
+    pub(crate) fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: usize,
+        target: &wgpu::TextureView,
+    ) {
+        if self.levels.is_empty() {
+            return;
+        }
+        let mut pass = |label: &str,
+                        view: &wgpu::TextureView,
+                        load: wgpu::LoadOp<wgpu::Color>,
+                        pipeline: &wgpu::RenderPipeline,
+                        bind_group: &wgpu::BindGroup| {
+            capture::begin_pass(label);
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            capture::set_pipeline(&mut rpass, pipeline, label);
+            capture::set_bind_group(&mut rpass, 0, bind_group, &[], "bloom:bg");
+            capture::draw(&mut rpass, 0..3, 0..1);
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        pass(
+            "bloom:prefilter",
+            &self.levels[0],
+            clear,
+            &self.prefilter,
+            &self.prefilter_bind_groups[input],
+        );
+        for (i, bind_group) in self.down_bind_groups.iter().enumerate() {
+            pass(
+                "bloom:downsample",
+                &self.levels[i + 1],
+                clear,
+                &self.downsample,
+                bind_group,
+            );
+        }
+        for (i, bind_group) in self.up_bind_groups.iter().enumerate().rev() {
+            pass(
+                "bloom:upsample",
+                &self.levels[i],
+                wgpu::LoadOp::Load,
+                &self.upsample,
+                bind_group,
+            );
+        }
+        pass(
+            "bloom:composite",
+            target,
+            clear,
+            &self.composite,
+            &self.composite_bind_groups[input],
+        );
+    }
+}
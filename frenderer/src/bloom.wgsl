@@ -0,0 +1,87 @@
+// Bloom's passes (see bloom.rs).  The prefilter keeps the bright
+// parts of the scene plus the glow texture, the downsample and
+// upsample passes blur them across a chain of shrinking textures, and
+// the composite adds the result onto the scene.
+
+// u_params[0]: x = threshold, y = knee (a fraction of the threshold),
+// z = intensity, w = upsampling radius in texels
+@group(0) @binding(0)
+var<uniform> u_params: array<vec4<f32>, 1>;
+// The scene for the prefilter, the first level for the composite,
+// and otherwise the level being downsampled or upsampled
+@group(0) @binding(1)
+var t_source: texture_2d<f32>;
+@group(0) @binding(2)
+var s_linear: sampler;
+// The glow texture for the prefilter and the scene for the composite
+@group(0) @binding(3)
+var t_aux: texture_2d<f32>;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.uv = uv;
+  return out;
+}
+
+// Four bilinear taps around uv, which average a 4x4 block of texels
+fn box4(uv: vec2<f32>) -> vec3<f32> {
+  let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+  var sum = textureSampleLevel(t_source, s_linear, uv + texel * vec2<f32>(-1.0, -1.0), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, uv + texel * vec2<f32>(1.0, -1.0), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, uv + texel * vec2<f32>(-1.0, 1.0), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, uv + texel * vec2<f32>(1.0, 1.0), 0.0).rgb;
+  return sum * 0.25;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+  let color = box4(in.uv);
+  let brightness = max(color.r, max(color.g, color.b));
+  // Brightness ramps in smoothly over the knee below the threshold
+  let threshold = u_params[0].x;
+  let knee = threshold * u_params[0].y + 0.00001;
+  var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee);
+  let contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+  // Glowing things bloom however bright they are
+  let glow = textureSampleLevel(t_aux, s_linear, in.uv, 0.0).rgb;
+  return vec4<f32>(color * contribution + glow, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(box4(in.uv), 1.0);
+}
+
+// A 3x3 tent filter, spread out by the radius
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+  let d = u_params[0].w / vec2<f32>(textureDimensions(t_source));
+  var sum = textureSampleLevel(t_source, s_linear, in.uv, 0.0).rgb * 4.0;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(-d.x, 0.0), 0.0).rgb * 2.0;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(d.x, 0.0), 0.0).rgb * 2.0;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(0.0, -d.y), 0.0).rgb * 2.0;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(0.0, d.y), 0.0).rgb * 2.0;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(-d.x, -d.y), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(d.x, -d.y), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + vec2<f32>(-d.x, d.y), 0.0).rgb;
+  sum += textureSampleLevel(t_source, s_linear, in.uv + d, 0.0).rgb;
+  return vec4<f32>(sum / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+  let scene = textureSampleLevel(t_aux, s_linear, in.uv, 0.0);
+  // The first level holds every level added together
+  let bloom = textureSampleLevel(t_source, s_linear, in.uv, 0.0).rgb;
+  return vec4<f32>(scene.rgb + bloom * u_params[0].z, scene.a);
+}
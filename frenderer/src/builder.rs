@@ -0,0 +1,182 @@
+//! Configuring a [`Renderer`] before it's created.  The
+//! [`Renderer::with_surface`] and [`Renderer::with_gpu`] constructors
+//! use frenderer's defaults for everything; a [`RendererBuilder`]
+//! can instead pick the backend and adapter (e.g. a discrete GPU
+//! over an integrated one), require device features and limits,
+//! and choose the surface format, presentation mode, and depth
+//! format up front:
+//!
+//! ```no_run
+//! # async fn make(window: std::sync::Arc<winit::window::Window>) -> Result<(), Box<dyn std::error::Error>> {
+//! let size = window.inner_size();
+//! let renderer = frenderer::RendererBuilder::new()
+//!     .power_preference(frenderer::wgpu::PowerPreference::HighPerformance)
+//!     .present_modes(&[frenderer::wgpu::PresentMode::Mailbox])
+//!     .build_with_surface(window, size.width, size.height)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use crate::{GpuOptions, Renderer, WGPU};
+
+/// Creates a [`Renderer`] with the given GPU, surface, and depth
+/// settings (see the [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct RendererBuilder {
+    render_size: Option<(u32, u32)>,
+    backends: Option<wgpu::Backends>,
+    gpu_options: GpuOptions,
+    surface_formats: Vec<wgpu::TextureFormat>,
+    present_modes: Vec<wgpu::PresentMode>,
+    depth_format: Option<wgpu::TextureFormat>,
+}
+
+impl Default for RendererBuilder {
+    fn default() -> Self {
+        Self {
+            render_size: None,
+            backends: None,
+            gpu_options: GpuOptions::default(),
+            surface_formats: Vec::new(),
+            present_modes: Vec::new(),
+            depth_format: Some(Renderer::DEPTH_FORMAT),
+        }
+    }
+}
+
+impl RendererBuilder {
+    /// Creates a builder with frenderer's default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the rendering resolution; by default it's the surface's
+    /// size (or 320 by 240 without a surface).
+    pub fn render_size(mut self, width: u32, height: u32) -> Self {
+        self.render_size = Some((width, height));
+        self
+    }
+    /// Restricts the backends adapters may come from, e.g.
+    /// [`wgpu::Backends::VULKAN`].  By default every backend is
+    /// considered (see [`WGPU::detect_instance`] for the web).
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = Some(backends);
+        self
+    }
+    /// Prefers an integrated ([`wgpu::PowerPreference::LowPower`]) or
+    /// discrete ([`wgpu::PowerPreference::HighPerformance`]) adapter.
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.gpu_options.power_preference = power_preference;
+        self
+    }
+    /// Only considers fallback (software) adapters if `force` is true.
+    pub fn force_fallback_adapter(mut self, force: bool) -> Self {
+        self.gpu_options.force_fallback_adapter = force;
+        self
+    }
+    /// Requires the device to have the given features (see
+    /// [`GpuOptions::required_features`]).
+    pub fn required_features(mut self, features: wgpu::Features) -> Self {
+        self.gpu_options.required_features = features;
+        self
+    }
+    /// Requires the device to support the given limits instead of
+    /// frenderer's defaults (see [`GpuOptions::required_limits`]).
+    pub fn required_limits(mut self, limits: wgpu::Limits) -> Self {
+        self.gpu_options.required_limits = Some(limits);
+        self
+    }
+    /// Sets the surface formats to use if the surface supports them,
+    /// in order of preference (see [`Renderer::set_preferred_surface_formats`]).
+    pub fn surface_formats(mut self, formats: &[wgpu::TextureFormat]) -> Self {
+        self.surface_formats = formats.to_vec();
+        self
+    }
+    /// Sets the presentation modes to use if the surface supports
+    /// them, in order of preference; otherwise (and by default) it's
+    /// [`wgpu::PresentMode::AutoVsync`].
+    pub fn present_modes(mut self, modes: &[wgpu::PresentMode]) -> Self {
+        self.present_modes = modes.to_vec();
+        self
+    }
+    /// Sets the depth format, or `None` for 2D-only mode (see
+    /// [`Renderer::set_depth_format`]).  The default is
+    /// [`Renderer::DEPTH_FORMAT`].
+    pub fn depth_format(mut self, format: Option<wgpu::TextureFormat>) -> Self {
+        self.depth_format = format;
+        self
+    }
+    async fn instance(&self) -> Arc<wgpu::Instance> {
+        match self.backends {
+            Some(backends) => Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                ..Default::default()
+            })),
+            None => WGPU::detect_instance().await,
+        }
+    }
+    /// Creates a [`Renderer`] drawing onto a surface for `target`
+    /// (e.g. a window) which is `surf_width` by `surf_height` pixels.
+    pub async fn build_with_surface(
+        self,
+        target: impl Into<wgpu::SurfaceTarget<'static>>,
+        surf_width: u32,
+        surf_height: u32,
+    ) -> Result<Renderer, Box<dyn std::error::Error>> {
+        let instance = self.instance().await;
+        let surface = instance.create_surface(target)?;
+        let gpu = WGPU::with_options(instance, Some(&surface), &self.gpu_options).await?;
+        Ok(self.build_with_gpu(gpu, Some(surface), surf_width, surf_height))
+    }
+    /// Creates a [`Renderer`] drawing into an SDL2 window (see [`crate::sdl`]).
+    ///
+    /// # Safety
+    ///
+    /// `window` must outlive the renderer, since its surface refers
+    /// to the window.
+    #[cfg(feature = "sdl2")]
+    pub async unsafe fn build_with_sdl_window(
+        self,
+        window: &sdl2::video::Window,
+    ) -> Result<Renderer, Box<dyn std::error::Error>> {
+        let instance = self.instance().await;
+        let surface =
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::from_window(window)?)?;
+        let gpu = WGPU::with_options(instance, Some(&surface), &self.gpu_options).await?;
+        let (surf_width, surf_height) = window.size();
+        Ok(self.build_with_gpu(gpu, Some(surface), surf_width, surf_height))
+    }
+    /// Creates a [`Renderer`] without a surface, e.g. for drawing
+    /// into textures with [`Renderer::render_to_view`].
+    pub async fn build_headless(self) -> Result<Renderer, Box<dyn std::error::Error>> {
+        let instance = self.instance().await;
+        let gpu = WGPU::with_options(instance, None, &self.gpu_options).await?;
+        Ok(self.build_with_gpu(gpu, None, 0, 0))
+    }
+    /// Creates a [`Renderer`] with already-initialized GPU resources,
+    /// so only the surface and depth settings apply.
+    pub fn build_with_gpu(
+        self,
+        gpu: WGPU,
+        surface: Option<wgpu::Surface<'static>>,
+        surf_width: u32,
+        surf_height: u32,
+    ) -> Renderer {
+        let (width, height) = self.render_size.unwrap_or((surf_width, surf_height));
+        let mut renderer = Renderer::with_gpu(width, height, surf_width, surf_height, gpu, surface);
+        if !self.surface_formats.is_empty() {
+            renderer.set_preferred_surface_formats(&self.surface_formats);
+        }
+        for mode in self.present_modes {
+            if renderer.set_present_mode(mode) {
+                break;
+            }
+        }
+        if self.depth_format != renderer.depth_format() {
+            renderer.set_depth_format(self.depth_format);
+        }
+        renderer
+    }
+}
@@ -0,0 +1,359 @@
+//! Frame capture for debugging: records the render passes, pipelines,
+//! bind groups, buffers, and draw calls frenderer issues while a
+//! capture is running, so they can be dumped as text or JSON and
+//! compared between frames.
+//!
+//! Call [`start`] before drawing and [`finish`] afterwards (or use
+//! [`crate::Renderer::capture_next_frame`]).  Only commands issued by
+//! frenderer's own renderers are recorded, labelled after the
+//! renderer and field they come from; passes you begin yourself show
+//! up as unlabelled passes holding whatever frenderer drew into them.
+//! Captures are kept per thread.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::ops::Range;
+
+thread_local! {
+    static CAPTURE: RefCell<Option<FrameCapture>> = const { RefCell::new(None) };
+}
+
+/// Everything recorded during one capture.
+#[derive(Clone, Debug, Default)]
+pub struct FrameCapture {
+    pub passes: Vec<PassRecord>,
+}
+
+/// The commands recorded within one render pass, or between passes
+/// for copies.
+#[derive(Clone, Debug)]
+pub struct PassRecord {
+    pub label: String,
+    pub commands: Vec<Command>,
+}
+
+/// One recorded command.  Buffer ranges are in bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    SetPipeline {
+        label: String,
+    },
+    SetBindGroup {
+        index: u32,
+        label: String,
+        offsets: Vec<u32>,
+    },
+    SetVertexBuffer {
+        slot: u32,
+        label: String,
+        range: Range<u64>,
+    },
+    SetIndexBuffer {
+        label: String,
+        format: wgpu::IndexFormat,
+        range: Range<u64>,
+    },
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    DrawIndexed {
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>,
+    },
+    /// A buffer or texture copy recorded on the command encoder
+    Copy {
+        label: String,
+    },
+}
+
+/// Starts recording, discarding any capture in progress.
+pub fn start() {
+    CAPTURE.with(|c| *c.borrow_mut() = Some(FrameCapture::default()));
+}
+
+/// Returns true if a capture is in progress.
+pub fn is_capturing() -> bool {
+    CAPTURE.with(|c| c.borrow().is_some())
+}
+
+/// Stops recording and returns what was captured, if a capture was in progress.
+pub fn finish() -> Option<FrameCapture> {
+    CAPTURE.with(|c| c.borrow_mut().take())
+}
+
+fn record(command: impl FnOnce() -> Command) {
+    CAPTURE.with(|c| {
+        if let Some(capture) = c.borrow_mut().as_mut() {
+            if capture.passes.is_empty() {
+                capture.passes.push(PassRecord {
+                    label: String::new(),
+                    commands: vec![],
+                });
+            }
+            capture.passes.last_mut().unwrap().commands.push(command());
+        }
+    })
+}
+
+/// Records the start of a render pass.
+pub(crate) fn begin_pass(label: &str) {
+    CAPTURE.with(|c| {
+        if let Some(capture) = c.borrow_mut().as_mut() {
+            capture.passes.push(PassRecord {
+                label: label.to_string(),
+                commands: vec![],
+            });
+        }
+    })
+}
+
+/// Records a copy made outside of any render pass.
+pub(crate) fn copy(label: &str) {
+    begin_pass(label);
+    record(|| Command::Copy {
+        label: label.to_string(),
+    });
+}
+
+pub(crate) fn set_pipeline<'pass>(
+    rpass: &mut wgpu::RenderPass<'pass>,
+    pipeline: &'pass wgpu::RenderPipeline,
+    label: &str,
+) {
+    record(|| Command::SetPipeline {
+        label: label.to_string(),
+    });
+    rpass.set_pipeline(pipeline);
+}
+
+pub(crate) fn set_bind_group<'pass>(
+    rpass: &mut wgpu::RenderPass<'pass>,
+    index: u32,
+    bind_group: &'pass wgpu::BindGroup,
+    offsets: &[u32],
+    label: &str,
+) {
+    record(|| Command::SetBindGroup {
+        index,
+        label: label.to_string(),
+        offsets: offsets.to_vec(),
+    });
+    rpass.set_bind_group(index, bind_group, offsets);
+}
+
+/// Binds a whole buffer as a vertex buffer.
+pub(crate) fn set_vertex_buffer<'pass>(
+    rpass: &mut wgpu::RenderPass<'pass>,
+    slot: u32,
+    buffer: &'pass wgpu::Buffer,
+    label: &str,
+) {
+    record(|| Command::SetVertexBuffer {
+        slot,
+        label: label.to_string(),
+        range: 0..buffer.size(),
+    });
+    rpass.set_vertex_buffer(slot, buffer.slice(..));
+}
+
+/// Binds a whole buffer as the index buffer.
+pub(crate) fn set_index_buffer<'pass>(
+    rpass: &mut wgpu::RenderPass<'pass>,
+    buffer: &'pass wgpu::Buffer,
+    format: wgpu::IndexFormat,
+    label: &str,
+) {
+    record(|| Command::SetIndexBuffer {
+        label: label.to_string(),
+        format,
+        range: 0..buffer.size(),
+    });
+    rpass.set_index_buffer(buffer.slice(..), format);
+}
+
+pub(crate) fn draw(rpass: &mut wgpu::RenderPass, vertices: Range<u32>, instances: Range<u32>) {
+    record(|| Command::Draw {
+        vertices: vertices.clone(),
+        instances: instances.clone(),
+    });
+    rpass.draw(vertices, instances);
+}
+
+pub(crate) fn draw_indexed(
+    rpass: &mut wgpu::RenderPass,
+    indices: Range<u32>,
+    base_vertex: i32,
+    instances: Range<u32>,
+) {
+    record(|| Command::DrawIndexed {
+        indices: indices.clone(),
+        base_vertex,
+        instances: instances.clone(),
+    });
+    rpass.draw_indexed(indices, base_vertex, instances);
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_range<T: std::fmt::Display>(r: &Range<T>) -> String {
+    format!("[{}, {}]", r.start, r.end)
+}
+
+impl Command {
+    fn to_json(&self) -> String {
+        match self {
+            Command::SetPipeline { label } => {
+                format!(
+                    r#"{{"op": "set_pipeline", "label": {}}}"#,
+                    json_string(label)
+                )
+            }
+            Command::SetBindGroup {
+                index,
+                label,
+                offsets,
+            } => format!(
+                r#"{{"op": "set_bind_group", "index": {index}, "label": {}, "offsets": {offsets:?}}}"#,
+                json_string(label)
+            ),
+            Command::SetVertexBuffer { slot, label, range } => format!(
+                r#"{{"op": "set_vertex_buffer", "slot": {slot}, "label": {}, "range": {}}}"#,
+                json_string(label),
+                json_range(range)
+            ),
+            Command::SetIndexBuffer {
+                label,
+                format,
+                range,
+            } => format!(
+                r#"{{"op": "set_index_buffer", "label": {}, "format": "{format:?}", "range": {}}}"#,
+                json_string(label),
+                json_range(range)
+            ),
+            Command::Draw {
+                vertices,
+                instances,
+            } => format!(
+                r#"{{"op": "draw", "vertices": {}, "instances": {}}}"#,
+                json_range(vertices),
+                json_range(instances)
+            ),
+            Command::DrawIndexed {
+                indices,
+                base_vertex,
+                instances,
+            } => format!(
+                r#"{{"op": "draw_indexed", "indices": {}, "base_vertex": {base_vertex}, "instances": {}}}"#,
+                json_range(indices),
+                json_range(instances)
+            ),
+            Command::Copy { label } => {
+                format!(r#"{{"op": "copy", "label": {}}}"#, json_string(label))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::SetPipeline { label } => write!(f, "set_pipeline {label}"),
+            Command::SetBindGroup {
+                index,
+                label,
+                offsets,
+            } => {
+                write!(f, "set_bind_group {index} {label}")?;
+                if !offsets.is_empty() {
+                    write!(f, " offsets {offsets:?}")?;
+                }
+                Ok(())
+            }
+            Command::SetVertexBuffer { slot, label, range } => {
+                write!(f, "set_vertex_buffer {slot} {label} bytes {range:?}")
+            }
+            Command::SetIndexBuffer {
+                label,
+                format,
+                range,
+            } => write!(f, "set_index_buffer {label} {format:?} bytes {range:?}"),
+            Command::Draw {
+                vertices,
+                instances,
+            } => write!(f, "draw vertices {vertices:?} instances {instances:?}"),
+            Command::DrawIndexed {
+                indices,
+                base_vertex,
+                instances,
+            } => write!(
+                f,
+                "draw_indexed indices {indices:?} base_vertex {base_vertex} instances {instances:?}"
+            ),
+            Command::Copy { label } => write!(f, "copy {label}"),
+        }
+    }
+}
+
+impl FrameCapture {
+    /// Returns the number of draw calls captured.
+    pub fn draw_count(&self) -> usize {
+        self.passes
+            .iter()
+            .flat_map(|pass| pass.commands.iter())
+            .filter(|c| matches!(c, Command::Draw { .. } | Command::DrawIndexed { .. }))
+            .count()
+    }
+    /// Serializes the capture as a JSON object with a `passes` array,
+    /// one command object per line so captures diff cleanly.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"passes\": [\n");
+        for (p, pass) in self.passes.iter().enumerate() {
+            writeln!(
+                out,
+                "  {{\"label\": {}, \"commands\": [",
+                json_string(&pass.label)
+            )
+            .unwrap();
+            for (c, command) in pass.commands.iter().enumerate() {
+                let comma = if c + 1 < pass.commands.len() { "," } else { "" };
+                writeln!(out, "    {}{comma}", command.to_json()).unwrap();
+            }
+            let comma = if p + 1 < self.passes.len() { "," } else { "" };
+            writeln!(out, "  ]}}{comma}").unwrap();
+        }
+        out.push_str("]}\n");
+        out
+    }
+}
+
+impl std::fmt::Display for FrameCapture {
+    /// Writes the capture as indented text, one command per line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for pass in self.passes.iter() {
+            if pass.label.is_empty() {
+                writeln!(f, "pass")?;
+            } else {
+                writeln!(f, "pass {}", pass.label)?;
+            }
+            for command in pass.commands.iter() {
+                writeln!(f, "  {command}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+// u_params[0]: x = channel separation at the corners
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let offset = (in.tex_coords - 0.5) * u_params[0].x;
+  let center = textureSample(t_color, s_color, in.tex_coords);
+  let r = textureSample(t_color, s_color, in.tex_coords + offset).x;
+  let b = textureSample(t_color, s_color, in.tex_coords - offset).z;
+  return vec4<f32>(r, center.y, b, center.w);
+}
@@ -24,6 +24,14 @@ pub struct Clock {
     last_t: Instant,
 }
 
+impl Default for Clock {
+    /// A 60hz clock with a little fudging, simulating at most five
+    /// steps per tick.
+    fn default() -> Self {
+        Self::new(1.0 / 60.0, 0.0002, 5)
+    }
+}
+
 impl Clock {
     /// Creates a clock with the given target simulation frame rate.
     ///
@@ -39,6 +47,16 @@ impl Clock {
             last_t: Instant::now(),
         }
     }
+    /// Returns the simulation timestep in seconds
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+    /// Returns how far (from 0 to 1) the last [`Clock::tick`] got
+    /// towards the next timestep, for interpolating between the last
+    /// two simulated states when rendering.
+    pub fn interpolation(&self) -> f32 {
+        (self.acc / self.dt).clamp(0.0, 1.0)
+    }
     /// Re-initialize the last-ticked time to the given instant and
     /// clear the accumulator.  This might be useful when a new game
     /// level is loaded or at some other interval to limit drift
@@ -0,0 +1,13 @@
+// u_params[0]: lift, u_params[1]: gamma, u_params[2]: gain (all rgb offsets)
+// u_params[3]: x = saturation offset
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureLoad(t_color, vec2<i32>(in.clip_position.xy), 0);
+  let lifted = color.xyz + u_params[0].xyz * (1.0 - color.xyz);
+  let gained = max(lifted * (1.0 + u_params[2].xyz), vec3<f32>(0.0));
+  let graded = pow(gained, 1.0 / max(1.0 + u_params[1].xyz, vec3<f32>(0.01)));
+  let luma = dot(graded, vec3<f32>(0.2126, 0.7152, 0.0722));
+  let saturated = mix(vec3<f32>(luma), graded, max(1.0 + u_params[3].x, 0.0));
+  return vec4<f32>(max(saturated, vec3<f32>(0.0)), color.w);
+}
@@ -0,0 +1,9 @@
+// u_params[0..3]: rows of the linear RGB color matrix
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureLoad(t_color, vec2<i32>(in.clip_position.xy), 0);
+  let m = mat3x3<f32>(u_params[0].xyz, u_params[1].xyz, u_params[2].xyz);
+  // m's columns are the matrix's rows, so multiply on the left
+  return vec4<f32>(clamp(color.xyz * m, vec3<f32>(0.0), vec3<f32>(1.0)), color.w);
+}
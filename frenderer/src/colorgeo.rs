@@ -1,8 +1,18 @@
 //! Color and geometry postprocessing step.
+//!
+//! This is also where the final image is encoded for HDR displays
+//! (see [`HdrOutput`]): frenderer's content is standard dynamic
+//! range, so it's placed at a configurable paper-white brightness
+//! and tone-mapped so that nothing exceeds the display's peak
+//! luminance.
+//!
+//! Scenes drawn into a floating point color texture can also be
+//! brighter than 1.0, in which case a [`Tonemap`] operator brings
+//! them back into range before the color lookup table is applied.
 
 use std::borrow::Cow;
 
-use crate::gpu::WGPU;
+use crate::{capture, gpu::WGPU};
 use wgpu::util::DeviceExt;
 
 /// Includes a 4x4 homogeneous geometry transformation, a 4x4
@@ -12,6 +22,11 @@ pub struct ColorGeo {
     shader: wgpu::ShaderModule,
     pipeline: wgpu::RenderPipeline,
     pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    hdr_output: Option<HdrOutput>,
+    // Pipelines for color targets used earlier, kept so that switching
+    // between e.g. a surface and an external texture view is cheap
+    spare_pipelines: Vec<(wgpu::ColorTargetState, wgpu::RenderPipeline)>,
     transform_bind_group: wgpu::BindGroup,
     texture_bind_group: wgpu::BindGroup,
     texture_bind_group_layout: wgpu::BindGroupLayout,
@@ -32,7 +47,81 @@ struct Transform {
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct ColorTransform {
     mat: [f32; 16],
+    // Saturation, then the tone mapping operator's discriminant
     saturation_padding: [f32; 4],
+    // The output mode (0 for SDR, then HdrMode's discriminant plus
+    // one), paper white, and maximum luminance
+    output: [f32; 4],
+}
+
+/// How colors brighter than 1.0 are brought back into range before
+/// the color lookup table is applied (see the [module documentation](self)).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Tonemap {
+    /// Colors are clamped
+    #[default]
+    None,
+    /// The Reinhard operator, `c / (1 + c)`, applied to luminance so
+    /// hues are kept
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, which also
+    /// desaturates very bright colors
+    Aces,
+}
+
+/// How the final image is encoded for an HDR display.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum HdrMode {
+    /// Linear extended sRGB, where 1.0 is 80 nits, in an
+    /// [`wgpu::TextureFormat::Rgba16Float`] surface
+    ScRgb,
+    /// HDR10: Rec. 2020 primaries encoded with the PQ (SMPTE ST 2084)
+    /// curve, in an [`wgpu::TextureFormat::Rgb10a2Unorm`] surface
+    Pq,
+}
+
+impl HdrMode {
+    /// The surface format this mode draws into.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            HdrMode::ScRgb => wgpu::TextureFormat::Rgba16Float,
+            HdrMode::Pq => wgpu::TextureFormat::Rgb10a2Unorm,
+        }
+    }
+}
+
+/// HDR output settings (see the [module documentation](self)).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HdrOutput {
+    /// How the image is encoded
+    pub mode: HdrMode,
+    /// How bright full white is, in nits
+    pub paper_white: f32,
+    /// The display's peak brightness, in nits; brighter colors are
+    /// smoothly compressed to fit under it
+    pub max_luminance: f32,
+}
+
+impl HdrOutput {
+    /// Output in the given mode with the BT.2408 reference paper
+    /// white of 203 nits and a peak of 1000 nits.
+    pub fn new(mode: HdrMode) -> Self {
+        Self {
+            mode,
+            paper_white: 203.0,
+            max_luminance: 1000.0,
+        }
+    }
+    fn uniform(output: Option<HdrOutput>) -> [f32; 4] {
+        match output {
+            None => [0.0, 80.0, 80.0, 0.0],
+            Some(HdrOutput {
+                mode,
+                paper_white,
+                max_luminance,
+            }) => [mode as u32 as f32 + 1.0, paper_white, max_luminance, 0.0],
+        }
+    }
 }
 
 /// Returns an identity lut, for convenience in constructing a [`ColorGeo`].
@@ -60,12 +149,15 @@ pub fn lut_identity(gpu: &WGPU) -> wgpu::Texture {
         // blue depth
         &(0..CUBE)
             .flat_map(|z| {
-                let b = z as f32 / CUBE as f32;
+                let b = z as f32 / (CUBE - 1) as f32;
                 (0..CUBE).flat_map(move |y| {
-                    let g = y as f32 / CUBE as f32;
+                    let g = y as f32 / (CUBE - 1) as f32;
                     (0..CUBE).flat_map(move |x| {
-                        let r = x as f32 / CUBE as f32;
-                        [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255]
+                        let r = x as f32 / (CUBE - 1) as f32;
+                        [r, g, b]
+                            .map(|c| (c * 255.0).round() as u8)
+                            .into_iter()
+                            .chain([255])
                     })
                 })
             })
@@ -198,6 +290,7 @@ impl ColorGeo {
                 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
             ],
             saturation_padding: [0.0; 4],
+            output: HdrOutput::uniform(None),
         };
         let transform_buf = gpu
             .device()
@@ -237,31 +330,15 @@ impl ColorGeo {
             gpu,
         );
 
-        let pipeline = gpu
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("post:pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_vbuf_main",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(color_target)],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+        let pipeline = Self::create_pipeline(gpu, &pipeline_layout, &shader, color_target.clone());
 
         Self {
             shader,
             pipeline,
             pipeline_layout,
+            color_target,
+            hdr_output: None,
+            spare_pipelines: Vec::new(),
             transform,
             colormod,
             transform_buf,
@@ -274,20 +351,23 @@ impl ColorGeo {
         }
     }
 
-    /// Changes the postprocessing phase's color target, re-creating the pipeline if needed
-    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
-        self.pipeline = gpu
-            .device()
+    fn create_pipeline(
+        gpu: &WGPU,
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        color_target: wgpu::ColorTargetState,
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some("post:pipeline"),
-                layout: Some(&self.pipeline_layout),
+                layout: Some(pipeline_layout),
                 vertex: wgpu::VertexState {
-                    module: &self.shader,
+                    module: shader,
                     entry_point: "vs_vbuf_main",
                     buffers: &[],
                 },
                 fragment: Some(wgpu::FragmentState {
-                    module: &self.shader,
+                    module: shader,
                     entry_point: "fs_main",
                     targets: &[Some(color_target)],
                 }),
@@ -295,14 +375,74 @@ impl ColorGeo {
                 depth_stencil: None,
                 multisample: wgpu::MultisampleState::default(),
                 multiview: None,
-            });
+            })
+    }
+
+    /// Changes the postprocessing phase's color target, re-creating
+    /// the pipeline if needed.  Pipelines for earlier color targets
+    /// are kept, so switching back to one of them is cheap.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        if color_target == self.color_target {
+            return;
+        }
+        let pipeline = match self
+            .spare_pipelines
+            .iter()
+            .position(|(target, _)| *target == color_target)
+        {
+            Some(idx) => self.spare_pipelines.swap_remove(idx).1,
+            None => Self::create_pipeline(
+                gpu,
+                &self.pipeline_layout,
+                &self.shader,
+                color_target.clone(),
+            ),
+        };
+        let old_target = std::mem::replace(&mut self.color_target, color_target);
+        let old_pipeline = std::mem::replace(&mut self.pipeline, pipeline);
+        self.spare_pipelines.push((old_target, old_pipeline));
+    }
+    /// Returns the color target the postprocessing phase draws into.
+    pub fn color_target(&self) -> &wgpu::ColorTargetState {
+        &self.color_target
+    }
+    /// Encodes the output for an HDR display, or for a standard one
+    /// if `output` is `None`.  The color target should have the
+    /// mode's format (see [`ColorGeo::set_color_target`]).
+    pub fn set_hdr_output(&mut self, gpu: &WGPU, output: Option<HdrOutput>) {
+        if output == self.hdr_output {
+            return;
+        }
+        self.hdr_output = output;
+        self.colormod.output = HdrOutput::uniform(output);
+        gpu.queue()
+            .write_buffer(&self.colormod_buf, 0, bytemuck::bytes_of(&self.colormod));
+    }
+    /// Sets how colors brighter than 1.0 are brought into range
+    /// before the lookup table is applied.
+    pub fn set_tonemap(&mut self, gpu: &WGPU, tonemap: Tonemap) {
+        self.colormod.saturation_padding[1] = tonemap as u32 as f32;
+        gpu.queue()
+            .write_buffer(&self.colormod_buf, 0, bytemuck::bytes_of(&self.colormod));
+    }
+    /// Returns the tone mapping operator.
+    pub fn tonemap(&self) -> Tonemap {
+        match self.colormod.saturation_padding[1] as u32 {
+            1 => Tonemap::Reinhard,
+            2 => Tonemap::Aces,
+            _ => Tonemap::None,
+        }
+    }
+    /// Returns the HDR output settings, if any.
+    pub fn hdr_output(&self) -> Option<HdrOutput> {
+        self.hdr_output
     }
     /// Updates simple parameters for color-geometry transforms.  To replace the lut, call [`ColorGeo::replace_lut`].
     pub fn set_post(&mut self, gpu: &WGPU, trf: [f32; 16], color_trf: [f32; 16], sat: f32) {
         // update buffers
         self.transform.mat = trf;
         self.colormod.mat = color_trf;
-        self.colormod.saturation_padding = [sat, 0.0, 0.0, 0.0];
+        self.colormod.saturation_padding[0] = sat;
         gpu.queue()
             .write_buffer(&self.transform_buf, 0, bytemuck::bytes_of(&self.transform));
         gpu.queue()
@@ -322,7 +462,10 @@ impl ColorGeo {
         );
     }
     /// Replaces the lookup table used by this postprocessing stage.
-    /// The LUT should be a 3D texture.
+    /// The LUT should be an N by N by N 3D texture with red along x,
+    /// green along y, and blue along its depth, whose texel at
+    /// `(r, g, b)` holds the output color for the input color
+    /// `(r, g, b) / (N - 1)`; colors between texels are interpolated.
     pub fn replace_lut(&mut self, gpu: &WGPU, lut: &wgpu::Texture) {
         self.lut_texture_view = lut.create_view(&wgpu::TextureViewDescriptor::default());
         self.texture_bind_group = Self::create_bind_group(
@@ -395,11 +538,23 @@ impl ColorGeo {
     where
         's: 'pass,
     {
-        rpass.set_pipeline(&self.pipeline);
+        capture::set_pipeline(rpass, &self.pipeline, "colorgeo:pipeline");
         // todo future: subdivide quad according to params, for cool visual effects
-        rpass.set_bind_group(0, &self.transform_bind_group, &[]);
-        rpass.set_bind_group(1, &self.texture_bind_group, &[]);
-        rpass.draw(0..6, 0..1);
+        capture::set_bind_group(
+            rpass,
+            0,
+            &self.transform_bind_group,
+            &[],
+            "colorgeo:transform_bind_group",
+        );
+        capture::set_bind_group(
+            rpass,
+            1,
+            &self.texture_bind_group,
+            &[],
+            "colorgeo:texture_bind_group",
+        );
+        capture::draw(rpass, 0..6, 0..1);
     }
     /// Returns the current geometric transform (a 4x4 homogeneous column-major matrix).
     pub fn transform(&self) -> [f32; 16] {
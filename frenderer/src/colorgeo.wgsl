@@ -27,9 +27,20 @@ struct ColorTransform {
    b: vec4<f32>,
    c: vec4<f32>,
    d: vec4<f32>,
+   // x: saturation; y: 0 for no tone mapping, 1 for Reinhard, 2 for ACES
    saturation_padding:vec4<f32>,
+   // x: 0 for SDR, 1 for scRGB, 2 for PQ; y: paper white and z:
+   // maximum luminance, in nits
+   output:vec4<f32>,
 }
 
+// Linear Rec. 709 to linear Rec. 2020 primaries
+const REC709_TO_REC2020:mat3x3<f32> = mat3x3<f32>(
+    vec3<f32>(0.6274, 0.0691, 0.0164),
+    vec3<f32>(0.3293, 0.9195, 0.0880),
+    vec3<f32>(0.0433, 0.0114, 0.8956)
+);
+
 @group(0) @binding(0)
 var<uniform> u_transform: Transform;
 
@@ -73,6 +84,50 @@ fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     let intensity:f32 = (color.x + color.y + color.z) / 3.0;
     let dev:vec4<f32> = vec4<f32>(intensity-color.x, intensity-color.y, intensity-color.z, 1.0);
     color += dev * -u_color.saturation_padding.x;
-    // apply LUT
-    return textureSample(t_lut, s_lut, color.xyz);
+    let mapped = tonemap(max(color.xyz, vec3<f32>(0.0)), u_color.saturation_padding.y);
+    // apply LUT, sampling between the centers of its first and last texels
+    let lut_size = vec3<f32>(textureDimensions(t_lut));
+    let lut_coords = (clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)) * (lut_size - 1.0) + 0.5) / lut_size;
+    return encode_output(textureSample(t_lut, s_lut, lut_coords));
+}
+
+fn tonemap(color:vec3<f32>, op:f32) -> vec3<f32> {
+    if op > 1.5 {
+        // Narkowicz's ACES fit
+        return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    }
+    if op > 0.5 {
+        let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        return color / (1.0 + luma);
+    }
+    return color;
+}
+
+// Smoothly compresses brightness above a knee so nothing exceeds max_nits
+fn tone_map(nits:vec3<f32>, max_nits:f32) -> vec3<f32> {
+    let knee = 0.75 * max_nits;
+    let range = max_nits - knee;
+    let compressed = knee + range * (1.0 - exp(-(nits - knee) / range));
+    return select(nits, compressed, nits > vec3<f32>(knee));
+}
+
+fn pq_encode(nits:vec3<f32>) -> vec3<f32> {
+    let y = pow(clamp(nits / 10000.0, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(0.1593017578125));
+    return pow((0.8359375 + 18.8515625 * y) / (1.0 + 18.6875 * y), vec3<f32>(78.84375));
+}
+
+fn encode_output(color:vec4<f32>) -> vec4<f32> {
+    let mode = u_color.output.x;
+    if mode < 0.5 {
+        return color;
+    }
+    var rgb = max(color.rgb, vec3<f32>(0.0));
+    if mode > 1.5 {
+        rgb = REC709_TO_REC2020 * rgb;
+    }
+    let nits = tone_map(rgb * u_color.output.y, u_color.output.z);
+    if mode > 1.5 {
+        return vec4<f32>(pq_encode(nits), color.a);
+    }
+    return vec4<f32>(nits / 80.0, color.a);
 }
@@ -0,0 +1,22 @@
+// u_params[0]: x = curvature, y = scanline strength, z = grille strength, w = scanline count
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let size = vec2<f32>(textureDimensions(t_color));
+  // Bulge the screen outwards, more towards its corners
+  let centered = in.tex_coords * 2.0 - 1.0;
+  let bent = centered * (1.0 + u_params[0].x * dot(centered, centered));
+  let uv = bent * 0.5 + 0.5;
+  let color = textureSample(t_color, s_color, uv);
+  if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+    return vec4<f32>(0.0, 0.0, 0.0, color.w);
+  }
+  let lines = select(size.y, u_params[0].w, u_params[0].w > 0.0);
+  let scan = 0.5 + 0.5 * cos(uv.y * lines * 6.2831853);
+  let scanline = mix(1.0, scan, clamp(u_params[0].y, 0.0, 1.0));
+  // Every third column of pixels favors red, green, or blue
+  let column = u32(in.clip_position.x) % 3u;
+  let mask = vec3<f32>(f32(column == 0u), f32(column == 1u), f32(column == 2u));
+  let grille = mix(vec3<f32>(1.0), mask * 1.5 + 0.25, clamp(u_params[0].z, 0.0, 1.0));
+  return vec4<f32>(color.xyz * scanline * grille, color.w);
+}
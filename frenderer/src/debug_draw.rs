@@ -0,0 +1,423 @@
+//! Immediate-mode debug drawing of lines and simple shapes, for
+//! visualizing colliders, paths, and raycasts without building a
+//! pipeline for them.
+//!
+//! [`DebugDraw`] collects shapes as one-pixel-wide lines during a
+//! frame.  [`crate::Renderer::debug_draw`] returns the renderer's own,
+//! whose shapes are uploaded by
+//! [`crate::Renderer::do_uploads`], drawn over the scene by
+//! [`crate::Renderer::render_into`], and then forgotten, so a shape
+//! has to be drawn again each frame it should stay visible.  2D
+//! shapes are positioned through a [`Camera2D`] like sprites (by
+//! default, in render target pixels from the bottom left) and drawn
+//! over everything; 3D shapes are seen through the textured mesh
+//! camera and hidden behind scene geometry when there's a depth
+//! buffer.
+
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use ultraviolet::Vec3;
+
+use crate::{capture, meshes::Camera3D, sprites::Camera2D, WGPU};
+
+// Segments in each circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct DebugUniforms {
+    projection_2d: [f32; 16],
+    projection_view_3d: [f32; 16],
+}
+
+/// Collects lines, rectangles, circles, and crosses in 2D and 3D and
+/// draws them as lines (see the [module documentation](self)).
+pub struct DebugDraw {
+    lines_2d: Vec<DebugVertex>,
+    lines_3d: Vec<DebugVertex>,
+    camera_2d: Option<Camera2D>,
+    camera_3d: Option<Camera3D>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    pipeline_2d: wgpu::RenderPipeline,
+    pipeline_3d: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    // The 2D and then 3D vertices uploaded by the last prepare
+    uploaded: (u32, u32),
+    // Whether those have been drawn since, and so are stale
+    drawn: AtomicBool,
+}
+
+impl DebugDraw {
+    /// Creates a new `DebugDraw` meant to draw into the given color
+    /// target state with the given depth texture format (or without
+    /// depth testing if `depth_format` is `None`).
+    pub fn new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("debug_draw:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("debug_draw.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("debug_draw:bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<DebugUniforms>() as u64,
+                            ),
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("debug_draw:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        // Translucent colors blend over the scene
+        let color_target = wgpu::ColorTargetState {
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            ..color_target
+        };
+        let depth_format = depth_format.into();
+        let (pipeline_2d, pipeline_3d) = Self::create_pipelines(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            &color_target,
+            (depth_format, 1),
+        );
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug_draw:uniforms"),
+            size: std::mem::size_of::<DebugUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug_draw:bg"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let vertex_buffer = Self::create_vertex_buffer(gpu, 1024);
+        Self {
+            lines_2d: vec![],
+            lines_3d: vec![],
+            camera_2d: None,
+            camera_3d: None,
+            shader,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipeline_2d,
+            pipeline_3d,
+            uniform_buffer,
+            bind_group,
+            vertex_buffer,
+            uploaded: (0, 0),
+            drawn: AtomicBool::new(false),
+        }
+    }
+    fn create_vertex_buffer(gpu: &WGPU, vertices: usize) -> wgpu::Buffer {
+        gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug_draw:vertices"),
+            size: (vertices * std::mem::size_of::<DebugVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    fn create_pipelines(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: &wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        let create = |label, vs_entry, depth_compare| {
+            gpu.device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: vs_entry,
+                        buffers: &[wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<DebugVertex>() as u64,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                        }],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(color_target.clone())],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::LineList,
+                        ..Default::default()
+                    },
+                    // Lines are tested against the scene but don't
+                    // write depth
+                    depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                        format,
+                        depth_write_enabled: false,
+                        depth_compare,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        ..Default::default()
+                    },
+                    multiview: None,
+                })
+        };
+        (
+            create(
+                "debug_draw:pipeline_2d",
+                "vs_2d",
+                wgpu::CompareFunction::Always,
+            ),
+            create(
+                "debug_draw:pipeline_3d",
+                "vs_3d",
+                wgpu::CompareFunction::LessEqual,
+            ),
+        )
+    }
+    fn push_2d(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.lines_2d.extend([from, to].map(|[x, y]| DebugVertex {
+            position: [x, y, 0.0],
+            color,
+        }));
+    }
+    /// Draws a 2D line segment.
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.push_2d(from, to, color);
+    }
+    /// Draws the outline of a 2D axis-aligned rectangle between two
+    /// opposite corners.
+    pub fn rect(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        let corners = [min, [max[0], min[1]], max, [min[0], max[1]]];
+        for i in 0..4 {
+            self.push_2d(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+    /// Draws the outline of a 2D circle.
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        let point = |i: usize| {
+            let angle = i as f32 * std::f32::consts::TAU / CIRCLE_SEGMENTS as f32;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.push_2d(point(i), point(i + 1), color);
+        }
+    }
+    /// Marks a 2D point with a horizontal and a vertical line, each
+    /// `size` long.
+    pub fn cross(&mut self, center: [f32; 2], size: f32, color: [f32; 4]) {
+        let half = size / 2.0;
+        let [x, y] = center;
+        self.push_2d([x - half, y], [x + half, y], color);
+        self.push_2d([x, y - half], [x, y + half], color);
+    }
+    /// Draws a 3D line segment.
+    pub fn line_3d(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 4]) {
+        self.lines_3d
+            .extend([from, to].map(|position| DebugVertex { position, color }));
+    }
+    /// Draws the edges of a 3D axis-aligned box between two opposite
+    /// corners, e.g. a collider's bounds.
+    pub fn box_3d(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        let corner = |i: usize| {
+            [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ]
+        };
+        // Each edge joins corners differing in one axis
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line_3d(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+    /// Draws the outline of a 3D circle facing along `normal`.
+    pub fn circle_3d(&mut self, center: [f32; 3], radius: f32, normal: [f32; 3], color: [f32; 4]) {
+        let normal = Vec3::from(normal).normalized();
+        // Any vector not parallel to the normal gives the circle's plane
+        let other = if normal.x.abs() < 0.9 {
+            Vec3::unit_x()
+        } else {
+            Vec3::unit_y()
+        };
+        let u = normal.cross(other).normalized() * radius;
+        let v = normal.cross(u);
+        let center = Vec3::from(center);
+        let point = |i: usize| {
+            let angle = i as f32 * std::f32::consts::TAU / CIRCLE_SEGMENTS as f32;
+            (center + u * angle.cos() + v * angle.sin()).into()
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line_3d(point(i), point(i + 1), color);
+        }
+    }
+    /// Marks a 3D point with a line along each axis, each `size` long.
+    pub fn cross_3d(&mut self, center: [f32; 3], size: f32, color: [f32; 4]) {
+        for axis in 0..3 {
+            let (mut from, mut to) = (center, center);
+            from[axis] -= size / 2.0;
+            to[axis] += size / 2.0;
+            self.line_3d(from, to, color);
+        }
+    }
+    /// Forgets every shape drawn so far.
+    pub fn clear(&mut self) {
+        self.lines_2d.clear();
+        self.lines_3d.clear();
+    }
+    /// Returns true if no shapes have been drawn since the last clear.
+    pub fn is_empty(&self) -> bool {
+        self.lines_2d.is_empty() && self.lines_3d.is_empty()
+    }
+    // Whether prepare has anything to change: shapes drawn since
+    // the last one, or drawn shapes from it to take away
+    pub(crate) fn needs_prepare(&self) -> bool {
+        !self.is_empty() || self.drawn.load(Ordering::Relaxed)
+    }
+    /// Positions 2D shapes through the given camera, or through the
+    /// default one passed to [`DebugDraw::prepare`] if `camera` is
+    /// `None`.
+    pub fn set_camera_2d(&mut self, camera: Option<Camera2D>) {
+        self.camera_2d = camera;
+    }
+    /// Returns the camera 2D shapes are positioned through, if it's
+    /// not the default.
+    pub fn camera_2d(&self) -> Option<Camera2D> {
+        self.camera_2d
+    }
+    /// Draws 3D shapes from the given camera, or from the default one
+    /// passed to [`DebugDraw::prepare`] if `camera` is `None`.
+    pub fn set_camera_3d(&mut self, camera: Option<Camera3D>) {
+        self.camera_3d = camera;
+    }
+    /// Returns the camera 3D shapes are drawn from, if it's not the
+    /// default.
+    pub fn camera_3d(&self) -> Option<Camera3D> {
+        self.camera_3d
+    }
+    /// Uploads the shapes drawn so far and the cameras, using the
+    /// given 2D and 3D cameras where none have been set; call this
+    /// before drawing with [`DebugDraw::render`].
+    pub fn prepare(&mut self, gpu: &WGPU, (camera_2d, camera_3d): (Camera2D, Camera3D)) {
+        let uniforms = DebugUniforms {
+            projection_2d: *self.camera_2d.unwrap_or(camera_2d).projection().as_array(),
+            projection_view_3d: *self
+                .camera_3d
+                .unwrap_or(camera_3d)
+                .projection_view()
+                .as_array(),
+        };
+        gpu.queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        let len = self.lines_2d.len() + self.lines_3d.len();
+        let capacity = self.vertex_buffer.size() as usize / std::mem::size_of::<DebugVertex>();
+        if len > capacity {
+            self.vertex_buffer = Self::create_vertex_buffer(gpu, len.next_power_of_two());
+        }
+        gpu.queue()
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.lines_2d));
+        gpu.queue().write_buffer(
+            &self.vertex_buffer,
+            std::mem::size_of_val(self.lines_2d.as_slice()) as u64,
+            bytemuck::cast_slice(&self.lines_3d),
+        );
+        self.uploaded = (self.lines_2d.len() as u32, self.lines_3d.len() as u32);
+        *self.drawn.get_mut() = false;
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipelines.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        (self.pipeline_2d, self.pipeline_3d) = Self::create_pipelines(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            &self.color_target,
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass this draws
+    /// into, re-creating the pipelines.  The default is 1 (no
+    /// multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target the shapes are drawn into (e.g. for a
+    /// new color format), re-creating the pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Draws the shapes uploaded by the last [`DebugDraw::prepare`]
+    /// into the given renderpass, which should have been begun with
+    /// the same color and depth formats this renderer was created
+    /// with: 3D shapes first, then 2D shapes over them.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        let (len_2d, len_3d) = self.uploaded;
+        if len_2d + len_3d == 0 {
+            return;
+        }
+        self.drawn.store(true, Ordering::Relaxed);
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "debug_draw:bind_group");
+        capture::set_vertex_buffer(rpass, 0, &self.vertex_buffer, "debug_draw:vertices");
+        if len_3d > 0 {
+            capture::set_pipeline(rpass, &self.pipeline_3d, "debug_draw:pipeline_3d");
+            capture::draw(rpass, len_2d..len_2d + len_3d, 0..1);
+        }
+        if len_2d > 0 {
+            capture::set_pipeline(rpass, &self.pipeline_2d, "debug_draw:pipeline_2d");
+            capture::draw(rpass, 0..len_2d, 0..1);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+struct Cameras {
+    // Maps Camera2D world space to clip space
+    projection_2d: mat4x4<f32>,
+    projection_view_3d: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> cameras: Cameras;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_2d(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    return VertexOutput(cameras.projection_2d * vec4(position.xy, 0.0, 1.0), color);
+}
+
+@vertex
+fn vs_3d(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    return VertexOutput(cameras.projection_view_3d * vec4(position, 1.0), color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
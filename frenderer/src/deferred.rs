@@ -0,0 +1,407 @@
+//! An optional deferred shading path for the 3D mesh renderers.
+//!
+//! Rather than shading each fragment as it's drawn, the mesh
+//! renderers' `render_gbuffer` methods draw their groups' surfaces
+//! into a [`GBuffer`]: each pixel's albedo, world-space normal,
+//! material, and depth.  [`DeferredLighting`] then resolves the
+//! G-buffer into the color target in one fullscreen pass, lighting
+//! every visible pixel exactly once with up to
+//! [`MAX_DEFERRED_LIGHTS`] point lights (see
+//! [`crate::shadow::PointLight`]) no matter how many meshes overlap
+//! it, and writing the G-buffer's depth into the target's depth
+//! texture so that sprites, the grid, and screen-space effects like
+//! SSAO (see [`crate::postprocess`]) see the meshes as usual.  The
+//! G-buffer textures are also available to custom effects.
+//! [`crate::Renderer::deferred_set`] does all of this.
+//!
+//! The G-buffer holds:
+//! - albedo ([`ALBEDO_FORMAT`]): the surface color, with alpha;
+//! - normal ([`NORMAL_FORMAT`]): the world-space normal in `xyz`
+//!   (zero for surfaces without normals), and 1.0 in `w` wherever
+//!   anything was drawn;
+//! - material ([`MATERIAL_FORMAT`]): the light already reaching the
+//!   surface in `rgb` (a flat mesh's hemisphere and directional
+//!   lighting, or white for unlit surfaces), and how strongly point
+//!   lights light it in `a` (1.0 for flat meshes, 0.0 for textured
+//!   meshes, which are unlit);
+//! - depth ([`GBUFFER_DEPTH_FORMAT`]).
+//!
+//! Deferred shading only covers opaque surfaces: blending, materials,
+//! and instance colors aren't drawn into the G-buffer, and point
+//! lights don't cast shadows.
+
+use std::borrow::Cow;
+
+use crate::{capture, meshes::Camera3D, shadow::PointLight, WGPU};
+
+/// How many point lights [`DeferredLighting`] can light a scene with at once.
+pub const MAX_DEFERRED_LIGHTS: usize = 256;
+
+/// The format of the G-buffer's albedo texture.
+pub const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// The format of the G-buffer's normal texture.
+pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// The format of the G-buffer's material texture.
+pub const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// The format of the G-buffer's depth texture.
+pub const GBUFFER_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// The color target states for drawing into a G-buffer.
+pub(crate) fn gbuffer_color_targets() -> [Option<wgpu::ColorTargetState>; 3] {
+    [ALBEDO_FORMAT, NORMAL_FORMAT, MATERIAL_FORMAT].map(|format| Some(format.into()))
+}
+
+/// The textures of the deferred shading path's G-buffer (see the
+/// [module documentation](self)).
+pub struct GBuffer {
+    albedo: (wgpu::Texture, wgpu::TextureView),
+    normal: (wgpu::Texture, wgpu::TextureView),
+    material: (wgpu::Texture, wgpu::TextureView),
+    depth: (wgpu::Texture, wgpu::TextureView),
+}
+
+impl GBuffer {
+    /// Creates a `width` by `height` G-buffer.
+    pub fn new(gpu: &WGPU, width: u32, height: u32) -> Self {
+        let create = |label, format| {
+            let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        };
+        Self {
+            albedo: create("deferred:albedo", ALBEDO_FORMAT),
+            normal: create("deferred:normal", NORMAL_FORMAT),
+            material: create("deferred:material", MATERIAL_FORMAT),
+            depth: create("deferred:depth", GBUFFER_DEPTH_FORMAT),
+        }
+    }
+    /// Re-creates the G-buffer with a new size; any
+    /// [`DeferredLighting`] reading it needs
+    /// [`DeferredLighting::set_gbuffer`] afterwards.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        *self = Self::new(gpu, width, height);
+    }
+    /// Returns the albedo texture.
+    pub fn albedo(&self) -> &wgpu::Texture {
+        &self.albedo.0
+    }
+    /// Returns the world-space normal texture.
+    pub fn normal(&self) -> &wgpu::Texture {
+        &self.normal.0
+    }
+    /// Returns the material texture.
+    pub fn material(&self) -> &wgpu::Texture {
+        &self.material.0
+    }
+    /// Returns the depth texture.
+    pub fn depth(&self) -> &wgpu::Texture {
+        &self.depth.0
+    }
+    /// Begins a render pass which clears the G-buffer; draw opaque
+    /// surfaces into it with the mesh renderers' `render_gbuffer`
+    /// methods.
+    pub fn begin_pass<'s>(&'s self, encoder: &'s mut wgpu::CommandEncoder) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("deferred:gbuffer");
+        let target = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("deferred:gbuffer"),
+            color_attachments: &[
+                target(&self.albedo.1),
+                target(&self.normal.1),
+                target(&self.material.1),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth.1,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+// One light as laid out in the shader
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DeferredLightUniform {
+    position_range: [f32; 4],
+    color: [f32; 4],
+}
+
+// The inverse projection-view matrix comes first, then the light count
+const CAMERA_SIZE: u64 = std::mem::size_of::<[[f32; 4]; 4]>() as u64;
+const LIGHTS_SIZE: u64 = (std::mem::size_of::<[u32; 4]>()
+    + std::mem::size_of::<[DeferredLightUniform; MAX_DEFERRED_LIGHTS]>())
+    as u64;
+
+/// Lights a [`GBuffer`] with point lights and draws the result into
+/// a color target (see the [module documentation](self)).
+pub struct DeferredLighting {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl DeferredLighting {
+    /// Creates a `DeferredLighting` which reads the given G-buffer and
+    /// draws into the given color target state, writing depth in the
+    /// given depth texture format (or not at all if `depth_format` is
+    /// `None`).
+    pub fn new(
+        gpu: &WGPU,
+        gbuffer: &GBuffer,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("deferred:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("deferred.wgsl"))),
+            });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("deferred:bgl"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        texture_entry(1),
+                        texture_entry(2),
+                        texture_entry(3),
+                        texture_entry(4),
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("deferred:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let depth_format = depth_format.into();
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            color_target.clone(),
+            (depth_format, 1),
+        );
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("deferred:lights"),
+            size: CAMERA_SIZE + LIGHTS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::create_bind_group(gpu, &bind_group_layout, &buffer, gbuffer);
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("deferred:pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // The G-buffer's depth replaces whatever was there
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    fn create_bind_group(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        gbuffer: &GBuffer,
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("deferred:bg"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.albedo.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.normal.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.material.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.depth.1),
+                },
+            ],
+        })
+    }
+    /// Reads from the given G-buffer from now on (e.g. after it was resized).
+    pub fn set_gbuffer(&mut self, gpu: &WGPU, gbuffer: &GBuffer) {
+        self.bind_group =
+            Self::create_bind_group(gpu, &self.bind_group_layout, &self.buffer, gbuffer);
+    }
+    /// Changes the depth format this writes, re-creating the pipeline.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.color_target.clone(),
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass
+    /// [`DeferredLighting::resolve`] draws into, re-creating the
+    /// pipeline.  The default is 1 (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target the lighting is drawn into (e.g. for a
+    /// new color format), re-creating the pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Sets the camera the G-buffer was drawn with, which is needed
+    /// to find each pixel's position in the world.
+    pub fn set_camera(&mut self, gpu: &WGPU, camera: Camera3D) {
+        let inv_projview = camera.projection_view().inversed();
+        gpu.queue()
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(inv_projview.as_array()));
+    }
+    /// Lights the G-buffer with the given point lights, replacing any
+    /// earlier ones; their `shadows` are ignored.  Panics if there
+    /// are more than [`MAX_DEFERRED_LIGHTS`].
+    pub fn set_lights(&mut self, gpu: &WGPU, lights: &[PointLight]) {
+        assert!(
+            lights.len() <= MAX_DEFERRED_LIGHTS,
+            "at most {MAX_DEFERRED_LIGHTS} deferred point lights are supported"
+        );
+        let count = [lights.len() as u32, 0, 0, 0];
+        let lights: Vec<_> = lights
+            .iter()
+            .map(|light| {
+                let [x, y, z] = light.position;
+                let [r, g, b] = light.color;
+                DeferredLightUniform {
+                    position_range: [x, y, z, light.range],
+                    color: [r, g, b, 1.0],
+                }
+            })
+            .collect();
+        gpu.queue()
+            .write_buffer(&self.buffer, CAMERA_SIZE, bytemuck::bytes_of(&count));
+        if !lights.is_empty() {
+            gpu.queue().write_buffer(
+                &self.buffer,
+                CAMERA_SIZE + std::mem::size_of::<[u32; 4]>() as u64,
+                bytemuck::cast_slice(&lights),
+            );
+        }
+    }
+    /// Draws the lit G-buffer into the given renderpass, which should
+    /// have been begun with the same color and depth formats this was
+    /// created with and be the same size as the G-buffer.  Pixels
+    /// where nothing was drawn into the G-buffer are left alone.
+    pub fn resolve<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "deferred:pipeline");
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "deferred:bind_group");
+        capture::draw(rpass, 0..3, 0..1);
+    }
+}
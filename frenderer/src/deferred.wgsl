@@ -0,0 +1,71 @@
+// Resolves the G-buffer of the deferred shading path: lights each
+// pixel with any number of point lights and writes its depth back to
+// the target so that forward-rendered geometry is tested against it.
+
+struct DeferredLight {
+  position_range: vec4<f32>,
+  color: vec4<f32>,
+}
+struct DeferredLights {
+  inv_projview: mat4x4<f32>,
+  // x is the number of lights
+  count: vec4<u32>,
+  lights: array<DeferredLight, 256>,
+}
+
+@group(0) @binding(0)
+var<uniform> u_lights: DeferredLights;
+@group(0) @binding(1)
+var t_albedo: texture_2d<f32>;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var t_material: texture_2d<f32>;
+@group(0) @binding(4)
+var t_depth: texture_2d<f32>;
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+  let uv = vec2<f32>(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+  return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+struct FragmentOutput {
+  @location(0) color: vec4<f32>,
+  @builtin(frag_depth) depth: f32,
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_pos: vec4<f32>) -> FragmentOutput {
+  let p = vec2<i32>(frag_pos.xy);
+  let normal = textureLoad(t_normal, p, 0);
+  // Nothing was drawn here
+  if normal.w == 0.0 {
+    discard;
+  }
+  let albedo = textureLoad(t_albedo, p, 0);
+  let material = textureLoad(t_material, p, 0);
+  let depth = textureLoad(t_depth, p, 0).r;
+  let size = vec2<f32>(textureDimensions(t_depth));
+  let ndc = vec4<f32>(frag_pos.x / size.x * 2.0 - 1.0, 1.0 - frag_pos.y / size.y * 2.0, depth, 1.0);
+  let world = u_lights.inv_projview * ndc;
+  let pos = world.xyz / world.w;
+  // Surfaces without normals only get their material's base light
+  var light = vec3<f32>(0.0);
+  if dot(normal.xyz, normal.xyz) > 0.0 {
+    let n = normalize(normal.xyz);
+    for (var i = 0u; i < u_lights.count.x; i++) {
+      let point = u_lights.lights[i];
+      let to_light = point.position_range.xyz - pos;
+      let dist = length(to_light);
+      let falloff = clamp(1.0 - dist / point.position_range.w, 0.0, 1.0);
+      let lambert = max(dot(n, to_light / max(dist, 0.0001)), 0.0);
+      light += point.color.rgb * (lambert * falloff * falloff);
+    }
+  }
+  var out:FragmentOutput;
+  out.color = vec4<f32>(albedo.rgb * (material.rgb + material.a * light), albedo.a);
+  out.depth = depth;
+  return out;
+}
@@ -0,0 +1,51 @@
+// u_params[0]: x = levels per channel, y = pattern, z = palette length, w = spread
+// u_params[1..17]: palette colors
+
+// Threshold from a 2^bits square Bayer matrix, built by interleaving
+// coordinate bits (low coordinate bits pick the high value bits).
+fn bayer(p:vec2<u32>, bits:u32) -> f32 {
+  var v = 0u;
+  for (var i = 0u; i < bits; i++) {
+    let x = (p.x >> i) & 1u;
+    let y = (p.y >> i) & 1u;
+    let shift = 2u*(bits-1u-i);
+    v = v | ((x ^ y) << (shift+1u)) | (y << shift);
+  }
+  return (f32(v) + 0.5) / f32(1u << (2u*bits));
+}
+
+// Interleaved gradient noise, a cheap pattern with blue-noise-like spectrum
+fn gradient_noise(p:vec2<f32>) -> f32 {
+  return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let p = vec2<u32>(in.clip_position.xy);
+  let color = textureLoad(t_color, vec2<i32>(p), 0);
+  let pattern = u32(u_params[0].y);
+  var threshold:f32;
+  if pattern == 3u {
+    threshold = gradient_noise(floor(in.clip_position.xy));
+  } else {
+    threshold = bayer(p, pattern + 1u);
+  }
+  let dithered = color.xyz + (threshold - 0.5) * u_params[0].w;
+  let palette_len = u32(u_params[0].z);
+  if palette_len == 0u {
+    let steps = max(u_params[0].x - 1.0, 1.0);
+    return vec4<f32>(round(clamp(dithered, vec3<f32>(0.0), vec3<f32>(1.0)) * steps) / steps, color.w);
+  }
+  // snap to the nearest palette color
+  var best = u_params[1].xyz;
+  var best_dist = 1e9;
+  for (var i = 0u; i < palette_len; i++) {
+    let diff = dithered - u_params[1u + i].xyz;
+    let dist = dot(diff, diff);
+    if dist < best_dist {
+      best = u_params[1u + i].xyz;
+      best_dist = dist;
+    }
+  }
+  return vec4<f32>(best, color.w);
+}
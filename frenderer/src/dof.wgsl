@@ -0,0 +1,58 @@
+// u_params[0]: x = focus mode (0 = by depth, 1 = by screen height),
+//              y = focus center, z = focus range, w = largest blur radius in pixels
+// u_params[1]: x = sample count
+// u_params[2..6]: inverse projection matrix columns
+
+fn inv_proj() -> mat4x4<f32> {
+  return mat4x4<f32>(u_params[2], u_params[3], u_params[4], u_params[5]);
+}
+
+// The blur radius in pixels of the point drawn at pixel p
+fn coc(p:vec2<i32>) -> f32 {
+  let size = vec2<i32>(textureDimensions(t_color));
+  let pc = clamp(p, vec2<i32>(0), size - vec2<i32>(1));
+  let uv = (vec2<f32>(pc) + 0.5) / vec2<f32>(size);
+  var offset:f32;
+  if u_params[0].x == 0.0 {
+    // Without a real depth texture everything is in focus
+    if any(textureDimensions(t_depth) != textureDimensions(t_color)) {
+      return 0.0;
+    }
+    let depth = textureLoad(t_depth, pc, 0).r;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let v = inv_proj() * ndc;
+    offset = abs(-v.z / v.w - u_params[0].y);
+  } else {
+    offset = abs(uv.y - u_params[0].y);
+  }
+  // Sharp within the focus range, fully blurred at twice the range
+  let range = max(u_params[0].z, 1e-4);
+  return clamp((offset - range) / range, 0.0, 1.0) * u_params[0].w;
+}
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let p = vec2<i32>(in.clip_position.xy);
+  let center = textureLoad(t_color, p, 0);
+  let radius = coc(p);
+  if radius < 0.5 {
+    return center;
+  }
+  // Gather samples on a disc; each sample counts if its own blur
+  // reaches this pixel, so sharp foreground doesn't smear behind it
+  let count = u32(u_params[1].x);
+  var sum = center;
+  var weight = 1.0;
+  for (var i = 0u; i < count; i++) {
+    let t = sqrt((f32(i) + 0.5) / f32(count));
+    let phi = f32(i) * 2.3999632;
+    let offset = vec2<f32>(cos(phi), sin(phi)) * t * radius;
+    let q = p + vec2<i32>(round(offset));
+    let reach = coc(q);
+    let w = clamp(reach - length(offset) + 1.0, 0.0, 1.0);
+    let size = vec2<i32>(textureDimensions(t_color));
+    sum += textureLoad(t_color, clamp(q, vec2<i32>(0), size - vec2<i32>(1)), 0) * w;
+    weight += w;
+  }
+  return sum / weight;
+}
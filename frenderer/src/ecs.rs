@@ -0,0 +1,272 @@
+//! An adapter which keeps frenderer's sprite and mesh groups in sync
+//! with a [`bevy_ecs`] [`World`] (requires the `bevy_ecs` feature).
+//!
+//! Give entities a [`Position`] and either a [`Sprite`] or a
+//! [`MeshInstance`], create the groups they name with the
+//! [`crate::Renderer`] as usual, and call [`EcsSync::sync`] once per
+//! frame before rendering.  Each entity is given a slot in its group
+//! (growing the group if needed), and slots of despawned entities or
+//! entities that lost their components are hidden and reused.  Only
+//! entities whose components changed since the last sync are written,
+//! and each group or mesh gets at most one upload covering its
+//! changed slots.
+
+use crate::{
+    hierarchy,
+    meshes::{MeshGroup, Transform3D},
+    replay::MeshKind,
+    sprites::{SheetRegion, Transform},
+    Renderer,
+};
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::{Component, Tick},
+    entity::Entity,
+    world::{Ref, World},
+};
+use std::{collections::HashMap, ops::Range};
+
+/// Where an entity is drawn.  Sprites take their position and
+/// rotation from this, but not their size (see
+/// [`crate::hierarchy`]).
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Position(pub Transform3D);
+
+impl Position {
+    /// A position in the xy plane with a counterclockwise rotation in
+    /// radians, like a sprite's.
+    pub fn from_2d(x: f32, y: f32, rot: f32) -> Self {
+        Self(hierarchy::transform_2d(x, y, rot))
+    }
+}
+
+/// Draws an entity as a sprite in the given sprite group.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Sprite {
+    /// The sprite group to draw the sprite in
+    pub group: usize,
+    /// The sprite's region of the group's spritesheet
+    pub region: SheetRegion,
+    /// The width of the sprite
+    pub width: u16,
+    /// The height of the sprite
+    pub height: u16,
+}
+
+/// Draws an entity as an instance of the given mesh of a textured or
+/// flat mesh group.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct MeshInstance {
+    /// Whether `group` is a textured or flat mesh group
+    pub kind: MeshKind,
+    /// The mesh group containing the mesh
+    pub group: MeshGroup,
+    /// Which mesh of the group to draw
+    pub mesh: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Slots {
+    Sprite(usize),
+    Mesh(MeshKind, MeshGroup, usize),
+}
+
+enum Instance {
+    Sprite(Transform, SheetRegion),
+    Mesh(Transform3D),
+}
+
+/// Synchronizes entities' [`Position`], [`Sprite`], and
+/// [`MeshInstance`] components into a [`Renderer`] (see the [module
+/// documentation](self)).
+pub struct EcsSync {
+    slots: HashMap<Entity, (Slots, usize)>,
+    // Hidden slots of each group or mesh, ready for reuse
+    free: HashMap<Slots, Vec<usize>>,
+    last_run: Tick,
+}
+
+impl Default for EcsSync {
+    fn default() -> Self {
+        Self {
+            slots: HashMap::new(),
+            free: HashMap::new(),
+            last_run: Tick::new(0),
+        }
+    }
+}
+
+impl EcsSync {
+    /// Creates an adapter which hasn't synchronized any entities yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the group slot an entity is drawn in, if any: its
+    /// sprite index or its mesh instance index.
+    pub fn slot(&self, entity: Entity) -> Option<usize> {
+        self.slots.get(&entity).map(|(_, index)| *index)
+    }
+    /// Writes changed entities into the renderer's groups and queues
+    /// their uploads.  Panics if a component names a group (or mesh)
+    /// which isn't populated.
+    pub fn sync(&mut self, world: &mut World, renderer: &mut Renderer) {
+        trace_span!("frenderer::ecs::sync", entities = self.slots.len());
+        let this_run = world.increment_change_tick();
+        let last_run = self.last_run;
+        self.last_run = this_run;
+        let changed = |a: Tick, b: Tick| {
+            a.is_newer_than(last_run, this_run) || b.is_newer_than(last_run, this_run)
+        };
+        // Gather what every drawn entity wants, building instance
+        // data only for entities that changed
+        let mut wanted: Vec<(Entity, Slots, Option<Instance>)> = vec![];
+        let mut sprites = world.query::<(Entity, Ref<Position>, Ref<Sprite>)>();
+        for (entity, pos, sprite) in sprites.iter(world) {
+            let slots = Slots::Sprite(sprite.group);
+            let instance = self
+                .stale(
+                    entity,
+                    slots,
+                    changed(pos.last_changed(), sprite.last_changed()),
+                )
+                .then(|| {
+                    let (x, y, rot) = hierarchy::sprite_placement(&pos.0);
+                    Instance::Sprite(
+                        Transform {
+                            w: sprite.width,
+                            h: sprite.height,
+                            x,
+                            y,
+                            rot,
+                        },
+                        sprite.region,
+                    )
+                });
+            wanted.push((entity, slots, instance));
+        }
+        let mut meshes = world.query::<(Entity, Ref<Position>, Ref<MeshInstance>)>();
+        for (entity, pos, mesh) in meshes.iter(world) {
+            let slots = Slots::Mesh(mesh.kind, mesh.group, mesh.mesh);
+            let instance = self
+                .stale(
+                    entity,
+                    slots,
+                    changed(pos.last_changed(), mesh.last_changed()),
+                )
+                .then_some(Instance::Mesh(pos.0));
+            wanted.push((entity, slots, instance));
+        }
+        // Free the slots of entities which are gone or moved to another group
+        let mut dirty: HashMap<Slots, Vec<(usize, Instance)>> = HashMap::new();
+        let keep: HashMap<Entity, Slots> = wanted.iter().map(|(e, s, _)| (*e, *s)).collect();
+        self.slots.retain(|entity, (slots, index)| {
+            if keep.get(entity) == Some(slots) {
+                return true;
+            }
+            // Zero-sized instances draw nothing
+            let hidden = match slots {
+                Slots::Sprite(_) => Instance::Sprite(Transform::ZERO, SheetRegion::ZERO),
+                Slots::Mesh(..) => Instance::Mesh(bytemuck::Zeroable::zeroed()),
+            };
+            dirty.entry(*slots).or_default().push((*index, hidden));
+            self.free.entry(*slots).or_default().push(*index);
+            false
+        });
+        // Give new entities slots, growing each group at most once
+        let mut ends: HashMap<Slots, usize> = HashMap::new();
+        for (entity, slots, instance) in wanted {
+            let Some(instance) = instance else {
+                continue;
+            };
+            let index = match self.slots.get(&entity) {
+                Some((_, index)) => *index,
+                None => {
+                    let free = self.free.entry(slots).or_default();
+                    let index = match free.pop() {
+                        Some(index) => index,
+                        None => {
+                            let end = ends
+                                .entry(slots)
+                                .or_insert_with(|| Self::len(renderer, slots));
+                            *end += 1;
+                            *end - 1
+                        }
+                    };
+                    self.slots.insert(entity, (slots, index));
+                    index
+                }
+            };
+            dirty.entry(slots).or_default().push((index, instance));
+        }
+        for (slots, writes) in dirty {
+            let start = writes.iter().map(|(index, _)| *index).min().unwrap();
+            let end = writes.iter().map(|(index, _)| index + 1).max().unwrap();
+            let range = start..end;
+            if range.end > Self::len(renderer, slots) {
+                Self::resize(renderer, slots, range.end);
+            }
+            Self::write(renderer, slots, range, writes);
+        }
+    }
+    // Whether an entity's instance data needs writing
+    fn stale(&self, entity: Entity, slots: Slots, changed: bool) -> bool {
+        changed || self.slots.get(&entity).map(|(s, _)| *s) != Some(slots)
+    }
+    fn len(renderer: &Renderer, slots: Slots) -> usize {
+        match slots {
+            Slots::Sprite(group) => renderer.sprite_group_size(group),
+            Slots::Mesh(MeshKind::Textured, group, mesh) => {
+                renderer.mesh_instance_count(group, mesh)
+            }
+            Slots::Mesh(MeshKind::Flat, group, mesh) => renderer.flat_instance_count(group, mesh),
+        }
+    }
+    fn resize(renderer: &mut Renderer, slots: Slots, len: usize) {
+        match slots {
+            Slots::Sprite(group) => {
+                renderer.sprite_group_resize(group, len);
+            }
+            Slots::Mesh(MeshKind::Textured, group, mesh) => {
+                renderer.mesh_instance_resize(group, mesh, len);
+            }
+            Slots::Mesh(MeshKind::Flat, group, mesh) => {
+                renderer.flat_instance_resize(group, mesh, len);
+            }
+        }
+    }
+    fn write(
+        renderer: &mut Renderer,
+        slots: Slots,
+        range: Range<usize>,
+        writes: Vec<(usize, Instance)>,
+    ) {
+        let start = range.start;
+        match slots {
+            Slots::Sprite(group) => {
+                let (trfs, uvs) = renderer.sprites_mut(group, range);
+                for (index, instance) in writes {
+                    if let Instance::Sprite(trf, uv) = instance {
+                        trfs[index - start] = trf;
+                        uvs[index - start] = uv;
+                    }
+                }
+            }
+            Slots::Mesh(MeshKind::Textured, group, mesh) => {
+                let trfs = renderer.meshes_mut(group, mesh, range);
+                for (index, instance) in writes {
+                    if let Instance::Mesh(trf) = instance {
+                        trfs[index - start] = trf;
+                    }
+                }
+            }
+            Slots::Mesh(MeshKind::Flat, group, mesh) => {
+                let trfs = renderer.flats_mut(group, mesh, range);
+                for (index, instance) in writes {
+                    if let Instance::Mesh(trf) = instance {
+                        trfs[index - start] = trf;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Rendering into texture views owned by another application.
+//!
+//! [`crate::Renderer::render_to_view`] draws everything
+//! [`crate::Renderer::render`] would, but into a caller-supplied
+//! [`ViewTarget`] instead of the renderer's surface, so that
+//! frenderer can be embedded as a viewport inside another wgpu
+//! application or UI framework.  The target can be any size and any
+//! color format the adapter can render to: the scene is drawn at the
+//! renderer's render resolution (see
+//! [`crate::Renderer::resize_render`]) and stretched over the target,
+//! just as it is over the surface.  If the target has a depth view,
+//! the scene's depth is stretched over it too, so the host can draw
+//! its own geometry into the viewport afterwards.
+
+use std::borrow::Cow;
+
+use crate::{capture, WGPU};
+
+/// A color view, and optionally a depth view, for
+/// [`crate::Renderer::render_to_view`] to draw into.  Both views must
+/// cover the whole of a single-sampled 2D texture layer; they needn't
+/// be the same size.
+#[derive(Clone, Copy, Debug)]
+pub struct ViewTarget<'a> {
+    /// The view the final image is drawn into
+    pub color: &'a wgpu::TextureView,
+    /// The format of `color`, which must be renderable
+    pub color_format: wgpu::TextureFormat,
+    /// A view which receives the scene's depth, and its format, which
+    /// must have a depth aspect
+    pub depth: Option<(&'a wgpu::TextureView, wgpu::TextureFormat)>,
+}
+
+impl<'a> ViewTarget<'a> {
+    /// A target with the given color view and no depth.
+    pub fn new(color: &'a wgpu::TextureView, color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            color,
+            color_format,
+            depth: None,
+        }
+    }
+    /// Also write the scene's depth into the given view.
+    pub fn with_depth(mut self, depth: &'a wgpu::TextureView, format: wgpu::TextureFormat) -> Self {
+        self.depth = Some((depth, format));
+        self
+    }
+    /// Checks that the target's formats can be rendered to on `gpu`.
+    pub fn validate(&self, gpu: &WGPU) -> Result<(), ViewTargetError> {
+        let renderable = |format| {
+            gpu.adapter()
+                .get_texture_format_features(format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        };
+        if !self.color_format.has_color_aspect() || !renderable(self.color_format) {
+            return Err(ViewTargetError::ColorFormat(self.color_format));
+        }
+        if let Some((_, format)) = self.depth {
+            if !format.has_depth_aspect() || !renderable(format) {
+                return Err(ViewTargetError::DepthFormat(format));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`ViewTarget`] can't be rendered to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ViewTargetError {
+    /// The color format isn't a color format the adapter can render to
+    ColorFormat(wgpu::TextureFormat),
+    /// The depth format isn't a depth format the adapter can render to
+    DepthFormat(wgpu::TextureFormat),
+}
+
+impl std::fmt::Display for ViewTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ViewTargetError::ColorFormat(format) => {
+                write!(f, "Can't render color into a view of format {format:?}")
+            }
+            ViewTargetError::DepthFormat(format) => {
+                write!(f, "Can't render depth into a view of format {format:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ViewTargetError {}
+
+/// Stretches a depth texture over a depth target of another size and
+/// format.
+pub(crate) struct DepthCopy {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    // Built for the most recent target format
+    pipeline: Option<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl DepthCopy {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("embed:depth_shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("embed.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("embed:depth_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("embed:depth_pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline: None,
+        }
+    }
+    /// Clears `target` and then, if `source` is given, stretches its
+    /// depth over it.
+    pub(crate) fn copy(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        source: Option<&wgpu::Texture>,
+        target: &wgpu::TextureView,
+        target_format: wgpu::TextureFormat,
+    ) {
+        if self.pipeline.as_ref().map(|(format, _)| *format) != Some(target_format) {
+            self.pipeline = Some((target_format, self.create_pipeline(gpu, target_format)));
+        }
+        let bind_group = source.map(|source| {
+            let view = source.create_view(&wgpu::TextureViewDescriptor {
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+            gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("embed:depth_bg"),
+                layout: &self.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            })
+        });
+        capture::begin_pass("embed:depth");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("embed:depth"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        if let (Some(bind_group), Some((_, pipeline))) =
+            (bind_group.as_ref(), self.pipeline.as_ref())
+        {
+            capture::set_pipeline(&mut rpass, pipeline, "embed:depth_pipeline");
+            capture::set_bind_group(&mut rpass, 0, bind_group, &[], "embed:depth_bg");
+            capture::draw(&mut rpass, 0..3, 0..1);
+        }
+    }
+    fn create_pipeline(&self, gpu: &WGPU, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("embed:depth_pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    targets: &[],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+    }
+}
@@ -0,0 +1,26 @@
+// Stretches the scene's depth over a depth target of another size.
+
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.uv = uv;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @builtin(frag_depth) f32 {
+  let size = vec2<f32>(textureDimensions(t_depth));
+  let p = vec2<i32>(min(in.uv * size, size - 1.0));
+  return textureLoad(t_depth, p, 0).r;
+}
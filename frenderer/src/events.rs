@@ -94,7 +94,7 @@ impl<T> FrendererEvents<T> for crate::Immediate {
 pub struct Driver {
     builder: winit::window::WindowBuilder,
     render_size: Option<(u32, u32)>,
-    logger: Box<dyn Logger>
+    logger: Box<dyn Logger>,
 }
 #[cfg(all(target_arch = "wasm32", feature = "winit"))]
 pub mod web_error {
@@ -121,18 +121,20 @@ impl std::task::Wake for NoopWaker {
 
 impl Driver {
     /// Create a [`Driver`] with the given window builder and render target size (if absent, will use the window's inner size instead).
-    pub fn new(builder: winit::window::WindowBuilder, render_size: Option<(u32, u32)>) -> Self 
-    {
+    pub fn new(builder: winit::window::WindowBuilder, render_size: Option<(u32, u32)>) -> Self {
         Self {
             builder,
             render_size,
-            logger: Box::new(EnvLogger{})
+            logger: Box::new(EnvLogger {}),
         }
     }
-    /// Create a [`Driver`] with the given window builder and render target size (if absent, will use the window's inner size instead). 
+    /// Create a [`Driver`] with the given window builder and render target size (if absent, will use the window's inner size instead).
     /// Allows for a custom logging to be externally defined for Frenderer
-    pub fn new_with_logger<L: Logger + 'static>(builder: winit::window::WindowBuilder, render_size: Option<(u32, u32)>, logger: L) -> Self 
-    {
+    pub fn new_with_logger<L: Logger + 'static>(
+        builder: winit::window::WindowBuilder,
+        render_size: Option<(u32, u32)>,
+        logger: L,
+    ) -> Self {
         Self {
             builder,
             render_size,
@@ -198,7 +200,6 @@ impl Driver {
         logger.prepare_logging()?;
         let event_loop: EventLoop<T> =
             winit::event_loop::EventLoopBuilder::with_user_event().build()?;
-        let instance = Arc::new(wgpu::Instance::default());
         let waker = Arc::new(NoopWaker()).into();
         let mut init_cb = Some(init_cb);
         let driver_state = std::cell::Cell::new(DriverState::WaitingForResume(builder));
@@ -209,17 +210,23 @@ impl Driver {
                     if let winit::event::Event::Resumed = event {
                         let window = Arc::new(builder.build(target).unwrap());
                         prepare_window(&window);
-                        let surface = instance.create_surface(Arc::clone(&window)).unwrap();
                         let wsz = window.inner_size();
                         let sz = render_size.unwrap_or((wsz.width, wsz.height));
-                        let future = Box::pin(crate::Renderer::with_surface(
-                            sz.0,
-                            sz.1,
-                            wsz.width,
-                            wsz.height,
-                            Arc::clone(&instance),
-                            Some(surface),
-                        ));
+                        let surface_window = Arc::clone(&window);
+                        let future = Box::pin(async move {
+                            // The surface can only be made once the backend is chosen
+                            let instance = crate::WGPU::detect_instance().await;
+                            let surface = instance.create_surface(surface_window)?;
+                            crate::Renderer::with_surface(
+                                sz.0,
+                                sz.1,
+                                wsz.width,
+                                wsz.height,
+                                instance,
+                                Some(surface),
+                            )
+                            .await
+                        });
                         DriverState::PollingFuture(window, future)
                     } else {
                         DriverState::WaitingForResume(builder)
@@ -256,6 +263,115 @@ impl Driver {
             Ok(event_loop.spawn(cb))
         }
     }
+    /// Kick off the event loop and run a fixed-timestep game in it,
+    /// as in [`Driver::run_event_loop`] but without handling winit
+    /// events yourself.  Once frenderer is initialized, `init_cb`
+    /// creates the game's state.  Then, every frame, `update` is
+    /// called as many times as `clock` says to simulate (with the
+    /// clock's timestep) and `render` once, with the fraction of a
+    /// timestep since the last update (see
+    /// [`crate::clock::Clock::interpolation`]); frenderer renders
+    /// afterwards.  `update` returns whether the game should keep
+    /// running, and closing the window quits too.
+    ///
+    /// The driver resizes the surface along with the window
+    /// (including when its scale factor changes), feeds events to an
+    /// [`crate::input::Input`], and stops rendering while the window
+    /// is occluded, restarting the clock when it's visible again so
+    /// the game doesn't try to catch up on the time it was hidden.
+    /// Frames the surface can't provide are skipped (see
+    /// [`crate::Renderer::try_render`]); it only panics if it runs out
+    /// of memory.
+    pub fn run_fixed_timestep<U: 'static>(
+        self,
+        clock: crate::clock::Clock,
+        init_cb: impl FnOnce(std::sync::Arc<winit::window::Window>, &mut crate::Renderer) -> U + 'static,
+        mut update: impl FnMut(&mut U, &mut crate::Renderer, &crate::input::Input, f32) -> bool
+            + 'static,
+        mut render: impl FnMut(&mut U, &mut crate::Renderer, f32) + 'static,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        struct FixedTimestep<U> {
+            window: Arc<winit::window::Window>,
+            frend: crate::Renderer,
+            clock: crate::clock::Clock,
+            input: crate::input::Input,
+            occluded: bool,
+            game: U,
+        }
+        self.run_event_loop::<(), _>(
+            move |window, mut frend| {
+                let game = init_cb(Arc::clone(&window), &mut frend);
+                FixedTimestep {
+                    window,
+                    frend,
+                    clock,
+                    input: crate::input::Input::default(),
+                    occluded: false,
+                    game,
+                }
+            },
+            move |event, target, state| {
+                use winit::event::{Event, WindowEvent};
+                match &event {
+                    Event::WindowEvent {
+                        event: WindowEvent::Occluded(occluded),
+                        ..
+                    } => {
+                        state.occluded = *occluded;
+                        if !state.occluded {
+                            state.clock.set_now(crate::clock::Instant::now());
+                            state.window.request_redraw();
+                        }
+                        return;
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::ScaleFactorChanged { .. },
+                        ..
+                    } => {
+                        if !state.frend.gpu.is_web() {
+                            let size = state.window.inner_size();
+                            state.frend.resize_surface(size.width, size.height);
+                        }
+                        state.window.request_redraw();
+                        return;
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::RedrawRequested,
+                        ..
+                    } if state.occluded => return,
+                    _ => (),
+                }
+                match state.frend.handle_event(
+                    &mut state.clock,
+                    &state.window,
+                    &event,
+                    target,
+                    &mut state.input,
+                ) {
+                    EventPhase::Run(steps) => {
+                        let dt = state.clock.dt();
+                        for _ in 0..steps {
+                            if !update(&mut state.game, &mut state.frend, &state.input, dt) {
+                                target.exit();
+                                return;
+                            }
+                            state.input.next_frame();
+                        }
+                        let alpha = state.clock.interpolation();
+                        render(&mut state.game, &mut state.frend, alpha);
+                        // Frames the surface can't provide (e.g. while
+                        // the window is being minimized) are skipped
+                        if let Err(err @ crate::RenderError::OutOfMemory) = state.frend.try_render()
+                        {
+                            panic!("Failed to acquire next swap chain texture: {err}");
+                        }
+                    }
+                    EventPhase::Quit => target.exit(),
+                    EventPhase::Wait => (),
+                }
+            },
+        )
+    }
 }
 
 /// If you don't use [`Driver`], it may still be convenient to call
@@ -284,7 +400,7 @@ pub fn prepare_window(window: &winit::window::Window) {
 }
 
 /// A trait used to allow for users to define custom logging procedures
-pub trait Logger{
+pub trait Logger {
     fn prepare_logging(&self) -> Result<(), Box<dyn std::error::Error>>;
 }
 
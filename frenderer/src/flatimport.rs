@@ -0,0 +1,586 @@
+//! Importing colored meshes for the [`crate::meshes::FlatRenderer`].
+//!
+//! [`read_ply`] reads PLY files (ASCII or binary) with optional
+//! per-vertex colors and normals, which is what tools like Blender,
+//! MeshLab, and MagicaVoxel export for vertex-painted models.  Its
+//! material color table has a single white entry, so vertex colors
+//! show through unchanged.
+//!
+//! [`read_obj`] reads Wavefront OBJ files along with their MTL
+//! material libraries, using each material's diffuse color (`Kd`)
+//! and opacity (`d` or `Tr`) for the material color table.  Each
+//! object or group in the file becomes one mesh with a single
+//! submesh; vertex colors (as `v x y z r g b`) and normals are kept
+//! too.
+//!
+//! Either way the result is a [`FlatAsset`], whose fields can be
+//! passed to [`crate::Renderer::flat_group_add`] (or returned from
+//! [`crate::scene::SceneAssets::flat`]).  Every mesh starts with one
+//! instance; change `instance_count` on its [`MeshEntry`] before
+//! adding the group to allocate more.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, Read},
+    path::Path,
+};
+
+use crate::meshes::{FlatVertex, MeshEntry, SubmeshEntry};
+use crate::scene::FlatAsset;
+
+/// The most material colors a flat mesh group can have.
+const MAX_MATERIALS: usize = 256;
+
+/// Errors from importing a mesh.
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    /// A line (counting from 1) couldn't be understood; binary PLY
+    /// data is reported as line 0
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The file uses more than 256 materials, the most a flat mesh
+    /// group supports
+    TooManyMaterials(usize),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "Couldn't read mesh: {err}"),
+            ImportError::Parse { line, message } => {
+                write!(f, "Invalid mesh at line {line}: {message}")
+            }
+            ImportError::TooManyMaterials(count) => {
+                write!(
+                    f,
+                    "Mesh uses {count} materials, but at most {MAX_MATERIALS} are supported"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> ImportError {
+    ImportError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Reads a PLY file from the given path (see [`read_ply`]).
+pub fn load_ply(path: impl AsRef<Path>) -> Result<FlatAsset, ImportError> {
+    read_ply(std::io::BufReader::new(std::fs::File::open(path)?))
+}
+
+/// Reads an OBJ file from the given path, loading material libraries
+/// relative to the directory it's in (see [`read_obj`]).
+pub fn load_obj(path: impl AsRef<Path>) -> Result<FlatAsset, ImportError> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    read_obj(
+        std::io::BufReader::new(std::fs::File::open(path)?),
+        |mtllib| {
+            Ok(std::io::BufReader::new(std::fs::File::open(
+                dir.join(mtllib),
+            )?))
+        },
+    )
+}
+
+/// Adds a polygon's triangles to `indices` as a fan around its first corner.
+fn fan_triangulate(polygon: &[u32], indices: &mut Vec<u32>) {
+    for i in 1..polygon.len().saturating_sub(1) {
+        indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+    }
+}
+
+/// A mesh with one instance and one submesh drawing the given range of indices.
+fn single_mesh(indices: std::ops::Range<usize>) -> MeshEntry {
+    MeshEntry {
+        instance_count: 1,
+        submeshes: vec![SubmeshEntry {
+            indices: indices.start as u32..indices.end as u32,
+            vertex_base: 0,
+            layer: None,
+        }],
+    }
+}
+
+// PLY
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+    /// The value which means full intensity for colors of this type
+    fn color_scale(self) -> f64 {
+        match self {
+            PlyType::I8 => i8::MAX as f64,
+            PlyType::U8 => u8::MAX as f64,
+            PlyType::I16 => i16::MAX as f64,
+            PlyType::U16 => u16::MAX as f64,
+            PlyType::I32 => i32::MAX as f64,
+            PlyType::U32 => u32::MAX as f64,
+            PlyType::F32 | PlyType::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PlyProperty {
+    Scalar(PlyType, String),
+    List(PlyType, PlyType, String),
+}
+
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Reads the scalar values of a PLY body in either format
+enum PlyValues<R> {
+    Ascii {
+        tokens: std::vec::IntoIter<(usize, String)>,
+    },
+    Binary {
+        reader: R,
+        big_endian: bool,
+    },
+}
+
+impl<R: Read> PlyValues<R> {
+    fn next(&mut self, ty: PlyType) -> Result<f64, ImportError> {
+        match self {
+            PlyValues::Ascii { tokens } => {
+                let (line, token) = tokens
+                    .next()
+                    .ok_or_else(|| parse_error(0, "unexpected end of data"))?;
+                token
+                    .parse()
+                    .map_err(|_| parse_error(line, format!("invalid value {token:?}")))
+            }
+            PlyValues::Binary { reader, big_endian } => {
+                let mut bytes = [0_u8; 8];
+                let size = ty.size();
+                reader.read_exact(&mut bytes[..size]).map_err(|err| {
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof {
+                        parse_error(0, "unexpected end of data")
+                    } else {
+                        ImportError::Io(err)
+                    }
+                })?;
+                if *big_endian {
+                    bytes[..size].reverse();
+                }
+                let word: [u8; 4] = bytes[..4].try_into().unwrap();
+                Ok(match ty {
+                    PlyType::I8 => bytes[0] as i8 as f64,
+                    PlyType::U8 => bytes[0] as f64,
+                    PlyType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    PlyType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    PlyType::I32 => i32::from_le_bytes(word) as f64,
+                    PlyType::U32 => u32::from_le_bytes(word) as f64,
+                    PlyType::F32 => f32::from_le_bytes(word) as f64,
+                    PlyType::F64 => f64::from_le_bytes(bytes),
+                })
+            }
+        }
+    }
+}
+
+/// Reads a PLY file's vertices (with `x`, `y`, and `z` properties
+/// and optionally `nx`, `ny`, `nz` normals and `red`, `green`,
+/// `blue`, `alpha` colors) and faces (with a `vertex_indices` or
+/// `vertex_index` list, triangulated as fans) into a single mesh.
+/// Other elements and properties are skipped.
+pub fn read_ply(mut r: impl BufRead) -> Result<FlatAsset, ImportError> {
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = vec![];
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(parse_error(line_number, "missing end_header"));
+        }
+        line_number += 1;
+        let mut fields = line.split_whitespace();
+        let keyword = fields.next().unwrap_or("");
+        if line_number == 1 {
+            if keyword != "ply" {
+                return Err(parse_error(line_number, "not a PLY file"));
+            }
+            continue;
+        }
+        match keyword {
+            "format" => {
+                format = Some(match fields.next() {
+                    Some("ascii") => PlyFormat::Ascii,
+                    Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                    Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                    other => {
+                        return Err(parse_error(
+                            line_number,
+                            format!("unknown format {other:?}"),
+                        ))
+                    }
+                })
+            }
+            "element" => {
+                let (Some(name), Some(count)) = (fields.next(), fields.next()) else {
+                    return Err(parse_error(line_number, "incomplete element"));
+                };
+                let count = count
+                    .parse()
+                    .map_err(|_| parse_error(line_number, format!("invalid count {count:?}")))?;
+                elements.push(PlyElement {
+                    name: name.to_string(),
+                    count,
+                    properties: vec![],
+                });
+            }
+            "property" => {
+                let Some(element) = elements.last_mut() else {
+                    return Err(parse_error(line_number, "property outside of an element"));
+                };
+                let ty = |name: Option<&str>| {
+                    name.and_then(PlyType::parse).ok_or_else(|| {
+                        parse_error(line_number, format!("unknown property type {name:?}"))
+                    })
+                };
+                let property = match fields.next() {
+                    Some("list") => {
+                        let count_ty = ty(fields.next())?;
+                        let item_ty = ty(fields.next())?;
+                        PlyProperty::List(count_ty, item_ty, fields.next().unwrap_or("").into())
+                    }
+                    name => PlyProperty::Scalar(ty(name)?, fields.next().unwrap_or("").into()),
+                };
+                element.properties.push(property);
+            }
+            "end_header" => break,
+            // comment, obj_info, and anything else
+            _ => (),
+        }
+    }
+    let format = format.ok_or_else(|| parse_error(line_number, "missing format"))?;
+    let mut values = match format {
+        PlyFormat::Ascii => {
+            let mut body = String::new();
+            r.read_to_string(&mut body)?;
+            let tokens: Vec<(usize, String)> = body
+                .lines()
+                .enumerate()
+                .flat_map(|(i, l)| {
+                    l.split_whitespace()
+                        .map(move |token| (line_number + i + 1, token.to_string()))
+                })
+                .collect();
+            PlyValues::Ascii {
+                tokens: tokens.into_iter(),
+            }
+        }
+        PlyFormat::BinaryLittleEndian | PlyFormat::BinaryBigEndian => PlyValues::Binary {
+            reader: r,
+            big_endian: format == PlyFormat::BinaryBigEndian,
+        },
+    };
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut polygon = vec![];
+    for element in elements.iter() {
+        for _ in 0..element.count {
+            let mut position = [0.0_f32; 3];
+            let mut normal = None::<[f32; 3]>;
+            let mut color = None::<[f32; 4]>;
+            polygon.clear();
+            for property in element.properties.iter() {
+                match property {
+                    PlyProperty::Scalar(ty, name) => {
+                        let value = values.next(*ty)?;
+                        if element.name != "vertex" {
+                            continue;
+                        }
+                        let color_channel = |color: &mut Option<[f32; 4]>, channel: usize| {
+                            color.get_or_insert([1.0; 4])[channel] =
+                                (value / ty.color_scale()) as f32;
+                        };
+                        match name.as_str() {
+                            "x" => position[0] = value as f32,
+                            "y" => position[1] = value as f32,
+                            "z" => position[2] = value as f32,
+                            "nx" => normal.get_or_insert([0.0; 3])[0] = value as f32,
+                            "ny" => normal.get_or_insert([0.0; 3])[1] = value as f32,
+                            "nz" => normal.get_or_insert([0.0; 3])[2] = value as f32,
+                            "red" | "diffuse_red" => color_channel(&mut color, 0),
+                            "green" | "diffuse_green" => color_channel(&mut color, 1),
+                            "blue" | "diffuse_blue" => color_channel(&mut color, 2),
+                            "alpha" => color_channel(&mut color, 3),
+                            _ => (),
+                        }
+                    }
+                    PlyProperty::List(count_ty, item_ty, name) => {
+                        let count = values.next(*count_ty)? as usize;
+                        let is_face = element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index");
+                        for _ in 0..count {
+                            let index = values.next(*item_ty)?;
+                            if is_face {
+                                polygon.push(index as u32);
+                            }
+                        }
+                    }
+                }
+            }
+            match element.name.as_str() {
+                "vertex" => {
+                    let vertex = FlatVertex::with_color(position, 0, color.unwrap_or([1.0; 4]));
+                    vertices.push(match normal {
+                        Some(normal) => vertex.with_normal(normal),
+                        None => vertex,
+                    });
+                }
+                "face" => fan_triangulate(&polygon, &mut indices),
+                _ => (),
+            }
+        }
+    }
+    if let Some(&bad) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+        return Err(parse_error(
+            0,
+            format!("face uses vertex {bad}, but there are {}", vertices.len()),
+        ));
+    }
+    Ok(FlatAsset {
+        material_colors: vec![[1.0; 4]],
+        meshes: vec![single_mesh(0..indices.len())],
+        vertices,
+        indices,
+    })
+}
+
+// OBJ
+
+/// Reads an MTL material library's diffuse colors and opacities,
+/// adding to the given names and colors.
+fn read_mtl(
+    r: impl BufRead,
+    names: &mut HashMap<String, u32>,
+    colors: &mut Vec<[f32; 4]>,
+) -> Result<(), ImportError> {
+    let mut current = None;
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        let line_number = i + 1;
+        let mut fields = line.split_whitespace();
+        let floats = |fields: std::str::SplitWhitespace| -> Result<Vec<f32>, ImportError> {
+            fields
+                .map(|f| {
+                    f.parse()
+                        .map_err(|_| parse_error(line_number, format!("invalid number {f:?}")))
+                })
+                .collect()
+        };
+        match fields.next() {
+            Some("newmtl") => {
+                let name = fields.collect::<Vec<_>>().join(" ");
+                let which = *names.entry(name).or_insert_with(|| {
+                    colors.push([1.0; 4]);
+                    colors.len() as u32 - 1
+                });
+                current = Some(which as usize);
+            }
+            Some("Kd") => {
+                if let (Some(which), [r, g, b, ..]) = (current, floats(fields)?.as_slice()) {
+                    let alpha = colors[which][3];
+                    colors[which] = [*r, *g, *b, alpha];
+                }
+            }
+            Some("d") => {
+                if let (Some(which), [d, ..]) = (current, floats(fields)?.as_slice()) {
+                    colors[which][3] = *d;
+                }
+            }
+            Some("Tr") => {
+                if let (Some(which), [tr, ..]) = (current, floats(fields)?.as_slice()) {
+                    colors[which][3] = 1.0 - *tr;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Reads an OBJ file's vertices (with optional `r g b` vertex colors
+/// after the position), normals, and faces (triangulated as fans),
+/// along with the material libraries it names, which are opened by
+/// calling `mtl` with their names.  A new mesh starts at each `o` or
+/// `g` line.  Faces with no material or a material missing from the
+/// libraries are white.  Texture coordinates, lines, and points are
+/// skipped.
+pub fn read_obj<R: BufRead>(
+    r: impl BufRead,
+    mut mtl: impl FnMut(&str) -> std::io::Result<R>,
+) -> Result<FlatAsset, ImportError> {
+    let mut positions: Vec<([f32; 3], Option<[f32; 4]>)> = vec![];
+    let mut normals: Vec<[f32; 3]> = vec![];
+    let mut material_names: HashMap<String, u32> = HashMap::new();
+    let mut material_colors: Vec<[f32; 4]> = vec![];
+    // The white material used by faces without a known material
+    let mut default_material = None;
+    let mut material = None;
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut meshes = vec![];
+    let mut mesh_start = 0;
+    // Each distinct position, normal, and material becomes a vertex
+    let mut vertex_ids: HashMap<(usize, Option<usize>, u32), u32> = HashMap::new();
+    let mut polygon = vec![];
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        let line_number = i + 1;
+        let mut fields = line.split_whitespace();
+        let floats = |fields: std::str::SplitWhitespace| -> Result<Vec<f32>, ImportError> {
+            fields
+                .map(|f| {
+                    f.parse()
+                        .map_err(|_| parse_error(line_number, format!("invalid number {f:?}")))
+                })
+                .collect()
+        };
+        match fields.next() {
+            Some("v") => match floats(fields)?.as_slice() {
+                [x, y, z, r, g, b, ..] => positions.push(([*x, *y, *z], Some([*r, *g, *b, 1.0]))),
+                [x, y, z, ..] => positions.push(([*x, *y, *z], None)),
+                _ => return Err(parse_error(line_number, "vertex needs three coordinates")),
+            },
+            Some("vn") => match floats(fields)?.as_slice() {
+                [x, y, z, ..] => normals.push([*x, *y, *z]),
+                _ => return Err(parse_error(line_number, "normal needs three coordinates")),
+            },
+            Some("mtllib") => {
+                let name = fields.collect::<Vec<_>>().join(" ");
+                read_mtl(mtl(&name)?, &mut material_names, &mut material_colors)?;
+            }
+            Some("usemtl") => {
+                let name = fields.collect::<Vec<_>>().join(" ");
+                material = material_names.get(&name).copied();
+            }
+            // leading `o` or `g` lines don't make empty meshes
+            Some("o") | Some("g") if indices.len() > mesh_start => {
+                meshes.push(single_mesh(mesh_start..indices.len()));
+                mesh_start = indices.len();
+            }
+            Some("f") => {
+                let which = match material {
+                    Some(which) => which,
+                    None => *default_material.get_or_insert_with(|| {
+                        material_colors.push([1.0; 4]);
+                        material_colors.len() as u32 - 1
+                    }),
+                };
+                // OBJ indices count from 1, or back from the end if negative
+                let resolve = |index: &str, len: usize| -> Result<usize, ImportError> {
+                    let bad = || parse_error(line_number, format!("invalid index {index:?}"));
+                    let index: isize = index.parse().map_err(|_| bad())?;
+                    let resolved = if index < 0 {
+                        len as isize + index
+                    } else {
+                        index - 1
+                    };
+                    if resolved < 0 || resolved as usize >= len {
+                        return Err(bad());
+                    }
+                    Ok(resolved as usize)
+                };
+                polygon.clear();
+                for corner in fields {
+                    let mut parts = corner.split('/');
+                    let position = resolve(parts.next().unwrap_or(""), positions.len())?;
+                    let normal = match parts.nth(1) {
+                        Some(n) if !n.is_empty() => Some(resolve(n, normals.len())?),
+                        _ => None,
+                    };
+                    let id = *vertex_ids
+                        .entry((position, normal, which))
+                        .or_insert_with(|| {
+                            let (pos, color) = positions[position];
+                            let vertex =
+                                FlatVertex::with_color(pos, which, color.unwrap_or([1.0; 4]));
+                            vertices.push(match normal {
+                                Some(n) => vertex.with_normal(normals[n]),
+                                None => vertex,
+                            });
+                            vertices.len() as u32 - 1
+                        });
+                    polygon.push(id);
+                }
+                fan_triangulate(&polygon, &mut indices);
+            }
+            _ => (),
+        }
+    }
+    if indices.len() > mesh_start {
+        meshes.push(single_mesh(mesh_start..indices.len()));
+    }
+    if material_colors.len() > MAX_MATERIALS {
+        return Err(ImportError::TooManyMaterials(material_colors.len()));
+    }
+    Ok(FlatAsset {
+        material_colors,
+        vertices,
+        indices,
+        meshes,
+    })
+}
@@ -0,0 +1,441 @@
+//! Froxel-based volumetric fog for the 3D renderers.
+//!
+//! [`FogRenderer`] divides the view frustum into a small grid of
+//! "froxels" (frustum voxels), stored as the layers of a 2D array
+//! texture, one layer per depth slice.  Each froxel holds the light
+//! scattered toward the camera and the fraction of light let through
+//! by all the fog between the camera and the far end of its slice,
+//! which is accumulated by marching through the slices in front of
+//! it.  The froxel grid only depends on the [`Fog`] and the camera,
+//! so it's only recomputed when either changes.  Applying the fog
+//! then looks up each pixel's distance from the depth texture and
+//! blends the matching froxel over the scene.
+//!
+//! Fog is made of a global height fog, which thins out above a given
+//! height, plus any number (up to [`Fog::MAX_VOLUMES`]) of local
+//! box-shaped [`FogVolume`]s.
+
+use std::borrow::Cow;
+
+use crate::{capture, meshes::Camera3D, WGPU};
+
+/// The horizontal resolution of the froxel grid.
+const FROXELS_X: u32 = 160;
+/// The vertical resolution of the froxel grid.
+const FROXELS_Y: u32 = 90;
+/// How many depth slices the froxel grid has.
+const SLICES: u32 = 64;
+const FROXEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// A box-shaped region of extra fog, e.g. mist over a lake.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FogVolume {
+    /// The center of the box in world space
+    pub center: [f32; 3],
+    /// Half the box's size along each axis
+    pub half_extents: [f32; 3],
+    /// The fog's density (its extinction per world unit) inside the box
+    pub density: f32,
+    /// The color of light scattered by the fog
+    pub color: [f32; 3],
+    /// How much of the box, from 0.0 (none) to 1.0 (all the way to
+    /// the center), fades out toward its faces
+    pub softness: f32,
+}
+
+/// The appearance of volumetric fog.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fog {
+    /// The color of light scattered by the global fog
+    pub color: [f32; 3],
+    /// The global fog's density (its extinction per world unit) below `height`
+    pub density: f32,
+    /// The height above which the global fog starts thinning out
+    pub height: f32,
+    /// How quickly the global fog thins out above `height`; 0.0 means uniform fog
+    pub height_falloff: f32,
+    /// How far from the camera fog is computed; farther geometry and
+    /// the background get the fog up to this distance
+    pub max_distance: f32,
+    /// Local fog volumes (only the first [`Fog::MAX_VOLUMES`] are used)
+    pub volumes: Vec<FogVolume>,
+}
+
+impl Fog {
+    /// The largest supported number of fog volumes.
+    pub const MAX_VOLUMES: usize = 8;
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            color: [0.7, 0.75, 0.8],
+            density: 0.02,
+            height: 0.0,
+            height_falloff: 0.5,
+            max_distance: 100.0,
+            volumes: vec![],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct FogUniforms {
+    inv_projview: [f32; 16],
+    eye: [f32; 4],
+    color_density: [f32; 4],
+    height: [f32; 4],
+    volumes: [[[f32; 4]; 3]; Fog::MAX_VOLUMES],
+}
+
+/// Draws volumetric [`Fog`] over a scene seen from a [`Camera3D`].
+pub struct FogRenderer {
+    apply_pipeline: wgpu::RenderPipeline,
+    froxel_pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    froxel_bind_group: wgpu::BindGroup,
+    apply_bind_group_layout: wgpu::BindGroupLayout,
+    // Only present once a depth texture has been given
+    apply_bind_group: Option<wgpu::BindGroup>,
+    froxel_texture: wgpu::Texture,
+    froxel_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    fog: Fog,
+    camera: Camera3D,
+    // Set when the froxel grid needs to be recomputed
+    dirty: bool,
+}
+
+impl FogRenderer {
+    /// Creates a new `FogRenderer` meant to draw over color targets
+    /// of the given format.  Call [`FogRenderer::set_depth`] before
+    /// rendering.
+    pub fn new(gpu: &WGPU, color_format: wgpu::TextureFormat, fog: Fog, camera: Camera3D) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("fog:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("fog.wgsl"))),
+            });
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FogUniforms>() as u64),
+            },
+            count: None,
+        };
+        let froxel_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("fog:froxel_bgl"),
+                    entries: &[uniform_entry],
+                });
+        let apply_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("fog:apply_bgl"),
+                    entries: &[
+                        uniform_entry,
+                        // The froxel grid
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        // The scene's depth
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let froxel_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &froxel_bind_group_layout,
+            "fs_froxel",
+            FROXEL_FORMAT.into(),
+        );
+        // The fog's alpha is the scene's transmittance, so the result is scene * a + rgb
+        let apply_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &apply_bind_group_layout,
+            "fs_apply",
+            wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::SrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+        );
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("fog:uniforms"),
+            size: std::mem::size_of::<FogUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let froxel_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog:froxel_bg"),
+            layout: &froxel_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let froxel_texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("fog:froxels"),
+            size: wgpu::Extent3d {
+                width: FROXELS_X,
+                height: FROXELS_Y,
+                depth_or_array_layers: SLICES,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FROXEL_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let froxel_view = froxel_texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("fog:sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            apply_pipeline,
+            froxel_pipeline,
+            uniform_buffer,
+            froxel_bind_group,
+            apply_bind_group_layout,
+            apply_bind_group: None,
+            froxel_texture,
+            froxel_view,
+            sampler,
+            fog,
+            camera,
+            dirty: true,
+        }
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        fs_entry: &str,
+        color_target: wgpu::ColorTargetState,
+    ) -> wgpu::RenderPipeline {
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("fog:pipeline_layout"),
+                    bind_group_layouts: &[bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(fs_entry),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: fs_entry,
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+    }
+    fn upload(&self, gpu: &WGPU) {
+        let fog = &self.fog;
+        let volumes = &fog.volumes[..fog.volumes.len().min(Fog::MAX_VOLUMES)];
+        let mut uniforms = FogUniforms {
+            inv_projview: *self.camera.projection_view().inversed().as_array(),
+            eye: [
+                self.camera.translation[0],
+                self.camera.translation[1],
+                self.camera.translation[2],
+                fog.max_distance.max(f32::EPSILON),
+            ],
+            color_density: [fog.color[0], fog.color[1], fog.color[2], fog.density],
+            height: [
+                fog.height,
+                fog.height_falloff,
+                SLICES as f32,
+                volumes.len() as f32,
+            ],
+            volumes: [[[0.0; 4]; 3]; Fog::MAX_VOLUMES],
+        };
+        for (data, volume) in uniforms.volumes.iter_mut().zip(volumes) {
+            let [x, y, z] = volume.center;
+            let [hx, hy, hz] = volume.half_extents;
+            let [r, g, b] = volume.color;
+            *data = [
+                [x, y, z, volume.density],
+                // keep the edge's smoothstep well-defined
+                [hx, hy, hz, 1.0 - volume.softness.clamp(0.001, 1.0)],
+                [r, g, b, 1.0],
+            ];
+        }
+        gpu.queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    /// Returns the fog's current appearance.
+    pub fn fog(&self) -> &Fog {
+        &self.fog
+    }
+    /// Changes the fog's appearance.
+    pub fn set_fog(&mut self, fog: Fog) {
+        self.fog = fog;
+        self.dirty = true;
+    }
+    /// Returns the camera the fog is seen from.
+    pub fn camera(&self) -> Camera3D {
+        self.camera
+    }
+    /// Sets the camera the fog is seen from; this should match the
+    /// camera of the meshes drawn alongside it.
+    pub fn set_camera(&mut self, camera: Camera3D) {
+        self.camera = camera;
+        self.dirty = true;
+    }
+    /// Sets the depth texture of the scene the fog is drawn over.
+    pub fn set_depth(&mut self, gpu: &WGPU, depth: &wgpu::Texture) {
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+        self.apply_bind_group = Some(gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog:apply_bg"),
+            layout: &self.apply_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&self.froxel_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+            ],
+        }));
+    }
+    /// Recomputes the froxel grid if the fog or camera changed, then
+    /// blends the fog over `color`, whose depth texture must have
+    /// been given to [`FogRenderer::set_depth`] and must not be in
+    /// use by another render pass.
+    pub fn render(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        color: &wgpu::TextureView,
+    ) {
+        let Some(apply_bind_group) = &self.apply_bind_group else {
+            return;
+        };
+        if self.dirty {
+            self.upload(gpu);
+            // Each slice is its own layer, so it needs its own pass
+            for slice in 0..SLICES {
+                let view = self
+                    .froxel_texture
+                    .create_view(&wgpu::TextureViewDescriptor {
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_array_layer: slice,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    });
+                capture::begin_pass("fog:froxels");
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("fog:froxels"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                });
+                capture::set_pipeline(&mut rpass, &self.froxel_pipeline, "fog:froxel_pipeline");
+                capture::set_bind_group(
+                    &mut rpass,
+                    0,
+                    &self.froxel_bind_group,
+                    &[],
+                    "fog:froxel_bind_group",
+                );
+                capture::draw(&mut rpass, slice * 6..slice * 6 + 6, 0..1);
+            }
+            self.dirty = false;
+        }
+        capture::begin_pass("fog:apply");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("fog:apply"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        capture::set_pipeline(&mut rpass, &self.apply_pipeline, "fog:apply_pipeline");
+        capture::set_bind_group(&mut rpass, 0, apply_bind_group, &[], "fog:apply_bind_group");
+        capture::draw(&mut rpass, 0..6, 0..1);
+    }
+}
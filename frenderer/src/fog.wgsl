@@ -0,0 +1,120 @@
+struct FogVolume {
+  // xyz: center, w: density
+  center_density: vec4<f32>,
+  // xyz: half extents, w: where the soft edge starts (as a fraction of the half extents)
+  half_extents: vec4<f32>,
+  color: vec4<f32>,
+}
+
+struct FogUniforms {
+  inv_projview: mat4x4<f32>,
+  // xyz: camera position, w: the distance covered by the froxel grid
+  eye: vec4<f32>,
+  // rgb: fog color, w: density
+  color_density: vec4<f32>,
+  // x: height where density starts falling off, y: falloff rate, z: slice count, w: volume count
+  height: vec4<f32>,
+  volumes: array<FogVolume, 8>,
+}
+
+@group(0) @binding(0)
+var<uniform> fog: FogUniforms;
+// Each layer holds the in-scattered light (rgb) and transmittance (a)
+// from the camera up to the far end of one depth slice
+@group(0) @binding(1)
+var t_froxels: texture_2d_array<f32>;
+@group(0) @binding(2)
+var s_froxels: sampler;
+@group(0) @binding(3)
+var t_depth: texture_2d<f32>;
+
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    vec2<f32>(-1., -1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(1., 1.)
+);
+
+struct FogVertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  // Which froxel slice is being drawn
+  @location(1) @interpolate(flat) slice: u32,
+}
+
+// Six vertices are drawn per slice, so the vertex index picks the slice
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> FogVertexOutput {
+  let p = VERTICES[in_vertex_index % 6u];
+  var out:FogVertexOutput;
+  out.clip_position = vec4<f32>(p, 0.0, 1.0);
+  out.uv = p * vec2<f32>(0.5, -0.5) + 0.5;
+  out.slice = in_vertex_index / 6u;
+  return out;
+}
+
+fn unproject(uv:vec2<f32>, depth:f32) -> vec3<f32> {
+  let world = fog.inv_projview * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+  return world.xyz / world.w;
+}
+
+// Slices get longer with distance
+fn slice_distance(k:f32) -> f32 {
+  let t = k / fog.height.z;
+  return fog.eye.w * t * t;
+}
+
+// Scattered color times density (rgb) and density (a) at a point
+fn density_at(p:vec3<f32>) -> vec4<f32> {
+  var sigma = fog.color_density.w * exp(-fog.height.y * max(p.y - fog.height.x, 0.0));
+  var color = fog.color_density.rgb * sigma;
+  for (var i = 0u; i < u32(fog.height.w); i++) {
+    let v = fog.volumes[i];
+    let d = abs(p - v.center_density.xyz) / max(v.half_extents.xyz, vec3<f32>(1e-4));
+    let edge = max(d.x, max(d.y, d.z));
+    let s = v.center_density.w * (1.0 - smoothstep(v.half_extents.w, 1.0, edge));
+    sigma += s;
+    color += v.color.rgb * s;
+  }
+  return vec4<f32>(color, sigma);
+}
+
+@fragment
+fn fs_froxel(in:FogVertexOutput) -> @location(0) vec4<f32> {
+  let dir = normalize(unproject(in.uv, 1.0) - fog.eye.xyz);
+  var scatter = vec3<f32>(0.0);
+  var transmittance = 1.0;
+  for (var j = 0u; j <= in.slice; j++) {
+    let d0 = slice_distance(f32(j));
+    let d1 = slice_distance(f32(j + 1u));
+    let m = density_at(fog.eye.xyz + dir * (d0 + d1) * 0.5);
+    let segment = exp(-m.w * (d1 - d0));
+    if m.w > 0.0 {
+      scatter += transmittance * (m.rgb / m.w) * (1.0 - segment);
+    }
+    transmittance *= segment;
+  }
+  return vec4<f32>(scatter, transmittance);
+}
+
+// Blended as scene * a + rgb
+@fragment
+fn fs_apply(in:FogVertexOutput) -> @location(0) vec4<f32> {
+  let size = vec2<i32>(textureDimensions(t_depth));
+  let p = clamp(vec2<i32>(in.uv * vec2<f32>(size)), vec2<i32>(0), size - vec2<i32>(1));
+  let depth = textureLoad(t_depth, p, 0).r;
+  var dist = fog.eye.w;
+  if depth < 1.0 {
+    dist = distance(unproject(in.uv, depth), fog.eye.xyz);
+  }
+  // Layer k holds the fog up to the far end of slice k
+  let slices = fog.height.z;
+  let k = sqrt(clamp(dist / fog.eye.w, 0.0, 1.0)) * slices - 1.0;
+  let k0 = floor(k);
+  let before = textureSampleLevel(t_froxels, s_froxels, in.uv, i32(max(k0, 0.0)), 0.0);
+  let after = textureSampleLevel(t_froxels, s_froxels, in.uv, i32(min(k0 + 1.0, slices - 1.0)), 0.0);
+  let a = select(before, vec4<f32>(0.0, 0.0, 0.0, 1.0), k0 < 0.0);
+  return mix(a, after, k - k0);
+}
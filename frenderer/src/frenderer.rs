@@ -11,13 +11,13 @@
 //!
 //! Besides managing the swapchain, [`Renderer`] also offers
 //! facilities for accessing the internal data of a sprite renderer, a
-//! textured unlit mesh renderer, and a flat-colored unlit mesh
-//! renderer, as well as a color postprocessing step.  Accesses to
-//! subsets of their data through [`Renderer`] are recorded for upload
-//! before rendering starts; so, any sprite transform data or mesh
-//! data accessed through [`Renderer`] will be marked for upload
-//! automatically.  This won't always be the most efficient strategy,
-//! but you can always create your own
+//! textured unlit mesh renderer, and a flat-colored mesh renderer
+//! with optional simple lighting, as well as a color postprocessing
+//! step.  Accesses to subsets of their data through [`Renderer`] are
+//! recorded for upload before rendering starts; so, any sprite
+//! transform data or mesh data accessed through [`Renderer`] will be
+//! marked for upload automatically.  This won't always be the most
+//! efficient strategy, but you can always create your own
 //! [`crate::sprites::SpriteRenderer`] for example and use your own
 //! scheme.
 //!
@@ -26,11 +26,40 @@
 //! crate.  It's just a convenience.
 
 use crate::{
-    colorgeo::{self, ColorGeo},
-    sprites::SpriteRenderer,
-    WGPU,
+    bloom::GlowPass,
+    capture::{self, FrameCapture},
+    colorgeo::{self, ColorGeo, HdrMode, HdrOutput, Tonemap},
+    debug_draw::DebugDraw,
+    deferred::{DeferredLighting, GBuffer},
+    embed::{DepthCopy, ViewTarget, ViewTargetError},
+    fog::{Fog, FogRenderer},
+    grid::{Grid, GridRenderer},
+    hierarchy::{self, Hierarchy, Target},
+    highlight::Highlight,
+    lighting2d::{Lighting2D, Lighting2DRenderer},
+    material::{Material, MaterialDesc, MaterialId},
+    mipmap::MipGenerator,
+    mirror::{Mirror, MirrorPass},
+    msaa::MsaaTargets,
+    outline::OutlinePass,
+    overlay::{DebugOverlay, FrameStats, GpuTimer},
+    palette::Palette,
+    particles::{EmitterDesc, ParticleCamera, ParticleRenderer},
+    pick::{DepthQuery, PickPass, PickQuery},
+    postprocess::{ColorVision, EffectId, MotionBlur, PostProcess},
+    replay::{MeshKind, Recorder, Recording},
+    scene::{LoadedScene, MeshGroupScene, Scene, SceneAssets, SceneGroup, SpriteGroupScene},
+    shadow::{PointLight, PointShadows, ShadowConfig, SunShadows},
+    skinned::SkinnedMeshRenderer,
+    sprites::{BlendMode, CoordinateMode, DepthMode, SpriteRenderer},
+    stereo::{Eye, Stereo, StereoPass},
+    target::RenderTarget,
+    tiles::TileMapRenderer,
+    velocity::VelocityPass,
+    BufferWriter, WGPU,
 };
 use std::{
+    collections::HashMap,
     ops::{Range, RangeBounds},
     sync::Arc,
 };
@@ -44,17 +73,148 @@ pub struct Renderer {
     render_height: u32,
     surface: Option<wgpu::Surface<'static>>,
     config: wgpu::SurfaceConfiguration,
-    depth_texture: wgpu::Texture,
-    depth_texture_view: wgpu::TextureView,
+    // None in 2D-only mode (see Renderer::set_depth_format)
+    depth_texture: Option<wgpu::Texture>,
+    depth_texture_view: Option<wgpu::TextureView>,
     color_texture: wgpu::Texture,
     color_texture_view: wgpu::TextureView,
+    // Only present while multisampling is on; the main pass draws
+    // into these and resolves into the color and depth textures
+    msaa: Option<MsaaTargets>,
+    render_pass_config: RenderPassConfig,
+    // When set, the scene is drawn at this size and scaled up by a
+    // whole number onto the surface
+    virtual_resolution: Option<(u32, u32)>,
     // These ones are tracked for auto uploading of assets and automatic rendering.
     // You can make your own renderers and use them for more control.
     sprites: SpriteRenderer,
     meshes: MeshRenderer,
     flats: FlatRenderer,
-    postprocess: ColorGeo,
+    postprocess: PostProcess,
+    colorgeo: ColorGeo,
+    outline: OutlinePass,
+    // A second outline pass with its own mask, for selection highlights
+    highlight: Option<(OutlinePass, Highlight)>,
+    grid: Option<GridRenderer>,
+    // Created the first time it's asked for
+    debug_draw: Option<DebugDraw>,
+    tilemaps: Vec<Option<TileMapRenderer>>,
+    // Created with the first skinned group
+    skinned: Option<SkinnedMeshRenderer>,
+    // Created with the first particle emitter
+    particles: Option<ParticleRenderer>,
+    // Created the first time mips are generated
+    mipmaps: Option<MipGenerator>,
+    // Nine-slice panels, by the sprite group that draws each one
+    panels: HashMap<usize, crate::nineslice::Panel>,
+    // Palettes, by the sprite group drawn with each one
+    palettes: HashMap<usize, Palette>,
+    sprite_states: HashMap<usize, Interpolation<crate::sprites::Transform>>,
+    mesh_states:
+        HashMap<(crate::meshes::MeshGroup, usize), Interpolation<crate::meshes::Transform3D>>,
+    flat_states:
+        HashMap<(crate::meshes::MeshGroup, usize), Interpolation<crate::meshes::Transform3D>>,
+    fog: Option<FogRenderer>,
+    lighting2d: Option<Lighting2DRenderer>,
+    // The stereo pass and each eye's mesh and flat camera views
+    stereo: Option<(StereoPass, [CameraViews; 2])>,
+    // The mirror pass and its reflected mesh and flat camera views
+    mirror: Option<(MirrorPass, CameraViews)>,
+    // Only present while point light shadows are on
+    point_shadows: Option<PointShadows>,
+    // Only present while the sun casts shadows
+    sun: Option<SunShadows>,
+    // Only present while deferred shading is on
+    deferred: Option<(GBuffer, DeferredLighting)>,
+    // Kept while deferred shading is off
+    deferred_lights: Vec<PointLight>,
+    pick: Option<PickPass>,
+    // Only present while motion blur is on
+    velocity: Option<VelocityPass>,
+    // Only present while bloom is on and some sprite group has an emissive layer
+    glow: Option<GlowPass>,
+    // Only present while the debug overlay is visible
+    overlay: Option<(DebugOverlay, Option<GpuTimer>)>,
+    frame_stats: FrameStats,
+    last_frame: Option<crate::clock::Instant>,
+    // Bytes uploaded by do_uploads since the last frame
+    upload_bytes: usize,
+    coalesced_bytes: usize,
+    // Instance uploads are written through this and submitted ahead
+    // of the frame, rather than each one being copied by the queue
+    staging_belt: wgpu::util::StagingBelt,
+    // Set by capture_next_frame until render records a frame
+    capture_next: bool,
+    last_capture: Option<FrameCapture>,
+    // Present while a recording is in progress
+    recorder: Option<Recorder>,
+    last_recording: Option<Recording>,
+    // Removed materials leave None behind
+    materials: Vec<Option<Material>>,
     queued_uploads: Vec<Upload>,
+    // At most this many bytes of instance data are uploaded per frame
+    upload_budget: Option<usize>,
+    // Uploads left over from earlier frames, at most one per mesh or sprite group
+    deferred_uploads: Vec<Upload>,
+    upload_priorities: HashMap<UploadGroup, i32>,
+    hdr_output: Option<HdrOutput>,
+    // Surface formats to pick from first, in order of preference
+    preferred_surface_formats: Vec<wgpu::TextureFormat>,
+    // Created the first time Renderer::render_to_view is given a depth view
+    depth_copy: Option<DepthCopy>,
+    hierarchy: Hierarchy,
+    // Set when the surface was resized to a zero extent (e.g. a minimized window)
+    surface_dormant: bool,
+}
+
+// The previous simulated state of a group's transforms, for
+// interpolating towards the current one (see Renderer::sprites_lerp)
+struct Interpolation<T> {
+    previous: Vec<T>,
+    // How far to interpolate the transforms drawn next, if at all
+    alpha: Option<f32>,
+    // Set while the GPU holds interpolated transforms instead of the
+    // simulated ones
+    drawn: bool,
+}
+
+impl<T> Default for Interpolation<T> {
+    fn default() -> Self {
+        Self {
+            previous: vec![],
+            alpha: None,
+            drawn: false,
+        }
+    }
+}
+
+impl<T: Copy> Interpolation<T> {
+    // Interpolates from the previous state to `current`; transforms
+    // added since the state was stored are left as they are
+    fn lerp(&self, current: &[T], alpha: f32, lerp: fn(&T, &T, f32) -> T) -> Vec<T> {
+        let mut drawn = current.to_vec();
+        for (trf, previous) in drawn.iter_mut().zip(&self.previous) {
+            *trf = lerp(previous, trf, alpha);
+        }
+        drawn
+    }
+}
+
+// Copies `new` over `old` and returns the runs of indices where they
+// differed
+fn write_changed<T: PartialEq + Copy>(old: &mut [T], new: &[T]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = vec![];
+    for (index, (old, new)) in old.iter_mut().zip(new).enumerate() {
+        if old == new {
+            continue;
+        }
+        *old = *new;
+        match runs.last_mut() {
+            Some(run) if run.end == index => run.end += 1,
+            _ => runs.push(index..index + 1),
+        }
+    }
+    runs
 }
 
 #[derive(Debug)]
@@ -64,10 +224,212 @@ enum Upload {
     Sprite(usize, Range<usize>),
 }
 
+impl Upload {
+    fn group(&self) -> UploadGroup {
+        match self {
+            Upload::Mesh(group, _, _) => UploadGroup::Meshes(*group),
+            Upload::Flat(group, _, _) => UploadGroup::Flats(*group),
+            Upload::Sprite(group, _) => UploadGroup::Sprites(*group),
+        }
+    }
+    // Which group and mesh (0 for sprites) this writes to
+    fn target(&self) -> (UploadGroup, usize) {
+        match self {
+            Upload::Mesh(_, mesh, _) | Upload::Flat(_, mesh, _) => (self.group(), *mesh),
+            Upload::Sprite(_, _) => (self.group(), 0),
+        }
+    }
+    fn range(&self) -> Range<usize> {
+        match self {
+            Upload::Mesh(_, _, r) | Upload::Flat(_, _, r) | Upload::Sprite(_, r) => r.clone(),
+        }
+    }
+    fn range_mut(&mut self) -> &mut Range<usize> {
+        match self {
+            Upload::Mesh(_, _, r) | Upload::Flat(_, _, r) | Upload::Sprite(_, r) => r,
+        }
+    }
+    fn bytes(&self) -> usize {
+        match self {
+            Upload::Mesh(_, _, r) | Upload::Flat(_, _, r) => {
+                r.len() * std::mem::size_of::<crate::meshes::Transform3D>()
+            }
+            Upload::Sprite(_, r) => {
+                r.len()
+                    * (std::mem::size_of::<crate::sprites::Transform>()
+                        + std::mem::size_of::<crate::sprites::SheetRegion>()
+                        + std::mem::size_of::<[u8; 4]>())
+            }
+        }
+    }
+}
+
+/// A sprite, mesh, or flat mesh group whose instance uploads can be
+/// prioritized with [`Renderer::upload_priority_set`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UploadGroup {
+    Sprites(usize),
+    Meshes(crate::meshes::MeshGroup),
+    Flats(crate::meshes::MeshGroup),
+}
+
+/// Presets trading input latency against throughput for
+/// [`Renderer::set_latency_mode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LatencyMode {
+    /// At most one frame queued, presented with
+    /// [`wgpu::PresentMode::Mailbox`] if the surface supports it so
+    /// that the newest frame is shown at the next vertical blank;
+    /// for rhythm and action games
+    LowLatency,
+    /// Two frames queued with vsync (the default)
+    Balanced,
+    /// Three frames queued with vsync, so that the CPU and GPU stall
+    /// on each other less, at the cost of a frame of latency
+    Throughput,
+}
+
+/// Camera views to draw each built-in renderer with in
+/// [`Renderer::render_into_with_views`], e.g. for a minimap or split
+/// screen; `None` keeps that renderer's usual cameras.  Views are
+/// created with [`Renderer::sprite_add_camera_view`],
+/// [`Renderer::mesh_add_camera_view`], and
+/// [`Renderer::flat_add_camera_view`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CameraViews {
+    pub sprites: Option<usize>,
+    pub meshes: Option<usize>,
+    pub flats: Option<usize>,
+}
+
+/// Which groups of each built-in renderer to draw in
+/// [`Renderer::render_into_selected`], e.g. only the groups tagged
+/// as shadow casters for a shadow pass.  The default draws
+/// everything, like [`Renderer::render_into`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderSelection {
+    pub sprites: crate::GroupSelection,
+    pub meshes: crate::GroupSelection,
+    pub flats: crate::GroupSelection,
+    /// Whether to draw the grid (if there is one).
+    pub grid: bool,
+    /// Whether to draw tile maps.
+    pub tilemaps: bool,
+    /// Whether to draw skinned mesh groups.
+    pub skinned: bool,
+    /// Whether to draw particles.
+    pub particles: bool,
+}
+
+impl Default for RenderSelection {
+    fn default() -> Self {
+        Self {
+            sprites: crate::GroupSelection::All,
+            meshes: crate::GroupSelection::All,
+            flats: crate::GroupSelection::All,
+            grid: true,
+            tilemaps: true,
+            skinned: true,
+            particles: true,
+        }
+    }
+}
+
+/// How [`Renderer::render`]'s main pass treats the color and depth
+/// textures: whether it clears them (and to what) or draws over what
+/// they already hold, and whether it keeps what it drew.  The default
+/// clears color to black and depth to 1.0 and stores both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderPassConfig {
+    /// Clearing color gives the background color; loading it draws
+    /// over the previous frame's image (the multisampled one, while
+    /// multisampling is on).
+    pub color_load: wgpu::LoadOp<wgpu::Color>,
+    pub color_store: wgpu::StoreOp,
+    pub depth_load: wgpu::LoadOp<f32>,
+    /// Fog, outlines, depth queries, and postprocessing effects read
+    /// depth after the main pass, so discarding it breaks them.
+    pub depth_store: wgpu::StoreOp,
+}
+
+impl Default for RenderPassConfig {
+    fn default() -> Self {
+        Self {
+            color_load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            color_store: wgpu::StoreOp::Store,
+            depth_load: wgpu::LoadOp::Clear(1.0),
+            depth_store: wgpu::StoreOp::Store,
+        }
+    }
+}
+
+impl RenderPassConfig {
+    /// The default configuration, but clearing color to `color`.
+    pub fn clear_color(color: wgpu::Color) -> Self {
+        Self {
+            color_load: wgpu::LoadOp::Clear(color),
+            ..Self::default()
+        }
+    }
+}
+
+/// Why [`Renderer::try_render`] couldn't acquire a frame from the
+/// surface.  Outdated and lost surfaces are reconfigured once before
+/// giving up, so these are rare; skipping the frame and trying again
+/// next frame is usually the right response to any of them but
+/// [`RenderError::OutOfMemory`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderError {
+    /// The surface took too long to provide a frame, e.g. because the
+    /// window is hidden
+    Timeout,
+    /// The surface no longer matches the window, even after
+    /// reconfiguring it
+    Outdated,
+    /// The surface was lost, even after reconfiguring it
+    Lost,
+    /// There's no memory left to allocate a frame
+    OutOfMemory,
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::Timeout => write!(f, "Timed out acquiring a surface texture"),
+            RenderError::Outdated => write!(f, "The surface is outdated"),
+            RenderError::Lost => write!(f, "The surface was lost"),
+            RenderError::OutOfMemory => write!(f, "Out of memory acquiring a surface texture"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<wgpu::SurfaceError> for RenderError {
+    fn from(err: wgpu::SurfaceError) -> Self {
+        match err {
+            wgpu::SurfaceError::Timeout => RenderError::Timeout,
+            wgpu::SurfaceError::Outdated => RenderError::Outdated,
+            wgpu::SurfaceError::Lost => RenderError::Lost,
+            wgpu::SurfaceError::OutOfMemory => RenderError::OutOfMemory,
+        }
+    }
+}
+
 impl Renderer {
     /// The format used for depth textures within frenderer.
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // Bytes per staging belt chunk; bigger uploads get their own
+    const STAGING_CHUNK_SIZE: u64 = 1 << 18;
     /// Creates a [Renderer] and its internal [crate::gpu::WGPU] using a wgpu [wgpu::Instance] and [wgpu::Surface], along with the rendering resolution (`w`, `h`) and surface dimensions.
+    ///
+    /// The returned future never blocks the thread polling it, so an
+    /// application that already has an executor (e.g. tokio or
+    /// async-std) can simply await it there; there's no need to nest
+    /// a blocking executor like `pollster` inside the async runtime.
+    /// Without one, [`crate::events::Driver`] polls it from the event
+    /// loop, and a native application can also drive it with any
+    /// `block_on`.
     pub async fn with_surface(
         width: u32,
         height: u32,
@@ -133,9 +495,10 @@ impl Renderer {
             wgpu::TextureFormat::Rgba8Unorm,
         );
         let lut = colorgeo::lut_identity(&gpu);
-        let postprocess = ColorGeo::new(&gpu, &color_texture, &lut, swapchain_format_srgb.into());
+        let colorgeo = ColorGeo::new(&gpu, &color_texture, &lut, swapchain_format_srgb.into());
         let (depth_texture, depth_texture_view) =
-            Self::create_depth_texture(gpu.device(), width, height);
+            Self::create_depth_texture(gpu.device(), width, height, Self::DEPTH_FORMAT);
+        let mut postprocess = PostProcess::new(&gpu, &color_texture, Some(&depth_texture));
 
         let intermediate_color_state = wgpu::ColorTargetState {
             format: color_texture.format(),
@@ -145,92 +508,660 @@ impl Renderer {
             }),
             write_mask: wgpu::ColorWrites::ALL,
         };
-        let sprites = SpriteRenderer::new(
+        let mut sprites = SpriteRenderer::new(
             &gpu,
             intermediate_color_state.clone(),
             depth_texture.format(),
         );
+        sprites.set_screen_size(&gpu, [width as f32, height as f32]);
+        sprites.defer_sorting();
         let meshes = MeshRenderer::new(
             &gpu,
             intermediate_color_state.clone(),
             depth_texture.format(),
         );
         let flats = FlatRenderer::new(&gpu, intermediate_color_state, depth_texture.format());
+        postprocess.set_camera(meshes.camera());
+        let outline = OutlinePass::new(&gpu, width, height, color_texture.format().into());
         Self {
             gpu,
             render_width: width,
             render_height: height,
             surface,
             config,
-            depth_texture,
-            depth_texture_view,
+            depth_texture: Some(depth_texture),
+            depth_texture_view: Some(depth_texture_view),
             postprocess,
+            colorgeo,
+            outline,
+            highlight: None,
+            grid: None,
+            debug_draw: None,
+            tilemaps: vec![],
+            skinned: None,
+            particles: None,
+            mipmaps: None,
+            panels: HashMap::new(),
+            palettes: HashMap::new(),
+            sprite_states: HashMap::new(),
+            mesh_states: HashMap::new(),
+            flat_states: HashMap::new(),
+            fog: None,
+            lighting2d: None,
+            stereo: None,
+            mirror: None,
+            point_shadows: None,
+            sun: None,
+            deferred: None,
+            deferred_lights: vec![],
+            pick: None,
+            velocity: None,
+            glow: None,
+            overlay: None,
+            frame_stats: FrameStats::default(),
+            capture_next: false,
+            last_capture: None,
+            recorder: None,
+            last_recording: None,
+            materials: vec![],
+            last_frame: None,
+            upload_bytes: 0,
+            coalesced_bytes: 0,
+            staging_belt: wgpu::util::StagingBelt::new(Self::STAGING_CHUNK_SIZE),
             sprites,
             meshes,
             flats,
             queued_uploads: Vec::with_capacity(16),
+            upload_budget: None,
+            deferred_uploads: vec![],
+            upload_priorities: HashMap::new(),
+            depth_copy: None,
+            hdr_output: None,
+            preferred_surface_formats: Vec::new(),
+            hierarchy: Hierarchy::new(),
             color_texture,
             color_texture_view,
+            msaa: None,
+            render_pass_config: RenderPassConfig::default(),
+            virtual_resolution: None,
+            surface_dormant: false,
         }
     }
-    /// Change the presentation mode used by the swapchain
-    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+    /// Change the presentation mode used by the swapchain, e.g. from
+    /// an options menu: [`wgpu::PresentMode::Fifo`] or
+    /// [`wgpu::PresentMode::AutoVsync`] for vsync,
+    /// [`wgpu::PresentMode::Mailbox`] for low latency without tearing,
+    /// or [`wgpu::PresentMode::Immediate`] or
+    /// [`wgpu::PresentMode::AutoNoVsync`] for no vsync.  Returns false,
+    /// leaving the mode as it was, if the surface doesn't support
+    /// `mode` (see [`Renderer::supported_present_modes`]); the `Auto`
+    /// modes are always supported.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        let automatic = matches!(
+            mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        );
+        if self.surface.is_some() && !automatic && !self.supported_present_modes().contains(&mode) {
+            return false;
+        }
         self.config.present_mode = mode;
         self.configure_surface();
+        true
+    }
+    /// Returns the presentation mode used by the swapchain
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+    /// Returns the presentation modes the surface supports; this is
+    /// empty if there's no surface.
+    pub fn supported_present_modes(&self) -> Vec<wgpu::PresentMode> {
+        self.surface
+            .as_ref()
+            .map(|surface| surface.get_capabilities(self.gpu.adapter()).present_modes)
+            .unwrap_or_default()
+    }
+    /// Sets how many frames the swapchain may queue up ahead of the
+    /// display (at least 1).  Fewer frames mean less input latency,
+    /// more let the CPU run further ahead of the GPU.  The backend
+    /// treats this as a hint.
+    pub fn set_frame_latency(&mut self, frames: u32) {
+        self.config.desired_maximum_frame_latency = frames.max(1);
+        self.configure_surface();
+    }
+    /// Returns how many frames the swapchain may queue up ahead of the display
+    pub fn frame_latency(&self) -> u32 {
+        self.config.desired_maximum_frame_latency
+    }
+    /// Sets the frame latency and presentation mode to one of the
+    /// [`LatencyMode`] presets.
+    pub fn set_latency_mode(&mut self, mode: LatencyMode) {
+        let (frames, present_mode) = match mode {
+            LatencyMode::LowLatency
+                if self
+                    .supported_present_modes()
+                    .contains(&wgpu::PresentMode::Mailbox) =>
+            {
+                (1, wgpu::PresentMode::Mailbox)
+            }
+            LatencyMode::LowLatency => (1, wgpu::PresentMode::AutoVsync),
+            LatencyMode::Balanced => (2, wgpu::PresentMode::AutoVsync),
+            LatencyMode::Throughput => (3, wgpu::PresentMode::AutoVsync),
+        };
+        self.config.desired_maximum_frame_latency = frames;
+        self.config.present_mode = present_mode;
+        self.configure_surface();
     }
     /// Returns the current surface
     pub fn surface(&self) -> Option<&wgpu::Surface<'static>> {
         self.surface.as_ref()
     }
-    /// Creates a new surface for this renderer
+    /// Creates a new surface for this renderer.  HDR output (see
+    /// [`Renderer::hdr_output_set`]) is turned off if the new
+    /// surface doesn't support it.
     pub fn create_surface(&mut self, window: Arc<winit::window::Window>) {
         let surface = self.gpu.instance().create_surface(window).unwrap();
         let swapchain_capabilities = surface.get_capabilities(self.gpu.adapter());
-        let swapchain_format = swapchain_capabilities.formats[0];
-        let swapchain_format_srgb = swapchain_format.add_srgb_suffix();
-
-        self.config = wgpu::SurfaceConfiguration {
-            format: swapchain_format,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
-            view_formats: vec![swapchain_format, swapchain_format_srgb],
-            ..self.config
-        };
-        self.postprocess.set_color_target(
-            &self.gpu,
-            (*self.config.view_formats.last().unwrap()).into(),
-        );
+        self.config.alpha_mode = swapchain_capabilities.alpha_modes[0];
         self.surface = Some(surface);
+        if !self
+            .hdr_output
+            .is_some_and(|hdr| self.hdr_modes().contains(&hdr.mode))
+        {
+            self.hdr_output = None;
+        }
+        self.configure_output_format();
+        self.configure_surface();
+    }
+    /// Picks the surface's format for the current HDR output mode and
+    /// rebuilds the pipelines which draw into it.
+    fn configure_output_format(&mut self) {
+        let Some(surface) = self.surface.as_ref() else {
+            return;
+        };
+        let (format, view_format) = match self.hdr_output {
+            Some(hdr) => (hdr.mode.format(), hdr.mode.format()),
+            None => {
+                let formats = surface.get_capabilities(self.gpu.adapter()).formats;
+                let format = self
+                    .preferred_surface_formats
+                    .iter()
+                    .find(|format| formats.contains(format))
+                    .copied()
+                    .unwrap_or(formats[0]);
+                (format, format.add_srgb_suffix())
+            }
+        };
+        self.config.format = format;
+        self.config.view_formats = vec![format, view_format];
+        self.config.view_formats.dedup();
+        self.colorgeo
+            .set_color_target(&self.gpu, view_format.into());
+        self.colorgeo.set_hdr_output(&self.gpu, self.hdr_output);
+        if let Some((overlay, _)) = self.overlay.as_mut() {
+            *overlay = DebugOverlay::new(&self.gpu, view_format.into());
+        }
+    }
+    /// Sets the surface formats to use if the surface supports them,
+    /// in order of preference; otherwise (and by default) the
+    /// surface's own preferred format is used.  Either way the final
+    /// image is drawn through an sRGB view of the format, if it has
+    /// one, and HDR output (see [`Renderer::hdr_output_set`]) picks
+    /// its own format.
+    pub fn set_preferred_surface_formats(&mut self, formats: &[wgpu::TextureFormat]) {
+        self.preferred_surface_formats = formats.to_vec();
+        self.configure_output_format();
         self.configure_surface();
     }
+    /// The format of the views the final image is drawn into.
+    fn surface_view_format(&self) -> wgpu::TextureFormat {
+        *self.config.view_formats.last().unwrap()
+    }
+    /// Returns the HDR output modes the surface supports, in
+    /// [`HdrMode`] order; this is empty if there's no surface.
+    pub fn hdr_modes(&self) -> Vec<HdrMode> {
+        let Some(surface) = self.surface.as_ref() else {
+            return vec![];
+        };
+        let formats = surface.get_capabilities(self.gpu.adapter()).formats;
+        [HdrMode::ScRgb, HdrMode::Pq]
+            .into_iter()
+            .filter(|mode| formats.contains(&mode.format()))
+            .collect()
+    }
+    /// Turns HDR output on with the given settings (see
+    /// [`crate::colorgeo::HdrOutput`]), or off if `output` is `None`,
+    /// reconfiguring the surface with the mode's format.  Returns
+    /// false, leaving the output as it was, if the surface doesn't
+    /// support the mode (see [`Renderer::hdr_modes`]).  The debug
+    /// overlay isn't tone-mapped, so it may look dim or glaring.
+    pub fn hdr_output_set(&mut self, output: Option<HdrOutput>) -> bool {
+        if output.is_some_and(|hdr| !self.hdr_modes().contains(&hdr.mode)) {
+            return false;
+        }
+        if output != self.hdr_output {
+            self.hdr_output = output;
+            self.configure_output_format();
+            self.configure_surface();
+        }
+        true
+    }
+    /// Returns the HDR output settings, if HDR output is on.
+    pub fn hdr_output(&self) -> Option<HdrOutput> {
+        self.hdr_output
+    }
     fn configure_surface(&mut self) {
+        if self.surface_dormant {
+            return;
+        }
         if let Some(surface) = self.surface.as_ref() {
             surface.configure(self.gpu.device(), &self.config);
         }
     }
     /// Resize the internal surface texture (typically called when the window or canvas size changes).
+    ///
+    /// A zero width or height (e.g. a minimized window on Windows)
+    /// can't be used to configure a surface, so in that case the
+    /// surface is marked dormant and rendering is skipped until a
+    /// valid size arrives.
     pub fn resize_surface(&mut self, w: u32, h: u32) {
+        if w == 0 || h == 0 {
+            self.surface_dormant = true;
+            return;
+        }
+        self.surface_dormant = false;
         self.config.width = w;
         self.config.height = h;
         self.configure_surface();
     }
+    /// Returns true if the surface has been resized to a zero extent
+    /// and is waiting for a valid size before rendering resumes.
+    pub fn surface_dormant(&self) -> bool {
+        self.surface_dormant
+    }
     /// Resize the internal color and depth targets (the actual rendering resolution).
     pub fn resize_render(&mut self, w: u32, h: u32) {
         self.render_width = w;
         self.render_height = h;
         let (color_texture, color_texture_view) =
-            Self::create_color_texture(self.gpu.device(), w, h, self.config.format);
+            Self::create_color_texture(self.gpu.device(), w, h, self.color_texture.format());
         self.color_texture = color_texture;
         self.color_texture_view = color_texture_view;
+        self.colorgeo
+            .replace_color_texture(&self.gpu, &self.color_texture);
+        self.sprites
+            .set_screen_size(&self.gpu, [w as f32, h as f32]);
+        self.outline.resize(&self.gpu, w, h);
+        if let Some(lighting) = self.lighting2d.as_mut() {
+            lighting.resize(&self.gpu, w, h);
+        }
+        if let Some((highlight, _)) = self.highlight.as_mut() {
+            highlight.resize(&self.gpu, w, h);
+        }
+        if let Some(pick) = self.pick.as_mut() {
+            pick.resize(&self.gpu, w, h);
+        }
+        if let Some(velocity) = self.velocity.as_mut() {
+            velocity.resize(&self.gpu, w, h);
+            self.postprocess
+                .set_velocity(&self.gpu, Some(velocity.texture()));
+        }
+        if let Some(glow) = self.glow.as_mut() {
+            glow.resize(&self.gpu, w, h);
+            self.postprocess.set_glow(&self.gpu, Some(glow.texture()));
+        }
+        if let Some(format) = self.depth_format() {
+            let (depth_tex, depth_view) =
+                Self::create_depth_texture(self.gpu.device(), w, h, format);
+            self.depth_texture = Some(depth_tex);
+            self.depth_texture_view = Some(depth_view);
+        }
+        if let (Some(fog), Some(depth)) = (self.fog.as_mut(), self.depth_texture.as_ref()) {
+            fog.set_depth(&self.gpu, depth);
+        }
+        if let Some((stereo, _)) = self.stereo.as_mut() {
+            stereo.resize(&self.gpu, w, h);
+        }
+        if let Some((mirror, _)) = self.mirror.as_mut() {
+            mirror.resize(&self.gpu, w, h);
+        }
+        if let Some((gbuffer, lighting)) = self.deferred.as_mut() {
+            gbuffer.resize(&self.gpu, w, h);
+            lighting.set_gbuffer(&self.gpu, gbuffer);
+        }
+        self.recreate_msaa();
+        self.postprocess
+            .resize(&self.gpu, &self.color_texture, self.depth_texture.as_ref());
+    }
+    /// Changes the depth format used by the built-in renderers,
+    /// re-creating the depth texture and the renderers' pipelines.
+    /// Passing `None` puts the renderer in 2D-only mode: no depth
+    /// texture is allocated and sprites are drawn in painter's order
+    /// (group by group, and in order within each group).
+    pub fn set_depth_format(&mut self, format: Option<wgpu::TextureFormat>) {
+        if let Some(format) = format {
+            let (depth_tex, depth_view) = Self::create_depth_texture(
+                self.gpu.device(),
+                self.render_width,
+                self.render_height,
+                format,
+            );
+            self.depth_texture = Some(depth_tex);
+            self.depth_texture_view = Some(depth_view);
+        } else {
+            self.depth_texture = None;
+            self.depth_texture_view = None;
+        }
+        self.sprites.set_depth_format(&self.gpu, format);
+        self.meshes.set_depth_format(&self.gpu, format);
+        self.flats.set_depth_format(&self.gpu, format);
+        if let Some(grid) = self.grid.as_mut() {
+            grid.set_depth_format(&self.gpu, format);
+        }
+        if let Some(debug_draw) = self.debug_draw.as_mut() {
+            debug_draw.set_depth_format(&self.gpu, format);
+        }
+        for tilemap in self.tilemaps.iter_mut().flatten() {
+            tilemap.set_depth_format(&self.gpu, format);
+        }
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.set_depth_format(&self.gpu, format);
+        }
+        if let Some(particles) = self.particles.as_mut() {
+            particles.set_depth_format(&self.gpu, format);
+        }
+        if let Some(pick) = self.pick.as_mut() {
+            pick.set_depth_format(&self.gpu, format);
+        }
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_depth_format(&self.gpu, format);
+        }
+        if let Some(velocity) = self.velocity.as_mut() {
+            velocity.set_depth_format(&self.gpu, format);
+        }
+        if let Some(glow) = self.glow.as_mut() {
+            glow.set_depth_format(&self.gpu, format);
+        }
+        if let (Some(fog), Some(depth)) = (self.fog.as_mut(), self.depth_texture.as_ref()) {
+            fog.set_depth(&self.gpu, depth);
+        }
+        self.recreate_msaa();
         self.postprocess
+            .resize(&self.gpu, &self.color_texture, self.depth_texture.as_ref());
+    }
+    /// Returns the depth format used by the built-in renderers, or `None` in 2D-only mode.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_texture.as_ref().map(|d| d.format())
+    }
+    /// Turns multisample antialiasing of the main render pass on with
+    /// up to `sample_count` samples per pixel, or off if it's 1.  The
+    /// count is lowered to the largest one the color and depth
+    /// formats support (see [`crate::msaa::supported_sample_count`])
+    /// and returned.  The built-in renderers' pipelines are
+    /// re-created for the new count, so a render pass given to
+    /// [`Renderer::render_into`] must have the same sample count.
+    pub fn msaa_set(&mut self, sample_count: u32) -> u32 {
+        let formats: Vec<_> = std::iter::once(self.color_texture.format())
+            .chain(self.depth_format())
+            .collect();
+        let sample_count = crate::msaa::supported_sample_count(&self.gpu, &formats, sample_count);
+        if sample_count == self.msaa() {
+            return sample_count;
+        }
+        self.msaa = (sample_count > 1).then(|| {
+            MsaaTargets::new(
+                &self.gpu,
+                self.render_width,
+                self.render_height,
+                self.color_texture.format(),
+                self.depth_format(),
+                sample_count,
+            )
+        });
+        self.sprites.set_sample_count(&self.gpu, sample_count);
+        self.meshes.set_sample_count(&self.gpu, sample_count);
+        self.flats.set_sample_count(&self.gpu, sample_count);
+        if let Some(grid) = self.grid.as_mut() {
+            grid.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some(debug_draw) = self.debug_draw.as_mut() {
+            debug_draw.set_sample_count(&self.gpu, sample_count);
+        }
+        for tilemap in self.tilemaps.iter_mut().flatten() {
+            tilemap.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some(particles) = self.particles.as_mut() {
+            particles.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some((stereo, _)) = self.stereo.as_mut() {
+            stereo.set_sample_count(&self.gpu, sample_count);
+        }
+        if let Some((mirror, _)) = self.mirror.as_mut() {
+            mirror.set_sample_count(&self.gpu, sample_count);
+        }
+        sample_count
+    }
+    /// Sets how colors brighter than 1.0 are brought back into range
+    /// before the color lookup table is applied (see
+    /// [`crate::colorgeo`]).  With any operator but
+    /// [`Tonemap::None`], the scene is drawn into a
+    /// [`wgpu::TextureFormat::Rgba16Float`] color texture instead of
+    /// an 8-bit one so it has headroom above 1.0; switching between
+    /// the two re-creates the built-in renderers' pipelines, so a
+    /// render pass given to [`Renderer::render_into`] must have the
+    /// new color format (see [`Renderer::color_format`]).
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.colorgeo.set_tonemap(&self.gpu, tonemap);
+        let format = if tonemap == Tonemap::None {
+            wgpu::TextureFormat::Rgba8Unorm
+        } else {
+            wgpu::TextureFormat::Rgba16Float
+        };
+        if format != self.color_texture.format() {
+            self.set_color_format(format);
+        }
+    }
+    /// Returns the tone mapping operator.
+    pub fn tonemap(&self) -> Tonemap {
+        self.colorgeo.tonemap()
+    }
+    /// Returns the format of the color texture the scene is drawn into.
+    pub fn color_format(&self) -> wgpu::TextureFormat {
+        self.color_texture.format()
+    }
+    // Re-creates the color texture and everything drawing into it
+    // with a new format
+    fn set_color_format(&mut self, format: wgpu::TextureFormat) {
+        // The new format may support a different number of samples
+        let sample_count = self.msaa();
+        self.msaa_set(1);
+        let (w, h) = (self.render_width, self.render_height);
+        (self.color_texture, self.color_texture_view) =
+            Self::create_color_texture(self.gpu.device(), w, h, format);
+        self.colorgeo
             .replace_color_texture(&self.gpu, &self.color_texture);
-        let (depth_tex, depth_view) = Self::create_depth_texture(self.gpu.device(), w, h);
-        self.depth_texture = depth_tex;
-        self.depth_texture_view = depth_view;
+        let blended = wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent::OVER,
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+        self.sprites.set_color_target(&self.gpu, blended.clone());
+        self.meshes.set_color_target(&self.gpu, blended.clone());
+        self.flats.set_color_target(&self.gpu, blended);
+        let outline = OutlinePass::new(&self.gpu, w, h, format.into());
+        let old_outline = std::mem::replace(&mut self.outline, outline);
+        self.outline
+            .set_thickness(&self.gpu, old_outline.thickness());
+        self.outline.set_fill(&self.gpu, old_outline.fill());
+        if let Some((pass, highlight)) = self.highlight.as_mut() {
+            *pass = OutlinePass::new(&self.gpu, w, h, format.into());
+            pass.set_thickness(&self.gpu, highlight.thickness);
+            pass.set_fill(&self.gpu, highlight.fill);
+        }
+        if let Some(grid) = self.grid.as_mut() {
+            grid.set_color_target(&self.gpu, format.into());
+        }
+        if let Some(debug_draw) = self.debug_draw.as_mut() {
+            debug_draw.set_color_target(&self.gpu, format.into());
+        }
+        for tilemap in self.tilemaps.iter_mut().flatten() {
+            tilemap.set_color_target(&self.gpu, format.into());
+        }
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.set_color_target(&self.gpu, format.into());
+        }
+        if let Some(particles) = self.particles.as_mut() {
+            particles.set_color_target(&self.gpu, format.into());
+        }
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_color_target(&self.gpu, format.into());
+        }
+        if let Some(fog) = self.fog.as_mut() {
+            *fog = FogRenderer::new(&self.gpu, format, fog.fog().clone(), fog.camera());
+            if let Some(depth) = self.depth_texture.as_ref() {
+                fog.set_depth(&self.gpu, depth);
+            }
+        }
+        if let Some(lighting) = self.lighting2d.as_mut() {
+            let camera = lighting.camera();
+            *lighting = Lighting2DRenderer::new(
+                &self.gpu,
+                format,
+                (w, h),
+                lighting.lighting().clone(),
+                camera,
+            );
+        }
+        if let Some((pass, _)) = self.stereo.as_mut() {
+            *pass = StereoPass::new(&self.gpu, w, h, format, *pass.stereo());
+        }
+        if let Some((pass, _)) = self.mirror.as_mut() {
+            *pass = MirrorPass::new(&self.gpu, w, h, format, pass.mirror().clone());
+        }
+        // Re-created with the new format before the next frame if it's still needed
+        self.glow = None;
+        self.postprocess.set_glow(&self.gpu, None);
+        self.postprocess
+            .resize(&self.gpu, &self.color_texture, self.depth_texture.as_ref());
+        self.msaa_set(sample_count);
+    }
+    /// Returns the number of samples per pixel of the main render
+    /// pass, which is 1 unless multisampling is on.
+    pub fn msaa(&self) -> u32 {
+        self.msaa.as_ref().map_or(1, MsaaTargets::sample_count)
+    }
+    /// Sets how the main pass clears or loads the color and depth
+    /// textures and whether it stores them, e.g. to draw on a
+    /// non-black background or over the previous frame.  In stereo
+    /// mode the combined eye images cover the whole color texture,
+    /// so the color load makes no difference.
+    pub fn render_pass_config_set(&mut self, config: RenderPassConfig) {
+        self.render_pass_config = config;
+    }
+    /// Returns how the main pass clears or loads its targets.
+    pub fn render_pass_config(&self) -> RenderPassConfig {
+        self.render_pass_config
+    }
+    /// Turns on a fixed "virtual" resolution, e.g. `(320, 180)` for a
+    /// retro-style game, or turns it off if `resolution` is `None`.
+    /// While it's on, the scene is rendered at that resolution (see
+    /// [`Renderer::resize_render`]) and drawn onto the surface scaled
+    /// up by the largest whole number that fits, with nearest
+    /// neighbor sampling and black bars around it; if the surface is
+    /// smaller than the virtual resolution, the scene is scaled down
+    /// to fit instead.  Turning it off stretches the scene over the
+    /// surface again, keeping the render resolution.
+    /// [`Renderer::render_to_view`] always stretches.
+    pub fn virtual_resolution_set(&mut self, resolution: Option<(u32, u32)>) {
+        self.virtual_resolution = resolution;
+        if let Some((w, h)) = resolution {
+            if (w, h) != self.render_size() {
+                self.resize_render(w, h);
+            }
+        }
+    }
+    /// Returns the virtual resolution, if it's on.
+    pub fn virtual_resolution(&self) -> Option<(u32, u32)> {
+        self.virtual_resolution
+    }
+    /// Returns the part of the surface the scene is drawn onto, as
+    /// its left, top, width, and height in pixels: the whole surface
+    /// unless a virtual resolution is on.
+    pub fn present_viewport(&self) -> [u32; 4] {
+        Self::letterbox(
+            self.virtual_resolution.map(|_| self.render_size()),
+            self.surface_size(),
+        )
+    }
+    /// Converts a point on the surface (e.g. the mouse position, in
+    /// pixels from the top left) into render target pixels from the
+    /// top left, accounting for stretching or a virtual resolution's
+    /// scaling and bars.  Returns `None` for points in the bars.
+    pub fn surface_to_render(&self, point: [f32; 2]) -> Option<[f32; 2]> {
+        let [x, y, w, h] = self.present_viewport().map(|v| v as f32);
+        let (render_w, render_h) = self.render_size();
+        let local = [(point[0] - x) / w, (point[1] - y) / h];
+        local
+            .iter()
+            .all(|v| (0.0..1.0).contains(v))
+            .then(|| [local[0] * render_w as f32, local[1] * render_h as f32])
+    }
+    // Where a scene of the given size is drawn on a target: centered
+    // and scaled by a whole number if possible, or scaled down to fit
+    // if not; or over the whole target if `scene` is None
+    fn letterbox(scene: Option<(u32, u32)>, (target_w, target_h): (u32, u32)) -> [u32; 4] {
+        let Some((scene_w, scene_h)) = scene.filter(|&(w, h)| w > 0 && h > 0) else {
+            return [0, 0, target_w, target_h];
+        };
+        let (w, h) = match (target_w / scene_w).min(target_h / scene_h) {
+            0 => {
+                let scale =
+                    (target_w as f32 / scene_w as f32).min(target_h as f32 / scene_h as f32);
+                (
+                    ((scene_w as f32 * scale) as u32).max(1),
+                    ((scene_h as f32 * scale) as u32).max(1),
+                )
+            }
+            scale => (scene_w * scale, scene_h * scale),
+        };
+        [(target_w - w) / 2, (target_h - h) / 2, w, h]
+    }
+    // Re-creates the multisampled targets, if any, to match the
+    // color and depth textures.
+    fn recreate_msaa(&mut self) {
+        let sample_count = self.msaa();
+        if sample_count > 1 {
+            self.msaa = Some(MsaaTargets::new(
+                &self.gpu,
+                self.render_width,
+                self.render_height,
+                self.color_texture.format(),
+                self.depth_format(),
+                sample_count,
+            ));
+        }
+    }
+    // The depth texture the main pass and the passes sharing its
+    // depth draw into
+    fn pass_depth_view(&self) -> Option<&wgpu::TextureView> {
+        match self.msaa.as_ref() {
+            Some(msaa) => msaa.depth_view(),
+            None => self.depth_texture_view.as_ref(),
+        }
     }
     fn create_depth_texture(
         device: &wgpu::Device,
         width: u32,
         height: u32,
+        format: wgpu::TextureFormat,
     ) -> (wgpu::Texture, wgpu::TextureView) {
         let size = wgpu::Extent3d {
             width,
@@ -243,9 +1174,11 @@ impl Renderer {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[Self::DEPTH_FORMAT],
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[format],
         };
         let texture = device.create_texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -269,7 +1202,10 @@ impl Renderer {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            // Postprocessing effects may copy their results back into the color texture
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[format],
         };
         let texture = device.create_texture(&desc);
@@ -278,17 +1214,343 @@ impl Renderer {
     }
 
     /// Uploads sprite, mesh, and flat data accessed since the last
-    /// time [`Renderer::do_uploads`] was called.  Call this manually if you
+    /// time [`Renderer::do_uploads`] was called, along with the
+    /// frame's debug drawing.  Call this manually if you
     /// want, or let [`Renderer::render`] call it automatically.
+    ///
+    /// With an upload budget (see [`Renderer::upload_budget_set`]),
+    /// uploads beyond the budget are left for later calls, in order
+    /// of their groups' priorities and then of how long they've been
+    /// waiting.
     pub fn do_uploads(&mut self) {
+        self.resolve_hierarchy();
+        trace_span!("frenderer::do_uploads", uploads = self.queued_uploads.len());
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.record_frame(&self.sprites, &self.meshes, &self.flats);
+            if recorder.is_finished() {
+                self.last_recording = Some(recorder.finish());
+            } else {
+                self.recorder = Some(recorder);
+            }
+        }
+        // Skinned groups are small and animated, so they aren't budgeted
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.upload(&self.gpu);
+        }
+        // Groups drawn interpolated last frame but not in this one go
+        // back to their simulated transforms
+        for (&which, state) in &mut self.sprite_states {
+            if state.alpha.is_none() && std::mem::take(&mut state.drawn) {
+                let len = self.sprites.sprite_group_size(which);
+                self.queued_uploads.push(Upload::Sprite(which, 0..len));
+            }
+        }
+        for (&(which, idx), state) in &mut self.mesh_states {
+            if state.alpha.is_none() && std::mem::take(&mut state.drawn) {
+                let len = self.meshes.mesh_instance_count(which, idx);
+                self.queued_uploads.push(Upload::Mesh(which, idx, 0..len));
+            }
+        }
+        for (&(which, idx), state) in &mut self.flat_states {
+            if state.alpha.is_none() && std::mem::take(&mut state.drawn) {
+                let len = self.flats.mesh_instance_count(which, idx);
+                self.queued_uploads.push(Upload::Flat(which, idx, 0..len));
+            }
+        }
+        // New uploads to the same mesh as a waiting upload are merged
+        // into it, so waiting uploads can't pile up
+        let mut uploads = std::mem::take(&mut self.deferred_uploads);
+        let waiting = uploads.len();
+        let mut fresh = Vec::with_capacity(self.queued_uploads.len());
         for upload in self.queued_uploads.drain(..) {
+            match uploads[..waiting]
+                .iter_mut()
+                .find(|waiting| waiting.target() == upload.target())
+            {
+                Some(waiting) => {
+                    let new = upload.range();
+                    let range = waiting.range_mut();
+                    *range = range.start.min(new.start)..range.end.max(new.end);
+                }
+                None => fresh.push(upload),
+            }
+        }
+        // Overlapping and adjacent ranges of the same mesh are merged
+        // into one upload, so nothing is written twice
+        let requested: usize = fresh.iter().map(Upload::bytes).sum();
+        fresh.sort_by_key(|upload| upload.range().start);
+        let mut last_upload: HashMap<(UploadGroup, usize), usize> = HashMap::new();
+        for upload in fresh {
+            let new = upload.range();
+            match last_upload.get(&upload.target()) {
+                Some(&which) if uploads[which].range().end >= new.start => {
+                    let range = uploads[which].range_mut();
+                    range.end = range.end.max(new.end);
+                }
+                _ => {
+                    last_upload.insert(upload.target(), uploads.len());
+                    uploads.push(upload);
+                }
+            }
+        }
+        let merged: usize = uploads[waiting..].iter().map(Upload::bytes).sum();
+        self.coalesced_bytes += requested - merged;
+        if self.upload_budget.is_some() {
+            // A stable sort keeps waiting uploads ahead of newer ones
+            uploads.sort_by_key(|upload| std::cmp::Reverse(self.upload_priority(upload.group())));
+        }
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("frenderer:uploads"),
+                });
+        let mut spent = 0;
+        for mut upload in uploads {
+            // Groups may have been removed or resized while an upload waited
+            if !self.clamp_upload(&mut upload) {
+                continue;
+            }
+            let bytes = upload.bytes();
+            // Something is always uploaded, however small the budget
+            if self
+                .upload_budget
+                .is_some_and(|budget| spent > 0 && spent + bytes > budget)
+            {
+                self.deferred_uploads.push(upload);
+                continue;
+            }
             log::info!("upload: {upload:?}");
+            spent += bytes;
+            let mut writer = BufferWriter::Belt {
+                device: self.gpu.device(),
+                encoder: &mut encoder,
+                belt: &mut self.staging_belt,
+            };
             match upload {
-                Upload::Mesh(mg, m, r) => self.meshes.upload_meshes(&self.gpu, mg, m, r),
-                Upload::Flat(mg, m, r) => self.flats.upload_meshes(&self.gpu, mg, m, r),
-                Upload::Sprite(s, r) => self.sprites.upload_sprites(&self.gpu, s, r),
+                Upload::Mesh(mg, m, r) => self.meshes.upload_meshes_with(&mut writer, mg, m, r),
+                Upload::Flat(mg, m, r) => self.flats.upload_meshes_with(&mut writer, mg, m, r),
+                Upload::Sprite(s, r) => {
+                    self.sprites
+                        .upload_sprites_with(&self.gpu, &mut writer, s, r)
+                }
+            }
+        }
+        spent += self.sprites.sort_unsorted_with(
+            &self.gpu,
+            &mut BufferWriter::Belt {
+                device: self.gpu.device(),
+                encoder: &mut encoder,
+                belt: &mut self.staging_belt,
+            },
+        );
+        // Interpolated transforms go over whatever was just uploaded
+        spent += self.upload_interpolations(&mut encoder);
+        if spent > 0 {
+            self.staging_belt.finish();
+            self.gpu.queue().submit(Some(encoder.finish()));
+            self.staging_belt.recall();
+        }
+        self.upload_bytes += spent;
+        // Uploading again before the last upload was drawn would
+        // replace its shapes with the (cleared) ones since
+        if let Some(debug_draw) = self.debug_draw.as_mut().filter(|d| d.needs_prepare()) {
+            let screen = crate::sprites::Camera2D {
+                screen_pos: [0.0, 0.0],
+                screen_size: [self.render_width as f32, self.render_height as f32],
+            };
+            debug_draw.prepare(&self.gpu, (screen, self.meshes.camera()));
+            // Cleared now rather than after drawing, so shapes can't pile
+            // up over frames that are never drawn (e.g. while minimized)
+            debug_draw.clear();
+        }
+        trace_event!(
+            bytes = self.upload_bytes,
+            deferred = self.deferred_uploads.len(),
+            "uploaded instance data"
+        );
+    }
+    // Writes the interpolated transforms of the groups drawn
+    // interpolated this frame (see Renderer::sprites_lerp), returning
+    // how many bytes were written
+    fn upload_interpolations(&mut self, encoder: &mut wgpu::CommandEncoder) -> usize {
+        let mut writer = BufferWriter::Belt {
+            device: self.gpu.device(),
+            encoder,
+            belt: &mut self.staging_belt,
+        };
+        let mut bytes = 0;
+        for (&which, state) in &mut self.sprite_states {
+            let Some(alpha) = state.alpha.take() else {
+                continue;
+            };
+            let current = self.sprites.get_sprites(which).0;
+            let drawn = state.lerp(current, alpha, crate::sprites::Transform::lerp);
+            self.sprites
+                .write_drawn_transforms(&mut writer, which, &drawn);
+            bytes += std::mem::size_of_val(drawn.as_slice());
+            state.drawn = true;
+        }
+        for (&(which, idx), state) in &mut self.mesh_states {
+            let Some(alpha) = state.alpha.take() else {
+                continue;
+            };
+            let current = self.meshes.get_meshes(which, idx);
+            let drawn = state.lerp(current, alpha, crate::meshes::Transform3D::lerp);
+            self.meshes
+                .write_drawn_instances(&mut writer, which, idx, &drawn);
+            bytes += std::mem::size_of_val(drawn.as_slice());
+            state.drawn = true;
+        }
+        for (&(which, idx), state) in &mut self.flat_states {
+            let Some(alpha) = state.alpha.take() else {
+                continue;
+            };
+            let current = self.flats.get_meshes(which, idx);
+            let drawn = state.lerp(current, alpha, crate::meshes::Transform3D::lerp);
+            self.flats
+                .write_drawn_instances(&mut writer, which, idx, &drawn);
+            bytes += std::mem::size_of_val(drawn.as_slice());
+            state.drawn = true;
+        }
+        bytes
+    }
+    // Clips an upload's range to its group's current size, returning
+    // false if there's nothing left to upload
+    fn clamp_upload(&self, upload: &mut Upload) -> bool {
+        let len = match upload {
+            Upload::Mesh(mg, m, _) => (self.meshes.has_mesh_group(*mg)
+                && *m < self.meshes.mesh_count(*mg))
+            .then(|| self.meshes.mesh_instance_count(*mg, *m)),
+            Upload::Flat(mg, m, _) => (self.flats.has_mesh_group(*mg)
+                && *m < self.flats.mesh_count(*mg))
+            .then(|| self.flats.mesh_instance_count(*mg, *m)),
+            Upload::Sprite(s, _) => self
+                .sprites
+                .has_sprite_group(*s)
+                .then(|| self.sprites.sprite_group_size(*s)),
+        };
+        let Some(len) = len else {
+            return false;
+        };
+        let range = upload.range_mut();
+        range.end = range.end.min(len);
+        range.start < range.end
+    }
+    /// Limits how many bytes of instance data [`Renderer::do_uploads`]
+    /// sends to the GPU each frame, or removes the limit if `budget`
+    /// is `None` (the default).  Uploads over the budget wait for
+    /// later frames, smoothing out frames where many groups change at
+    /// once (e.g. while loading a level) at the cost of those groups
+    /// showing stale instance data for a few frames.  At least one
+    /// upload is made each frame, even if it's over the budget.
+    pub fn upload_budget_set(&mut self, budget: Option<usize>) {
+        self.upload_budget = budget;
+    }
+    /// Returns the per-frame upload budget in bytes, if there is one.
+    pub fn upload_budget(&self) -> Option<usize> {
+        self.upload_budget
+    }
+    /// Sets the priority of a group's uploads when they're over the
+    /// upload budget: uploads of higher-priority groups are made
+    /// first.  Groups start with priority 0, and removing a group
+    /// resets its priority.
+    pub fn upload_priority_set(&mut self, group: UploadGroup, priority: i32) {
+        if priority == 0 {
+            self.upload_priorities.remove(&group);
+        } else {
+            self.upload_priorities.insert(group, priority);
+        }
+    }
+    /// Returns the priority of a group's uploads.
+    pub fn upload_priority(&self, group: UploadGroup) -> i32 {
+        self.upload_priorities.get(&group).copied().unwrap_or(0)
+    }
+    /// Returns how many bytes of instance data are waiting for a
+    /// later frame because they were over the upload budget.
+    pub fn pending_upload_bytes(&self) -> usize {
+        self.deferred_uploads.iter().map(Upload::bytes).sum()
+    }
+    // Writes world transforms from the hierarchy into the instances
+    // attached to it, skipping instances that no longer exist, and
+    // queues one upload per touched sprite group or mesh
+    fn resolve_hierarchy(&mut self) {
+        let resolved = self.hierarchy.resolve();
+        if resolved.is_empty() {
+            return;
+        }
+        trace_span!("frenderer::resolve_hierarchy", targets = resolved.len());
+        let mut sprites: Vec<(usize, Range<usize>)> = vec![];
+        let mut meshes: Vec<(crate::meshes::MeshGroup, usize, Range<usize>)> = vec![];
+        let mut flats: Vec<(crate::meshes::MeshGroup, usize, Range<usize>)> = vec![];
+        fn widen(range: &mut Range<usize>, index: usize) {
+            range.start = range.start.min(index);
+            range.end = range.end.max(index + 1);
+        }
+        for (target, world) in resolved {
+            match target {
+                Target::Sprite { group, index } => {
+                    if !self.sprites.has_sprite_group(group)
+                        || index >= self.sprites.sprite_group_size(group)
+                    {
+                        continue;
+                    }
+                    let (x, y, rot) = hierarchy::sprite_placement(&world);
+                    let trf = &mut self.sprites.get_sprites_mut(group).0[index];
+                    trf.x = x;
+                    trf.y = y;
+                    trf.rot = rot;
+                    match sprites.iter_mut().find(|(g, _)| *g == group) {
+                        Some((_, range)) => widen(range, index),
+                        None => sprites.push((group, index..index + 1)),
+                    }
+                }
+                Target::Mesh { group, mesh, index } => {
+                    if !self.meshes.has_mesh_group(group)
+                        || mesh >= self.meshes.mesh_count(group)
+                        || index >= self.meshes.mesh_instance_count(group, mesh)
+                    {
+                        continue;
+                    }
+                    self.meshes.get_meshes_mut(group, mesh)[index] = world;
+                    match meshes
+                        .iter_mut()
+                        .find(|(g, m, _)| (*g, *m) == (group, mesh))
+                    {
+                        Some((_, _, range)) => widen(range, index),
+                        None => meshes.push((group, mesh, index..index + 1)),
+                    }
+                }
+                Target::Flat { group, mesh, index } => {
+                    if !self.flats.has_mesh_group(group)
+                        || mesh >= self.flats.mesh_count(group)
+                        || index >= self.flats.mesh_instance_count(group, mesh)
+                    {
+                        continue;
+                    }
+                    self.flats.get_meshes_mut(group, mesh)[index] = world;
+                    match flats.iter_mut().find(|(g, m, _)| (*g, *m) == (group, mesh)) {
+                        Some((_, _, range)) => widen(range, index),
+                        None => flats.push((group, mesh, index..index + 1)),
+                    }
+                }
             }
         }
+        self.queued_uploads.extend(
+            sprites
+                .into_iter()
+                .map(|(group, range)| Upload::Sprite(group, range))
+                .chain(
+                    meshes
+                        .into_iter()
+                        .map(|(group, mesh, range)| Upload::Mesh(group, mesh, range)),
+                )
+                .chain(
+                    flats
+                        .into_iter()
+                        .map(|(group, mesh, range)| Upload::Flat(group, mesh, range)),
+                ),
+        );
     }
 
     /// Acquire the next frame, create a [`wgpu::RenderPass`], draw
@@ -296,51 +1558,459 @@ impl Renderer {
     /// mesh, sprite, or other instance data, so if you don't use
     /// [`Renderer::render`] in your code be sure to call [`Renderer::do_uploads`] if you're
     /// using the built-in mesh, flat, or sprite renderers.
+    ///
+    /// Panics if a frame can't be acquired; see [`Renderer::try_render`].
     pub fn render(&mut self) {
-        self.do_uploads();
-        let Some((frame, view, mut encoder)) = self.render_setup() else {
-            return;
+        if let Err(err) = self.try_render() {
+            panic!("Failed to acquire next swap chain texture: {err}");
+        }
+    }
+    /// Like [`Renderer::render`], but returns an error instead of
+    /// panicking if the surface can't provide a frame (see
+    /// [`RenderError`]), in which case nothing is drawn.
+    pub fn try_render(&mut self) -> Result<(), RenderError> {
+        trace_span!("frenderer::render");
+        self.prepare_frame();
+        let Some((frame, view, mut encoder)) = self.try_render_setup()? else {
+            return Ok(());
         };
+        let format = self.surface_view_format();
+        let size = self.surface_size();
+        self.draw_frame(&mut encoder, &view, format, Some(size));
         {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
+            trace_span!("frenderer::render::submit");
+            self.render_finish(frame, encoder);
+        }
+        self.finish_frame();
+        Ok(())
+    }
+    /// Like [`Renderer::render`], but draws into the given
+    /// [`ViewTarget`] instead of the surface, so that frenderer can be
+    /// drawn as a viewport inside another wgpu application (see
+    /// [`crate::embed`]).  The surface isn't touched and needn't
+    /// exist.  The debug overlay is only drawn if the target has the
+    /// surface's format.
+    pub fn render_to_view(&mut self, target: &ViewTarget) -> Result<(), ViewTargetError> {
+        trace_span!("frenderer::render_to_view");
+        target.validate(&self.gpu)?;
+        self.prepare_frame();
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("embed:encoder"),
+                });
+        self.draw_frame(&mut encoder, target.color, target.color_format, None);
+        if let Some((depth, format)) = target.depth {
+            trace_span!("frenderer::render_to_view::depth");
+            self.depth_copy
+                .get_or_insert_with(|| DepthCopy::new(&self.gpu))
+                .copy(
+                    &self.gpu,
+                    &mut encoder,
+                    self.depth_texture.as_ref(),
+                    depth,
+                    format,
+                );
+        }
+        {
+            trace_span!("frenderer::render_to_view::submit");
+            self.gpu.queue().submit(Some(encoder.finish()));
+        }
+        self.finish_frame();
+        Ok(())
+    }
+    /// Like [`Renderer::render`], but draws into an offscreen
+    /// [`RenderTarget`], whose texture can then be drawn by sprite or
+    /// mesh groups (see [`crate::target`]).
+    pub fn render_to(&mut self, target: &RenderTarget) -> Result<(), ViewTargetError> {
+        self.render_to_view(&target.view_target())
+    }
+    /// Creates a [`RenderTarget`] of the given size, with an sRGB
+    /// color texture like the spritesheets from
+    /// [`Renderer::create_array_texture`] usually are (so images drawn
+    /// from it look like the scene did) and a depth texture in the
+    /// renderer's depth format, if it has one.
+    pub fn create_render_target(&self, (width, height): (u32, u32)) -> RenderTarget {
+        RenderTarget::new(
+            &self.gpu,
+            (width, height),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            self.depth_format(),
+        )
+        .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Uploads the frame's data and updates the cameras of the
+    /// renderers' extra views.
+    fn prepare_frame(&mut self) {
+        self.do_uploads();
+        self.update_frame_stats();
+        if self.sprites.debug_view() != crate::sprites::DebugView::Off {
+            self.sprites.update_debug_view(&self.gpu);
+        }
+        if self.pick.is_some() {
+            self.sprites.prepare_pick(&self.gpu);
+            self.meshes.prepare_pick(&self.gpu);
+            self.flats.prepare_pick(&self.gpu);
+        }
+        if self.velocity.is_some() {
+            self.meshes.prepare_velocity(&self.gpu);
+            self.flats.prepare_velocity(&self.gpu);
+        }
+        if let Some(particles) = self.particles.as_mut() {
+            particles.prepare(&self.gpu, self.meshes.camera());
+        }
+        if let Some(sun) = self.sun.as_mut() {
+            // The sun follows the flat lighting's direction
+            let direction = self
+                .flats
+                .lighting()
+                .map_or(sun.direction(), |lighting| lighting.direction);
+            sun.update(direction, &self.meshes.camera());
+            self.meshes.update_sun_shadows(&self.gpu, sun);
+            self.flats.update_sun_shadows(&self.gpu, sun);
+        }
+        // Emissive sprites only need a glow texture while bloom is on
+        let glowing = self.postprocess.bloom().is_some() && self.sprites.has_emissive();
+        if glowing != self.glow.is_some() {
+            self.glow = glowing.then(|| {
+                GlowPass::new(
+                    &self.gpu,
+                    self.render_width,
+                    self.render_height,
+                    self.color_texture.format(),
+                    self.depth_format(),
+                )
+            });
+            self.postprocess
+                .set_glow(&self.gpu, self.glow.as_ref().map(GlowPass::texture));
+        }
+        if self.deferred.is_some() {
+            self.meshes.prepare_gbuffer(&self.gpu);
+            self.flats.prepare_gbuffer(&self.gpu);
+        }
+        if let Some((stereo, views)) = self.stereo.as_ref() {
+            let stereo = *stereo.stereo();
+            let meshes = stereo.eye_cameras(&self.meshes.camera());
+            let flats = stereo.eye_cameras(&self.flats.camera());
+            for (views, (mesh_camera, flat_camera)) in
+                views.iter().zip(meshes.into_iter().zip(flats))
+            {
+                self.meshes
+                    .set_camera_view(&self.gpu, views.meshes.unwrap(), mesh_camera);
+                self.flats
+                    .set_camera_view(&self.gpu, views.flats.unwrap(), flat_camera);
+            }
+        }
+        if let Some((mirror, views)) = self.mirror.as_ref() {
+            let mesh_camera = self.meshes.camera();
+            let flat_camera = self.flats.camera();
+            let meshes = mirror.mirror().reflected_projection_view(&mesh_camera);
+            let flats = mirror.mirror().reflected_projection_view(&flat_camera);
+            self.meshes.set_camera_view_matrix(
+                &self.gpu,
+                views.meshes.unwrap(),
+                mesh_camera,
+                meshes.into(),
+            );
+            self.flats.set_camera_view_matrix(
+                &self.gpu,
+                views.flats.unwrap(),
+                flat_camera,
+                flats.into(),
+            );
+        }
+    }
+    /// Encodes every pass of a frame, ending with one which draws the
+    /// final image into `view`, whose format is `format`.
+    // `size` is the size of `view`, if known, which is needed to
+    // letterbox a virtual resolution
+    fn draw_frame(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        size: Option<(u32, u32)>,
+    ) {
+        let surface_format = format == self.surface_view_format();
+        self.colorgeo.set_color_target(&self.gpu, format.into());
+        // Views other than the surface get standard dynamic range
+        self.colorgeo
+            .set_hdr_output(&self.gpu, self.hdr_output.filter(|_| surface_format));
+        if self.capture_next {
+            capture::start();
+        }
+        if let Some(sun) = self.sun.as_ref() {
+            trace_span!("frenderer::render::sun_shadows");
+            let mut rpass = sun.begin_pass(encoder);
+            for cascade in 0..sun.cascades().len() {
+                sun.set_cascade_viewport(&mut rpass, cascade);
+                self.meshes
+                    .render_sun_shadow(&mut rpass, &crate::GroupSelection::All, cascade);
+                self.flats
+                    .render_sun_shadow(&mut rpass, &crate::GroupSelection::All, cascade);
+            }
+        }
+        if let Some(shadows) = self.point_shadows.as_ref() {
+            trace_span!("frenderer::render::point_shadows");
+            for (light, _) in self
+                .flats
+                .point_lights()
+                .iter()
+                .enumerate()
+                .filter(|(_, light)| light.shadows)
+            {
+                for face in 0..6 {
+                    let mut rpass = shadows.begin_face_pass(encoder, light, face);
+                    self.meshes.render_point_shadow(
+                        &mut rpass,
+                        &crate::GroupSelection::All,
+                        light,
+                        face,
+                    );
+                    self.flats.render_point_shadow(
+                        &mut rpass,
+                        &crate::GroupSelection::All,
+                        light,
+                        face,
+                    );
+                }
+            }
+        }
+        // The reflection is drawn first so that the main pass can sample it
+        if let Some((mirror, views)) = self.mirror.as_ref() {
+            trace_span!("frenderer::render::mirror");
+            let mut rpass = mirror.begin_pass(encoder, self.pass_depth_view());
+            self.meshes
+                .render_selected(&mut rpass, &mirror.mirror().meshes, views.meshes);
+            self.flats
+                .render_selected(&mut rpass, &mirror.mirror().flats, views.flats);
+        }
+        // Stereo rendering draws the meshes forward into each eye instead
+        let deferred = self.deferred.as_ref().filter(|_| self.stereo.is_none());
+        if let Some((gbuffer, _)) = deferred {
+            trace_span!("frenderer::render::gbuffer");
+            let mut rpass = gbuffer.begin_pass(encoder);
+            self.meshes
+                .render_gbuffer(&mut rpass, &crate::GroupSelection::All);
+            self.flats
+                .render_gbuffer(&mut rpass, &crate::GroupSelection::All);
+        }
+        if let Some((stereo, views)) = self.stereo.as_ref() {
+            trace_span!("frenderer::render::stereo");
+            for (eye, views) in [Eye::Left, Eye::Right].into_iter().zip(views) {
+                let mut rpass = stereo.begin_eye_pass(encoder, eye, self.pass_depth_view());
+                self.meshes
+                    .render_with_view(&mut rpass, .., views.meshes.unwrap());
+                self.flats
+                    .render_with_view(&mut rpass, .., views.flats.unwrap());
+            }
+            capture::begin_pass("stereo:composite");
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("stereo:composite"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.color_texture_view,
-                    resolve_target: None,
+                    view: self
+                        .msaa
+                        .as_ref()
+                        .map_or(&self.color_texture_view, MsaaTargets::color_view),
+                    resolve_target: self.msaa.as_ref().map(|_| &self.color_texture_view),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        load: self.render_pass_config.color_load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
+                ..Default::default()
+            });
+            stereo.render(&mut rpass);
+        }
+        {
+            trace_span!("frenderer::render::main");
+            capture::begin_pass("main");
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self
+                        .msaa
+                        .as_ref()
+                        .map_or(&self.color_texture_view, MsaaTargets::color_view),
+                    resolve_target: self.msaa.as_ref().map(|_| &self.color_texture_view),
+                    ops: wgpu::Operations {
+                        // In stereo mode, sprites are drawn over the combined eye images
+                        load: if self.stereo.is_some() {
+                            wgpu::LoadOp::Load
+                        } else {
+                            self.render_pass_config.color_load
+                        },
+                        store: self.render_pass_config.color_store,
+                    },
+                })],
+                depth_stencil_attachment: self.pass_depth_view().map(|view| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: self.render_pass_config.depth_load,
+                            store: self.render_pass_config.depth_store,
+                        }),
+                        stencil_ops: None,
+                    }
                 }),
+                timestamp_writes: self.gpu_timer().map(|timer| timer.begin_writes()),
                 ..Default::default()
             });
-            self.render_into(&mut rpass);
+            if self.stereo.is_some() {
+                self.sprites.render(&mut rpass, ..);
+            } else if let Some((_, lighting)) = deferred {
+                lighting.resolve(&mut rpass);
+                let forward = RenderSelection {
+                    meshes: crate::GroupSelection::Groups(vec![]),
+                    flats: crate::GroupSelection::Groups(vec![]),
+                    ..RenderSelection::default()
+                };
+                self.render_into_selected(&mut rpass, &forward, CameraViews::default());
+            } else {
+                self.render_into(&mut rpass);
+            }
+        }
+        // Later passes read depth from the single sampled depth texture
+        if let (Some(msaa), Some(depth_view)) =
+            (self.msaa.as_ref(), self.depth_texture_view.as_ref())
+        {
+            msaa.resolve_depth(encoder, depth_view);
+        }
+        if let Some(lighting) = self.lighting2d.as_mut() {
+            trace_span!("frenderer::render::lighting2d");
+            lighting.render(&self.gpu, encoder, &self.color_texture_view);
+        }
+        // Fog reads the depth texture, so it can't be drawn in the main
+        // pass; in stereo mode the depth texture doesn't hold the meshes
+        if let Some(fog) = self
+            .fog
+            .as_mut()
+            .filter(|_| self.depth_texture.is_some() && self.stereo.is_none())
+        {
+            trace_span!("frenderer::render::fog");
+            fog.render(&self.gpu, encoder, &self.color_texture_view);
+        }
+        if let Some(pick) = self.pick.as_ref() {
+            trace_span!("frenderer::render::pick");
+            let mut rpass = pick.begin_render_pass(encoder);
+            self.meshes.render_pick(&mut rpass, ..);
+            self.flats.render_pick(&mut rpass, ..);
+            self.sprites.render_pick(&mut rpass, ..);
+        }
+        if let Some(velocity) = self.velocity.as_ref() {
+            trace_span!("frenderer::render::velocity");
+            {
+                let mut rpass = velocity.begin_render_pass(encoder);
+                self.meshes.render_velocity(&mut rpass, ..);
+                self.flats.render_velocity(&mut rpass, ..);
+            }
+            self.meshes.finish_velocity(encoder);
+            self.flats.finish_velocity(encoder);
+        }
+        if self.sprites.has_outlines() || self.meshes.has_outlines() || self.flats.has_outlines() {
+            self.render_outlines(encoder);
+        }
+        if self.highlight.is_some()
+            && (self.sprites.has_highlights()
+                || self.meshes.has_highlights()
+                || self.flats.has_highlights())
+        {
+            self.render_highlights(encoder);
+        }
+        if let Some(glow) = self.glow.as_ref() {
+            trace_span!("frenderer::render::glow");
+            let mut rpass = glow.begin_render_pass(encoder);
+            self.sprites.render_emissive(&mut rpass, ..);
+        }
+        if self.postprocess.is_active() {
+            trace_span!("frenderer::render::postprocess");
+            self.postprocess
+                .render(&self.gpu, encoder, &self.color_texture);
         }
         {
+            trace_span!("frenderer::render::present");
+            capture::begin_pass("present");
+            let letterbox = size.filter(|_| self.virtual_resolution.is_some());
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
+                        // The bars are cleared
+                        load: if letterbox.is_some() {
+                            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
                         store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: None,
+                timestamp_writes: self.gpu_timer().map(|timer| timer.end_writes()),
                 ..Default::default()
             });
-            self.postprocess.render(&mut rpass);
+            if let Some(size) = letterbox {
+                let [x, y, w, h] = Self::letterbox(Some(self.render_size()), size);
+                rpass.set_viewport(x as f32, y as f32, w as f32, h as f32, 0.0, 1.0);
+                self.colorgeo.render(&mut rpass);
+                // The overlay covers the whole target
+                rpass.set_viewport(0.0, 0.0, size.0 as f32, size.1 as f32, 0.0, 1.0);
+            } else {
+                self.colorgeo.render(&mut rpass);
+            }
+            // The overlay's pipeline is built for the surface's format
+            if let Some((overlay, _)) = self.overlay.as_ref().filter(|_| surface_format) {
+                overlay.render(&mut rpass);
+            }
+        }
+        if let Some((_, Some(timer))) = self.overlay.as_mut() {
+            timer.resolve(encoder);
+        }
+    }
+    fn finish_frame(&mut self) {
+        if let Some((_, Some(timer))) = self.overlay.as_mut() {
+            timer.after_submit();
+        }
+        if std::mem::take(&mut self.capture_next) {
+            self.last_capture = capture::finish();
+        }
+    }
+    fn gpu_timer(&self) -> Option<&GpuTimer> {
+        self.overlay.as_ref().and_then(|(_, timer)| timer.as_ref())
+    }
+    /// Measures the frame that's about to be drawn and refreshes the
+    /// debug overlay, if it's visible.
+    fn update_frame_stats(&mut self) {
+        let now = crate::clock::Instant::now();
+        let stats = &mut self.frame_stats;
+        if let Some(last) = self.last_frame.replace(now) {
+            stats.frame_time = (now - last).as_secs_f32();
+            if stats.frame_time > 0.0 {
+                let fps = 1.0 / stats.frame_time;
+                stats.fps = if stats.fps > 0.0 {
+                    stats.fps * 0.9 + fps * 0.1
+                } else {
+                    fps
+                };
+            }
+        }
+        stats.upload_bytes = std::mem::take(&mut self.upload_bytes);
+        stats.coalesced_bytes = std::mem::take(&mut self.coalesced_bytes);
+        let counts = [
+            self.sprites.draw_counts(),
+            self.meshes.draw_counts(),
+            self.flats.draw_counts(),
+        ];
+        stats.draw_calls = counts.iter().map(|c| c.0).sum();
+        stats.instances = counts.iter().map(|c| c.1).sum();
+        if let Some((overlay, timer)) = self.overlay.as_mut() {
+            stats.gpu_time = timer.as_mut().and_then(|timer| timer.poll(&self.gpu));
+            overlay.update(&self.gpu, stats, [self.config.width, self.config.height]);
+        } else {
+            stats.gpu_time = None;
         }
-        self.render_finish(frame, encoder);
     }
     /// Renders all the frenderer stuff into a given
     /// [`wgpu::RenderPass`].  Just does rendering of the built-in
@@ -350,12 +2020,160 @@ impl Renderer {
     where
         's: 'pass,
     {
-        self.meshes.render(rpass, ..);
-        self.flats.render(rpass, ..);
-        self.sprites.render(rpass, ..);
+        self.render_into_with_views(rpass, CameraViews::default())
+    }
+    /// Like [`Renderer::render_into`], but draws with the given camera
+    /// views in place of the renderers' own cameras, so the same
+    /// groups can be drawn several times in a frame from different
+    /// points of view (e.g. into a second pass with its own
+    /// viewport).  The grid is only drawn when neither mesh renderer
+    /// uses a view.
+    pub fn render_into_with_views<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        views: CameraViews,
+    ) where
+        's: 'pass,
+    {
+        self.render_into_selected(rpass, &RenderSelection::default(), views)
+    }
+    /// Like [`Renderer::render_into_with_views`], but only draws the
+    /// selected groups of each renderer, so that custom passes
+    /// (shadows, minimaps, reflections) can draw just the groups
+    /// they need.  The grid is only drawn if it's selected and
+    /// neither mesh renderer uses a view.
+    pub fn render_into_selected<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        selection: &RenderSelection,
+        views: CameraViews,
+    ) where
+        's: 'pass,
+    {
+        self.meshes
+            .render_selected(rpass, &selection.meshes, views.meshes);
+        self.flats
+            .render_selected(rpass, &selection.flats, views.flats);
+        if let (Some(skinned), true, None) =
+            (self.skinned.as_ref(), selection.skinned, views.meshes)
+        {
+            skinned.render(rpass);
+        }
+        // The grid is blended over opaque meshes
+        if let (Some(grid), true, None, None) = (
+            self.grid.as_ref(),
+            selection.grid,
+            views.meshes,
+            views.flats,
+        ) {
+            grid.render(rpass);
+        }
+        // Tile maps use the sprites' view, which they can't follow
+        if selection.tilemaps && views.sprites.is_none() {
+            for tilemap in self.tilemaps.iter().flatten() {
+                tilemap.render(rpass);
+            }
+        }
+        self.sprites
+            .render_selected(rpass, &selection.sprites, views.sprites);
+        // Particles have their own camera, which views can't replace
+        if let (Some(particles), true, true) = (
+            self.particles.as_ref(),
+            selection.particles,
+            views == CameraViews::default(),
+        ) {
+            particles.render(rpass);
+        }
+        // Debug shapes go over everything
+        if let (Some(debug_draw), true) =
+            (self.debug_draw.as_ref(), views == CameraViews::default())
+        {
+            debug_draw.render(rpass);
+        }
+    }
+    /// Draws outlined sprites and meshes into the outline mask, then
+    /// draws their outlines onto the color texture.
+    fn render_outlines(&self, encoder: &mut wgpu::CommandEncoder) {
+        trace_span!("frenderer::render::outlines");
+        {
+            capture::begin_pass("outline:mask");
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("outline:mask"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.outline.mask_texture_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            self.meshes.render_outline_mask(&mut rpass, ..);
+            self.flats.render_outline_mask(&mut rpass, ..);
+            self.sprites.render_outline_mask(&mut rpass, ..);
+        }
+        capture::begin_pass("outline:composite");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("outline:composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.color_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        self.outline.render(&mut rpass);
+    }
+    /// Draws highlighted sprites and meshes into the highlight mask,
+    /// then tints and outlines them on the color texture.
+    fn render_highlights(&self, encoder: &mut wgpu::CommandEncoder) {
+        trace_span!("frenderer::render::highlights");
+        let Some((highlight, _)) = self.highlight.as_ref() else {
+            return;
+        };
+        {
+            capture::begin_pass("highlight:mask");
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("highlight:mask"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: highlight.mask_texture_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            self.meshes.render_highlight_mask(&mut rpass, ..);
+            self.flats.render_highlight_mask(&mut rpass, ..);
+            self.sprites.render_highlight_mask(&mut rpass, ..);
+        }
+        capture::begin_pass("highlight:composite");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("highlight:composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.color_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        highlight.render(&mut rpass);
     }
     /// Convenience method for acquiring a surface texture, view, and
-    /// command encoder.  If this returns `None` it means the surface isn't ready yet.
+    /// command encoder.  If this returns `None` it means the surface
+    /// isn't ready yet or is dormant (see [`Renderer::resize_surface`]).
+    ///
+    /// Panics if a frame can't be acquired; see
+    /// [`Renderer::try_render_setup`].
     pub fn render_setup(
         &self,
     ) -> Option<(
@@ -363,22 +2181,47 @@ impl Renderer {
         wgpu::TextureView,
         wgpu::CommandEncoder,
     )> {
+        self.try_render_setup()
+            .unwrap_or_else(|err| panic!("Failed to acquire next swap chain texture: {err}"))
+    }
+    /// Like [`Renderer::render_setup`], but returns an error instead
+    /// of panicking if the surface can't provide a frame.  If the
+    /// surface is outdated or lost (e.g. after the window was resized
+    /// or minimized), it's reconfigured and asked once more first.
+    #[allow(clippy::type_complexity)]
+    pub fn try_render_setup(
+        &self,
+    ) -> Result<
+        Option<(
+            wgpu::SurfaceTexture,
+            wgpu::TextureView,
+            wgpu::CommandEncoder,
+        )>,
+        RenderError,
+    > {
+        if self.surface_dormant {
+            return Ok(None);
+        }
         let Some(surface) = self.surface.as_ref() else {
             println!("render_setup called before surface was ready");
-            return None;
+            return Ok(None);
+        };
+        let frame = match surface.get_current_texture() {
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                surface.configure(self.gpu.device(), &self.config);
+                surface.get_current_texture()?
+            }
+            frame => frame?,
         };
-        let frame = surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
         let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
-            format: Some(self.config.view_formats[1]),
+            format: Some(self.surface_view_format()),
             ..Default::default()
         });
         let encoder = self
             .gpu
             .device()
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        Some((frame, view, encoder))
+        Ok(Some((frame, view, encoder)))
     }
     /// Convenience method for submitting a command encoder and
     /// presenting the swapchain image.
@@ -394,6 +2237,75 @@ impl Renderer {
     pub fn render_size(&self) -> (u32, u32) {
         (self.render_width, self.render_height)
     }
+    /// Shows or hides the built-in debug overlay (see
+    /// [`crate::overlay`]), which is drawn over the final image in
+    /// the top-left corner of the surface.
+    pub fn debug_overlay_set_visible(&mut self, visible: bool) {
+        if !visible {
+            self.overlay = None;
+        } else if self.overlay.is_none() {
+            let overlay = DebugOverlay::new(&self.gpu, self.surface_view_format().into());
+            self.overlay = Some((overlay, GpuTimer::new(&self.gpu)));
+        }
+    }
+    /// Returns true if the debug overlay is visible.
+    pub fn debug_overlay_visible(&self) -> bool {
+        self.overlay.is_some()
+    }
+    /// Returns statistics about the most recent frame drawn by
+    /// [`Renderer::render`].  GPU times are only measured while the
+    /// debug overlay is visible.
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+    /// Returns the renderer's transform hierarchy (see [`crate::hierarchy`]).
+    pub fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+    /// Returns the renderer's transform hierarchy for modification;
+    /// instances attached to its nodes are updated on the next
+    /// [`Renderer::do_uploads`].
+    pub fn hierarchy_mut(&mut self) -> &mut Hierarchy {
+        &mut self.hierarchy
+    }
+    /// Records every pass, pipeline, bind group, buffer, and draw
+    /// call of the next frame drawn by [`Renderer::render`]; fetch
+    /// the result afterwards with [`Renderer::take_capture`].
+    pub fn capture_next_frame(&mut self) {
+        self.capture_next = true;
+    }
+    /// Returns the most recent frame capture, if there is one which
+    /// hasn't been taken yet.
+    pub fn take_capture(&mut self) -> Option<FrameCapture> {
+        self.last_capture.take()
+    }
+    /// Starts recording the sprite groups, mesh groups, instances, and
+    /// cameras of the next `frames` frames (see [`crate::replay`]),
+    /// replacing any recording in progress.  `key` is called for each
+    /// group as it's first recorded to get the name of the asset it
+    /// was made from, as with [`Renderer::scene`].  Frames are
+    /// recorded by [`Renderer::do_uploads`], which [`Renderer::render`]
+    /// calls; fetch the result afterwards with [`Renderer::take_recording`].
+    pub fn record_start(&mut self, frames: usize, key: impl FnMut(SceneGroup) -> String + 'static) {
+        self.last_recording = None;
+        self.recorder = Some(Recorder::new(frames, Box::new(key)));
+    }
+    /// Returns true while a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+    /// Ends a recording in progress early, keeping the frames
+    /// recorded so far for [`Renderer::take_recording`].
+    pub fn record_stop(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            self.last_recording = Some(recorder.finish());
+        }
+    }
+    /// Returns the most recent finished recording, if there is one
+    /// which hasn't been taken yet.
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.last_recording.take()
+    }
     /// Creates an array texture on the renderer's GPU.
     pub fn create_array_texture(
         &self,
@@ -401,6 +2313,99 @@ impl Renderer {
         format: wgpu::TextureFormat,
         (width, height): (u32, u32),
         label: Option<&str>,
+    ) -> wgpu::Texture {
+        self.array_texture(
+            images,
+            format,
+            (width, height),
+            label,
+            (1, wgpu::TextureUsages::empty()),
+        )
+    }
+    /// Creates an array texture from `layers` images packed one
+    /// after another in `data`, as they are when decoded from an
+    /// atlas or a texture array file, without splitting them into
+    /// separate buffers first.  Each layer is written straight from
+    /// `data`, like the images given to [`Renderer::create_array_texture`].
+    /// Panics if `layers` is 0 or `data` doesn't hold exactly
+    /// `layers` images of `width` by `height` texels in `format`.
+    pub fn create_array_texture_dense(
+        &self,
+        data: &[u8],
+        layers: u32,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        let (block_w, block_h) = format.block_dimensions();
+        let block_size = format
+            .block_copy_size(None)
+            .unwrap_or_else(|| panic!("Can't create {format:?} textures from data"));
+        let layer_size = width.div_ceil(block_w) as usize
+            * height.div_ceil(block_h) as usize
+            * block_size as usize;
+        assert!(
+            layers > 0 && data.len() == layer_size * layers as usize,
+            "Array texture data has the wrong size for {layers} {width}x{height} layers of {format:?}"
+        );
+        let images: Vec<&[u8]> = data.chunks_exact(layer_size).collect();
+        self.create_array_texture(&images, format, (width, height), label)
+    }
+    /// Like [`Renderer::create_array_texture`], but allocates a full
+    /// mip chain and generates it on the GPU (see
+    /// [`crate::mipmap`]), so that the texture is filtered smoothly
+    /// when it's drawn small.  The format must be one that can be
+    /// drawn into and filtered, such as
+    /// [`wgpu::TextureFormat::Rgba8UnormSrgb`].
+    pub fn create_texture_with_mips(
+        &mut self,
+        images: &[&[u8]],
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        let texture = self.array_texture(
+            images,
+            format,
+            (width, height),
+            label,
+            (
+                crate::mipmap::mip_level_count(width, height),
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ),
+        );
+        self.generate_mips(&texture);
+        texture
+    }
+    /// Creates an array texture from a KTX2 file, uploading BCn or
+    /// ASTC data directly when the GPU supports it and transcoding it
+    /// to RGBA8 otherwise (see [`crate::ktx2`]).
+    pub fn create_texture_ktx2(
+        &self,
+        data: &[u8],
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, crate::ktx2::Ktx2Error> {
+        let image = crate::ktx2::Ktx2Image::parse(data)?;
+        crate::ktx2::create_texture(&self.gpu, &image, label)
+    }
+    /// Regenerates mip levels `1..` of every layer of a texture from
+    /// its first level, e.g. after drawing into it or updating it
+    /// with [`Renderer::update_texture_region`].  Panics if the
+    /// texture can't be drawn into (see
+    /// [`crate::mipmap::MipGenerator::generate`]).
+    pub fn generate_mips(&mut self, texture: &wgpu::Texture) {
+        let gpu = &self.gpu;
+        self.mipmaps
+            .get_or_insert_with(|| MipGenerator::new(gpu))
+            .generate(gpu, texture);
+    }
+    fn array_texture(
+        &self,
+        images: &[&[u8]],
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+        (mip_level_count, usage): (u32, wgpu::TextureUsages),
     ) -> wgpu::Texture {
         let size = wgpu::Extent3d {
             width,
@@ -419,11 +2424,11 @@ impl Renderer {
         let texture = self.gpu.device().create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | usage,
             view_formats: &[],
         });
         for (layer, img) in images.iter().enumerate() {
@@ -458,6 +2463,7 @@ impl Renderer {
         }
         // again, if it's opengl we may need to copy our first texture again to the last (bonus) layer index.
         if size.depth_or_array_layers > images.len() as u32 {
+            self.gpu.mark_padded(&texture);
             self.gpu.queue().write_texture(
                 wgpu::ImageCopyTexture {
                     texture: &texture,
@@ -484,6 +2490,90 @@ impl Renderer {
         }
         texture
     }
+    /// Overwrites a `width` by `height` region of one layer of a
+    /// texture (created with [`Renderer::create_texture`] or
+    /// [`Renderer::create_array_texture`]) whose top left corner is
+    /// at `origin`, e.g. to add glyphs to a font atlas or paint into
+    /// a minimap without recreating the whole texture.  `data` holds
+    /// the region's rows tightly packed in the texture's format.
+    /// Panics if the region doesn't fit in the texture or `data` is
+    /// the wrong size.
+    pub fn update_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        layer: u32,
+        origin: (u32, u32),
+        (width, height): (u32, u32),
+        data: &[u8],
+    ) {
+        let format = texture.format();
+        let (block_w, block_h) = format.block_dimensions();
+        let block_size = format
+            .block_copy_size(None)
+            .unwrap_or_else(|| panic!("Can't update regions of {format:?} textures"));
+        // On GL, the extra layer of a 1- or 6-image array texture is a
+        // copy of the first one, so it isn't updated on its own
+        let padding = self.gpu.padding_layer(texture);
+        let layers = padding.unwrap_or(texture.depth_or_array_layers());
+        assert!(
+            origin.0 + width <= texture.width()
+                && origin.1 + height <= texture.height()
+                && layer < layers,
+            "Region {width}x{height} at {origin:?} of layer {layer} is outside of a {}x{}x{layers} texture",
+            texture.width(),
+            texture.height(),
+        );
+        let bytes_per_row = width.div_ceil(block_w) * block_size;
+        let rows = height.div_ceil(block_h);
+        assert_eq!(
+            data.len(),
+            (bytes_per_row * rows) as usize,
+            "Texture region data has the wrong size for a {width}x{height} region of {format:?}"
+        );
+        let bonus = padding.filter(|_| layer == 0);
+        for z in std::iter::once(layer).chain(bonus) {
+            self.gpu.queue().write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: origin.0,
+                        y: origin.1,
+                        z,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+    /// Re-uploads one whole layer of an array texture, e.g. to hot
+    /// reload one frame of an animated sprite sheet or one page of a
+    /// tileset.  Other layers are left alone, and groups drawing
+    /// from the texture pick up the new image without being rebuilt.
+    /// `data` must be sized like the images the texture was created
+    /// with.  On GL, textures created from one or six images also
+    /// hold a copy of their first layer, which is replaced along with
+    /// it and can't be replaced on its own.
+    pub fn replace_array_layer(&self, texture: &wgpu::Texture, layer: u32, data: &[u8]) {
+        self.update_texture_region(
+            texture,
+            layer,
+            (0, 0),
+            (texture.width(), texture.height()),
+            data,
+        );
+    }
     /// Creates a single texture on the renderer's GPU.
     pub fn create_texture(
         &self,
@@ -532,14 +2622,96 @@ impl Renderer {
         self.sprites
             .add_sprite_group(&self.gpu, tex, world_transforms, sheet_regions, camera)
     }
+    /// Like [`Renderer::sprite_group_add`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// group wouldn't fit on this GPU.
+    pub fn try_sprite_group_add(
+        &mut self,
+        tex: &wgpu::Texture,
+        world_transforms: Vec<crate::sprites::Transform>,
+        sheet_regions: Vec<crate::sprites::SheetRegion>,
+        camera: crate::sprites::Camera2D,
+    ) -> Result<usize, crate::LimitError> {
+        self.sprites
+            .try_add_sprite_group(&self.gpu, tex, world_transforms, sheet_regions, camera)
+    }
+    /// Like [`Renderer::sprite_group_add`], but the group is drawn
+    /// with a custom shader: a new material registered from `shader`,
+    /// which is either a `material` snippet or a whole fragment shader
+    /// using the sprite renderer's vertex stage and bindings (see
+    /// [`crate::material`]).  Returns the group's index and the
+    /// material's id, e.g. to update its parameters.
+    pub fn sprite_group_add_with_shader(
+        &mut self,
+        tex: &wgpu::Texture,
+        world_transforms: Vec<crate::sprites::Transform>,
+        sheet_regions: Vec<crate::sprites::SheetRegion>,
+        camera: crate::sprites::Camera2D,
+        shader: &MaterialDesc,
+    ) -> (usize, MaterialId) {
+        let group = self.sprite_group_add(tex, world_transforms, sheet_regions, camera);
+        let material = self.material_add(shader);
+        self.sprite_group_set_material(group, Some(material));
+        (group, material)
+    }
     /// Returns the number of sprite groups (including placeholders for removed groups).
     pub fn sprite_group_count(&self) -> usize {
         self.sprites.sprite_group_count()
     }
     /// Deletes a sprite group, leaving an empty group slot behind (this might get recycled later).
     pub fn sprite_group_remove(&mut self, which: usize) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.removed_sprite_group(which);
+        }
+        self.upload_priorities.remove(&UploadGroup::Sprites(which));
+        self.panels.remove(&which);
+        self.palettes.remove(&which);
+        self.sprite_states.remove(&which);
         self.sprites.remove_sprite_group(which)
     }
+    /// Adds a sprite group which draws a nine-slice panel (see
+    /// [`crate::nineslice`]) from the given spritesheet, returning
+    /// the group's index.  The group is resized and redrawn whenever
+    /// the panel changes, so UI boxes can be moved and resized
+    /// without stretching their borders; otherwise it's an ordinary
+    /// sprite group, with its own camera, layer, and blend mode.
+    pub fn nineslice_panel_add(
+        &mut self,
+        tex: &wgpu::Texture,
+        panel: crate::nineslice::Panel,
+        camera: crate::sprites::Camera2D,
+    ) -> usize {
+        let count = panel.sprite_count();
+        let mut trfs = vec![crate::sprites::Transform::ZERO; count];
+        let mut uvs = vec![crate::sprites::SheetRegion::ZERO; count];
+        panel.draw(&mut trfs, &mut uvs);
+        let which = self.sprite_group_add(tex, trfs, uvs, camera);
+        self.panels.insert(which, panel);
+        which
+    }
+    /// Returns the panel drawn by a sprite group, if it's a nine-slice panel group.
+    pub fn nineslice_panel(&self, which: usize) -> Option<&crate::nineslice::Panel> {
+        self.panels.get(&which)
+    }
+    /// Replaces the panel drawn by a nine-slice panel group and
+    /// redraws the group's sprites to match.
+    pub fn nineslice_panel_set(&mut self, which: usize, panel: crate::nineslice::Panel) {
+        self.sprite_group_resize(which, panel.sprite_count());
+        let (trfs, uvs) = self.sprites_mut(which, ..);
+        panel.draw(trfs, uvs);
+        self.panels.insert(which, panel);
+    }
+    /// Moves and resizes a nine-slice panel group's panel to cover
+    /// `(x, y)` to `(x + w, y + h)` and redraws it.  Panics if the
+    /// group isn't a nine-slice panel group.
+    pub fn nineslice_panel_set_rect(&mut self, which: usize, x: f32, y: f32, w: f32, h: f32) {
+        let mut panel = self
+            .panels
+            .remove(&which)
+            .expect("Not a nine-slice panel group");
+        (panel.x, panel.y, panel.w, panel.h) = (x, y, w, h);
+        self.nineslice_panel_set(which, panel);
+    }
     /// Reports the size of the given sprite group.  Panics if the given sprite group is not populated.
     pub fn sprite_group_size(&self, which: usize) -> usize {
         self.sprites.sprite_group_size(which)
@@ -554,15 +2726,78 @@ impl Renderer {
     pub fn sprite_group_resize(&mut self, which: usize, len: usize) -> usize {
         self.sprites.resize_sprite_group(&self.gpu, which, len)
     }
+    /// Like [`Renderer::sprite_group_resize`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// group's buffers wouldn't fit on this GPU.
+    pub fn try_sprite_group_resize(
+        &mut self,
+        which: usize,
+        len: usize,
+    ) -> Result<usize, crate::LimitError> {
+        self.sprites.try_resize_sprite_group(&self.gpu, which, len)
+    }
+    /// Makes sure the given sprite group's GPU buffers have room for
+    /// at least `capacity` sprites (see
+    /// [`SpriteRenderer::reserve_sprite_group`]).  Panics if the
+    /// given sprite group is not populated.
+    pub fn sprite_group_reserve(&mut self, which: usize, capacity: usize) {
+        self.sprites
+            .reserve_sprite_group(&self.gpu, which, capacity)
+    }
+    /// Sets how the sprite, mesh, and flat renderers grow and shrink
+    /// their groups' GPU buffers as groups are resized.
+    pub fn set_growth_policy(&mut self, growth: crate::GrowthPolicy) {
+        self.sprites.set_growth_policy(growth);
+        self.meshes.set_growth_policy(growth);
+        self.flats.set_growth_policy(growth);
+    }
+    /// Draws only the given ranges of a sprite group, or all of it
+    /// if `visible` is `None` (see
+    /// [`SpriteRenderer::set_visible_ranges`]).  Panics if the given
+    /// sprite group is not populated.
+    pub fn sprite_group_set_visible(&mut self, which: usize, visible: Option<Vec<Range<usize>>>) {
+        self.sprites.set_visible_ranges(&self.gpu, which, visible)
+    }
+    /// Culls a sprite group to the sprites of `tree` which its
+    /// camera can see, merging runs of visible sprites separated by
+    /// at most `max_gap` hidden ones into one draw (see
+    /// [`crate::quadtree`]).  Call this again whenever the camera or
+    /// the tree changes.  Panics if the given sprite group is not
+    /// populated.
+    pub fn sprite_group_cull(
+        &mut self,
+        which: usize,
+        tree: &crate::quadtree::SpriteQuadtree,
+        max_gap: usize,
+    ) {
+        let camera = self.sprites.effective_camera(which);
+        let visible = tree.visible_ranges(&camera, max_gap);
+        self.sprite_group_set_visible(which, Some(visible))
+    }
     /// Set the given camera transform on a specific sprite group.  Uploads to the GPU.
     /// Panics if the given sprite group is not populated.
     pub fn sprite_group_set_camera(&mut self, which: usize, camera: crate::sprites::Camera2D) {
         self.sprites.set_camera(&self.gpu, which, camera)
     }
+    /// Adds a camera view which sprite groups can be drawn with via
+    /// [`Renderer::render_into_with_views`], returning its index.
+    pub fn sprite_add_camera_view(&mut self, camera: crate::sprites::Camera2D) -> usize {
+        self.sprites.add_camera_view(&self.gpu, camera)
+    }
+    /// Changes the camera of a sprite camera view.  Uploads to the GPU.
+    pub fn sprite_set_camera_view(&mut self, view: usize, camera: crate::sprites::Camera2D) {
+        self.sprites.set_camera_view(&self.gpu, view, camera)
+    }
+    /// Deletes a sprite camera view.
+    pub fn sprite_remove_camera_view(&mut self, view: usize) {
+        self.sprites.remove_camera_view(view)
+    }
     /// Get a mutable slice of a specified sprite group's world transforms and texture regions.
     /// Marks these sprites for later upload.
     /// Since this causes an upload later on, call it as few times as possible per frame.
-    /// Most importantly, don't call it with lots of tiny or overlapped regions.
+    /// Overlapping and adjacent ranges marked in the same frame are
+    /// uploaded together, but lots of tiny, scattered ranges are each
+    /// uploaded on their own.
     ///
     /// Panics if the given sprite group is not populated or the range is out of bounds.
     pub fn sprites_mut(
@@ -581,19 +2816,916 @@ impl Renderer {
         let (trfs, uvs) = self.sprites.get_sprites_mut(which);
         (&mut trfs[range.clone()], &mut uvs[range])
     }
-
-    /// Sets the given camera for all textured mesh groups.
-    pub fn mesh_set_camera(&mut self, camera: crate::meshes::Camera3D) {
-        self.meshes.set_camera(&self.gpu, camera)
-    }
-    /// Add a mesh group with the given array texture.  All meshes in
-    /// the group pull from the same vertex buffer, and each submesh
-    /// is defined in terms of a range of indices within that buffer.
-    /// When loading your mesh resources from whatever format they're
-    /// stored in, fill out vertex and index vecs while tracking the
-    /// beginning and end of each mesh and submesh (see
-    /// [`crate::meshes::MeshEntry`] for details).
-    pub fn mesh_group_add(
+    /// Writes sprites into a sprite group starting at `start`, and
+    /// marks only the sprites whose transforms or texture regions
+    /// actually changed for upload, unlike [`Renderer::sprites_mut`]
+    /// which uploads the whole range it's given.  This suits large,
+    /// mostly-static groups (like HUDs) which are rebuilt every
+    /// frame.  Returns whether any sprite changed.
+    ///
+    /// Panics if the given sprite group is not populated, if
+    /// `transforms` and `regions` have different lengths, or if they
+    /// run past the end of the group.
+    pub fn sprites_write(
+        &mut self,
+        which: usize,
+        start: usize,
+        transforms: &[crate::sprites::Transform],
+        regions: &[crate::sprites::SheetRegion],
+    ) -> bool {
+        assert_eq!(
+            transforms.len(),
+            regions.len(),
+            "Sprites need as many transforms as texture regions"
+        );
+        let end = start + transforms.len();
+        let count = self.sprite_group_size(which);
+        assert!(end <= count, "Sprites {start}..{end} out of range {count}");
+        let (trfs, uvs) = self.sprites.get_sprites_mut(which);
+        let runs = write_changed(&mut trfs[start..end], transforms)
+            .into_iter()
+            .chain(write_changed(&mut uvs[start..end], regions));
+        let len = self.queued_uploads.len();
+        self.queued_uploads
+            .extend(runs.map(|run| Upload::Sprite(which, (start + run.start)..(start + run.end))));
+        self.queued_uploads.len() > len
+    }
+    /// Writes one sprite of a sprite group, marking it for upload
+    /// only if it changed (see [`Renderer::sprites_write`]).
+    pub fn sprite_write(
+        &mut self,
+        which: usize,
+        index: usize,
+        transform: crate::sprites::Transform,
+        region: crate::sprites::SheetRegion,
+    ) -> bool {
+        self.sprites_write(which, index, &[transform], &[region])
+    }
+    /// Remembers a sprite group's transforms as its previous
+    /// simulated state for [`Renderer::sprites_lerp`].  Call this
+    /// before each simulation step which moves the group.
+    ///
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_store_state(&mut self, which: usize) {
+        let previous = self.sprites.get_sprites(which).0.to_vec();
+        self.sprite_states.entry(which).or_default().previous = previous;
+    }
+    /// Draws a sprite group's transforms interpolated from the state
+    /// stored by [`Renderer::sprite_group_store_state`] (at `alpha`
+    /// 0) to the current one (at 1) in the next frame, so that
+    /// rendering more often than a fixed-timestep simulation doesn't
+    /// stutter; `alpha` is typically
+    /// [`crate::clock::Clock::interpolation`].  Sprites added since
+    /// the state was stored are drawn as they are.  The interpolated
+    /// transforms are computed while uploading and only sent to the
+    /// GPU, so the group's transforms (as seen by
+    /// [`Renderer::sprites_mut`], scenes, the hierarchy, and so on)
+    /// stay the simulated ones.  Does nothing if no state was stored
+    /// for the group.
+    pub fn sprites_lerp(&mut self, which: usize, alpha: f32) {
+        if let Some(state) = self.sprite_states.get_mut(&which) {
+            state.alpha = Some(alpha);
+        }
+    }
+    /// Get a mutable slice of a specified sprite group's tints, RGBA
+    /// bytes which multiply each sprite's color and fade it out (see
+    /// [`crate::sprites::NO_TINT`]).  Marks these sprites for later
+    /// upload, like [`Renderer::sprites_mut`].
+    ///
+    /// Panics if the given sprite group is not populated or the range is out of bounds.
+    pub fn sprite_tints_mut(
+        &mut self,
+        which: usize,
+        range: impl RangeBounds<usize>,
+    ) -> &mut [[u8; 4]] {
+        let count = self.sprite_group_size(which);
+        let range = crate::range(range, count);
+        self.queued_uploads
+            .push(Upload::Sprite(which, range.clone()));
+        &mut self.sprites.get_tints_mut(which)[range]
+    }
+
+    /// Outlines the given range of sprites in a sprite group with
+    /// the given color, or removes the group's outline if `color` is
+    /// `None` (see [`crate::outline`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_outline(
+        &mut self,
+        which: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.sprites.set_outline(&self.gpu, which, color, range)
+    }
+    /// Highlights or unhighlights the given range of sprites in a
+    /// sprite group (see [`Renderer::highlight_set`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_highlighted(
+        &mut self,
+        which: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.sprites.set_highlighted(which, range, highlighted)
+    }
+    /// Returns true if the given sprite is highlighted.
+    pub fn sprite_is_highlighted(&self, which: usize, index: usize) -> bool {
+        self.sprites.is_highlighted(which, index)
+    }
+    /// Gives a sprite group an emissive layer laid out like its
+    /// spritesheet, or removes it if `emissive` is `None` (see
+    /// [`crate::sprites::SpriteRenderer::set_emissive`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_emissive(&mut self, which: usize, emissive: Option<&wgpu::Texture>) {
+        self.sprites.set_emissive(&self.gpu, which, emissive)
+    }
+    /// Sets whether a sprite group is depth tested and writes depth,
+    /// e.g. [`DepthMode::OVERLAY`] for UI drawn over 3D geometry (see
+    /// [`crate::sprites::SpriteRenderer::set_depth_mode`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_depth_mode(&mut self, which: usize, depth_mode: DepthMode) {
+        self.sprites.set_depth_mode(&self.gpu, which, depth_mode)
+    }
+    /// Returns how a sprite group interacts with the depth buffer.
+    pub fn sprite_group_depth_mode(&self, which: usize) -> DepthMode {
+        self.sprites.depth_mode(which)
+    }
+    /// Sets how a sprite group's colors combine with what's behind
+    /// them, e.g. [`BlendMode::Alpha`] for smoke and fades or
+    /// [`BlendMode::Additive`] for glows; call it right after
+    /// [`Renderer::sprite_group_add`] (see
+    /// [`crate::sprites::SpriteRenderer::set_blend_mode`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_blend_mode(&mut self, which: usize, blend_mode: BlendMode) {
+        self.sprites.set_blend_mode(&self.gpu, which, blend_mode)
+    }
+    /// Returns how a sprite group's colors combine with what's behind them.
+    pub fn sprite_group_blend_mode(&self, which: usize) -> BlendMode {
+        self.sprites.blend_mode(which)
+    }
+    /// Puts a sprite group on a render layer (0 by default); groups
+    /// are drawn from the lowest layer to the highest, and in index
+    /// order within a layer, so e.g. backgrounds, gameplay, and the
+    /// HUD can be reordered at any time (see
+    /// [`crate::sprites::SpriteRenderer::set_layer`]).  Panics if the
+    /// given sprite group is not populated.
+    pub fn sprite_group_set_layer(&mut self, which: usize, layer: i32) {
+        self.sprites.set_layer(&self.gpu, which, layer)
+    }
+    /// Returns a sprite group's render layer.
+    pub fn sprite_group_layer(&self, which: usize) -> i32 {
+        self.sprites.layer(which)
+    }
+    /// Sets whether a sprite group is positioned in world space through
+    /// its camera or directly in screen space, e.g. for HUD elements
+    /// (see [`crate::sprites::SpriteRenderer::set_coordinate_mode`]).
+    /// Screen-space groups follow [`Renderer::resize_render`].
+    /// Panics if the given sprite group is not populated.
+    pub fn sprite_group_set_coordinate_mode(&mut self, which: usize, mode: CoordinateMode) {
+        self.sprites.set_coordinate_mode(&self.gpu, which, mode)
+    }
+    /// Returns which coordinate space a sprite group is positioned in.
+    pub fn sprite_group_coordinate_mode(&self, which: usize) -> CoordinateMode {
+        self.sprites.coordinate_mode(which)
+    }
+    /// Draws a sprite group with a registered material, or with
+    /// regular sprite shading if `material` is `None` (see
+    /// [`crate::sprites::SpriteRenderer::set_material`]).
+    pub fn sprite_group_set_material(&mut self, which: usize, material: Option<MaterialId>) {
+        let material = material.map(|id| self.material(id).clone());
+        self.palettes.remove(&which);
+        self.sprites
+            .set_material(&self.gpu, which, material.as_ref())
+    }
+    /// Draws a sprite group with indexed colors looked up in the given
+    /// sRGB palette, or with regular sprite shading if `colors` is
+    /// `None` (see [`crate::palette`]).  Changing a group's palette
+    /// only uploads the new colors.  A palette takes the place of the
+    /// group's material, if it has one.
+    pub fn sprite_group_set_palette(&mut self, which: usize, colors: Option<&[[u8; 4]]>) {
+        match (colors, self.palettes.get_mut(&which)) {
+            (Some(colors), Some(palette)) => palette.set_colors(&self.gpu, colors),
+            (Some(colors), None) => {
+                let palette = Palette::new(&self.gpu, colors);
+                self.sprites
+                    .set_material(&self.gpu, which, Some(palette.material()));
+                self.palettes.insert(which, palette);
+            }
+            (None, Some(_)) => {
+                self.palettes.remove(&which);
+                self.sprites.set_material(&self.gpu, which, None);
+            }
+            (None, None) => {}
+        }
+    }
+    /// Returns the colors of a sprite group's palette, if it has one.
+    pub fn sprite_group_palette(&self, which: usize) -> Option<&[[u8; 4]]> {
+        self.palettes.get(&which).map(Palette::colors)
+    }
+    /// Creates a material and adds it to the renderer's registry (see [`crate::material`]).
+    pub fn material_add(&mut self, desc: &MaterialDesc) -> MaterialId {
+        let material = Material::new(&self.gpu, desc);
+        if let Some(idx) = self.materials.iter().position(|m| m.is_none()) {
+            self.materials[idx] = Some(material);
+            MaterialId(idx)
+        } else {
+            self.materials.push(Some(material));
+            MaterialId(self.materials.len() - 1)
+        }
+    }
+    /// Removes a material from the registry.  Groups drawn with it
+    /// keep using it until they're given another material.
+    pub fn material_remove(&mut self, which: MaterialId) {
+        self.materials[which.0] = None;
+    }
+    /// Returns a registered material.  Panics if it has been removed.
+    pub fn material(&self, which: MaterialId) -> &Material {
+        self.materials[which.0]
+            .as_ref()
+            .expect("Material has been removed")
+    }
+    /// Replaces a material's parameter block (see [`Material::set_params`]).
+    pub fn material_set_params(&mut self, which: MaterialId, params: &[u8]) {
+        self.material(which).set_params(&self.gpu, params)
+    }
+    /// Turns a sprite debug view on or off (see
+    /// [`crate::sprites::DebugView`]); it's kept up to date with
+    /// sprite changes as frames are rendered.
+    /// Panics if the view shows a sprite group which is not populated.
+    pub fn sprite_set_debug_view(&mut self, view: crate::sprites::DebugView) {
+        self.sprites.set_debug_view(&self.gpu, view)
+    }
+    /// Sets the largest sprite group which can be batched together
+    /// with its neighbors, or turns batching off if `limit` is 0 (see
+    /// [`SpriteRenderer::set_batch_limit`]).
+    pub fn sprite_set_batch_limit(&mut self, limit: usize) {
+        self.sprites.set_batch_limit(&self.gpu, limit)
+    }
+    /// Returns the thickness of outlines in pixels.
+    pub fn outline_thickness(&self) -> f32 {
+        self.outline.thickness()
+    }
+    /// Sets the thickness of outlines in pixels (at most [`crate::outline::MAX_THICKNESS`]).
+    pub fn outline_set_thickness(&mut self, thickness: f32) {
+        self.outline.set_thickness(&self.gpu, thickness)
+    }
+    /// Turns drawing of selection highlights on with the given
+    /// appearance, or off if `highlight` is `None` (see
+    /// [`crate::highlight`]).  Instances stay flagged as highlighted
+    /// while highlights aren't drawn.
+    pub fn highlight_set(&mut self, highlight: Option<Highlight>) {
+        let Some(highlight) = highlight else {
+            self.highlight = None;
+            return;
+        };
+        let (pass, current) = self.highlight.get_or_insert_with(|| {
+            (
+                OutlinePass::new(
+                    &self.gpu,
+                    self.render_width,
+                    self.render_height,
+                    self.color_texture.format().into(),
+                ),
+                highlight,
+            )
+        });
+        *current = highlight;
+        pass.set_thickness(&self.gpu, highlight.thickness);
+        pass.set_fill(&self.gpu, highlight.fill);
+        self.sprites.set_highlight_color(&self.gpu, highlight.color);
+        self.meshes.set_highlight_color(&self.gpu, highlight.color);
+        self.flats.set_highlight_color(&self.gpu, highlight.color);
+    }
+    /// Returns the appearance of selection highlights, if they're drawn.
+    pub fn highlight(&self) -> Option<Highlight> {
+        self.highlight.as_ref().map(|(_, highlight)| *highlight)
+    }
+    /// Returns the debug drawing for this frame, to add lines and
+    /// shapes to (see [`crate::debug_draw`]).  They're drawn over the
+    /// scene by [`Renderer::render_into`] unless it's drawing through
+    /// camera views, with 2D shapes in render target pixels and 3D
+    /// shapes seen through the textured mesh camera unless other
+    /// cameras are set.  They're uploaded and cleared by
+    /// [`Renderer::do_uploads`] (which [`Renderer::render`] and its
+    /// variants call, even when they can't get a frame from the
+    /// surface).
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        let sample_count = self.msaa();
+        let depth_format = self.depth_format();
+        self.debug_draw.get_or_insert_with(|| {
+            let mut debug_draw =
+                DebugDraw::new(&self.gpu, self.color_texture.format().into(), depth_format);
+            if sample_count > 1 {
+                debug_draw.set_sample_count(&self.gpu, sample_count);
+            }
+            debug_draw
+        })
+    }
+    /// Shows an infinite ground grid, or hides it if `grid` is `None`
+    /// (see [`crate::grid`]).  When first shown the grid is drawn
+    /// from the textured mesh camera; after that it follows
+    /// whichever of [`Renderer::mesh_set_camera`] and
+    /// [`Renderer::flat_set_camera`] was called most recently.
+    pub fn grid_set(&mut self, grid: Option<Grid>) {
+        let Some(grid) = grid else {
+            self.grid = None;
+            return;
+        };
+        if let Some(renderer) = self.grid.as_mut() {
+            renderer.set_grid(&self.gpu, grid);
+        } else {
+            let mut renderer = GridRenderer::new(
+                &self.gpu,
+                self.color_texture.format().into(),
+                self.depth_format(),
+                grid,
+                self.meshes.camera(),
+            );
+            if self.msaa() > 1 {
+                renderer.set_sample_count(&self.gpu, self.msaa());
+            }
+            self.grid = Some(renderer);
+        }
+    }
+    /// Adds a `width` by `height` tile map, initially empty, drawing
+    /// `tile_size` tiles from the layers of `tileset` (see
+    /// [`crate::tiles`]).  Returns the tile map's index.  Tile maps
+    /// are drawn before sprites.  Panics if the map is too large for
+    /// the device.
+    pub fn tilemap_add(
+        &mut self,
+        tileset: &wgpu::Texture,
+        tile_size: [f32; 2],
+        size: (u32, u32),
+        camera: crate::sprites::Camera2D,
+    ) -> usize {
+        match self.try_tilemap_add(tileset, tile_size, size, camera) {
+            Ok(which) => which,
+            Err(err) => panic!("{err}"),
+        }
+    }
+    /// Like [`Renderer::tilemap_add`], but returns an error naming
+    /// the exceeded device limit instead of panicking if the map
+    /// wouldn't fit on this GPU.
+    pub fn try_tilemap_add(
+        &mut self,
+        tileset: &wgpu::Texture,
+        tile_size: [f32; 2],
+        size: (u32, u32),
+        camera: crate::sprites::Camera2D,
+    ) -> Result<usize, crate::LimitError> {
+        let mut tilemap = TileMapRenderer::try_new(
+            &self.gpu,
+            self.color_texture.format().into(),
+            self.depth_format(),
+            tileset,
+            tile_size,
+            size,
+            camera,
+        )?;
+        if self.msaa() > 1 {
+            tilemap.set_sample_count(&self.gpu, self.msaa());
+        }
+        if let Some(which) = self.tilemaps.iter().position(Option::is_none) {
+            self.tilemaps[which] = Some(tilemap);
+            Ok(which)
+        } else {
+            self.tilemaps.push(Some(tilemap));
+            Ok(self.tilemaps.len() - 1)
+        }
+    }
+    /// Deletes a tile map, leaving an empty slot behind (this might get recycled later).
+    pub fn tilemap_remove(&mut self, which: usize) {
+        if let Some(tilemap) = self.tilemaps.get_mut(which) {
+            *tilemap = None;
+        }
+    }
+    /// Returns the given tile map.  Panics if it's not populated.
+    pub fn tilemap(&self, which: usize) -> &TileMapRenderer {
+        self.tilemaps[which]
+            .as_ref()
+            .expect("Tile map not populated")
+    }
+    fn tilemap_mut(&mut self, which: usize) -> (&WGPU, &mut TileMapRenderer) {
+        let tilemap = self.tilemaps[which]
+            .as_mut()
+            .expect("Tile map not populated");
+        (&self.gpu, tilemap)
+    }
+    /// Overwrites a rectangle of a tile map's tiles (see
+    /// [`TileMapRenderer::set_tiles`]).  Panics if the tile map is
+    /// not populated.
+    pub fn tilemap_set_tiles(&mut self, which: usize, pos: (u32, u32), width: u32, tiles: &[u16]) {
+        let (gpu, tilemap) = self.tilemap_mut(which);
+        tilemap.set_tiles(gpu, pos, width, tiles);
+    }
+    /// Sets the camera a tile map is drawn with.  Panics if the tile
+    /// map is not populated.
+    pub fn tilemap_set_camera(&mut self, which: usize, camera: crate::sprites::Camera2D) {
+        let (gpu, tilemap) = self.tilemap_mut(which);
+        tilemap.set_camera(gpu, camera);
+    }
+    /// Moves a tile map's top-left corner (see
+    /// [`TileMapRenderer::set_origin`]).  Panics if the tile map is
+    /// not populated.
+    pub fn tilemap_set_origin(&mut self, which: usize, origin: [f32; 2]) {
+        let (gpu, tilemap) = self.tilemap_mut(which);
+        tilemap.set_origin(gpu, origin);
+    }
+    /// Changes the depth a tile map is drawn at (see
+    /// [`TileMapRenderer::set_depth`]).  Panics if the tile map is
+    /// not populated.
+    pub fn tilemap_set_depth(&mut self, which: usize, depth: u16) {
+        let (gpu, tilemap) = self.tilemap_mut(which);
+        tilemap.set_depth(gpu, depth);
+    }
+    /// Shows volumetric fog over the 3D scene, or hides it if `fog`
+    /// is `None` (see [`crate::fog`]).  Like the grid, fog follows
+    /// whichever mesh camera was set most recently.  Fog needs a
+    /// depth texture, so it isn't drawn in 2D-only mode.
+    pub fn fog_set(&mut self, fog: Option<Fog>) {
+        let Some(fog) = fog else {
+            self.fog = None;
+            return;
+        };
+        if let Some(renderer) = self.fog.as_mut() {
+            renderer.set_fog(fog);
+        } else {
+            let mut renderer = FogRenderer::new(
+                &self.gpu,
+                self.color_texture.format(),
+                fog,
+                self.meshes.camera(),
+            );
+            if let Some(depth) = self.depth_texture.as_ref() {
+                renderer.set_depth(&self.gpu, depth);
+            }
+            self.fog = Some(renderer);
+        }
+    }
+    /// Returns the fog's appearance, if fog is shown.
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref().map(|f| f.fog())
+    }
+    /// Lights everything drawn in the main pass with the given 2D
+    /// lights and occluders, or stops if `lighting` is `None` (see
+    /// [`crate::lighting2d`]).  Until [`Renderer::lighting2d_set_camera`]
+    /// is called, lights are positioned in pixels from the bottom left
+    /// of the render target.
+    pub fn lighting2d_set(&mut self, lighting: Option<Lighting2D>) {
+        let Some(lighting) = lighting else {
+            self.lighting2d = None;
+            return;
+        };
+        if let Some(renderer) = self.lighting2d.as_mut() {
+            renderer.set_lighting(lighting);
+        } else {
+            let (w, h) = (self.render_width, self.render_height);
+            self.lighting2d = Some(Lighting2DRenderer::new(
+                &self.gpu,
+                self.color_texture.format(),
+                (w, h),
+                lighting,
+                crate::sprites::Camera2D {
+                    screen_pos: [0.0, 0.0],
+                    screen_size: [w as f32, h as f32],
+                },
+            ));
+        }
+    }
+    /// Returns the 2D lights and occluders, if 2D lighting is on.
+    pub fn lighting2d(&self) -> Option<&Lighting2D> {
+        self.lighting2d.as_ref().map(|l| l.lighting())
+    }
+    /// Sets the camera 2D lights and occluders are positioned with,
+    /// which should match the camera of the sprites they light.  Does
+    /// nothing if 2D lighting is off.
+    pub fn lighting2d_set_camera(&mut self, camera: crate::sprites::Camera2D) {
+        if let Some(lighting) = self.lighting2d.as_mut() {
+            lighting.set_camera(camera);
+        }
+    }
+    /// Turns stereo rendering of the 3D scene on with the given
+    /// parameters, or off if `stereo` is `None` (see
+    /// [`crate::stereo`]).  Each frame, meshes are drawn once per eye
+    /// from either side of the mesh and flat cameras, and sprites are
+    /// drawn over the combined image.  The grid and fog aren't drawn
+    /// in stereo mode.
+    pub fn stereo_set(&mut self, stereo: Option<Stereo>) {
+        let Some(stereo) = stereo else {
+            if let Some((_, views)) = self.stereo.take() {
+                for views in views {
+                    self.meshes.remove_camera_view(views.meshes.unwrap());
+                    self.flats.remove_camera_view(views.flats.unwrap());
+                }
+            }
+            return;
+        };
+        if let Some((pass, _)) = self.stereo.as_mut() {
+            pass.set_stereo(&self.gpu, stereo);
+            return;
+        }
+        let mut pass = StereoPass::new(
+            &self.gpu,
+            self.render_width,
+            self.render_height,
+            self.color_texture.format(),
+            stereo,
+        );
+        if self.msaa() > 1 {
+            pass.set_sample_count(&self.gpu, self.msaa());
+        }
+        // The eye cameras are updated at the start of each frame
+        let views = [(); 2].map(|_| CameraViews {
+            sprites: None,
+            meshes: Some(self.meshes.add_camera_view(&self.gpu, self.meshes.camera())),
+            flats: Some(self.flats.add_camera_view(&self.gpu, self.flats.camera())),
+        });
+        self.stereo = Some((pass, views));
+    }
+    /// Returns the stereo parameters, if stereo rendering is on.
+    pub fn stereo(&self) -> Option<&Stereo> {
+        self.stereo.as_ref().map(|(pass, _)| pass.stereo())
+    }
+    /// Lights flat and textured meshes with the given point lights,
+    /// replacing any earlier ones (see [`crate::shadow`]).  Panics if
+    /// there are more than [`crate::shadow::MAX_POINT_LIGHTS`].
+    pub fn point_lights_set(&mut self, lights: &[PointLight]) {
+        self.meshes.set_point_lights(&self.gpu, lights);
+        self.flats.set_point_lights(&self.gpu, lights);
+    }
+    /// Returns the point lights.
+    pub fn point_lights(&self) -> &[PointLight] {
+        self.flats.point_lights()
+    }
+    /// Turns shadows of shadow-casting point lights on with `size` by
+    /// `size` shadow maps, or off if `size` is `None`.  While they're
+    /// on, every mesh and flat mesh group is drawn six times per
+    /// shadow-casting light each frame, before the main pass.
+    pub fn point_shadows_set(&mut self, size: Option<u32>) {
+        if self.point_shadows.as_ref().map(PointShadows::size) == size {
+            return;
+        }
+        self.point_shadows = size.map(|size| PointShadows::new(&self.gpu, size));
+        self.meshes
+            .set_point_shadows(&self.gpu, self.point_shadows.as_ref());
+        self.flats
+            .set_point_shadows(&self.gpu, self.point_shadows.as_ref());
+    }
+    /// Returns the size of the point light shadow maps, if point light shadows are on.
+    pub fn point_shadows(&self) -> Option<u32> {
+        self.point_shadows.as_ref().map(PointShadows::size)
+    }
+    /// Points the directional light of flat mesh lighting towards
+    /// `direction` (turning lighting on with the default colors if
+    /// it was off) and has it cast shadows with the given
+    /// configuration, or none if `shadows` is `None` (see
+    /// [`SunShadows`]).  While they're on, the cascades are fitted
+    /// to the mesh camera each frame and every mesh and flat mesh
+    /// group is drawn once per cascade before the main pass.
+    /// Panics if `shadows` has more than
+    /// [`crate::shadow::MAX_CASCADES`] cascades.
+    pub fn set_sun(&mut self, direction: [f32; 3], shadows: Option<ShadowConfig>) {
+        let lighting = crate::meshes::FlatLighting {
+            direction,
+            ..self.flats.lighting().unwrap_or_default()
+        };
+        self.flat_set_lighting(Some(lighting));
+        if self.sun.as_ref().map(SunShadows::config) == shadows.as_ref() {
+            return;
+        }
+        self.sun = shadows.map(|config| SunShadows::new(&self.gpu, config));
+        self.meshes.set_sun_shadows(&self.gpu, self.sun.as_ref());
+        self.flats.set_sun_shadows(&self.gpu, self.sun.as_ref());
+    }
+    /// Returns the sun's shadows, if they're on.
+    pub fn sun_shadows(&self) -> Option<&SunShadows> {
+        self.sun.as_ref()
+    }
+    /// Turns the deferred shading path on or off (see
+    /// [`crate::deferred`]).  While it's on, mesh and flat mesh groups
+    /// are drawn into a G-buffer and then lit in one pass with the
+    /// lights from [`Renderer::deferred_lights_set`] instead of being
+    /// drawn directly; sprites and the grid are still drawn over
+    /// them as usual.  Stereo rendering always draws meshes directly.
+    pub fn deferred_set(&mut self, enabled: bool) {
+        if !enabled {
+            self.deferred = None;
+            return;
+        }
+        if self.deferred.is_some() {
+            return;
+        }
+        let gbuffer = GBuffer::new(&self.gpu, self.render_width, self.render_height);
+        let mut lighting = DeferredLighting::new(
+            &self.gpu,
+            &gbuffer,
+            self.color_texture.format().into(),
+            self.depth_format(),
+        );
+        if self.msaa() > 1 {
+            lighting.set_sample_count(&self.gpu, self.msaa());
+        }
+        lighting.set_camera(&self.gpu, self.meshes.camera());
+        lighting.set_lights(&self.gpu, &self.deferred_lights);
+        self.deferred = Some((gbuffer, lighting));
+    }
+    /// Returns true if the deferred shading path is on.
+    pub fn deferred(&self) -> bool {
+        self.deferred.is_some()
+    }
+    /// Lights the deferred shading path with the given point lights,
+    /// replacing any earlier ones; they're kept while deferred shading
+    /// is off.  Unlike [`Renderer::point_lights_set`], these don't
+    /// cast shadows.  Panics if there are more than
+    /// [`crate::deferred::MAX_DEFERRED_LIGHTS`].
+    pub fn deferred_lights_set(&mut self, lights: &[PointLight]) {
+        assert!(
+            lights.len() <= crate::deferred::MAX_DEFERRED_LIGHTS,
+            "at most {} deferred point lights are supported",
+            crate::deferred::MAX_DEFERRED_LIGHTS
+        );
+        self.deferred_lights = lights.to_vec();
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_lights(&self.gpu, lights);
+        }
+    }
+    /// Returns the deferred shading path's point lights.
+    pub fn deferred_lights(&self) -> &[PointLight] {
+        &self.deferred_lights
+    }
+    /// Returns the G-buffer, if deferred shading is on, for effects
+    /// which need the scene's normals or materials.  It's re-created
+    /// when the render size changes.
+    pub fn gbuffer(&self) -> Option<&GBuffer> {
+        self.deferred.as_ref().map(|(gbuffer, _)| gbuffer)
+    }
+    /// Turns the planar mirror reflection on with the given mirror,
+    /// or off if `mirror` is `None` (see [`crate::mirror`]).  Each
+    /// frame, the mirror's selected mesh and flat groups are drawn
+    /// reflected about its plane into [`Renderer::mirror_texture`]
+    /// before the main pass.
+    pub fn mirror_set(&mut self, mirror: Option<Mirror>) {
+        let Some(mirror) = mirror else {
+            if let Some((_, views)) = self.mirror.take() {
+                self.meshes.remove_camera_view(views.meshes.unwrap());
+                self.flats.remove_camera_view(views.flats.unwrap());
+            }
+            return;
+        };
+        if let Some((pass, _)) = self.mirror.as_mut() {
+            pass.set_mirror(mirror);
+            return;
+        }
+        let mut pass = MirrorPass::new(
+            &self.gpu,
+            self.render_width,
+            self.render_height,
+            self.color_texture.format(),
+            mirror,
+        );
+        if self.msaa() > 1 {
+            pass.set_sample_count(&self.gpu, self.msaa());
+        }
+        // The reflected views are updated at the start of each frame
+        let views = CameraViews {
+            sprites: None,
+            meshes: Some(self.meshes.add_camera_view(&self.gpu, self.meshes.camera())),
+            flats: Some(self.flats.add_camera_view(&self.gpu, self.flats.camera())),
+        };
+        self.mirror = Some((pass, views));
+    }
+    /// Returns the mirror, if the mirror reflection is on.
+    pub fn mirror(&self) -> Option<&Mirror> {
+        self.mirror.as_ref().map(|(pass, _)| pass.mirror())
+    }
+    /// Returns the texture the mirror reflection is drawn into, if
+    /// it's on, for use as a mesh group's texture.  It's re-created
+    /// when the render size or color format (see
+    /// [`Renderer::set_tonemap`]) changes.
+    pub fn mirror_texture(&self) -> Option<&wgpu::Texture> {
+        self.mirror.as_ref().map(|(pass, _)| pass.texture())
+    }
+    /// Turns the object ID pass used for picking on or off (see
+    /// [`crate::pick`]).  While it's on, each frame also draws the
+    /// IDs of sprites and mesh instances into an ID texture.
+    pub fn set_picking(&mut self, enabled: bool) {
+        if !enabled {
+            self.pick = None;
+        } else if self.pick.is_none() {
+            self.pick = Some(PickPass::new(
+                &self.gpu,
+                self.render_width,
+                self.render_height,
+                self.depth_format(),
+            ));
+        }
+    }
+    /// Returns true if the object ID pass used for picking is on.
+    pub fn picking(&self) -> bool {
+        self.pick.is_some()
+    }
+    /// Starts reading back whatever was drawn at the given point on
+    /// the surface (in pixels from the top left) in the last rendered
+    /// frame; the result arrives asynchronously through the returned
+    /// [`PickQuery`].  This assumes an identity post transform, but
+    /// accounts for a virtual resolution's letterboxing (see
+    /// [`Renderer::surface_to_render`]).  Returns `None` if picking is
+    /// off (see [`Renderer::set_picking`]) or the point is outside the
+    /// drawn scene.
+    pub fn pick(&self, screen_point: [f32; 2]) -> Option<PickQuery> {
+        let pick = self.pick.as_ref()?;
+        let [x, y] = self.render_point(screen_point)?;
+        pick.pick(&self.gpu, x, y)
+    }
+    /// Starts reading back the depth buffer at the given point on
+    /// the surface (in pixels from the top left) in the last rendered
+    /// frame, like [`Renderer::pick`]; the result is a single depth.
+    /// Returns `None` in 2D-only mode (see
+    /// [`Renderer::set_depth_format`]), if the depth format or device
+    /// can't read depth back (e.g. on GL), or if the point is outside
+    /// the surface.
+    pub fn depth_at(&self, screen_point: [f32; 2]) -> Option<DepthQuery> {
+        let [x, y] = self.render_point(screen_point)?;
+        self.read_depth_region((x, y), (1, 1))
+    }
+    /// Starts reading back a region of the depth buffer as of the
+    /// last rendered frame, in render target pixels from the top
+    /// left.  Returns `None` in 2D-only mode, if the depth format or
+    /// device can't read depth back, or if the region is empty or out
+    /// of bounds.
+    pub fn read_depth_region(&self, origin: (u32, u32), size: (u32, u32)) -> Option<DepthQuery> {
+        let depth = self.depth_texture.as_ref()?;
+        crate::pick::read_depth(&self.gpu, depth, [origin.0, origin.1], [size.0, size.1])
+    }
+    /// Reads back the last rendered frame as RGBA8 rows from the top
+    /// left, at the render resolution (see
+    /// [`Renderer::render_size`]), waiting for the GPU to finish.
+    /// The image has the post transform, color grading, and LUT
+    /// applied as on screen, but not HDR encoding or the debug
+    /// overlay.  Returns `None` if the read fails, or on the web,
+    /// where it can't wait (see [`crate::readback`]).
+    pub fn read_frame(&mut self) -> Option<Vec<u8>> {
+        trace_span!("frenderer::read_frame");
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let texture = self.gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("readback:frame"),
+            size: wgpu::Extent3d {
+                width: self.render_width,
+                height: self.render_height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // The next frame sets these back for its own target
+        self.colorgeo.set_color_target(&self.gpu, format.into());
+        self.colorgeo.set_hdr_output(&self.gpu, None);
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("readback:frame"),
+                });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("readback:frame"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            self.colorgeo.render(&mut rpass);
+        }
+        self.gpu.queue().submit(Some(encoder.finish()));
+        crate::readback::read_texture(&self.gpu, &texture)?.wait(&self.gpu)
+    }
+    /// Reads back layer 0 of `texture` (e.g. a
+    /// [`RenderTarget::texture`]) as RGBA8 rows from the top left,
+    /// waiting for the GPU to finish.  Returns `None` if the texture
+    /// isn't 8-bit RGBA or BGRA or can't be copied from, or if the
+    /// read fails; see [`crate::readback::read_texture`] to read
+    /// without waiting.
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Option<Vec<u8>> {
+        crate::readback::read_texture(&self.gpu, texture)?.wait(&self.gpu)
+    }
+    // Maps a point on the surface to a pixel of the render target
+    fn render_point(&self, point: [f32; 2]) -> Option<[u32; 2]> {
+        let [x, y] = self.surface_to_render(point)?;
+        Some([x as u32, y as u32])
+    }
+    /// Returns the depth of field parameters, if it's on.
+    pub fn post_depth_of_field(&self) -> Option<&crate::postprocess::DepthOfField> {
+        self.postprocess.depth_of_field()
+    }
+    /// Turns depth of field on with the given parameters, or off with
+    /// `None`.  Focusing by depth uses the mesh camera.
+    pub fn post_set_depth_of_field(
+        &mut self,
+        depth_of_field: Option<crate::postprocess::DepthOfField>,
+    ) {
+        self.postprocess.set_depth_of_field(depth_of_field);
+    }
+    /// Turns motion blur on with the given parameters, or off with
+    /// `None`.  While it's on, each frame also draws the screen-space
+    /// motion of every mesh instance since the previous frame into a
+    /// velocity texture (see [`crate::velocity`]), which the blur
+    /// reads.
+    pub fn post_set_motion_blur(&mut self, motion_blur: Option<MotionBlur>) {
+        if motion_blur.is_none() {
+            self.velocity = None;
+            self.meshes.clear_velocity();
+            self.flats.clear_velocity();
+            self.postprocess.set_velocity(&self.gpu, None);
+        } else if self.velocity.is_none() {
+            let velocity = VelocityPass::new(
+                &self.gpu,
+                self.render_width,
+                self.render_height,
+                self.depth_format(),
+            );
+            self.postprocess
+                .set_velocity(&self.gpu, Some(velocity.texture()));
+            self.velocity = Some(velocity);
+        }
+        self.postprocess.set_motion_blur(motion_blur);
+    }
+    /// Returns the motion blur parameters, if it's on.
+    pub fn post_motion_blur(&self) -> Option<&MotionBlur> {
+        self.postprocess.motion_blur()
+    }
+    /// Turns a color-blindness filter on, simulating or compensating
+    /// for a color vision deficiency, or off with `None` (see
+    /// [`crate::postprocess::ColorVision`]).  This can be switched
+    /// every frame, e.g. from an accessibility menu.
+    pub fn post_set_color_vision(&mut self, color_vision: Option<ColorVision>) {
+        self.postprocess.set_color_vision(color_vision);
+    }
+    /// Returns the color-blindness filter, if it's on.
+    pub fn post_color_vision(&self) -> Option<&ColorVision> {
+        self.postprocess.color_vision()
+    }
+    /// Returns the velocity texture drawn each frame while motion blur
+    /// is on, e.g. for use in temporal antialiasing.
+    pub fn velocity_texture(&self) -> Option<&wgpu::Texture> {
+        self.velocity.as_ref().map(VelocityPass::texture)
+    }
+    /// Returns the grid's appearance, if the grid is shown.
+    pub fn grid(&self) -> Option<&Grid> {
+        self.grid.as_ref().map(|g| g.grid())
+    }
+
+    /// Sets the given camera for all textured mesh groups (and the
+    /// grid, fog, and depth-based post effects, if enabled).
+    pub fn mesh_set_camera(&mut self, camera: crate::meshes::Camera3D) {
+        self.meshes.set_camera(&self.gpu, camera);
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.set_camera(&self.gpu, camera);
+        }
+        self.postprocess.set_camera(camera);
+        if let Some(grid) = self.grid.as_mut() {
+            grid.set_camera(&self.gpu, camera);
+        }
+        if let Some(fog) = self.fog.as_mut() {
+            fog.set_camera(camera);
+        }
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_camera(&self.gpu, camera);
+        }
+    }
+    /// Adds a camera view which textured mesh groups can be drawn with via
+    /// [`Renderer::render_into_with_views`], returning its index.
+    /// Unlike [`Renderer::mesh_set_camera`], this doesn't affect the
+    /// grid, fog, or post effects.
+    pub fn mesh_add_camera_view(&mut self, camera: crate::meshes::Camera3D) -> usize {
+        self.meshes.add_camera_view(&self.gpu, camera)
+    }
+    /// Changes the camera of a textured mesh camera view.
+    pub fn mesh_set_camera_view(&mut self, view: usize, camera: crate::meshes::Camera3D) {
+        self.meshes.set_camera_view(&self.gpu, view, camera)
+    }
+    /// Deletes a textured mesh camera view.
+    pub fn mesh_remove_camera_view(&mut self, view: usize) {
+        self.meshes.remove_camera_view(view)
+    }
+    /// Add a mesh group with the given array texture.  All meshes in
+    /// the group pull from the same vertex buffer, and each submesh
+    /// is defined in terms of a range of indices within that buffer.
+    /// When loading your mesh resources from whatever format they're
+    /// stored in, fill out vertex and index vecs while tracking the
+    /// beginning and end of each mesh and submesh (see
+    /// [`crate::meshes::MeshEntry`] for details).
+    pub fn mesh_group_add(
         &mut self,
         texture: &wgpu::Texture,
         vertices: Vec<crate::meshes::Vertex>,
@@ -603,10 +3735,263 @@ impl Renderer {
         self.meshes
             .add_mesh_group(&self.gpu, texture, vertices, indices, mesh_info)
     }
+    /// Like [`Renderer::mesh_group_add`], but returns an error naming
+    /// the exceeded device limit instead of panicking if the group
+    /// wouldn't fit on this GPU.
+    pub fn try_mesh_group_add(
+        &mut self,
+        texture: &wgpu::Texture,
+        vertices: Vec<crate::meshes::Vertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<crate::meshes::MeshEntry>,
+    ) -> Result<crate::meshes::MeshGroup, crate::LimitError> {
+        self.meshes
+            .try_add_mesh_group(&self.gpu, texture, vertices, indices, mesh_info)
+    }
+    /// Like [`Renderer::mesh_group_add`], but with normal maps in the
+    /// given layers of `texture` (see
+    /// [`crate::meshes::TextureLayers`]).  Tangents are computed for
+    /// vertices which have normals but no tangents.
+    pub fn mesh_group_add_with_layers(
+        &mut self,
+        texture: &wgpu::Texture,
+        layers: crate::meshes::TextureLayers,
+        mut vertices: Vec<crate::meshes::Vertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<crate::meshes::MeshEntry>,
+    ) -> crate::meshes::MeshGroup {
+        crate::meshes::compute_tangents(&mut vertices, &indices, &mesh_info);
+        let group = self.mesh_group_add(texture, vertices, indices, mesh_info);
+        self.meshes
+            .set_texture_layers(&self.gpu, group, Some(layers));
+        group
+    }
+    /// Says which layers of a textured mesh group's texture hold its
+    /// normal maps (see [`crate::meshes::MeshRenderer::set_texture_layers`]).
+    pub fn mesh_group_set_layers(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        layers: Option<crate::meshes::TextureLayers>,
+    ) {
+        self.meshes.set_texture_layers(&self.gpu, which, layers)
+    }
+    /// Adds a group of skinned meshes with the given array texture,
+    /// skeleton size, and number of instances (see
+    /// [`crate::skinned`]), returning its index.  Skinned groups are
+    /// drawn with the textured mesh camera.  Panics if the device
+    /// can't draw skinned meshes or the group would exceed its limits.
+    pub fn skinned_group_add(
+        &mut self,
+        texture: &wgpu::Texture,
+        vertices: &[crate::skinned::SkinnedVertex],
+        indices: &[u32],
+        bone_count: usize,
+        instance_count: usize,
+    ) -> usize {
+        self.try_skinned_group_add(texture, vertices, indices, bone_count, instance_count)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`Renderer::skinned_group_add`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// device can't draw skinned meshes or the group wouldn't fit.
+    pub fn try_skinned_group_add(
+        &mut self,
+        texture: &wgpu::Texture,
+        vertices: &[crate::skinned::SkinnedVertex],
+        indices: &[u32],
+        bone_count: usize,
+        instance_count: usize,
+    ) -> Result<usize, crate::LimitError> {
+        if self.skinned.is_none() {
+            let mut skinned = SkinnedMeshRenderer::try_new(
+                &self.gpu,
+                self.color_texture.format().into(),
+                self.depth_format(),
+            )?;
+            if self.msaa() > 1 {
+                skinned.set_sample_count(&self.gpu, self.msaa());
+            }
+            skinned.set_camera(&self.gpu, self.meshes.camera());
+            self.skinned = Some(skinned);
+        }
+        let (gpu, skinned) = self.skinned_mut();
+        skinned.try_add_group(gpu, texture, vertices, indices, bone_count, instance_count)
+    }
+    /// Deletes a skinned group, leaving an empty placeholder.
+    pub fn skinned_group_remove(&mut self, which: usize) {
+        if let Some(skinned) = self.skinned.as_mut() {
+            skinned.remove_group(which);
+        }
+    }
+    /// Returns the skinned mesh renderer, if a skinned group has been added.
+    pub fn skinned(&self) -> Option<&SkinnedMeshRenderer> {
+        self.skinned.as_ref()
+    }
+    /// Adds a particle emitter which draws from the given array
+    /// texture (or soft dots or plain white if `texture` is `None`),
+    /// returning its index (see [`crate::particles`]).  Particles
+    /// are stepped by [`Renderer::particles_update`] and drawn over
+    /// sprites, through the textured mesh camera unless
+    /// [`Renderer::particles_set_camera`] gives them another.  Panics
+    /// if the device can't simulate particles or the emitter would
+    /// exceed its limits.
+    pub fn particle_emitter_add(
+        &mut self,
+        desc: EmitterDesc,
+        texture: Option<&wgpu::Texture>,
+    ) -> usize {
+        self.try_particle_emitter_add(desc, texture)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`Renderer::particle_emitter_add`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// device can't simulate particles or the emitter wouldn't fit.
+    pub fn try_particle_emitter_add(
+        &mut self,
+        desc: EmitterDesc,
+        texture: Option<&wgpu::Texture>,
+    ) -> Result<usize, crate::LimitError> {
+        if self.particles.is_none() {
+            let mut particles = ParticleRenderer::try_new(
+                &self.gpu,
+                self.color_texture.format().into(),
+                self.depth_format(),
+            )?;
+            if self.msaa() > 1 {
+                particles.set_sample_count(&self.gpu, self.msaa());
+            }
+            self.particles = Some(particles);
+        }
+        let (gpu, particles) = self.particles_mut();
+        particles.add_emitter(gpu, desc, texture)
+    }
+    /// Changes a particle emitter's description (see
+    /// [`ParticleRenderer::set_emitter`]).  Panics if its new
+    /// capacity would exceed the device's limits.
+    pub fn particle_emitter_set(&mut self, which: usize, desc: EmitterDesc) {
+        let (gpu, particles) = self.particles_mut();
+        particles
+            .set_emitter(gpu, which, desc)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Returns a particle emitter's description.
+    pub fn particle_emitter(&self, which: usize) -> &EmitterDesc {
+        self.particles
+            .as_ref()
+            .expect("No particle emitters have been added")
+            .emitter(which)
+    }
+    /// Deletes a particle emitter, leaving an empty placeholder.
+    pub fn particle_emitter_remove(&mut self, which: usize) {
+        if let Some(particles) = self.particles.as_mut() {
+            particles.remove_emitter(which);
+        }
+    }
+    /// Spawns `count` extra particles from an emitter at the next
+    /// [`Renderer::particles_update`].
+    pub fn particle_emitter_burst(&mut self, which: usize, count: u32) {
+        self.particles_mut().1.burst(which, count)
+    }
+    /// Steps every particle emitter forward by `dt` seconds on the
+    /// GPU; call it once per simulation step.
+    pub fn particles_update(&mut self, dt: f32) {
+        if let Some(particles) = self.particles.as_mut() {
+            particles.update(&self.gpu, dt);
+        }
+    }
+    /// Sets the camera particles are seen through, or `None` to
+    /// follow the textured mesh camera.
+    pub fn particles_set_camera(&mut self, camera: Option<ParticleCamera>) {
+        self.particles_mut().1.set_camera(camera)
+    }
+    /// Returns the particle renderer, if an emitter has been added.
+    pub fn particles(&self) -> Option<&ParticleRenderer> {
+        self.particles.as_ref()
+    }
+    fn particles_mut(&mut self) -> (&WGPU, &mut ParticleRenderer) {
+        let particles = self
+            .particles
+            .as_mut()
+            .expect("No particle emitters have been added");
+        (&self.gpu, particles)
+    }
+    fn skinned_mut(&mut self) -> (&WGPU, &mut SkinnedMeshRenderer) {
+        let skinned = self
+            .skinned
+            .as_mut()
+            .expect("No skinned groups have been added");
+        (&self.gpu, skinned)
+    }
+    /// Changes how many instances a skinned group has (see
+    /// [`SkinnedMeshRenderer::resize_group`]).
+    pub fn skinned_instance_resize(&mut self, which: usize, len: usize) {
+        let (gpu, skinned) = self.skinned_mut();
+        skinned.resize_group(gpu, which, len)
+    }
+    /// Gets the (mutable) transforms of every instance of a skinned
+    /// group; they're uploaded with the other instance data.
+    pub fn skinned_instances_mut(&mut self, which: usize) -> &mut [crate::meshes::Transform3D] {
+        self.skinned_mut().1.instances_mut(which)
+    }
+    /// Gets the (mutable) bone matrices of one instance of a skinned
+    /// group, one per bone; they're uploaded with the other instance data.
+    pub fn skinned_bones_mut(
+        &mut self,
+        which: usize,
+        instance: usize,
+    ) -> &mut [crate::skinned::BoneMatrix] {
+        self.skinned_mut().1.bones_mut(which, instance)
+    }
     /// Deletes a mesh group, leaving an empty placeholder.
     pub fn mesh_group_remove(&mut self, which: crate::meshes::MeshGroup) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.removed_mesh_group(MeshKind::Textured, which);
+        }
+        self.upload_priorities.remove(&UploadGroup::Meshes(which));
+        self.mesh_states.retain(|(group, _), _| *group != which);
         self.meshes.remove_mesh_group(which)
     }
+    /// Draws a mesh group with a registered material, or with
+    /// regular textured shading if `material` is `None`.
+    pub fn mesh_group_set_material(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        material: Option<MaterialId>,
+    ) {
+        let material = material.map(|id| self.material(id).clone());
+        self.meshes
+            .set_material(&self.gpu, which, material.as_ref())
+    }
+    /// Draws a textured mesh group with its own camera (e.g. a
+    /// first-person viewmodel or a 3D inset in the UI), or with the
+    /// shared one from [`Renderer::mesh_set_camera`] again if
+    /// `camera` is `None`.  See
+    /// [`crate::meshes::MeshRenderer::set_group_camera`].
+    pub fn mesh_group_set_camera(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        camera: Option<crate::meshes::Camera3D>,
+    ) {
+        self.meshes.set_group_camera(&self.gpu, which, camera)
+    }
+    /// Returns a textured mesh group's own camera, if it has one.
+    pub fn mesh_group_camera(
+        &self,
+        which: crate::meshes::MeshGroup,
+    ) -> Option<crate::meshes::Camera3D> {
+        self.meshes.group_camera(which)
+    }
+    /// Multiplies a mesh group's colors by a lightmap texture (e.g.
+    /// one made with [`Renderer::create_texture`]) at its vertices'
+    /// lightmap coordinates, or stops if `lightmap` is `None`.  See
+    /// [`crate::meshes::MeshRenderer::set_lightmap`].
+    pub fn mesh_group_set_lightmap(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        lightmap: Option<&wgpu::Texture>,
+    ) {
+        self.meshes.set_lightmap(&self.gpu, which, lightmap)
+    }
     /// Returns how many mesh groups there are.
     pub fn mesh_group_count(&self) -> usize {
         self.meshes.mesh_group_count()
@@ -623,6 +4008,16 @@ impl Renderer {
     ) -> usize {
         self.meshes.mesh_instance_count(which, mesh_number)
     }
+    /// Returns the model-space bounds of the given mesh of the given
+    /// mesh group; see [`crate::meshes::MeshBounds::transformed`] to
+    /// get an instance's bounds in world space.
+    pub fn mesh_bounds(
+        &self,
+        which: crate::meshes::MeshGroup,
+        mesh_number: usize,
+    ) -> crate::meshes::MeshBounds {
+        self.meshes.mesh_bounds(which, mesh_number)
+    }
     /// Change the number of instances of the given mesh of the given mesh group.
     pub fn mesh_instance_resize(
         &mut self,
@@ -632,9 +4027,28 @@ impl Renderer {
     ) -> usize {
         self.meshes.resize_group_mesh(&self.gpu, which, idx, len)
     }
+    /// Like [`Renderer::mesh_instance_resize`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// group's instance buffer wouldn't fit on this GPU.
+    pub fn try_mesh_instance_resize(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        len: usize,
+    ) -> Result<usize, crate::LimitError> {
+        self.meshes
+            .try_resize_group_mesh(&self.gpu, which, idx, len)
+    }
+    /// Makes sure the given mesh group's instance buffer has room
+    /// for at least `capacity` instances across all of its meshes.
+    pub fn mesh_group_reserve(&mut self, which: crate::meshes::MeshGroup, capacity: usize) {
+        self.meshes.reserve_mesh_group(&self.gpu, which, capacity)
+    }
     /// Gets the (mutable) transforms of every instance of the given mesh of a mesh group.
     /// Since this causes an upload later on, call it as few times as possible per frame.
-    /// Most importantly, don't call it with lots of tiny regions or overlapped regions.
+    /// Overlapping and adjacent ranges marked in the same frame are
+    /// uploaded together, but lots of tiny, scattered ranges are each
+    /// uploaded on their own.
     pub fn meshes_mut(
         &mut self,
         which: crate::meshes::MeshGroup,
@@ -648,10 +4062,109 @@ impl Renderer {
         let trfs = self.meshes.get_meshes_mut(which, idx);
         &mut trfs[range]
     }
+    /// Writes instance transforms of the given mesh of a mesh group
+    /// starting at `start`, marking only those which changed for
+    /// upload (see [`Renderer::sprites_write`]).  Returns whether any
+    /// instance changed.
+    pub fn meshes_write(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        start: usize,
+        transforms: &[crate::meshes::Transform3D],
+    ) -> bool {
+        let end = start + transforms.len();
+        let count = self.meshes.mesh_instance_count(which, idx);
+        assert!(
+            end <= count,
+            "Instances {start}..{end} out of range {count}"
+        );
+        let trfs = self.meshes.get_meshes_mut(which, idx);
+        let runs = write_changed(&mut trfs[start..end], transforms);
+        let changed = !runs.is_empty();
+        self.queued_uploads.extend(
+            runs.into_iter()
+                .map(|run| Upload::Mesh(which, idx, (start + run.start)..(start + run.end))),
+        );
+        changed
+    }
+    /// Remembers the transforms of every instance of the given mesh
+    /// of a mesh group as their previous simulated state for
+    /// [`Renderer::meshes_lerp`], like [`Renderer::sprite_group_store_state`].
+    pub fn mesh_store_state(&mut self, which: crate::meshes::MeshGroup, idx: usize) {
+        let previous = self.meshes.get_meshes(which, idx).to_vec();
+        self.mesh_states.entry((which, idx)).or_default().previous = previous;
+    }
+    /// Draws the instances of the given mesh of a mesh group
+    /// interpolated from the state stored by [`Renderer::mesh_store_state`]
+    /// to the current one, like [`Renderer::sprites_lerp`].
+    pub fn meshes_lerp(&mut self, which: crate::meshes::MeshGroup, idx: usize, alpha: f32) {
+        if let Some(state) = self.mesh_states.get_mut(&(which, idx)) {
+            state.alpha = Some(alpha);
+        }
+    }
+    /// Outlines the given range of instances of a textured mesh with
+    /// the given color, or removes the mesh's outline if `color` is
+    /// `None` (see [`crate::outline`]).
+    pub fn mesh_set_outline(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.meshes.set_outline(&self.gpu, which, idx, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a
+    /// textured mesh (see [`Renderer::highlight_set`]).
+    pub fn mesh_set_highlighted(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.meshes.set_highlighted(which, idx, range, highlighted)
+    }
+    /// Returns true if the given instance of a textured mesh is highlighted.
+    pub fn mesh_is_highlighted(
+        &self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        instance: usize,
+    ) -> bool {
+        self.meshes.is_highlighted(which, idx, instance)
+    }
 
-    /// Sets the given camera for all flat mesh groups.
+    /// Sets the given camera for all flat mesh groups (and the grid,
+    /// fog, and depth-based post effects, if enabled).
     pub fn flat_set_camera(&mut self, camera: crate::meshes::Camera3D) {
-        self.flats.set_camera(&self.gpu, camera)
+        self.flats.set_camera(&self.gpu, camera);
+        self.postprocess.set_camera(camera);
+        if let Some(grid) = self.grid.as_mut() {
+            grid.set_camera(&self.gpu, camera);
+        }
+        if let Some(fog) = self.fog.as_mut() {
+            fog.set_camera(camera);
+        }
+        if let Some((_, lighting)) = self.deferred.as_mut() {
+            lighting.set_camera(&self.gpu, camera);
+        }
+    }
+    /// Adds a camera view which flat mesh groups can be drawn with via
+    /// [`Renderer::render_into_with_views`], returning its index.
+    /// Unlike [`Renderer::flat_set_camera`], this doesn't affect the
+    /// grid, fog, or post effects.
+    pub fn flat_add_camera_view(&mut self, camera: crate::meshes::Camera3D) -> usize {
+        self.flats.add_camera_view(&self.gpu, camera)
+    }
+    /// Changes the camera of a flat mesh camera view.
+    pub fn flat_set_camera_view(&mut self, view: usize, camera: crate::meshes::Camera3D) {
+        self.flats.set_camera_view(&self.gpu, view, camera)
+    }
+    /// Deletes a flat mesh camera view.
+    pub fn flat_remove_camera_view(&mut self, view: usize) {
+        self.flats.remove_camera_view(view)
     }
     /// Add a flat mesh group with the given color materials.  All
     /// meshes in the group pull from the same vertex buffer, and each
@@ -670,10 +4183,57 @@ impl Renderer {
         self.flats
             .add_mesh_group(&self.gpu, material_colors, vertices, indices, mesh_info)
     }
+    /// Like [`Renderer::flat_group_add`], but returns an error naming
+    /// the exceeded device limit instead of panicking if the group
+    /// wouldn't fit on this GPU.
+    pub fn try_flat_group_add(
+        &mut self,
+        material_colors: &[[f32; 4]],
+        vertices: Vec<crate::meshes::FlatVertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<crate::meshes::MeshEntry>,
+    ) -> Result<crate::meshes::MeshGroup, crate::LimitError> {
+        self.flats
+            .try_add_mesh_group(&self.gpu, material_colors, vertices, indices, mesh_info)
+    }
     /// Deletes a mesh group, leaving an empty placeholder.
     pub fn flat_group_remove(&mut self, which: crate::meshes::MeshGroup) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.removed_mesh_group(MeshKind::Flat, which);
+        }
+        self.upload_priorities.remove(&UploadGroup::Flats(which));
+        self.flat_states.retain(|(group, _), _| *group != which);
         self.flats.remove_mesh_group(which)
     }
+    /// Draws a flat mesh group with a registered material, or with
+    /// plain flat colors if `material` is `None`.
+    pub fn flat_group_set_material(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        material: Option<MaterialId>,
+    ) {
+        let material = material.map(|id| self.material(id).clone());
+        self.flats.set_material(&self.gpu, which, material.as_ref())
+    }
+    /// Draws a flat mesh group with its own camera (e.g. a
+    /// first-person viewmodel or a 3D inset in the UI), or with the
+    /// shared one from [`Renderer::flat_set_camera`] again if
+    /// `camera` is `None`.  See
+    /// [`crate::meshes::FlatRenderer::set_group_camera`].
+    pub fn flat_group_set_camera(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        camera: Option<crate::meshes::Camera3D>,
+    ) {
+        self.flats.set_group_camera(&self.gpu, which, camera)
+    }
+    /// Returns a flat mesh group's own camera, if it has one.
+    pub fn flat_group_camera(
+        &self,
+        which: crate::meshes::MeshGroup,
+    ) -> Option<crate::meshes::Camera3D> {
+        self.flats.group_camera(which)
+    }
     /// Returns how many mesh groups there are.
     pub fn flat_group_count(&self) -> usize {
         self.flats.mesh_group_count()
@@ -690,6 +4250,34 @@ impl Renderer {
     ) -> usize {
         self.flats.mesh_instance_count(which, mesh_number)
     }
+    /// Keeps a copy of the positions and indices of textured and
+    /// flat mesh groups added from now on (or stops, if `keep` is
+    /// false), so that they can be hit by [`Self::raycast_meshes`].
+    pub fn set_keep_mesh_geometry(&mut self, keep: bool) {
+        self.meshes.set_keep_geometry(keep);
+        self.flats.set_keep_geometry(keep);
+    }
+    /// Returns the closest textured or flat mesh instance hit by the
+    /// given world-space ray, among groups which kept their geometry
+    /// (see [`Self::set_keep_mesh_geometry`]).  Instances are tested
+    /// against their bounding boxes before their triangles.
+    pub fn raycast_meshes(&self, ray: &crate::pick::Ray) -> Option<crate::pick::RayHit> {
+        match (self.meshes.raycast(ray), self.flats.raycast(ray)) {
+            (Some(mesh), Some(flat)) if flat.distance < mesh.distance => Some(flat),
+            (Some(mesh), _) => Some(mesh),
+            (None, flat) => flat,
+        }
+    }
+    /// Returns the model-space bounds of the given mesh of the given
+    /// flat mesh group; see [`crate::meshes::MeshBounds::transformed`]
+    /// to get an instance's bounds in world space.
+    pub fn flat_bounds(
+        &self,
+        which: crate::meshes::MeshGroup,
+        mesh_number: usize,
+    ) -> crate::meshes::MeshBounds {
+        self.flats.mesh_bounds(which, mesh_number)
+    }
     /// Change the number of instances of the given mesh of the given mesh group.
     pub fn flat_instance_resize(
         &mut self,
@@ -699,65 +4287,395 @@ impl Renderer {
     ) -> usize {
         self.flats.resize_group_mesh(&self.gpu, which, idx, len)
     }
+    /// Like [`Renderer::flat_instance_resize`], but returns an error
+    /// naming the exceeded device limit instead of panicking if the
+    /// group's instance buffer wouldn't fit on this GPU.
+    pub fn try_flat_instance_resize(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        len: usize,
+    ) -> Result<usize, crate::LimitError> {
+        self.flats.try_resize_group_mesh(&self.gpu, which, idx, len)
+    }
+    /// Makes sure the given flat mesh group's instance buffer has
+    /// room for at least `capacity` instances across all of its meshes.
+    pub fn flat_group_reserve(&mut self, which: crate::meshes::MeshGroup, capacity: usize) {
+        self.flats.reserve_mesh_group(&self.gpu, which, capacity)
+    }
     /// Gets the (mutable) transforms of every instance of the given mesh of a mesh group.
     /// Since this causes an upload later on, call it as few times as possible per frame.
-    /// Most importantly, don't call it with lots of tiny regions or overlapped regions.
+    /// Overlapping and adjacent ranges marked in the same frame are
+    /// uploaded together, but lots of tiny, scattered ranges are each
+    /// uploaded on their own.
     pub fn flats_mut(
         &mut self,
         which: crate::meshes::MeshGroup,
-        idx: usize,
-        range: impl RangeBounds<usize>,
-    ) -> &mut [crate::meshes::Transform3D] {
-        let count = self.flats.mesh_instance_count(which, idx);
-        let range = crate::range(range, count);
-        self.queued_uploads
-            .push(Upload::Flat(which, idx, range.clone()));
-        let trfs = self.flats.get_meshes_mut(which, idx);
-        &mut trfs[range]
+        idx: usize,
+        range: impl RangeBounds<usize>,
+    ) -> &mut [crate::meshes::Transform3D] {
+        let count = self.flats.mesh_instance_count(which, idx);
+        let range = crate::range(range, count);
+        self.queued_uploads
+            .push(Upload::Flat(which, idx, range.clone()));
+        let trfs = self.flats.get_meshes_mut(which, idx);
+        &mut trfs[range]
+    }
+    /// Writes instance transforms of the given mesh of a flat mesh
+    /// group starting at `start`, marking only those which changed
+    /// for upload (see [`Renderer::sprites_write`]).  Returns whether
+    /// any instance changed.
+    pub fn flats_write(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        start: usize,
+        transforms: &[crate::meshes::Transform3D],
+    ) -> bool {
+        let end = start + transforms.len();
+        let count = self.flats.mesh_instance_count(which, idx);
+        assert!(
+            end <= count,
+            "Instances {start}..{end} out of range {count}"
+        );
+        let trfs = self.flats.get_meshes_mut(which, idx);
+        let runs = write_changed(&mut trfs[start..end], transforms);
+        let changed = !runs.is_empty();
+        self.queued_uploads.extend(
+            runs.into_iter()
+                .map(|run| Upload::Flat(which, idx, (start + run.start)..(start + run.end))),
+        );
+        changed
+    }
+    /// Remembers the transforms of every instance of the given mesh
+    /// of a flat mesh group as their previous simulated state for
+    /// [`Renderer::flats_lerp`], like [`Renderer::sprite_group_store_state`].
+    pub fn flat_store_state(&mut self, which: crate::meshes::MeshGroup, idx: usize) {
+        let previous = self.flats.get_meshes(which, idx).to_vec();
+        self.flat_states.entry((which, idx)).or_default().previous = previous;
+    }
+    /// Draws the instances of the given mesh of a flat mesh group
+    /// interpolated from the state stored by [`Renderer::flat_store_state`]
+    /// to the current one, like [`Renderer::sprites_lerp`].
+    pub fn flats_lerp(&mut self, which: crate::meshes::MeshGroup, idx: usize, alpha: f32) {
+        if let Some(state) = self.flat_states.get_mut(&(which, idx)) {
+            state.alpha = Some(alpha);
+        }
+    }
+    /// Outlines the given range of instances of a flat mesh with the
+    /// given color, or removes the mesh's outline if `color` is
+    /// `None` (see [`crate::outline`]).
+    pub fn flat_set_outline(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.flats.set_outline(&self.gpu, which, idx, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a
+    /// flat mesh (see [`Renderer::highlight_set`]).
+    pub fn flat_set_highlighted(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.flats.set_highlighted(which, idx, range, highlighted)
+    }
+    /// Returns true if the given instance of a flat mesh is highlighted.
+    pub fn flat_is_highlighted(
+        &self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        instance: usize,
+    ) -> bool {
+        self.flats.is_highlighted(which, idx, instance)
+    }
+    /// Draws the given range of instances of a flat mesh in a single
+    /// color instead of its group's colors, or removes that override
+    /// if `color` is `None` (see
+    /// [`crate::meshes::FlatRenderer::set_instance_color`]).
+    pub fn flat_set_instance_color(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.flats
+            .set_instance_color(&self.gpu, which, idx, color, range)
+    }
+    /// Returns the color override of an instance of a flat mesh, if any.
+    pub fn flat_instance_color(
+        &self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        instance: usize,
+    ) -> Option<[f32; 4]> {
+        self.flats.instance_color(which, idx, instance)
+    }
+    /// Turns lighting of flat and textured meshes on with the given
+    /// parameters, or off if `lighting` is `None` (see
+    /// [`crate::meshes::FlatRenderer::set_lighting`] and
+    /// [`crate::meshes::MeshRenderer::set_lighting`]).
+    pub fn flat_set_lighting(&mut self, lighting: Option<crate::meshes::FlatLighting>) {
+        self.flats.set_lighting(&self.gpu, lighting);
+        self.meshes.set_lighting(&self.gpu, lighting);
+    }
+    /// Returns the lighting of flat meshes, if any.
+    pub fn flat_lighting(&self) -> Option<crate::meshes::FlatLighting> {
+        self.flats.lighting()
+    }
+    /// Lights textured and flat meshes with the given lights,
+    /// replacing the lighting from [`Renderer::flat_set_lighting`]
+    /// (which is turned off without a directional light) and the
+    /// point lights from [`Renderer::point_lights_set`].  Only
+    /// vertices with normals are lit.  Panics if there's more than
+    /// one directional light or more than
+    /// [`crate::shadow::MAX_POINT_LIGHTS`] point lights.
+    pub fn mesh_set_lights(&mut self, lights: &[crate::meshes::Light]) {
+        use crate::meshes::Light;
+        let mut directional = lights.iter().filter_map(|light| match light {
+            Light::Directional(lighting) => Some(*lighting),
+            Light::Point(_) => None,
+        });
+        let lighting = directional.next();
+        assert!(
+            directional.next().is_none(),
+            "At most one directional light is supported"
+        );
+        let points: Vec<PointLight> = lights
+            .iter()
+            .filter_map(|light| match light {
+                Light::Point(point) => Some(*point),
+                Light::Directional(_) => None,
+            })
+            .collect();
+        self.flat_set_lighting(lighting);
+        self.point_lights_set(&points);
+    }
+    /// Sets the highlights of lit textured meshes (see
+    /// [`crate::meshes::MeshRenderer::set_specular`]).
+    pub fn mesh_set_specular(&mut self, specular: crate::meshes::Specular) {
+        self.meshes.set_specular(&self.gpu, specular)
+    }
+    /// Returns the highlights of lit textured meshes.
+    pub fn mesh_specular(&self) -> crate::meshes::Specular {
+        self.meshes.specular()
+    }
+    /// Draws a textured mesh group with physically based shading, or
+    /// as usual if `params` is `None` (see
+    /// [`crate::meshes::MeshRenderer::set_group_pbr`]).
+    pub fn mesh_group_set_pbr(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        params: Option<crate::pbr::PbrParams>,
+    ) {
+        self.meshes.set_group_pbr(&self.gpu, which, params)
+    }
+    /// Lights physically based mesh groups with the given cubemap, or
+    /// with the hemisphere light if `environment` is `None` (see
+    /// [`crate::meshes::MeshRenderer::set_environment`]).
+    pub fn mesh_set_environment(&mut self, environment: Option<&wgpu::Texture>) {
+        self.meshes.set_environment(&self.gpu, environment)
+    }
+    /// Describes the renderer's sprite groups, mesh groups, flat
+    /// groups, and cameras as a [`crate::scene::Scene`].  `key` is
+    /// called for each group to get the name of the asset it was
+    /// made from (see [`crate::scene`]).  Removed groups are skipped,
+    /// so group indices may differ when the scene is loaded again.
+    pub fn scene(&self, mut key: impl FnMut(SceneGroup) -> String) -> Scene {
+        let sprite_groups = (0..self.sprites.sprite_group_count())
+            .filter(|&which| self.sprites.has_sprite_group(which))
+            .map(|which| {
+                let (transforms, regions) = self.sprites.get_sprites(which);
+                SpriteGroupScene {
+                    texture: key(SceneGroup::Sprite(which)),
+                    camera: self.sprites.camera(which),
+                    transforms: transforms.to_vec(),
+                    regions: regions.to_vec(),
+                }
+            })
+            .collect();
+        let mesh_groups = (0..self.meshes.mesh_group_count())
+            .map(crate::meshes::MeshGroup::from)
+            .filter(|&which| self.meshes.has_mesh_group(which))
+            .map(|which| MeshGroupScene {
+                asset: key(SceneGroup::Mesh(which)),
+                instances: (0..self.meshes.mesh_count(which))
+                    .map(|mesh| self.meshes.get_meshes(which, mesh).to_vec())
+                    .collect(),
+            })
+            .collect();
+        let flat_groups = (0..self.flats.mesh_group_count())
+            .map(crate::meshes::MeshGroup::from)
+            .filter(|&which| self.flats.has_mesh_group(which))
+            .map(|which| MeshGroupScene {
+                asset: key(SceneGroup::Flat(which)),
+                instances: (0..self.flats.mesh_count(which))
+                    .map(|mesh| self.flats.get_meshes(which, mesh).to_vec())
+                    .collect(),
+            })
+            .collect();
+        Scene {
+            mesh_camera: self.meshes.camera(),
+            flat_camera: self.flats.camera(),
+            sprite_groups,
+            mesh_groups,
+            flat_groups,
+        }
+    }
+    /// Adds the groups of a [`crate::scene::Scene`] to the renderer
+    /// and sets its cameras, loading textures and geometry with
+    /// `assets`.  Existing groups are left alone.  If an asset fails
+    /// to load or a mesh group's asset has a different number of
+    /// meshes than the scene, the error is returned and the groups
+    /// added so far are kept.
+    pub fn scene_load(
+        &mut self,
+        scene: &Scene,
+        assets: &mut impl SceneAssets,
+    ) -> Result<LoadedScene, Box<dyn std::error::Error>> {
+        let mut loaded = LoadedScene::default();
+        self.mesh_set_camera(scene.mesh_camera);
+        self.flat_set_camera(scene.flat_camera);
+        for group in scene.sprite_groups.iter() {
+            let texture = assets.sprite_texture(self, &group.texture)?;
+            loaded.sprite_groups.push(self.sprite_group_add(
+                &texture,
+                group.transforms.clone(),
+                group.regions.clone(),
+                group.camera,
+            ));
+        }
+        for group in scene.mesh_groups.iter() {
+            let mut asset = assets.mesh(self, &group.asset)?;
+            crate::scene::check_meshes(&group.asset, &group.instances, &mut asset.meshes)?;
+            let which =
+                self.mesh_group_add(&asset.texture, asset.vertices, asset.indices, asset.meshes);
+            for (mesh, instances) in group.instances.iter().enumerate() {
+                self.meshes_mut(which, mesh, ..).copy_from_slice(instances);
+            }
+            loaded.mesh_groups.push(which);
+        }
+        for group in scene.flat_groups.iter() {
+            let mut asset = assets.flat(self, &group.asset)?;
+            crate::scene::check_meshes(&group.asset, &group.instances, &mut asset.meshes)?;
+            let which = self.flat_group_add(
+                &asset.material_colors,
+                asset.vertices,
+                asset.indices,
+                asset.meshes,
+            );
+            for (mesh, instances) in group.instances.iter().enumerate() {
+                self.flats_mut(which, mesh, ..).copy_from_slice(instances);
+            }
+            loaded.flat_groups.push(which);
+        }
+        Ok(loaded)
     }
     /// Returns the current geometric transform used in postprocessing (a 4x4 column-major homogeneous matrix)
     pub fn post_transform(&self) -> [f32; 16] {
-        self.postprocess.transform()
+        self.colorgeo.transform()
     }
     /// Returns the current color transform used in postprocessing (a 4x4 column-major homogeneous matrix)
     pub fn post_color_transform(&self) -> [f32; 16] {
-        self.postprocess.color_transform()
+        self.colorgeo.color_transform()
     }
     /// Returns the current saturation value in postprocessing (a value between -1 and 1, with 0.0 meaning an identity transformation)
     pub fn post_saturation(&self) -> f32 {
-        self.postprocess.saturation()
+        self.colorgeo.saturation()
     }
     /// Sets all postprocessing parameters
     pub fn post_set(&mut self, trf: [f32; 16], color_trf: [f32; 16], sat: f32) {
-        self.postprocess.set_post(&self.gpu, trf, color_trf, sat);
+        self.colorgeo.set_post(&self.gpu, trf, color_trf, sat);
     }
     /// Sets the postprocessing geometric transform (a 4x4 column-major homogeneous matrix)
     pub fn post_set_transform(&mut self, trf: [f32; 16]) {
-        self.postprocess.set_transform(&self.gpu, trf);
+        self.colorgeo.set_transform(&self.gpu, trf);
     }
     /// Sets the postprocessing color transform (a 4x4 column-major homogeneous matrix)
     pub fn post_set_color_transform(&mut self, trf: [f32; 16]) {
-        self.postprocess.set_color_transform(&self.gpu, trf);
+        self.colorgeo.set_color_transform(&self.gpu, trf);
     }
     /// Sets the postprocessing saturation value (a number between -1 and 1, with 0.0 meaning an identity transformation)
     pub fn post_set_saturation(&mut self, sat: f32) {
-        self.postprocess.set_saturation(&self.gpu, sat);
+        self.colorgeo.set_saturation(&self.gpu, sat);
     }
-    /// Sets the postprocessing color lookup table texture
+    /// Sets the postprocessing color lookup table texture (see [`Renderer::set_color_lut`])
     pub fn post_set_lut(&mut self, lut: &wgpu::Texture) {
-        self.postprocess.replace_lut(&self.gpu, lut);
+        self.colorgeo.replace_lut(&self.gpu, lut);
+    }
+    /// Grades the final image's colors with a 3D lookup table, applied
+    /// after tone mapping (see [`ColorGeo::replace_lut`] for its layout).
+    pub fn set_color_lut(&mut self, lut: &wgpu::Texture) {
+        self.colorgeo.replace_lut(&self.gpu, lut);
+    }
+    /// Goes back to the identity color lookup table.
+    pub fn clear_color_lut(&mut self) {
+        let lut = colorgeo::lut_identity(&self.gpu);
+        self.colorgeo.replace_lut(&self.gpu, &lut);
+    }
+    /// Returns the screen-space ambient occlusion parameters, if it's enabled
+    pub fn post_ssao(&self) -> Option<&crate::postprocess::Ssao> {
+        self.postprocess.ssao()
+    }
+    /// Enables screen-space ambient occlusion computed from the depth
+    /// texture and the mesh camera, or disables it with `None`.  This
+    /// has no effect in 2D-only mode.
+    pub fn post_set_ssao(&mut self, ssao: Option<crate::postprocess::Ssao>) {
+        self.postprocess.set_ssao(ssao);
+    }
+    /// Returns the bloom parameters, if it's enabled
+    pub fn post_bloom(&self) -> Option<&crate::postprocess::Bloom> {
+        self.postprocess.bloom()
+    }
+    /// Enables bloom, or disables it with `None`.  Besides pixels
+    /// over the bloom threshold, the emissive layers of sprite groups
+    /// (see [`Renderer::sprite_group_set_emissive`]) glow; meshes
+    /// don't hide glowing sprites behind them.
+    pub fn post_set_bloom(&mut self, bloom: Option<crate::postprocess::Bloom>) {
+        self.postprocess.set_bloom(bloom);
+    }
+    /// Adds a fullscreen effect which runs after the built-in
+    /// postprocessing effects and any added before it (see
+    /// [`PostProcess::push_effect`] for what `shader` has to define,
+    /// and [`crate::postprocess::VIGNETTE`] and its neighbors for some
+    /// ready-made ones).
+    pub fn post_push_effect(&mut self, label: &str, shader: &str) -> EffectId {
+        self.postprocess.push_effect(label, shader)
+    }
+    /// Sets the parameters of an effect added with [`Renderer::post_push_effect`]
+    pub fn post_set_effect_params(&mut self, effect: EffectId, params: &[[f32; 4]]) {
+        self.postprocess.set_effect_params(effect, params);
+    }
+    /// Removes an effect added with [`Renderer::post_push_effect`],
+    /// returning false if it was already removed
+    pub fn post_remove_effect(&mut self, effect: EffectId) -> bool {
+        self.postprocess.remove_effect(effect)
+    }
+    /// Gets the postprocessing effects applied before the final color transform
+    pub fn postprocess(&self) -> &PostProcess {
+        &self.postprocess
+    }
+    /// Gets the postprocessing effects mutably, e.g. to enable dithering with [`PostProcess::set_dither`]
+    pub fn postprocess_mut(&mut self) -> &mut PostProcess {
+        &mut self.postprocess
     }
     /// Gets the surface configuration
     pub fn config(&self) -> &wgpu::SurfaceConfiguration {
         &self.config
     }
-    /// Gets a reference to the active depth texture
-    pub fn depth_texture(&self) -> &wgpu::Texture {
-        &self.depth_texture
+    /// Gets a reference to the active depth texture, if there is one (see [`Renderer::set_depth_format`])
+    pub fn depth_texture(&self) -> Option<&wgpu::Texture> {
+        self.depth_texture.as_ref()
     }
-    /// Gets a view on the active depth texture
-    pub fn depth_texture_view(&self) -> &wgpu::TextureView {
-        &self.depth_texture_view
+    /// Gets a view on the active depth texture, if there is one (see [`Renderer::set_depth_format`])
+    pub fn depth_texture_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth_texture_view.as_ref()
     }
 }
 
@@ -804,10 +4722,31 @@ impl Immediate {
             used_sets.fill(0);
         }
     }
-    /// Changes the present mode for this renderer
-    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+    /// Changes the present mode for this renderer, returning false if
+    /// the surface doesn't support it
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
         self.renderer.set_present_mode(mode)
     }
+    /// Returns the present modes the surface supports
+    pub fn supported_present_modes(&self) -> Vec<wgpu::PresentMode> {
+        self.renderer.supported_present_modes()
+    }
+    /// Returns the present mode for this renderer
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.renderer.present_mode()
+    }
+    /// Sets how many frames the swapchain may queue up ahead of the display
+    pub fn set_frame_latency(&mut self, frames: u32) {
+        self.renderer.set_frame_latency(frames)
+    }
+    /// Returns how many frames the swapchain may queue up ahead of the display
+    pub fn frame_latency(&self) -> u32 {
+        self.renderer.frame_latency()
+    }
+    /// Sets the frame latency and present mode to one of the [`LatencyMode`] presets
+    pub fn set_latency_mode(&mut self, mode: LatencyMode) {
+        self.renderer.set_latency_mode(mode)
+    }
     /// Returns the current surface
     pub fn surface(&self) -> Option<&wgpu::Surface<'static>> {
         self.renderer.surface()
@@ -820,16 +4759,40 @@ impl Immediate {
     pub fn resize_surface(&mut self, w: u32, h: u32) {
         self.renderer.resize_surface(w, h)
     }
+    /// Returns true if the surface is waiting for a non-zero size before rendering resumes.
+    pub fn surface_dormant(&self) -> bool {
+        self.renderer.surface_dormant()
+    }
     /// Resize the internal color and depth targets (the actual rendering resolution).
     pub fn resize_render(&mut self, w: u32, h: u32) {
         self.renderer.resize_render(w, h)
     }
+    /// Turns a fixed virtual resolution with integer scaling on or
+    /// off (see [`Renderer::virtual_resolution_set`]).
+    pub fn virtual_resolution_set(&mut self, resolution: Option<(u32, u32)>) {
+        self.renderer.virtual_resolution_set(resolution)
+    }
+    /// Returns this frame's debug drawing (see [`Renderer::debug_draw`]).
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        self.renderer.debug_draw()
+    }
     /// Acquire the next frame, create a [`wgpu::RenderPass`], draw
     /// into it, and submit the encoder.  This also queues uploads of
     /// mesh, sprite, or other instance data, so if you don't use
     /// [`Renderer::render`] in your code be sure to call [`Renderer::do_uploads`] if you're
     /// using the built-in mesh, flat, or sprite renderers.
+    ///
+    /// Panics if a frame can't be acquired; see [`Immediate::try_render`].
     pub fn render(&mut self) {
+        if let Err(err) = self.try_render() {
+            panic!("Failed to acquire next swap chain texture: {err}");
+        }
+    }
+    /// Like [`Immediate::render`], but returns an error instead of
+    /// panicking if the surface can't provide a frame (see
+    /// [`Renderer::try_render`]).  The queued instances are cleared
+    /// either way if auto-clearing is on.
+    pub fn try_render(&mut self) -> Result<(), RenderError> {
         // upload affected ranges
         for (sg, used) in self.sprites_used.iter_mut().enumerate() {
             self.renderer
@@ -871,10 +4834,11 @@ impl Immediate {
                 );
             }
         }
-        self.renderer.render();
+        let result = self.renderer.try_render();
         if self.auto_clear {
             self.clear();
         }
+        result
     }
     /// Returns the size of the surface onto which the rendered image is stretched
     pub fn surface_size(&self) -> (u32, u32) {
@@ -895,6 +4859,37 @@ impl Immediate {
         self.renderer
             .create_array_texture(images, format, (width, height), label)
     }
+    /// Creates an array texture from densely packed layers (see [`Renderer::create_array_texture_dense`])
+    pub fn create_array_texture_dense(
+        &self,
+        data: &[u8],
+        layers: u32,
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        self.renderer
+            .create_array_texture_dense(data, layers, format, (width, height), label)
+    }
+    /// Creates an array texture with generated mips (see [`Renderer::create_texture_with_mips`])
+    pub fn create_texture_with_mips(
+        &mut self,
+        images: &[&[u8]],
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        self.renderer
+            .create_texture_with_mips(images, format, (width, height), label)
+    }
+    /// Creates an array texture from a KTX2 file (see [`Renderer::create_texture_ktx2`])
+    pub fn create_texture_ktx2(
+        &self,
+        data: &[u8],
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, crate::ktx2::Ktx2Error> {
+        self.renderer.create_texture_ktx2(data, label)
+    }
     /// Creates a single texture on the renderer's GPU.
     pub fn create_texture(
         &self,
@@ -906,6 +4901,39 @@ impl Immediate {
         self.renderer
             .create_texture(image, format, (width, height), label)
     }
+    /// Creates an offscreen render target on the renderer's GPU (see
+    /// [`Renderer::create_render_target`]).
+    pub fn create_render_target(&self, (width, height): (u32, u32)) -> RenderTarget {
+        self.renderer.create_render_target((width, height))
+    }
+    /// Reads back the last rendered frame as RGBA8 pixels (see
+    /// [`Renderer::read_frame`]).
+    pub fn read_frame(&mut self) -> Option<Vec<u8>> {
+        self.renderer.read_frame()
+    }
+    /// Reads back layer 0 of an 8-bit RGBA or BGRA texture as RGBA8
+    /// pixels (see [`Renderer::read_texture`]).
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Option<Vec<u8>> {
+        self.renderer.read_texture(texture)
+    }
+    /// Overwrites a region of one layer of a texture (see
+    /// [`Renderer::update_texture_region`]).
+    pub fn update_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        layer: u32,
+        origin: (u32, u32),
+        size: (u32, u32),
+        data: &[u8],
+    ) {
+        self.renderer
+            .update_texture_region(texture, layer, origin, size, data)
+    }
+    /// Re-uploads one whole layer of an array texture (see
+    /// [`Renderer::replace_array_layer`]).
+    pub fn replace_array_layer(&self, texture: &wgpu::Texture, layer: u32, data: &[u8]) {
+        self.renderer.replace_array_layer(texture, layer, data)
+    }
     /// Create a new sprite group sized to fit `world_transforms` and
     /// `sheet_regions`, which should be the same length.  Returns the
     /// sprite group index corresponding to this group.
@@ -922,6 +4950,26 @@ impl Immediate {
         self.sprites_used.resize(group_count + 1, 0);
         group_count
     }
+    /// Adds a sprite group drawn with a custom shader (see
+    /// [`Renderer::sprite_group_add_with_shader`]).
+    pub fn sprite_group_add_with_shader(
+        &mut self,
+        tex: &wgpu::Texture,
+        world_transforms: Vec<crate::sprites::Transform>,
+        sheet_regions: Vec<crate::sprites::SheetRegion>,
+        camera: crate::sprites::Camera2D,
+        shader: &MaterialDesc,
+    ) -> (usize, MaterialId) {
+        let (group, material) = self.renderer.sprite_group_add_with_shader(
+            tex,
+            world_transforms,
+            sheet_regions,
+            camera,
+            shader,
+        );
+        self.sprites_used.resize(group + 1, 0);
+        (group, material)
+    }
     /// Returns the number of sprite groups (including placeholders for removed groups).
     pub fn sprite_group_count(&self) -> usize {
         self.renderer.sprite_group_count()
@@ -934,6 +4982,17 @@ impl Immediate {
     pub fn sprite_group_size(&self, which: usize) -> usize {
         self.renderer.sprite_group_size(which)
     }
+    /// Makes sure the given sprite group's GPU buffers have room for
+    /// at least `capacity` sprites.  Panics if the given sprite group
+    /// is not populated.
+    pub fn sprite_group_reserve(&mut self, which: usize, capacity: usize) {
+        self.renderer.sprite_group_reserve(which, capacity)
+    }
+    /// Sets how the sprite, mesh, and flat renderers grow and shrink
+    /// their groups' GPU buffers as groups are resized.
+    pub fn set_growth_policy(&mut self, growth: crate::GrowthPolicy) {
+        self.renderer.set_growth_policy(growth)
+    }
     /// Makes sure that the size of the given sprite group is at least as large as num.
     pub fn ensure_sprites_size(&mut self, which: usize, num: usize) {
         if self.renderer.sprites.sprite_group_size(which) <= num {
@@ -949,6 +5008,158 @@ impl Immediate {
     pub fn sprite_group_set_camera(&mut self, which: usize, camera: crate::sprites::Camera2D) {
         self.renderer.sprite_group_set_camera(which, camera)
     }
+    /// Outlines the given range of sprites drawn into a sprite group
+    /// this frame, or removes the group's outline if `color` is `None`.
+    pub fn sprite_group_set_outline(
+        &mut self,
+        which: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.renderer.sprite_group_set_outline(which, color, range)
+    }
+    /// Highlights or unhighlights the given range of sprites drawn
+    /// into a sprite group this frame.
+    pub fn sprite_group_set_highlighted(
+        &mut self,
+        which: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.renderer
+            .sprite_group_set_highlighted(which, range, highlighted)
+    }
+    /// Gives a sprite group an emissive layer, or removes it if `emissive` is `None`.
+    pub fn sprite_group_set_emissive(&mut self, which: usize, emissive: Option<&wgpu::Texture>) {
+        self.renderer.sprite_group_set_emissive(which, emissive)
+    }
+    /// Sets whether a sprite group is depth tested and writes depth.
+    pub fn sprite_group_set_depth_mode(&mut self, which: usize, depth_mode: DepthMode) {
+        self.renderer.sprite_group_set_depth_mode(which, depth_mode)
+    }
+    /// Sets how a sprite group's colors combine with what's behind them.
+    pub fn sprite_group_set_blend_mode(&mut self, which: usize, blend_mode: BlendMode) {
+        self.renderer.sprite_group_set_blend_mode(which, blend_mode)
+    }
+    /// Puts a sprite group on a render layer; lower layers are drawn first.
+    pub fn sprite_group_set_layer(&mut self, which: usize, layer: i32) {
+        self.renderer.sprite_group_set_layer(which, layer)
+    }
+    /// Sets whether a sprite group is positioned in world or screen space.
+    pub fn sprite_group_set_coordinate_mode(&mut self, which: usize, mode: CoordinateMode) {
+        self.renderer.sprite_group_set_coordinate_mode(which, mode)
+    }
+    /// Draws a sprite group with a registered material, or without one if `material` is `None`.
+    pub fn sprite_group_set_material(&mut self, which: usize, material: Option<MaterialId>) {
+        self.renderer.sprite_group_set_material(which, material)
+    }
+    /// Draws a sprite group with indexed colors (see [`Renderer::sprite_group_set_palette`]).
+    pub fn sprite_group_set_palette(&mut self, which: usize, colors: Option<&[[u8; 4]]>) {
+        self.renderer.sprite_group_set_palette(which, colors)
+    }
+    /// Creates a material and adds it to the renderer's registry (see [`Renderer::material_add`]).
+    pub fn material_add(&mut self, desc: &MaterialDesc) -> MaterialId {
+        self.renderer.material_add(desc)
+    }
+    /// Removes a material from the registry.
+    pub fn material_remove(&mut self, which: MaterialId) {
+        self.renderer.material_remove(which)
+    }
+    /// Replaces a material's parameter block.
+    pub fn material_set_params(&mut self, which: MaterialId, params: &[u8]) {
+        self.renderer.material_set_params(which, params)
+    }
+    /// Turns a sprite debug view on or off.
+    pub fn sprite_set_debug_view(&mut self, view: crate::sprites::DebugView) {
+        self.renderer.sprite_set_debug_view(view)
+    }
+    /// Sets the largest sprite group which can be batched together
+    /// with its neighbors, or turns batching off if `limit` is 0.
+    pub fn sprite_set_batch_limit(&mut self, limit: usize) {
+        self.renderer.sprite_set_batch_limit(limit)
+    }
+    /// Sets the thickness of outlines in pixels.
+    pub fn outline_set_thickness(&mut self, thickness: f32) {
+        self.renderer.outline_set_thickness(thickness)
+    }
+    /// Turns drawing of selection highlights on or off (see [`Renderer::highlight_set`]).
+    pub fn highlight_set(&mut self, highlight: Option<Highlight>) {
+        self.renderer.highlight_set(highlight)
+    }
+    /// Shows or hides the built-in debug overlay (see [`Renderer::debug_overlay_set_visible`])
+    pub fn debug_overlay_set_visible(&mut self, visible: bool) {
+        self.renderer.debug_overlay_set_visible(visible)
+    }
+    /// Returns statistics about the most recently rendered frame.
+    pub fn frame_stats(&self) -> &FrameStats {
+        self.renderer.frame_stats()
+    }
+    /// Returns the transform hierarchy (see [`Renderer::hierarchy`])
+    pub fn hierarchy(&self) -> &Hierarchy {
+        self.renderer.hierarchy()
+    }
+    /// Returns the transform hierarchy for modification (see [`Renderer::hierarchy_mut`])
+    pub fn hierarchy_mut(&mut self) -> &mut Hierarchy {
+        self.renderer.hierarchy_mut()
+    }
+    /// Captures the next rendered frame (see [`Renderer::capture_next_frame`])
+    pub fn capture_next_frame(&mut self) {
+        self.renderer.capture_next_frame()
+    }
+    /// Returns the most recent frame capture, if it hasn't been taken yet.
+    pub fn take_capture(&mut self) -> Option<FrameCapture> {
+        self.renderer.take_capture()
+    }
+    /// Turns the object ID pass used for picking on or off (see [`Renderer::set_picking`])
+    pub fn set_picking(&mut self, enabled: bool) {
+        self.renderer.set_picking(enabled)
+    }
+    /// Starts reading back whatever was drawn at the given surface point (see [`Renderer::pick`])
+    pub fn pick(&self, screen_point: [f32; 2]) -> Option<PickQuery> {
+        self.renderer.pick(screen_point)
+    }
+    /// Starts reading back the depth at the given surface point (see [`Renderer::depth_at`])
+    pub fn depth_at(&self, screen_point: [f32; 2]) -> Option<DepthQuery> {
+        self.renderer.depth_at(screen_point)
+    }
+    /// Shows or hides an infinite ground grid (see [`Renderer::grid_set`])
+    pub fn grid_set(&mut self, grid: Option<Grid>) {
+        self.renderer.grid_set(grid)
+    }
+    /// Adds a tile map (see [`Renderer::tilemap_add`])
+    pub fn tilemap_add(
+        &mut self,
+        tileset: &wgpu::Texture,
+        tile_size: [f32; 2],
+        size: (u32, u32),
+        camera: crate::sprites::Camera2D,
+    ) -> usize {
+        self.renderer.tilemap_add(tileset, tile_size, size, camera)
+    }
+    /// Deletes a tile map (see [`Renderer::tilemap_remove`])
+    pub fn tilemap_remove(&mut self, which: usize) {
+        self.renderer.tilemap_remove(which)
+    }
+    /// Overwrites some of a tile map's tiles (see [`Renderer::tilemap_set_tiles`])
+    pub fn tilemap_set_tiles(&mut self, which: usize, pos: (u32, u32), width: u32, tiles: &[u16]) {
+        self.renderer.tilemap_set_tiles(which, pos, width, tiles)
+    }
+    /// Sets a tile map's camera (see [`Renderer::tilemap_set_camera`])
+    pub fn tilemap_set_camera(&mut self, which: usize, camera: crate::sprites::Camera2D) {
+        self.renderer.tilemap_set_camera(which, camera)
+    }
+    /// Shows or hides volumetric fog (see [`Renderer::fog_set`])
+    pub fn fog_set(&mut self, fog: Option<Fog>) {
+        self.renderer.fog_set(fog)
+    }
+    /// Turns 2D lighting on or off (see [`Renderer::lighting2d_set`])
+    pub fn lighting2d_set(&mut self, lighting: Option<Lighting2D>) {
+        self.renderer.lighting2d_set(lighting)
+    }
+    /// Sets the camera of 2D lighting (see [`Renderer::lighting2d_set_camera`])
+    pub fn lighting2d_set_camera(&mut self, camera: crate::sprites::Camera2D) {
+        self.renderer.lighting2d_set_camera(camera)
+    }
     /// Draws a sprite with the given transform and sheet region
     pub fn draw_sprite(
         &mut self,
@@ -997,6 +5208,43 @@ impl Immediate {
         let (corner, used) = bitfont.draw_text(trfs, uvs, text, screen_pos, depth, char_height);
         (corner, used)
     }
+    /// Draws a [`crate::textlayout::TextLayout`] with its top-left
+    /// corner at `screen_pos`, returning how many sprites were used.
+    pub fn draw_text_layout<B: RangeBounds<char>>(
+        &mut self,
+        group: usize,
+        bitfont: &crate::bitfont::BitFont<B>,
+        layout: &crate::textlayout::TextLayout,
+        screen_pos: [f32; 2],
+        depth: u16,
+    ) -> usize {
+        let (trfs, uvs) = self.draw_sprites(group, layout.sprite_count());
+        layout.draw(bitfont, trfs, uvs, screen_pos, depth)
+    }
+    /// Draws a [`crate::textlayout::TextLayout`] as a label anchored
+    /// at the world-space `position` seen through `camera` (usually
+    /// the mesh or flat camera), using the sprite group's effective
+    /// camera to map it onto the screen.  Returns how many sprites were
+    /// used, which is zero if the anchor is not in view.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_label<B: RangeBounds<char>>(
+        &mut self,
+        group: usize,
+        bitfont: &crate::bitfont::BitFont<B>,
+        layout: &crate::textlayout::TextLayout,
+        camera: &crate::meshes::Camera3D,
+        position: [f32; 3],
+        options: &crate::labels::LabelOptions,
+    ) -> usize {
+        let camera2d = self.renderer.sprites.effective_camera(group);
+        let Some(placement) =
+            crate::labels::place_label(camera, &camera2d, position, layout, options)
+        else {
+            return 0;
+        };
+        let (trfs, uvs) = self.draw_sprites(group, layout.sprite_count());
+        placement.draw(bitfont, layout, trfs, uvs)
+    }
     /// Draws the sprites of a [`crate::nineslice::NineSlice`].
     #[allow(clippy::too_many_arguments)]
     pub fn draw_nineslice(
@@ -1017,6 +5265,50 @@ impl Immediate {
     pub fn mesh_set_camera(&mut self, camera: crate::meshes::Camera3D) {
         self.renderer.mesh_set_camera(camera)
     }
+    /// Adds a group of skinned meshes (see [`Renderer::skinned_group_add`])
+    pub fn skinned_group_add(
+        &mut self,
+        texture: &wgpu::Texture,
+        vertices: &[crate::skinned::SkinnedVertex],
+        indices: &[u32],
+        bone_count: usize,
+        instance_count: usize,
+    ) -> usize {
+        self.renderer
+            .skinned_group_add(texture, vertices, indices, bone_count, instance_count)
+    }
+    /// Gets the transforms of a skinned group's instances (see [`Renderer::skinned_instances_mut`])
+    pub fn skinned_instances_mut(&mut self, which: usize) -> &mut [crate::meshes::Transform3D] {
+        self.renderer.skinned_instances_mut(which)
+    }
+    /// Gets the bone matrices of a skinned instance (see [`Renderer::skinned_bones_mut`])
+    pub fn skinned_bones_mut(
+        &mut self,
+        which: usize,
+        instance: usize,
+    ) -> &mut [crate::skinned::BoneMatrix] {
+        self.renderer.skinned_bones_mut(which, instance)
+    }
+    /// Adds a particle emitter (see [`Renderer::particle_emitter_add`])
+    pub fn particle_emitter_add(
+        &mut self,
+        desc: EmitterDesc,
+        texture: Option<&wgpu::Texture>,
+    ) -> usize {
+        self.renderer.particle_emitter_add(desc, texture)
+    }
+    /// Changes a particle emitter's description (see [`Renderer::particle_emitter_set`])
+    pub fn particle_emitter_set(&mut self, which: usize, desc: EmitterDesc) {
+        self.renderer.particle_emitter_set(which, desc)
+    }
+    /// Spawns extra particles from an emitter (see [`Renderer::particle_emitter_burst`])
+    pub fn particle_emitter_burst(&mut self, which: usize, count: u32) {
+        self.renderer.particle_emitter_burst(which, count)
+    }
+    /// Steps the particle emitters (see [`Renderer::particles_update`])
+    pub fn particles_update(&mut self, dt: f32) {
+        self.renderer.particles_update(dt)
+    }
     /// Add a mesh group with the given array texture.  All meshes in
     /// the group pull from the same vertex buffer, and each submesh
     /// is defined in terms of a range of indices within that buffer.
@@ -1040,10 +5332,53 @@ impl Immediate {
         self.meshes_used[group.index()].resize(mesh_count, 0);
         group
     }
+    /// Adds a mesh group with normal maps (see [`Renderer::mesh_group_add_with_layers`]).
+    pub fn mesh_group_add_with_layers(
+        &mut self,
+        texture: &wgpu::Texture,
+        layers: crate::meshes::TextureLayers,
+        mut vertices: Vec<crate::meshes::Vertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<crate::meshes::MeshEntry>,
+    ) -> crate::meshes::MeshGroup {
+        crate::meshes::compute_tangents(&mut vertices, &indices, &mesh_info);
+        let group = self.mesh_group_add(texture, vertices, indices, mesh_info);
+        self.renderer.mesh_group_set_layers(group, Some(layers));
+        group
+    }
     /// Deletes a mesh group, leaving an empty placeholder.
     pub fn mesh_group_remove(&mut self, which: crate::meshes::MeshGroup) {
         self.renderer.mesh_group_remove(which)
     }
+    /// Makes sure the given mesh group's instance buffer has room
+    /// for at least `capacity` instances across all of its meshes.
+    pub fn mesh_group_reserve(&mut self, which: crate::meshes::MeshGroup, capacity: usize) {
+        self.renderer.mesh_group_reserve(which, capacity)
+    }
+    /// Draws a mesh group with a registered material, or without one if `material` is `None`.
+    pub fn mesh_group_set_material(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        material: Option<MaterialId>,
+    ) {
+        self.renderer.mesh_group_set_material(which, material)
+    }
+    /// Draws a textured mesh group with its own camera, or with the shared one if `camera` is `None`.
+    pub fn mesh_group_set_camera(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        camera: Option<crate::meshes::Camera3D>,
+    ) {
+        self.renderer.mesh_group_set_camera(which, camera)
+    }
+    /// Multiplies a mesh group's colors by a lightmap texture, or stops if `lightmap` is `None`.
+    pub fn mesh_group_set_lightmap(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        lightmap: Option<&wgpu::Texture>,
+    ) {
+        self.renderer.mesh_group_set_lightmap(which, lightmap)
+    }
     /// Returns how many mesh groups there are.
     pub fn mesh_group_count(&self) -> usize {
         self.renderer.mesh_group_count()
@@ -1091,6 +5426,28 @@ impl Immediate {
         self.meshes_used[group.index()][idx] += howmany;
         trfs
     }
+    /// Outlines the given range of instances of a textured mesh, or
+    /// removes the mesh's outline if `color` is `None`.
+    pub fn mesh_set_outline(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.renderer.mesh_set_outline(which, idx, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a textured mesh.
+    pub fn mesh_set_highlighted(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.renderer
+            .mesh_set_highlighted(which, idx, range, highlighted)
+    }
     /// Sets the given camera for all flat mesh groups.
     pub fn flat_set_camera(&mut self, camera: crate::meshes::Camera3D) {
         self.renderer.flat_set_camera(camera)
@@ -1121,6 +5478,27 @@ impl Immediate {
     pub fn flat_group_remove(&mut self, which: crate::meshes::MeshGroup) {
         self.renderer.flat_group_remove(which)
     }
+    /// Makes sure the given flat mesh group's instance buffer has
+    /// room for at least `capacity` instances across all of its meshes.
+    pub fn flat_group_reserve(&mut self, which: crate::meshes::MeshGroup, capacity: usize) {
+        self.renderer.flat_group_reserve(which, capacity)
+    }
+    /// Draws a flat mesh group with a registered material, or without one if `material` is `None`.
+    pub fn flat_group_set_material(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        material: Option<MaterialId>,
+    ) {
+        self.renderer.flat_group_set_material(which, material)
+    }
+    /// Draws a flat mesh group with its own camera, or with the shared one if `camera` is `None`.
+    pub fn flat_group_set_camera(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        camera: Option<crate::meshes::Camera3D>,
+    ) {
+        self.renderer.flat_group_set_camera(which, camera)
+    }
     /// Returns how many mesh groups there are.
     pub fn flat_group_count(&self) -> usize {
         self.renderer.flat_group_count()
@@ -1168,6 +5546,83 @@ impl Immediate {
         self.flats_used[group.index()][idx] += howmany;
         trfs
     }
+    /// Outlines the given range of instances of a flat mesh, or
+    /// removes the mesh's outline if `color` is `None`.
+    pub fn flat_set_outline(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.renderer.flat_set_outline(which, idx, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a flat mesh.
+    pub fn flat_set_highlighted(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        range: impl RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.renderer
+            .flat_set_highlighted(which, idx, range, highlighted)
+    }
+    /// Draws the given range of instances of a flat mesh in a single
+    /// color instead of its group's colors, or removes that override
+    /// if `color` is `None`.
+    pub fn flat_set_instance_color(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        color: Option<[f32; 4]>,
+        range: impl RangeBounds<usize>,
+    ) {
+        self.renderer
+            .flat_set_instance_color(which, idx, color, range)
+    }
+    /// Returns the color override of an instance of a flat mesh, if any.
+    pub fn flat_instance_color(
+        &self,
+        which: crate::meshes::MeshGroup,
+        idx: usize,
+        instance: usize,
+    ) -> Option<[f32; 4]> {
+        self.renderer.flat_instance_color(which, idx, instance)
+    }
+    /// Turns lighting of flat and textured meshes on with the given
+    /// parameters, or off if `lighting` is `None`.
+    pub fn flat_set_lighting(&mut self, lighting: Option<crate::meshes::FlatLighting>) {
+        self.renderer.flat_set_lighting(lighting)
+    }
+    /// Returns the lighting of flat meshes, if any.
+    pub fn flat_lighting(&self) -> Option<crate::meshes::FlatLighting> {
+        self.renderer.flat_lighting()
+    }
+    /// Sets the lights of textured and flat meshes (see [`Renderer::mesh_set_lights`]).
+    pub fn mesh_set_lights(&mut self, lights: &[crate::meshes::Light]) {
+        self.renderer.mesh_set_lights(lights)
+    }
+    /// Sets the highlights of lit textured meshes (see [`Renderer::mesh_set_specular`]).
+    pub fn mesh_set_specular(&mut self, specular: crate::meshes::Specular) {
+        self.renderer.mesh_set_specular(specular)
+    }
+    /// Draws a textured mesh group with physically based shading (see [`Renderer::mesh_group_set_pbr`]).
+    pub fn mesh_group_set_pbr(
+        &mut self,
+        which: crate::meshes::MeshGroup,
+        params: Option<crate::pbr::PbrParams>,
+    ) {
+        self.renderer.mesh_group_set_pbr(which, params)
+    }
+    /// Sets the environment of physically based mesh groups (see [`Renderer::mesh_set_environment`]).
+    pub fn mesh_set_environment(&mut self, environment: Option<&wgpu::Texture>) {
+        self.renderer.mesh_set_environment(environment)
+    }
+    /// Points the sun and sets its shadows (see [`Renderer::set_sun`]).
+    pub fn set_sun(&mut self, direction: [f32; 3], shadows: Option<ShadowConfig>) {
+        self.renderer.set_sun(direction, shadows)
+    }
     /// Returns the current geometric transform used in postprocessing (a 4x4 column-major homogeneous matrix)
     pub fn post_transform(&self) -> [f32; 16] {
         self.renderer.post_transform()
@@ -1200,16 +5655,107 @@ impl Immediate {
     pub fn post_set_lut(&mut self, lut: &wgpu::Texture) {
         self.renderer.post_set_lut(lut)
     }
+    /// Grades colors with a 3D lookup table (see [`Renderer::set_color_lut`])
+    pub fn set_color_lut(&mut self, lut: &wgpu::Texture) {
+        self.renderer.set_color_lut(lut)
+    }
+    /// Goes back to the identity color lookup table
+    pub fn clear_color_lut(&mut self) {
+        self.renderer.clear_color_lut()
+    }
+    /// Sets the tone mapping operator (see [`Renderer::set_tonemap`])
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.renderer.set_tonemap(tonemap)
+    }
+    /// Returns the tone mapping operator
+    pub fn tonemap(&self) -> Tonemap {
+        self.renderer.tonemap()
+    }
+    /// Returns the screen-space ambient occlusion parameters, if it's enabled
+    pub fn post_ssao(&self) -> Option<&crate::postprocess::Ssao> {
+        self.renderer.post_ssao()
+    }
+    /// Enables or disables screen-space ambient occlusion (see [`Renderer::post_set_ssao`])
+    pub fn post_set_ssao(&mut self, ssao: Option<crate::postprocess::Ssao>) {
+        self.renderer.post_set_ssao(ssao)
+    }
+    /// Returns the bloom parameters, if it's on
+    pub fn post_bloom(&self) -> Option<&crate::postprocess::Bloom> {
+        self.renderer.post_bloom()
+    }
+    /// Enables or disables bloom (see [`Renderer::post_set_bloom`])
+    pub fn post_set_bloom(&mut self, bloom: Option<crate::postprocess::Bloom>) {
+        self.renderer.post_set_bloom(bloom)
+    }
+    /// Adds a fullscreen postprocessing effect (see [`Renderer::post_push_effect`])
+    pub fn post_push_effect(&mut self, label: &str, shader: &str) -> EffectId {
+        self.renderer.post_push_effect(label, shader)
+    }
+    /// Sets a postprocessing effect's parameters (see [`Renderer::post_set_effect_params`])
+    pub fn post_set_effect_params(&mut self, effect: EffectId, params: &[[f32; 4]]) {
+        self.renderer.post_set_effect_params(effect, params)
+    }
+    /// Removes a postprocessing effect (see [`Renderer::post_remove_effect`])
+    pub fn post_remove_effect(&mut self, effect: EffectId) -> bool {
+        self.renderer.post_remove_effect(effect)
+    }
+    /// Returns the depth of field parameters, if it's on
+    pub fn post_depth_of_field(&self) -> Option<&crate::postprocess::DepthOfField> {
+        self.renderer.post_depth_of_field()
+    }
+    /// Turns depth of field on or off (see [`Renderer::post_set_depth_of_field`])
+    pub fn post_set_depth_of_field(
+        &mut self,
+        depth_of_field: Option<crate::postprocess::DepthOfField>,
+    ) {
+        self.renderer.post_set_depth_of_field(depth_of_field)
+    }
+    /// Returns the motion blur parameters, if it's on
+    pub fn post_motion_blur(&self) -> Option<&MotionBlur> {
+        self.renderer.post_motion_blur()
+    }
+    /// Turns motion blur on or off (see [`Renderer::post_set_motion_blur`])
+    pub fn post_set_motion_blur(&mut self, motion_blur: Option<MotionBlur>) {
+        self.renderer.post_set_motion_blur(motion_blur)
+    }
+    /// Returns the color-blindness filter, if it's on
+    pub fn post_color_vision(&self) -> Option<&ColorVision> {
+        self.renderer.post_color_vision()
+    }
+    /// Turns a color-blindness filter on or off (see [`Renderer::post_set_color_vision`])
+    pub fn post_set_color_vision(&mut self, color_vision: Option<ColorVision>) {
+        self.renderer.post_set_color_vision(color_vision)
+    }
+    /// Gets the postprocessing effects applied before the final color transform
+    pub fn postprocess(&self) -> &PostProcess {
+        self.renderer.postprocess()
+    }
+    /// Gets the postprocessing effects mutably (see [`Renderer::postprocess_mut`])
+    pub fn postprocess_mut(&mut self) -> &mut PostProcess {
+        self.renderer.postprocess_mut()
+    }
     /// Gets the surface configuration
     pub fn config(&self) -> &wgpu::SurfaceConfiguration {
         self.renderer.config()
     }
-    /// Gets a reference to the active depth texture
-    pub fn depth_texture(&self) -> &wgpu::Texture {
+    /// Changes the depth format used by the built-in renderers; `None` means 2D-only mode (see [`Renderer::set_depth_format`]).
+    pub fn set_depth_format(&mut self, format: Option<wgpu::TextureFormat>) {
+        self.renderer.set_depth_format(format)
+    }
+    /// Turns multisampling of the main pass on or off, returning the sample count used (see [`Renderer::msaa_set`]).
+    pub fn msaa_set(&mut self, sample_count: u32) -> u32 {
+        self.renderer.msaa_set(sample_count)
+    }
+    /// Sets how the main pass clears or loads its targets (see [`Renderer::render_pass_config_set`]).
+    pub fn render_pass_config_set(&mut self, config: RenderPassConfig) {
+        self.renderer.render_pass_config_set(config)
+    }
+    /// Gets a reference to the active depth texture, if there is one
+    pub fn depth_texture(&self) -> Option<&wgpu::Texture> {
         self.renderer.depth_texture()
     }
-    /// Gets a view on the active depth texture
-    pub fn depth_texture_view(&self) -> &wgpu::TextureView {
+    /// Gets a view on the active depth texture, if there is one
+    pub fn depth_texture_view(&self) -> Option<&wgpu::TextureView> {
         self.renderer.depth_texture_view()
     }
     /// Get the GPU from the inner renderer
@@ -0,0 +1,422 @@
+//! Importing glTF models for the [`crate::meshes::MeshRenderer`]
+//! (with the `gltf` feature).
+//!
+//! [`load_gltf`] reads a `.gltf` file (with its buffers and images)
+//! or a `.glb` file, and [`read_gltf`] reads either from memory.  The
+//! result is a [`GltfModel`]:
+//!
+//! - Each glTF mesh becomes one [`MeshEntry`], with one submesh per
+//!   triangle primitive.  Point and line primitives are skipped.
+//! - Each material becomes one layer of the model's array texture:
+//!   its base color texture (read with the primitive's texture
+//!   coordinate set) multiplied by its base color factor, or just the
+//!   factor if it has no texture.  Primitives without a material use
+//!   a white layer at the end.  Images are resized (nearest
+//!   neighbor) to the largest image's size, since every layer of an
+//!   array texture is the same size.
+//! - Each node becomes a [`GltfNode`] with its parent, local
+//!   transform, and the mesh instance it draws, if any; meshes get one
+//!   instance per node that uses them (or one if none do).
+//!
+//! [`GltfModel::into_asset`] turns the model into a
+//! [`crate::scene::MeshAsset`] whose fields can be passed straight to
+//! [`crate::Renderer::mesh_group_add`], and
+//! [`GltfModel::attach_nodes`] recreates the node tree in a
+//! [`Hierarchy`] so instances follow their nodes.
+//!
+//! [`Transform3D`] only has a uniform scale, so non-uniform node
+//! scales are averaged, and skins, morph targets, cameras, and
+//! animations are ignored.
+
+use std::path::Path;
+
+use crate::hierarchy::{Hierarchy, NodeId, Target};
+use crate::meshes::{MeshEntry, MeshGroup, SubmeshEntry, Transform3D, Vertex};
+use crate::scene::MeshAsset;
+
+/// Errors from importing a glTF model.
+#[derive(Debug)]
+pub enum GltfError {
+    /// The file, one of its buffers, or one of its images couldn't
+    /// be read or decoded
+    Gltf(::gltf::Error),
+    /// A triangle primitive has no vertex positions
+    MissingPositions { mesh: usize, primitive: usize },
+}
+
+impl std::fmt::Display for GltfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GltfError::Gltf(err) => write!(f, "Couldn't read glTF: {err}"),
+            GltfError::MissingPositions { mesh, primitive } => write!(
+                f,
+                "Primitive {primitive} of glTF mesh {mesh} has no vertex positions"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<::gltf::Error> for GltfError {
+    fn from(err: ::gltf::Error) -> Self {
+        GltfError::Gltf(err)
+    }
+}
+
+/// A node of a glTF model's scene graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// The index of the parent node in [`GltfModel::nodes`], if any
+    pub parent: Option<usize>,
+    /// The node's transform relative to its parent (or the world)
+    pub local: Transform3D,
+    /// The mesh this node draws and which of that mesh's instances it
+    /// uses, if it draws one
+    pub mesh: Option<(usize, usize)>,
+}
+
+/// A glTF model's geometry, textures, and nodes (see the [module
+/// documentation](self)).
+pub struct GltfModel {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub meshes: Vec<MeshEntry>,
+    /// Each mesh's name, if it has one
+    pub mesh_names: Vec<Option<String>>,
+    /// Parents always come before their children
+    pub nodes: Vec<GltfNode>,
+    /// The width and height of every texture layer
+    pub texture_size: (u32, u32),
+    /// One layer of sRGB RGBA8 pixels per material, plus a white
+    /// layer for primitives without a material
+    pub texture_layers: Vec<Vec<u8>>,
+}
+
+/// Reads a `.gltf` or `.glb` file from the given path, loading
+/// external buffers and images relative to the directory it's in.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<GltfModel, GltfError> {
+    let (document, buffers, images) = ::gltf::import(path)?;
+    convert(&document, &buffers, &images)
+}
+
+/// Reads a glTF model from memory: a `.glb` file, or a `.gltf` file
+/// whose buffers and images are embedded as data URIs.
+pub fn read_gltf(data: &[u8]) -> Result<GltfModel, GltfError> {
+    let (document, buffers, images) = ::gltf::import_slice(data)?;
+    convert(&document, &buffers, &images)
+}
+
+impl GltfModel {
+    /// Creates the model's array texture on the renderer's GPU.
+    pub fn texture(&self, renderer: &crate::Renderer) -> wgpu::Texture {
+        let layers: Vec<&[u8]> = self.texture_layers.iter().map(Vec::as_slice).collect();
+        renderer.create_array_texture(
+            &layers,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            self.texture_size,
+            Some("gltf:texture"),
+        )
+    }
+    /// Creates the model's texture and returns it with the model's
+    /// geometry, ready for [`crate::Renderer::mesh_group_add`].
+    pub fn into_asset(self, renderer: &crate::Renderer) -> MeshAsset {
+        MeshAsset {
+            texture: self.texture(renderer),
+            vertices: self.vertices,
+            indices: self.indices,
+            meshes: self.meshes,
+        }
+    }
+    /// Returns the world transform of each node.
+    pub fn world_transforms(&self) -> Vec<Transform3D> {
+        let mut world: Vec<Transform3D> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let transform = match node.parent {
+                Some(parent) => crate::hierarchy::compose(&world[parent], &node.local),
+                None => node.local,
+            };
+            world.push(transform);
+        }
+        world
+    }
+    /// Adds a node to `hierarchy` for each of the model's nodes, under
+    /// `root` (or as root nodes), attaching the ones which draw meshes
+    /// to their instances in `group`, which should have been added
+    /// from this model.  Returns the new nodes in the same order as
+    /// [`GltfModel::nodes`].
+    pub fn attach_nodes(
+        &self,
+        hierarchy: &mut Hierarchy,
+        root: Option<NodeId>,
+        group: MeshGroup,
+    ) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let parent = node.parent.map(|parent| ids[parent]).or(root);
+            let id = hierarchy.add_node(parent, node.local);
+            if let Some((mesh, index)) = node.mesh {
+                hierarchy.attach(id, Some(Target::Mesh { group, mesh, index }));
+            }
+            ids.push(id);
+        }
+        ids
+    }
+}
+
+// glTF quaternions are (x, y, z, w); Transform3D's put the scalar
+// first and rotate the other way (see hierarchy::compose)
+fn node_transform(node: &::gltf::Node) -> Transform3D {
+    let (translation, [x, y, z, w], [sx, sy, sz]) = node.transform().decomposed();
+    Transform3D {
+        translation,
+        scale: (sx + sy + sz) / 3.0,
+        rotation: [w, -x, -y, -z],
+    }
+}
+
+fn convert(
+    document: &::gltf::Document,
+    buffers: &[::gltf::buffer::Data],
+    images: &[::gltf::image::Data],
+) -> Result<GltfModel, GltfError> {
+    let (texture_size, texture_layers) = material_layers(document, images);
+    let white_layer = texture_layers.len() as u32 - 1;
+    // Every node's parent, then the nodes in an order with parents first
+    let mut parents = vec![None; document.nodes().len()];
+    for node in document.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+    let mut order: Vec<usize> = (0..parents.len())
+        .filter(|&n| parents[n].is_none())
+        .collect();
+    let mut next = 0;
+    while next < order.len() {
+        let node = document.nodes().nth(order[next]).unwrap();
+        order.extend(node.children().map(|child| child.index()));
+        next += 1;
+    }
+    let mut instance_counts = vec![0_u32; document.meshes().len()];
+    let mut position = vec![0; parents.len()];
+    for (i, &n) in order.iter().enumerate() {
+        position[n] = i;
+    }
+    let nodes: Vec<GltfNode> = order
+        .iter()
+        .map(|&n| {
+            let node = document.nodes().nth(n).unwrap();
+            let mesh = node.mesh().map(|mesh| {
+                let count = &mut instance_counts[mesh.index()];
+                *count += 1;
+                (mesh.index(), *count as usize - 1)
+            });
+            GltfNode {
+                name: node.name().map(str::to_string),
+                parent: parents[n].map(|p| position[p]),
+                local: node_transform(&node),
+                mesh,
+            }
+        })
+        .collect();
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut meshes = Vec::with_capacity(document.meshes().len());
+    let mut mesh_names = Vec::with_capacity(document.meshes().len());
+    for mesh in document.meshes() {
+        let mut submeshes = vec![];
+        for (p, primitive) in mesh.primitives().enumerate() {
+            if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipping {:?} primitive {p} of glTF mesh {}",
+                    primitive.mode(),
+                    mesh.index()
+                );
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = reader.read_positions().ok_or(GltfError::MissingPositions {
+                mesh: mesh.index(),
+                primitive: p,
+            })?;
+            let material = primitive.material();
+            let layer = material.index().map_or(white_layer, |m| m as u32);
+            // Materials without textures read (0, 0), which is fine
+            let set = material
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .map_or(0, |info| info.tex_coord());
+            let mut uvs = reader
+                .read_tex_coords(set)
+                .map(|uvs| uvs.into_f32())
+                .into_iter()
+                .flatten();
+            // Primitives without normals stay unlit
+            let mut normals = reader.read_normals().into_iter().flatten();
+            let mut tangents = reader.read_tangents().into_iter().flatten();
+            // Vertex bases aren't supported everywhere, so indices
+            // are offset into the shared vertex buffer instead
+            let base = vertices.len() as u32;
+            vertices.extend(positions.map(|position| {
+                Vertex::new(position, uvs.next().unwrap_or([0.0, 0.0]), layer)
+                    .with_normal(normals.next().unwrap_or([0.0; 3]))
+                    .with_tangent(tangents.next().unwrap_or([0.0; 4]))
+            }));
+            let start = indices.len() as u32;
+            match reader.read_indices() {
+                Some(read) => indices.extend(read.into_u32().map(|i| i + base)),
+                None => indices.extend(base..vertices.len() as u32),
+            }
+            submeshes.push(SubmeshEntry {
+                indices: start..indices.len() as u32,
+                vertex_base: 0,
+                layer: Some(layer),
+            });
+        }
+        meshes.push(MeshEntry {
+            instance_count: instance_counts[mesh.index()].max(1),
+            submeshes,
+        });
+        mesh_names.push(mesh.name().map(str::to_string));
+    }
+    Ok(GltfModel {
+        vertices,
+        indices,
+        meshes,
+        mesh_names,
+        nodes,
+        texture_size,
+        texture_layers,
+    })
+}
+
+// Makes one RGBA8 layer per material plus a white one, all the size
+// of the largest image any material uses
+fn material_layers(
+    document: &::gltf::Document,
+    images: &[::gltf::image::Data],
+) -> ((u32, u32), Vec<Vec<u8>>) {
+    let image_of = |material: &::gltf::Material| {
+        material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map(|info| &images[info.texture().source().index()])
+    };
+    let size = document
+        .materials()
+        .filter_map(|material| image_of(&material))
+        .fold((1, 1), |(w, h), image| {
+            (w.max(image.width), h.max(image.height))
+        });
+    let texels = (size.0 * size.1) as usize;
+    let mut layers: Vec<Vec<u8>> = document
+        .materials()
+        .map(|material| {
+            let factor = material.pbr_metallic_roughness().base_color_factor();
+            let pixels = match image_of(&material) {
+                Some(image) => resize(&rgba8(image), (image.width, image.height), size),
+                None => [255; 4].repeat(texels),
+            };
+            // The factor is linear and the texture is sRGB
+            let lut: [[u8; 256]; 4] = std::array::from_fn(|c| {
+                std::array::from_fn(|v| {
+                    if c == 3 {
+                        (v as f32 * factor[3]).round() as u8
+                    } else {
+                        to_srgb(from_srgb(v as u8) * factor[c])
+                    }
+                })
+            });
+            pixels
+                .chunks(4)
+                .flat_map(|px| [0, 1, 2, 3].map(|c| lut[c][px[c] as usize]))
+                .collect()
+        })
+        .collect();
+    layers.push([255; 4].repeat(texels));
+    (size, layers)
+}
+
+fn from_srgb(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let s = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (s * 255.0).round() as u8
+}
+
+// Converts an image to RGBA8, keeping the high byte of 16-bit channels
+fn rgba8(image: &::gltf::image::Data) -> Vec<u8> {
+    use ::gltf::image::Format;
+    let (channels, bytes) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |px: &[u8], c: usize| -> u8 {
+        let at = &px[c * bytes..(c + 1) * bytes];
+        match bytes {
+            1 => at[0],
+            2 => at[1],
+            _ => {
+                let linear = f32::from_le_bytes([at[0], at[1], at[2], at[3]]);
+                if c == 3 {
+                    (linear.clamp(0.0, 1.0) * 255.0).round() as u8
+                } else {
+                    to_srgb(linear)
+                }
+            }
+        }
+    };
+    image
+        .pixels
+        .chunks_exact(channels * bytes)
+        .flat_map(|px| match channels {
+            1 => {
+                let v = channel(px, 0);
+                [v, v, v, 255]
+            }
+            2 => [channel(px, 0), channel(px, 1), 0, 255],
+            3 => [channel(px, 0), channel(px, 1), channel(px, 2), 255],
+            _ => [0, 1, 2, 3].map(|c| channel(px, c)),
+        })
+        .collect()
+}
+
+// Nearest-neighbor resizing of RGBA8 pixels
+fn resize(pixels: &[u8], (w, h): (u32, u32), (to_w, to_h): (u32, u32)) -> Vec<u8> {
+    if (w, h) == (to_w, to_h) {
+        return pixels.to_vec();
+    }
+    let mut out = Vec::with_capacity((to_w * to_h * 4) as usize);
+    for y in 0..to_h {
+        let sy = (y as u64 * h as u64 / to_h as u64) as u32;
+        for x in 0..to_w {
+            let sx = (x as u64 * w as u64 / to_w as u64) as u32;
+            let at = ((sy * w + sx) * 4) as usize;
+            out.extend_from_slice(&pixels[at..at + 4]);
+        }
+    }
+    out
+}
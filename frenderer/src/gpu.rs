@@ -1,6 +1,9 @@
 //! A wrapper for WGPU state.
 
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 
 #[derive(Debug)]
 pub enum FrendererError {
@@ -17,6 +20,232 @@ impl std::fmt::Display for FrendererError {
 }
 impl std::error::Error for FrendererError {}
 
+/// A group couldn't be created or resized because one of its GPU
+/// resources would exceed the device's limits.  Each variant names
+/// the [`wgpu::Limits`] field in question, the size the group would
+/// have needed, and the device's maximum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    /// A buffer would be larger than `max_buffer_size` bytes.
+    BufferSize {
+        buffer: &'static str,
+        size: u64,
+        max: u64,
+    },
+    /// A storage buffer would be larger than
+    /// `max_storage_buffer_binding_size` bytes.
+    StorageBindingSize {
+        buffer: &'static str,
+        size: u64,
+        max: u64,
+    },
+    /// A texture has more than `max_texture_array_layers` layers.
+    TextureLayers { layers: u32, max: u32 },
+    /// A 2D texture would be wider or taller than
+    /// `max_texture_dimension_2d` texels.
+    TextureSize { size: u32, max: u32 },
+}
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::BufferSize { buffer, size, max } => write!(
+                f,
+                "{buffer} would need {size} bytes, but the device's max_buffer_size is {max}; split the group or request higher limits for the device"
+            ),
+            LimitError::StorageBindingSize { buffer, size, max } => write!(
+                f,
+                "{buffer} would need {size} bytes, but the device's max_storage_buffer_binding_size is {max}; split the group or request higher limits for the device"
+            ),
+            LimitError::TextureLayers { layers, max } => write!(
+                f,
+                "texture has {layers} array layers, but the device's max_texture_array_layers is {max}; split it into several textures"
+            ),
+            LimitError::TextureSize { size, max } => write!(
+                f,
+                "texture would be {size} texels across, but the device's max_texture_dimension_2d is {max}; split it into several textures"
+            ),
+        }
+    }
+}
+impl std::error::Error for LimitError {}
+
+/// How the built-in renderers get per-instance data (sprite
+/// transforms, mesh instance transforms, ...) into their vertex
+/// shaders.  This is chosen once per device when the [`WGPU`] is
+/// created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstanceStorage {
+    /// Instance data lives in storage buffers read by the vertex shader.
+    StorageBuffer,
+    /// Instance data lives in instance-rate vertex buffers.
+    VertexBuffer,
+    /// Instance data lives in uniform buffers which are bound and
+    /// drawn in fixed-size chunks, for downlevel targets which
+    /// support neither of the other options.
+    UniformChunks,
+}
+
+/// How precisely the mesh and flat renderers store per-instance
+/// transforms in GPU buffers.  Half precision packs each instance
+/// into 16 bytes instead of 32, halving upload bandwidth for huge
+/// instance counts, at the cost of rounding translations to steps
+/// of about 1/1000 of their magnitude (half a unit at 1000 units
+/// from the origin) and slightly quantizing rotations.  Sprite
+/// transforms are already 16 bytes and aren't affected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InstancePrecision {
+    /// 32-bit floats.
+    #[default]
+    Full,
+    /// 16-bit floats.
+    Half,
+}
+
+/// How the built-in renderers size the GPU buffers holding a group's
+/// instance data when the group is resized.  The default grows
+/// buffers to exactly the size needed and never shrinks them;
+/// churn-heavy games may want to grow by a larger factor to
+/// reallocate less often, while memory-sensitive (e.g. web) builds
+/// may want buffers to shrink again after a group gets much smaller.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrowthPolicy {
+    /// When a buffer has to grow, it gets room for the needed number
+    /// of instances times this factor (values below 1.0 act like 1.0).
+    pub growth_factor: f32,
+    /// Buffer capacities are rounded up to a multiple of this many instances.
+    pub chunk: usize,
+    /// If set, a resize which leaves less than this fraction of a
+    /// buffer's capacity in use reallocates the buffer to fit (as if
+    /// it were growing to the new size).  Space reserved with the
+    /// renderers' `reserve` methods is never given back.
+    pub shrink_below: Option<f32>,
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        Self {
+            growth_factor: 1.0,
+            chunk: 1,
+            shrink_below: None,
+        }
+    }
+}
+
+/// Which groups of a built-in renderer to draw, for render passes
+/// that should only show some of them (e.g. a shadow pass that
+/// skips UI sprites, or a minimap that only draws terrain).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GroupSelection {
+    /// Every group.
+    #[default]
+    All,
+    /// The groups with indices in the given range.
+    Range(std::ops::Range<usize>),
+    /// Exactly the listed groups, drawn in index order; indices of
+    /// removed groups are ignored.
+    Groups(Vec<usize>),
+}
+
+impl GroupSelection {
+    /// Returns true if the group with the given index is selected.
+    pub fn contains(&self, group: usize) -> bool {
+        match self {
+            GroupSelection::All => true,
+            GroupSelection::Range(range) => range.contains(&group),
+            GroupSelection::Groups(groups) => groups.contains(&group),
+        }
+    }
+}
+
+impl From<std::ops::Range<usize>> for GroupSelection {
+    fn from(range: std::ops::Range<usize>) -> Self {
+        GroupSelection::Range(range)
+    }
+}
+
+impl From<Vec<usize>> for GroupSelection {
+    fn from(groups: Vec<usize>) -> Self {
+        GroupSelection::Groups(groups)
+    }
+}
+
+impl GrowthPolicy {
+    /// Returns the capacity a buffer which needs to hold `len`
+    /// instances should be reallocated with (always at least one).
+    pub fn grown_capacity(&self, len: usize) -> usize {
+        let grown = (len as f32 * self.growth_factor).ceil() as usize;
+        grown.max(len).max(1).next_multiple_of(self.chunk.max(1))
+    }
+    /// Returns the capacity a buffer holding `len` instances out of
+    /// `capacity` (with `reserved` instances' worth of space reserved)
+    /// should be reallocated with, or `None` if it can stay as it is.
+    pub fn resized_capacity(&self, len: usize, capacity: usize, reserved: usize) -> Option<usize> {
+        if len > capacity {
+            return Some(self.grown_capacity(len).max(reserved));
+        }
+        let shrink_below = self.shrink_below?;
+        let fitted = self.grown_capacity(len).max(reserved);
+        ((len as f32) < capacity as f32 * shrink_below && fitted < capacity).then_some(fitted)
+    }
+}
+
+/// How [`WGPU::with_options`] picks an adapter and creates its
+/// device.  The defaults are what [`WGPU::new`] uses.
+#[derive(Clone, Debug, Default)]
+pub struct GpuOptions {
+    /// Whether to prefer an integrated (low power) or a discrete
+    /// (high performance) adapter
+    pub power_preference: wgpu::PowerPreference,
+    /// Only consider fallback (software) adapters
+    pub force_fallback_adapter: bool,
+    /// Features the device must have, on top of the optional ones
+    /// frenderer asks for when the adapter has them (timestamp
+    /// queries and compressed texture formats)
+    pub required_features: wgpu::Features,
+    /// Limits the device must support; by default these are
+    /// [`wgpu::Limits::downlevel_defaults`] (or the WebGL2 ones if
+    /// vertex shaders can't read storage buffers) with the adapter's
+    /// texture sizes.  Raising e.g. `max_storage_buffer_binding_size`
+    /// here allows bigger groups, and lowering
+    /// `max_storage_buffers_per_shader_stage` below 3 makes the
+    /// renderers use vertex buffers instead (see [`InstanceStorage`]).
+    pub required_limits: Option<wgpu::Limits>,
+}
+
+/// Where the renderers write instance data when it's uploaded:
+/// straight through the queue, or through a staging belt into a
+/// command encoder which must be submitted before the data is used.
+pub(crate) enum BufferWriter<'a> {
+    Queue(&'a wgpu::Queue),
+    Belt {
+        device: &'a wgpu::Device,
+        encoder: &'a mut wgpu::CommandEncoder,
+        belt: &'a mut wgpu::util::StagingBelt,
+    },
+}
+
+impl BufferWriter<'_> {
+    /// Writes `data` into `buffer` at `offset`; like
+    /// [`wgpu::Queue::write_buffer`], both must be multiples of
+    /// [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub(crate) fn write(&mut self, buffer: &wgpu::Buffer, offset: u64, data: &[u8]) {
+        match self {
+            BufferWriter::Queue(queue) => queue.write_buffer(buffer, offset, data),
+            BufferWriter::Belt {
+                device,
+                encoder,
+                belt,
+            } => {
+                let Some(size) = wgpu::BufferSize::new(data.len() as u64) else {
+                    return;
+                };
+                belt.write_buffer(encoder, buffer, offset, size, device)
+                    .copy_from_slice(data);
+            }
+        }
+    }
+}
+
 /// A wrapper for a WGPU instance, surface, adapter, device, queue, and surface configuration.
 #[allow(dead_code)]
 pub struct WGPU {
@@ -24,60 +253,160 @@ pub struct WGPU {
     adapter: Arc<wgpu::Adapter>,
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
+    instance_storage: InstanceStorage,
+    instance_precision: InstancePrecision,
+    // Textures whose last layer is a GL-only copy of their first
+    // (see WGPU::mark_padded)
+    padded_textures: Mutex<HashSet<wgpu::Id<wgpu::Texture>>>,
 }
 
 impl WGPU {
     /// Create a WGPU structure with already-created GPU resources.
+    /// Storage buffer support is determined from the adapter's
+    /// capabilities and the device's limits.
     pub fn with_resources(
         instance: Arc<wgpu::Instance>,
         adapter: Arc<wgpu::Adapter>,
         device: Arc<wgpu::Device>,
         queue: Arc<wgpu::Queue>,
     ) -> Self {
+        let limits = device.limits();
+        let instance_storage = if Self::storage_supported(&adapter, &limits) {
+            InstanceStorage::StorageBuffer
+        } else if limits.max_vertex_buffers >= 2 && limits.max_vertex_attributes >= 4 {
+            // meshes use two vertex buffers (vertices and instances)
+            // and up to four attributes
+            InstanceStorage::VertexBuffer
+        } else {
+            InstanceStorage::UniformChunks
+        };
         Self {
             instance,
             adapter,
             device,
             queue,
+            instance_storage,
+            instance_precision: InstancePrecision::Full,
+            padded_textures: Mutex::new(HashSet::new()),
+        }
+    }
+    /// Whether vertex shaders can read storage buffers on this
+    /// adapter, given the limits a device was (or will be) created with.
+    fn storage_supported(adapter: &wgpu::Adapter, limits: &wgpu::Limits) -> bool {
+        let is_gl = adapter.get_info().backend == wgpu::Backend::Gl;
+        let is_web = cfg!(target_arch = "wasm32");
+        !(is_web && is_gl)
+            && adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            // the sprite renderer binds three storage buffers
+            // (transforms, regions, and tints)
+            && limits.max_storage_buffers_per_shader_stage >= 3
+    }
+    /// Creates a [`wgpu::Instance`] for this platform.  On the web,
+    /// this probes for a WebGPU adapter and falls back to the WebGL
+    /// backend (if the `webgl` feature is on) when there isn't one,
+    /// so that a single wasm build works in every browser; [`WGPU::new`]
+    /// then picks device limits and an [`InstanceStorage`] suited to
+    /// whichever backend was chosen (see [`WGPU::backend`]).  Since a
+    /// canvas can only ever be used with one backend, surfaces should
+    /// be created after calling this.  Elsewhere this is just
+    /// [`wgpu::Instance::default`].
+    pub async fn detect_instance() -> Arc<wgpu::Instance> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let webgpu = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::BROWSER_WEBGPU,
+                ..Default::default()
+            });
+            // Browsers may expose navigator.gpu without being able to
+            // provide an adapter, so ask for one to be sure
+            if webgpu
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .is_some()
+            {
+                log::info!("Using the WebGPU backend");
+                return Arc::new(webgpu);
+            }
+            log::warn!("WebGPU is unavailable, falling back to WebGL");
+            Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::GL,
+                ..Default::default()
+            }))
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            Arc::new(wgpu::Instance::default())
         }
     }
     /// Create a WGPU structure by initializing WGPU for display onto the given surface.
     pub async fn new(
         instance: Arc<wgpu::Instance>,
         surface: Option<&wgpu::Surface<'static>>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_options(instance, surface, &GpuOptions::default()).await
+    }
+    /// Like [`WGPU::new`], but picks the adapter and creates the
+    /// device according to `options`.  Fails if no adapter fits, or if
+    /// the adapter lacks the required features or limits.
+    pub async fn with_options(
+        instance: Arc<wgpu::Instance>,
+        surface: Option<&wgpu::Surface<'static>>,
+        options: &GpuOptions,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
+                power_preference: options.power_preference,
+                force_fallback_adapter: options.force_fallback_adapter,
                 // Request an adapter which can render to our surface
                 compatible_surface: surface,
             })
             .await
             .ok_or(FrendererError::NoUsableAdapter)?;
-        let is_gl = adapter.get_info().backend == wgpu::Backend::Gl;
-        #[cfg(not(target_arch = "wasm32"))]
-        let is_web = false;
-        #[cfg(target_arch = "wasm32")]
-        let is_web = true;
-        let use_storage = !(is_web && is_gl);
+        // Only ask for storage buffer limits if the adapter can
+        // actually use them from the vertex stage; otherwise the
+        // renderers will fall back to instanced vertex buffers.
+        let required_limits = options.required_limits.clone();
+        let use_storage = Self::storage_supported(
+            &adapter,
+            required_limits.as_ref().unwrap_or(&adapter.limits()),
+        );
 
         // Create the logical device and command queue
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: if use_storage {
-                        wgpu::Limits::downlevel_defaults()
-                    } else {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    }
-                    .using_resolution(adapter.limits()),
+                    // Timestamps are only used by the debug overlay's GPU
+                    // timer, and compressed formats by crate::ktx2
+                    required_features: options.required_features
+                        | (adapter.features()
+                            & (wgpu::Features::TIMESTAMP_QUERY
+                                | wgpu::Features::TEXTURE_COMPRESSION_BC
+                                | wgpu::Features::TEXTURE_COMPRESSION_ASTC)),
+                    required_limits: required_limits.unwrap_or_else(|| {
+                        if use_storage {
+                            wgpu::Limits::downlevel_defaults()
+                        } else {
+                            wgpu::Limits::downlevel_webgl2_defaults()
+                        }
+                        .using_resolution(adapter.limits())
+                    }),
                 },
                 None,
             )
             .await?;
+        log::info!(
+            "Using {:?} backend ({})",
+            adapter.get_info().backend,
+            if use_storage {
+                "storage buffers"
+            } else {
+                "downlevel limits"
+            }
+        );
         Ok(Self::with_resources(
             instance,
             Arc::new(adapter),
@@ -85,6 +414,11 @@ impl WGPU {
             Arc::new(queue),
         ))
     }
+    /// Returns the backend this GPU interface's adapter uses, e.g.
+    /// [`wgpu::Backend::BrowserWebGpu`] or [`wgpu::Backend::Gl`] on the web.
+    pub fn backend(&self) -> wgpu::Backend {
+        self.adapter.get_info().backend
+    }
     /// Returns true if this GPU interface is using a GL backend, important to work around some bugs
     pub fn is_gl(&self) -> bool {
         self.adapter.get_info().backend == wgpu::Backend::Gl
@@ -98,15 +432,93 @@ impl WGPU {
     pub fn is_web(&self) -> bool {
         false
     }
-    /// Whether this GPU supports storage buffers
+    /// Whether this GPU supports storage buffers in vertex shaders.
+    /// This is detected once when the [`WGPU`] is created, and
+    /// renderers use it to choose between storage buffer and vertex
+    /// buffer paths for instance data.
     pub fn supports_storage(&self) -> bool {
-        !(self.is_gl() && self.is_web())
-            && self
-                .adapter
-                .get_downlevel_capabilities()
-                .flags
-                .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
-            && self.device.limits().max_storage_buffers_per_shader_stage > 0
+        self.instance_storage == InstanceStorage::StorageBuffer
+    }
+    /// Returns how renderers should provide instance data to their
+    /// shaders on this GPU (see [`InstanceStorage`]).
+    pub fn instance_storage(&self) -> InstanceStorage {
+        self.instance_storage
+    }
+    /// Checks that a buffer of `size` bytes fits in this device's
+    /// limits; storage buffers are also bound in full, so they must
+    /// fit in a single storage binding.
+    pub(crate) fn check_buffer(
+        &self,
+        buffer: &'static str,
+        size: u64,
+        storage: bool,
+    ) -> Result<(), LimitError> {
+        let limits = self.device.limits();
+        if size > limits.max_buffer_size {
+            return Err(LimitError::BufferSize {
+                buffer,
+                size,
+                max: limits.max_buffer_size,
+            });
+        }
+        let max = u64::from(limits.max_storage_buffer_binding_size);
+        if storage && size > max {
+            return Err(LimitError::StorageBindingSize { buffer, size, max });
+        }
+        Ok(())
+    }
+    /// Checks that a texture's array layers fit in this device's limits.
+    pub(crate) fn check_texture_layers(&self, tex: &wgpu::Texture) -> Result<(), LimitError> {
+        let max = self.device.limits().max_texture_array_layers;
+        let layers = tex.depth_or_array_layers();
+        if tex.dimension() == wgpu::TextureDimension::D2 && layers > max {
+            return Err(LimitError::TextureLayers { layers, max });
+        }
+        Ok(())
+    }
+    /// Records that `texture`'s last layer is a copy of its first,
+    /// added because GL treats 1-layer array textures as plain 2D
+    /// textures and 6-layer ones as cubemaps (see
+    /// [`crate::Renderer::create_array_texture`]).
+    pub(crate) fn mark_padded(&self, texture: &wgpu::Texture) {
+        self.padded_textures
+            .lock()
+            .unwrap()
+            .insert(texture.global_id());
+    }
+    /// Returns the layer of `texture` which copies its first, if it
+    /// was created with one (see [`WGPU::mark_padded`]).
+    pub(crate) fn padding_layer(&self, texture: &wgpu::Texture) -> Option<u32> {
+        self.padded_textures
+            .lock()
+            .unwrap()
+            .contains(&texture.global_id())
+            .then(|| texture.depth_or_array_layers() - 1)
+    }
+    /// Asks for mesh and flat instance transforms to be stored with
+    /// the given precision, returning the precision actually used.
+    /// Half precision falls back to full precision when instances
+    /// are drawn from uniform buffer chunks (see
+    /// [`InstanceStorage::UniformChunks`]).  Renderers pick this up
+    /// when they're created, so call it before creating them (e.g.
+    /// before [`crate::Renderer::with_gpu`]).
+    pub fn request_instance_precision(
+        &mut self,
+        precision: InstancePrecision,
+    ) -> InstancePrecision {
+        self.instance_precision = match precision {
+            InstancePrecision::Half if self.instance_storage == InstanceStorage::UniformChunks => {
+                InstancePrecision::Full
+            }
+            precision => precision,
+        };
+        self.instance_precision
+    }
+    /// Returns the precision mesh and flat renderers created on
+    /// this GPU store instance transforms with (see
+    /// [`InstancePrecision`]).
+    pub fn instance_precision(&self) -> InstancePrecision {
+        self.instance_precision
     }
     /// Returns this GPU wrapper's [`wgpu::Instance`].
     pub fn instance(&self) -> &wgpu::Instance {
@@ -0,0 +1,278 @@
+//! An infinite ground grid for editors and debugging views built on
+//! the 3D mesh renderers.
+//!
+//! [`GridRenderer`] draws a single fullscreen triangle pair and, for
+//! each pixel, intersects the camera ray with a horizontal plane.
+//! Grid lines are antialiased using screen-space derivatives and
+//! fade out with distance from the camera; the grid writes depth
+//! values for the plane so it is correctly hidden by (and hides)
+//! mesh geometry, but it should be drawn after opaque meshes since
+//! it is alpha blended.
+
+use std::borrow::Cow;
+
+use crate::{capture, meshes::Camera3D, WGPU};
+
+/// The appearance of an infinite grid on the plane `y = height`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Grid {
+    /// The height of the grid plane
+    pub height: f32,
+    /// The distance between minor grid lines
+    pub spacing: f32,
+    /// Every `major_every`th line is a major line
+    pub major_every: u32,
+    /// The width of grid lines in pixels
+    pub line_width: f32,
+    /// The color of minor grid lines
+    pub minor_color: [f32; 4],
+    /// The color of major grid lines
+    pub major_color: [f32; 4],
+    /// The horizontal distance from the camera at which the grid fades out completely
+    pub fade_distance: f32,
+    /// Whether to highlight the X axis (red) and Z axis (blue)
+    pub show_axes: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            height: 0.0,
+            spacing: 1.0,
+            major_every: 10,
+            line_width: 1.0,
+            minor_color: [0.5, 0.5, 0.5, 0.5],
+            major_color: [0.8, 0.8, 0.8, 0.8],
+            fade_distance: 100.0,
+            show_axes: true,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct GridUniforms {
+    projview: [f32; 16],
+    inv_projview: [f32; 16],
+    eye_fade: [f32; 4],
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    params: [f32; 4],
+    flags: [f32; 4],
+}
+
+/// Draws an infinite [`Grid`] as seen from a [`Camera3D`].
+pub struct GridRenderer {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    grid: Grid,
+    camera: Camera3D,
+}
+
+impl GridRenderer {
+    /// Creates a new `GridRenderer` meant to draw into the given
+    /// color target state with the given depth texture format (or
+    /// without depth testing if `depth_format` is `None`).
+    pub fn new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+        grid: Grid,
+        camera: Camera3D,
+    ) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("grid:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("grid.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("grid:bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<GridUniforms>() as u64,
+                            ),
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("grid:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        // Grid lines' alpha blends them over the scene
+        let color_target = wgpu::ColorTargetState {
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            ..color_target
+        };
+        let depth_format = depth_format.into();
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            color_target.clone(),
+            (depth_format, 1),
+        );
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("grid:uniforms"),
+            size: std::mem::size_of::<GridUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("grid:bg"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let ret = Self {
+            shader,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            grid,
+            camera,
+        };
+        ret.upload(gpu);
+        ret
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("grid:pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // The grid is tested against the scene but is
+                // transparent, so it doesn't write depth
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    fn upload(&self, gpu: &WGPU) {
+        let projview = self.camera.projection_view();
+        let grid = &self.grid;
+        let uniforms = GridUniforms {
+            projview: *projview.as_array(),
+            inv_projview: *projview.inversed().as_array(),
+            eye_fade: [
+                self.camera.translation[0],
+                self.camera.translation[1],
+                self.camera.translation[2],
+                grid.fade_distance.max(f32::EPSILON),
+            ],
+            minor_color: grid.minor_color,
+            major_color: grid.major_color,
+            params: [
+                grid.spacing,
+                grid.major_every as f32,
+                grid.line_width,
+                grid.height,
+            ],
+            flags: [if grid.show_axes { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0],
+        };
+        gpu.queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    /// Returns the grid's current appearance.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    /// Changes the grid's appearance.
+    pub fn set_grid(&mut self, gpu: &WGPU, grid: Grid) {
+        self.grid = grid;
+        self.upload(gpu);
+    }
+    /// Returns the camera the grid is drawn from.
+    pub fn camera(&self) -> Camera3D {
+        self.camera
+    }
+    /// Sets the camera the grid is drawn from; this should match the
+    /// camera of the meshes drawn alongside it.
+    pub fn set_camera(&mut self, gpu: &WGPU, camera: Camera3D) {
+        self.camera = camera;
+        self.upload(gpu);
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipeline.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.color_target.clone(),
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass this draws
+    /// into, re-creating the pipeline.  The default is 1 (no
+    /// multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target the grid is drawn into (e.g. for a
+    /// new color format), re-creating the pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Draws the grid into the given renderpass, which should have
+    /// been begun with the same color and depth formats this renderer
+    /// was created with.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "grid:pipeline");
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "grid:bind_group");
+        capture::draw(rpass, 0..6, 0..1);
+    }
+}
@@ -0,0 +1,89 @@
+struct GridUniforms {
+  projview: mat4x4<f32>,
+  inv_projview: mat4x4<f32>,
+  // xyz: camera position, w: fade distance
+  eye_fade: vec4<f32>,
+  minor_color: vec4<f32>,
+  major_color: vec4<f32>,
+  // x: spacing, y: major line every N lines, z: line width in pixels, w: plane height
+  params: vec4<f32>,
+  // x: nonzero to color the X and Z axes
+  flags: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> grid: GridUniforms;
+
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    vec2<f32>(-1., -1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(1., 1.)
+);
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  // The points on the near and far planes under this pixel
+  @location(0) near_point: vec3<f32>,
+  @location(1) far_point: vec3<f32>,
+}
+
+struct FragmentOutput {
+  @location(0) color: vec4<f32>,
+  @builtin(frag_depth) depth: f32,
+}
+
+fn unproject(p:vec3<f32>) -> vec3<f32> {
+  let world = grid.inv_projview * vec4<f32>(p, 1.0);
+  return world.xyz / world.w;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+  let p = VERTICES[in_vertex_index];
+  var out:VertexOutput;
+  out.clip_position = vec4<f32>(p, 0.0, 1.0);
+  out.near_point = unproject(vec3<f32>(p, 0.0));
+  out.far_point = unproject(vec3<f32>(p, 1.0));
+  return out;
+}
+
+// Coverage of grid lines every `spacing` units, antialiased using
+// screen-space derivatives
+fn lines(coord:vec2<f32>, width:f32) -> f32 {
+  let deriv = fwidth(coord);
+  let dist = abs(fract(coord - 0.5) - 0.5) / deriv;
+  return 1.0 - clamp(min(dist.x, dist.y) - (width - 1.0) * 0.5, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(in:VertexOutput) -> FragmentOutput {
+  let height = grid.params.w;
+  let t = (height - in.near_point.y) / (in.far_point.y - in.near_point.y);
+  let pos = in.near_point + t * (in.far_point - in.near_point);
+  let clip = grid.projview * vec4<f32>(pos, 1.0);
+  let depth = clip.z / clip.w;
+  // Derivatives are taken before any discards, in uniform control flow
+  let width = grid.params.z;
+  let coord = pos.xz / grid.params.x;
+  let minor = lines(coord, width);
+  let major = lines(coord / max(grid.params.y, 1.0), width);
+  let axis_deriv = fwidth(pos.xz);
+  var color = mix(grid.minor_color * minor, grid.major_color, major);
+  if grid.flags.x != 0.0 {
+    // X axis (along z == 0) in red, Z axis (along x == 0) in blue
+    let axis = 1.0 - clamp(abs(pos.xz) / axis_deriv - (width - 1.0) * 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+    color = mix(color, vec4<f32>(0.9, 0.2, 0.2, 1.0), axis.y);
+    color = mix(color, vec4<f32>(0.2, 0.3, 0.9, 1.0), axis.x);
+  }
+  let fade = 1.0 - clamp(distance(pos.xz, grid.eye_fade.xz) / grid.eye_fade.w, 0.0, 1.0);
+  color.a = color.a * fade;
+  // Skip pixels where the view ray doesn't hit the plane in front
+  // of the camera, or hits it beyond the far plane
+  if !(t > 0.0) || depth > 1.0 || color.a <= 0.0 {
+    discard;
+  }
+  return FragmentOutput(color, depth);
+}
@@ -0,0 +1,240 @@
+//! An optional transform hierarchy for placing instances relative to
+//! each other, e.g. turrets on tanks, items held in hands, or props
+//! attached to a character's bones.
+//!
+//! A [`Hierarchy`] is a forest of nodes, each with a local
+//! [`Transform3D`] relative to its parent (or to the world, for root
+//! nodes).  A node can be attached to a sprite or to a mesh or flat
+//! mesh instance (see [`Target`]), in which case the renderer
+//! overwrites that instance's transform with the node's world
+//! transform whenever the hierarchy has changed, just before
+//! uploading instance data.  The [`crate::Renderer`] owns one
+//! hierarchy (see [`crate::Renderer::hierarchy_mut`]); it costs
+//! nothing if no nodes are added.
+//!
+//! Sprites take their position and rotation from their node's world
+//! transform (ignoring its depth), but keep their own sizes; use
+//! [`transform_2d`] to make local transforms for 2D nodes.
+
+use crate::meshes::{MeshGroup, Transform3D};
+
+/// Identifies a node in a [`Hierarchy`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeId(usize);
+
+/// An instance whose transform is driven by a hierarchy node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    /// A sprite in a sprite group.
+    Sprite { group: usize, index: usize },
+    /// An instance of a mesh in a textured mesh group.
+    Mesh {
+        group: MeshGroup,
+        mesh: usize,
+        index: usize,
+    },
+    /// An instance of a mesh in a flat mesh group.
+    Flat {
+        group: MeshGroup,
+        mesh: usize,
+        index: usize,
+    },
+}
+
+struct Node {
+    parent: Option<NodeId>,
+    local: Transform3D,
+    target: Option<Target>,
+}
+
+/// A forest of transform nodes (see the [module documentation](self)).
+#[derive(Default)]
+pub struct Hierarchy {
+    nodes: Vec<Option<Node>>,
+    free_nodes: Vec<usize>,
+    // Whether anything changed since the last resolve
+    dirty: bool,
+}
+
+impl Hierarchy {
+    /// Creates an empty hierarchy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a node with the given local transform, under `parent` or
+    /// as a root node.  Panics if `parent` doesn't exist.
+    pub fn add_node(&mut self, parent: Option<NodeId>, local: Transform3D) -> NodeId {
+        if let Some(parent) = parent {
+            self.node(parent);
+        }
+        let node = Node {
+            parent,
+            local,
+            target: None,
+        };
+        let idx = match self.free_nodes.pop() {
+            Some(idx) => {
+                self.nodes[idx] = Some(node);
+                idx
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.dirty = true;
+        NodeId(idx)
+    }
+    /// Removes a node.  Its children are moved up to its parent (or
+    /// become root nodes), keeping their local transforms, and its
+    /// target instance keeps its last world transform.  Does nothing
+    /// if the node doesn't exist.
+    pub fn remove_node(&mut self, node: NodeId) {
+        let Some(removed) = self.nodes.get_mut(node.0).and_then(Option::take) else {
+            return;
+        };
+        for child in self.nodes.iter_mut().flatten() {
+            if child.parent == Some(node) {
+                child.parent = removed.parent;
+            }
+        }
+        self.free_nodes.push(node.0);
+        self.dirty = true;
+    }
+    /// Returns true if the given node exists.
+    pub fn has_node(&self, node: NodeId) -> bool {
+        self.nodes.get(node.0).is_some_and(Option::is_some)
+    }
+    /// Returns how many nodes there are.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() - self.free_nodes.len()
+    }
+    /// Moves a node under a new parent (or makes it a root node),
+    /// keeping its local transform.  Panics if either node doesn't
+    /// exist or if `parent` is `node` or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) {
+        self.node(node);
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert_ne!(
+                a, node,
+                "Parenting {node:?} to {parent:?} would create a cycle"
+            );
+            ancestor = self.node(a).parent;
+        }
+        self.node_mut(node).parent = parent;
+    }
+    /// Returns a node's parent.  Panics if the node doesn't exist.
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.node(node).parent
+    }
+    /// Sets a node's transform relative to its parent.  Panics if the node doesn't exist.
+    pub fn set_local(&mut self, node: NodeId, local: Transform3D) {
+        self.node_mut(node).local = local;
+    }
+    /// Returns a node's transform relative to its parent.  Panics if the node doesn't exist.
+    pub fn local(&self, node: NodeId) -> Transform3D {
+        self.node(node).local
+    }
+    /// Drives the given instance's transform with this node, or
+    /// stops driving any instance if `target` is `None`.  Panics if
+    /// the node doesn't exist.
+    pub fn attach(&mut self, node: NodeId, target: Option<Target>) {
+        self.node_mut(node).target = target;
+    }
+    /// Returns the instance a node drives, if any.  Panics if the node doesn't exist.
+    pub fn target(&self, node: NodeId) -> Option<Target> {
+        self.node(node).target
+    }
+    /// Computes a node's world transform from its own and its
+    /// ancestors' local transforms.  Panics if the node doesn't exist.
+    pub fn world_transform(&self, node: NodeId) -> Transform3D {
+        let node = self.node(node);
+        match node.parent {
+            Some(parent) => compose(&self.world_transform(parent), &node.local),
+            None => node.local,
+        }
+    }
+    // Returns the world transform of every attached node if anything
+    // changed since the last call, and nothing otherwise
+    pub(crate) fn resolve(&mut self) -> Vec<(Target, Transform3D)> {
+        if !std::mem::take(&mut self.dirty) {
+            return vec![];
+        }
+        let mut worlds = vec![None; self.nodes.len()];
+        (0..self.nodes.len())
+            .filter_map(|idx| {
+                let target = self.nodes[idx].as_ref()?.target?;
+                Some((target, self.cached_world(idx, &mut worlds)))
+            })
+            .collect()
+    }
+    fn cached_world(&self, idx: usize, worlds: &mut [Option<Transform3D>]) -> Transform3D {
+        if let Some(world) = worlds[idx] {
+            return world;
+        }
+        let node = self.nodes[idx].as_ref().unwrap();
+        let world = match node.parent {
+            Some(parent) => compose(&self.cached_world(parent.0, worlds), &node.local),
+            None => node.local,
+        };
+        worlds[idx] = Some(world);
+        world
+    }
+    fn node(&self, node: NodeId) -> &Node {
+        self.nodes
+            .get(node.0)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| panic!("No hierarchy node {node:?}"))
+    }
+    fn node_mut(&mut self, node: NodeId) -> &mut Node {
+        self.dirty = true;
+        self.nodes
+            .get_mut(node.0)
+            .and_then(Option::as_mut)
+            .unwrap_or_else(|| panic!("No hierarchy node {node:?}"))
+    }
+}
+
+/// Places `local` in the space of `parent`: an instance drawn with
+/// the result looks like one drawn with `local` and then moved,
+/// rotated, and scaled by `parent`.
+pub fn compose(parent: &Transform3D, local: &Transform3D) -> Transform3D {
+    let model = parent.model_matrix() * local.model_matrix();
+    // The product of the two rotations in the quaternion layout
+    // `Transform3D::model_matrix` reads, with the scalar part first
+    let [p0, p1, p2, p3] = parent.rotation;
+    let [l0, l1, l2, l3] = local.rotation;
+    Transform3D {
+        translation: model.cols[3].xyz().into(),
+        scale: parent.scale * local.scale,
+        rotation: [
+            l0 * p0 - l1 * p1 - l2 * p2 - l3 * p3,
+            l0 * p1 + l1 * p0 + l2 * p3 - l3 * p2,
+            l0 * p2 - l1 * p3 + l2 * p0 + l3 * p1,
+            l0 * p3 + l1 * p2 - l2 * p1 + l3 * p0,
+        ],
+    }
+}
+
+/// A transform for 2D nodes: a translation in the xy plane and a
+/// counterclockwise rotation in radians, like a sprite's.
+pub fn transform_2d(x: f32, y: f32, rot: f32) -> Transform3D {
+    let (sin, cos) = (rot / 2.0).sin_cos();
+    Transform3D {
+        translation: [x, y, 0.0],
+        scale: 1.0,
+        rotation: [cos, 0.0, 0.0, -sin],
+    }
+}
+
+// The position and rotation a sprite takes from a world transform
+pub(crate) fn sprite_placement(world: &Transform3D) -> (f32, f32, f32) {
+    let model = world.model_matrix();
+    let x_axis = model.cols[0];
+    (
+        world.translation[0],
+        world.translation[1],
+        x_axis.y.atan2(x_axis.x),
+    )
+}
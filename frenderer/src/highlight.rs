@@ -0,0 +1,93 @@
+//! Selection highlights for sprites and mesh instances.
+//!
+//! Any sprite or mesh instance can be flagged as highlighted (see
+//! e.g. [`crate::sprites::SpriteRenderer::set_highlighted`]) without
+//! moving it into a special group.  Highlighting works like
+//! outlining (see [`crate::outline`]), but with its own mask: each
+//! renderer draws its highlighted instances into the mask in its
+//! highlight color, and then an [`crate::outline::OutlinePass`] with
+//! a nonzero fill tints the highlighted pixels and outlines them.
+//! [`crate::Renderer`] does all of this when highlighting is turned
+//! on with [`crate::Renderer::highlight_set`].
+
+use std::ops::Range;
+
+/// The appearance of highlighted instances.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Highlight {
+    /// The color of the tint and outline
+    pub color: [f32; 4],
+    /// The outline thickness in pixels (up to
+    /// [`crate::outline::MAX_THICKNESS`]), or 0 for no outline
+    pub thickness: f32,
+    /// How strongly highlighted pixels are tinted with
+    /// [`Highlight::color`], from 0 (not at all) to 1 (completely)
+    pub fill: f32,
+}
+
+impl Default for Highlight {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.8, 0.2, 1.0],
+            thickness: 2.0,
+            fill: 0.35,
+        }
+    }
+}
+
+/// The highlighted instances of a sprite group or mesh, as sorted,
+/// non-overlapping, non-adjacent runs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HighlightSet {
+    runs: Vec<Range<usize>>,
+}
+
+impl HighlightSet {
+    /// Highlights or unhighlights a range of instances.
+    pub(crate) fn set(&mut self, range: Range<usize>, highlighted: bool) {
+        if range.is_empty() {
+            return;
+        }
+        let mut runs = Vec::with_capacity(self.runs.len() + 1);
+        let mut new = range.clone();
+        for run in self.runs.drain(..) {
+            if highlighted && run.start <= new.end && new.start <= run.end {
+                // overlapping or adjacent runs merge
+                new = new.start.min(run.start)..new.end.max(run.end);
+                continue;
+            }
+            if !highlighted && run.start < range.end && range.start < run.end {
+                // keep whatever is left on either side
+                if run.start < range.start {
+                    runs.push(run.start..range.start);
+                }
+                if range.end < run.end {
+                    runs.push(range.end..run.end);
+                }
+                continue;
+            }
+            runs.push(run);
+        }
+        if highlighted {
+            runs.push(new);
+        }
+        runs.sort_by_key(|run| run.start);
+        self.runs = runs;
+    }
+    pub(crate) fn contains(&self, index: usize) -> bool {
+        self.runs.iter().any(|run| run.contains(&index))
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+    pub(crate) fn clear(&mut self) {
+        self.runs.clear()
+    }
+    /// The highlighted runs, clipped to the first `len` instances.
+    pub(crate) fn clipped_runs(&self, len: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.runs
+            .iter()
+            .map(move |run| run.start.min(len)..run.end.min(len))
+            .filter(|run| !run.is_empty())
+    }
+}
@@ -0,0 +1,1260 @@
+//! Loading compressed textures from KTX2 containers, so large games
+//! don't have to spend four bytes of video memory on every texel.
+//!
+//! [`Ktx2Image::parse`] reads a KTX2 file's mip levels and layers,
+//! and [`create_texture`] (or [`crate::Renderer::create_texture_ktx2`])
+//! uploads them.  BC1-BC7 and ASTC data is uploaded as-is when the
+//! device supports the format (see [`Ktx2Image::supported_by`]);
+//! otherwise BC1-BC5, BC7, and LDR ASTC data is transcoded to RGBA8
+//! on the CPU first, which is slower to load and takes more memory
+//! but looks the same.  Uncompressed RGBA8 and BGRA8 files are
+//! loaded too.  Supercompressed files (Basis Universal or zstd), 3D
+//! textures, and other formats are reported as errors.
+//!
+//! Like [`crate::Renderer::create_array_texture`], the texture's
+//! layers (or cube faces, in order) become the layers of a 2D array
+//! texture.
+
+use crate::{LimitError, WGPU};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+// ASTC blocks which can't be decoded come out magenta
+const ASTC_ERROR: [u8; 4] = [255, 0, 255, 255];
+
+/// An error loading a KTX2 texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ktx2Error {
+    /// The data isn't a well-formed KTX2 file.
+    Invalid(&'static str),
+    /// The file is supercompressed with the given scheme (1 is Basis
+    /// Universal, 2 is zstd), which isn't supported.
+    Supercompressed(u32),
+    /// The file's Vulkan format (`vkFormat`) can't be loaded, or
+    /// can't be transcoded on a device which doesn't support it.
+    UnsupportedFormat(u32),
+    /// The texture is 3D, which isn't supported.
+    Volume,
+    /// The texture exceeds the device's limits.
+    Limit(LimitError),
+}
+impl std::fmt::Display for Ktx2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ktx2Error::Invalid(why) => write!(f, "Invalid KTX2 file: {why}"),
+            Ktx2Error::Supercompressed(scheme) => {
+                write!(f, "KTX2 supercompression scheme {scheme} isn't supported")
+            }
+            Ktx2Error::UnsupportedFormat(format) => {
+                write!(f, "KTX2 vkFormat {format} isn't supported")
+            }
+            Ktx2Error::Volume => write!(f, "3D KTX2 textures aren't supported"),
+            Ktx2Error::Limit(err) => err.fmt(f),
+        }
+    }
+}
+impl std::error::Error for Ktx2Error {}
+impl From<LimitError> for Ktx2Error {
+    fn from(err: LimitError) -> Self {
+        Ktx2Error::Limit(err)
+    }
+}
+
+/// The mip levels and layers of a KTX2 file, in the format they're
+/// stored in.
+#[derive(Clone, Debug)]
+pub struct Ktx2Image {
+    vk_format: u32,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    layers: u32,
+    // Each level holds every layer's image in turn
+    levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Image {
+    /// Reads a KTX2 file.
+    pub fn parse(data: &[u8]) -> Result<Self, Ktx2Error> {
+        if data.len() < 80 || data[..12] != IDENTIFIER {
+            return Err(Ktx2Error::Invalid("missing KTX2 identifier"));
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        let vk_format = u32_at(12);
+        let width = u32_at(20);
+        let height = u32_at(24).max(1);
+        if u32_at(28) > 1 {
+            return Err(Ktx2Error::Volume);
+        }
+        let layers = u32_at(32)
+            .max(1)
+            .checked_mul(u32_at(36).max(1))
+            .ok_or(Ktx2Error::Invalid("too many layers"))?;
+        let level_count = u32_at(40).max(1);
+        match u32_at(44) {
+            0 => (),
+            scheme => return Err(Ktx2Error::Supercompressed(scheme)),
+        }
+        let format = vk_to_wgpu(vk_format).ok_or(Ktx2Error::UnsupportedFormat(vk_format))?;
+        if width == 0 {
+            return Err(Ktx2Error::Invalid("zero width"));
+        }
+        if level_count > 32 || 80 + level_count as usize * 24 > data.len() {
+            return Err(Ktx2Error::Invalid("truncated level index"));
+        }
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = 80 + level as usize * 24;
+                let (offset, length) = (u64_at(entry), u64_at(entry + 8));
+                let expected = level_image_size(format, width, height, level)
+                    .and_then(|size| size.checked_mul(u64::from(layers)))
+                    .ok_or(Ktx2Error::Invalid("level too large"))?;
+                if length < expected || offset.saturating_add(expected) > data.len() as u64 {
+                    return Err(Ktx2Error::Invalid("truncated level data"));
+                }
+                Ok(data[offset as usize..(offset + expected) as usize].to_vec())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            vk_format,
+            format,
+            width,
+            height,
+            layers,
+            levels,
+        })
+    }
+    /// The format the texture's data is stored in.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+    /// The width and height of the texture's first mip level, in texels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    /// How many layers the texture has, counting each cube face as a layer.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+    /// How many mip levels the file holds.
+    pub fn level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+    /// The stored data of one mip level, holding each layer's image in turn.
+    pub fn level_data(&self, level: u32) -> &[u8] {
+        &self.levels[level as usize]
+    }
+    /// Whether the device can sample this texture's format directly;
+    /// if not, [`create_texture`] transcodes it to RGBA8.
+    pub fn supported_by(&self, gpu: &WGPU) -> bool {
+        let (bw, bh) = self.format.block_dimensions();
+        gpu.device()
+            .features()
+            .contains(self.format.required_features())
+            && self.width % bw == 0
+            && self.height % bh == 0
+    }
+    /// The RGBA8 format [`Ktx2Image::decode_rgba8`] transcodes this
+    /// texture to, if it can.
+    pub fn decoded_format(&self) -> Option<wgpu::TextureFormat> {
+        use wgpu::TextureFormat as F;
+        match self.format {
+            F::Rgba8Unorm
+            | F::Bgra8Unorm
+            | F::Bc1RgbaUnorm
+            | F::Bc2RgbaUnorm
+            | F::Bc3RgbaUnorm
+            | F::Bc4RUnorm
+            | F::Bc5RgUnorm
+            | F::Bc7RgbaUnorm
+            | F::Astc {
+                channel: wgpu::AstcChannel::Unorm,
+                ..
+            } => Some(F::Rgba8Unorm),
+            F::Rgba8UnormSrgb
+            | F::Bgra8UnormSrgb
+            | F::Bc1RgbaUnormSrgb
+            | F::Bc2RgbaUnormSrgb
+            | F::Bc3RgbaUnormSrgb
+            | F::Bc7RgbaUnormSrgb
+            | F::Astc {
+                channel: wgpu::AstcChannel::UnormSrgb,
+                ..
+            } => Some(F::Rgba8UnormSrgb),
+            _ => None,
+        }
+    }
+    /// Transcodes one mip level to tightly packed RGBA8 rows from the
+    /// top left, holding each layer's image in turn.  Red-only and
+    /// red-green formats fill the other channels with 0 and alpha
+    /// with 255.  Returns an error for formats which can't be
+    /// transcoded (see [`Ktx2Image::decoded_format`]).
+    pub fn decode_rgba8(&self, level: u32) -> Result<Vec<u8>, Ktx2Error> {
+        use wgpu::TextureFormat as F;
+        if self.decoded_format().is_none() {
+            return Err(Ktx2Error::UnsupportedFormat(self.vk_format));
+        }
+        let data = &self.levels[level as usize];
+        let (width, height) = mip_size(self.width, self.height, level);
+        let (bw, bh) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap() as usize;
+        let (blocks_x, blocks_y) = (width.div_ceil(bw), height.div_ceil(bh));
+        let mut out = vec![0; width as usize * height as usize * 4 * self.layers as usize];
+        let mut texels = vec![[0u8; 4]; (bw * bh) as usize];
+        let blocks_per_layer = blocks_x as usize * blocks_y as usize;
+        let blocks = data.chunks_exact(block_size);
+        for (index, block) in blocks.enumerate() {
+            let block_index = index % blocks_per_layer;
+            let layer = index / blocks_per_layer;
+            match self.format {
+                F::Rgba8Unorm | F::Rgba8UnormSrgb => texels[0].copy_from_slice(block),
+                F::Bgra8Unorm | F::Bgra8UnormSrgb => {
+                    texels[0] = [block[2], block[1], block[0], block[3]]
+                }
+                F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => decode_bc1(block, false, &mut texels),
+                F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => {
+                    decode_bc1(&block[8..], true, &mut texels);
+                    for (i, texel) in texels.iter_mut().enumerate() {
+                        texel[3] = ((block[i / 2] >> (4 * (i % 2))) & 0xF) * 17;
+                    }
+                }
+                F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => {
+                    decode_bc1(&block[8..], true, &mut texels);
+                    for (texel, alpha) in texels.iter_mut().zip(decode_bc4(block)) {
+                        texel[3] = alpha;
+                    }
+                }
+                F::Bc4RUnorm => {
+                    for (texel, red) in texels.iter_mut().zip(decode_bc4(block)) {
+                        *texel = [red, 0, 0, 255];
+                    }
+                }
+                F::Bc5RgUnorm => {
+                    let green = decode_bc4(&block[8..]);
+                    for ((texel, red), green) in texels.iter_mut().zip(decode_bc4(block)).zip(green)
+                    {
+                        *texel = [red, green, 0, 255];
+                    }
+                }
+                F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => decode_bc7(block, &mut texels),
+                F::Astc { channel, .. } => decode_astc(
+                    block,
+                    (bw, bh),
+                    channel == wgpu::AstcChannel::UnormSrgb,
+                    &mut texels,
+                ),
+                _ => unreachable!(),
+            }
+            let (bx, by) = (
+                (block_index % blocks_x as usize) as u32 * bw,
+                (block_index / blocks_x as usize) as u32 * bh,
+            );
+            for ty in 0..bh.min(height - by) {
+                for tx in 0..bw.min(width - bx) {
+                    let row = layer * height as usize + (by + ty) as usize;
+                    let at = (row * width as usize + (bx + tx) as usize) * 4;
+                    out[at..at + 4].copy_from_slice(&texels[(ty * bw + tx) as usize]);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Creates an array texture from a KTX2 image, uploading its data
+/// as-is if the device supports its format and otherwise transcoding
+/// it to RGBA8 (see the [module documentation](self)).
+pub fn create_texture(
+    gpu: &WGPU,
+    image: &Ktx2Image,
+    label: Option<&str>,
+) -> Result<wgpu::Texture, Ktx2Error> {
+    let limits = gpu.device().limits();
+    let size = image.width.max(image.height);
+    if size > limits.max_texture_dimension_2d {
+        return Err(LimitError::TextureSize {
+            size,
+            max: limits.max_texture_dimension_2d,
+        }
+        .into());
+    }
+    if image.layers > limits.max_texture_array_layers {
+        return Err(LimitError::TextureLayers {
+            layers: image.layers,
+            max: limits.max_texture_array_layers,
+        }
+        .into());
+    }
+    let direct = image.supported_by(gpu);
+    let format = if direct {
+        image.format
+    } else {
+        image
+            .decoded_format()
+            .ok_or(Ktx2Error::UnsupportedFormat(image.vk_format))?
+    };
+    // GL treats 1-layer array textures as plain 2D textures and
+    // 6-layer ones as cubemaps (see Renderer::create_array_texture),
+    // so those get a copy of the first layer at the end
+    let padded = gpu.is_gl() && (image.layers == 1 || image.layers == 6);
+    let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+        label,
+        size: wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: image.layers + u32::from(padded),
+        },
+        mip_level_count: image.level_count(),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    if padded {
+        gpu.mark_padded(&texture);
+    }
+    for level in 0..image.level_count() {
+        let data = if direct {
+            image.levels[level as usize].clone()
+        } else {
+            image.decode_rgba8(level)?
+        };
+        let (width, height) = mip_size(image.width, image.height, level);
+        let (bw, bh) = format.block_dimensions();
+        let bytes_per_row = width.div_ceil(bw) * format.block_copy_size(None).unwrap();
+        let rows = height.div_ceil(bh);
+        let layer_size = (bytes_per_row * rows) as usize;
+        let extent = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+        .physical_size(format);
+        let layers = (0..image.layers).chain(padded.then_some(0));
+        for (z, layer) in layers.enumerate() {
+            let start = layer as usize * layer_size;
+            gpu.queue().write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: z as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &data[start..start + layer_size],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                extent,
+            );
+        }
+    }
+    Ok(texture)
+}
+
+fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+// The size of one layer's image at the given mip level
+// None if the size doesn't fit in a u64
+fn level_image_size(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> Option<u64> {
+    let (width, height) = mip_size(width, height, level);
+    let (bw, bh) = format.block_dimensions();
+    (u64::from(width.div_ceil(bw)) * u64::from(height.div_ceil(bh)))
+        .checked_mul(u64::from(format.block_copy_size(None).unwrap()))
+}
+
+fn vk_to_wgpu(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock as B, AstcChannel, TextureFormat as F};
+    const ASTC_BLOCKS: [B; 14] = [
+        B::B4x4,
+        B::B5x4,
+        B::B5x5,
+        B::B6x5,
+        B::B6x6,
+        B::B8x5,
+        B::B8x6,
+        B::B8x8,
+        B::B10x5,
+        B::B10x6,
+        B::B10x8,
+        B::B10x10,
+        B::B12x10,
+        B::B12x12,
+    ];
+    Some(match vk_format {
+        37 => F::Rgba8Unorm,
+        43 => F::Rgba8UnormSrgb,
+        44 => F::Bgra8Unorm,
+        50 => F::Bgra8UnormSrgb,
+        131 | 133 => F::Bc1RgbaUnorm,
+        132 | 134 => F::Bc1RgbaUnormSrgb,
+        135 => F::Bc2RgbaUnorm,
+        136 => F::Bc2RgbaUnormSrgb,
+        137 => F::Bc3RgbaUnorm,
+        138 => F::Bc3RgbaUnormSrgb,
+        139 => F::Bc4RUnorm,
+        140 => F::Bc4RSnorm,
+        141 => F::Bc5RgUnorm,
+        142 => F::Bc5RgSnorm,
+        143 => F::Bc6hRgbUfloat,
+        144 => F::Bc6hRgbFloat,
+        145 => F::Bc7RgbaUnorm,
+        146 => F::Bc7RgbaUnormSrgb,
+        157..=184 => F::Astc {
+            block: ASTC_BLOCKS[(vk_format - 157) as usize / 2],
+            channel: if vk_format % 2 == 1 {
+                AstcChannel::Unorm
+            } else {
+                AstcChannel::UnormSrgb
+            },
+        },
+        _ => return None,
+    })
+}
+
+// Reads bits from the low end of a block up
+struct Bits {
+    data: u128,
+    pos: u32,
+}
+
+impl Bits {
+    fn new(data: u128, pos: u32) -> Self {
+        Self { data, pos }
+    }
+    fn read(&mut self, count: u32) -> u32 {
+        let value = if self.pos >= 128 {
+            0
+        } else {
+            ((self.data >> self.pos) & ((1u128 << count) - 1)) as u32
+        };
+        self.pos += count;
+        value
+    }
+}
+
+// Decodes the color half of a BC1, BC2, or BC3 block; BC2 and BC3
+// always use four colors
+fn decode_bc1(block: &[u8], four_color: bool, texels: &mut [[u8; 4]]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let expand = |c: u16| {
+        let (r, g, b) = (
+            (c >> 11) as u32,
+            ((c >> 5) & 0x3F) as u32,
+            (c & 0x1F) as u32,
+        );
+        [
+            (r << 3) | (r >> 2),
+            (g << 2) | (g >> 4),
+            (b << 3) | (b >> 2),
+        ]
+    };
+    let (e0, e1) = (expand(c0), expand(c1));
+    let mix = |w0: u32, w1: u32, div: u32| {
+        let [r, g, b] = [0, 1, 2].map(|i| ((w0 * e0[i] + w1 * e1[i]) / div) as u8);
+        [r, g, b, 255]
+    };
+    let palette = if c0 > c1 || four_color {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0; 4]]
+    };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+// Decodes one channel of a BC3, BC4, or BC5 block
+fn decode_bc4(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (u32::from(block[0]), u32::from(block[1]));
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = ((7 - i) * a0 + i * a1 + 3) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = ((5 - i) * a0 + i * a1 + 2) / 5;
+        }
+    }
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize] as u8)
+}
+
+// Subsets, partition bits, rotation bits, index selection bits,
+// color bits, alpha bits, per-endpoint p-bits, shared p-bits, index
+// bits, and secondary index bits of each BC7 mode
+const BC7_MODES: [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+#[rustfmt::skip]
+const BC7_PARTITIONS_2: [u8; 1024] = [
+    0,0,1,1,0,0,1,1,0,0,1,1,0,0,1,1, 0,0,0,1,0,0,0,1,0,0,0,1,0,0,0,1, 0,1,1,1,0,1,1,1,0,1,1,1,0,1,1,1, 0,0,0,1,0,0,1,1,0,0,1,1,0,1,1,1,
+    0,0,0,0,0,0,0,1,0,0,0,1,0,0,1,1, 0,0,1,1,0,1,1,1,0,1,1,1,1,1,1,1, 0,0,0,1,0,0,1,1,0,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,1,0,0,1,1,0,1,1,1,
+    0,0,0,0,0,0,0,0,0,0,0,1,0,0,1,1, 0,0,1,1,0,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,1,0,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,0,0,0,1,0,1,1,1,
+    0,0,0,1,0,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,1,1,1,1,1,1,1,1, 0,0,0,0,1,1,1,1,1,1,1,1,1,1,1,1, 0,0,0,0,0,0,0,0,0,0,0,0,1,1,1,1,
+    0,0,0,0,1,0,0,0,1,1,1,0,1,1,1,1, 0,1,1,1,0,0,0,1,0,0,0,0,0,0,0,0, 0,0,0,0,0,0,0,0,1,0,0,0,1,1,1,0, 0,1,1,1,0,0,1,1,0,0,0,1,0,0,0,0,
+    0,0,1,1,0,0,0,1,0,0,0,0,0,0,0,0, 0,0,0,0,1,0,0,0,1,1,0,0,1,1,1,0, 0,0,0,0,0,0,0,0,1,0,0,0,1,1,0,0, 0,1,1,1,0,0,1,1,0,0,1,1,0,0,0,1,
+    0,0,1,1,0,0,0,1,0,0,0,1,0,0,0,0, 0,0,0,0,1,0,0,0,1,0,0,0,1,1,0,0, 0,1,1,0,0,1,1,0,0,1,1,0,0,1,1,0, 0,0,1,1,0,1,1,0,0,1,1,0,1,1,0,0,
+    0,0,0,1,0,1,1,1,1,1,1,0,1,0,0,0, 0,0,0,0,1,1,1,1,1,1,1,1,0,0,0,0, 0,1,1,1,0,0,0,1,1,0,0,0,1,1,1,0, 0,0,1,1,1,0,0,1,1,0,0,1,1,1,0,0,
+    0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1, 0,0,0,0,1,1,1,1,0,0,0,0,1,1,1,1, 0,1,0,1,1,0,1,0,0,1,0,1,1,0,1,0, 0,0,1,1,0,0,1,1,1,1,0,0,1,1,0,0,
+    0,0,1,1,1,1,0,0,0,0,1,1,1,1,0,0, 0,1,0,1,0,1,0,1,1,0,1,0,1,0,1,0, 0,1,1,0,1,0,0,1,0,1,1,0,1,0,0,1, 0,1,0,1,1,0,1,0,1,0,1,0,0,1,0,1,
+    0,1,1,1,0,0,1,1,1,1,0,0,1,1,1,0, 0,0,0,1,0,0,1,1,1,1,0,0,1,0,0,0, 0,0,1,1,0,0,1,0,0,1,0,0,1,1,0,0, 0,0,1,1,1,0,1,1,1,1,0,1,1,1,0,0,
+    0,1,1,0,1,0,0,1,1,0,0,1,0,1,1,0, 0,0,1,1,1,1,0,0,1,1,0,0,0,0,1,1, 0,1,1,0,0,1,1,0,1,0,0,1,1,0,0,1, 0,0,0,0,0,1,1,0,0,1,1,0,0,0,0,0,
+    0,1,0,0,1,1,1,0,0,1,0,0,0,0,0,0, 0,0,1,0,0,1,1,1,0,0,1,0,0,0,0,0, 0,0,0,0,0,0,1,0,0,1,1,1,0,0,1,0, 0,0,0,0,0,1,0,0,1,1,1,0,0,1,0,0,
+    0,1,1,0,1,1,0,0,1,0,0,1,0,0,1,1, 0,0,1,1,0,1,1,0,1,1,0,0,1,0,0,1, 0,1,1,0,0,0,1,1,1,0,0,1,1,1,0,0, 0,0,1,1,1,0,0,1,1,1,0,0,0,1,1,0,
+    0,1,1,0,1,1,0,0,1,1,0,0,1,0,0,1, 0,1,1,0,0,0,1,1,0,0,1,1,1,0,0,1, 0,1,1,1,1,1,1,0,1,0,0,0,0,0,0,1, 0,0,0,1,1,0,0,0,1,1,1,0,0,1,1,1,
+    0,0,0,0,1,1,1,1,0,0,1,1,0,0,1,1, 0,0,1,1,0,0,1,1,1,1,1,1,0,0,0,0, 0,0,1,0,0,0,1,0,1,1,1,0,1,1,1,0, 0,1,0,0,0,1,0,0,0,1,1,1,0,1,1,1,
+];
+
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [u8; 1024] = [
+    0,0,1,1,0,0,1,1,0,2,2,1,2,2,2,2, 0,0,0,1,0,0,1,1,2,2,1,1,2,2,2,1, 0,0,0,0,2,0,0,1,2,2,1,1,2,2,1,1, 0,2,2,2,0,0,2,2,0,0,1,1,0,1,1,1,
+    0,0,0,0,0,0,0,0,1,1,2,2,1,1,2,2, 0,0,1,1,0,0,1,1,0,0,2,2,0,0,2,2, 0,0,2,2,0,0,2,2,1,1,1,1,1,1,1,1, 0,0,1,1,0,0,1,1,2,2,1,1,2,2,1,1,
+    0,0,0,0,0,0,0,0,1,1,1,1,2,2,2,2, 0,0,0,0,1,1,1,1,1,1,1,1,2,2,2,2, 0,0,0,0,1,1,1,1,2,2,2,2,2,2,2,2, 0,0,1,2,0,0,1,2,0,0,1,2,0,0,1,2,
+    0,1,1,2,0,1,1,2,0,1,1,2,0,1,1,2, 0,1,2,2,0,1,2,2,0,1,2,2,0,1,2,2, 0,0,1,1,0,1,1,2,1,1,2,2,1,2,2,2, 0,0,1,1,2,0,0,1,2,2,0,0,2,2,2,0,
+    0,0,0,1,0,0,1,1,0,1,1,2,1,1,2,2, 0,1,1,1,0,0,1,1,2,0,0,1,2,2,0,0, 0,0,0,0,1,1,2,2,1,1,2,2,1,1,2,2, 0,0,2,2,0,0,2,2,0,0,2,2,1,1,1,1,
+    0,1,1,1,0,1,1,1,0,2,2,2,0,2,2,2, 0,0,0,1,0,0,0,1,2,2,2,1,2,2,2,1, 0,0,0,0,0,0,1,1,0,1,2,2,0,1,2,2, 0,0,0,0,1,1,0,0,2,2,1,0,2,2,1,0,
+    0,1,2,2,0,1,2,2,0,0,1,1,0,0,0,0, 0,0,1,2,0,0,1,2,1,1,2,2,2,2,2,2, 0,1,1,0,1,2,2,1,1,2,2,1,0,1,1,0, 0,0,0,0,0,1,1,0,1,2,2,1,1,2,2,1,
+    0,0,2,2,1,1,0,2,1,1,0,2,0,0,2,2, 0,1,1,0,0,1,1,0,2,0,0,2,2,2,2,2, 0,0,1,1,0,1,2,2,0,1,2,2,0,0,1,1, 0,0,0,0,2,0,0,0,2,2,1,1,2,2,2,1,
+    0,0,0,0,0,0,0,2,1,1,2,2,1,2,2,2, 0,2,2,2,0,0,2,2,0,0,1,2,0,0,1,1, 0,0,1,1,0,0,1,2,0,0,2,2,0,2,2,2, 0,1,2,0,0,1,2,0,0,1,2,0,0,1,2,0,
+    0,0,0,0,1,1,1,1,2,2,2,2,0,0,0,0, 0,1,2,0,1,2,0,1,2,0,1,2,0,1,2,0, 0,1,2,0,2,0,1,2,1,2,0,1,0,1,2,0, 0,0,1,1,2,2,0,0,1,1,2,2,0,0,1,1,
+    0,0,1,1,1,1,2,2,2,2,0,0,0,0,1,1, 0,1,0,1,0,1,0,1,2,2,2,2,2,2,2,2, 0,0,0,0,0,0,0,0,2,1,2,1,2,1,2,1, 0,0,2,2,1,1,2,2,0,0,2,2,1,1,2,2,
+    0,0,2,2,0,0,1,1,0,0,2,2,0,0,1,1, 0,2,2,0,1,2,2,1,0,2,2,0,1,2,2,1, 0,1,0,1,2,2,2,2,2,2,2,2,0,1,0,1, 0,0,0,0,2,1,2,1,2,1,2,1,2,1,2,1,
+    0,1,0,1,0,1,0,1,0,1,0,1,2,2,2,2, 0,2,2,2,0,1,1,1,0,2,2,2,0,1,1,1, 0,0,0,2,1,1,1,2,0,0,0,2,1,1,1,2, 0,0,0,0,2,1,1,2,2,1,1,2,2,1,1,2,
+    0,2,2,2,0,1,1,1,0,1,1,1,0,2,2,2, 0,0,0,2,1,1,1,2,1,1,1,2,0,0,0,2, 0,1,1,0,0,1,1,0,0,1,1,0,2,2,2,2, 0,0,0,0,0,0,0,0,2,1,1,2,2,1,1,2,
+    0,1,1,0,0,1,1,0,2,2,2,2,2,2,2,2, 0,0,2,2,0,0,1,1,0,0,1,1,0,0,2,2, 0,0,2,2,1,1,2,2,1,1,2,2,0,0,2,2, 0,0,0,0,0,0,0,0,0,0,0,0,2,1,1,2,
+    0,0,0,2,0,0,0,1,0,0,0,2,0,0,0,1, 0,2,2,2,1,2,2,2,0,2,2,2,1,2,2,2, 0,1,0,1,2,2,2,2,2,2,2,2,2,2,2,2, 0,1,1,1,2,0,1,1,2,2,0,1,2,2,2,0,
+];
+
+// The texel where each partition's second subset's indices start
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [u8; 64] = [
+    15,15,15,15,15,15,15,15, 15,15,15,15,15,15,15,15,
+    15, 2, 8, 2, 2, 8, 8,15,  2, 8, 2, 2, 8, 8, 2, 2,
+    15,15, 6, 8, 2, 8,15,15,  2, 8, 2, 2, 2,15,15, 6,
+     6, 2, 6, 8,15,15, 2, 2, 15,15,15,15,15, 2, 2,15,
+];
+// The same for the second and third subsets of three
+#[rustfmt::skip]
+const BC7_ANCHORS_3: [[u8; 64]; 2] = [[
+     3, 3,15,15, 8, 3,15,15,  8, 8, 6, 6, 6, 5, 3, 3,
+     3, 3, 8,15, 3, 3, 6,10,  5, 8, 8, 6, 8, 5,15,15,
+     8,15, 3, 5, 6,10, 8,15, 15, 3,15, 5,15,15,15,15,
+     3,15, 5, 5, 5, 8, 5,10,  5,10, 8,13,15,12, 3, 3,
+], [
+    15, 8, 8, 3,15,15, 3, 8, 15,15,15,15,15,15,15, 8,
+    15, 8,15, 3,15, 8,15, 8,  3,15, 6,10,15,15,10, 8,
+    15, 3,15,10,10, 8, 9,10,  6,15, 8,15, 3, 6, 6, 8,
+    15, 3,15,15,15,15,15,15, 15,15,15,15, 3,15,15, 8,
+]];
+
+const BC7_WEIGHTS: [&[u32]; 3] = [
+    &[0, 21, 43, 64],
+    &[0, 9, 18, 27, 37, 46, 55, 64],
+    &[0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64],
+];
+
+fn decode_bc7(block: &[u8], texels: &mut [[u8; 4]]) {
+    let mode = block[0].trailing_zeros();
+    if mode >= 8 {
+        texels.fill([0; 4]);
+        return;
+    }
+    let [subsets, partition_bits, rotation_bits, selection_bits, color_bits, alpha_bits, endpoint_pbits, shared_pbits, index_bits, index_bits_2] =
+        BC7_MODES[mode as usize];
+    let mut bits = Bits::new(u128::from_le_bytes(block.try_into().unwrap()), mode + 1);
+    let partition = bits.read(partition_bits) as usize;
+    let rotation = bits.read(rotation_bits);
+    let selection = bits.read(selection_bits);
+    // Subset, endpoint, channel
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..4 {
+        let count = if channel < 3 { color_bits } else { alpha_bits };
+        for subset in endpoints.iter_mut().take(subsets as usize) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.read(count);
+            }
+        }
+    }
+    let mut pbits = [[0; 2]; 3];
+    for subset in pbits.iter_mut().take(subsets as usize) {
+        if endpoint_pbits == 1 {
+            *subset = [bits.read(1), bits.read(1)];
+        } else if shared_pbits == 1 {
+            *subset = [bits.read(1); 2];
+        }
+    }
+    let has_pbits = endpoint_pbits + shared_pbits;
+    for (subset, pbits) in endpoints.iter_mut().zip(pbits) {
+        for (endpoint, pbit) in subset.iter_mut().zip(pbits) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let count = if channel < 3 { color_bits } else { alpha_bits };
+                if count == 0 {
+                    *value = 255;
+                    continue;
+                }
+                let count = count + has_pbits;
+                let v = ((*value << has_pbits) | (pbit * has_pbits)) << (8 - count);
+                *value = v | (v >> count);
+            }
+        }
+    }
+    let subset_of = |texel: usize| match subsets {
+        1 => 0,
+        2 => BC7_PARTITIONS_2[partition * 16 + texel] as usize,
+        _ => BC7_PARTITIONS_3[partition * 16 + texel] as usize,
+    };
+    let anchors = match subsets {
+        1 => [0, 0, 0],
+        2 => [0, BC7_ANCHORS_2[partition], 0],
+        _ => [0, BC7_ANCHORS_3[0][partition], BC7_ANCHORS_3[1][partition]],
+    };
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = anchors[subset_of(texel)] as usize == texel;
+        *index = bits.read(index_bits - u32::from(anchor));
+    }
+    let mut indices_2 = [0; 16];
+    if index_bits_2 > 0 {
+        for (texel, index) in indices_2.iter_mut().enumerate() {
+            *index = bits.read(index_bits_2 - u32::from(texel == 0));
+        }
+    }
+    let interpolate = |e0: u32, e1: u32, index: u32, bits: u32| {
+        let weight = BC7_WEIGHTS[bits as usize - 2][index as usize];
+        (((64 - weight) * e0 + weight * e1 + 32) >> 6) as u8
+    };
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let [e0, e1] = endpoints[subset_of(texel)];
+        // Modes 4 and 5 weight colors and alpha separately
+        let ((color_index, color_bits), (alpha_index, alpha_bits)) = if index_bits_2 == 0 {
+            ((indices[texel], index_bits), (indices[texel], index_bits))
+        } else if selection == 0 {
+            (
+                (indices[texel], index_bits),
+                (indices_2[texel], index_bits_2),
+            )
+        } else {
+            (
+                (indices_2[texel], index_bits_2),
+                (indices[texel], index_bits),
+            )
+        };
+        let mut color: [u8; 4] = std::array::from_fn(|channel| {
+            if channel < 3 {
+                interpolate(e0[channel], e1[channel], color_index, color_bits)
+            } else {
+                interpolate(e0[3], e1[3], alpha_index, alpha_bits)
+            }
+        });
+        if rotation > 0 {
+            color.swap(rotation as usize - 1, 3);
+        }
+        *out = color;
+    }
+}
+
+// The number of values in each ASTC quantization range
+const ASTC_RANGES: [u32; 21] = [
+    2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256,
+];
+
+// The trits, quints, and bits each value of a range is encoded with
+fn astc_range_encoding(range: usize) -> (u32, u32, u32) {
+    let n = ASTC_RANGES[range];
+    if n % 3 == 0 {
+        (1, 0, (n / 3).trailing_zeros())
+    } else if n % 5 == 0 {
+        (0, 1, (n / 5).trailing_zeros())
+    } else {
+        (0, 0, n.trailing_zeros())
+    }
+}
+
+// How many bits `count` values of a range take up
+fn astc_ise_bits(range: usize, count: u32) -> u32 {
+    let (trits, quints, bits) = astc_range_encoding(range);
+    count * bits + trits * (8 * count).div_ceil(5) + quints * (7 * count).div_ceil(3)
+}
+
+// Decodes an integer sequence, returning each value's trit or quint
+// (or 0) and its low bits
+fn astc_decode_ise(data: u128, range: usize, count: u32) -> Vec<(u32, u32)> {
+    let (trits, quints, bits) = astc_range_encoding(range);
+    let len = astc_ise_bits(range, count);
+    // Bits past the end of the sequence read as zero
+    let data = if len >= 128 {
+        data
+    } else {
+        data & ((1u128 << len) - 1)
+    };
+    let mut reader = Bits::new(data, 0);
+    let mut values = Vec::with_capacity(count as usize + 4);
+    while (values.len() as u32) < count {
+        if trits == 1 {
+            let mut m = [0; 5];
+            let mut t = 0;
+            for (i, (shift, width)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].iter().enumerate() {
+                m[i] = reader.read(bits);
+                t |= reader.read(*width) << shift;
+            }
+            for (i, trit) in astc_trits(t).into_iter().enumerate() {
+                values.push((trit, m[i]));
+            }
+        } else if quints == 1 {
+            let mut m = [0; 3];
+            let mut q = 0;
+            for (i, (shift, width)) in [(0, 3), (3, 2), (5, 2)].iter().enumerate() {
+                m[i] = reader.read(bits);
+                q |= reader.read(*width) << shift;
+            }
+            for (i, quint) in astc_quints(q).into_iter().enumerate() {
+                values.push((quint, m[i]));
+            }
+        } else {
+            values.push((0, reader.read(bits)));
+        }
+    }
+    values.truncate(count as usize);
+    values
+}
+
+fn astc_trits(t: u32) -> [u32; 5] {
+    let bit = |n: u32| (t >> n) & 1;
+    let (c, t4, t3);
+    if (t >> 2) & 7 == 7 {
+        c = ((t >> 5) << 2) | (t & 3);
+        (t4, t3) = (2, 2);
+    } else {
+        c = t & 0x1F;
+        if (t >> 5) & 3 == 3 {
+            (t4, t3) = (2, bit(7));
+        } else {
+            (t4, t3) = (bit(7), (t >> 5) & 3);
+        }
+    }
+    let cbit = |n: u32| (c >> n) & 1;
+    let (t2, t1, t0);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = cbit(4);
+        t0 = (cbit(3) << 1) | (cbit(2) & !cbit(3) & 1);
+    } else if (c >> 2) & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = cbit(4);
+        t1 = (c >> 2) & 3;
+        t0 = (cbit(1) << 1) | (cbit(0) & !cbit(1) & 1);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn astc_quints(q: u32) -> [u32; 3] {
+    let bit = |n: u32| (q >> n) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = (bit(0) << 2) | ((bit(4) & !bit(0) & 1) << 1) | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c);
+    if (q >> 1) & 3 == 3 {
+        q2 = 4;
+        c = (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | bit(0);
+    } else {
+        q2 = (q >> 5) & 3;
+        c = q & 0x1F;
+    }
+    let (q1, q0) = if c & 7 == 5 {
+        (4, (c >> 3) & 3)
+    } else {
+        ((c >> 3) & 3, c & 7)
+    };
+    [q0, q1, q2]
+}
+
+// Replicates the low `bits` bits of `value` to fill `to` bits
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    if bits == 0 {
+        return 0;
+    }
+    let mut out = 0;
+    let mut filled = 0;
+    while filled < to {
+        out = (out << bits) | value;
+        filled += bits;
+    }
+    out >> (filled - to)
+}
+
+// Unquantizes a color endpoint value to 0..=255
+fn astc_unquantize_color(range: usize, (digit, m): (u32, u32)) -> u32 {
+    let (trits, quints, bits) = astc_range_encoding(range);
+    if trits == 0 && quints == 0 {
+        return replicate(m, bits, 8);
+    }
+    let bit = |n: u32| (m >> n) & 1;
+    let a = if m & 1 == 1 { 0x1FF } else { 0 };
+    let (b, c) = match (trits, bits) {
+        (1, 1) => (0, 204),
+        (1, 2) => {
+            let b = bit(1);
+            ((b << 8) | (b << 4) | (b << 2) | (b << 1), 93)
+        }
+        (1, 3) => {
+            let (c, b) = (bit(2), bit(1));
+            ((c << 8) | (b << 7) | (c << 3) | (b << 2) | (c << 1) | b, 44)
+        }
+        (1, 4) => {
+            let dcb = (m >> 1) & 7;
+            ((dcb << 6) | dcb, 22)
+        }
+        (1, 5) => {
+            let edcb = (m >> 1) & 0xF;
+            ((edcb << 5) | (edcb >> 2), 11)
+        }
+        (1, _) => {
+            let fedcb = (m >> 1) & 0x1F;
+            ((fedcb << 4) | (fedcb >> 4), 5)
+        }
+        (_, 1) => (0, 113),
+        (_, 2) => {
+            let b = bit(1);
+            ((b << 8) | (b << 3) | (b << 2), 54)
+        }
+        (_, 3) => {
+            let cb = (m >> 1) & 3;
+            ((cb << 7) | (cb << 1) | (cb >> 1), 26)
+        }
+        (_, 4) => {
+            let dcb = (m >> 1) & 7;
+            ((dcb << 6) | (dcb >> 1), 13)
+        }
+        _ => {
+            let edcb = (m >> 1) & 0xF;
+            ((edcb << 5) | (edcb >> 3), 6)
+        }
+    };
+    let t = (digit * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+// Unquantizes a weight to 0..=64
+fn astc_unquantize_weight(range: usize, (digit, m): (u32, u32)) -> u32 {
+    let (trits, quints, bits) = astc_range_encoding(range);
+    let w = if trits == 0 && quints == 0 {
+        replicate(m, bits, 6)
+    } else if bits == 0 {
+        match (trits, digit) {
+            (1, _) => [0, 32, 63][digit as usize],
+            _ => [0, 16, 32, 47, 63][digit as usize],
+        }
+    } else {
+        let bit = |n: u32| (m >> n) & 1;
+        let a = if m & 1 == 1 { 0x7F } else { 0 };
+        let (b, c) = match (trits, bits) {
+            (1, 1) => (0, 50),
+            (1, 2) => {
+                let b = bit(1);
+                ((b << 6) | (b << 2) | b, 23)
+            }
+            (1, _) => {
+                let (c, b) = (bit(2), bit(1));
+                ((c << 6) | (b << 5) | (c << 1) | b, 11)
+            }
+            (_, 1) => (0, 28),
+            _ => {
+                let b = bit(1);
+                ((b << 6) | (b << 1), 13)
+            }
+        };
+        let t = (digit * c + b) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+    if w > 32 {
+        w + 1
+    } else {
+        w
+    }
+}
+
+// The weight grid size, whether there are two weight planes, and the
+// weight range of a block mode, if it's valid
+fn astc_block_mode(mode: u32) -> Option<(u32, u32, bool, usize)> {
+    let bit = |n: u32| (mode >> n) & 1;
+    let mut high = bit(9);
+    let mut dual = bit(10);
+    let a = (mode >> 5) & 3;
+    let (r, w, h);
+    if mode & 3 != 0 {
+        r = bit(4) | ((mode & 3) << 1);
+        let b = (mode >> 7) & 3;
+        (w, h) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(8) == 1 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        };
+    } else {
+        r = bit(4) | (((mode >> 2) & 3) << 1);
+        if (mode >> 2) & 3 == 0 {
+            return None;
+        }
+        let b = (mode >> 9) & 3;
+        (w, h) = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                (high, dual) = (0, 0);
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+    }
+    Some((w, h, dual == 1, (r - 2 + 6 * high) as usize))
+}
+
+fn astc_hash(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn astc_partition(seed: u32, x: u32, y: u32, partitions: u32, small: bool) -> usize {
+    let (x, y) = if small { (x << 1, y << 1) } else { (x, y) };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = astc_hash(seed);
+    let mut s = [
+        rnum & 0xF,
+        (rnum >> 4) & 0xF,
+        (rnum >> 8) & 0xF,
+        (rnum >> 12) & 0xF,
+        (rnum >> 16) & 0xF,
+        (rnum >> 20) & 0xF,
+        (rnum >> 24) & 0xF,
+        (rnum >> 28) & 0xF,
+        (rnum >> 18) & 0xF,
+        (rnum >> 22) & 0xF,
+        (rnum >> 26) & 0xF,
+        rnum.rotate_left(2) & 0xF,
+    ];
+    for v in s.iter_mut() {
+        *v *= *v;
+    }
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (
+            if seed & 2 == 2 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 == 2 { 4 } else { 5 },
+        )
+    };
+    let sh3 = if seed & 0x10 != 0 { sh1 } else { sh2 };
+    for (i, v) in s.iter_mut().enumerate() {
+        *v >>= match i {
+            0..=7 if i % 2 == 0 => sh1,
+            0..=7 => sh2,
+            _ => sh3,
+        };
+    }
+    let a = (s[0] * x + s[1] * y + (rnum >> 14)) & 0x3F;
+    let b = (s[2] * x + s[3] * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions < 3 {
+        0
+    } else {
+        (s[4] * x + s[5] * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if partitions < 4 {
+        0
+    } else {
+        (s[6] * x + s[7] * y + (rnum >> 2)) & 0x3F
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+// Decodes a pair of LDR color endpoints, or returns None for HDR modes
+fn astc_endpoints(mode: u32, v: &[u32]) -> Option<[[i32; 4]; 2]> {
+    let v: Vec<i32> = v.iter().map(|&x| x as i32).collect();
+    // Moves the top bit of b into a and leaves b as a signed offset
+    let transfer = |a: i32, b: i32| {
+        let (mut a, mut b) = (a, b);
+        a = (a >> 1) | (b & 0x80);
+        b = (b >> 1) & 0x3F;
+        if b & 0x20 != 0 {
+            b -= 0x40;
+        }
+        (a, b)
+    };
+    let blue_contract = |[r, g, b, a]: [i32; 4]| [(r + b) >> 1, (g + b) >> 1, b, a];
+    let clamp = |e: [i32; 4]| e.map(|c| c.clamp(0, 255));
+    Some(match mode {
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (l0, dl) = transfer(v[0], v[1]);
+            let (a0, da) = transfer(v[2], v[3]);
+            [
+                [l0, l0, l0, a0],
+                clamp([l0 + dl, l0 + dl, l0 + dl, a0 + da]),
+            ]
+        }
+        6 | 10 => {
+            let (a0, a1) = if mode == 6 { (255, 255) } else { (v[4], v[5]) };
+            [
+                [
+                    (v[0] * v[3]) >> 8,
+                    (v[1] * v[3]) >> 8,
+                    (v[2] * v[3]) >> 8,
+                    a0,
+                ],
+                [v[0], v[1], v[2], a1],
+            ]
+        }
+        8 | 12 => {
+            let (a0, a1) = if mode == 8 { (255, 255) } else { (v[6], v[7]) };
+            let e0 = [v[0], v[2], v[4], a0];
+            let e1 = [v[1], v[3], v[5], a1];
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [e0, e1]
+            } else {
+                [blue_contract(e1), blue_contract(e0)]
+            }
+        }
+        9 | 13 => {
+            let (r, dr) = transfer(v[0], v[1]);
+            let (g, dg) = transfer(v[2], v[3]);
+            let (b, db) = transfer(v[4], v[5]);
+            let (a, da) = if mode == 9 {
+                (255, 0)
+            } else {
+                transfer(v[6], v[7])
+            };
+            let e0 = [r, g, b, a];
+            let e1 = [r + dr, g + dg, b + db, a + da];
+            if dr + dg + db >= 0 {
+                [e0, clamp(e1)]
+            } else {
+                [clamp(blue_contract(e1)), blue_contract(e0)]
+            }
+        }
+        _ => return None,
+    })
+}
+
+fn decode_astc(block: &[u8], (bw, bh): (u32, u32), srgb: bool, texels: &mut [[u8; 4]]) {
+    if decode_astc_block(block, (bw, bh), srgb, texels).is_none() {
+        texels.fill(ASTC_ERROR);
+    }
+}
+
+fn decode_astc_block(
+    block: &[u8],
+    (bw, bh): (u32, u32),
+    srgb: bool,
+    texels: &mut [[u8; 4]],
+) -> Option<()> {
+    let data = u128::from_le_bytes(block.try_into().unwrap());
+    let mut bits = Bits::new(data, 0);
+    let mode = bits.read(11);
+    if mode & 0x1FF == 0x1FC {
+        // A void-extent block of a single color
+        if mode & 0x200 != 0 || (mode >> 10) & 1 == 0 || bits.read(1) == 0 {
+            return None;
+        }
+        let extents: [u32; 4] = std::array::from_fn(|_| bits.read(13));
+        let all_ones = extents.iter().all(|&e| e == 0x1FFF);
+        if !all_ones && (extents[0] >= extents[1] || extents[2] >= extents[3]) {
+            return None;
+        }
+        let color: [u8; 4] = std::array::from_fn(|_| (bits.read(16) >> 8) as u8);
+        texels.fill(color);
+        return Some(());
+    }
+    let (gw, gh, dual, weight_range) = astc_block_mode(mode)?;
+    let planes = 1 + u32::from(dual);
+    let weight_count = gw * gh * planes;
+    let weight_bits = astc_ise_bits(weight_range, weight_count);
+    if gw > bw || gh > bh || weight_count > 64 || !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+    let partitions = bits.read(2) + 1;
+    if partitions == 4 && dual {
+        return None;
+    }
+    let mut below_weights = 128 - weight_bits;
+    let mut color_modes = [0; 4];
+    let (seed, color_start);
+    if partitions == 1 {
+        seed = 0;
+        color_modes[0] = bits.read(4);
+        color_start = 17;
+    } else {
+        seed = bits.read(10);
+        let low = bits.read(6);
+        color_start = 29;
+        if low & 3 == 0 {
+            color_modes = [low >> 2; 4];
+        } else {
+            let extra_bits = 3 * partitions - 4;
+            below_weights -= extra_bits;
+            let high = Bits::new(data, below_weights).read(extra_bits);
+            let encoded = low | (high << 6);
+            let base = (encoded & 3) - 1;
+            for i in 0..partitions {
+                let class = ((encoded >> (2 + i)) & 1) + base;
+                let low_bits = (encoded >> (2 + partitions + 2 * i)) & 3;
+                color_modes[i as usize] = (class << 2) | low_bits;
+            }
+        }
+    }
+    let color_count: u32 = color_modes[..partitions as usize]
+        .iter()
+        .map(|mode| ((mode >> 2) + 1) * 2)
+        .sum();
+    if color_count > 18 {
+        return None;
+    }
+    let plane_component = if dual {
+        below_weights -= 2;
+        Some(Bits::new(data, below_weights).read(2) as usize)
+    } else {
+        None
+    };
+    let color_bits = below_weights.saturating_sub(color_start);
+    // The finest range whose values fit, at least range 6
+    let color_range = (4..ASTC_RANGES.len())
+        .rev()
+        .find(|&range| astc_ise_bits(range, color_count) <= color_bits)?;
+    let colors: Vec<u32> = astc_decode_ise(data >> color_start, color_range, color_count)
+        .into_iter()
+        .map(|value| astc_unquantize_color(color_range, value))
+        .collect();
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    let mut at = 0;
+    for (mode, endpoint) in color_modes
+        .iter()
+        .zip(endpoints.iter_mut())
+        .take(partitions as usize)
+    {
+        let count = (((mode >> 2) + 1) * 2) as usize;
+        *endpoint = astc_endpoints(*mode, &colors[at..at + count])?;
+        at += count;
+    }
+    // Weights are stored backwards from the top of the block
+    let weights: Vec<u32> = astc_decode_ise(data.reverse_bits(), weight_range, weight_count)
+        .into_iter()
+        .map(|value| astc_unquantize_weight(weight_range, value))
+        .collect();
+    let ds = (1024 + bw / 2) / (bw - 1);
+    let dt = (1024 + bh / 2) / (bh - 1);
+    let small = bw * bh < 31;
+    for y in 0..bh {
+        for x in 0..bw {
+            let gs = (ds * x * (gw - 1) + 32) >> 6;
+            let gt = (dt * y * (gh - 1) + 32) >> 6;
+            let (js, fs) = (gs >> 4, gs & 0xF);
+            let (jt, ft) = (gt >> 4, gt & 0xF);
+            let w11 = (fs * ft + 8) >> 4;
+            let (w10, w01) = (ft - w11, fs - w11);
+            let w00 = 16 + w11 - fs - ft;
+            let weight = |plane: u32| {
+                let at = |gx: u32, gy: u32| {
+                    if gx < gw && gy < gh {
+                        weights[((gy * gw + gx) * planes + plane) as usize]
+                    } else {
+                        0
+                    }
+                };
+                (at(js, jt) * w00
+                    + at(js + 1, jt) * w01
+                    + at(js, jt + 1) * w10
+                    + at(js + 1, jt + 1) * w11
+                    + 8)
+                    >> 4
+            };
+            let (w0, w1) = (weight(0), if dual { weight(1) } else { 0 });
+            let partition = if partitions == 1 {
+                0
+            } else {
+                astc_partition(seed, x, y, partitions, small)
+            };
+            let [e0, e1] = endpoints[partition];
+            let texel = &mut texels[(y * bw + x) as usize];
+            for channel in 0..4 {
+                let w = if plane_component == Some(channel) {
+                    w1
+                } else {
+                    w0
+                };
+                let expand = |c: i32| {
+                    let c = c as u32;
+                    if srgb {
+                        (c << 8) | 0x80
+                    } else {
+                        (c << 8) | c
+                    }
+                };
+                let c = (expand(e0[channel]) * (64 - w) + expand(e1[channel]) * w + 32) >> 6;
+                texel[channel] = (c >> 8) as u8;
+            }
+        }
+    }
+    Some(())
+}
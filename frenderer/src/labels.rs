@@ -0,0 +1,151 @@
+//! Text labels anchored in 3D space, such as nameplates, damage
+//! numbers, or editor annotations.  A label is a
+//! [`crate::textlayout::TextLayout`] drawn with the sprite renderer,
+//! but positioned by projecting a world-space point through a
+//! [`crate::meshes::Camera3D`]; since it is made of screen-space
+//! sprites it always faces the camera.  Labels can shrink with
+//! distance (see [`LabelOptions::reference_distance`]) and can
+//! optionally be hidden behind meshes by the shared depth buffer (see
+//! [`LabelOptions::depth_test`]).
+//!
+//! Use [`place_label`] to find where a label lands on screen this
+//! frame and [`LabelPlacement::draw`] to write its sprites, or
+//! [`crate::Immediate::draw_label`] to do both at once.
+
+use std::ops::RangeBounds;
+
+use crate::bitfont::BitFont;
+use crate::meshes::Camera3D;
+use crate::sprites::{Camera2D, SheetRegion, Transform};
+use crate::textlayout::TextLayout;
+
+/// Parameters controlling how a label is positioned and sized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LabelOptions {
+    /// Which point of the label's bounding box sits on the projected
+    /// anchor, as a fraction of its width and height from the
+    /// top-left corner; `[0.5, 1.0]` centers the label just above
+    /// the anchor.
+    pub align: [f32; 2],
+    /// An offset in sprite-space pixels added after projection (and
+    /// not affected by distance scaling), e.g. to lift a nameplate
+    /// above a character's head
+    pub offset: [f32; 2],
+    /// If set, the label is drawn at its layout size when the anchor
+    /// is this far from the camera and scaled inversely with
+    /// distance otherwise; if `None`, labels have a constant screen size.
+    pub reference_distance: Option<f32>,
+    /// The smallest scale factor distance scaling may produce
+    pub min_scale: f32,
+    /// The largest scale factor distance scaling may produce
+    pub max_scale: f32,
+    /// If true, the label's sprites take the anchor's depth so meshes
+    /// in front of it hide it; if false, the label is drawn in front
+    /// of everything else.
+    pub depth_test: bool,
+}
+
+impl Default for LabelOptions {
+    fn default() -> Self {
+        Self {
+            align: [0.5, 1.0],
+            offset: [0.0, 0.0],
+            reference_distance: None,
+            min_scale: 0.0,
+            max_scale: f32::INFINITY,
+            depth_test: false,
+        }
+    }
+}
+
+/// Where a label appears on screen for a given pair of cameras.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LabelPlacement {
+    /// The label's top-left corner in sprite space
+    pub screen_pos: [f32; 2],
+    /// The scale factor applied to the layout's glyphs
+    pub scale: f32,
+    /// The sprite depth to draw with (see [`SheetRegion::depth`])
+    pub depth: u16,
+    /// The distance from the camera plane to the anchor
+    pub distance: f32,
+}
+
+/// Projects `position` through `camera` and maps it into the sprite
+/// space described by `camera2d` (usually the camera of the sprite
+/// group the label will be drawn into), returning where a label of
+/// the given `layout` should be drawn.  Returns `None` if the anchor
+/// is behind the camera or outside its near and far planes.
+pub fn place_label(
+    camera: &Camera3D,
+    camera2d: &Camera2D,
+    position: [f32; 3],
+    layout: &TextLayout,
+    options: &LabelOptions,
+) -> Option<LabelPlacement> {
+    let clip = camera.projection_view()
+        * ultraviolet::Vec4::new(position[0], position[1], position[2], 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.xyz() / clip.w;
+    if !(0.0..=1.0).contains(&ndc.z) {
+        return None;
+    }
+    let scale = options
+        .reference_distance
+        .map(|d| (d / clip.w).clamp(options.min_scale, options.max_scale))
+        .unwrap_or(1.0);
+    let [w, h] = layout.size();
+    let anchor = [
+        camera2d.screen_pos[0] + (ndc.x + 1.0) / 2.0 * camera2d.screen_size[0],
+        camera2d.screen_pos[1] + (ndc.y + 1.0) / 2.0 * camera2d.screen_size[1],
+    ];
+    // y increases upwards in sprite space, so the top edge is at
+    // anchor.y plus the part of the label below the anchor
+    let screen_pos = [
+        anchor[0] - options.align[0] * w * scale + options.offset[0],
+        anchor[1] + options.align[1] * h * scale + options.offset[1],
+    ];
+    let depth = if options.depth_test {
+        // sprite depth 65535 is the far plane; keep labels just in front of it
+        (ndc.z * 65535.0).round().min(65534.0) as u16
+    } else {
+        0
+    };
+    Some(LabelPlacement {
+        screen_pos,
+        scale,
+        depth,
+        distance: clip.w,
+    })
+}
+
+impl LabelPlacement {
+    /// Draws `layout` at this placement, filling the first
+    /// [`TextLayout::sprite_count`] elements of `trfs` and `uvs`, and
+    /// returns how many sprites were used.  Panics under the same
+    /// conditions as [`TextLayout::draw`].
+    pub fn draw<B: RangeBounds<char>>(
+        &self,
+        font: &BitFont<B>,
+        layout: &TextLayout,
+        trfs: &mut [Transform],
+        uvs: &mut [SheetRegion],
+    ) -> usize {
+        let glyphs = layout.glyphs();
+        assert!(trfs.len() >= glyphs.len() && uvs.len() >= glyphs.len());
+        for (g, (trf, uv)) in glyphs.iter().zip(trfs.iter_mut().zip(uvs.iter_mut())) {
+            let size = [g.size[0] * self.scale, g.size[1] * self.scale];
+            *trf = Transform {
+                w: size[0].round() as u16,
+                h: size[1].round() as u16,
+                x: self.screen_pos[0] + g.pos[0] * self.scale + size[0] / 2.0,
+                y: self.screen_pos[1] + g.pos[1] * self.scale - size[1] / 2.0,
+                rot: 0.0,
+            };
+            *uv = font.char_region(g.ch, self.depth).with_colormod(g.colormod);
+        }
+        glyphs.len()
+    }
+}
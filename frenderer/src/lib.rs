@@ -19,7 +19,9 @@
 //! feature flag).  Finally, in use case (3), you'll use
 //! [`clock::Clock`], the extension trait in
 //! [`events::FrendererEvents`], and the [`input::Input`] struct to
-//! simplify your game loop's lifecycle.
+//! simplify your game loop's lifecycle.  On the web, frenderer can
+//! also render from a web worker into an `OffscreenCanvas` (see
+//! [`worker`], only available on `wasm32`).
 //!
 //! frenderer is highly modular, especially in case (1); in
 //! particular, frenderer does not need to take control of the event
@@ -34,25 +36,109 @@
 //! sprite data and [`frenderer::Renderer::render`] to draw.
 //!
 //! The 3D rendering facilities of frenderer are pretty basic at the
-//! moment, with simple perspective cameras and unlit textured or
-//! flat-colored meshes.  As in the sprite renderer, the overriding
-//! performance concern has been to minimize pipeline state changes
-//! and draw calls using features like instanced rendering, storage
-//! buffers (where available), array textures, and packing multiple
-//! meshes into a single buffer.
+//! moment, with simple perspective cameras, unlit textured meshes,
+//! and flat-colored meshes with optional per-vertex lighting.  As in
+//! the sprite renderer, the overriding performance concern has been
+//! to minimize pipeline state changes and draw calls using features
+//! like instanced rendering, storage buffers (where available), array
+//! textures, and packing multiple meshes into a single buffer.
 //!
 //! Frenderer works in retained mode, but the "engine-immediate"
 //! example shows how an immediate-mode render API could be built on
 //! top of it.
+//!
+//! With the `tracing` feature flag, frenderer reports uploads, group
+//! creation and resizing, and each section of [`Renderer::render`]
+//! as [`tracing`](https://docs.rs/tracing) spans and events, so they
+//! show up in whatever profiler the application already uses.
+//!
+//! With the `bevy_ecs` feature flag, the `ecs` module keeps sprite
+//! and mesh groups in sync with the components of a
+//! [`bevy_ecs`](https://docs.rs/bevy_ecs) world.
+//!
+//! With the `gltf` feature flag, the `gltf` module imports glTF
+//! models as textured mesh groups.
+//!
+//! With the `spritesheet` feature flag, the `spritesheet` module
+//! reads the frame rectangles and animation tags of sprite sheets
+//! exported by Aseprite or TexturePacker.
+
+// Enters a tracing span until the end of the enclosing block if the
+// `tracing` feature is on, and does nothing otherwise.
+macro_rules! trace_span {
+    ($($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($($args)*).entered();
+    };
+}
+
+// Emits a tracing event if the `tracing` feature is on, and does
+// nothing otherwise.
+macro_rules! trace_event {
+    ($($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($args)*);
+    };
+}
 
 mod gpu;
-pub use gpu::WGPU;
+pub(crate) use gpu::BufferWriter;
+pub use gpu::{
+    GpuOptions, GroupSelection, GrowthPolicy, InstancePrecision, InstanceStorage, LimitError, WGPU,
+};
 pub use wgpu;
 
+pub mod atlas;
+pub mod bloom;
+pub mod builder;
+pub mod capture;
 pub mod colorgeo;
+pub mod debug_draw;
+pub mod deferred;
+#[cfg(feature = "bevy_ecs")]
+pub mod ecs;
+pub mod embed;
+pub mod flatimport;
+pub mod fog;
 pub mod frenderer;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod grid;
+pub mod hierarchy;
+pub mod highlight;
+pub mod ktx2;
+pub mod labels;
+pub mod lighting2d;
+pub mod material;
 pub mod meshes;
+pub mod mipmap;
+pub mod mirror;
+pub mod msaa;
+pub mod outline;
+pub mod overlay;
+pub mod palette;
+pub mod particles;
+pub mod pbr;
+pub mod pick;
+pub mod postprocess;
+pub mod quadtree;
+pub mod readback;
+pub mod replay;
+pub mod scene;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+pub mod shadow;
+pub mod skinned;
 pub mod sprites;
+#[cfg(feature = "spritesheet")]
+pub mod spritesheet;
+pub mod stereo;
+pub mod target;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod tiles;
+pub mod velocity;
+pub use builder::RendererBuilder;
 pub use frenderer::*;
 
 fn range<R: std::ops::RangeBounds<usize>>(r: R, hi: usize) -> std::ops::Range<usize> {
@@ -75,8 +161,12 @@ mod events;
 pub mod input;
 #[cfg(feature = "winit")]
 pub use events::*;
+#[cfg(target_arch = "wasm32")]
+pub mod worker;
 
+pub mod animation;
 pub mod bitfont;
 pub mod nineslice;
+pub mod textlayout;
 
 pub mod clock;
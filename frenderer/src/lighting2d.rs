@@ -0,0 +1,561 @@
+//! Dynamic lighting for 2D scenes.
+//!
+//! [`Lighting2DRenderer`] draws a [`Lighting2D`]'s lights into a
+//! lightmap the size of the color target, starting from the ambient
+//! light: each [`Light2D`] adds its color, fading out quadratically
+//! towards its radius.  Lights which cast shadows are blocked by
+//! occluders, line segments in the same world space as the lights,
+//! whose shadows are extruded from the light out to infinity and
+//! masked out with the stencil buffer before the light is drawn.
+//! The scene is then multiplied by the lightmap, so lights brighter
+//! than 1.0 can brighten it (up to what the color target holds).
+//!
+//! Lights and occluders are positioned with a [`Camera2D`], which
+//! should match the camera of the sprites they light.  The lightmap
+//! is only redrawn when the lighting, the camera, or the size
+//! changes.  [`crate::Renderer`] can manage a lighting renderer for
+//! you (see [`crate::Renderer::lighting2d_set`]); it multiplies
+//! everything drawn in the main pass, so user interface sprites
+//! should be drawn afterwards.
+
+use std::borrow::Cow;
+
+use crate::{capture, sprites::Camera2D, WGPU};
+
+const LIGHTMAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// A point light in a 2D scene.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Light2D {
+    /// The light's position in world space
+    pub position: [f32; 2],
+    /// How far the light reaches, in world units
+    pub radius: f32,
+    /// The light's color
+    pub color: [f32; 3],
+    /// Multiplied with the light's color
+    pub intensity: f32,
+    /// Whether occluders block the light
+    pub casts_shadows: bool,
+}
+
+impl Light2D {
+    /// A light of the given color and reach with unit intensity,
+    /// which casts shadows.
+    pub fn new(position: [f32; 2], radius: f32, color: [f32; 3]) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+            intensity: 1.0,
+            casts_shadows: true,
+        }
+    }
+}
+
+/// The lights and occluders of a 2D scene.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lighting2D {
+    /// The light everywhere, including in shadows
+    pub ambient: [f32; 3],
+    /// The lights (only the first [`Lighting2D::MAX_LIGHTS`] are used)
+    pub lights: Vec<Light2D>,
+    /// Line segments, given by their ends, which block shadow-casting lights
+    pub occluders: Vec<[[f32; 2]; 2]>,
+}
+
+impl Lighting2D {
+    /// The largest supported number of lights.
+    pub const MAX_LIGHTS: usize = 64;
+    /// Adds the four edges of an axis-aligned rectangle to the occluders.
+    pub fn add_occluder_rect(&mut self, min: [f32; 2], max: [f32; 2]) {
+        let [x0, y0] = min;
+        let [x1, y1] = max;
+        self.occluders.extend([
+            [[x0, y0], [x1, y0]],
+            [[x1, y0], [x1, y1]],
+            [[x1, y1], [x0, y1]],
+            [[x0, y1], [x0, y0]],
+        ]);
+    }
+}
+
+impl Default for Lighting2D {
+    fn default() -> Self {
+        Self {
+            ambient: [0.2; 3],
+            lights: vec![],
+            occluders: vec![],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct Lighting2DUniforms {
+    camera: [f32; 16],
+    // Position and radius, then color times intensity
+    lights: [[[f32; 4]; 2]; Lighting2D::MAX_LIGHTS],
+}
+
+/// Draws a lightmap from a [`Lighting2D`] and multiplies a color
+/// target by it.
+pub struct Lighting2DRenderer {
+    light_pipeline: wgpu::RenderPipeline,
+    shadow_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    lighting_bind_group: wgpu::BindGroup,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group: wgpu::BindGroup,
+    lightmap: wgpu::Texture,
+    lightmap_view: wgpu::TextureView,
+    stencil_view: wgpu::TextureView,
+    // Grown to fit the occluders
+    occluder_buffer: wgpu::Buffer,
+    lighting: Lighting2D,
+    camera: Camera2D,
+    // Set when the lightmap needs to be redrawn
+    dirty: bool,
+}
+
+impl Lighting2DRenderer {
+    /// Creates a new `Lighting2DRenderer` meant to draw over color
+    /// targets of the given format and size.
+    pub fn new(
+        gpu: &WGPU,
+        color_format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        lighting: Lighting2D,
+        camera: Camera2D,
+    ) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("lighting2d:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("lighting2d.wgsl"))),
+            });
+        let lighting_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("lighting2d:lighting_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                Lighting2DUniforms,
+                            >()
+                                as u64),
+                        },
+                        count: None,
+                    }],
+                });
+        let composite_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("lighting2d:composite_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+        let stencil = |compare, pass_op| wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: wgpu::StencilFaceState {
+                    compare,
+                    fail_op: wgpu::StencilOperation::Keep,
+                    depth_fail_op: wgpu::StencilOperation::Keep,
+                    pass_op,
+                },
+                back: wgpu::StencilFaceState {
+                    compare,
+                    fail_op: wgpu::StencilOperation::Keep,
+                    depth_fail_op: wgpu::StencilOperation::Keep,
+                    pass_op,
+                },
+                read_mask: 0xff,
+                write_mask: 0xff,
+            },
+            bias: wgpu::DepthBiasState::default(),
+        };
+        // Shadows mark the stencil buffer with their light's reference
+        // value, and the light is drawn everywhere else
+        let light_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &lighting_bind_group_layout,
+            ("vs_light", "fs_light"),
+            &[],
+            wgpu::ColorTargetState {
+                format: LIGHTMAP_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+            Some(stencil(
+                wgpu::CompareFunction::NotEqual,
+                wgpu::StencilOperation::Keep,
+            )),
+        );
+        let shadow_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &lighting_bind_group_layout,
+            ("vs_shadow", "fs_shadow"),
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<[[f32; 2]; 2]>() as u64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4],
+            }],
+            wgpu::ColorTargetState {
+                format: LIGHTMAP_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            },
+            Some(stencil(
+                wgpu::CompareFunction::Always,
+                wgpu::StencilOperation::Replace,
+            )),
+        );
+        // The scene is multiplied by the lightmap, keeping its alpha
+        let composite_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &composite_bind_group_layout,
+            ("vs_composite", "fs_composite"),
+            &[],
+            wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Dst,
+                        dst_factor: wgpu::BlendFactor::Zero,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            },
+            None,
+        );
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("lighting2d:uniforms"),
+            size: std::mem::size_of::<Lighting2DUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let lighting_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lighting2d:lighting_bg"),
+            layout: &lighting_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let (lightmap, lightmap_view, stencil_view) = Self::create_targets(gpu, width, height);
+        let composite_bind_group =
+            Self::create_composite_bind_group(gpu, &composite_bind_group_layout, &lightmap_view);
+        let occluder_buffer = Self::create_occluder_buffer(gpu, 16);
+        Self {
+            light_pipeline,
+            shadow_pipeline,
+            composite_pipeline,
+            uniform_buffer,
+            lighting_bind_group,
+            composite_bind_group_layout,
+            composite_bind_group,
+            lightmap,
+            lightmap_view,
+            stencil_view,
+            occluder_buffer,
+            lighting,
+            camera,
+            dirty: true,
+        }
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        (vs_entry, fs_entry): (&str, &str),
+        buffers: &[wgpu::VertexBufferLayout],
+        color_target: wgpu::ColorTargetState,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+    ) -> wgpu::RenderPipeline {
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("lighting2d:pipeline_layout"),
+                    bind_group_layouts: &[bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(fs_entry),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: vs_entry,
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: fs_entry,
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+    }
+    fn create_targets(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView, wgpu::TextureView) {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let lightmap = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("lighting2d:lightmap"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: LIGHTMAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let stencil = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("lighting2d:stencil"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: STENCIL_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let lightmap_view = lightmap.create_view(&wgpu::TextureViewDescriptor::default());
+        let stencil_view = stencil.create_view(&wgpu::TextureViewDescriptor::default());
+        (lightmap, lightmap_view, stencil_view)
+    }
+    fn create_composite_bind_group(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        lightmap_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lighting2d:composite_bg"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(lightmap_view),
+            }],
+        })
+    }
+    fn create_occluder_buffer(gpu: &WGPU, capacity: usize) -> wgpu::Buffer {
+        gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("lighting2d:occluders"),
+            size: (capacity * std::mem::size_of::<[[f32; 2]; 2]>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    fn lights(&self) -> &[Light2D] {
+        let lights = &self.lighting.lights;
+        &lights[..lights.len().min(Lighting2D::MAX_LIGHTS)]
+    }
+    fn upload(&mut self, gpu: &WGPU) {
+        let mut uniforms = Lighting2DUniforms {
+            camera: *self.camera.projection().as_array(),
+            lights: [[[0.0; 4]; 2]; Lighting2D::MAX_LIGHTS],
+        };
+        for (data, light) in uniforms.lights.iter_mut().zip(self.lights()) {
+            let [x, y] = light.position;
+            let [r, g, b] = light.color.map(|c| c * light.intensity);
+            *data = [[x, y, light.radius, 0.0], [r, g, b, 1.0]];
+        }
+        gpu.queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+        let occluders = &self.lighting.occluders;
+        let size = std::mem::size_of_val(occluders.as_slice()) as u64;
+        if size > self.occluder_buffer.size() {
+            self.occluder_buffer =
+                Self::create_occluder_buffer(gpu, occluders.len().next_power_of_two());
+        }
+        if size > 0 {
+            gpu.queue().write_buffer(
+                &self.occluder_buffer,
+                0,
+                bytemuck::cast_slice(occluders.as_slice()),
+            );
+        }
+    }
+    /// Returns the current lights and occluders.
+    pub fn lighting(&self) -> &Lighting2D {
+        &self.lighting
+    }
+    /// Changes the lights and occluders.
+    pub fn set_lighting(&mut self, lighting: Lighting2D) {
+        self.lighting = lighting;
+        self.dirty = true;
+    }
+    /// Returns the camera lights and occluders are positioned with.
+    pub fn camera(&self) -> Camera2D {
+        self.camera
+    }
+    /// Sets the camera lights and occluders are positioned with; this
+    /// should match the camera of the sprites drawn alongside them.
+    pub fn set_camera(&mut self, camera: Camera2D) {
+        if camera != self.camera {
+            self.camera = camera;
+            self.dirty = true;
+        }
+    }
+    /// Resizes the lightmap to match a new color target size.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        (self.lightmap, self.lightmap_view, self.stencil_view) =
+            Self::create_targets(gpu, width, height);
+        self.composite_bind_group = Self::create_composite_bind_group(
+            gpu,
+            &self.composite_bind_group_layout,
+            &self.lightmap_view,
+        );
+        self.dirty = true;
+    }
+    /// Returns the lightmap, which holds the light reaching each
+    /// pixel as of the last [`Lighting2DRenderer::render`].
+    pub fn lightmap(&self) -> &wgpu::Texture {
+        &self.lightmap
+    }
+    /// Redraws the lightmap if the lighting, camera, or size changed,
+    /// then multiplies `color`, which must be the size the renderer
+    /// was created or last resized with, by it.
+    pub fn render(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        color: &wgpu::TextureView,
+    ) {
+        if self.dirty {
+            self.upload(gpu);
+            self.render_lightmap(encoder);
+            self.dirty = false;
+        }
+        capture::begin_pass("lighting2d:composite");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("lighting2d:composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        capture::set_pipeline(
+            &mut rpass,
+            &self.composite_pipeline,
+            "lighting2d:composite_pipeline",
+        );
+        capture::set_bind_group(
+            &mut rpass,
+            0,
+            &self.composite_bind_group,
+            &[],
+            "lighting2d:composite_bind_group",
+        );
+        capture::draw(&mut rpass, 0..6, 0..1);
+    }
+    fn render_lightmap(&self, encoder: &mut wgpu::CommandEncoder) {
+        let [r, g, b] = self.lighting.ambient.map(f64::from);
+        capture::begin_pass("lighting2d:lightmap");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("lighting2d:lightmap"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.lightmap_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.stencil_view,
+                depth_ops: None,
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            ..Default::default()
+        });
+        capture::set_bind_group(
+            &mut rpass,
+            0,
+            &self.lighting_bind_group,
+            &[],
+            "lighting2d:lighting_bind_group",
+        );
+        let occluders = self.lighting.occluders.len() as u32;
+        for (light, data) in (0..).zip(self.lights()) {
+            let vertices = light * 6..light * 6 + 6;
+            // Each light marks its shadows with its own value, so the
+            // stencil buffer never needs clearing between lights
+            rpass.set_stencil_reference(light + 1);
+            if data.casts_shadows && occluders > 0 {
+                capture::set_pipeline(
+                    &mut rpass,
+                    &self.shadow_pipeline,
+                    "lighting2d:shadow_pipeline",
+                );
+                capture::set_vertex_buffer(
+                    &mut rpass,
+                    0,
+                    &self.occluder_buffer,
+                    "lighting2d:occluders",
+                );
+                capture::draw(&mut rpass, vertices.clone(), 0..occluders);
+            }
+            capture::set_pipeline(
+                &mut rpass,
+                &self.light_pipeline,
+                "lighting2d:light_pipeline",
+            );
+            capture::draw(&mut rpass, vertices, 0..1);
+        }
+    }
+}
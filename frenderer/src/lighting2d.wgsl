@@ -0,0 +1,74 @@
+// Each light's position (xy) and radius (z), and its color times its
+// intensity
+struct Light {
+  position_radius: vec4<f32>,
+  color: vec4<f32>,
+}
+
+struct Lighting {
+  camera: mat4x4<f32>,
+  lights: array<Light, 64>,
+}
+
+@group(0) @binding(0)
+var<uniform> lighting: Lighting;
+// Only bound when compositing
+@group(0) @binding(1)
+var t_lightmap: texture_2d<f32>;
+
+// The corners of two triangles covering a square, as 0 or 1 along x
+// and y; each light's quads take six vertices
+fn corner(index:u32) -> vec2<f32> {
+  var corners = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+  let k = corners[index % 6u];
+  return vec2(f32(k & 1u), f32(k >> 1u));
+}
+
+struct LightOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  // -1..1 across the light's square
+  @location(0) offset: vec2<f32>,
+  @location(1) @interpolate(flat) light: u32,
+}
+
+@vertex
+fn vs_light(@builtin(vertex_index) index:u32) -> LightOutput {
+  let light = lighting.lights[index / 6u];
+  let offset = corner(index) * 2.0 - 1.0;
+  let world = light.position_radius.xy + offset * light.position_radius.z;
+  return LightOutput(lighting.camera * vec4(world, 0.0, 1.0), offset, index / 6u);
+}
+
+@fragment
+fn fs_light(in:LightOutput) -> @location(0) vec4<f32> {
+  let falloff = clamp(1.0 - length(in.offset), 0.0, 1.0);
+  return vec4(lighting.lights[in.light].color.rgb * (falloff * falloff), 0.0);
+}
+
+// The shadow of an occluder segment (xy to zw) cast by the light whose
+// vertices these are: the segment, plus its ends pushed away from
+// the light out to infinity (w = 0)
+@vertex
+fn vs_shadow(@builtin(vertex_index) index:u32, @location(0) segment:vec4<f32>) -> @builtin(position) vec4<f32> {
+  let light = lighting.lights[index / 6u].position_radius.xy;
+  let c = corner(index);
+  let end = select(segment.xy, segment.zw, c.x > 0.5);
+  let pos = select(vec4(end, 0.0, 1.0), vec4(end - light, 0.0, 0.0), c.y > 0.5);
+  return lighting.camera * pos;
+}
+
+@fragment
+fn fs_shadow() -> @location(0) vec4<f32> {
+  return vec4(0.0);
+}
+
+@vertex
+fn vs_composite(@builtin(vertex_index) index:u32) -> @builtin(position) vec4<f32> {
+  return vec4(corner(index) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Blended so that the scene is multiplied by the lightmap
+@fragment
+fn fs_composite(@builtin(position) pos:vec4<f32>) -> @location(0) vec4<f32> {
+  return textureLoad(t_lightmap, vec2<i32>(pos.xy), 0);
+}
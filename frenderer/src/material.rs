@@ -0,0 +1,333 @@
+//! Materials: custom fragment shading for sprite groups and mesh
+//! groups, described by data.  A [`Material`] is a snippet of WGSL,
+//! a block of shader parameters, some textures, and an optional
+//! blend state; the sprite, mesh, and flat renderers each draw a
+//! group that has a material with a pipeline built from their own
+//! vertex shader and the material's snippet.
+//!
+//! The snippet must define
+//! ```wgsl
+//! fn material(base: vec4<f32>, uv: vec2<f32>, frag_pos: vec4<f32>) -> vec4<f32>
+//! ```
+//! which gets the color the renderer would normally have drawn
+//! (after color modulation for sprites; `uv` is always zero for flat
+//! meshes) and the fragment's framebuffer position, and returns the
+//! color to draw.  It can declare these bindings of bind group 3:
+//!
+//! * `@binding(0)`: a uniform buffer holding [`MaterialDesc::params`]
+//! * `@binding(1)`: a filtering sampler
+//! * `@binding(2)` onwards: [`MaterialDesc::textures`] in order, as
+//!   `texture_2d_array<f32>`
+//!
+//! Instead of a snippet, a material can have a whole fragment shader
+//! (see [`MaterialDesc::fragment_entry`]), for effects which need
+//! more than the color the renderer would have drawn, e.g. sampling
+//! the group's texture at distorted coordinates.  It's compiled after
+//! the renderer's own shader source, so it can use that shader's
+//! vertex output and bindings; for sprites, that's `VertexOutput`
+//! (with `tex_coords`, `tex_index`, `colormod`, and `tint`) and the
+//! spritesheet in `t_diffuse` and `s_diffuse`.  The vertex stage and
+//! the other bind groups are the renderer's own either way.
+//!
+//! Materials only change how groups are drawn in the main pass;
+//! outlines, picking, and velocities ignore them.  The
+//! [`crate::Renderer`] keeps a registry of materials referred to by
+//! [`MaterialId`]s, but materials can also be created directly and
+//! given to the renderers' `set_material` methods.
+
+use std::sync::Arc;
+
+use crate::WGPU;
+
+/// The bind group index materials' bindings use.
+pub const MATERIAL_GROUP: u32 = 3;
+
+/// A description of a material, used to create a [`Material`].
+#[derive(Clone, Debug)]
+pub struct MaterialDesc<'a> {
+    /// Used to label the material's GPU resources and in captures
+    pub label: &'a str,
+    /// WGSL source defining the `material` function and any bindings
+    /// it uses, or a whole fragment shader if `fragment_entry` is set
+    pub shader: &'a str,
+    /// The name of the fragment entry point `shader` defines, if it's
+    /// a whole fragment shader rather than a `material` function
+    pub fragment_entry: Option<&'a str>,
+    /// The initial contents of the parameter block, which must keep
+    /// this size when updated.  It is padded up to a multiple of 16 bytes.
+    pub params: &'a [u8],
+    /// Array textures bound from `@binding(2)` onwards.  On GL
+    /// backends these can't have exactly 1 or 6 layers.
+    pub textures: &'a [&'a wgpu::Texture],
+    /// How material textures are filtered
+    pub filter: wgpu::FilterMode,
+    /// How material textures are addressed outside of `0.0..1.0`
+    pub address_mode: wgpu::AddressMode,
+    /// Overrides the blending of the renderer drawing the material
+    pub blend: Option<wgpu::BlendState>,
+}
+
+impl Default for MaterialDesc<'_> {
+    fn default() -> Self {
+        Self {
+            label: "material",
+            shader: "",
+            fragment_entry: None,
+            params: &[],
+            textures: &[],
+            filter: wgpu::FilterMode::Nearest,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            blend: None,
+        }
+    }
+}
+
+struct MaterialData {
+    label: String,
+    shader: String,
+    fragment_entry: Option<String>,
+    blend: Option<wgpu::BlendState>,
+    params_len: usize,
+    params_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A material created on the GPU.  Materials are cheap to clone;
+/// clones share their parameter block.
+#[derive(Clone)]
+pub struct Material(Arc<MaterialData>);
+
+impl Material {
+    /// Creates a material's parameter buffer, sampler, and bindings.
+    /// The shader is only compiled once a renderer draws a group with
+    /// the material.  Panics if a texture has 1 or 6 layers on a GL
+    /// backend.
+    pub fn new(gpu: &WGPU, desc: &MaterialDesc) -> Self {
+        let params_size = desc.params.len().next_multiple_of(16).max(16);
+        let params_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some(desc.label),
+            size: params_size as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        write_padded(gpu, &params_buffer, desc.params);
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(desc.label),
+            address_mode_u: desc.address_mode,
+            address_mode_v: desc.address_mode,
+            address_mode_w: desc.address_mode,
+            mag_filter: desc.filter,
+            min_filter: desc.filter,
+            ..Default::default()
+        });
+        let mut layout_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+        let views: Vec<_> = desc
+            .textures
+            .iter()
+            .map(|tex| {
+                if gpu.is_gl()
+                    && (tex.depth_or_array_layers() == 1 || tex.depth_or_array_layers() == 6)
+                {
+                    panic!("Array textures with 1 or 6 layers aren't supported in webgl or other GL backends {:?}", tex);
+                }
+                tex.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2Array),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ];
+        for (binding, view) in (2..).zip(views.iter()) {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+        }
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(desc.label),
+                    entries: &layout_entries,
+                });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(desc.label),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+        Self(Arc::new(MaterialData {
+            label: desc.label.to_string(),
+            shader: desc.shader.to_string(),
+            fragment_entry: desc.fragment_entry.map(str::to_string),
+            blend: desc.blend,
+            params_len: desc.params.len(),
+            params_buffer,
+            bind_group_layout,
+            bind_group,
+        }))
+    }
+    /// Returns the material's label.
+    pub fn label(&self) -> &str {
+        &self.0.label
+    }
+    /// Replaces the material's parameter block.  Panics if `params`
+    /// is not the size the material was created with.
+    pub fn set_params(&self, gpu: &WGPU, params: &[u8]) {
+        assert_eq!(
+            params.len(),
+            self.0.params_len,
+            "Material parameters can't change size"
+        );
+        write_padded(gpu, &self.0.params_buffer, params);
+    }
+    /// Compiles the material for one of the renderers, after the
+    /// renderer's own shader source and, unless the material has its
+    /// own fragment entry point, one which calls the material's
+    /// `material` function.
+    pub(crate) fn create_shader(
+        &self,
+        gpu: &WGPU,
+        base_shader: &str,
+        entry_shader: &str,
+    ) -> wgpu::ShaderModule {
+        let entry_shader = if self.0.fragment_entry.is_some() {
+            ""
+        } else {
+            entry_shader
+        };
+        let source = format!("{base_shader}\n{entry_shader}\n{}", self.0.shader);
+        gpu.device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&self.0.label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+    }
+    /// The fragment entry point to draw the material with, given the
+    /// one a renderer's `material`-calling entry point is named.
+    pub(crate) fn fragment_entry<'s>(&'s self, default: &'s str) -> &'s str {
+        self.0.fragment_entry.as_deref().unwrap_or(default)
+    }
+    /// Creates a pipeline layout with a renderer's first three bind
+    /// group layouts followed by the material's.
+    pub(crate) fn create_pipeline_layout(
+        &self,
+        gpu: &WGPU,
+        bind_group_layouts: [&wgpu::BindGroupLayout; 3],
+    ) -> wgpu::PipelineLayout {
+        gpu.device()
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&self.0.label),
+                bind_group_layouts: &[
+                    bind_group_layouts[0],
+                    bind_group_layouts[1],
+                    bind_group_layouts[2],
+                    &self.0.bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            })
+    }
+    /// Applies the material's blend state, if it has one, to a renderer's color target.
+    pub(crate) fn color_target(
+        &self,
+        color_target: &wgpu::ColorTargetState,
+    ) -> wgpu::ColorTargetState {
+        wgpu::ColorTargetState {
+            blend: self.0.blend.or(color_target.blend),
+            ..color_target.clone()
+        }
+    }
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.0.bind_group
+    }
+}
+
+// Buffer writes must be a multiple of 4 bytes long
+fn write_padded(gpu: &WGPU, buffer: &wgpu::Buffer, params: &[u8]) {
+    if params.is_empty() {
+        return;
+    }
+    let mut padded = params.to_vec();
+    padded.resize(params.len().next_multiple_of(4), 0);
+    gpu.queue().write_buffer(buffer, 0, &padded);
+}
+
+/// A group's material along with the pipeline its renderer draws it with.
+pub(crate) struct GroupMaterial {
+    pub(crate) material: Material,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    // How the pipeline is labelled in frame captures
+    pub(crate) capture_label: String,
+}
+
+impl GroupMaterial {
+    pub(crate) fn new(material: &Material, pipeline: wgpu::RenderPipeline) -> Self {
+        Self {
+            material: material.clone(),
+            pipeline,
+            capture_label: format!("material:{}", material.label()),
+        }
+    }
+}
+
+/// Creates a bind group layout with no entries and a bind group for
+/// it, to fill bind group indices a renderer leaves unused before
+/// [`MATERIAL_GROUP`].
+pub(crate) fn empty_bind_group(gpu: &WGPU) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    let layout = gpu
+        .device()
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[],
+        });
+    let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &layout,
+        entries: &[],
+    });
+    (layout, bind_group)
+}
+
+/// An opaque identifier for a material registered with a [`crate::Renderer`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct MaterialId(pub(crate) usize);
+impl MaterialId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
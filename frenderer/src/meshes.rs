@@ -22,40 +22,384 @@ use bytemuck::Zeroable;
 use std::{borrow::Cow, marker::PhantomData, ops::Range};
 use wgpu::util::{self as wutil, DeviceExt};
 
-/// A vertex for meshes in the [`MeshRenderer`].
+use crate::{
+    capture,
+    highlight::HighlightSet,
+    material::{GroupMaterial, Material},
+    outline::OutlineSet,
+    pbr::{PbrBindings, PbrParams},
+    pick::{Pick, PickTable, Ray, RayHit},
+    shadow::{
+        PointLight, PointLightsUniform, PointShadows, SunShadows, SunUniform, MAX_CASCADES,
+        MAX_POINT_LIGHTS,
+    },
+    BufferWriter, GroupSelection, GrowthPolicy, InstancePrecision, InstanceStorage, LimitError,
+};
+
+/// How many instances are drawn per chunk when instance data is held
+/// in uniform buffers; this must match `static_meshes.wgsl`.
+const UNIFORM_CHUNK: usize = 512;
+
+/// A vertex for meshes in the [`MeshRenderer`].  Besides its texture
+/// coordinates, it has a second set of coordinates into its group's
+/// lightmap, if the group has one (see [`MeshRenderer::set_lightmap`]).
+/// Vertices with a normal are shaded by the renderer's lighting and
+/// point lights, if it has any, and vertices with a tangent as well
+/// can have their normals perturbed by a normal map (see
+/// [`TextureLayers`]).
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
 pub struct Vertex {
     position: [f32; 3],
     uv_which: [f32; 3],
+    lightmap_uv: [f32; 2],
+    normal: [f32; 3],
+    tangent: [f32; 4],
 }
 impl Vertex {
     pub const ZERO: Self = Self {
         position: [0.0; 3],
         uv_which: [0.0; 3],
+        lightmap_uv: [0.0; 2],
+        normal: [0.0; 3],
+        tangent: [0.0; 4],
     };
     /// Creates a vertex with the given position, UV coordinates, and index into the texture array.
     pub fn new(position: [f32; 3], uv: [f32; 2], which: u32) -> Self {
         Self {
             position,
             uv_which: [uv[0], uv[1], f32::from_bits(which)],
+            lightmap_uv: [0.0; 2],
+            normal: [0.0; 3],
+            tangent: [0.0; 4],
+        }
+    }
+    /// Returns this vertex with the given normal, in model space.
+    /// Vertices without a normal (or with a zero normal) are never
+    /// lit.
+    pub fn with_normal(self, normal: [f32; 3]) -> Self {
+        Self { normal, ..self }
+    }
+    /// Returns this vertex with the given tangent, in model space:
+    /// the direction in which its texture's u coordinate increases,
+    /// with `w` being 1.0 or -1.0 depending on whether v increases
+    /// along the normal crossed with the tangent or against it (as
+    /// in glTF).  [`compute_tangents`] can fill these in.
+    pub fn with_tangent(self, tangent: [f32; 4]) -> Self {
+        Self { tangent, ..self }
+    }
+    /// Returns this vertex with the given coordinates into its
+    /// group's lightmap.  Lightmap coordinates usually differ from
+    /// texture coordinates, since every surface needs its own
+    /// non-overlapping part of the lightmap.
+    pub fn with_lightmap_uv(self, lightmap_uv: [f32; 2]) -> Self {
+        Self {
+            lightmap_uv,
+            ..self
         }
     }
 }
-/// A vertex for meshes in the [`FlatRenderer`].
+/// A vertex for meshes in the [`FlatRenderer`].  Its color is
+/// multiplied with the material color it indexes, so meshes can be
+/// vertex-painted or shaded with gradients.  Vertices with a normal
+/// are shaded by the renderer's [`FlatLighting`], if it has any.
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
 pub struct FlatVertex {
     position_which: [f32; 4],
+    color: [u8; 4],
+    normal: [f32; 3],
 }
 impl FlatVertex {
     pub const ZERO: Self = Self {
         position_which: [0.0; 4],
+        color: [0; 4],
+        normal: [0.0; 3],
     };
-    /// Creates a vertex with the given position and index into the color array.
+    /// Creates a white vertex with the given position and index into the color array.
     pub fn new(pos: [f32; 3], which: u32) -> Self {
+        Self::with_color(pos, which, [1.0; 4])
+    }
+    /// Creates a vertex with the given position, index into the
+    /// color array, and RGBA vertex color (stored with 8 bits per
+    /// channel).
+    pub fn with_color(pos: [f32; 3], which: u32, color: [f32; 4]) -> Self {
         Self {
             position_which: [pos[0], pos[1], pos[2], f32::from_bits(which)],
+            color: color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8),
+            normal: [0.0; 3],
+        }
+    }
+    /// Returns this vertex with the given normal, in model space.
+    /// Vertices without a normal (or with a zero normal) are never
+    /// lit.  For faceted low-poly shading, give each face its own
+    /// vertices with the face's normal.
+    pub fn with_normal(self, normal: [f32; 3]) -> Self {
+        Self { normal, ..self }
+    }
+}
+
+/// Lighting for the [`FlatRenderer`]: a hemisphere light blending
+/// from `ground` to `sky` colors by how much a normal faces up (+y),
+/// plus a directional light shading by Lambert's cosine law.  Colors
+/// are linear RGB multipliers and are computed per vertex.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FlatLighting {
+    /// The direction towards the directional light, in world space
+    pub direction: [f32; 3],
+    /// The directional light's color
+    pub color: [f32; 3],
+    /// The ambient color of normals facing straight up
+    pub sky: [f32; 3],
+    /// The ambient color of normals facing straight down
+    pub ground: [f32; 3],
+}
+impl Default for FlatLighting {
+    fn default() -> Self {
+        Self {
+            direction: [0.3, 1.0, 0.5],
+            color: [0.7; 3],
+            sky: [0.4; 3],
+            ground: [0.15; 3],
+        }
+    }
+}
+/// The Blinn-Phong highlights of lit textured meshes (see
+/// [`MeshRenderer::set_specular`]).  Flat meshes, which are lit per
+/// vertex, have no highlights.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Specular {
+    /// How bright highlights are, as a multiplier of each light's color
+    pub strength: f32,
+    /// The Blinn-Phong exponent; higher values give smaller, sharper
+    /// highlights
+    pub shininess: f32,
+}
+impl Default for Specular {
+    fn default() -> Self {
+        Self {
+            strength: 0.25,
+            shininess: 32.0,
+        }
+    }
+}
+
+/// A light for [`crate::Renderer::mesh_set_lights`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Light {
+    /// The directional light along with the hemisphere light; there
+    /// can only be one
+    Directional(FlatLighting),
+    /// A point light, of which there can be up to
+    /// [`crate::shadow::MAX_POINT_LIGHTS`]
+    Point(PointLight),
+}
+
+/// The maps in a textured mesh group's array texture besides the
+/// color (albedo) layers its vertices index (see
+/// [`MeshRenderer::set_texture_layers`]).  Normal maps hold
+/// tangent-space normals in their red, green, and blue channels,
+/// mapped from -1..1 to 0..1; they're only used by lit vertices
+/// which have tangents.  Metallic-roughness maps hold roughness in
+/// their green channel and metalness in their blue one, as in glTF;
+/// they're only used by physically based groups (see [`crate::pbr`]).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TextureLayers {
+    /// The layer holding the normal map of each albedo layer,
+    /// indexed by albedo layer; albedo layers past the end have no
+    /// normal map.  At most [`MAX_TEXTURE_LAYERS`] entries are used.
+    pub normal_maps: Vec<Option<u32>>,
+    /// The layer holding the metallic-roughness map of each albedo
+    /// layer, in the same way
+    pub metallic_roughness: Vec<Option<u32>>,
+}
+/// The number of albedo layers a [`TextureLayers`] can give maps to.
+pub const MAX_TEXTURE_LAYERS: usize = 256;
+impl TextureLayers {
+    /// Layers from 0 to `albedo_layers` are albedo, and each one's
+    /// normal map follows them in the same order.
+    pub fn paired(albedo_layers: u32) -> Self {
+        Self {
+            normal_maps: (albedo_layers..albedo_layers * 2).map(Some).collect(),
+            metallic_roughness: vec![],
+        }
+    }
+    // As laid out in the uniform buffer: a flag which is 1 if maps
+    // were decoded from sRGB, then the normal map and
+    // metallic-roughness tables with each entry being one more than
+    // the map's layer (or 0 for none)
+    fn uniform(&self, srgb: bool) -> [u32; MAX_TEXTURE_LAYERS * 2 + 4] {
+        let mut uniform = [0; MAX_TEXTURE_LAYERS * 2 + 4];
+        uniform[0] = srgb.into();
+        let (normal_maps, metallic_roughness) = uniform[4..].split_at_mut(MAX_TEXTURE_LAYERS);
+        for (table, layers) in [
+            (normal_maps, &self.normal_maps),
+            (metallic_roughness, &self.metallic_roughness),
+        ] {
+            for (entry, layer) in table.iter_mut().zip(layers) {
+                *entry = layer.map_or(0, |layer| layer + 1);
+            }
+        }
+        uniform
+    }
+}
+
+/// Fills in the tangents of vertices which have normals but no
+/// tangents (see [`Vertex::with_tangent`]) from the positions and
+/// texture coordinates of the triangles of the given meshes, as
+/// they'd be passed to [`MeshRenderer::add_mesh_group`], for normal
+/// mapping.  Vertices shared by several triangles get the average of
+/// their tangents.
+pub fn compute_tangents(vertices: &mut [Vertex], indices: &[u32], mesh_info: &[MeshEntry]) {
+    use ultraviolet::{Vec2, Vec3};
+    let mut tangents = vec![(Vec3::zero(), Vec3::zero()); vertices.len()];
+    let triangles = mesh_info
+        .iter()
+        .flat_map(|mesh| &mesh.submeshes)
+        .flat_map(|sm| {
+            indices[sm.indices.start as usize..sm.indices.end as usize]
+                .chunks_exact(3)
+                .map(|tri| [0, 1, 2].map(|i| (tri[i] as i64 + sm.vertex_base as i64) as usize))
+        });
+    for tri in triangles {
+        if tri.iter().any(|&i| i >= vertices.len()) {
+            continue;
+        }
+        let [a, b, c] = tri.map(|i| &vertices[i]);
+        let pos = |v: &Vertex| Vec3::from(v.position);
+        let uv = |v: &Vertex| Vec2::new(v.uv_which[0], v.uv_which[1]);
+        let (e1, e2) = (pos(b) - pos(a), pos(c) - pos(a));
+        let (d1, d2) = (uv(b) - uv(a), uv(c) - uv(a));
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (e1 * d2.y - e2 * d1.y) / det;
+        let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+        for i in tri {
+            tangents[i].0 += tangent;
+            tangents[i].1 += bitangent;
+        }
+    }
+    for (vertex, (tangent, bitangent)) in vertices.iter_mut().zip(tangents) {
+        let normal = Vec3::from(vertex.normal);
+        if vertex.tangent != [0.0; 4] || normal.mag_sq() == 0.0 {
+            continue;
+        }
+        let normal = normal.normalized();
+        // Made perpendicular to the normal
+        let tangent = tangent - normal * normal.dot(tangent);
+        if tangent.mag_sq() == 0.0 {
+            continue;
+        }
+        let tangent = tangent.normalized();
+        let w = if normal.cross(tangent).dot(bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, w];
+    }
+}
+
+impl FlatLighting {
+    /// The lighting as laid out in the camera uniform; the first
+    /// vector's `w` is 1.0 when lighting is on.
+    fn uniform(lighting: Option<&Self>) -> [[f32; 4]; 4] {
+        let Some(lighting) = lighting else {
+            return [[0.0; 4]; 4];
+        };
+        let dir = ultraviolet::Vec3::from(lighting.direction).normalized();
+        let [r, g, b] = lighting.color;
+        let [sr, sg, sb] = lighting.sky;
+        let [gr, gg, gb] = lighting.ground;
+        [
+            [dir.x, dir.y, dir.z, 1.0],
+            [r, g, b, 0.0],
+            [sr, sg, sb, 0.0],
+            [gr, gg, gb, 0.0],
+        ]
+    }
+}
+
+/// Vertex types the mesh renderers can compute bounds for.
+trait MeshVertex: bytemuck::Pod + bytemuck::Zeroable + Copy {
+    fn position(&self) -> [f32; 3];
+    /// The texture layer or material color index
+    fn which(&self) -> u32;
+    fn with_which(self, which: u32) -> Self;
+}
+impl MeshVertex for Vertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+    fn which(&self) -> u32 {
+        self.uv_which[2].to_bits()
+    }
+    fn with_which(mut self, which: u32) -> Self {
+        self.uv_which[2] = f32::from_bits(which);
+        self
+    }
+}
+impl MeshVertex for FlatVertex {
+    fn position(&self) -> [f32; 3] {
+        let [x, y, z, _] = self.position_which;
+        [x, y, z]
+    }
+    fn which(&self) -> u32 {
+        self.position_which[3].to_bits()
+    }
+    fn with_which(mut self, which: u32) -> Self {
+        self.position_which[3] = f32::from_bits(which);
+        self
+    }
+}
+
+/// Points the vertices of submeshes with a `layer` at that layer,
+/// copying vertices shared with submeshes that use another one.
+fn apply_submesh_layers<Vtx: MeshVertex>(
+    vertices: &mut Vec<Vtx>,
+    indices: &mut [u32],
+    mesh_info: &[MeshEntry],
+) {
+    let submeshes = || mesh_info.iter().flat_map(|me| me.submeshes.iter());
+    if submeshes().all(|sm| sm.layer.is_none()) {
+        return;
+    }
+    let vertex_of = |sm: &SubmeshData, idx: u32| (idx as i64 + sm.vertex_base as i64) as usize;
+    // The layer each vertex has been claimed with; submeshes without
+    // a layer claim theirs first, with the vertices' own layers
+    let mut claimed: Vec<Option<u32>> = vec![None; vertices.len()];
+    for sm in submeshes().filter(|sm| sm.layer.is_none()) {
+        for &idx in &indices[sm.indices.start as usize..sm.indices.end as usize] {
+            if let Some(vtx) = vertices.get(vertex_of(sm, idx)) {
+                claimed[vertex_of(sm, idx)] = Some(vtx.which());
+            }
+        }
+    }
+    let mut copies: std::collections::HashMap<(usize, u32), usize> = Default::default();
+    for sm in submeshes() {
+        let Some(layer) = sm.layer else {
+            continue;
+        };
+        for idx in &mut indices[sm.indices.start as usize..sm.indices.end as usize] {
+            let vtx = vertex_of(sm, *idx);
+            if vtx >= claimed.len() {
+                continue;
+            }
+            let target = match claimed[vtx] {
+                None => {
+                    claimed[vtx] = Some(layer);
+                    vertices[vtx] = vertices[vtx].with_which(layer);
+                    vtx
+                }
+                Some(which) if which == layer => vtx,
+                Some(_) => *copies.entry((vtx, layer)).or_insert_with(|| {
+                    vertices.push(vertices[vtx].with_which(layer));
+                    vertices.len() - 1
+                }),
+            };
+            *idx = (target as i64 - sm.vertex_base as i64) as u32;
         }
     }
 }
@@ -64,34 +408,341 @@ struct MeshRendererInner<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> {
     groups: Vec<Option<MeshGroupData>>,
     free_groups: Vec<usize>,
     bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
     camera_buffer: wgpu::Buffer,
     camera: Camera3D,
+    shader: wgpu::ShaderModule,
+    // Kept to compile materials with
+    shader_source: &'static str,
+    pipeline_layout: wgpu::PipelineLayout,
+    vs_entry: &'static str,
+    fs_entry: &'static str,
+    fs_material_entry: &'static str,
+    // Bound to bind group 2 before a material's bindings when
+    // instances aren't drawn in uniform chunks
+    empty_bind_group_layout: wgpu::BindGroupLayout,
+    empty_bind_group: wgpu::BindGroup,
+    vertex_stride: u64,
+    vertex_attributes: Vec<wgpu::VertexAttribute>,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    // The multisample count of the main pass
+    sample_count: u32,
     pipeline: wgpu::RenderPipeline,
+    // Only present when instances are drawn in uniform buffer chunks
+    instance_bind_group_layout: Option<wgpu::BindGroupLayout>,
+    half_instances: bool,
+    outline_pipeline: wgpu::RenderPipeline,
+    outline_pipeline_layout: wgpu::PipelineLayout,
+    outline_bind_group_layout: wgpu::BindGroupLayout,
+    fs_outline_entry: &'static str,
+    // Only its color is used
+    highlight_color: OutlineSet,
+    // Draws instances in a single color; created the first time an
+    // instance color is set
+    instance_color_pipeline: Option<wgpu::RenderPipeline>,
+    fs_pick_entry: &'static str,
+    pick_kind: u32,
+    pick_pipeline_layout: wgpu::PipelineLayout,
+    pick_pipeline: wgpu::RenderPipeline,
+    pick_bind_group_layout: wgpu::BindGroupLayout,
+    pick_table: PickTable,
+    // The first pick table entry of each group
+    pick_first_entries: Vec<usize>,
+    vs_velocity_entry: &'static str,
+    velocity_pipeline_layout: wgpu::PipelineLayout,
+    velocity_pipeline: wgpu::RenderPipeline,
+    // The camera as of the last velocity pass, if there was one
+    prev_camera: Option<Camera3D>,
+    prev_camera_buffer: wgpu::Buffer,
+    prev_camera_bind_group: wgpu::BindGroup,
+    growth: GrowthPolicy,
+    keep_geometry: bool,
+    // The lighting uniform following the camera, copied into camera views
+    lighting: [[f32; 4]; 4],
+    camera_views: Vec<Option<CameraView>>,
+    point_lights: Vec<PointLight>,
+    light_bindings: LightBindings,
+    // One camera for each face of each light, created with the first
+    // shadow-casting light, and the depth-only pipeline they draw with
+    shadow_cameras: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    shadow_pipeline: Option<wgpu::RenderPipeline>,
+    // One camera for each cascade of the sun's shadows, created when
+    // they're turned on
+    sun_cameras: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    fs_gbuffer_entry: &'static str,
+    // Created the first time a G-buffer is drawn
+    gbuffer_pipeline: Option<wgpu::RenderPipeline>,
+    // Draws physically based groups; created the first time a group
+    // is made physically based (only by MeshRenderer)
+    pbr_pipeline: Option<PbrPipeline>,
     _vertex_data: PhantomData<Vtx>,
 }
 
-/// Renders groups of 3D meshes with textures and no lighting.
+// The point lights, the sun, and their shadow maps bound after the
+// camera uniform in bind group 0 (see crate::shadow)
+struct LightBindings {
+    buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    // Bound when there are no shadow maps, and while drawing into them
+    empty_maps: wgpu::TextureView,
+    // The shadow maps and their size
+    maps: Option<(wgpu::TextureView, u32)>,
+    sun_buffer: wgpu::Buffer,
+    // Bound in place of the sun's atlas like empty_maps
+    empty_sun_map: wgpu::TextureView,
+    sun_map: Option<wgpu::TextureView>,
+}
+
+impl LightBindings {
+    fn new(gpu: &crate::WGPU) -> Self {
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("meshes:point_lights"),
+            size: std::mem::size_of::<PointLightsUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("meshes:shadow_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let sun_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("meshes:sun"),
+            size: std::mem::size_of::<SunUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let empty_sun_map = gpu
+            .device()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("meshes:empty_sun_map"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: crate::shadow::SHADOW_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let empty_maps = gpu
+            .device()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("meshes:empty_shadow_maps"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: crate::shadow::SHADOW_LAYERS,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: crate::shadow::SHADOW_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+        Self {
+            buffer,
+            sampler,
+            empty_maps,
+            maps: None,
+            sun_buffer,
+            empty_sun_map,
+            sun_map: None,
+        }
+    }
+    fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 5] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ]
+    }
+    /// Binds a camera buffer along with the lights, and the shadow
+    /// maps unless `shadowed` is false.
+    fn bind_group(
+        &self,
+        gpu: &crate::WGPU,
+        layout: &wgpu::BindGroupLayout,
+        camera: &wgpu::Buffer,
+        shadowed: bool,
+    ) -> wgpu::BindGroup {
+        let maps = match &self.maps {
+            Some((maps, _)) if shadowed => maps,
+            _ => &self.empty_maps,
+        };
+        let sun_map = match &self.sun_map {
+            Some(map) if shadowed => map,
+            _ => &self.empty_sun_map,
+        };
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(maps),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.sun_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(sun_map),
+                },
+            ],
+        })
+    }
+}
+
+// An extra camera which mesh groups can be drawn with instead of the
+// renderer's own (see MeshRenderer::add_camera_view)
+struct CameraView {
+    camera: Camera3D,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+struct GroupTextures {
+    texture: wgpu::TextureView,
+    srgb: bool,
+    lightmap: Option<wgpu::TextureView>,
+    layers: Option<(TextureLayers, wgpu::Buffer)>,
+    pbr: Option<(PbrParams, wgpu::Buffer)>,
+}
+
+// The pipeline physically based groups are drawn with, kept with its
+// shader and layout to recreate it when the targets change
+struct PbrPipeline {
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// Renders groups of 3D meshes with textures and optional lighting.
 pub struct MeshRenderer {
     data: MeshRendererInner<Vertex>,
+    lighting: Option<FlatLighting>,
+    specular: Specular,
+    // Each group's textures, kept to rebind them when the lightmap
+    // or texture layers change
+    group_textures: Vec<Option<GroupTextures>>,
+    lightmap_sampler: wgpu::Sampler,
+    // Bound for groups without a lightmap
+    white_lightmap: wgpu::TextureView,
+    // Bound for groups without texture layers
+    no_texture_layers: wgpu::Buffer,
+    // Created the first time a group is made physically based or an
+    // environment is set
+    pbr: Option<PbrBindings>,
 }
-/// Renders groups of 3D meshes with flat colors and no lighting.
+/// Renders groups of 3D meshes with flat colors and optional lighting.
 pub struct FlatRenderer {
     data: MeshRendererInner<FlatVertex>,
+    lighting: Option<FlatLighting>,
 }
 struct MeshGroupData {
     instance_data: Vec<Transform3D>,
     instance_buffer: wgpu::Buffer,
+    instance_bind_group: Option<wgpu::BindGroup>,
+    // Last frame's instance data, only allocated while velocities are drawn
+    prev_instances: Option<(wgpu::Buffer, Option<wgpu::BindGroup>)>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
     bind_group: wgpu::BindGroup,
     meshes: Vec<MeshData>,
+    material: Option<GroupMaterial>,
+    // Bound at MATERIAL_GROUP to draw the group with the PBR pipeline
+    pbr: Option<wgpu::BindGroup>,
+    // Instances' worth of buffer space kept by reserve_mesh_group
+    reserved: usize,
+    // Positions and indices kept for raycasting, if keep_geometry was on
+    geometry: Option<(Vec<[f32; 3]>, Vec<u32>)>,
+    // Drawn with instead of the renderer's camera (see set_group_camera)
+    camera: Option<CameraView>,
 }
 
 #[derive(Debug)]
 struct MeshData {
     instances: Range<u32>,
     submeshes: Vec<SubmeshData>,
+    bounds: MeshBounds,
+    // instance range is relative to the start of `instances`
+    outline: Option<OutlineSet>,
+    // Instance color overrides, sorted and non-overlapping; ranges are
+    // relative to the start of `instances` like the outline's
+    instance_colors: Vec<OutlineSet>,
+    // Relative to the start of `instances` too
+    highlighted: HighlightSet,
 }
 /// The range of indices and base vertex for a single submesh.
 #[derive(Debug)]
@@ -102,6 +753,12 @@ pub struct SubmeshData {
     /// submesh.  Warning: `vertex_base` values greater than 0 are not
     /// supported on some targets (notably web).
     pub vertex_base: i32,
+    /// The texture array layer (for the [`MeshRenderer`]) or
+    /// material color (for the [`FlatRenderer`]) of every vertex of
+    /// this submesh, overriding the vertices' own, or `None` to keep
+    /// theirs.  Vertices shared with submeshes using other layers
+    /// are copied when the mesh group is added.
+    pub layer: Option<u32>,
 }
 
 /// A transform in 3D space comprised of a translation, a rotation (a quaternion), and a scale.
@@ -113,12 +770,179 @@ pub struct Transform3D {
     pub rotation: [f32; 4],
 }
 
+// A Transform3D packed into half floats for InstancePrecision::Half;
+// the vertex stage unpacks these into the same vec4s as full precision
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct HalfTransform3D {
+    translation_scale: [u16; 4],
+    rotation: [u16; 4],
+}
+
+impl From<&Transform3D> for HalfTransform3D {
+    fn from(t: &Transform3D) -> Self {
+        let half = |x: f32| half::f16::from_f32(x).to_bits();
+        let [x, y, z] = t.translation;
+        Self {
+            translation_scale: [half(x), half(y), half(z), half(t.scale)],
+            rotation: t.rotation.map(half),
+        }
+    }
+}
+
+// How a renderer's instance buffers are laid out
+#[derive(Clone, Copy)]
+struct InstanceLayout {
+    // Drawn in uniform buffer chunks rather than from vertex buffers
+    chunked: bool,
+    // Packed into half floats
+    half: bool,
+}
+
+impl InstanceLayout {
+    fn instance_size(self) -> usize {
+        if self.half {
+            std::mem::size_of::<HalfTransform3D>()
+        } else {
+            std::mem::size_of::<Transform3D>()
+        }
+    }
+}
+
 impl Transform3D {
     pub const ZERO: Self = Self {
         translation: [0.0; 3],
         scale: 0.0,
         rotation: [0.0; 4],
     };
+    /// Interpolates from this transform (at `alpha` 0) to `other`
+    /// (at 1), blending rotations along the shorter way around.
+    pub fn lerp(&self, other: &Self, alpha: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * alpha;
+        let [ax, ay, az] = self.translation;
+        let [bx, by, bz] = other.translation;
+        // q and -q are the same rotation, so blend towards whichever is closer
+        let dot: f32 = (0..4).map(|i| self.rotation[i] * other.rotation[i]).sum();
+        let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+        let mut rotation = [0.0; 4];
+        for (i, r) in rotation.iter_mut().enumerate() {
+            *r = mix(self.rotation[i], sign * other.rotation[i]);
+        }
+        let len = rotation.iter().map(|r| r * r).sum::<f32>().sqrt();
+        if len > 0.0 {
+            rotation.iter_mut().for_each(|r| *r /= len);
+        }
+        Self {
+            translation: [mix(ax, bx), mix(ay, by), mix(az, bz)],
+            scale: mix(self.scale, other.scale),
+            rotation,
+        }
+    }
+    /// The model matrix instances are drawn with; this matches
+    /// `mat_from_trs` in `static_meshes.wgsl`.
+    pub(crate) fn model_matrix(&self) -> ultraviolet::Mat4 {
+        use ultraviolet::{Mat4, Vec4};
+        let [x, y, z, w] = self.rotation;
+        let s = self.scale;
+        let trans = Mat4::from_translation(self.translation.into());
+        let rot = Mat4::new(
+            Vec4::new(
+                2.0 * (x * x + y * y) - 1.0,
+                2.0 * (y * z - x * w),
+                2.0 * (y * w + x * z),
+                0.0,
+            ),
+            Vec4::new(
+                2.0 * (y * z + x * w),
+                2.0 * (x * x + z * z) - 1.0,
+                2.0 * (z * w - x * y),
+                0.0,
+            ),
+            Vec4::new(
+                2.0 * (y * w - x * z),
+                2.0 * (z * w + x * y),
+                2.0 * (x * x + w * w) - 1.0,
+                0.0,
+            ),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        );
+        trans * rot * Mat4::from_scale(s)
+    }
+}
+
+/// The extent of a mesh's vertices in model space (or in world space
+/// for one instance, see [`MeshBounds::transformed`]): an
+/// axis-aligned bounding box and a bounding sphere around the box's
+/// center.  Meshes without any triangles have zero-sized bounds at
+/// the origin.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MeshBounds {
+    /// The smallest corner of the bounding box
+    pub min: [f32; 3],
+    /// The largest corner of the bounding box
+    pub max: [f32; 3],
+    /// The center of the bounding sphere (and box)
+    pub center: [f32; 3],
+    /// The radius of the bounding sphere
+    pub radius: f32,
+}
+
+impl MeshBounds {
+    /// Computes the bounds of some points, which should not be empty.
+    fn from_points(points: impl Iterator<Item = [f32; 3]> + Clone) -> Self {
+        let (min, max) = points.clone().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), p| {
+                (
+                    std::array::from_fn(|i| min[i].min(p[i])),
+                    std::array::from_fn(|i| max[i].max(p[i])),
+                )
+            },
+        );
+        let center: [f32; 3] = std::array::from_fn(|i| (min[i] + max[i]) / 2.0);
+        let center_v = ultraviolet::Vec3::from(center);
+        let radius = points
+            .map(|p| (ultraviolet::Vec3::from(p) - center_v).mag())
+            .fold(0.0, f32::max);
+        Self {
+            min,
+            max,
+            center,
+            radius,
+        }
+    }
+    /// Returns the bounds of a mesh instance with the given transform
+    /// in world space.  The box bounds the transformed box, so it may
+    /// be looser than the mesh's own box; the sphere stays tight.
+    pub fn transformed(&self, transform: &Transform3D) -> Self {
+        let model = transform.model_matrix();
+        let corners = (0..8).map(|corner| {
+            let p = ultraviolet::Vec3::new(
+                if corner & 1 == 0 {
+                    self.min[0]
+                } else {
+                    self.max[0]
+                },
+                if corner & 2 == 0 {
+                    self.min[1]
+                } else {
+                    self.max[1]
+                },
+                if corner & 4 == 0 {
+                    self.min[2]
+                } else {
+                    self.max[2]
+                },
+            );
+            model.transform_point3(p).into()
+        });
+        let bounds = Self::from_points(corners);
+        Self {
+            center: model.transform_point3(self.center.into()).into(),
+            radius: self.radius * transform.scale.abs(),
+            ..bounds
+        }
+    }
 }
 
 /// A 3D perspective camera positioned at some point and rotated in some orientation (a quaternion).
@@ -133,12 +957,57 @@ pub struct Camera3D {
     pub fov: f32,
 }
 
+impl Camera3D {
+    /// The projection matrix for this camera.
+    pub(crate) fn projection(&self) -> ultraviolet::Mat4 {
+        ultraviolet::projection::rh_yup::perspective_wgpu_dx(
+            self.fov,
+            self.aspect,
+            self.near,
+            self.far,
+        )
+    }
+    /// The view matrix for this camera, from world space to camera space.
+    pub(crate) fn view(&self) -> ultraviolet::Mat4 {
+        let tr = ultraviolet::Vec3::from(self.translation);
+        (ultraviolet::Mat4::from_translation(tr)
+            * ultraviolet::Rotor3::from_quaternion_array(self.rotation)
+                .into_matrix()
+                .into_homogeneous())
+        .inversed()
+    }
+    /// The combined projection and view matrix for this camera.
+    pub(crate) fn projection_view(&self) -> ultraviolet::Mat4 {
+        self.projection() * self.view()
+    }
+    /// Returns the ray from the near plane through the given point in
+    /// normalized device coordinates (from -1 to 1 left to right and
+    /// bottom to top), with a normalized direction.
+    pub fn ray(&self, ndc: [f32; 2]) -> Ray {
+        let inv = self.projection_view().inversed();
+        let near = inv.transform_point3(ultraviolet::Vec3::new(ndc[0], ndc[1], 0.0));
+        let far = inv.transform_point3(ultraviolet::Vec3::new(ndc[0], ndc[1], 1.0));
+        Ray {
+            origin: near.into(),
+            direction: (far - near).normalized().into(),
+        }
+    }
+    /// Returns the world-space point drawn at the given point in
+    /// normalized device coordinates with the given depth buffer value
+    /// (e.g. from [`crate::Renderer::depth_at`]).
+    pub fn unproject(&self, ndc: [f32; 2], depth: f32) -> [f32; 3] {
+        let inv = self.projection_view().inversed();
+        inv.transform_point3(ultraviolet::Vec3::new(ndc[0], ndc[1], depth))
+            .into()
+    }
+}
+
 impl MeshRenderer {
-    /// Creates a new `MeshRenderer` meant to draw into the given color target state with the given depth texture format..
+    /// Creates a new `MeshRenderer` meant to draw into the given color target state with the given depth texture format (or without depth testing if `depth_format` is `None`).
     pub fn new(
         gpu: &crate::WGPU,
         color_target: wgpu::ColorTargetState,
-        depth_format: wgpu::TextureFormat,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
     ) -> Self {
         let bind_group_layout =
             gpu.device()
@@ -174,6 +1043,34 @@ impl MeshRenderer {
                             // No count
                             count: None,
                         },
+                        // The lightmap and its sampler
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        // The texture layers' uniform (see TextureLayers::uniform)
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
                     ],
                 });
         let vertex_layout = wgpu::VertexBufferLayout {
@@ -191,79 +1088,527 @@ impl MeshRenderer {
                     offset: std::mem::size_of::<f32>() as u64 * 3,
                     shader_location: 1,
                 },
+                // lightmap_uv
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: std::mem::size_of::<f32>() as u64 * 6,
+                    shader_location: 7,
+                },
+                // normal
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: std::mem::size_of::<f32>() as u64 * 8,
+                    shader_location: 6,
+                },
+                // tangent
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: std::mem::size_of::<f32>() as u64 * 11,
+                    shader_location: 8,
+                },
             ],
             step_mode: wgpu::VertexStepMode::Vertex,
         };
         let data = MeshRendererInner::new(
             gpu,
-            wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("static_meshes.wgsl"))),
-            "vs_main",
-            "fs_main",
+            include_str!("static_meshes.wgsl"),
+            ("vs_main", "vs_uniform_main"),
+            ("vs_velocity", "vs_velocity_uniform"),
+            (
+                "fs_main",
+                "fs_outline_mask",
+                "fs_pick",
+                "fs_material",
+                "fs_gbuffer",
+            ),
+            crate::pick::KIND_MESH,
             bind_group_layout,
             vertex_layout,
             color_target,
-            depth_format,
+            depth_format.into(),
         );
-
-        Self { data }
+        let lightmap_sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("meshes:lightmap_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let white_lightmap = gpu
+            .device()
+            .create_texture_with_data(
+                gpu.queue(),
+                &wgpu::TextureDescriptor {
+                    label: Some("meshes:white_lightmap"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wutil::TextureDataOrder::LayerMajor,
+                &[255; 4],
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let no_texture_layers = gpu
+            .device()
+            .create_buffer_init(&wutil::BufferInitDescriptor {
+                label: Some("meshes:no_texture_layers"),
+                contents: bytemuck::cast_slice(&TextureLayers::default().uniform(false)),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let mut renderer = Self {
+            data,
+            lighting: None,
+            specular: Specular::default(),
+            group_textures: vec![],
+            lightmap_sampler,
+            white_lightmap,
+            no_texture_layers,
+            pbr: None,
+        };
+        renderer.write_lighting(gpu);
+        renderer
     }
     /// Sets the given camera for all mesh groups.
     pub fn set_camera(&mut self, gpu: &crate::WGPU, camera: Camera3D) {
         self.data.set_camera(gpu, camera)
     }
-    /// Add a mesh group with the given array texture.  All meshes in
-    /// the group pull from the same vertex buffer, and each submesh
-    /// is defined in terms of a range of indices within that buffer.
-    /// When loading your mesh resources from whatever format they're
-    /// stored in, fill out vertex and index vecs while tracking the
-    /// beginning and end of each mesh and submesh (see [`MeshEntry`]
-    /// for details).
-    pub fn add_mesh_group(
+    /// Returns the camera used for all mesh groups.
+    pub fn camera(&self) -> Camera3D {
+        self.data.camera
+    }
+    /// Adds a camera view: an extra camera which mesh groups can be
+    /// drawn with using [`Self::render_with_view`] (e.g. for a
+    /// minimap or split screen), without changing the renderer's
+    /// camera.  Returns the view's index.
+    pub fn add_camera_view(&mut self, gpu: &crate::WGPU, camera: Camera3D) -> usize {
+        self.data.add_camera_view(gpu, camera)
+    }
+    /// Changes the camera of a camera view.  Panics if the view
+    /// doesn't exist.
+    pub fn set_camera_view(&mut self, gpu: &crate::WGPU, view: usize, camera: Camera3D) {
+        self.data.set_camera_view(gpu, view, camera)
+    }
+    // Like set_camera_view, but draws with the given matrix instead
+    // of the camera's own (e.g. a mirror's reflected view)
+    pub(crate) fn set_camera_view_matrix(
         &mut self,
         gpu: &crate::WGPU,
-        texture: &wgpu::Texture,
-        vertices: Vec<Vertex>,
-        indices: Vec<u32>,
-        mesh_info: Vec<MeshEntry>,
-    ) -> MeshGroup {
-        if gpu.is_gl()
-            && (texture.depth_or_array_layers() == 1 || texture.depth_or_array_layers() == 6)
-        {
-            panic!("Array textures with 1 or 6 layers aren't supported in webgl or other GL backends {:?}", texture);
-        }
-
-        let view_mesh = texture.create_view(&wgpu::TextureViewDescriptor {
-            dimension: Some(wgpu::TextureViewDimension::D2Array),
-            base_array_layer: 0,
-            array_layer_count: match texture.depth_or_array_layers() {
-                0 => Some(1),
-                layers => Some(layers),
-            },
-            ..Default::default()
-        });
-        let sampler_mesh = gpu
-            .device()
-            .create_sampler(&wgpu::SamplerDescriptor::default());
-        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
+        view: usize,
+        camera: Camera3D,
+        projection_view: ultraviolet::Mat4,
+    ) {
+        self.data
+            .set_camera_view_matrix(gpu, view, camera, projection_view)
+    }
+    /// Returns the camera of a camera view.  Panics if the view
+    /// doesn't exist.
+    pub fn camera_view(&self, view: usize) -> Camera3D {
+        self.data.camera_views[view].as_ref().unwrap().camera
+    }
+    /// Deletes a camera view; its index may be reused by a later view.
+    pub fn remove_camera_view(&mut self, view: usize) {
+        self.data.camera_views[view] = None;
+    }
+    /// Draws a group with its own camera instead of the renderer's
+    /// (e.g. a first-person viewmodel with a narrower field of view),
+    /// or with the renderer's again if `camera` is `None`.  Groups
+    /// drawn through a camera view (see [`Self::render_with_view`])
+    /// use the view's camera regardless, and the velocity pass
+    /// always uses the renderer's.
+    pub fn set_group_camera(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        camera: Option<Camera3D>,
+    ) {
+        self.data.set_group_camera(gpu, which, camera)
+    }
+    /// Returns a group's own camera, if it has one.
+    pub fn group_camera(&self, which: MeshGroup) -> Option<Camera3D> {
+        self.data.groups[which.0]
+            .as_ref()
+            .unwrap()
+            .camera
+            .as_ref()
+            .map(|view| view.camera)
+    }
+    /// Sets the point lights which light this renderer's meshes and
+    /// whose shadows they cast (see [`crate::shadow`]).  Panics if
+    /// there are more than [`MAX_POINT_LIGHTS`].
+    pub fn set_point_lights(&mut self, gpu: &crate::WGPU, lights: &[PointLight]) {
+        self.data.set_point_lights(gpu, lights)
+    }
+    /// Draws the selected mesh groups into the shadow map of one
+    /// face of a point light given to [`Self::set_point_lights`],
+    /// in a pass begun with [`PointShadows::begin_face_pass`].
+    pub fn render_point_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        light: usize,
+        face: usize,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_point_shadow(rpass, which, light, face)
+    }
+    /// Samples the given shadow maps for the shadows of
+    /// shadow-casting point lights on lit meshes, or turns point
+    /// light shadows off if `shadows` is `None` (see
+    /// [`FlatRenderer::set_point_shadows`]).
+    pub fn set_point_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&PointShadows>) {
+        self.data.set_point_shadows(gpu, shadows)
+    }
+    /// Returns the point lights set with [`Self::set_point_lights`].
+    pub fn point_lights(&self) -> &[PointLight] {
+        &self.data.point_lights
+    }
+    /// Turns lighting on with the given [`FlatLighting`], or off if
+    /// `lighting` is `None` (the default).  Unlike flat meshes,
+    /// textured meshes are lit per fragment, with highlights from
+    /// [`Self::set_specular`].  Only vertices with normals (see
+    /// [`Vertex::with_normal`]) are lit, by this and by point lights.
+    pub fn set_lighting(&mut self, gpu: &crate::WGPU, lighting: Option<FlatLighting>) {
+        self.lighting = lighting;
+        self.write_lighting(gpu);
+    }
+    /// Returns the lighting set with [`Self::set_lighting`], if any.
+    pub fn lighting(&self) -> Option<FlatLighting> {
+        self.lighting
+    }
+    /// Sets the highlights of lit meshes, from both the directional
+    /// light and point lights.
+    pub fn set_specular(&mut self, gpu: &crate::WGPU, specular: Specular) {
+        self.specular = specular;
+        self.write_lighting(gpu);
+    }
+    /// Returns the highlights set with [`Self::set_specular`].
+    pub fn specular(&self) -> Specular {
+        self.specular
+    }
+    // The specular terms ride along in the otherwise unused w
+    // components of the light color and sky color
+    fn write_lighting(&mut self, gpu: &crate::WGPU) {
+        let mut uniform = FlatLighting::uniform(self.lighting.as_ref());
+        uniform[1][3] = self.specular.strength;
+        uniform[2][3] = self.specular.shininess;
+        self.data.set_lighting(gpu, uniform)
+    }
+    /// Samples the given sun shadows, which take away the directional
+    /// light of lit meshes and darken the rest by
+    /// [`ShadowConfig::shade`](crate::shadow::ShadowConfig::shade),
+    /// or turns them off if `shadows` is `None`.  Call
+    /// [`Self::update_sun_shadows`] after each [`SunShadows::update`].
+    pub fn set_sun_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&SunShadows>) {
+        self.data.set_sun_shadows(gpu, shadows)
+    }
+    /// Uploads the cascades of sun shadows given to
+    /// [`Self::set_sun_shadows`] after they've been updated.
+    pub fn update_sun_shadows(&self, gpu: &crate::WGPU, shadows: &SunShadows) {
+        self.data.update_sun_shadows(gpu, shadows)
+    }
+    /// Draws the selected mesh groups into one cascade of the sun
+    /// shadows given to [`Self::set_sun_shadows`], in a pass begun
+    /// with [`SunShadows::begin_pass`] and restricted to the cascade
+    /// with [`SunShadows::set_cascade_viewport`].
+    pub fn render_sun_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        cascade: usize,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_sun_shadow(rpass, which, cascade)
+    }
+    /// Changes the multisample count of the render pass this
+    /// renderer draws into with `render`, re-creating its pipelines.
+    /// The default is 1 (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &crate::WGPU, sample_count: u32) {
+        self.data.set_sample_count(gpu, sample_count)
+    }
+    /// Changes the color target this renderer draws into (e.g. for a
+    /// new color format), re-creating its pipelines.
+    pub fn set_color_target(&mut self, gpu: &crate::WGPU, color_target: wgpu::ColorTargetState) {
+        self.data.set_color_target(gpu, color_target)
+    }
+    /// The multisample count this renderer draws with.
+    pub fn sample_count(&self) -> u32 {
+        self.data.sample_count
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipeline.
+    pub fn set_depth_format(
+        &mut self,
+        gpu: &crate::WGPU,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) {
+        self.data.set_depth_format(gpu, depth_format)
+    }
+    /// Add a mesh group with the given array texture.  All meshes in
+    /// the group pull from the same vertex buffer, and each submesh
+    /// is defined in terms of a range of indices within that buffer.
+    /// When loading your mesh resources from whatever format they're
+    /// stored in, fill out vertex and index vecs while tracking the
+    /// beginning and end of each mesh and submesh (see [`MeshEntry`]
+    /// for details).  Indices are stored as 16-bit values on the GPU
+    /// if they all fit.  Panics if the group would exceed the
+    /// device's limits (see [`MeshRenderer::try_add_mesh_group`]).
+    pub fn add_mesh_group(
+        &mut self,
+        gpu: &crate::WGPU,
+        texture: &wgpu::Texture,
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<MeshEntry>,
+    ) -> MeshGroup {
+        self.try_add_mesh_group(gpu, texture, vertices, indices, mesh_info)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`MeshRenderer::add_mesh_group`], but returns an error
+    /// naming the exceeded limit if the texture has too many layers
+    /// or the group's vertex, index, or instance buffers would be too
+    /// large for the device.
+    pub fn try_add_mesh_group(
+        &mut self,
+        gpu: &crate::WGPU,
+        texture: &wgpu::Texture,
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<MeshEntry>,
+    ) -> Result<MeshGroup, LimitError> {
+        if gpu.is_gl()
+            && (texture.depth_or_array_layers() == 1 || texture.depth_or_array_layers() == 6)
+        {
+            panic!("Array textures with 1 or 6 layers aren't supported in webgl or other GL backends {:?}", texture);
+        }
+        gpu.check_texture_layers(texture)?;
+
+        let view_mesh = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            base_array_layer: 0,
+            array_layer_count: match texture.depth_or_array_layers() {
+                0 => Some(1),
+                layers => Some(layers),
+            },
+            ..Default::default()
+        });
+        let textures = GroupTextures {
+            texture: view_mesh,
+            srgb: texture.format().is_srgb(),
+            lightmap: None,
+            layers: None,
+            pbr: None,
+        };
+        let bind_group = self.group_bind_group(gpu, &textures);
+        let group = self
+            .data
+            .add_mesh_group(gpu, bind_group, vertices, indices, mesh_info)?;
+        if group.0 >= self.group_textures.len() {
+            self.group_textures.resize_with(group.0 + 1, || None);
+        }
+        self.group_textures[group.0] = Some(textures);
+        Ok(group)
+    }
+    fn group_bind_group(&self, gpu: &crate::WGPU, textures: &GroupTextures) -> wgpu::BindGroup {
+        let sampler_mesh = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("meshes:texture_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            // Blends between mip levels, for textures that have them
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
             layout: &self.data.bind_group_layout,
             entries: &[
                 // One for the texture, one for the sampler
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view_mesh),
+                    resource: wgpu::BindingResource::TextureView(&textures.texture),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&sampler_mesh),
                 },
+                // And the same for the lightmap
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        textures.lightmap.as_ref().unwrap_or(&self.white_lightmap),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.lightmap_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: textures
+                        .layers
+                        .as_ref()
+                        .map_or(&self.no_texture_layers, |(_, buffer)| buffer)
+                        .as_entire_binding(),
+                },
             ],
+        })
+    }
+    /// Multiplies the colors of a mesh group's texture by the given
+    /// lightmap (e.g. baked lighting or global illumination) at each
+    /// vertex's lightmap coordinates (see [`Vertex::with_lightmap_uv`]),
+    /// or stops if `lightmap` is `None`.  The lightmap should be a
+    /// filterable 2D texture (only its first layer is used); since it's multiplied in
+    /// as is, a floating point format can brighten as well as darken.
+    pub fn set_lightmap(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        lightmap: Option<&wgpu::Texture>,
+    ) {
+        let lightmap = lightmap.map(|lightmap| {
+            lightmap.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
         });
-
-        self.data
-            .add_mesh_group(gpu, bind_group, vertices, indices, mesh_info)
+        self.group_textures[which.0].as_mut().unwrap().lightmap = lightmap;
+        self.rebind_group_textures(gpu, which);
+    }
+    /// Returns true if the given mesh group has a lightmap.
+    pub fn has_lightmap(&self, which: MeshGroup) -> bool {
+        self.group_textures[which.0]
+            .as_ref()
+            .is_some_and(|textures| textures.lightmap.is_some())
+    }
+    /// Says which layers of a mesh group's texture hold its normal
+    /// and metallic-roughness maps, or that it has none if `layers`
+    /// is `None`.  Maps should be stored linearly; if the texture's format is sRGB,
+    /// they're converted back after sampling, which is close but not
+    /// exact.  Normal mapping needs vertices with tangents (see
+    /// [`compute_tangents`]).
+    pub fn set_texture_layers(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        layers: Option<TextureLayers>,
+    ) {
+        let textures = self.group_textures[which.0].as_mut().unwrap();
+        textures.layers = layers.map(|layers| {
+            let buffer = gpu
+                .device()
+                .create_buffer_init(&wutil::BufferInitDescriptor {
+                    label: Some("meshes:texture_layers"),
+                    contents: bytemuck::cast_slice(&layers.uniform(textures.srgb)),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+            (layers, buffer)
+        });
+        self.rebind_group_textures(gpu, which);
+    }
+    /// Returns the texture layers of a mesh group given to
+    /// [`Self::set_texture_layers`], if any.
+    pub fn texture_layers(&self, which: MeshGroup) -> Option<&TextureLayers> {
+        self.group_textures[which.0]
+            .as_ref()
+            .and_then(|textures| textures.layers.as_ref())
+            .map(|(layers, _)| layers)
+    }
+    /// Draws a mesh group with physically based shading (see
+    /// [`crate::pbr`]) using the given parameters, or with the usual
+    /// shading if `params` is `None`.  A group's material, if it has
+    /// one, is drawn instead.  Panics if the given mesh group is not
+    /// populated.
+    pub fn set_group_pbr(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        params: Option<PbrParams>,
+    ) {
+        let bindings = self.pbr.get_or_insert_with(|| PbrBindings::new(gpu));
+        let textures = self.group_textures[which.0].as_mut().unwrap();
+        let Some(params) = params else {
+            textures.pbr = None;
+            self.data.set_pbr(gpu, which, None, bindings.layout());
+            return;
+        };
+        let uniform = params.uniform(bindings.environment_mips());
+        let bind_group = match textures.pbr.as_mut() {
+            Some((old, buffer)) => {
+                *old = params;
+                gpu.queue()
+                    .write_buffer(buffer, 0, bytemuck::bytes_of(&uniform));
+                None
+            }
+            None => {
+                let buffer = gpu
+                    .device()
+                    .create_buffer_init(&wutil::BufferInitDescriptor {
+                        label: Some("meshes:pbr_params"),
+                        contents: bytemuck::bytes_of(&uniform),
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    });
+                let bind_group = bindings.bind_group(gpu, &buffer);
+                textures.pbr = Some((params, buffer));
+                Some(bind_group)
+            }
+        };
+        if let Some(bind_group) = bind_group {
+            self.data
+                .set_pbr(gpu, which, Some(bind_group), bindings.layout());
+        }
+    }
+    /// Returns the physically based parameters of a mesh group given
+    /// to [`Self::set_group_pbr`], if any.
+    pub fn group_pbr(&self, which: MeshGroup) -> Option<PbrParams> {
+        self.group_textures[which.0]
+            .as_ref()
+            .and_then(|textures| textures.pbr.as_ref())
+            .map(|(params, _)| *params)
+    }
+    /// Lights physically based mesh groups with the given cubemap
+    /// (see [`crate::pbr`]), or with the hemisphere light if
+    /// `environment` is `None`.  The environment should be a
+    /// filterable texture with six square layers, in the order +x,
+    /// -x, +y, -y, +z, -z; panics if it isn't.
+    pub fn set_environment(&mut self, gpu: &crate::WGPU, environment: Option<&wgpu::Texture>) {
+        let bindings = self.pbr.get_or_insert_with(|| PbrBindings::new(gpu));
+        bindings.set_environment(environment);
+        for (which, textures) in self.group_textures.iter().enumerate() {
+            let Some((params, buffer)) = textures.as_ref().and_then(|t| t.pbr.as_ref()) else {
+                continue;
+            };
+            let uniform = params.uniform(bindings.environment_mips());
+            gpu.queue()
+                .write_buffer(buffer, 0, bytemuck::bytes_of(&uniform));
+            let bind_group = bindings.bind_group(gpu, buffer);
+            self.data
+                .set_pbr(gpu, MeshGroup(which), Some(bind_group), bindings.layout());
+        }
     }
-    /// Change the number of instances of the given mesh of the given mesh group.
+    /// Returns true if physically based mesh groups are lit by an environment.
+    pub fn has_environment(&self) -> bool {
+        self.pbr
+            .as_ref()
+            .is_some_and(|bindings| bindings.environment_mips().is_some())
+    }
+    fn rebind_group_textures(&mut self, gpu: &crate::WGPU, which: MeshGroup) {
+        let textures = self.group_textures[which.0].as_ref().unwrap();
+        let bind_group = self.group_bind_group(gpu, textures);
+        self.data.groups[which.0].as_mut().unwrap().bind_group = bind_group;
+    }
+    /// Change the number of instances of the given mesh of the given
+    /// mesh group.  When the group's instance buffer has to grow (or
+    /// may shrink), the renderer's [`GrowthPolicy`] decides its new
+    /// size.  Panics if the instance buffer would exceed the device's
+    /// limits (see [`Self::try_resize_group_mesh`]).
     pub fn resize_group_mesh(
         &mut self,
         gpu: &crate::WGPU,
@@ -271,8 +1616,42 @@ impl MeshRenderer {
         mesh_idx: usize,
         len: usize,
     ) -> usize {
+        self.try_resize_group_mesh(gpu, which, mesh_idx, len)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`Self::resize_group_mesh`], but returns an error naming
+    /// the exceeded limit (leaving the group as it was) if the
+    /// group's instance buffer would be too large for the device.
+    pub fn try_resize_group_mesh(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_idx: usize,
+        len: usize,
+    ) -> Result<usize, LimitError> {
         self.data.resize_group_mesh(gpu, which, mesh_idx, len)
     }
+    /// Makes sure the given mesh group's instance buffer has room for
+    /// at least `capacity` instances across all of its meshes, so
+    /// that resizing meshes up to that total won't reallocate it.
+    /// The space is kept even if the [`GrowthPolicy`] would shrink
+    /// the buffer.
+    pub fn reserve_mesh_group(&mut self, gpu: &crate::WGPU, which: MeshGroup, capacity: usize) {
+        self.data.reserve_mesh_group(gpu, which, capacity)
+    }
+    /// Returns how many instances the given mesh group's instance
+    /// buffer can hold without being reallocated.
+    pub fn mesh_group_capacity(&self, which: MeshGroup) -> usize {
+        self.data.mesh_group_capacity(which)
+    }
+    /// Sets how mesh groups' instance buffers grow and shrink as meshes are resized.
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.data.growth = growth;
+    }
+    /// Returns how mesh groups' instance buffers grow and shrink as meshes are resized.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.data.growth
+    }
     /// Returns how many mesh groups there are.
     pub fn mesh_group_count(&self) -> usize {
         self.data.mesh_group_count()
@@ -281,10 +1660,44 @@ impl MeshRenderer {
     pub fn mesh_count(&self, which: MeshGroup) -> usize {
         self.data.mesh_count(which)
     }
+    /// Returns true if the given mesh group is populated (i.e. it
+    /// exists and hasn't been removed).
+    pub fn has_mesh_group(&self, which: MeshGroup) -> bool {
+        self.data.has_mesh_group(which)
+    }
     /// Returns how many mesh instances there are in the given mesh of the given mesh group.
     pub fn mesh_instance_count(&self, which: MeshGroup, mesh_number: usize) -> usize {
         self.data.mesh_instance_count(which, mesh_number)
     }
+    /// Returns the model-space bounds of the given mesh of a mesh
+    /// group, computed from its vertices when the group was added.
+    pub fn mesh_bounds(&self, which: MeshGroup, mesh_number: usize) -> MeshBounds {
+        self.data.mesh_bounds(which, mesh_number)
+    }
+    /// Keeps a copy of the positions and indices of mesh groups added
+    /// from now on (or stops, if `keep` is false), so that they can
+    /// be hit by [`Self::raycast`].
+    pub fn set_keep_geometry(&mut self, keep: bool) {
+        self.data.keep_geometry = keep;
+    }
+    /// Returns the closest instance hit by the given ray, among mesh
+    /// groups which kept their geometry (see
+    /// [`Self::set_keep_geometry`]).  Like rendering, only the fronts
+    /// of triangles count.
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let (group, mesh, instance, distance) = self.data.raycast(ray)?;
+        let [ox, oy, oz] = ray.origin;
+        let [dx, dy, dz] = ray.direction;
+        Some(RayHit {
+            pick: Pick::Mesh {
+                group,
+                mesh,
+                instance,
+            },
+            distance,
+            point: [ox + dx * distance, oy + dy * distance, oz + dz * distance],
+        })
+    }
     /// Gets the transforms of every instance of the given mesh of a mesh group.
     pub fn get_meshes(&self, which: MeshGroup, mesh_number: usize) -> &[Transform3D] {
         self.data.get_meshes(which, mesh_number)
@@ -295,7 +1708,8 @@ impl MeshRenderer {
     }
     /// Deletes a mesh group, leaving its slot free to be reused.
     pub fn remove_mesh_group(&mut self, which: MeshGroup) {
-        self.data.remove_mesh_group(which)
+        self.data.remove_mesh_group(which);
+        self.group_textures[which.0] = None;
     }
     /// Uploads a range of instance data for the given mesh of a given mesh group.
     pub fn upload_meshes(
@@ -305,12 +1719,203 @@ impl MeshRenderer {
         mesh_number: usize,
         range: impl std::ops::RangeBounds<usize>,
     ) {
-        self.data.upload_meshes(gpu, which, mesh_number, range)
+        self.data.upload_meshes(
+            &mut BufferWriter::Queue(gpu.queue()),
+            which,
+            mesh_number,
+            range,
+        )
+    }
+    /// Like [`Self::upload_meshes`], but writes the instances with `writer`.
+    pub(crate) fn upload_meshes_with(
+        &mut self,
+        writer: &mut BufferWriter,
+        which: MeshGroup,
+        mesh_number: usize,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        self.data.upload_meshes(writer, which, mesh_number, range)
+    }
+    /// Writes the given transforms to the GPU in place of all of a
+    /// mesh's own instances, without changing the mesh's instance
+    /// data (e.g. for interpolated transforms which are only drawn).
+    pub(crate) fn write_drawn_instances(
+        &self,
+        writer: &mut BufferWriter,
+        which: MeshGroup,
+        mesh_number: usize,
+        transforms: &[Transform3D],
+    ) {
+        self.data
+            .write_drawn_instances(writer, which, mesh_number, transforms)
     }
     /// Uploads instance data for all the meshes of a given mesh group.
     pub fn upload_meshes_group(&mut self, gpu: &crate::WGPU, which: MeshGroup) {
         self.data.upload_meshes_group(gpu, which)
     }
+    /// Outlines the given range of instances of a mesh in a mesh
+    /// group with the given color, or removes the mesh's outline if
+    /// `color` is `None`.  Outlines are drawn by `render_outline_mask`
+    /// and [`crate::outline::OutlinePass`].
+    pub fn set_outline(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_number: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        self.data.set_outline(gpu, which, mesh_number, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a
+    /// mesh in a mesh group (see [`crate::highlight`]).  Highlighted
+    /// instances are drawn by `render_highlight_mask`.
+    pub fn set_highlighted(
+        &mut self,
+        which: MeshGroup,
+        mesh_number: usize,
+        range: impl std::ops::RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.data
+            .set_highlighted(which, mesh_number, range, highlighted)
+    }
+    /// Returns true if the given instance of a mesh is highlighted.
+    pub fn is_highlighted(&self, which: MeshGroup, mesh_number: usize, instance: usize) -> bool {
+        self.data.groups[which.0].as_ref().unwrap().meshes[mesh_number]
+            .highlighted
+            .contains(instance)
+    }
+    /// Unhighlights every instance of every mesh in a mesh group.
+    pub fn clear_highlighted(&mut self, which: MeshGroup) {
+        for mesh in self.data.groups[which.0]
+            .as_mut()
+            .unwrap()
+            .meshes
+            .iter_mut()
+        {
+            mesh.highlighted.clear();
+        }
+    }
+    /// Returns true if any mesh instance is highlighted.
+    pub fn has_highlights(&self) -> bool {
+        self.data.has_highlights()
+    }
+    /// Sets the color highlighted instances are drawn into the highlight mask with.
+    pub fn set_highlight_color(&mut self, gpu: &crate::WGPU, color: [f32; 4]) {
+        self.data.highlight_color.update(gpu, color, 0..0);
+    }
+    /// Returns the color highlighted instances are drawn into the highlight mask with.
+    pub fn highlight_color(&self) -> [f32; 4] {
+        self.data.highlight_color.color()
+    }
+    /// Draws a mesh group with a material (see [`crate::material`]),
+    /// or with the renderer's usual shading if `material` is `None`.
+    /// Panics if the given mesh group is not populated.
+    pub fn set_material(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        material: Option<&Material>,
+    ) {
+        self.data.set_material(gpu, which, material)
+    }
+    /// Returns true if any mesh has an outline.
+    pub fn has_outlines(&self) -> bool {
+        self.data.has_outlines()
+    }
+    /// Returns how many draw calls and instances [`Self::render`]
+    /// issues when drawing every mesh group.
+    pub fn draw_counts(&self) -> (usize, usize) {
+        self.data.draw_counts()
+    }
+    /// Draws the outlined instances of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target an outline mask texture (see [`crate::outline`]).
+    pub fn render_outline_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_outline_mask(rpass, which)
+    }
+    /// Draws the highlighted instances of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target a highlight mask texture (see [`crate::highlight`]).
+    pub fn render_highlight_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_highlight_mask(rpass, which)
+    }
+    /// Updates the pick table used by `render_pick`; call this
+    /// before drawing IDs whenever mesh groups or their instance
+    /// counts have changed.
+    pub fn prepare_pick(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_pick(gpu)
+    }
+    /// Draws the object IDs of the given range of mesh groups into
+    /// the given [`wgpu::RenderPass`], which should target an object
+    /// ID texture (see [`crate::pick`]).  Call `prepare_pick` first.
+    pub fn render_pick<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_pick(rpass, which)
+    }
+    /// Uploads last frame's camera and allocates copies of last
+    /// frame's instance data for any new mesh groups; call this
+    /// before `render_velocity` each frame.
+    pub fn prepare_velocity(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_velocity(gpu)
+    }
+    /// Draws the screen-space motion of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target a velocity texture (see [`crate::velocity`]).
+    pub fn render_velocity<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_velocity(rpass, which)
+    }
+    /// Records copies of this frame's instance data to compare the
+    /// next frame against; call this after the velocity pass.
+    pub fn finish_velocity(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.data.finish_velocity(encoder)
+    }
+    /// Frees last frame's instance data once velocities are no longer needed.
+    pub fn clear_velocity(&mut self) {
+        self.data.clear_velocity()
+    }
+    /// Creates the pipeline which draws into a G-buffer, if it
+    /// doesn't exist yet; call this before `render_gbuffer`.
+    pub fn prepare_gbuffer(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_gbuffer(gpu)
+    }
+    /// Draws the surfaces of the selected mesh groups into the given
+    /// [`wgpu::RenderPass`], which should target a G-buffer (see
+    /// [`crate::deferred`]).  Materials and instance colors aren't
+    /// drawn into the G-buffer.
+    pub fn render_gbuffer<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_gbuffer(rpass, which)
+    }
     /// Renders the given range of mesh groups into the given [`wgpu::RenderPass`].
     pub fn render<'s, 'pass>(
         &'s self,
@@ -319,16 +1924,46 @@ impl MeshRenderer {
     ) where
         's: 'pass,
     {
-        self.data.render(rpass, which)
+        let which = GroupSelection::Range(crate::range(which, self.data.groups.len()));
+        self.data.render(rpass, &which, None)
+    }
+    /// Renders the given range of mesh groups into the given
+    /// [`wgpu::RenderPass`] as seen through a camera view (see
+    /// [`Self::add_camera_view`]) instead of the renderer's camera.
+    /// Panics if the view doesn't exist.
+    pub fn render_with_view<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+        view: usize,
+    ) where
+        's: 'pass,
+    {
+        let which = GroupSelection::Range(crate::range(which, self.data.groups.len()));
+        self.data.render(rpass, &which, Some(view))
+    }
+    /// Renders the selected mesh groups into the given
+    /// [`wgpu::RenderPass`], as seen through a camera view (see
+    /// [`Self::add_camera_view`]) if `view` is given.  Panics if the
+    /// view doesn't exist.
+    pub fn render_selected<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        view: Option<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render(rpass, which, view)
     }
 }
 
 impl FlatRenderer {
-    /// Creates a new `FlatRenderer` meant to draw into the given color target state with the given depth texture format.
+    /// Creates a new `FlatRenderer` meant to draw into the given color target state with the given depth texture format (or without depth testing if `depth_format` is `None`).
     pub fn new(
         gpu: &crate::WGPU,
         color_target: wgpu::ColorTargetState,
-        depth_format: wgpu::TextureFormat,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
     ) -> Self {
         let bind_group_layout =
             gpu.device()
@@ -361,42 +1996,238 @@ impl FlatRenderer {
                     offset: 0,
                     shader_location: 0,
                 },
+                // color
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Unorm8x4,
+                    offset: std::mem::size_of::<f32>() as u64 * 4,
+                    shader_location: 1,
+                },
+                // normal (after the position, which, and color)
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: std::mem::size_of::<f32>() as u64 * 5,
+                    shader_location: 6,
+                },
             ],
             step_mode: wgpu::VertexStepMode::Vertex,
         };
         let data = MeshRendererInner::new(
             gpu,
-            wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("static_meshes.wgsl"))),
-            "vs_flat_main",
-            "fs_flat_main",
+            include_str!("static_meshes.wgsl"),
+            ("vs_flat_main", "vs_flat_uniform_main"),
+            ("vs_flat_velocity", "vs_flat_velocity_uniform"),
+            (
+                "fs_flat_main",
+                "fs_flat_outline_mask",
+                "fs_flat_pick",
+                "fs_flat_material",
+                "fs_flat_gbuffer",
+            ),
+            crate::pick::KIND_FLAT,
             bind_group_layout,
             vertex_layout,
             color_target,
-            depth_format,
+            depth_format.into(),
         );
 
-        Self { data }
+        Self {
+            data,
+            lighting: None,
+        }
     }
     /// Sets the given camera for all mesh groups.
     pub fn set_camera(&mut self, gpu: &crate::WGPU, camera: Camera3D) {
         self.data.set_camera(gpu, camera)
     }
-    /// Add a mesh group with the given array of material colors.  All
-    /// meshes in the group pull from the same vertex buffer, and each
-    /// submesh is defined in terms of a range of indices within that
-    /// buffer.  When loading your mesh resources from whatever format
-    /// they're stored in, fill out vertex and index vecs while
-    /// tracking the beginning and end of each mesh and submesh (see
-    /// [`MeshEntry`] for details).
-    pub fn add_mesh_group(
+    /// Returns the camera used for all mesh groups.
+    pub fn camera(&self) -> Camera3D {
+        self.data.camera
+    }
+    /// Adds a camera view: an extra camera which mesh groups can be
+    /// drawn with using [`Self::render_with_view`] (e.g. for a
+    /// minimap or split screen), without changing the renderer's
+    /// camera.  Returns the view's index.
+    pub fn add_camera_view(&mut self, gpu: &crate::WGPU, camera: Camera3D) -> usize {
+        self.data.add_camera_view(gpu, camera)
+    }
+    /// Changes the camera of a camera view.  Panics if the view
+    /// doesn't exist.
+    pub fn set_camera_view(&mut self, gpu: &crate::WGPU, view: usize, camera: Camera3D) {
+        self.data.set_camera_view(gpu, view, camera)
+    }
+    // Like set_camera_view, but draws with the given matrix instead
+    // of the camera's own (e.g. a mirror's reflected view)
+    pub(crate) fn set_camera_view_matrix(
         &mut self,
         gpu: &crate::WGPU,
-        // RGBA colors (A currently unused)
-        material_colors: &[[f32; 4]],
-        vertices: Vec<FlatVertex>,
-        indices: Vec<u32>,
-        mesh_info: Vec<MeshEntry>,
-    ) -> MeshGroup {
+        view: usize,
+        camera: Camera3D,
+        projection_view: ultraviolet::Mat4,
+    ) {
+        self.data
+            .set_camera_view_matrix(gpu, view, camera, projection_view)
+    }
+    /// Returns the camera of a camera view.  Panics if the view
+    /// doesn't exist.
+    pub fn camera_view(&self, view: usize) -> Camera3D {
+        self.data.camera_views[view].as_ref().unwrap().camera
+    }
+    /// Deletes a camera view; its index may be reused by a later view.
+    pub fn remove_camera_view(&mut self, view: usize) {
+        self.data.camera_views[view] = None;
+    }
+    /// Draws a group with its own camera instead of the renderer's
+    /// (e.g. a first-person viewmodel with a narrower field of view),
+    /// or with the renderer's again if `camera` is `None`.  Groups
+    /// drawn through a camera view (see [`Self::render_with_view`])
+    /// use the view's camera regardless, and the velocity pass
+    /// always uses the renderer's.
+    pub fn set_group_camera(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        camera: Option<Camera3D>,
+    ) {
+        self.data.set_group_camera(gpu, which, camera)
+    }
+    /// Returns a group's own camera, if it has one.
+    pub fn group_camera(&self, which: MeshGroup) -> Option<Camera3D> {
+        self.data.groups[which.0]
+            .as_ref()
+            .unwrap()
+            .camera
+            .as_ref()
+            .map(|view| view.camera)
+    }
+    /// Lights flat meshes with the given point lights (see
+    /// [`crate::shadow`]), replacing any earlier ones.  Like
+    /// [`FlatLighting`], they only light vertices with normals.
+    /// Panics if there are more than [`MAX_POINT_LIGHTS`].
+    pub fn set_point_lights(&mut self, gpu: &crate::WGPU, lights: &[PointLight]) {
+        self.data.set_point_lights(gpu, lights)
+    }
+    /// Returns the point lights set with [`Self::set_point_lights`].
+    pub fn point_lights(&self) -> &[PointLight] {
+        &self.data.point_lights
+    }
+    /// Samples the given shadow maps for the shadows of
+    /// shadow-casting point lights, or turns point light shadows off
+    /// if `shadows` is `None`.  Draw the maps with
+    /// [`Self::render_point_shadow`] (and
+    /// [`MeshRenderer::render_point_shadow`]) before drawing the
+    /// lit meshes each frame.
+    pub fn set_point_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&PointShadows>) {
+        self.data.set_point_shadows(gpu, shadows)
+    }
+    /// Draws the selected mesh groups into the shadow map of one
+    /// face of a point light given to [`Self::set_point_lights`],
+    /// in a pass begun with [`PointShadows::begin_face_pass`].
+    pub fn render_point_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        light: usize,
+        face: usize,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_point_shadow(rpass, which, light, face)
+    }
+    /// Samples the given sun shadows, which take away the directional
+    /// part of [`FlatLighting`], or turns them off if `shadows` is
+    /// `None`.  Call
+    /// [`Self::update_sun_shadows`] after each [`SunShadows::update`].
+    pub fn set_sun_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&SunShadows>) {
+        self.data.set_sun_shadows(gpu, shadows)
+    }
+    /// Uploads the cascades of sun shadows given to
+    /// [`Self::set_sun_shadows`] after they've been updated.
+    pub fn update_sun_shadows(&self, gpu: &crate::WGPU, shadows: &SunShadows) {
+        self.data.update_sun_shadows(gpu, shadows)
+    }
+    /// Draws the selected mesh groups into one cascade of the sun
+    /// shadows given to [`Self::set_sun_shadows`], in a pass begun
+    /// with [`SunShadows::begin_pass`] and restricted to the cascade
+    /// with [`SunShadows::set_cascade_viewport`].
+    pub fn render_sun_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        cascade: usize,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_sun_shadow(rpass, which, cascade)
+    }
+    /// Turns lighting on with the given [`FlatLighting`], or off if
+    /// `lighting` is `None` (the default).  Only vertices with normals
+    /// (see [`FlatVertex::with_normal`]) are lit.
+    pub fn set_lighting(&mut self, gpu: &crate::WGPU, lighting: Option<FlatLighting>) {
+        self.lighting = lighting;
+        self.data
+            .set_lighting(gpu, FlatLighting::uniform(lighting.as_ref()))
+    }
+    /// Returns the lighting set with [`Self::set_lighting`], if any.
+    pub fn lighting(&self) -> Option<FlatLighting> {
+        self.lighting
+    }
+    /// Changes the multisample count of the render pass this
+    /// renderer draws into with `render`, re-creating its pipelines.
+    /// The default is 1 (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &crate::WGPU, sample_count: u32) {
+        self.data.set_sample_count(gpu, sample_count)
+    }
+    /// Changes the color target this renderer draws into (e.g. for a
+    /// new color format), re-creating its pipelines.
+    pub fn set_color_target(&mut self, gpu: &crate::WGPU, color_target: wgpu::ColorTargetState) {
+        self.data.set_color_target(gpu, color_target)
+    }
+    /// The multisample count this renderer draws with.
+    pub fn sample_count(&self) -> u32 {
+        self.data.sample_count
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipeline.
+    pub fn set_depth_format(
+        &mut self,
+        gpu: &crate::WGPU,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) {
+        self.data.set_depth_format(gpu, depth_format)
+    }
+    /// Add a mesh group with the given array of material colors.  All
+    /// meshes in the group pull from the same vertex buffer, and each
+    /// submesh is defined in terms of a range of indices within that
+    /// buffer.  When loading your mesh resources from whatever format
+    /// they're stored in, fill out vertex and index vecs while
+    /// tracking the beginning and end of each mesh and submesh (see
+    /// [`MeshEntry`] for details).  Indices are stored as 16-bit
+    /// values on the GPU if they all fit.  Panics if the group would
+    /// exceed the device's limits (see
+    /// [`FlatRenderer::try_add_mesh_group`]).
+    pub fn add_mesh_group(
+        &mut self,
+        gpu: &crate::WGPU,
+        // RGBA colors (A currently unused)
+        material_colors: &[[f32; 4]],
+        vertices: Vec<FlatVertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<MeshEntry>,
+    ) -> MeshGroup {
+        self.try_add_mesh_group(gpu, material_colors, vertices, indices, mesh_info)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`FlatRenderer::add_mesh_group`], but returns an error
+    /// naming the exceeded limit if the group's vertex, index, or
+    /// instance buffers would be too large for the device.
+    pub fn try_add_mesh_group(
+        &mut self,
+        gpu: &crate::WGPU,
+        // RGBA colors (A currently unused)
+        material_colors: &[[f32; 4]],
+        vertices: Vec<FlatVertex>,
+        indices: Vec<u32>,
+        mesh_info: Vec<MeshEntry>,
+    ) -> Result<MeshGroup, LimitError> {
         let mat_count = material_colors.len();
         if mat_count > 256 {
             panic!("Can't support >256 materials in one group (got {mat_count})");
@@ -425,7 +2256,11 @@ impl FlatRenderer {
         self.data
             .add_mesh_group(gpu, bind_group, vertices, indices, mesh_info)
     }
-    /// Change the number of instances of the given mesh of the given mesh group.
+    /// Change the number of instances of the given mesh of the given
+    /// mesh group.  When the group's instance buffer has to grow (or
+    /// may shrink), the renderer's [`GrowthPolicy`] decides its new
+    /// size.  Panics if the instance buffer would exceed the device's
+    /// limits (see [`Self::try_resize_group_mesh`]).
     pub fn resize_group_mesh(
         &mut self,
         gpu: &crate::WGPU,
@@ -433,8 +2268,42 @@ impl FlatRenderer {
         mesh_idx: usize,
         len: usize,
     ) -> usize {
+        self.try_resize_group_mesh(gpu, which, mesh_idx, len)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`Self::resize_group_mesh`], but returns an error naming
+    /// the exceeded limit (leaving the group as it was) if the
+    /// group's instance buffer would be too large for the device.
+    pub fn try_resize_group_mesh(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_idx: usize,
+        len: usize,
+    ) -> Result<usize, LimitError> {
         self.data.resize_group_mesh(gpu, which, mesh_idx, len)
     }
+    /// Makes sure the given mesh group's instance buffer has room for
+    /// at least `capacity` instances across all of its meshes, so
+    /// that resizing meshes up to that total won't reallocate it.
+    /// The space is kept even if the [`GrowthPolicy`] would shrink
+    /// the buffer.
+    pub fn reserve_mesh_group(&mut self, gpu: &crate::WGPU, which: MeshGroup, capacity: usize) {
+        self.data.reserve_mesh_group(gpu, which, capacity)
+    }
+    /// Returns how many instances the given mesh group's instance
+    /// buffer can hold without being reallocated.
+    pub fn mesh_group_capacity(&self, which: MeshGroup) -> usize {
+        self.data.mesh_group_capacity(which)
+    }
+    /// Sets how mesh groups' instance buffers grow and shrink as meshes are resized.
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.data.growth = growth;
+    }
+    /// Returns how mesh groups' instance buffers grow and shrink as meshes are resized.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.data.growth
+    }
     /// Returns how many mesh groups there are.
     pub fn mesh_group_count(&self) -> usize {
         self.data.mesh_group_count()
@@ -443,10 +2312,44 @@ impl FlatRenderer {
     pub fn mesh_count(&self, which: MeshGroup) -> usize {
         self.data.mesh_count(which)
     }
+    /// Returns true if the given mesh group is populated (i.e. it
+    /// exists and hasn't been removed).
+    pub fn has_mesh_group(&self, which: MeshGroup) -> bool {
+        self.data.has_mesh_group(which)
+    }
     /// Returns how many mesh instances there are in the given mesh of the given mesh group.
     pub fn mesh_instance_count(&self, which: MeshGroup, mesh_number: usize) -> usize {
         self.data.mesh_instance_count(which, mesh_number)
     }
+    /// Returns the model-space bounds of the given mesh of a mesh
+    /// group, computed from its vertices when the group was added.
+    pub fn mesh_bounds(&self, which: MeshGroup, mesh_number: usize) -> MeshBounds {
+        self.data.mesh_bounds(which, mesh_number)
+    }
+    /// Keeps a copy of the positions and indices of mesh groups added
+    /// from now on (or stops, if `keep` is false), so that they can
+    /// be hit by [`Self::raycast`].
+    pub fn set_keep_geometry(&mut self, keep: bool) {
+        self.data.keep_geometry = keep;
+    }
+    /// Returns the closest instance hit by the given ray, among mesh
+    /// groups which kept their geometry (see
+    /// [`Self::set_keep_geometry`]).  Like rendering, only the fronts
+    /// of triangles count.
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        let (group, mesh, instance, distance) = self.data.raycast(ray)?;
+        let [ox, oy, oz] = ray.origin;
+        let [dx, dy, dz] = ray.direction;
+        Some(RayHit {
+            pick: Pick::Flat {
+                group,
+                mesh,
+                instance,
+            },
+            distance,
+            point: [ox + dx * distance, oy + dy * distance, oz + dz * distance],
+        })
+    }
     /// Gets the transforms of every instance of the given mesh of a mesh group.
     pub fn get_meshes(&self, which: MeshGroup, mesh_number: usize) -> &[Transform3D] {
         self.data.get_meshes(which, mesh_number)
@@ -467,12 +2370,234 @@ impl FlatRenderer {
         mesh_number: usize,
         range: impl std::ops::RangeBounds<usize>,
     ) {
-        self.data.upload_meshes(gpu, which, mesh_number, range)
+        self.data.upload_meshes(
+            &mut BufferWriter::Queue(gpu.queue()),
+            which,
+            mesh_number,
+            range,
+        )
+    }
+    /// Like [`Self::upload_meshes`], but writes the instances with `writer`.
+    pub(crate) fn upload_meshes_with(
+        &mut self,
+        writer: &mut BufferWriter,
+        which: MeshGroup,
+        mesh_number: usize,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        self.data.upload_meshes(writer, which, mesh_number, range)
+    }
+    /// Writes the given transforms to the GPU in place of all of a
+    /// mesh's own instances, without changing the mesh's instance
+    /// data (e.g. for interpolated transforms which are only drawn).
+    pub(crate) fn write_drawn_instances(
+        &self,
+        writer: &mut BufferWriter,
+        which: MeshGroup,
+        mesh_number: usize,
+        transforms: &[Transform3D],
+    ) {
+        self.data
+            .write_drawn_instances(writer, which, mesh_number, transforms)
     }
     /// Uploads instance data for all the meshes of a given mesh group.
     pub fn upload_meshes_group(&mut self, gpu: &crate::WGPU, which: MeshGroup) {
         self.data.upload_meshes_group(gpu, which)
     }
+    /// Outlines the given range of instances of a mesh in a mesh
+    /// group with the given color, or removes the mesh's outline if
+    /// `color` is `None`.  Outlines are drawn by `render_outline_mask`
+    /// and [`crate::outline::OutlinePass`].
+    pub fn set_outline(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_number: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        self.data.set_outline(gpu, which, mesh_number, color, range)
+    }
+    /// Highlights or unhighlights the given range of instances of a
+    /// mesh in a mesh group (see [`crate::highlight`]).  Highlighted
+    /// instances are drawn by `render_highlight_mask`.
+    pub fn set_highlighted(
+        &mut self,
+        which: MeshGroup,
+        mesh_number: usize,
+        range: impl std::ops::RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        self.data
+            .set_highlighted(which, mesh_number, range, highlighted)
+    }
+    /// Returns true if the given instance of a mesh is highlighted.
+    pub fn is_highlighted(&self, which: MeshGroup, mesh_number: usize, instance: usize) -> bool {
+        self.data.groups[which.0].as_ref().unwrap().meshes[mesh_number]
+            .highlighted
+            .contains(instance)
+    }
+    /// Unhighlights every instance of every mesh in a mesh group.
+    pub fn clear_highlighted(&mut self, which: MeshGroup) {
+        for mesh in self.data.groups[which.0]
+            .as_mut()
+            .unwrap()
+            .meshes
+            .iter_mut()
+        {
+            mesh.highlighted.clear();
+        }
+    }
+    /// Returns true if any mesh instance is highlighted.
+    pub fn has_highlights(&self) -> bool {
+        self.data.has_highlights()
+    }
+    /// Sets the color highlighted instances are drawn into the highlight mask with.
+    pub fn set_highlight_color(&mut self, gpu: &crate::WGPU, color: [f32; 4]) {
+        self.data.highlight_color.update(gpu, color, 0..0);
+    }
+    /// Returns the color highlighted instances are drawn into the highlight mask with.
+    pub fn highlight_color(&self) -> [f32; 4] {
+        self.data.highlight_color.color()
+    }
+    /// Draws the given range of instances of a mesh in a single
+    /// color instead of the group's material colors (or its
+    /// [`Material`]), or goes back to the group's colors if `color` is
+    /// `None`.  This is handy for highlighting or fading out
+    /// individual instances; as with material colors, alpha only
+    /// matters if the renderer's color target blends.  Each distinct
+    /// range of colored instances costs an extra draw call.  Like
+    /// outlines, an unbounded range includes instances added later by
+    /// [`Self::resize_group_mesh`].
+    pub fn set_instance_color(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_number: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        self.data
+            .set_instance_color(gpu, which, mesh_number, color, range)
+    }
+    /// Returns the color set for the given instance of a mesh by
+    /// [`Self::set_instance_color`], if any.
+    pub fn instance_color(
+        &self,
+        which: MeshGroup,
+        mesh_number: usize,
+        instance: usize,
+    ) -> Option<[f32; 4]> {
+        self.data.instance_color(which, mesh_number, instance)
+    }
+    /// Draws a mesh group with a material (see [`crate::material`]),
+    /// or with flat colors if `material` is `None`; the material's
+    /// `base` color is the submesh's flat color times its vertex
+    /// color.  Panics if the given mesh group is not populated.
+    pub fn set_material(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        material: Option<&Material>,
+    ) {
+        self.data.set_material(gpu, which, material)
+    }
+    /// Returns true if any mesh has an outline.
+    pub fn has_outlines(&self) -> bool {
+        self.data.has_outlines()
+    }
+    /// Returns how many draw calls and instances [`Self::render`]
+    /// issues when drawing every mesh group.
+    pub fn draw_counts(&self) -> (usize, usize) {
+        self.data.draw_counts()
+    }
+    /// Draws the outlined instances of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target an outline mask texture (see [`crate::outline`]).
+    pub fn render_outline_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_outline_mask(rpass, which)
+    }
+    /// Draws the highlighted instances of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target a highlight mask texture (see [`crate::highlight`]).
+    pub fn render_highlight_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_highlight_mask(rpass, which)
+    }
+    /// Updates the pick table used by `render_pick`; call this
+    /// before drawing IDs whenever mesh groups or their instance
+    /// counts have changed.
+    pub fn prepare_pick(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_pick(gpu)
+    }
+    /// Draws the object IDs of the given range of mesh groups into
+    /// the given [`wgpu::RenderPass`], which should target an object
+    /// ID texture (see [`crate::pick`]).  Call `prepare_pick` first.
+    pub fn render_pick<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_pick(rpass, which)
+    }
+    /// Uploads last frame's camera and allocates copies of last
+    /// frame's instance data for any new mesh groups; call this
+    /// before `render_velocity` each frame.
+    pub fn prepare_velocity(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_velocity(gpu)
+    }
+    /// Draws the screen-space motion of the given range of mesh
+    /// groups into the given [`wgpu::RenderPass`], which should
+    /// target a velocity texture (see [`crate::velocity`]).
+    pub fn render_velocity<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_velocity(rpass, which)
+    }
+    /// Records copies of this frame's instance data to compare the
+    /// next frame against; call this after the velocity pass.
+    pub fn finish_velocity(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.data.finish_velocity(encoder)
+    }
+    /// Frees last frame's instance data once velocities are no longer needed.
+    pub fn clear_velocity(&mut self) {
+        self.data.clear_velocity()
+    }
+    /// Creates the pipeline which draws into a G-buffer, if it
+    /// doesn't exist yet; call this before `render_gbuffer`.
+    pub fn prepare_gbuffer(&mut self, gpu: &crate::WGPU) {
+        self.data.prepare_gbuffer(gpu)
+    }
+    /// Draws the surfaces of the selected mesh groups into the given
+    /// [`wgpu::RenderPass`], which should target a G-buffer (see
+    /// [`crate::deferred`]).  Materials and instance colors aren't
+    /// drawn into the G-buffer.
+    pub fn render_gbuffer<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+    ) where
+        's: 'pass,
+    {
+        self.data.render_gbuffer(rpass, which)
+    }
     /// Renders the given range of mesh groups into the given [`wgpu::RenderPass`].
     pub fn render<'s, 'pass>(
         &'s self,
@@ -481,126 +2606,302 @@ impl FlatRenderer {
     ) where
         's: 'pass,
     {
-        self.data.render(rpass, which)
+        let which = GroupSelection::Range(crate::range(which, self.data.groups.len()));
+        self.data.render(rpass, &which, None)
+    }
+    /// Renders the given range of mesh groups into the given
+    /// [`wgpu::RenderPass`] as seen through a camera view (see
+    /// [`Self::add_camera_view`]) instead of the renderer's camera.
+    /// Panics if the view doesn't exist.
+    pub fn render_with_view<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+        view: usize,
+    ) where
+        's: 'pass,
+    {
+        let which = GroupSelection::Range(crate::range(which, self.data.groups.len()));
+        self.data.render(rpass, &which, Some(view))
+    }
+    /// Renders the selected mesh groups into the given
+    /// [`wgpu::RenderPass`], as seen through a camera view (see
+    /// [`Self::add_camera_view`]) if `view` is given.  Panics if the
+    /// view doesn't exist.
+    pub fn render_selected<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        view: Option<usize>,
+    ) where
+        's: 'pass,
+    {
+        self.data.render(rpass, which, view)
     }
 }
 
-impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
+impl<Vtx: MeshVertex> MeshRendererInner<Vtx> {
     #[allow(clippy::too_many_arguments)]
     fn new(
         gpu: &crate::WGPU,
-        shader: wgpu::ShaderSource,
-        vs_entry: &str,
-        fs_entry: &str,
+        shader_source: &'static str,
+        (vs_entry, vs_uniform_entry): (&'static str, &'static str),
+        (vs_velocity_entry, vs_velocity_uniform_entry): (&'static str, &'static str),
+        (fs_entry, fs_outline_entry, fs_pick_entry, fs_material_entry, fs_gbuffer_entry): (
+            &'static str,
+            &'static str,
+            &'static str,
+            &'static str,
+            &'static str,
+        ),
+        pick_kind: u32,
         bind_group_layout: wgpu::BindGroupLayout,
         vertex_layout: wgpu::VertexBufferLayout,
         color_target: wgpu::ColorTargetState,
-        depth_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
     ) -> Self {
         let shader = gpu
             .device()
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: None,
-                source: shader,
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader_source)),
             });
+        // the camera is followed by lighting parameters (see `FlatLighting`)
         let camera_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: std::mem::size_of::<[f32; 16]>() as u64,
+            size: std::mem::size_of::<[f32; 32]>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
+        let [lights_entry, maps_entry, sampler_entry, sun_entry, sun_map_entry] =
+            LightBindings::layout_entries();
         let camera_bind_group_layout =
             gpu.device()
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: None,
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        // This matches the binding in the shader
-                        binding: 0,
-                        // Available in vertex shader
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        // It's a uniform buffer
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            // This matches the binding in the shader
+                            binding: 0,
+                            // The fragment shader lights textured meshes with it
+                            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            // It's a uniform buffer
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            // No count, not a buffer array binding
+                            count: None,
                         },
-                        // No count, not a buffer array binding
-                        count: None,
-                    }],
+                        // Point lights, the sun, and their shadow maps
+                        lights_entry,
+                        maps_entry,
+                        sampler_entry,
+                        sun_entry,
+                        sun_map_entry,
+                    ],
+                });
+        let light_bindings = LightBindings::new(gpu);
+        let camera_bind_group =
+            light_bindings.bind_group(gpu, &camera_bind_group_layout, &camera_buffer, true);
+        let instance_bind_group_layout = (gpu.instance_storage() == InstanceStorage::UniformChunks)
+            .then(|| {
+                gpu.device()
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: None,
+                        entries: &[wgpu::BindGroupLayoutEntry {
+                            // This matches the binding in the shader
+                            binding: 0,
+                            // Available in vertex shader
+                            visibility: wgpu::ShaderStages::VERTEX,
+                            // It's a uniform buffer bound one chunk at a time
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: true,
+                                min_binding_size: wgpu::BufferSize::new(
+                                    (UNIFORM_CHUNK * std::mem::size_of::<Transform3D>()) as u64,
+                                ),
+                            },
+                            // No count, not a buffer array binding
+                            count: None,
+                        }],
+                    })
+            });
+        let (vs_entry, vs_velocity_entry) = if instance_bind_group_layout.is_some() {
+            (vs_uniform_entry, vs_velocity_uniform_entry)
+        } else {
+            (vs_entry, vs_velocity_entry)
+        };
+        let instance_vertex_buffers = usize::from(instance_bind_group_layout.is_none());
+        let half_instances = instance_bind_group_layout.is_none()
+            && gpu.instance_precision() == InstancePrecision::Half;
+        let pipeline_layout = match &instance_bind_group_layout {
+            Some(instance_layout) => {
+                gpu.device()
+                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: None,
+                        bind_group_layouts: &[
+                            &camera_bind_group_layout,
+                            &bind_group_layout,
+                            instance_layout,
+                        ],
+                        push_constant_ranges: &[],
+                    })
+            }
+            None => gpu
+                .device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&camera_bind_group_layout, &bind_group_layout],
+                    push_constant_ranges: &[],
+                }),
+        };
+        let vertex_stride = vertex_layout.array_stride;
+        let vertex_attributes = vertex_layout.attributes.to_vec();
+        let outline_bind_group_layout = crate::outline::color_bind_group_layout(gpu);
+        let outline_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&camera_bind_group_layout, &outline_bind_group_layout]
+                        .into_iter()
+                        .chain(instance_bind_group_layout.as_ref())
+                        .collect::<Vec<_>>(),
+                    push_constant_ranges: &[],
+                });
+        let outline_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &outline_pipeline_layout,
+            (vs_entry, fs_outline_entry),
+            vertex_layout.clone(),
+            (instance_vertex_buffers, half_instances),
+            &[Some(crate::outline::mask_color_target())],
+            (None, 1),
+        );
+        let pick_bind_group_layout = crate::pick::table_bind_group_layout(gpu);
+        let pick_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&camera_bind_group_layout, &pick_bind_group_layout]
+                        .into_iter()
+                        .chain(instance_bind_group_layout.as_ref())
+                        .collect::<Vec<_>>(),
+                    push_constant_ranges: &[],
                 });
-        let camera_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        let pick_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pick_pipeline_layout,
+            (vs_entry, fs_pick_entry),
+            vertex_layout.clone(),
+            (instance_vertex_buffers, half_instances),
+            &[Some(crate::pick::id_color_target())],
+            (depth_format, 1),
+        );
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            (vs_entry, fs_entry),
+            vertex_layout.clone(),
+            (instance_vertex_buffers, half_instances),
+            &[Some(color_target.clone())],
+            (depth_format, 1),
+        );
+        // The previous camera is laid out just like the current one,
+        // and last frame's instances like this frame's
+        let prev_camera_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            size: std::mem::size_of::<[f32; 16]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
-        let pipeline_layout =
+        let prev_camera_bind_group =
+            light_bindings.bind_group(gpu, &camera_bind_group_layout, &prev_camera_buffer, false);
+        let velocity_pipeline_layout =
             gpu.device()
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: None,
-                    bind_group_layouts: &[&camera_bind_group_layout, &bind_group_layout],
+                    bind_group_layouts: &[&camera_bind_group_layout, &camera_bind_group_layout]
+                        .into_iter()
+                        .chain(instance_bind_group_layout.as_ref())
+                        .chain(instance_bind_group_layout.as_ref())
+                        .collect::<Vec<_>>(),
                     push_constant_ranges: &[],
                 });
-        let pipeline = gpu
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: vs_entry,
-                    buffers: &[
-                        vertex_layout,
-                        wgpu::VertexBufferLayout {
-                            array_stride: std::mem::size_of::<Transform3D>() as u64,
-                            attributes: &[
-                                // trans_scale
-                                wgpu::VertexAttribute {
-                                    format: wgpu::VertexFormat::Float32x4,
-                                    offset: 0,
-                                    shader_location: 2,
-                                },
-                                // rot
-                                wgpu::VertexAttribute {
-                                    format: wgpu::VertexFormat::Float32x4,
-                                    offset: std::mem::size_of::<f32>() as u64 * 4,
-                                    shader_location: 3,
-                                },
-                            ],
-                            step_mode: wgpu::VertexStepMode::Instance,
-                        },
-                    ],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: fs_entry,
-                    targets: &[Some(color_target)],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    ..Default::default()
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: depth_format,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+        let velocity_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &velocity_pipeline_layout,
+            (vs_velocity_entry, "fs_velocity"),
+            vertex_layout,
+            (instance_vertex_buffers * 2, half_instances),
+            &[Some(crate::velocity::velocity_color_target())],
+            (depth_format, 1),
+        );
+        let (empty_bind_group_layout, empty_bind_group) = crate::material::empty_bind_group(gpu);
         let mut ret = Self {
             groups: vec![],
             free_groups: vec![],
             bind_group_layout,
+            camera_bind_group_layout,
             camera_bind_group,
             camera_buffer,
+            shader,
+            shader_source,
+            pipeline_layout,
+            vs_entry,
+            fs_entry,
+            fs_material_entry,
+            empty_bind_group_layout,
+            empty_bind_group,
+            vertex_stride,
+            vertex_attributes,
+            color_target,
+            depth_format,
+            sample_count: 1,
             pipeline,
+            instance_bind_group_layout,
+            half_instances,
+            outline_pipeline,
+            outline_pipeline_layout,
+            highlight_color: OutlineSet::new(
+                gpu,
+                &outline_bind_group_layout,
+                crate::highlight::Highlight::default().color,
+                0..0,
+            ),
+            outline_bind_group_layout,
+            fs_outline_entry,
+            instance_color_pipeline: None,
+            fs_pick_entry,
+            pick_kind,
+            pick_pipeline_layout,
+            pick_pipeline,
+            pick_bind_group_layout,
+            pick_table: PickTable::new(gpu),
+            pick_first_entries: vec![],
+            vs_velocity_entry,
+            velocity_pipeline_layout,
+            velocity_pipeline,
+            prev_camera: None,
+            prev_camera_buffer,
+            prev_camera_bind_group,
+            growth: GrowthPolicy::default(),
+            keep_geometry: false,
+            lighting: [[0.0; 4]; 4],
+            camera_views: vec![],
+            point_lights: vec![],
+            light_bindings,
+            shadow_cameras: vec![],
+            shadow_pipeline: None,
+            sun_cameras: vec![],
+            fs_gbuffer_entry,
+            gbuffer_pipeline: None,
+            pbr_pipeline: None,
             _vertex_data: PhantomData,
             camera: Camera3D {
                 translation: [0.0; 3],
@@ -615,32 +2916,675 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
         ret
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline(
+        gpu: &crate::WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        (vs_entry, fs_entry): (&str, &str),
+        vertex_layout: wgpu::VertexBufferLayout,
+        (instance_vertex_buffers, half_instances): (usize, bool),
+        // Depth-only pipelines have no fragment stage
+        color_targets: &[Option<wgpu::ColorTargetState>],
+        // Only pipelines drawn in the main pass are multisampled
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> wgpu::RenderPipeline {
+        // Half floats are widened to the same vec4s in the vertex stage
+        let (format, stride) = if half_instances {
+            (
+                wgpu::VertexFormat::Float16x4,
+                std::mem::size_of::<HalfTransform3D>() as u64,
+            )
+        } else {
+            (
+                wgpu::VertexFormat::Float32x4,
+                std::mem::size_of::<Transform3D>() as u64,
+            )
+        };
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: vs_entry,
+                    buffers: &[
+                        vertex_layout,
+                        wgpu::VertexBufferLayout {
+                            array_stride: stride,
+                            attributes: &[
+                                // trans_scale
+                                wgpu::VertexAttribute {
+                                    format,
+                                    offset: 0,
+                                    shader_location: 2,
+                                },
+                                // rot
+                                wgpu::VertexAttribute {
+                                    format,
+                                    offset: stride / 2,
+                                    shader_location: 3,
+                                },
+                            ],
+                            step_mode: wgpu::VertexStepMode::Instance,
+                        },
+                        // last frame's instances, for velocities
+                        wgpu::VertexBufferLayout {
+                            array_stride: stride,
+                            attributes: &[
+                                wgpu::VertexAttribute {
+                                    format,
+                                    offset: 0,
+                                    shader_location: 4,
+                                },
+                                wgpu::VertexAttribute {
+                                    format,
+                                    offset: stride / 2,
+                                    shader_location: 5,
+                                },
+                            ],
+                            step_mode: wgpu::VertexStepMode::Instance,
+                        },
+                    ][..1 + instance_vertex_buffers],
+                },
+                fragment: (!color_targets.is_empty()).then_some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: fs_entry,
+                    targets: color_targets,
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    fn set_sample_count(&mut self, gpu: &crate::WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    fn set_color_target(&mut self, gpu: &crate::WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    fn set_depth_format(&mut self, gpu: &crate::WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            (self.vs_entry, self.fs_entry),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &[Some(self.color_target.clone())],
+            (depth_format, self.sample_count),
+        );
+        self.pick_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pick_pipeline_layout,
+            (self.vs_entry, self.fs_pick_entry),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &[Some(crate::pick::id_color_target())],
+            (depth_format, 1),
+        );
+        self.velocity_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.velocity_pipeline_layout,
+            (self.vs_velocity_entry, "fs_velocity"),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(2),
+            &[Some(crate::velocity::velocity_color_target())],
+            (depth_format, 1),
+        );
+        if self.instance_color_pipeline.is_some() {
+            self.instance_color_pipeline = Some(self.create_instance_color_pipeline(gpu));
+        }
+        if let Some(pbr) = self.pbr_pipeline.as_ref() {
+            let pipeline = self.create_pbr_pipeline(gpu, &pbr.shader, &pbr.layout);
+            self.pbr_pipeline.as_mut().unwrap().pipeline = pipeline;
+        }
+        for which in 0..self.groups.len() {
+            let Some(material) = self.groups[which]
+                .as_ref()
+                .and_then(|group| group.material.as_ref())
+                .map(|m| m.material.clone())
+            else {
+                continue;
+            };
+            self.set_material(gpu, MeshGroup(which), Some(&material));
+        }
+    }
+    /// Instance colors are drawn with the outline mask's shaders and
+    /// bindings, but into the color target with depth testing.
+    fn create_instance_color_pipeline(&self, gpu: &crate::WGPU) -> wgpu::RenderPipeline {
+        Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.outline_pipeline_layout,
+            (self.vs_entry, self.fs_outline_entry),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &[Some(self.color_target.clone())],
+            (self.depth_format, self.sample_count),
+        )
+    }
+    fn set_material(&mut self, gpu: &crate::WGPU, which: MeshGroup, material: Option<&Material>) {
+        let material = material.map(|material| {
+            let shader = material.create_shader(
+                gpu,
+                self.shader_source,
+                include_str!("meshes_material.wgsl"),
+            );
+            let pipeline_layout = material.create_pipeline_layout(
+                gpu,
+                [
+                    &self.camera_bind_group_layout,
+                    &self.bind_group_layout,
+                    self.instance_bind_group_layout
+                        .as_ref()
+                        .unwrap_or(&self.empty_bind_group_layout),
+                ],
+            );
+            let pipeline = Self::create_pipeline(
+                gpu,
+                &shader,
+                &pipeline_layout,
+                (
+                    self.vs_entry,
+                    material.fragment_entry(self.fs_material_entry),
+                ),
+                wgpu::VertexBufferLayout {
+                    array_stride: self.vertex_stride,
+                    attributes: &self.vertex_attributes,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                },
+                self.instance_vertex_buffers(1),
+                &[Some(material.color_target(&self.color_target))],
+                (self.depth_format, self.sample_count),
+            );
+            GroupMaterial::new(material, pipeline)
+        });
+        self.groups[which.0].as_mut().unwrap().material = material;
+    }
+    fn create_pbr_pipeline(
+        &self,
+        gpu: &crate::WGPU,
+        shader: &wgpu::ShaderModule,
+        layout: &wgpu::PipelineLayout,
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline(
+            gpu,
+            shader,
+            layout,
+            (self.vs_entry, "fs_pbr"),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &[Some(self.color_target.clone())],
+            (self.depth_format, self.sample_count),
+        )
+    }
+    // Draws the group with the PBR pipeline and the given bind group,
+    // or as usual if it's `None`
+    fn set_pbr(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        bind_group: Option<wgpu::BindGroup>,
+        pbr_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        if bind_group.is_some() && self.pbr_pipeline.is_none() {
+            let shader = gpu
+                .device()
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("meshes:pbr_shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!(
+                            "{}\n{}",
+                            self.shader_source,
+                            include_str!("meshes_pbr.wgsl")
+                        )
+                        .into(),
+                    ),
+                });
+            let layout = gpu
+                .device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("meshes:pbr_pipeline_layout"),
+                    bind_group_layouts: &[
+                        &self.camera_bind_group_layout,
+                        &self.bind_group_layout,
+                        self.instance_bind_group_layout
+                            .as_ref()
+                            .unwrap_or(&self.empty_bind_group_layout),
+                        pbr_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+            let pipeline = self.create_pbr_pipeline(gpu, &shader, &layout);
+            self.pbr_pipeline = Some(PbrPipeline {
+                shader,
+                layout,
+                pipeline,
+            });
+        }
+        self.groups[which.0].as_mut().unwrap().pbr = bind_group;
+    }
+
     fn set_camera(&mut self, gpu: &crate::WGPU, camera: Camera3D) {
         self.camera = camera;
-        let tr = ultraviolet::Vec3::from(camera.translation);
-        let view = (ultraviolet::Mat4::from_translation(tr)
-            * ultraviolet::Rotor3::from_quaternion_array(camera.rotation)
-                .into_matrix()
-                .into_homogeneous())
-        .inversed();
-        let proj = ultraviolet::projection::rh_yup::perspective_wgpu_dx(
-            camera.fov,
-            camera.aspect,
-            camera.near,
-            camera.far,
-        );
-        let mat = proj * view;
+        let mat = camera.projection_view();
         gpu.queue()
             .write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&mat));
     }
+    fn set_lighting(&mut self, gpu: &crate::WGPU, lighting: [[f32; 4]; 4]) {
+        self.lighting = lighting;
+        for buffer in std::iter::once(&self.camera_buffer)
+            .chain(self.camera_views.iter().flatten().map(|view| &view.buffer))
+            .chain(self.group_cameras().map(|view| &view.buffer))
+        {
+            gpu.queue().write_buffer(
+                buffer,
+                std::mem::size_of::<[f32; 16]>() as u64,
+                bytemuck::cast_slice(&lighting),
+            );
+        }
+    }
+    fn add_camera_view(&mut self, gpu: &crate::WGPU, camera: Camera3D) -> usize {
+        let view = self.create_camera_view(gpu, camera);
+        match self.camera_views.iter().position(Option::is_none) {
+            Some(idx) => {
+                self.camera_views[idx] = Some(view);
+                idx
+            }
+            None => {
+                self.camera_views.push(Some(view));
+                self.camera_views.len() - 1
+            }
+        }
+    }
+    fn create_camera_view(&self, gpu: &crate::WGPU, camera: Camera3D) -> CameraView {
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<[f32; 32]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group =
+            self.light_bindings
+                .bind_group(gpu, &self.camera_bind_group_layout, &buffer, true);
+        gpu.queue()
+            .write_buffer(&buffer, 0, bytemuck::bytes_of(&camera.projection_view()));
+        gpu.queue().write_buffer(
+            &buffer,
+            std::mem::size_of::<[f32; 16]>() as u64,
+            bytemuck::cast_slice(&self.lighting),
+        );
+        CameraView {
+            camera,
+            buffer,
+            bind_group,
+        }
+    }
+    fn set_group_camera(&mut self, gpu: &crate::WGPU, which: MeshGroup, camera: Option<Camera3D>) {
+        let view = match (camera, self.groups[which.0].as_mut().unwrap().camera.take()) {
+            (Some(camera), Some(mut view)) => {
+                view.camera = camera;
+                gpu.queue().write_buffer(
+                    &view.buffer,
+                    0,
+                    bytemuck::bytes_of(&camera.projection_view()),
+                );
+                Some(view)
+            }
+            (Some(camera), None) => Some(self.create_camera_view(gpu, camera)),
+            (None, _) => None,
+        };
+        self.groups[which.0].as_mut().unwrap().camera = view;
+    }
+    fn group_cameras(&self) -> impl Iterator<Item = &CameraView> {
+        self.groups
+            .iter()
+            .flatten()
+            .filter_map(|group| group.camera.as_ref())
+    }
+    // The camera bind group a group draws with, unless it's drawn
+    // through a camera view
+    fn group_camera_bind_group<'s>(&'s self, group: &'s MeshGroupData) -> &'s wgpu::BindGroup {
+        group
+            .camera
+            .as_ref()
+            .map_or(&self.camera_bind_group, |view| &view.bind_group)
+    }
+    fn set_camera_view(&mut self, gpu: &crate::WGPU, view: usize, camera: Camera3D) {
+        self.set_camera_view_matrix(gpu, view, camera, camera.projection_view())
+    }
+    fn set_camera_view_matrix(
+        &mut self,
+        gpu: &crate::WGPU,
+        view: usize,
+        camera: Camera3D,
+        projection_view: ultraviolet::Mat4,
+    ) {
+        let view = self.camera_views[view].as_mut().unwrap();
+        view.camera = camera;
+        gpu.queue()
+            .write_buffer(&view.buffer, 0, bytemuck::bytes_of(&projection_view));
+    }
+    fn set_point_lights(&mut self, gpu: &crate::WGPU, lights: &[PointLight]) {
+        assert!(
+            lights.len() <= MAX_POINT_LIGHTS,
+            "At most {MAX_POINT_LIGHTS} point lights are supported, not {}",
+            lights.len()
+        );
+        self.point_lights = lights.to_vec();
+        self.write_point_lights(gpu);
+        if !lights.iter().any(|light| light.shadows) {
+            return;
+        }
+        if self.shadow_cameras.is_empty() {
+            self.create_shadow_cameras(gpu);
+        }
+        for (light, cameras) in lights.iter().zip(self.shadow_cameras.chunks(6)) {
+            for (face, (buffer, _)) in light.face_projection_views().iter().zip(cameras) {
+                gpu.queue()
+                    .write_buffer(buffer, 0, bytemuck::bytes_of(face));
+            }
+        }
+    }
+    fn write_point_lights(&self, gpu: &crate::WGPU) {
+        let size = self.light_bindings.maps.as_ref().map(|(_, size)| *size);
+        gpu.queue().write_buffer(
+            &self.light_bindings.buffer,
+            0,
+            bytemuck::bytes_of(&PointLightsUniform::new(&self.point_lights, size)),
+        );
+    }
+    fn set_point_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&PointShadows>) {
+        self.light_bindings.maps = shadows.map(|shadows| {
+            let view = shadows.texture().create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            (view, shadows.size())
+        });
+        self.write_point_lights(gpu);
+        self.rebind_lights(gpu);
+    }
+    fn set_sun_shadows(&mut self, gpu: &crate::WGPU, shadows: Option<&SunShadows>) {
+        self.light_bindings.sun_map = shadows.map(|shadows| {
+            shadows
+                .texture()
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        match shadows {
+            Some(shadows) => {
+                if self.sun_cameras.is_empty() {
+                    self.sun_cameras = (0..MAX_CASCADES).map(|_| self.shadow_camera(gpu)).collect();
+                    self.create_shadow_pipeline(gpu);
+                }
+                self.update_sun_shadows(gpu, shadows);
+            }
+            None => gpu.queue().write_buffer(
+                &self.light_bindings.sun_buffer,
+                0,
+                bytemuck::bytes_of(&SunUniform::off()),
+            ),
+        }
+        self.rebind_lights(gpu);
+    }
+    fn update_sun_shadows(&self, gpu: &crate::WGPU, shadows: &SunShadows) {
+        gpu.queue().write_buffer(
+            &self.light_bindings.sun_buffer,
+            0,
+            bytemuck::bytes_of(&shadows.uniform()),
+        );
+        for (cascade, (buffer, _)) in shadows.cascades().iter().zip(&self.sun_cameras) {
+            gpu.queue()
+                .write_buffer(buffer, 0, bytemuck::bytes_of(cascade));
+        }
+    }
+    // Rebinds the shadow maps everywhere the lights are used
+    fn rebind_lights(&mut self, gpu: &crate::WGPU) {
+        self.camera_bind_group = self.light_bindings.bind_group(
+            gpu,
+            &self.camera_bind_group_layout,
+            &self.camera_buffer,
+            true,
+        );
+        let group_cameras = self
+            .groups
+            .iter_mut()
+            .flatten()
+            .filter_map(|group| group.camera.as_mut());
+        for view in self.camera_views.iter_mut().flatten().chain(group_cameras) {
+            view.bind_group = self.light_bindings.bind_group(
+                gpu,
+                &self.camera_bind_group_layout,
+                &view.buffer,
+                true,
+            );
+        }
+    }
+    fn create_shadow_cameras(&mut self, gpu: &crate::WGPU) {
+        self.shadow_cameras = (0..MAX_POINT_LIGHTS * 6)
+            .map(|_| self.shadow_camera(gpu))
+            .collect();
+        self.create_shadow_pipeline(gpu);
+    }
+    fn shadow_camera(&self, gpu: &crate::WGPU) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("meshes:shadow_camera"),
+            size: std::mem::size_of::<[f32; 32]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // The shadow maps can't be bound while they're drawn into
+        let bind_group =
+            self.light_bindings
+                .bind_group(gpu, &self.camera_bind_group_layout, &buffer, false);
+        (buffer, bind_group)
+    }
+    // The depth-only pipeline shared by point light and sun shadows
+    fn create_shadow_pipeline(&mut self, gpu: &crate::WGPU) {
+        if self.shadow_pipeline.is_some() {
+            return;
+        }
+        let layout = gpu
+            .device()
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("meshes:shadow_pipeline_layout"),
+                bind_group_layouts: &[
+                    &self.camera_bind_group_layout,
+                    &self.empty_bind_group_layout,
+                ]
+                .into_iter()
+                .chain(self.instance_bind_group_layout.as_ref())
+                .collect::<Vec<_>>(),
+                push_constant_ranges: &[],
+            });
+        self.shadow_pipeline = Some(Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &layout,
+            (self.vs_entry, self.fs_entry),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &[],
+            (Some(crate::shadow::SHADOW_FORMAT), 1),
+        ));
+    }
+    fn create_instance_buffer(
+        &self,
+        gpu: &crate::WGPU,
+        len: usize,
+    ) -> (wgpu::Buffer, Option<wgpu::BindGroup>) {
+        let Some(layout) = &self.instance_bind_group_layout else {
+            let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: Self::instance_buffer_size(self.instance_layout(), len),
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            return (buffer, None);
+        };
+        let chunk_bytes = (UNIFORM_CHUNK * std::mem::size_of::<Transform3D>()) as u64;
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: Self::instance_buffer_size(self.instance_layout(), len),
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(chunk_bytes),
+                }),
+            }],
+        });
+        (buffer, Some(bind_group))
+    }
+    fn instance_layout(&self) -> InstanceLayout {
+        InstanceLayout {
+            chunked: self.instance_bind_group_layout.is_some(),
+            half: self.half_instances,
+        }
+    }
+    // How many instance vertex buffers to give a pipeline which
+    // reads `per_draw` of them when not drawing from uniform chunks,
+    // and whether they're in half precision
+    fn instance_vertex_buffers(&self, per_draw: usize) -> (usize, bool) {
+        let count = if self.instance_bind_group_layout.is_some() {
+            0
+        } else {
+            per_draw
+        };
+        (count, self.half_instances)
+    }
+    fn instance_buffer_size(layout: InstanceLayout, len: usize) -> u64 {
+        // Round up to whole chunks so the last chunk can be bound in full
+        let len = if layout.chunked {
+            len.div_ceil(UNIFORM_CHUNK).max(1) * UNIFORM_CHUNK
+        } else {
+            len
+        };
+        (len * layout.instance_size()) as u64
+    }
+    // Checks that an instance buffer for `len` instances fits in the device's limits
+    fn check_instances(
+        gpu: &crate::WGPU,
+        layout: InstanceLayout,
+        len: usize,
+    ) -> Result<(), LimitError> {
+        gpu.check_buffer(
+            "mesh instance buffer",
+            Self::instance_buffer_size(layout, len),
+            false,
+        )
+    }
+    // Writes instances into an instance buffer starting at instance
+    // `first`, packing them into half floats if needed
+    fn write_instances(
+        writer: &mut BufferWriter,
+        layout: InstanceLayout,
+        buffer: &wgpu::Buffer,
+        first: usize,
+        instances: &[Transform3D],
+    ) {
+        let offset = (first * layout.instance_size()) as u64;
+        if layout.half {
+            let packed: Vec<HalfTransform3D> =
+                instances.iter().map(HalfTransform3D::from).collect();
+            writer.write(buffer, offset, bytemuck::cast_slice(&packed));
+        } else {
+            writer.write(buffer, offset, bytemuck::cast_slice(instances));
+        }
+    }
+    // Shrinks a capacity picked by the growth policy back down to
+    // `needed` if the extra room would exceed the device's limits
+    fn fit_capacity(&self, gpu: &crate::WGPU, capacity: usize, needed: usize) -> usize {
+        match Self::check_instances(gpu, self.instance_layout(), capacity) {
+            Ok(()) => capacity,
+            Err(_) => needed,
+        }
+    }
     fn add_mesh_group(
         &mut self,
         gpu: &crate::WGPU,
         bind_group: wgpu::BindGroup,
-        vertices: Vec<Vtx>,
-        indices: Vec<u32>,
+        mut vertices: Vec<Vtx>,
+        mut indices: Vec<u32>,
         mesh_info: Vec<MeshEntry>,
-    ) -> MeshGroup {
+    ) -> Result<MeshGroup, LimitError> {
+        trace_span!(
+            "meshes::add_group",
+            vertices = vertices.len(),
+            meshes = mesh_info.len()
+        );
+        apply_submesh_layers(&mut vertices, &mut indices, &mesh_info);
+        gpu.check_buffer(
+            "mesh vertex buffer",
+            std::mem::size_of_val(vertices.as_slice()) as u64,
+            false,
+        )?;
+        // Small meshes get 16-bit indices, halving index memory and bandwidth
+        let short_indices: Option<Vec<u16>> =
+            indices.iter().map(|&idx| u16::try_from(idx).ok()).collect();
+        let (index_bytes, index_format): (&[u8], _) = match &short_indices {
+            Some(short) => (bytemuck::cast_slice(short), wgpu::IndexFormat::Uint16),
+            None => (bytemuck::cast_slice(&indices), wgpu::IndexFormat::Uint32),
+        };
+        gpu.check_buffer("mesh index buffer", index_bytes.len() as u64, false)?;
+        let instance_count: u32 = mesh_info.iter().map(|me| me.instance_count).sum();
+        Self::check_instances(gpu, self.instance_layout(), instance_count as usize)?;
         let group_idx = if let Some(idx) = self.free_groups.pop() {
             idx
         } else {
@@ -658,17 +3602,12 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
             .device()
             .create_buffer_init(&wutil::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&indices),
+                contents: index_bytes,
                 usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
             });
-        let instance_count: u32 = mesh_info.iter().map(|me| me.instance_count).sum();
         let instance_data = vec![Transform3D::zeroed(); instance_count as usize];
-        let instance_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: instance_count as u64 * std::mem::size_of::<Transform3D>() as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let (instance_buffer, instance_bind_group) =
+            self.create_instance_buffer(gpu, instance_count as usize);
         let mut next_instance = 0_u32;
         let meshes: Vec<_> = mesh_info
             .into_iter()
@@ -682,22 +3621,50 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
                         "Meshes with non-zero vertex base are not supported in GL or web backends"
                     );
                 }
+                // bound the vertices the submeshes actually use
+                let points = me.submeshes.iter().flat_map(|sm| {
+                    indices[sm.indices.start as usize..sm.indices.end as usize]
+                        .iter()
+                        .filter_map(|&idx| {
+                            vertices.get((idx as i64 + sm.vertex_base as i64) as usize)
+                        })
+                        .map(|v| v.position())
+                });
+                let bounds = if points.clone().next().is_some() {
+                    MeshBounds::from_points(points)
+                } else {
+                    MeshBounds::default()
+                };
                 MeshData {
                     instances: instance..next_instance,
                     submeshes: me.submeshes,
+                    bounds,
+                    outline: None,
+                    instance_colors: vec![],
+                    highlighted: HighlightSet::default(),
                 }
             })
             .collect();
         let group = MeshGroupData {
             instance_data,
             instance_buffer,
+            instance_bind_group,
+            prev_instances: None,
             vertex_buffer,
             index_buffer,
+            index_format,
             bind_group,
             meshes,
+            material: None,
+            pbr: None,
+            reserved: 0,
+            geometry: self
+                .keep_geometry
+                .then(|| (vertices.iter().map(|v| v.position()).collect(), indices)),
+            camera: None,
         };
         self.groups[group_idx] = Some(group);
-        MeshGroup(group_idx)
+        Ok(MeshGroup(group_idx))
     }
     fn resize_group_mesh(
         &mut self,
@@ -705,7 +3672,11 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
         which: MeshGroup,
         mesh_idx: usize,
         len: usize,
-    ) -> usize {
+    ) -> Result<usize, LimitError> {
+        trace_span!("meshes::resize_group_mesh", which = which.0, mesh_idx, len);
+        let instance_buffer_len = self.mesh_group_capacity(which);
+        let growth = self.growth;
+        let layout = self.instance_layout();
         let group = self.groups[which.0].as_mut().unwrap();
         let mesh_count = group.meshes.len();
         let mesh = &group.meshes[mesh_idx];
@@ -718,7 +3689,7 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
         };
         let old_group_len = group.instance_data.len();
         if old_len == len {
-            return old_len;
+            return Ok(old_len);
         } else if len < old_len
             || match next_mesh {
                 Some(nm) => new_end < group.meshes[nm].instances.start,
@@ -728,12 +3699,22 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
         {
             // just increase (or decrease if we're shrinking) the instance data range
             group.meshes[mesh_idx].instances.end = new_end;
+            let used = group.meshes.iter().map(|mesh| mesh.instances.len()).sum();
+            if let Some(capacity) =
+                growth.resized_capacity(used, instance_buffer_len, group.reserved)
+            {
+                let capacity = self.fit_capacity(gpu, capacity, used);
+                self.realloc_group_instances(gpu, which, capacity);
+            }
         } else
         /* len > old_len, space not free; extend instance data and move stuff over */
         {
             // we may have to realloc.
             // make room in instance data
             let new_group_len = group.instance_data.len() + (len - old_len);
+            if new_group_len > instance_buffer_len {
+                Self::check_instances(gpu, layout, new_group_len)?;
+            }
             group
                 .instance_data
                 .resize(new_group_len, Transform3D::zeroed());
@@ -755,24 +3736,60 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
             }
             // extend end of mesh.instances
             group.meshes[mesh_idx].instances.end = new_end;
+            // instances moved, so last frame's data no longer lines up
+            group.prev_instances = None;
             // grow instance buffer if needed
-            let new_len_bytes = std::mem::size_of::<Transform3D>() * new_group_len;
-            if new_len_bytes > group.instance_buffer.size() as usize {
-                group.instance_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: new_len_bytes as u64,
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    mapped_at_creation: false,
-                });
-                // write immediately since otherwise it will contain garbage
-                gpu.queue().write_buffer(
-                    &group.instance_buffer,
-                    0,
-                    bytemuck::cast_slice(&group.instance_data),
-                );
+            if new_group_len > instance_buffer_len {
+                let capacity = growth.grown_capacity(new_group_len).max(group.reserved);
+                let capacity = self.fit_capacity(gpu, capacity, new_group_len);
+                self.realloc_group_instances(gpu, which, capacity);
             }
         }
-        old_len
+        Ok(old_len)
+    }
+    fn reserve_mesh_group(&mut self, gpu: &crate::WGPU, which: MeshGroup, capacity: usize) {
+        if let Err(err) = Self::check_instances(gpu, self.instance_layout(), capacity) {
+            panic!("{err}");
+        }
+        self.groups[which.0].as_mut().unwrap().reserved = capacity;
+        if capacity > self.mesh_group_capacity(which) {
+            self.realloc_group_instances(gpu, which, capacity);
+        }
+    }
+    fn mesh_group_capacity(&self, which: MeshGroup) -> usize {
+        self.groups[which.0]
+            .as_ref()
+            .unwrap()
+            .instance_buffer
+            .size() as usize
+            / self.instance_layout().instance_size()
+    }
+    // Packs a group's instances together in new buffers with room for `capacity` instances
+    fn realloc_group_instances(&mut self, gpu: &crate::WGPU, which: MeshGroup, capacity: usize) {
+        let (instance_buffer, instance_bind_group) = self.create_instance_buffer(gpu, capacity);
+        let layout = self.instance_layout();
+        let group = self.groups[which.0].as_mut().unwrap();
+        let mut instance_data = Vec::with_capacity(capacity);
+        for mesh in group.meshes.iter_mut() {
+            let start = instance_data.len() as u32;
+            instance_data.extend_from_slice(
+                &group.instance_data[mesh.instances.start as usize..mesh.instances.end as usize],
+            );
+            mesh.instances = start..instance_data.len() as u32;
+        }
+        group.instance_data = instance_data;
+        group.instance_buffer = instance_buffer;
+        group.instance_bind_group = instance_bind_group;
+        // last frame's buffer no longer matches in size or layout
+        group.prev_instances = None;
+        // write immediately since otherwise it will contain garbage
+        Self::write_instances(
+            &mut BufferWriter::Queue(gpu.queue()),
+            layout,
+            &group.instance_buffer,
+            0,
+            &group.instance_data,
+        );
     }
 
     fn mesh_group_count(&self) -> usize {
@@ -781,10 +3798,77 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
     fn mesh_count(&self, which: MeshGroup) -> usize {
         self.groups[which.0].as_ref().unwrap().meshes.len()
     }
+    fn has_mesh_group(&self, which: MeshGroup) -> bool {
+        self.groups
+            .get(which.0)
+            .is_some_and(|group| group.is_some())
+    }
     fn mesh_instance_count(&self, which: MeshGroup, mesh_number: usize) -> usize {
         let range = &self.groups[which.0].as_ref().unwrap().meshes[mesh_number].instances;
         range.end as usize - range.start as usize
     }
+    /// Finds the closest front-facing triangle of any instance hit by
+    /// the ray, as a group, mesh, instance, and distance.  Groups
+    /// without kept geometry are skipped.
+    fn raycast(&self, ray: &Ray) -> Option<(MeshGroup, usize, usize, f32)> {
+        let origin = ultraviolet::Vec3::from(ray.origin);
+        let direction = ultraviolet::Vec3::from(ray.direction);
+        let mut best: Option<(MeshGroup, usize, usize, f32)> = None;
+        for (group_idx, group) in self.groups.iter().enumerate() {
+            let Some(group) = group else {
+                continue;
+            };
+            let Some((positions, indices)) = &group.geometry else {
+                continue;
+            };
+            for (mesh_idx, mesh) in group.meshes.iter().enumerate() {
+                let instances = &group.instance_data
+                    [mesh.instances.start as usize..mesh.instances.end as usize];
+                for (instance_idx, trf) in instances.iter().enumerate() {
+                    // hidden instances are commonly scaled to zero
+                    if trf.scale == 0.0 {
+                        continue;
+                    }
+                    // the ray in model space has the same distances
+                    let inv = trf.model_matrix().inversed();
+                    let o = inv.transform_point3(origin);
+                    let d = inv.transform_vec3(direction);
+                    let max_t = best.map_or(f32::INFINITY, |(_, _, _, t)| t);
+                    if !ray_hits_box(o, d, &mesh.bounds, max_t) {
+                        continue;
+                    }
+                    // negative scales flip which side faces front
+                    let flip = trf.scale < 0.0;
+                    for submesh in mesh.submeshes.iter() {
+                        let tris = indices
+                            [submesh.indices.start as usize..submesh.indices.end as usize]
+                            .chunks_exact(3);
+                        for tri in tris {
+                            let corner = |i: usize| {
+                                positions
+                                    .get((tri[i] as i64 + submesh.vertex_base as i64) as usize)
+                                    .map(|&p| ultraviolet::Vec3::from(p))
+                            };
+                            let (Some(a), Some(b), Some(c)) = (corner(0), corner(1), corner(2))
+                            else {
+                                continue;
+                            };
+                            let Some(t) = ray_hits_triangle(o, d, [a, b, c], flip) else {
+                                continue;
+                            };
+                            if best.is_none_or(|(_, _, _, best_t)| t < best_t) {
+                                best = Some((MeshGroup(group_idx), mesh_idx, instance_idx, t));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+    fn mesh_bounds(&self, which: MeshGroup, mesh_number: usize) -> MeshBounds {
+        self.groups[which.0].as_ref().unwrap().meshes[mesh_number].bounds
+    }
     fn get_meshes(&self, which: MeshGroup, mesh_number: usize) -> &[Transform3D] {
         let group = &self.groups[which.0].as_ref().unwrap();
         let mesh = &group.meshes[mesh_number];
@@ -806,7 +3890,7 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
     }
     fn upload_meshes(
         &mut self,
-        gpu: &crate::WGPU,
+        writer: &mut BufferWriter,
         which: MeshGroup,
         mesh_number: usize,
         range: impl std::ops::RangeBounds<usize>,
@@ -818,26 +3902,92 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
             mesh.instances.end as usize - mesh.instances.start as usize,
         );
         // offset range by instance_start
-        gpu.queue().write_buffer(
+        let first = mesh.instances.start as usize + range.start;
+        Self::write_instances(
+            writer,
+            self.instance_layout(),
             &group.instance_buffer,
-            ((mesh.instances.start as usize + range.start as usize)
-                * std::mem::size_of::<Transform3D>()) as u64,
-            bytemuck::cast_slice(
-                &group.instance_data[(mesh.instances.start as usize + range.start)
-                    ..(mesh.instances.start as usize + range.end)],
-            ),
+            first,
+            &group.instance_data[first..(mesh.instances.start as usize + range.end)],
+        );
+    }
+    fn write_drawn_instances(
+        &self,
+        writer: &mut BufferWriter,
+        which: MeshGroup,
+        mesh_number: usize,
+        transforms: &[Transform3D],
+    ) {
+        let group = &self.groups[which.0].as_ref().unwrap();
+        let mesh = &group.meshes[mesh_number];
+        assert_eq!(
+            transforms.len(),
+            mesh.instances.end as usize - mesh.instances.start as usize
+        );
+        Self::write_instances(
+            writer,
+            self.instance_layout(),
+            &group.instance_buffer,
+            mesh.instances.start as usize,
+            transforms,
         );
     }
     fn upload_meshes_group(&mut self, gpu: &crate::WGPU, which: MeshGroup) {
         // upload the whole instance buffer
         let group = &self.groups[which.0].as_ref().unwrap();
-        gpu.queue().write_buffer(
+        Self::write_instances(
+            &mut BufferWriter::Queue(gpu.queue()),
+            self.instance_layout(),
             &group.instance_buffer,
             0,
-            bytemuck::cast_slice(&group.instance_data),
+            &group.instance_data,
         );
     }
-    fn render<'s, 'pass>(
+    fn set_outline(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_number: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        // instances past the end of the mesh are ignored when
+        // drawing, so an unbounded range covers the mesh as it grows
+        let range = crate::range(range, usize::MAX);
+        let mesh = &mut self.groups[which.0].as_mut().unwrap().meshes[mesh_number];
+        match (color, mesh.outline.as_mut()) {
+            (None, _) => mesh.outline = None,
+            (Some(color), Some(outline)) => outline.update(gpu, color, range),
+            (Some(color), None) => {
+                mesh.outline = Some(OutlineSet::new(
+                    gpu,
+                    &self.outline_bind_group_layout,
+                    color,
+                    range,
+                ))
+            }
+        }
+    }
+    fn set_highlighted(
+        &mut self,
+        which: MeshGroup,
+        mesh_number: usize,
+        range: impl std::ops::RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        let range = crate::range(range, usize::MAX);
+        self.groups[which.0].as_mut().unwrap().meshes[mesh_number]
+            .highlighted
+            .set(range, highlighted);
+    }
+    fn has_highlights(&self) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .flat_map(|group| group.meshes.iter())
+            .any(|mesh| !mesh.highlighted.is_empty())
+    }
+    fn render_highlight_mask<'s, 'pass>(
         &'s self,
         rpass: &mut wgpu::RenderPass<'pass>,
         which: impl std::ops::RangeBounds<usize>,
@@ -847,34 +3997,776 @@ impl<Vtx: bytemuck::Pod + bytemuck::Zeroable + Copy> MeshRendererInner<Vtx> {
         if self.groups.is_empty() {
             return;
         }
-        rpass.set_pipeline(&self.pipeline);
+        capture::set_pipeline(rpass, &self.outline_pipeline, "meshes:outline_pipeline");
         let which = crate::range(which, self.groups.len());
-        // camera
-        rpass.set_bind_group(0, &self.camera_bind_group, &[]);
+        capture::set_bind_group(
+            rpass,
+            1,
+            &self.highlight_color.bind_group,
+            &[],
+            "meshes:highlight_color",
+        );
         for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
-            rpass.set_bind_group(1, &group.bind_group, &[]);
-            rpass.set_vertex_buffer(0, group.vertex_buffer.slice(..));
-            rpass.set_vertex_buffer(1, group.instance_buffer.slice(..));
-            rpass.set_index_buffer(group.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            if group.meshes.iter().all(|mesh| mesh.highlighted.is_empty()) {
+                continue;
+            }
+            capture::set_bind_group(
+                rpass,
+                0,
+                self.group_camera_bind_group(group),
+                &[],
+                "meshes:camera_bind_group",
+            );
+            Self::bind_group_buffers(rpass, group);
             for mesh in group.meshes.iter() {
-                if mesh.instances.is_empty() {
+                for run in mesh.highlighted.clipped_runs(mesh.instances.len()) {
+                    Self::draw_mesh(
+                        rpass,
+                        group,
+                        mesh,
+                        (mesh.instances.start + run.start as u32)
+                            ..(mesh.instances.start + run.end as u32),
+                        None,
+                        None,
+                    );
+                }
+            }
+        }
+    }
+    fn set_instance_color(
+        &mut self,
+        gpu: &crate::WGPU,
+        which: MeshGroup,
+        mesh_number: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        // as with outlines, an unbounded range covers the mesh as it grows
+        let range = crate::range(range, usize::MAX);
+        if color.is_some() && self.instance_color_pipeline.is_none() {
+            self.instance_color_pipeline = Some(self.create_instance_color_pipeline(gpu));
+        }
+        let mesh = &mut self.groups[which.0].as_mut().unwrap().meshes[mesh_number];
+        // trim existing colors out of the range, splitting any which
+        // straddle it
+        let mut colors = Vec::with_capacity(mesh.instance_colors.len() + 2);
+        for mut set in std::mem::take(&mut mesh.instance_colors) {
+            let old = set.instances.clone();
+            if old.end <= range.start || range.end <= old.start {
+                colors.push(set);
+                continue;
+            }
+            if range.end < old.end {
+                colors.push(OutlineSet::new(
+                    gpu,
+                    &self.outline_bind_group_layout,
+                    set.color(),
+                    range.end..old.end,
+                ));
+            }
+            if old.start < range.start {
+                let color = set.color();
+                set.update(gpu, color, old.start..range.start);
+                colors.push(set);
+            }
+        }
+        if let Some(color) = color.filter(|_| !range.is_empty()) {
+            colors.push(OutlineSet::new(
+                gpu,
+                &self.outline_bind_group_layout,
+                color,
+                range,
+            ));
+        }
+        colors.sort_by_key(|set| set.instances.start);
+        mesh.instance_colors = colors;
+    }
+    fn instance_color(
+        &self,
+        which: MeshGroup,
+        mesh_number: usize,
+        instance: usize,
+    ) -> Option<[f32; 4]> {
+        self.groups[which.0].as_ref().unwrap().meshes[mesh_number]
+            .instance_colors
+            .iter()
+            .find(|set| set.instances.contains(&instance))
+            .map(|set| set.color())
+    }
+    /// The ranges of a mesh's instances which are drawn with their
+    /// group's colors or material rather than an instance color.
+    fn uncolored_instances(mesh: &MeshData) -> Vec<Range<u32>> {
+        let len = mesh.instances.len();
+        let mut ranges = vec![];
+        let mut start = 0;
+        for set in mesh.instance_colors.iter() {
+            let colored = set.clipped_instances(len);
+            if start < colored.start {
+                ranges.push(start..colored.start);
+            }
+            start = start.max(colored.end);
+        }
+        if start < len {
+            ranges.push(start..len);
+        }
+        ranges
+            .into_iter()
+            .map(|r| (mesh.instances.start + r.start as u32)..(mesh.instances.start + r.end as u32))
+            .collect()
+    }
+    fn draw_counts(&self) -> (usize, usize) {
+        self.groups
+            .iter()
+            .flatten()
+            .flat_map(|group| group.meshes.iter())
+            .filter(|mesh| !mesh.instances.is_empty())
+            .fold((0, 0), |(draws, instances), mesh| {
+                let len = mesh.instances.len();
+                let colored = mesh.instance_colors.iter().map(|set| {
+                    let colored = set.clipped_instances(len);
+                    (mesh.instances.start + colored.start as u32)
+                        ..(mesh.instances.start + colored.end as u32)
+                });
+                // pick tables have one entry per draw of each submesh
+                let mesh_draws: usize = Self::uncolored_instances(mesh)
+                    .into_iter()
+                    .chain(colored)
+                    .map(|range| self.pick_entry_count(&range))
+                    .sum();
+                (
+                    draws + mesh_draws * mesh.submeshes.len(),
+                    instances + mesh.instances.len(),
+                )
+            })
+    }
+    fn has_outlines(&self) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .flat_map(|group| group.meshes.iter())
+            .any(|mesh| mesh.outline.is_some())
+    }
+    fn render_outline_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.outline_pipeline, "meshes:outline_pipeline");
+        let which = crate::range(which, self.groups.len());
+        for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
+            if group.meshes.iter().all(|mesh| mesh.outline.is_none()) {
+                continue;
+            }
+            capture::set_bind_group(
+                rpass,
+                0,
+                self.group_camera_bind_group(group),
+                &[],
+                "meshes:camera_bind_group",
+            );
+            Self::bind_group_buffers(rpass, group);
+            for mesh in group.meshes.iter() {
+                let Some(outline) = mesh.outline.as_ref() else {
                     continue;
+                };
+                capture::set_bind_group(rpass, 1, &outline.bind_group, &[], "meshes:outline_color");
+                let outlined = outline.clipped_instances(mesh.instances.len());
+                Self::draw_mesh(
+                    rpass,
+                    group,
+                    mesh,
+                    (mesh.instances.start + outlined.start as u32)
+                        ..(mesh.instances.start + outlined.end as u32),
+                    None,
+                    None,
+                );
+            }
+        }
+    }
+    fn render<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        view: Option<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.pipeline, "meshes:pipeline");
+        let mut material_bound = false;
+        let selected = || {
+            self.groups
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| which.contains(i))
+                .filter_map(|(_, o)| o.as_ref())
+        };
+        // camera
+        let bind_camera = |rpass: &mut wgpu::RenderPass<'pass>, group: &'s MeshGroupData| match view
+        {
+            Some(view) => capture::set_bind_group(
+                rpass,
+                0,
+                &self.camera_views[view].as_ref().unwrap().bind_group,
+                &[],
+                "meshes:camera_view_bind_group",
+            ),
+            None => capture::set_bind_group(
+                rpass,
+                0,
+                self.group_camera_bind_group(group),
+                &[],
+                "meshes:camera_bind_group",
+            ),
+        };
+        for group in selected() {
+            bind_camera(rpass, group);
+            // Materials take precedence over physically based shading
+            let alternate = match (&group.material, &group.pbr, &self.pbr_pipeline) {
+                (Some(material), _, _) => Some((
+                    &material.pipeline,
+                    material.material.bind_group(),
+                    material.capture_label.as_str(),
+                )),
+                (None, Some(bind_group), Some(pbr)) => {
+                    Some((&pbr.pipeline, bind_group, "meshes:pbr_pipeline"))
                 }
-                for submesh in mesh.submeshes.iter() {
-                    rpass.draw_indexed(
-                        submesh.indices.clone(),
-                        submesh.vertex_base,
-                        mesh.instances.clone(),
+                _ => None,
+            };
+            if let Some((pipeline, bind_group, label)) = alternate {
+                capture::set_pipeline(rpass, pipeline, label);
+                if group.instance_bind_group.is_none() {
+                    capture::set_bind_group(
+                        rpass,
+                        2,
+                        &self.empty_bind_group,
+                        &[],
+                        "meshes:empty_bind_group",
                     );
                 }
+                capture::set_bind_group(
+                    rpass,
+                    crate::material::MATERIAL_GROUP,
+                    bind_group,
+                    &[],
+                    label,
+                );
+                material_bound = true;
+            } else if std::mem::take(&mut material_bound) {
+                capture::set_pipeline(rpass, &self.pipeline, "meshes:pipeline");
+            }
+            capture::set_bind_group(rpass, 1, &group.bind_group, &[], "meshes:group_bind_group");
+            Self::bind_group_buffers(rpass, group);
+            for mesh in group.meshes.iter() {
+                if mesh.instance_colors.is_empty() {
+                    Self::draw_mesh(rpass, group, mesh, mesh.instances.clone(), None, None);
+                    continue;
+                }
+                for instances in Self::uncolored_instances(mesh) {
+                    Self::draw_mesh(rpass, group, mesh, instances, None, None);
+                }
+            }
+        }
+        // then everything with an instance color, one draw per color
+        let Some(instance_color_pipeline) = self.instance_color_pipeline.as_ref() else {
+            return;
+        };
+        let mut pipeline_set = false;
+        for group in selected() {
+            if group
+                .meshes
+                .iter()
+                .all(|mesh| mesh.instance_colors.is_empty())
+            {
+                continue;
+            }
+            if !std::mem::replace(&mut pipeline_set, true) {
+                capture::set_pipeline(
+                    rpass,
+                    instance_color_pipeline,
+                    "meshes:instance_color_pipeline",
+                );
+            }
+            bind_camera(rpass, group);
+            Self::bind_group_buffers(rpass, group);
+            for mesh in group.meshes.iter() {
+                for set in mesh.instance_colors.iter() {
+                    let colored = set.clipped_instances(mesh.instances.len());
+                    capture::set_bind_group(
+                        rpass,
+                        1,
+                        &set.bind_group,
+                        &[],
+                        "meshes:instance_color",
+                    );
+                    Self::draw_mesh(
+                        rpass,
+                        group,
+                        mesh,
+                        (mesh.instances.start + colored.start as u32)
+                            ..(mesh.instances.start + colored.end as u32),
+                        None,
+                        None,
+                    );
+                }
+            }
+        }
+    }
+    /// The number of pick table entries (draws) needed for a range of instances.
+    fn pick_entry_count(&self, instances: &Range<u32>) -> usize {
+        if self.instance_bind_group_layout.is_none() {
+            1
+        } else if instances.is_empty() {
+            0
+        } else {
+            ((instances.end - 1) / UNIFORM_CHUNK as u32 - instances.start / UNIFORM_CHUNK as u32)
+                as usize
+                + 1
+        }
+    }
+    fn prepare_pick(&mut self, gpu: &crate::WGPU) {
+        let mut entries: Vec<[u32; 4]> = vec![];
+        self.pick_first_entries.clear();
+        for (which, group) in self.groups.iter().enumerate() {
+            self.pick_first_entries.push(entries.len());
+            let Some(group) = group else {
+                continue;
+            };
+            for (mesh_number, mesh) in group.meshes.iter().enumerate() {
+                // Instance indices are relative to the start of the
+                // group (or of the chunk, for uniform chunks), but
+                // IDs should be relative to the mesh
+                let first_chunk =
+                    mesh.instances.start - mesh.instances.start % UNIFORM_CHUNK as u32;
+                entries.extend((0..self.pick_entry_count(&mesh.instances)).map(|chunk| {
+                    let base = if self.instance_bind_group_layout.is_some() {
+                        (first_chunk + (chunk * UNIFORM_CHUNK) as u32) as i32
+                            - mesh.instances.start as i32
+                    } else {
+                        -(mesh.instances.start as i32)
+                    };
+                    [
+                        self.pick_kind,
+                        which as u32,
+                        mesh_number as u32,
+                        base as u32,
+                    ]
+                }));
+            }
+        }
+        self.pick_table
+            .write(gpu, &self.pick_bind_group_layout, &entries);
+    }
+    fn render_pick<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.pick_pipeline, "meshes:pick_pipeline");
+        let which = crate::range(which, self.groups.len());
+        for (group, first_entry) in self.groups[which.clone()]
+            .iter()
+            .zip(&self.pick_first_entries[which])
+        {
+            let Some(group) = group else {
+                continue;
+            };
+            capture::set_bind_group(
+                rpass,
+                0,
+                self.group_camera_bind_group(group),
+                &[],
+                "meshes:camera_bind_group",
+            );
+            Self::bind_group_buffers(rpass, group);
+            let mut entry = *first_entry;
+            for mesh in group.meshes.iter() {
+                Self::draw_mesh(
+                    rpass,
+                    group,
+                    mesh,
+                    mesh.instances.clone(),
+                    Some((&self.pick_table, entry)),
+                    None,
+                );
+                entry += self.pick_entry_count(&mesh.instances);
+            }
+        }
+    }
+    fn render_point_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        light: usize,
+        face: usize,
+    ) where
+        's: 'pass,
+    {
+        if let Some((_, camera)) = self.shadow_cameras.get(light * 6 + face) {
+            self.render_shadow(rpass, which, camera);
+        }
+    }
+    fn render_sun_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        cascade: usize,
+    ) where
+        's: 'pass,
+    {
+        if let Some((_, camera)) = self.sun_cameras.get(cascade) {
+            self.render_shadow(rpass, which, camera);
+        }
+    }
+    // Draws the selected groups' depth from a shadow camera
+    fn render_shadow<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        camera: &'s wgpu::BindGroup,
+    ) where
+        's: 'pass,
+    {
+        let Some(pipeline) = self.shadow_pipeline.as_ref() else {
+            return;
+        };
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, pipeline, "meshes:shadow_pipeline");
+        capture::set_bind_group(rpass, 0, camera, &[], "meshes:shadow_camera");
+        capture::set_bind_group(
+            rpass,
+            1,
+            &self.empty_bind_group,
+            &[],
+            "meshes:empty_bind_group",
+        );
+        for group in self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| which.contains(i))
+            .filter_map(|(_, o)| o.as_ref())
+        {
+            Self::bind_group_buffers(rpass, group);
+            for mesh in group.meshes.iter() {
+                Self::draw_mesh(rpass, group, mesh, mesh.instances.clone(), None, None);
+            }
+        }
+    }
+    fn prepare_gbuffer(&mut self, gpu: &crate::WGPU) {
+        if self.gbuffer_pipeline.is_some() {
+            return;
+        }
+        // The G-buffer has its own depth texture, so this doesn't
+        // depend on the depth format
+        self.gbuffer_pipeline = Some(Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            (self.vs_entry, self.fs_gbuffer_entry),
+            wgpu::VertexBufferLayout {
+                array_stride: self.vertex_stride,
+                attributes: &self.vertex_attributes,
+                step_mode: wgpu::VertexStepMode::Vertex,
+            },
+            self.instance_vertex_buffers(1),
+            &crate::deferred::gbuffer_color_targets(),
+            (Some(crate::deferred::GBUFFER_DEPTH_FORMAT), 1),
+        ));
+    }
+    fn render_gbuffer<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+    ) where
+        's: 'pass,
+    {
+        let Some(pipeline) = self.gbuffer_pipeline.as_ref() else {
+            return;
+        };
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, pipeline, "meshes:gbuffer_pipeline");
+        for group in self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| which.contains(i))
+            .filter_map(|(_, o)| o.as_ref())
+        {
+            capture::set_bind_group(
+                rpass,
+                0,
+                self.group_camera_bind_group(group),
+                &[],
+                "meshes:camera_bind_group",
+            );
+            capture::set_bind_group(rpass, 1, &group.bind_group, &[], "meshes:group_bind_group");
+            Self::bind_group_buffers(rpass, group);
+            for mesh in group.meshes.iter() {
+                Self::draw_mesh(rpass, group, mesh, mesh.instances.clone(), None, None);
             }
         }
     }
+    fn prepare_velocity(&mut self, gpu: &crate::WGPU) {
+        // With no previous frame, nothing has moved yet
+        let prev_camera = self.prev_camera.unwrap_or(self.camera);
+        gpu.queue().write_buffer(
+            &self.prev_camera_buffer,
+            0,
+            bytemuck::bytes_of(&prev_camera.projection_view()),
+        );
+        for idx in 0..self.groups.len() {
+            let Some(group) = &self.groups[idx] else {
+                continue;
+            };
+            if group.prev_instances.is_some() {
+                continue;
+            }
+            let layout = self.instance_layout();
+            let len = group.instance_buffer.size() as usize / layout.instance_size();
+            let (buffer, bind_group) = self.create_instance_buffer(gpu, len);
+            let group = self.groups[idx].as_mut().unwrap();
+            Self::write_instances(
+                &mut BufferWriter::Queue(gpu.queue()),
+                layout,
+                &buffer,
+                0,
+                &group.instance_data,
+            );
+            group.prev_instances = Some((buffer, bind_group));
+        }
+    }
+    fn render_velocity<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.velocity_pipeline, "meshes:velocity_pipeline");
+        let which = crate::range(which, self.groups.len());
+        capture::set_bind_group(
+            rpass,
+            0,
+            &self.camera_bind_group,
+            &[],
+            "meshes:camera_bind_group",
+        );
+        capture::set_bind_group(
+            rpass,
+            1,
+            &self.prev_camera_bind_group,
+            &[],
+            "meshes:prev_camera_bind_group",
+        );
+        for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
+            let Some((prev_buffer, prev_bind_group)) = &group.prev_instances else {
+                continue;
+            };
+            Self::bind_group_buffers(rpass, group);
+            if prev_bind_group.is_none() {
+                capture::set_vertex_buffer(rpass, 2, prev_buffer, "meshes:prev_buffer");
+            }
+            for mesh in group.meshes.iter() {
+                Self::draw_mesh(
+                    rpass,
+                    group,
+                    mesh,
+                    mesh.instances.clone(),
+                    None,
+                    prev_bind_group.as_ref(),
+                );
+            }
+        }
+    }
+    fn finish_velocity(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        for group in self.groups.iter().flatten() {
+            if let Some((prev_buffer, _)) = &group.prev_instances {
+                capture::copy("meshes:instance_buffer to prev_buffer");
+                encoder.copy_buffer_to_buffer(
+                    &group.instance_buffer,
+                    0,
+                    prev_buffer,
+                    0,
+                    group.instance_buffer.size(),
+                );
+            }
+        }
+        self.prev_camera = Some(self.camera);
+    }
+    fn clear_velocity(&mut self) {
+        self.prev_camera = None;
+        for group in self.groups.iter_mut().flatten() {
+            group.prev_instances = None;
+        }
+    }
+    fn bind_group_buffers<'s, 'pass>(rpass: &mut wgpu::RenderPass<'pass>, group: &'s MeshGroupData)
+    where
+        's: 'pass,
+    {
+        capture::set_vertex_buffer(rpass, 0, &group.vertex_buffer, "meshes:vertex_buffer");
+        capture::set_index_buffer(
+            rpass,
+            &group.index_buffer,
+            group.index_format,
+            "meshes:index_buffer",
+        );
+        if group.instance_bind_group.is_none() {
+            capture::set_vertex_buffer(rpass, 1, &group.instance_buffer, "meshes:instance_buffer");
+        }
+    }
+    /// Draws the given range of a group's instances of a mesh with
+    /// whatever pipeline is set.  When drawing IDs, `pick` gives the
+    /// pick table and the mesh's first entry.  When drawing
+    /// velocities with uniform chunks, `prev_instances` is last
+    /// frame's instance bind group.
+    fn draw_mesh<'s, 'pass>(
+        rpass: &mut wgpu::RenderPass<'pass>,
+        group: &'s MeshGroupData,
+        mesh: &MeshData,
+        instances: Range<u32>,
+        pick: Option<(&'s PickTable, usize)>,
+        prev_instances: Option<&'s wgpu::BindGroup>,
+    ) where
+        's: 'pass,
+    {
+        if instances.is_empty() {
+            return;
+        }
+        let Some(instance_bind_group) = &group.instance_bind_group else {
+            if let Some((table, entry)) = pick {
+                table.bind(rpass, 1, entry);
+            }
+            for submesh in mesh.submeshes.iter() {
+                capture::draw_indexed(
+                    rpass,
+                    submesh.indices.clone(),
+                    submesh.vertex_base,
+                    instances.clone(),
+                );
+            }
+            return;
+        };
+        // draw the instances falling in each chunk separately,
+        // binding the chunk each time
+        let mut start = instances.start;
+        while start < instances.end {
+            let chunk_start = start - start % UNIFORM_CHUNK as u32;
+            let end = instances.end.min(chunk_start + UNIFORM_CHUNK as u32);
+            let offset = chunk_start * std::mem::size_of::<Transform3D>() as u32;
+            capture::set_bind_group(
+                rpass,
+                2,
+                instance_bind_group,
+                &[offset],
+                "meshes:instance_bind_group",
+            );
+            if let Some(prev_instances) = prev_instances {
+                capture::set_bind_group(
+                    rpass,
+                    3,
+                    prev_instances,
+                    &[offset],
+                    "meshes:prev_instances",
+                );
+            }
+            if let Some((table, first_entry)) = pick {
+                let first_chunk = instances.start / UNIFORM_CHUNK as u32;
+                table.bind(
+                    rpass,
+                    1,
+                    first_entry + (chunk_start / UNIFORM_CHUNK as u32 - first_chunk) as usize,
+                );
+            }
+            for submesh in mesh.submeshes.iter() {
+                capture::draw_indexed(
+                    rpass,
+                    submesh.indices.clone(),
+                    submesh.vertex_base,
+                    (start - chunk_start)..(end - chunk_start),
+                );
+            }
+            start = end;
+        }
+    }
+}
+
+/// Whether a ray hits a box somewhere in `0.0..max_t`.
+fn ray_hits_box(
+    origin: ultraviolet::Vec3,
+    direction: ultraviolet::Vec3,
+    bounds: &MeshBounds,
+    max_t: f32,
+) -> bool {
+    let (mut t0, mut t1) = (0.0_f32, max_t);
+    for axis in 0..3 {
+        let inv_d = 1.0 / direction[axis];
+        let near = (bounds.min[axis] - origin[axis]) * inv_d;
+        let far = (bounds.max[axis] - origin[axis]) * inv_d;
+        // NaNs (a ray in the plane of a face) fall through to the triangle test
+        t0 = t0.max(near.min(far));
+        t1 = t1.min(near.max(far));
+        if t0 > t1 {
+            return false;
+        }
+    }
+    true
+}
+
+/// The distance along a ray to where it hits the front of a
+/// counterclockwise triangle (or the back, if `flip`), if it does.
+fn ray_hits_triangle(
+    origin: ultraviolet::Vec3,
+    direction: ultraviolet::Vec3,
+    [a, b, c]: [ultraviolet::Vec3; 3],
+    flip: bool,
+) -> Option<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let det = ab.dot(p);
+    // front faces have positive determinants here; skip back faces
+    // and rays parallel to the triangle
+    if (if flip { -det } else { det }) <= f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(ab);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) * inv_det;
+    (t > 0.0).then_some(t)
 }
 
 /// An opaque identifier for a mesh group.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
-pub struct MeshGroup(usize);
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct MeshGroup(pub(crate) usize);
 impl MeshGroup {
     pub fn index(&self) -> usize {
         self.0
@@ -0,0 +1,12 @@
+// Mesh groups with a material are drawn with these entry points,
+// which are compiled along with the material's `material` function
+@fragment
+fn fs_material(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textured_color(in);
+    return material(color, in.tex_coords, in.clip_position);
+}
+
+@fragment
+fn fs_flat_material(in:FlatVertexOutput) -> @location(0) vec4<f32> {
+    return material(mat_diffuse[in.mat_index] * flat_color(in), vec2<f32>(0.0), in.clip_position);
+}
@@ -0,0 +1,101 @@
+// Physically based shading for textured mesh groups (see
+// `crate::pbr`), compiled after static_meshes.wgsl
+struct Pbr {
+  base_color: vec4<f32>,
+  // The emissive color, and how strongly the environment lights the group
+  emissive_environment: vec4<f32>,
+  // Metallic, roughness, and the environment's last mip level, which
+  // is negative without an environment
+  metallic_roughness: vec4<f32>,
+}
+@group(3) @binding(0)
+var<uniform> pbr: Pbr;
+@group(3) @binding(1)
+var pbr_sampler: sampler;
+@group(3) @binding(2)
+var pbr_environment: texture_cube<f32>;
+
+const PI: f32 = 3.14159265;
+
+// Shadow maps and the metallic-roughness map are sampled before
+// anything branches on whether the fragment is lit.  The lightmap
+// scales the directional and indirect light, as in textured_color.
+@fragment
+fn fs_pbr(in:VertexOutput) -> @location(0) vec4<f32> {
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index) * pbr.base_color;
+    let lightmap = textureSample(t_lightmap, s_lightmap, in.lightmap_uv).rgb;
+    let normal = textured_normal(in);
+    var entry = 0u;
+    if in.tex_index < 256u {
+        entry = texture_layers.metallic_roughness[in.tex_index / 4u][in.tex_index % 4u];
+    }
+    let mapped = select(vec3(1.0), sample_map(in, entry), entry != 0u);
+    let metallic = clamp(pbr.metallic_roughness.x * mapped.b, 0.0, 1.0);
+    // Perfectly smooth surfaces would have vanishingly small highlights
+    let roughness = clamp(pbr.metallic_roughness.y * mapped.g, 0.04, 1.0);
+    let has_normal = dot(normal, normal) > 0.0;
+    let lit = camera.light_dir.w != 0.0 && has_normal;
+    let shadow = sun_shadow(in.world_position, select(sun.direction_count.xyz, normal, lit));
+    let v = normalize(in.to_eye);
+    let diffuse_color = albedo.rgb * (1.0 - metallic);
+    let f0 = mix(vec3(0.04), albedo.rgb, metallic);
+    var color = vec3(0.0);
+    for (var i = 0u; i < point_lights.count_size.x; i++) {
+        let reach = point_reach(i, in.world_position, normal);
+        color += reach.light * pbr_brdf(normal, v, reach.dir, diffuse_color, f0, roughness);
+    }
+    let emissive = pbr.emissive_environment.rgb;
+    if !has_normal {
+        return vec4(albedo.rgb * lightmap * mix(1.0, shadow, sun.params.w) + emissive, albedo.a);
+    }
+    if lit {
+        let direct = camera.light_color.rgb * shadow;
+        color += lightmap * direct * pbr_brdf(normal, v, camera.light_dir.xyz, diffuse_color, f0, roughness);
+    }
+    let specular_color = env_brdf(f0, roughness, max(dot(normal, v), 0.0001));
+    let last_mip = pbr.metallic_roughness.z;
+    if last_mip >= 0.0 {
+        let reflected = textureSampleLevel(pbr_environment, pbr_sampler, reflect(-v, normal), roughness * last_mip).rgb;
+        let irradiance = textureSampleLevel(pbr_environment, pbr_sampler, normal, last_mip).rgb;
+        let indirect = irradiance * diffuse_color + reflected * specular_color;
+        color += lightmap * indirect * pbr.emissive_environment.w;
+    } else if lit {
+        let ambient = mix(camera.ground.rgb, camera.sky.rgb, normal.y * 0.5 + 0.5);
+        color += lightmap * ambient * (diffuse_color + specular_color);
+    }
+    return vec4(color + emissive, albedo.a);
+}
+
+// The light reflected towards to_eye from a light arriving from
+// to_light (both normalized), per unit of the light's color.  It's
+// multiplied by pi so lights are as bright as under Lambert shading.
+fn pbr_brdf(normal:vec3<f32>, to_eye:vec3<f32>, to_light:vec3<f32>, diffuse_color:vec3<f32>, f0:vec3<f32>, roughness:f32) -> vec3<f32> {
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    if n_dot_l <= 0.0 {
+        return vec3(0.0);
+    }
+    let n_dot_v = max(dot(normal, to_eye), 0.0001);
+    let half_dir = normalize(to_light + to_eye);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+    let v_dot_h = max(dot(to_eye, half_dir), 0.0);
+    // GGX distribution
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d_denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    let distribution = a2 / (PI * d_denom * d_denom);
+    // Smith-Schlick shadowing and masking, with k for direct lights
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let geometry = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+    let specular = distribution * geometry * fresnel / (4.0 * n_dot_l * n_dot_v);
+    return ((1.0 - fresnel) * diffuse_color + specular * PI) * n_dot_l;
+}
+
+// The fraction of indirect light reflected specularly, integrated
+// over the hemisphere, using Karis's analytic fit for mobile
+fn env_brdf(f0:vec3<f32>, roughness:f32, n_dot_v:f32) -> vec3<f32> {
+    let r = roughness * vec4(-1.0, -0.0275, -0.572, 0.022) + vec4(1.0, 0.0425, 1.04, -0.04);
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let scale_bias = vec2(-1.04, 1.04) * a004 + r.zw;
+    return f0 * scale_bias.x + scale_bias.y;
+}
@@ -0,0 +1,207 @@
+//! Mipmap generation, so that textures seen from far away (e.g. on
+//! distant meshes or zoomed-out sprites) are filtered instead of
+//! shimmering.
+//!
+//! [`MipGenerator`] fills in every level of a texture's mip chain
+//! below the first, one layer at a time, by drawing each level as an
+//! average of the one above it.  Since it draws into the texture,
+//! the texture needs [`wgpu::TextureUsages::RENDER_ATTACHMENT`] as
+//! well as [`wgpu::TextureUsages::TEXTURE_BINDING`] and a format
+//! that can be drawn into and filtered (which rules out compressed
+//! and integer formats).  sRGB textures are averaged in linear
+//! space.  [`crate::Renderer::create_texture_with_mips`] creates a
+//! texture with a full mip chain and generates it in one step.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use wgpu::util::DeviceExt;
+
+use crate::{capture, WGPU};
+
+/// How many mip levels a full mip chain for a `width` by `height`
+/// texture has, down to and including 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Generates textures' mip levels on the GPU (see the [module
+/// documentation](self)).
+pub struct MipGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    // Created for each format the first time it's needed
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipGenerator {
+    /// Creates a new `MipGenerator`.
+    pub fn new(gpu: &WGPU) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("mipmap:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("mipmap.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("mipmap:bgl"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("mipmap:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap:sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            pipelines: HashMap::new(),
+        }
+    }
+    fn pipeline(&mut self, gpu: &WGPU, format: wgpu::TextureFormat) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| {
+            gpu.device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("mipmap:pipeline"),
+                    layout: Some(&self.pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &self.shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &self.shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(format.into())],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })
+    }
+    /// Fills in mip levels `1..` of every layer of `texture` from
+    /// level 0.  Panics if the texture wasn't created with both
+    /// [`wgpu::TextureUsages::RENDER_ATTACHMENT`] and
+    /// [`wgpu::TextureUsages::TEXTURE_BINDING`].
+    pub fn generate(&mut self, gpu: &WGPU, texture: &wgpu::Texture) {
+        let levels = texture.mip_level_count();
+        if levels < 2 {
+            return;
+        }
+        let needed = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        assert!(
+            texture.usage().contains(needed),
+            "Can't generate mips for a texture without {needed:?} usage"
+        );
+        let format = texture.format();
+        self.pipeline(gpu, format);
+        let pipeline = &self.pipelines[&format];
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("mipmap:generate"),
+            });
+        for layer in 0..texture.depth_or_array_layers() {
+            let layer_buffer = gpu
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("mipmap:layer"),
+                    contents: bytemuck::cast_slice(&[layer, 0, 0, 0]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+            for level in 1..levels {
+                let source = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2Array),
+                    base_mip_level: level - 1,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+                let target = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("mipmap:bg"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: layer_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+                capture::begin_pass("mipmap:level");
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("mipmap:level"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                });
+                capture::set_pipeline(&mut rpass, pipeline, "mipmap:pipeline");
+                capture::set_bind_group(&mut rpass, 0, &bind_group, &[], "mipmap:bind_group");
+                capture::draw(&mut rpass, 0..3, 0..1);
+            }
+        }
+        gpu.queue().submit(Some(encoder.finish()));
+    }
+}
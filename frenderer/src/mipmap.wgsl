@@ -0,0 +1,31 @@
+// Mip generation (see mipmap.rs).  Each pass draws one level of one
+// layer by sampling the level above it halfway between texels, which
+// averages each 2x2 block.
+
+@group(0) @binding(0)
+var t_source: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_linear: sampler;
+// x = the layer being drawn
+@group(0) @binding(2)
+var<uniform> u_layer: vec4<u32>;
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.uv = uv;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSampleLevel(t_source, s_linear, in.uv, u_layer.x, 0.0);
+}
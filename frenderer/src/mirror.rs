@@ -0,0 +1,227 @@
+//! Planar mirror reflections of the 3D scene.
+//!
+//! A [`Mirror`] is a plane in world space plus the mesh and flat
+//! groups that should appear in it.  Each frame, [`crate::Renderer`]
+//! draws those groups into a reflection texture owned by a
+//! [`MirrorPass`], through a camera view reflected about the plane
+//! whose near plane is replaced by the mirror plane itself (an
+//! oblique near plane), so that nothing behind the mirror leaks into
+//! the reflection.  Turn it on with [`crate::Renderer::mirror_set`]
+//! and use [`crate::Renderer::mirror_texture`] as the texture of a
+//! mirror or floor mesh group; leave that group out of the mirror's
+//! selections, since it can't sample the texture while it's being
+//! drawn into.
+//!
+//! The reflection is drawn from the main camera's point of view, so a
+//! mirror surface shows it correctly when it samples the texture at
+//! its own screen position, mirrored horizontally (the reflected
+//! image is flipped left to right to keep triangles' winding order):
+//!
+//! ```wgsl
+//! let uv = vec2(1.0 - frag_pos.x / screen_size.x, frag_pos.y / screen_size.y);
+//! ```
+//!
+//! Sampling with the mesh's own texture coordinates instead gives a
+//! cheaper approximation which holds up for distant reflections.
+
+use crate::{capture, meshes::Camera3D, msaa::MsaaTargets, GroupSelection, WGPU};
+use ultraviolet::{Mat4, Vec3, Vec4};
+
+/// A reflecting plane and what it reflects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mirror {
+    /// Any point on the mirror plane, in world space
+    pub point: [f32; 3],
+    /// The mirror plane's normal, pointing out of its reflecting side
+    pub normal: [f32; 3],
+    /// Which mesh groups appear in the reflection
+    pub meshes: GroupSelection,
+    /// Which flat mesh groups appear in the reflection
+    pub flats: GroupSelection,
+    /// The color of the reflection where nothing is drawn
+    pub clear_color: wgpu::Color,
+}
+
+impl Mirror {
+    /// A mirror plane through `point` facing along `normal`, which
+    /// reflects every mesh and flat mesh group over a black background.
+    pub fn new(point: [f32; 3], normal: [f32; 3]) -> Self {
+        Self {
+            point,
+            normal,
+            meshes: GroupSelection::All,
+            flats: GroupSelection::All,
+            clear_color: wgpu::Color::BLACK,
+        }
+    }
+    /// The projection and view matrix (in column-major order) which
+    /// draws the reflection of the scene seen by `camera`, clipped to
+    /// the reflecting side of the plane.  If the camera is behind the
+    /// mirror, everything is clipped.
+    pub fn reflected_projection_view(&self, camera: &Camera3D) -> [[f32; 4]; 4] {
+        let normal = Vec3::from(self.normal).normalized();
+        let dist = normal.dot(Vec3::from(self.point));
+        // Reflects points about the plane `normal . p = dist`
+        let reflect = {
+            let mut m = Mat4::identity();
+            for col in 0..3 {
+                let axis = m.cols[col].xyz() - normal * (2.0 * normal[col]);
+                m.cols[col] = axis.into_homogeneous_vector();
+            }
+            m.cols[3] = (normal * (2.0 * dist)).into_homogeneous_point();
+            m
+        };
+        let view = camera.view();
+        // The plane in view space, facing away from the camera so
+        // that only the reflected geometry beyond it is kept
+        let plane = view.inversed().transposed() * Vec4::new(-normal.x, -normal.y, -normal.z, dist);
+        let mut proj = camera.projection();
+        // Move the near plane onto the mirror plane while keeping the
+        // far plane's corners in place (for depth from 0 to 1)
+        let corner = proj.inversed() * Vec4::new(plane.x.signum(), plane.y.signum(), 1.0, 1.0);
+        let near = plane * (1.0 / plane.dot(corner));
+        for col in 0..4 {
+            proj.cols[col].z = near[col];
+        }
+        // Reflection reverses winding, so flip the image horizontally
+        // to draw front faces with back face culling
+        let flip = Mat4::from_nonuniform_scale(Vec3::new(-1.0, 1.0, 1.0));
+        (flip * proj * view * reflect).into()
+    }
+}
+
+/// The reflection texture of a [`Mirror`] and the pass that draws into it.
+pub struct MirrorPass {
+    mirror: Mirror,
+    format: wgpu::TextureFormat,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    // Drawn into and resolved into the reflection texture when multisampling
+    msaa: Option<MsaaTargets>,
+}
+
+impl MirrorPass {
+    /// Creates a [`MirrorPass`] with a `width` by `height` reflection
+    /// texture of the given format.
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        mirror: Mirror,
+    ) -> Self {
+        let (texture, view) = Self::create_texture(gpu, width, height, format);
+        Self {
+            mirror,
+            format,
+            texture,
+            view,
+            msaa: None,
+        }
+    }
+    fn create_texture(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("mirror:reflection"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                // Mesh groups need array textures, which GL can't
+                // tell apart from plain textures with one layer
+                depth_or_array_layers: if gpu.is_gl() { 2 } else { 1 },
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("mirror:reflection_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        (texture, view)
+    }
+    /// Re-creates the reflection texture with a new size.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        (self.texture, self.view) = Self::create_texture(gpu, width, height, self.format);
+        self.set_sample_count(gpu, self.sample_count());
+    }
+    /// Changes the number of samples per pixel the reflection is
+    /// drawn with; the pass's depth texture and the pipelines drawn
+    /// into it must have the same sample count.  The default is 1
+    /// (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.msaa = (sample_count > 1).then(|| {
+            MsaaTargets::new(
+                gpu,
+                self.texture.width(),
+                self.texture.height(),
+                self.format,
+                None,
+                sample_count,
+            )
+        });
+    }
+    /// Returns the number of samples per pixel the reflection is drawn with.
+    pub fn sample_count(&self) -> u32 {
+        self.msaa.as_ref().map_or(1, MsaaTargets::sample_count)
+    }
+    /// Returns the mirror.
+    pub fn mirror(&self) -> &Mirror {
+        &self.mirror
+    }
+    /// Changes the mirror.
+    pub fn set_mirror(&mut self, mirror: Mirror) {
+        self.mirror = mirror;
+    }
+    /// Returns the texture the reflection is drawn into.  Its first
+    /// layer holds the reflection.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Begins a render pass which clears the reflection texture and
+    /// the given depth texture (which should be the same size as the
+    /// reflection texture); draw the reflected view of the scene into
+    /// it, e.g. with the mesh renderers' `render_selected` methods
+    /// and a camera view set to [`Mirror::reflected_projection_view`].
+    pub fn begin_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+        depth_view: Option<&'s wgpu::TextureView>,
+    ) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("mirror");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("mirror"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self
+                    .msaa
+                    .as_ref()
+                    .map_or(&self.view, MsaaTargets::color_view),
+                resolve_target: self.msaa.as_ref().map(|_| &self.view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.mirror.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: depth_view.map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            ..Default::default()
+        })
+    }
+}
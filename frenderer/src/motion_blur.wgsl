@@ -0,0 +1,23 @@
+// u_params[0]: x = strength, y = sample count, z = maximum blur length in pixels
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let size = vec2<f32>(textureDimensions(t_color));
+  let velocity_size = vec2<f32>(textureDimensions(t_velocity));
+  var velocity = textureLoad(t_velocity, vec2<i32>(in.tex_coords * velocity_size), 0).xy * u_params[0].x;
+  let length_px = length(velocity * size);
+  if length_px < 0.5 {
+    return textureSampleLevel(t_color, s_color, in.tex_coords, 0.0);
+  }
+  if length_px > u_params[0].z {
+    velocity *= u_params[0].z / length_px;
+  }
+  // Average samples spread along the motion, centered on this pixel
+  let count = u32(u_params[0].y);
+  var sum = vec4<f32>(0.0);
+  for (var i = 0u; i < count; i++) {
+    let t = (f32(i) + 0.5) / f32(count) - 0.5;
+    sum += textureSampleLevel(t_color, s_color, in.tex_coords - velocity * t, 0.0);
+  }
+  return sum / f32(count);
+}
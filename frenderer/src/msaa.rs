@@ -0,0 +1,252 @@
+//! Multisampled render targets for antialiasing the main render pass.
+//!
+//! [`MsaaTargets`] holds a multisampled color texture and (optionally)
+//! a multisampled depth texture.  A pass drawing into them resolves
+//! its color into a single sampled texture by setting it as the color
+//! attachment's `resolve_target`; every pipeline drawn in that pass
+//! must be created with the same sample count (see e.g.
+//! [`crate::sprites::SpriteRenderer::set_sample_count`]).  Depth
+//! can't be resolved by a render pass, so
+//! [`MsaaTargets::resolve_depth`] copies the nearest sample of each
+//! pixel into a single sampled depth texture for effects that read
+//! depth afterwards, like fog or outlines.  GL can't sample
+//! multisampled textures, so there depth isn't resolved at all.
+
+use std::borrow::Cow;
+
+use crate::{capture, WGPU};
+
+/// The sample counts to try, from most to fewest samples per pixel.
+const SAMPLE_COUNTS: [u32; 5] = [16, 8, 4, 2, 1];
+
+/// Returns the largest sample count no greater than `requested` which
+/// all of the given formats support as render targets (1 if
+/// multisampling isn't supported at all).  Without the
+/// `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` feature, only the
+/// sample counts WebGPU guarantees (1 and 4) are considered.
+pub fn supported_sample_count(gpu: &WGPU, formats: &[wgpu::TextureFormat], requested: u32) -> u32 {
+    let features = gpu.device().features();
+    let flags = |format: wgpu::TextureFormat| {
+        if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            gpu.adapter().get_texture_format_features(format).flags
+        } else {
+            format.guaranteed_format_features(features).flags
+        }
+    };
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| {
+            formats
+                .iter()
+                .all(|&format| flags(format).sample_count_supported(count))
+        })
+        .unwrap_or(1)
+}
+
+/// Multisampled color and depth targets of one size (see the [module
+/// documentation](self)).
+pub struct MsaaTargets {
+    sample_count: u32,
+    color: (wgpu::Texture, wgpu::TextureView),
+    depth: Option<(wgpu::Texture, wgpu::TextureView)>,
+    // Writes the resolved depth; present along with the depth target,
+    // except on GL
+    depth_resolve: Option<(wgpu::RenderPipeline, wgpu::BindGroup)>,
+}
+
+impl MsaaTargets {
+    /// Creates `width` by `height` targets with `sample_count` samples
+    /// per pixel: a color texture of `color_format` and, unless
+    /// `depth_format` is `None`, a depth texture of `depth_format`.
+    /// The sample count should come from [`supported_sample_count`].
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        sample_count: u32,
+    ) -> Self {
+        let color = Self::create_target(
+            gpu,
+            "msaa:color",
+            (width, height),
+            color_format,
+            sample_count,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let depth = depth_format.map(|format| {
+            Self::create_target(
+                gpu,
+                "msaa:depth",
+                (width, height),
+                format,
+                sample_count,
+                if gpu.is_gl() {
+                    wgpu::TextureUsages::RENDER_ATTACHMENT
+                } else {
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+                },
+            )
+        });
+        let depth_resolve = depth
+            .as_ref()
+            .filter(|_| !gpu.is_gl())
+            .map(|(texture, view)| {
+                Self::create_depth_resolve(gpu, texture.format(), sample_count, view)
+            });
+        Self {
+            sample_count,
+            color,
+            depth,
+            depth_resolve,
+        }
+    }
+    fn create_target(
+        gpu: &WGPU,
+        label: &str,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        usage: wgpu::TextureUsages,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_depth_resolve(
+        gpu: &WGPU,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_view: &wgpu::TextureView,
+    ) -> (wgpu::RenderPipeline, wgpu::BindGroup) {
+        let source = format!(
+            "const SAMPLES: i32 = {sample_count};\n{}",
+            include_str!("msaa.wgsl")
+        );
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("msaa:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("msaa:bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: true,
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("msaa:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let pipeline = gpu
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("msaa:depth_resolve"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                // Only depth is written
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("msaa:bg"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            }],
+        });
+        (pipeline, bind_group)
+    }
+    /// Returns the number of samples per pixel.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    /// Returns the multisampled color texture.
+    pub fn color_texture(&self) -> &wgpu::Texture {
+        &self.color.0
+    }
+    /// Returns a view of the multisampled color texture.
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color.1
+    }
+    /// Returns a view of the multisampled depth texture, if there is one.
+    pub fn depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth.as_ref().map(|(_, view)| view)
+    }
+    /// Writes the nearest sample of each pixel of the multisampled
+    /// depth texture into `depth_view`, a single sampled depth
+    /// texture of the same size and format.  Does nothing if there's
+    /// no multisampled depth texture, or on GL.
+    pub fn resolve_depth(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let Some((pipeline, bind_group)) = self.depth_resolve.as_ref() else {
+            return;
+        };
+        capture::begin_pass("msaa:depth_resolve");
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("msaa:depth_resolve"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        capture::set_pipeline(&mut rpass, pipeline, "msaa:depth_resolve");
+        capture::set_bind_group(&mut rpass, 0, bind_group, &[], "msaa:bind_group");
+        capture::draw(&mut rpass, 0..6, 0..1);
+    }
+}
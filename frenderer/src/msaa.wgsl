@@ -0,0 +1,32 @@
+// Resolves a multisampled depth texture (see msaa.rs) into a single
+// sampled one, keeping the nearest of each pixel's samples.
+// `SAMPLES` is declared ahead of this by MsaaTargets, since GL can't
+// ask the texture.
+
+@group(0) @binding(0)
+// Read as floats, since GL can't load from depth textures
+var t_depth: texture_multisampled_2d<f32>;
+
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    vec2<f32>(-1., -1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(-1., 1.),
+    vec2<f32>(1., -1.),
+    vec2<f32>(1., 1.)
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+  return vec4<f32>(VERTICES[in_vertex_index], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @builtin(frag_depth) f32 {
+  let texel = vec2<i32>(pos.xy);
+  var depth = 1.0;
+  for (var i = 0; i < SAMPLES; i++) {
+    depth = min(depth, textureLoad(t_depth, texel, i).r);
+  }
+  return depth;
+}
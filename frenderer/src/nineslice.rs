@@ -1,3 +1,14 @@
+//! Nine-slice (nine-patch) boxes for UI panels and dialogs, whose
+//! corners keep their size while the edges and center stretch or
+//! tile to fill the rest.
+//!
+//! [`NineSlice::draw`] writes a box's sprites into any slices of
+//! transforms and sheet regions, e.g. a range of a sprite group.  A
+//! [`Panel`] is a nine-slice box with its own position and size;
+//! [`crate::Renderer::nineslice_panel_add`] gives a panel a sprite
+//! group of its own and keeps its sprites in sync as it's moved and
+//! resized.
+
 use crate::sprites::{SheetRegion, Transform};
 
 /// A repeat mode for 9-slice centers and edges.  [`Repeat::Stretch`]
@@ -51,14 +62,12 @@ impl NineSlice {
         Self::new(
             corner_top_left,
             CornerSlice {
-                w: corner_top_left.h,
-                h: corner_top_left.w,
                 region: corner_top_left.region.flip_horizontal(),
+                ..corner_top_left
             },
             CornerSlice {
                 region: corner_top_left.region.flip_vertical(),
-                w: corner_top_left.h,
-                h: corner_top_left.w,
+                ..corner_top_left
             },
             CornerSlice {
                 region: corner_top_left.region.flip_vertical().flip_horizontal(),
@@ -225,7 +234,7 @@ impl NineSlice {
 
         for (edge, ypos) in &[
             (self.bottom, y + self.bottom.h / 2.0),
-            (self.top, y + h - self.bottom.h / 2.0),
+            (self.top, y + h - self.top.h / 2.0),
         ] {
             let x = x + self.top_left.w;
             match edge.repeat {
@@ -305,3 +314,42 @@ impl NineSlice {
         which
     }
 }
+
+/// A nine-slice box at a particular place and size, drawn from
+/// `(x, y)` (its bottom left corner) to `(x + w, y + h)`.
+#[derive(Clone, Debug)]
+pub struct Panel {
+    pub nineslice: NineSlice,
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+    /// Added to the depth of each slice's sheet region
+    pub z_offset: u16,
+}
+
+impl Panel {
+    /// Creates a panel drawn from `(x, y)` to `(x + w, y + h)`.
+    pub fn new(nineslice: NineSlice, x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self {
+            nineslice,
+            x,
+            y,
+            w,
+            h,
+            z_offset: 0,
+        }
+    }
+    /// Returns how many sprites this panel needs at its current size
+    /// (see [`NineSlice::sprite_count`]).
+    pub fn sprite_count(&self) -> usize {
+        self.nineslice.sprite_count(self.w, self.h)
+    }
+    /// Draws this panel's sprites into the given slices, which must
+    /// hold at least [`Panel::sprite_count`] sprites, returning how
+    /// many were used.
+    pub fn draw(&self, trf: &mut [Transform], uvs: &mut [SheetRegion]) -> usize {
+        self.nineslice
+            .draw(trf, uvs, self.x, self.y, self.w, self.h, self.z_offset)
+    }
+}
@@ -0,0 +1,311 @@
+//! Outlines around sprites and meshes, for selection highlights and
+//! stylized looks.
+//!
+//! Outlining happens in two steps.  First, each renderer draws the
+//! instances which should be outlined into a mask texture using their
+//! outline colors (see e.g. [`crate::sprites::SpriteRenderer::set_outline`]).
+//! Then [`OutlinePass`] draws, onto the color target, every pixel
+//! which is outside of the mask but within some distance of it using
+//! the color of the nearest masked pixel.  The mask is drawn without
+//! depth testing, so outlines show through whatever is in front of
+//! the outlined objects.  An [`OutlinePass`] can also tint the masked
+//! pixels themselves (see [`OutlinePass::set_fill`]), which is how
+//! selection highlights are drawn (see [`crate::highlight`]).
+
+use std::{borrow::Cow, ops::Range};
+
+use crate::{capture, WGPU};
+
+/// The texture format of outline masks.
+pub const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// The largest supported outline thickness, in pixels.
+pub const MAX_THICKNESS: f32 = 16.0;
+
+/// The bind group layout renderers use for outline colors in their mask pipelines.
+pub(crate) fn color_bind_group_layout(gpu: &WGPU) -> wgpu::BindGroupLayout {
+    gpu.device()
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("outline:color_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+}
+
+/// The color target state for drawing into an outline mask.
+pub(crate) fn mask_color_target() -> wgpu::ColorTargetState {
+    MASK_FORMAT.into()
+}
+
+/// An outline color together with the range of instances it applies
+/// to, owned by the renderer doing the outlining.  Mesh renderers also
+/// use these for instance colors.
+#[derive(Debug)]
+pub(crate) struct OutlineSet {
+    pub(crate) instances: Range<usize>,
+    color: [f32; 4],
+    color_buffer: wgpu::Buffer,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl OutlineSet {
+    pub(crate) fn new(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        color: [f32; 4],
+        instances: Range<usize>,
+    ) -> Self {
+        let color_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("outline:color"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue()
+            .write_buffer(&color_buffer, 0, bytemuck::cast_slice(&color));
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("outline:color_bg"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: color_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            instances,
+            color,
+            color_buffer,
+            bind_group,
+        }
+    }
+    /// Changes the outline color and instance range, reusing the GPU resources.
+    pub(crate) fn update(&mut self, gpu: &WGPU, color: [f32; 4], instances: Range<usize>) {
+        self.instances = instances;
+        self.color = color;
+        gpu.queue()
+            .write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&color));
+    }
+    /// The outline color.
+    pub(crate) fn color(&self) -> [f32; 4] {
+        self.color
+    }
+    /// The outlined instances, clipped to the first `len` instances.
+    pub(crate) fn clipped_instances(&self, len: usize) -> Range<usize> {
+        self.instances.start.min(len)..self.instances.end.min(len)
+    }
+}
+
+/// Draws outlines around the contents of an outline mask texture.
+pub struct OutlinePass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    params_buf: wgpu::Buffer,
+    thickness: f32,
+    fill: f32,
+    mask_texture: wgpu::Texture,
+    mask_texture_view: wgpu::TextureView,
+}
+
+impl OutlinePass {
+    /// Creates an [`OutlinePass`] with a `width` by `height` mask
+    /// texture, which draws outlines into the given color target.
+    pub fn new(gpu: &WGPU, width: u32, height: u32, color_target: wgpu::ColorTargetState) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("outline:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("outline.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("outline:bgl"),
+                    entries: &[
+                        // Parameters
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        // The mask texture, read with textureLoad so no sampler is needed
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("outline:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let pipeline = gpu
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("outline:pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    // Outline colors' alpha blends them over the scene
+                    targets: &[Some(wgpu::ColorTargetState {
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        ..color_target
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        let thickness = 2.0;
+        let fill = 0.0;
+        let params_buf = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("outline:params"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue().write_buffer(
+            &params_buf,
+            0,
+            bytemuck::cast_slice(&[thickness, fill, 0.0, 0.0]),
+        );
+        let (mask_texture, mask_texture_view) = Self::create_mask(gpu, width, height);
+        let bind_group =
+            Self::create_bind_group(gpu, &bind_group_layout, &params_buf, &mask_texture_view);
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            params_buf,
+            thickness,
+            fill,
+            mask_texture,
+            mask_texture_view,
+        }
+    }
+    fn create_mask(gpu: &WGPU, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("outline:mask"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: MASK_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_bind_group(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        params_buf: &wgpu::Buffer,
+        mask_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("outline:bg"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask_view),
+                },
+            ],
+        })
+    }
+    /// Re-creates the mask texture at a new size (for example,
+    /// because the color target has changed size).
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        let (mask_texture, mask_texture_view) = Self::create_mask(gpu, width, height);
+        self.mask_texture = mask_texture;
+        self.mask_texture_view = mask_texture_view;
+        self.bind_group = Self::create_bind_group(
+            gpu,
+            &self.bind_group_layout,
+            &self.params_buf,
+            &self.mask_texture_view,
+        );
+    }
+    /// Returns the mask texture, into which outlined things should be drawn.
+    pub fn mask_texture(&self) -> &wgpu::Texture {
+        &self.mask_texture
+    }
+    /// Returns a view of the mask texture.
+    pub fn mask_texture_view(&self) -> &wgpu::TextureView {
+        &self.mask_texture_view
+    }
+    /// Returns the outline thickness in pixels.
+    pub fn thickness(&self) -> f32 {
+        self.thickness
+    }
+    /// Sets the outline thickness in pixels, up to [`MAX_THICKNESS`].
+    pub fn set_thickness(&mut self, gpu: &WGPU, thickness: f32) {
+        self.thickness = thickness.clamp(0.0, MAX_THICKNESS);
+        self.write_params(gpu);
+    }
+    /// Returns how strongly masked pixels are tinted with their mask color.
+    pub fn fill(&self) -> f32 {
+        self.fill
+    }
+    /// Sets how strongly masked pixels are tinted with their mask
+    /// color, from 0 (the default, just outlines) to 1.
+    pub fn set_fill(&mut self, gpu: &WGPU, fill: f32) {
+        self.fill = fill.clamp(0.0, 1.0);
+        self.write_params(gpu);
+    }
+    fn write_params(&self, gpu: &WGPU) {
+        gpu.queue().write_buffer(
+            &self.params_buf,
+            0,
+            bytemuck::cast_slice(&[self.thickness, self.fill, 0.0, 0.0]),
+        );
+    }
+    /// Draws outlines around the mask's contents onto the given
+    /// renderpass, which should target the color texture the mask
+    /// corresponds to.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "outline:pipeline");
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "outline:bind_group");
+        capture::draw(rpass, 0..6, 0..1);
+    }
+}
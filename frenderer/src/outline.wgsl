@@ -0,0 +1,52 @@
+var<private> VERTICES:array<vec4<f32>,6> = array<vec4<f32>,6>(
+    vec4<f32>(-1., -1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(1., 1., 0., 1.)
+);
+
+// x is the outline thickness in pixels, y the fill opacity; the rest is padding
+@group(0) @binding(0)
+var<uniform> u_params: vec4<f32>;
+// The outline mask: outlined things drawn in their outline colors
+@group(0) @binding(1)
+var t_mask: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+  return VERTICES[in_vertex_index];
+}
+
+@fragment
+fn fs_main(@builtin(position) pos:vec4<f32>) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(pos.xy);
+    let size = vec2<i32>(textureDimensions(t_mask));
+    // Pixels covered by the mask are inside the shape, not on its outline
+    let inside = textureLoad(t_mask, center, 0);
+    if inside.w > 0.0 {
+        if u_params.y > 0.0 {
+            return vec4<f32>(inside.rgb, inside.w * u_params.y);
+        }
+        discard;
+    }
+    let r = i32(u_params.x);
+    // Take the color of the closest covered pixel within the radius
+    var best = vec4<f32>(0.0);
+    var best_dist = f32(r*r+1);
+    for (var y = -r; y <= r; y++) {
+        for (var x = -r; x <= r; x++) {
+            let dist = f32(x*x+y*y);
+            if dist >= best_dist { continue; }
+            let p = clamp(center+vec2<i32>(x,y), vec2<i32>(0), size-vec2<i32>(1));
+            let m = textureLoad(t_mask, p, 0);
+            if m.w > 0.0 {
+                best = m;
+                best_dist = dist;
+            }
+        }
+    }
+    if best.w == 0.0 { discard; }
+    return best;
+}
@@ -0,0 +1,415 @@
+//! A debug overlay showing frame timings and rendering statistics.
+//!
+//! [`DebugOverlay`] draws a small panel with the frame rate, a graph
+//! of recent frame times, draw call and instance counts, upload
+//! volume, and (where the device supports timestamp queries) GPU
+//! time.  It has its own [`SpriteRenderer`] and a tiny built-in
+//! font, so it works without any assets of the application's own.
+//! [`crate::Renderer`] can manage one for you (see
+//! [`crate::Renderer::debug_overlay_set_visible`]); otherwise, fill
+//! in a [`FrameStats`] each frame, [`DebugOverlay::update`] the
+//! overlay, and draw it with [`DebugOverlay::render`] over the final
+//! image.
+
+use std::sync::mpsc;
+
+use crate::bitfont::BitFont;
+use crate::sprites::{Camera2D, SheetRegion, SpriteRenderer, Transform};
+use crate::textlayout::{LayoutOptions, TextLayout, TextRun};
+use crate::WGPU;
+
+/// Statistics about one frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    /// Seconds since the previous frame started
+    pub frame_time: f32,
+    /// Frames per second, smoothed over recent frames
+    pub fps: f32,
+    /// Draw calls issued by the main pass
+    pub draw_calls: usize,
+    /// Sprites and mesh instances drawn by the main pass
+    pub instances: usize,
+    /// Bytes of instance data uploaded this frame
+    pub upload_bytes: usize,
+    /// Bytes of instance data marked for upload this frame which
+    /// weren't uploaded again because the marked ranges overlapped
+    pub coalesced_bytes: usize,
+    /// Milliseconds the GPU spent on a recent frame, if timestamp queries are supported
+    pub gpu_time: Option<f32>,
+}
+
+/// How many frame times are kept for the graph.
+pub const HISTORY_LEN: usize = 120;
+
+// The built-in font covers ' '..='Z' in 4x6 cells, 16 per row, with
+// each glyph in the top-left 3x5 pixels of its cell.  Each octal
+// digit is one row of a glyph, from top to bottom, with the leftmost
+// pixel in the high bit.
+const GLYPHS: [(char, u16); 46] = [
+    ('%', 0o51245),
+    ('(', 0o12221),
+    (')', 0o42224),
+    ('+', 0o02720),
+    (',', 0o00024),
+    ('-', 0o00700),
+    ('.', 0o00002),
+    ('/', 0o11244),
+    ('0', 0o75557),
+    ('1', 0o26227),
+    ('2', 0o71747),
+    ('3', 0o71717),
+    ('4', 0o55711),
+    ('5', 0o74717),
+    ('6', 0o74757),
+    ('7', 0o71111),
+    ('8', 0o75757),
+    ('9', 0o75717),
+    (':', 0o02020),
+    ('=', 0o07070),
+    ('A', 0o25755),
+    ('B', 0o65656),
+    ('C', 0o34443),
+    ('D', 0o65556),
+    ('E', 0o74647),
+    ('F', 0o74644),
+    ('G', 0o34553),
+    ('H', 0o55755),
+    ('I', 0o72227),
+    ('J', 0o11152),
+    ('K', 0o55655),
+    ('L', 0o44447),
+    ('M', 0o57555),
+    ('N', 0o65555),
+    ('O', 0o25552),
+    ('P', 0o65644),
+    ('Q', 0o25563),
+    ('R', 0o65655),
+    ('S', 0o34716),
+    ('T', 0o72222),
+    ('U', 0o55557),
+    ('V', 0o55552),
+    ('W', 0o55575),
+    ('X', 0o55255),
+    ('Y', 0o55222),
+    ('Z', 0o71247),
+];
+const FONT_W: u32 = 64;
+const FONT_H: u32 = 32;
+// A solid white block below the glyphs, for drawing rectangles
+const BLOCK: SheetRegion = SheetRegion::new(0, 0, 24, 0, 4, 4);
+
+const CHAR_HEIGHT: f32 = 12.0;
+const PADDING: f32 = 6.0;
+const BAR_WIDTH: u16 = 2;
+const GRAPH_HEIGHT: f32 = 40.0;
+// Frame times at the top of the graph, in seconds
+const GRAPH_MAX: f32 = 1.0 / 20.0;
+
+/// A panel of frame statistics drawn with its own sprite renderer.
+pub struct DebugOverlay {
+    sprites: SpriteRenderer,
+    group: usize,
+    font: BitFont,
+    frame_times: Vec<f32>,
+    // Where the next frame time goes in `frame_times`
+    next_frame: usize,
+}
+
+impl DebugOverlay {
+    /// Creates a [`DebugOverlay`] meant to draw into the given color
+    /// target, typically the swapchain.
+    pub fn new(gpu: &WGPU, color_target: wgpu::ColorTargetState) -> Self {
+        let mut sprites = SpriteRenderer::new(gpu, color_target, None);
+        // Two layers, since GL can't use array textures with just one
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("overlay:font"),
+            size: wgpu::Extent3d {
+                width: FONT_W,
+                height: FONT_H,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let mut pixels = vec![0_u8; (FONT_W * FONT_H * 4) as usize];
+        let mut set = |x: u32, y: u32| {
+            let idx = ((y * FONT_W + x) * 4) as usize;
+            pixels[idx..idx + 4].fill(255);
+        };
+        for (ch, bits) in GLYPHS {
+            let idx = u32::from(ch) - u32::from(' ');
+            let (cell_x, cell_y) = ((idx % 16) * 4, (idx / 16) * 6);
+            for y in 0..5 {
+                for x in 0..3 {
+                    if bits >> (3 * (4 - y) + (2 - x)) & 1 == 1 {
+                        set(cell_x + x, cell_y + y);
+                    }
+                }
+            }
+        }
+        for y in 0..BLOCK.h as u32 {
+            for x in 0..BLOCK.w as u32 {
+                set(BLOCK.x as u32 + x, BLOCK.y as u32 + y);
+            }
+        }
+        gpu.queue().write_texture(
+            texture.as_image_copy(),
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(FONT_W * 4),
+                rows_per_image: Some(FONT_H),
+            },
+            wgpu::Extent3d {
+                width: FONT_W,
+                height: FONT_H,
+                depth_or_array_layers: 1,
+            },
+        );
+        let font =
+            BitFont::with_sheet_region(' '..='Z', SheetRegion::rect(0, 0, 64, 24), 4, 6, 0, 0);
+        // Room for the background, graph, and a few lines of text
+        let count = 1 + HISTORY_LEN + 128;
+        let group = sprites.add_sprite_group(
+            gpu,
+            &texture,
+            vec![Transform::ZERO; count],
+            vec![SheetRegion::ZERO; count],
+            Camera2D {
+                screen_pos: [0.0, 0.0],
+                screen_size: [1.0, 1.0],
+            },
+        );
+        Self {
+            sprites,
+            group,
+            font,
+            frame_times: vec![0.0; HISTORY_LEN],
+            next_frame: 0,
+        }
+    }
+    /// Records a frame's statistics and rebuilds the panel in the
+    /// top-left corner of a target of the given size.
+    pub fn update(&mut self, gpu: &WGPU, stats: &FrameStats, screen_size: [u32; 2]) {
+        self.frame_times[self.next_frame] = stats.frame_time;
+        self.next_frame = (self.next_frame + 1) % HISTORY_LEN;
+        let gpu_time = match stats.gpu_time {
+            Some(ms) => format!("{ms:.2} MS"),
+            None => "N/A".to_string(),
+        };
+        let text = format!(
+            "FPS {:.0} ({:.1} MS)\nDRAWS {} INSTANCES {}\nUPLOAD {:.1} KB\nGPU {}",
+            stats.fps,
+            stats.frame_time * 1000.0,
+            stats.draw_calls,
+            stats.instances,
+            stats.upload_bytes as f32 / 1024.0,
+            gpu_time
+        );
+        let layout = TextLayout::new(
+            &self.font,
+            &[TextRun::new(&text).with_colormod([255, 255, 255, 255])],
+            &LayoutOptions {
+                char_height: CHAR_HEIGHT,
+                line_spacing: 1.25,
+                ..Default::default()
+            },
+        );
+        let [text_w, text_h] = layout.size();
+        let graph_w = (HISTORY_LEN * BAR_WIDTH as usize) as f32;
+        let panel_w = text_w.max(graph_w) + PADDING * 2.0;
+        let panel_h = text_h + GRAPH_HEIGHT + PADDING * 3.0;
+        let top = screen_size[1] as f32 - PADDING;
+        let left = PADDING;
+
+        let count = 1 + HISTORY_LEN + layout.sprite_count();
+        self.sprites.resize_sprite_group(gpu, self.group, count);
+        let (trfs, uvs) = self.sprites.get_sprites_mut(self.group);
+        // Sprites are drawn in order, so the background goes first
+        trfs[0] = Transform {
+            w: panel_w as u16,
+            h: panel_h as u16,
+            x: left + panel_w / 2.0,
+            y: top - panel_h / 2.0,
+            rot: 0.0,
+        };
+        uvs[0] = BLOCK.with_colormod([16, 16, 24, 255]);
+        let graph_bottom = top - panel_h + PADDING;
+        for i in 0..HISTORY_LEN {
+            let t = self.frame_times[(self.next_frame + i) % HISTORY_LEN];
+            let h = (t / GRAPH_MAX).min(1.0) * GRAPH_HEIGHT;
+            trfs[1 + i] = Transform {
+                w: BAR_WIDTH,
+                h: h.max(1.0) as u16,
+                x: left + PADDING + (i as f32 + 0.5) * BAR_WIDTH as f32,
+                y: graph_bottom + h.max(1.0) / 2.0,
+                rot: 0.0,
+            };
+            let color = if t <= 1.0 / 55.0 {
+                [64, 224, 64, 255]
+            } else if t <= 1.0 / 28.0 {
+                [240, 200, 48, 255]
+            } else {
+                [240, 64, 48, 255]
+            };
+            uvs[1 + i] = BLOCK.with_colormod(color);
+        }
+        layout.draw(
+            &self.font,
+            &mut trfs[1 + HISTORY_LEN..],
+            &mut uvs[1 + HISTORY_LEN..],
+            [left + PADDING, top - PADDING],
+            0,
+        );
+        self.sprites.upload_sprites(gpu, self.group, 0..count);
+        self.sprites.set_camera(
+            gpu,
+            self.group,
+            Camera2D {
+                screen_pos: [0.0, 0.0],
+                screen_size: [screen_size[0] as f32, screen_size[1] as f32],
+            },
+        );
+    }
+    /// Draws the overlay into the given pass.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        self.sprites.render(rpass, ..);
+    }
+}
+
+enum TimerState {
+    // The readback buffer is free to receive new timestamps
+    Idle,
+    // Timestamps have been copied but not yet mapped
+    Copied,
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+/// Measures how long the GPU spends between the start of one render
+/// pass and the end of another using timestamp queries.  Results
+/// arrive a frame or more later; frames recorded while an earlier
+/// result is still being read back aren't measured.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    state: TimerState,
+    latest: Option<f32>,
+}
+
+impl GpuTimer {
+    /// Creates a [`GpuTimer`], or returns `None` if the device
+    /// wasn't created with [`wgpu::Features::TIMESTAMP_QUERY`].
+    pub fn new(gpu: &WGPU) -> Option<Self> {
+        if !gpu
+            .device()
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return None;
+        }
+        let query_set = gpu.device().create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("timer:queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let size = 2 * std::mem::size_of::<u64>() as u64;
+        let resolve_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("timer:resolve"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("timer:readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            state: TimerState::Idle,
+            latest: None,
+        })
+    }
+    /// Timestamp writes for the first pass to be measured.
+    pub fn begin_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: None,
+        }
+    }
+    /// Timestamp writes for the last pass to be measured.
+    pub fn end_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: None,
+            end_of_pass_write_index: Some(1),
+        }
+    }
+    /// Copies this frame's timestamps out for reading; call this
+    /// after the measured passes are recorded.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !matches!(self.state, TimerState::Idle) {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.resolve_buffer.size(),
+        );
+        self.state = TimerState::Copied;
+    }
+    /// Starts reading back resolved timestamps; call this after
+    /// submitting the encoder passed to [`GpuTimer::resolve`].
+    pub fn after_submit(&mut self) {
+        if !matches!(self.state, TimerState::Copied) {
+            return;
+        }
+        let (sender, ready) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.state = TimerState::Mapping(ready);
+    }
+    /// Returns the most recently measured GPU time in milliseconds,
+    /// picking up a finished readback if there is one.
+    pub fn poll(&mut self, gpu: &WGPU) -> Option<f32> {
+        let TimerState::Mapping(ready) = &self.state else {
+            return self.latest;
+        };
+        gpu.device().poll(wgpu::Maintain::Poll);
+        match ready.try_recv() {
+            Ok(Ok(())) => {
+                let ticks: [u64; 2] = {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    bytemuck::pod_read_unaligned(&data)
+                };
+                self.readback_buffer.unmap();
+                let period = gpu.queue().get_timestamp_period();
+                self.latest = Some(ticks[1].saturating_sub(ticks[0]) as f32 * period / 1_000_000.0);
+                self.state = TimerState::Idle;
+            }
+            Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => {
+                self.state = TimerState::Idle;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+        self.latest
+    }
+}
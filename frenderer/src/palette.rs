@@ -0,0 +1,102 @@
+//! Indexed-color sprites.  A sprite group drawn with a [`Palette`]
+//! reads palette indices from the red channel of its spritesheet
+//! instead of colors, and looks each index up in the palette's
+//! colors, so swapping palettes recolors the group (e.g. for team
+//! colors or a damage flash) without touching its sheet.
+//!
+//! Spritesheets for palettes should have a non-sRGB format like
+//! [`wgpu::TextureFormat::Rgba8Unorm`] (so indices are read back
+//! exactly) and can't be filtered, which sprite sheets aren't by
+//! default.  Palette colors are given in sRGB, like the texels of an
+//! sRGB spritesheet, and alpha below 0.05 is transparent as usual;
+//! index 0 is commonly left transparent.  Color modulation and tints
+//! apply to the looked-up colors.
+//!
+//! A palette is a [`Material`] with its own fragment shader, so it's
+//! given to [`crate::sprites::SpriteRenderer::set_material`] like
+//! any other; [`crate::Renderer::sprite_group_set_palette`] keeps one
+//! per group.
+
+use crate::{
+    material::{Material, MaterialDesc},
+    WGPU,
+};
+
+/// How many colors a palette holds.
+pub const PALETTE_SIZE: usize = 256;
+
+const PALETTE_SHADER: &str = r#"
+@group(3) @binding(0)
+var<uniform> palette: array<vec4<f32>, 256>;
+
+@fragment
+fn fs_palette(in:VertexOutput) -> @location(0) vec4<f32> {
+    let index = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index).r;
+    let color = palette[u32(round(index * 255.0))];
+    if color.w < 0.05 || in.tint.w == 0.0 { discard; }
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz;
+    return vec4<f32>(out_color, color.w * in.tint.w);
+}
+"#;
+
+/// Up to [`PALETTE_SIZE`] colors which indexed-color sprites are drawn with.
+pub struct Palette {
+    material: Material,
+    colors: Vec<[u8; 4]>,
+}
+
+impl Palette {
+    /// Creates a palette from sRGB colors; indices past the end of
+    /// `colors` are transparent.  Panics if there are more than
+    /// [`PALETTE_SIZE`] colors.
+    pub fn new(gpu: &WGPU, colors: &[[u8; 4]]) -> Self {
+        assert!(colors.len() <= PALETTE_SIZE, "Too many palette colors");
+        let material = Material::new(
+            gpu,
+            &MaterialDesc {
+                label: "palette",
+                shader: PALETTE_SHADER,
+                fragment_entry: Some("fs_palette"),
+                params: bytemuck::cast_slice(&Self::uniform(colors)),
+                ..Default::default()
+            },
+        );
+        Self {
+            material,
+            colors: colors.to_vec(),
+        }
+    }
+    // Linear colors, padded out to the full palette
+    fn uniform(colors: &[[u8; 4]]) -> [[f32; 4]; PALETTE_SIZE] {
+        let mut uniform = [[0.0; 4]; PALETTE_SIZE];
+        for (entry, &[r, g, b, a]) in uniform.iter_mut().zip(colors) {
+            *entry = [from_srgb(r), from_srgb(g), from_srgb(b), a as f32 / 255.0];
+        }
+        uniform
+    }
+    /// Returns the palette's colors.
+    pub fn colors(&self) -> &[[u8; 4]] {
+        &self.colors
+    }
+    /// Replaces the palette's colors, recoloring every group drawn
+    /// with it.  Panics if there are more than [`PALETTE_SIZE`] colors.
+    pub fn set_colors(&mut self, gpu: &WGPU, colors: &[[u8; 4]]) {
+        assert!(colors.len() <= PALETTE_SIZE, "Too many palette colors");
+        self.colors = colors.to_vec();
+        self.material
+            .set_params(gpu, bytemuck::cast_slice(&Self::uniform(colors)));
+    }
+    /// Returns the material sprite groups are drawn with to use this palette.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+fn from_srgb(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,700 @@
+//! GPU particle systems, for sparks, smoke, and dust.
+//!
+//! Each emitter is described by an [`EmitterDesc`]: where and how
+//! often particles spawn, how long they live, how fast they start
+//! moving and how acceleration and drag change that, and how their
+//! size and color ramp from the start of their lives to the end.
+//! [`ParticleRenderer::update`] steps every emitter's particles in a
+//! compute shader, so they never round-trip through the CPU, and
+//! [`ParticleRenderer::render`] draws them as camera-facing quads,
+//! either cut from an array texture or as soft round dots.
+//!
+//! Particles are seen through a [`ParticleCamera`], either a
+//! [`Camera2D`] (for sprite games, where they're drawn over
+//! everything) or a [`Camera3D`] (where they're hidden behind scene
+//! geometry when there's a depth buffer).  [`crate::Renderer`] keeps
+//! its own `ParticleRenderer` (see
+//! [`crate::Renderer::particle_emitter_add`]), which follows the
+//! textured mesh camera unless it's given another one.
+//!
+//! Particles need compute shaders and storage buffers in vertex
+//! shaders, which WebGL2 and some older GL devices don't have.
+
+use std::{borrow::Cow, sync::Arc};
+
+use ultraviolet::{Rotor3, Vec3};
+
+use crate::{capture, meshes::Camera3D, sprites::BlendMode, sprites::Camera2D, LimitError, WGPU};
+
+// Must match the compute shader's workgroup size
+const WORKGROUP_SIZE: u32 = 64;
+// Marks emitters which draw soft dots instead of texture regions
+const NO_LAYER: u32 = u32::MAX;
+
+/// Describes how an emitter spawns and moves its particles.  Ranges
+/// are `[min, max]` and each particle picks a random value in them
+/// when it spawns; ramps are `[start, end]` and are interpolated
+/// over each particle's life.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmitterDesc {
+    /// Where particles spawn, in the camera's world space
+    pub position: [f32; 3],
+    /// The size of the box around `position` that particles spawn
+    /// in; zero spawns them all at `position`
+    pub spawn_extent: [f32; 3],
+    /// How many particles spawn each second
+    pub spawn_rate: f32,
+    /// The most particles alive at once; when it's reached, new
+    /// particles replace the oldest
+    pub capacity: u32,
+    /// How many seconds each particle lives
+    pub lifetime: [f32; 2],
+    /// The range of velocities particles start with, per axis
+    pub velocity: [[f32; 3]; 2],
+    /// Added to every particle's velocity each second, e.g. gravity
+    pub acceleration: [f32; 3],
+    /// The fraction of its velocity each particle loses each second
+    pub drag: f32,
+    /// How wide particles are, in world units
+    pub size: [f32; 2],
+    /// Particles' colors, multiplied with their texture region
+    pub color: [[f32; 4]; 2],
+    /// Which layer of the emitter's texture to draw particles from,
+    /// or `None` to draw soft round dots
+    pub layer: Option<u32>,
+    /// The region of the texture layer to draw, as x, y, width, and
+    /// height in normalized texture coordinates
+    pub uv_rect: [f32; 4],
+    /// How particles combine with what's drawn behind them; blended
+    /// particles aren't sorted and don't write depth
+    pub blend: BlendMode,
+}
+
+impl Default for EmitterDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            spawn_extent: [0.0; 3],
+            spawn_rate: 10.0,
+            capacity: 256,
+            lifetime: [1.0, 1.0],
+            velocity: [[-1.0, 1.0, -1.0], [1.0, 2.0, 1.0]],
+            acceleration: [0.0; 3],
+            drag: 0.0,
+            size: [1.0, 1.0],
+            color: [[1.0; 4], [1.0, 1.0, 1.0, 0.0]],
+            layer: None,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+/// The camera particles are seen through (see
+/// [`ParticleRenderer::set_camera`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleCamera {
+    /// Particles are positioned in sprite world space, with z
+    /// ignored, and face the screen.
+    TwoD(Camera2D),
+    /// Particles are positioned in mesh world space and face the camera.
+    ThreeD(Camera3D),
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct Particle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct EmitterUniforms {
+    position: [f32; 4],
+    spawn_extent: [f32; 4],
+    velocity_min: [f32; 4],
+    velocity_max: [f32; 4],
+    acceleration: [f32; 4],
+    lifetime_size: [f32; 4],
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+    uv_rect: [f32; 4],
+    spawn: [u32; 4],
+    layer: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct CameraUniforms {
+    projection_view: [f32; 16],
+    right: [f32; 4],
+    up: [f32; 4],
+}
+
+struct Emitter {
+    desc: EmitterDesc,
+    texture_view: Arc<wgpu::TextureView>,
+    uniform_buffer: wgpu::Buffer,
+    sim_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+    // The ring slot the next particle spawns into
+    next_slot: u32,
+    // Fractions of a particle left over from earlier steps
+    spawn_debt: f32,
+    // Particles to spawn at the next step on top of the spawn rate
+    burst: u32,
+    steps: u32,
+}
+
+/// Simulates and draws emitters' particles (see the [module
+/// documentation](self)).
+pub struct ParticleRenderer {
+    emitters: Vec<Option<Emitter>>,
+    camera: Option<ParticleCamera>,
+    sim_pipeline: wgpu::ComputePipeline,
+    sim_bind_group_layout: wgpu::BindGroupLayout,
+    render_bind_group_layout: wgpu::BindGroupLayout,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    // One for each blend mode
+    pipelines: [wgpu::RenderPipeline; 3],
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    // Drawn from by emitters without a texture of their own
+    white_texture: wgpu::Texture,
+}
+
+impl ParticleRenderer {
+    /// Creates a new `ParticleRenderer` meant to draw into the given
+    /// color target state with the given depth texture format (or
+    /// without depth testing if `depth_format` is `None`).  Returns
+    /// an error if the device doesn't support compute shaders or
+    /// storage buffers in vertex shaders.
+    pub fn try_new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Result<Self, LimitError> {
+        let flags = gpu.adapter().get_downlevel_capabilities().flags;
+        let particle_size = std::mem::size_of::<Particle>() as u64;
+        if !flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::VERTEX_STORAGE)
+            || gpu.device().limits().max_storage_buffers_per_shader_stage == 0
+        {
+            return Err(LimitError::StorageBindingSize {
+                buffer: "particles:particles",
+                size: particle_size,
+                max: 0,
+            });
+        }
+        let sim_shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("particles:sim_shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("particles_sim.wgsl"))),
+            });
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("particles:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("particles.wgsl"))),
+            });
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sim_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("particles:sim_bgl"),
+                    entries: &[
+                        uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                        storage_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                    ],
+                });
+        let sim_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("particles:sim_pipeline_layout"),
+                    bind_group_layouts: &[&sim_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let sim_pipeline = gpu
+            .device()
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("particles:sim_pipeline"),
+                layout: Some(&sim_pipeline_layout),
+                module: &sim_shader,
+                entry_point: "cs_main",
+            });
+        let camera_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("particles:camera_bgl"),
+                    entries: &[uniform_entry(0, wgpu::ShaderStages::VERTEX)],
+                });
+        let render_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("particles:emitter_bgl"),
+                    entries: &[
+                        uniform_entry(0, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT),
+                        storage_entry(1, wgpu::ShaderStages::VERTEX, true),
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("particles:pipeline_layout"),
+                    bind_group_layouts: &[&camera_bind_group_layout, &render_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let depth_format = depth_format.into();
+        let pipelines = Self::create_pipelines(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            &color_target,
+            (depth_format, 1),
+        );
+        let camera_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles:camera"),
+            size: std::mem::size_of::<CameraUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particles:camera_bg"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let sampler = gpu
+            .device()
+            .create_sampler(&wgpu::SamplerDescriptor::default());
+        // GL treats single-layer array textures as plain 2D
+        // textures, so this has two layers
+        let white_size = wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 2,
+        };
+        let white_texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("particles:white"),
+            size: white_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        gpu.queue().write_texture(
+            white_texture.as_image_copy(),
+            &[255; 8],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            white_size,
+        );
+        Ok(Self {
+            emitters: vec![],
+            camera: None,
+            sim_pipeline,
+            sim_bind_group_layout,
+            render_bind_group_layout,
+            shader,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipelines,
+            camera_buffer,
+            camera_bind_group,
+            sampler,
+            white_texture,
+        })
+    }
+    fn create_pipelines(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: &wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> [wgpu::RenderPipeline; 3] {
+        [BlendMode::Cutout, BlendMode::Alpha, BlendMode::Additive].map(|blend| {
+            let cutout = blend == BlendMode::Cutout;
+            gpu.device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("particles:pipeline"),
+                    layout: Some(pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: if cutout { "fs_main" } else { "fs_main_blended" },
+                        targets: &[Some(blend.color_target(color_target))],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    // Blended particles would hide the ones drawn
+                    // after them if they wrote depth
+                    depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                        format,
+                        depth_write_enabled: cutout,
+                        depth_compare: wgpu::CompareFunction::LessEqual,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        ..Default::default()
+                    },
+                    multiview: None,
+                })
+        })
+    }
+    /// Adds an emitter which draws from the given array texture (or
+    /// from plain white if `texture` is `None`), returning its index.
+    /// Returns an error if its particles wouldn't fit in the
+    /// device's limits.
+    pub fn add_emitter(
+        &mut self,
+        gpu: &WGPU,
+        desc: EmitterDesc,
+        texture: Option<&wgpu::Texture>,
+    ) -> Result<usize, LimitError> {
+        let texture = texture.unwrap_or(&self.white_texture);
+        gpu.check_texture_layers(texture)?;
+        let texture_view = Arc::new(texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        }));
+        let emitter = self.create_emitter(gpu, desc, texture_view)?;
+        let which = self
+            .emitters
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.emitters.len());
+        if which == self.emitters.len() {
+            self.emitters.push(None);
+        }
+        self.emitters[which] = Some(emitter);
+        Ok(which)
+    }
+    fn create_emitter(
+        &self,
+        gpu: &WGPU,
+        desc: EmitterDesc,
+        texture_view: Arc<wgpu::TextureView>,
+    ) -> Result<Emitter, LimitError> {
+        let capacity = desc.capacity.max(1);
+        let size = u64::from(capacity) * std::mem::size_of::<Particle>() as u64;
+        gpu.check_buffer("particles:particles", size, true)?;
+        // Zeroed particles are dead
+        let particles = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles:particles"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particles:emitter"),
+            size: std::mem::size_of::<EmitterUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sim_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particles:sim_bg"),
+            layout: &self.sim_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+            ],
+        });
+        let render_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particles:emitter_bg"),
+            layout: &self.render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        let emitter = Emitter {
+            desc,
+            texture_view,
+            uniform_buffer,
+            sim_bind_group,
+            render_bind_group,
+            next_slot: 0,
+            spawn_debt: 0.0,
+            burst: 0,
+            steps: 0,
+        };
+        Self::write_uniforms(gpu, &emitter, 0.0, (0, 0));
+        Ok(emitter)
+    }
+    fn write_uniforms(gpu: &WGPU, emitter: &Emitter, dt: f32, (first, count): (u32, u32)) {
+        let desc = &emitter.desc;
+        let xyzw = |[x, y, z]: [f32; 3], w| [x, y, z, w];
+        let uniforms = EmitterUniforms {
+            position: xyzw(desc.position, desc.drag),
+            spawn_extent: xyzw(desc.spawn_extent, dt),
+            velocity_min: xyzw(desc.velocity[0], 0.0),
+            velocity_max: xyzw(desc.velocity[1], 0.0),
+            acceleration: xyzw(desc.acceleration, 0.0),
+            lifetime_size: [
+                desc.lifetime[0],
+                desc.lifetime[1],
+                desc.size[0],
+                desc.size[1],
+            ],
+            color_start: desc.color[0],
+            color_end: desc.color[1],
+            uv_rect: desc.uv_rect,
+            spawn: [first, count, desc.capacity.max(1), emitter.steps],
+            layer: [desc.layer.unwrap_or(NO_LAYER), 0, 0, 0],
+        };
+        gpu.queue()
+            .write_buffer(&emitter.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    /// Changes an emitter's description.  Changing its capacity
+    /// clears its particles; other changes apply to the particles
+    /// already alive (except their spawn-time ranges) from the next
+    /// [`ParticleRenderer::update`].  Returns an error if the new
+    /// capacity wouldn't fit in the device's limits, leaving the
+    /// emitter unchanged.
+    pub fn set_emitter(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        desc: EmitterDesc,
+    ) -> Result<(), LimitError> {
+        let emitter = self.emitters[which]
+            .as_ref()
+            .expect("No such particle emitter");
+        if emitter.desc.capacity.max(1) != desc.capacity.max(1) {
+            let texture_view = emitter.texture_view.clone();
+            let emitter = self.create_emitter(gpu, desc, texture_view)?;
+            self.emitters[which] = Some(emitter);
+        } else {
+            let emitter = self.emitter_mut(which);
+            emitter.desc = desc;
+            Self::write_uniforms(gpu, emitter, 0.0, (0, 0));
+        }
+        Ok(())
+    }
+    /// Returns an emitter's description.
+    pub fn emitter(&self, which: usize) -> &EmitterDesc {
+        &self.emitters[which]
+            .as_ref()
+            .expect("No such particle emitter")
+            .desc
+    }
+    fn emitter_mut(&mut self, which: usize) -> &mut Emitter {
+        self.emitters[which]
+            .as_mut()
+            .expect("No such particle emitter")
+    }
+    /// Deletes an emitter and its particles, leaving an empty
+    /// placeholder which the next added emitter will use.
+    pub fn remove_emitter(&mut self, which: usize) {
+        self.emitters[which] = None;
+    }
+    /// Returns how many emitter slots there are, including empty ones.
+    pub fn emitter_count(&self) -> usize {
+        self.emitters.len()
+    }
+    /// Spawns `count` extra particles from an emitter at the next
+    /// [`ParticleRenderer::update`], e.g. for explosions and impacts.
+    pub fn burst(&mut self, which: usize, count: u32) {
+        let emitter = self.emitter_mut(which);
+        emitter.burst = emitter.burst.saturating_add(count);
+    }
+    /// Steps every emitter's particles forward by `dt` seconds on the
+    /// GPU, spawning new ones and aging, moving, and retiring the
+    /// rest.
+    pub fn update(&mut self, gpu: &WGPU, dt: f32) {
+        if self.emitters.iter().all(Option::is_none) {
+            return;
+        }
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("particles:update"),
+            });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("particles:update"),
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(&self.sim_pipeline);
+            for emitter in self.emitters.iter_mut().flatten() {
+                let capacity = emitter.desc.capacity.max(1);
+                let spawned = emitter.spawn_debt + emitter.desc.spawn_rate.max(0.0) * dt;
+                emitter.spawn_debt = spawned.fract();
+                let count = (spawned as u32)
+                    .saturating_add(std::mem::take(&mut emitter.burst))
+                    .min(capacity);
+                let first = emitter.next_slot;
+                emitter.next_slot = (first + count) % capacity;
+                emitter.steps = emitter.steps.wrapping_add(1);
+                Self::write_uniforms(gpu, emitter, dt, (first, count));
+                cpass.set_bind_group(0, &emitter.sim_bind_group, &[]);
+                cpass.dispatch_workgroups(capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+        }
+        gpu.queue().submit(Some(encoder.finish()));
+    }
+    /// Sets the camera particles are seen through, or `None` to use
+    /// the camera given to [`ParticleRenderer::prepare`].
+    pub fn set_camera(&mut self, camera: Option<ParticleCamera>) {
+        self.camera = camera;
+    }
+    /// Returns the camera set with [`ParticleRenderer::set_camera`],
+    /// if any.
+    pub fn camera(&self) -> Option<ParticleCamera> {
+        self.camera
+    }
+    /// Uploads the camera, using the given 3D camera if none has been
+    /// set; call this before drawing with [`ParticleRenderer::render`].
+    pub fn prepare(&mut self, gpu: &WGPU, camera_3d: Camera3D) {
+        let uniforms = match self.camera.unwrap_or(ParticleCamera::ThreeD(camera_3d)) {
+            ParticleCamera::TwoD(camera) => CameraUniforms {
+                projection_view: *camera.projection().as_array(),
+                right: [1.0, 0.0, 0.0, 0.0],
+                up: [0.0, 1.0, 0.0, 0.0],
+            },
+            ParticleCamera::ThreeD(camera) => {
+                let rotation = Rotor3::from_quaternion_array(camera.rotation);
+                let right = rotation * Vec3::unit_x();
+                let up = rotation * Vec3::unit_y();
+                CameraUniforms {
+                    projection_view: *camera.projection_view().as_array(),
+                    right: [right.x, right.y, right.z, 0.0],
+                    up: [up.x, up.y, up.z, 0.0],
+                }
+            }
+        };
+        gpu.queue()
+            .write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipelines.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipelines = Self::create_pipelines(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            &self.color_target,
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass this draws
+    /// into, re-creating the pipelines.  The default is 1 (no
+    /// multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target this renderer draws into (e.g. for a
+    /// new color format), re-creating its pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Draws every emitter's particles, in the order the emitters
+    /// were added, into the given renderpass, which should have been
+    /// begun with the same color and depth formats this renderer was
+    /// created with.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        if self.emitters.iter().all(Option::is_none) {
+            return;
+        }
+        capture::set_bind_group(rpass, 0, &self.camera_bind_group, &[], "particles:camera");
+        for emitter in self.emitters.iter().flatten() {
+            let pipeline = match emitter.desc.blend {
+                BlendMode::Cutout => 0,
+                BlendMode::Alpha => 1,
+                BlendMode::Additive => 2,
+            };
+            capture::set_pipeline(rpass, &self.pipelines[pipeline], "particles:pipeline");
+            capture::set_bind_group(
+                rpass,
+                1,
+                &emitter.render_bind_group,
+                &[],
+                "particles:emitter",
+            );
+            capture::draw(rpass, 0..6, 0..emitter.desc.capacity.max(1));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    // Particles whose age has reached their lifetime are dead
+    lifetime: f32,
+}
+
+struct Emitter {
+    // xyz: where particles spawn, w: drag
+    position: vec4<f32>,
+    // xyz: the size of the box particles spawn in, w: the time step
+    spawn_extent: vec4<f32>,
+    velocity_min: vec4<f32>,
+    velocity_max: vec4<f32>,
+    acceleration: vec4<f32>,
+    // Lifetime range, then start and end sizes
+    lifetime_size: vec4<f32>,
+    color_start: vec4<f32>,
+    color_end: vec4<f32>,
+    // x, y, w, h in normalized texture coordinates
+    uv_rect: vec4<f32>,
+    // First slot to spawn into, spawn count, capacity, random seed
+    spawn: vec4<u32>,
+    // Texture layer (or 0xFFFFFFFF for soft dots), then padding
+    layer: vec4<u32>,
+}
+
+struct Camera {
+    projection_view: mat4x4<f32>,
+    // The world space directions of the quads' x and y axes
+    right: vec4<f32>,
+    up: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> emitter: Emitter;
+@group(1) @binding(1)
+var<storage, read> particles_in: array<Particle>;
+@group(1) @binding(2)
+var t_diffuse: texture_2d_array<f32>;
+@group(1) @binding(3)
+var s_diffuse: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // Position within the quad, from -0.5 to 0.5
+    @location(1) corner: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let p = particles_in[instance];
+    if p.age >= p.lifetime {
+        // Dead particles collapse outside the clip volume
+        return VertexOutput(vec4(2.0, 2.0, 2.0, 1.0), vec2(0.0), vec2(0.0), vec4(0.0));
+    }
+    let t = p.age / p.lifetime;
+    var corners = array(
+        vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(-0.5, 0.5),
+        vec2(-0.5, 0.5), vec2(0.5, -0.5), vec2(0.5, 0.5),
+    );
+    let corner = corners[vertex];
+    let size = mix(emitter.lifetime_size.z, emitter.lifetime_size.w, t);
+    let world = p.position + (camera.right.xyz * corner.x + camera.up.xyz * corner.y) * size;
+    let uv = emitter.uv_rect.xy + vec2(corner.x + 0.5, 0.5 - corner.y) * emitter.uv_rect.zw;
+    return VertexOutput(
+        camera.projection_view * vec4(world, 1.0),
+        uv,
+        corner,
+        mix(emitter.color_start, emitter.color_end, t),
+    );
+}
+
+fn particle_color(in: VertexOutput) -> vec4<f32> {
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords, select(emitter.layer.x, 0u, emitter.layer.x == 0xFFFFFFFFu));
+    if emitter.layer.x == 0xFFFFFFFFu {
+        let falloff = 1.0 - smoothstep(0.0, 1.0, length(in.corner) * 2.0);
+        return vec4(in.color.rgb, in.color.a * falloff);
+    }
+    return texel * in.color;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = particle_color(in);
+    if color.a < 0.5 {
+        discard;
+    }
+    return vec4(color.rgb, 1.0);
+}
+
+// Blended particles output premultiplied colors, like blended sprites
+@fragment
+fn fs_main_blended(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = particle_color(in);
+    return vec4(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,74 @@
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    // Particles whose age has reached their lifetime are dead
+    lifetime: f32,
+}
+
+struct Emitter {
+    // xyz: where particles spawn, w: drag
+    position: vec4<f32>,
+    // xyz: the size of the box particles spawn in, w: the time step
+    spawn_extent: vec4<f32>,
+    velocity_min: vec4<f32>,
+    velocity_max: vec4<f32>,
+    acceleration: vec4<f32>,
+    // Lifetime range, then start and end sizes
+    lifetime_size: vec4<f32>,
+    color_start: vec4<f32>,
+    color_end: vec4<f32>,
+    // x, y, w, h in normalized texture coordinates
+    uv_rect: vec4<f32>,
+    // First slot to spawn into, spawn count, capacity, random seed
+    spawn: vec4<u32>,
+    // Texture layer (or 0xFFFFFFFF for soft dots), then padding
+    layer: vec4<u32>,
+}
+
+@group(0) @binding(0)
+var<uniform> emitter: Emitter;
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand(state: ptr<function, u32>) -> f32 {
+    *state = pcg(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+fn rand3(state: ptr<function, u32>) -> vec3<f32> {
+    let x = rand(state);
+    let y = rand(state);
+    let z = rand(state);
+    return vec3(x, y, z);
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let capacity = emitter.spawn.z;
+    if i >= capacity {
+        return;
+    }
+    var p = particles[i];
+    let dt = emitter.spawn_extent.w;
+    // Spawned slots are a range of the ring starting at spawn.x
+    if (i + capacity - emitter.spawn.x) % capacity < emitter.spawn.y {
+        var state = pcg(i ^ pcg(emitter.spawn.w));
+        p.position = emitter.position.xyz + (rand3(&state) - 0.5) * emitter.spawn_extent.xyz;
+        p.velocity = mix(emitter.velocity_min.xyz, emitter.velocity_max.xyz, rand3(&state));
+        p.age = 0.0;
+        p.lifetime = mix(emitter.lifetime_size.x, emitter.lifetime_size.y, rand(&state));
+    } else if p.age < p.lifetime {
+        p.velocity = (p.velocity + emitter.acceleration.xyz * dt) * max(0.0, 1.0 - emitter.position.w * dt);
+        p.position += p.velocity * dt;
+        p.age += dt;
+    }
+    particles[i] = p;
+}
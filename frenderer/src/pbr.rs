@@ -0,0 +1,210 @@
+//! Physically based shading for textured mesh groups.  A group given
+//! [`PbrParams`] (see [`crate::meshes::MeshRenderer::set_group_pbr`])
+//! is drawn with a metallic-roughness model: a Cook-Torrance
+//! specular term (GGX distribution, Smith shadowing, and Schlick's
+//! Fresnel) under the directional and point lights, with the same
+//! shadows as the usual shading.  Metallic and roughness can be read
+//! from layers of the group's array texture (see
+//! [`crate::meshes::TextureLayers::metallic_roughness`]) and are
+//! multiplied with the group's factors.
+//!
+//! Lights are scaled so that a rough, white, non-metallic surface
+//! is as bright as it is under the usual shading.  Without an
+//! environment the hemisphere light stands in for indirect light;
+//! with one (see [`crate::meshes::MeshRenderer::set_environment`]),
+//! a cubemap gives image-based lighting: its mip levels are read as
+//! increasingly rough reflections and its smallest one as the
+//! diffuse irradiance, so ideally they're prefiltered, though
+//! [`crate::mipmap`]'s box-filtered levels will do in a pinch.
+//!
+//! Like materials, physically based shading only changes how groups
+//! are drawn in the main pass; the deferred path, outlines, picking,
+//! and velocities draw them as usual.
+
+use crate::WGPU;
+
+/// A mesh group's physically based material parameters.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PbrParams {
+    /// Multiplied with the group's texture
+    pub base_color: [f32; 4],
+    /// Multiplied with the metallic channel (blue) of metallic-roughness layers
+    pub metallic: f32,
+    /// Multiplied with the roughness channel (green) of metallic-roughness layers
+    pub roughness: f32,
+    /// Light given off regardless of lighting
+    pub emissive: [f32; 3],
+    /// How strongly the environment, if there is one, lights the group
+    pub environment: f32,
+}
+
+impl Default for PbrParams {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0; 3],
+            environment: 1.0,
+        }
+    }
+}
+
+// As laid out in meshes_pbr.wgsl's `Pbr` struct
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub(crate) struct PbrUniform {
+    base_color: [f32; 4],
+    emissive_environment: [f32; 4],
+    // Metallic, roughness, the environment's last mip level (or
+    // negative without one), and padding
+    metallic_roughness: [f32; 4],
+}
+
+impl PbrParams {
+    pub(crate) fn uniform(&self, environment_mips: Option<u32>) -> PbrUniform {
+        let [r, g, b] = self.emissive;
+        PbrUniform {
+            base_color: self.base_color,
+            emissive_environment: [r, g, b, self.environment],
+            metallic_roughness: [
+                self.metallic,
+                self.roughness,
+                environment_mips.map_or(-1.0, |mips| (mips - 1) as f32),
+                0.0,
+            ],
+        }
+    }
+}
+
+/// The bind group layout physically based groups are drawn with at
+/// [`crate::material::MATERIAL_GROUP`], along with the environment
+/// they all share.
+pub(crate) struct PbrBindings {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // Bound when there's no environment
+    no_environment: wgpu::TextureView,
+    environment: Option<(wgpu::TextureView, u32)>,
+}
+
+impl PbrBindings {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let layout = gpu
+            .device()
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("pbr:bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("pbr:environment_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let no_environment = gpu
+            .device()
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("pbr:no_environment"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+        Self {
+            layout,
+            sampler,
+            no_environment,
+            environment: None,
+        }
+    }
+    pub(crate) fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+    /// Uses the given cube texture (six square layers, in the order
+    /// +x, -x, +y, -y, +z, -z) as the environment, or none.  Panics if
+    /// the texture isn't a cube.
+    pub(crate) fn set_environment(&mut self, environment: Option<&wgpu::Texture>) {
+        self.environment = environment.map(|texture| {
+            assert!(
+                texture.depth_or_array_layers() == 6 && texture.width() == texture.height(),
+                "Environments must have six square layers {:?}",
+                texture
+            );
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("pbr:environment"),
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+            (view, texture.mip_level_count())
+        });
+    }
+    /// The environment's number of mip levels, if there is one.
+    pub(crate) fn environment_mips(&self) -> Option<u32> {
+        self.environment.as_ref().map(|(_, mips)| *mips)
+    }
+    /// Creates the bind group of a group whose parameters are in `params`.
+    pub(crate) fn bind_group(&self, gpu: &WGPU, params: &wgpu::Buffer) -> wgpu::BindGroup {
+        let environment = self
+            .environment
+            .as_ref()
+            .map_or(&self.no_environment, |(view, _)| view);
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("pbr:bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(environment),
+                },
+            ],
+        })
+    }
+}
@@ -0,0 +1,572 @@
+//! Pixel-accurate picking through an object ID buffer.
+//!
+//! When picking is enabled, each renderer draws its instances a
+//! second time into an ID texture (see e.g.
+//! [`crate::sprites::SpriteRenderer::render_pick`]), writing
+//! `[kind, group, mesh, instance]` for every covered pixel instead of
+//! a color.  The ID pass has its own depth buffer and uses the same
+//! depth tests as normal rendering, so the ID under a pixel belongs
+//! to whatever is visible there.  [`PickPass::pick`] copies out a
+//! single pixel of the ID texture and maps it for reading in the
+//! background; the result is available from [`PickQuery::try_result`]
+//! once the GPU has finished the copy.
+//!
+//! Meshes can also be picked on the CPU, without waiting on the GPU,
+//! by casting a [`Ray`] (e.g. from [`crate::meshes::Camera3D::ray`])
+//! with [`crate::Renderer::raycast_meshes`].  This needs a copy of
+//! each mesh group's geometry, which renderers only keep for groups
+//! added after [`crate::Renderer::set_keep_mesh_geometry`] is turned
+//! on.
+//!
+//! The depth buffer of the last frame can be read back the same way
+//! with [`crate::Renderer::depth_at`] or
+//! [`crate::Renderer::read_depth_region`], e.g. to place objects
+//! under the cursor (see [`crate::meshes::Camera3D::unproject`]) or
+//! for line-of-sight checks; this works for sprites too and doesn't
+//! need picking to be on.
+
+use std::sync::mpsc;
+
+use crate::{capture, meshes::MeshGroup, WGPU};
+
+/// The texture format of object ID textures.
+pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
+
+pub(crate) const KIND_SPRITE: u32 = 1;
+pub(crate) const KIND_MESH: u32 = 2;
+pub(crate) const KIND_FLAT: u32 = 3;
+
+/// The bind group layout renderers use for their pick tables in their ID pipelines.
+pub(crate) fn table_bind_group_layout(gpu: &WGPU) -> wgpu::BindGroupLayout {
+    gpu.device()
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("pick:table_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<[u32; 4]>() as u64),
+                },
+                count: None,
+            }],
+        })
+}
+
+/// The color target state for drawing into an ID texture.
+pub(crate) fn id_color_target() -> wgpu::ColorTargetState {
+    ID_FORMAT.into()
+}
+
+/// A uniform buffer of `[kind, group, mesh, instance base]` entries,
+/// one per draw call of a renderer's ID pass, bound one at a time
+/// with a dynamic offset.  The instance base is added (as an `i32`)
+/// to the shader's instance index to get the ID's instance number.
+pub(crate) struct PickTable {
+    buffer: Option<wgpu::Buffer>,
+    bind_group: Option<wgpu::BindGroup>,
+    capacity: usize,
+    stride: usize,
+    staging: Vec<u8>,
+}
+
+impl PickTable {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        Self {
+            buffer: None,
+            bind_group: None,
+            capacity: 0,
+            stride: gpu.device().limits().min_uniform_buffer_offset_alignment as usize,
+            staging: vec![],
+        }
+    }
+    /// Replaces the table's entries, growing the buffer if needed.
+    pub(crate) fn write(
+        &mut self,
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        entries: &[[u32; 4]],
+    ) {
+        if entries.len() > self.capacity || self.buffer.is_none() {
+            self.capacity = entries.len().max(self.capacity * 2).max(16);
+            let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pick:table"),
+                size: (self.capacity * self.stride) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.bind_group = Some(gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("pick:table_bg"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<[u32; 4]>() as u64),
+                    }),
+                }],
+            }));
+            self.buffer = Some(buffer);
+        }
+        if entries.is_empty() {
+            return;
+        }
+        self.staging.clear();
+        self.staging.resize(entries.len() * self.stride, 0);
+        for (chunk, entry) in self.staging.chunks_mut(self.stride).zip(entries) {
+            chunk[..16].copy_from_slice(bytemuck::cast_slice(entry));
+        }
+        gpu.queue()
+            .write_buffer(self.buffer.as_ref().unwrap(), 0, &self.staging);
+    }
+    /// Binds the given entry of the table.  Panics if the table was never written.
+    pub(crate) fn bind<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        index: u32,
+        entry: usize,
+    ) where
+        's: 'pass,
+    {
+        capture::set_bind_group(
+            rpass,
+            index,
+            self.bind_group
+                .as_ref()
+                .expect("ID pass drawn before its pick table was prepared"),
+            &[(entry * self.stride) as u32],
+            "pick:table",
+        );
+    }
+}
+
+/// Something which was drawn at a picked pixel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pick {
+    /// A sprite in a sprite group of a [`crate::sprites::SpriteRenderer`]
+    Sprite { group: usize, index: usize },
+    /// An instance of a mesh in a [`crate::meshes::MeshRenderer`]
+    Mesh {
+        group: MeshGroup,
+        mesh: usize,
+        instance: usize,
+    },
+    /// An instance of a mesh in a [`crate::meshes::FlatRenderer`]
+    Flat {
+        group: MeshGroup,
+        mesh: usize,
+        instance: usize,
+    },
+}
+
+impl Pick {
+    /// Decodes an ID texture texel, returning `None` if nothing was drawn there.
+    pub fn from_id([kind, group, mesh, instance]: [u32; 4]) -> Option<Self> {
+        let group = group as usize;
+        let mesh = mesh as usize;
+        let instance = instance as usize;
+        match kind {
+            KIND_SPRITE => Some(Self::Sprite {
+                group,
+                index: instance,
+            }),
+            KIND_MESH => Some(Self::Mesh {
+                group: MeshGroup(group),
+                mesh,
+                instance,
+            }),
+            KIND_FLAT => Some(Self::Flat {
+                group: MeshGroup(group),
+                mesh,
+                instance,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A ray in world space, starting at `origin` and heading along `direction`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ray {
+    pub origin: [f32; 3],
+    pub direction: [f32; 3],
+}
+
+/// The closest mesh instance hit by a [`Ray`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+    /// The instance that was hit, as a [`Pick::Mesh`] or [`Pick::Flat`]
+    pub pick: Pick,
+    /// How far along the ray the hit is, in multiples of the ray's
+    /// direction (so in world units if the direction is normalized)
+    pub distance: f32,
+    /// Where the hit is in world space
+    pub point: [f32; 3],
+}
+
+/// A pending read of one pixel of an ID texture.
+pub struct PickQuery {
+    buffer: wgpu::Buffer,
+    ready: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    result: Option<Option<Pick>>,
+}
+
+impl PickQuery {
+    /// Returns the picked object (or `Some(None)` if nothing was
+    /// drawn at the picked pixel) once the read has finished, or
+    /// `None` while it's still in flight.  On native targets reads
+    /// finish when the device is polled, which happens whenever
+    /// commands are submitted (for example, by rendering a frame).
+    pub fn try_result(&mut self) -> Option<Option<Pick>> {
+        if self.result.is_none() {
+            match self.ready.try_recv() {
+                Ok(Ok(())) => {
+                    let id: [u32; 4] = {
+                        let data = self.buffer.slice(..).get_mapped_range();
+                        *bytemuck::from_bytes(&data[..16])
+                    };
+                    self.buffer.unmap();
+                    self.result = Some(Pick::from_id(id));
+                }
+                // The mapping failed, so there's nothing to report
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => self.result = Some(None),
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+        }
+        self.result
+    }
+}
+
+/// A pending read of a region of a depth texture.
+pub struct DepthQuery {
+    buffer: wgpu::Buffer,
+    ready: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    format: wgpu::TextureFormat,
+    origin: [u32; 2],
+    size: [u32; 2],
+    bytes_per_row: u32,
+    result: Option<Option<Vec<f32>>>,
+}
+
+impl DepthQuery {
+    /// Returns the depths in the region row by row from its top left
+    /// (from 0 at the near plane to 1 at the far plane, which is also
+    /// the depth wherever nothing was drawn) once the read has
+    /// finished, `Some(None)` if it failed, or `None` while it's
+    /// still in flight.  Like [`PickQuery::try_result`], reads finish
+    /// when the device is polled.
+    pub fn try_result(&mut self) -> Option<Option<&[f32]>> {
+        if self.result.is_none() {
+            match self.ready.try_recv() {
+                Ok(Ok(())) => {
+                    let depths = {
+                        let data = self.buffer.slice(..).get_mapped_range();
+                        let [width, height] = self.size;
+                        (0..height as usize)
+                            .flat_map(|row| {
+                                let start = row * self.bytes_per_row as usize;
+                                match self.format {
+                                    wgpu::TextureFormat::Depth16Unorm => data
+                                        [start..start + width as usize * 2]
+                                        .chunks_exact(2)
+                                        .map(|d| {
+                                            u16::from_le_bytes([d[0], d[1]]) as f32
+                                                / u16::MAX as f32
+                                        })
+                                        .collect::<Vec<_>>(),
+                                    _ => data[start..start + width as usize * 4]
+                                        .chunks_exact(4)
+                                        .map(|d| f32::from_le_bytes([d[0], d[1], d[2], d[3]]))
+                                        .collect(),
+                                }
+                            })
+                            .collect()
+                    };
+                    self.buffer.unmap();
+                    self.result = Some(Some(depths));
+                }
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => self.result = Some(None),
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+        }
+        self.result.as_ref().map(Option::as_deref)
+    }
+    /// The top left corner of the region being read, in pixels.
+    pub fn origin(&self) -> [u32; 2] {
+        self.origin
+    }
+    /// The width and height of the region being read, in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+}
+
+/// Starts reading back a region of a depth texture (from the top
+/// left), as of the most recently submitted commands.  Returns `None`
+/// if the region is empty or out of bounds, or if the texture's
+/// depth can't be copied (as for [`wgpu::TextureFormat::Depth24Plus`],
+/// or on devices without depth copies such as GL).
+pub(crate) fn read_depth(
+    gpu: &WGPU,
+    texture: &wgpu::Texture,
+    origin: [u32; 2],
+    size: [u32; 2],
+) -> Option<DepthQuery> {
+    let format = texture.format();
+    let texel_size = match format {
+        wgpu::TextureFormat::Depth16Unorm => 2,
+        wgpu::TextureFormat::Depth32Float | wgpu::TextureFormat::Depth32FloatStencil8 => 4,
+        _ => return None,
+    };
+    let copies_depth = gpu
+        .adapter()
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::DEPTH_TEXTURE_AND_BUFFER_COPIES);
+    if !copies_depth {
+        return None;
+    }
+    let tex_size = texture.size();
+    if size[0] == 0
+        || size[1] == 0
+        || origin[0] + size[0] > tex_size.width
+        || origin[1] + size[1] > tex_size.height
+    {
+        return None;
+    }
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let bytes_per_row = (size[0] * texel_size).div_ceil(align) * align;
+    let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("depth:readback"),
+        size: (bytes_per_row * size[1]) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = gpu
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("depth:readback"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::DepthOnly,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        },
+    );
+    gpu.queue().submit(Some(encoder.finish()));
+    let (sender, ready) = mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    Some(DepthQuery {
+        buffer,
+        ready,
+        format,
+        origin,
+        size,
+        bytes_per_row,
+        result: None,
+    })
+}
+
+/// Owns an object ID texture and the depth texture used while drawing into it.
+pub struct PickPass {
+    id_texture: wgpu::Texture,
+    id_texture_view: wgpu::TextureView,
+    depth_texture: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl PickPass {
+    /// Creates a [`PickPass`] with `width` by `height` ID and depth
+    /// textures.  `depth_format` should match the depth format of the
+    /// renderers drawing into it (`None` means no depth testing).
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        let (id_texture, id_texture_view) = Self::create_texture(
+            gpu,
+            "pick:ids",
+            width,
+            height,
+            ID_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth_texture = depth_format
+            .into()
+            .map(|format| Self::create_depth_texture(gpu, width, height, format));
+        Self {
+            id_texture,
+            id_texture_view,
+            depth_texture,
+        }
+    }
+    fn create_texture(
+        gpu: &WGPU,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_depth_texture(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        Self::create_texture(
+            gpu,
+            "pick:depth",
+            width,
+            height,
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    }
+    /// Re-creates the ID and depth textures at a new size.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        let format = self.depth_format();
+        *self = Self::new(gpu, width, height, format);
+    }
+    /// Changes the depth format used while drawing IDs, re-creating the depth texture.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        let size = self.id_texture.size();
+        self.depth_texture = depth_format
+            .map(|format| Self::create_depth_texture(gpu, size.width, size.height, format));
+    }
+    /// Returns the depth format used while drawing IDs, if any.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_texture.as_ref().map(|(t, _)| t.format())
+    }
+    /// Returns the ID texture.
+    pub fn id_texture(&self) -> &wgpu::Texture {
+        &self.id_texture
+    }
+    /// Begins a render pass which clears the ID and depth textures;
+    /// draw into it with the renderers' `render_pick` methods.
+    pub fn begin_render_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("pick:ids");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("pick:ids"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.id_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth_texture.as_ref().map(|(_, view)| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            ..Default::default()
+        })
+    }
+    /// Starts reading back the ID at pixel `(x, y)` of the ID texture
+    /// (from the top left), as of the most recently submitted ID
+    /// pass.  Returns `None` if the pixel is out of bounds.
+    pub fn pick(&self, gpu: &WGPU, x: u32, y: u32) -> Option<PickQuery> {
+        let size = self.id_texture.size();
+        if x >= size.width || y >= size.height {
+            return None;
+        }
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pick:readback"),
+            size: std::mem::size_of::<[u32; 4]>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("pick:readback"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu.queue().submit(Some(encoder.finish()));
+        let (sender, ready) = mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                // The query may have been dropped already, which is fine
+                let _ = sender.send(result);
+            });
+        Some(PickQuery {
+            buffer,
+            ready,
+            result: None,
+        })
+    }
+}
@@ -0,0 +1,1153 @@
+//! Fullscreen post-processing effects which run on the rendered
+//! scene before the final color-geometry step
+//! ([`crate::colorgeo::ColorGeo`]).
+//!
+//! [`PostProcess`] owns a scratch texture the same size as the color
+//! target and ping-pongs between the two, running each enabled effect
+//! in order.  Effects are fragment shaders appended to a shared
+//! prelude (`postprocess.wgsl`) which binds an effect's parameters,
+//! the previous stage's color, the depth texture, and the velocity
+//! texture (see [`crate::velocity`]).  Effects' pipelines are only
+//! created once they're first enabled.  Bloom (see [`crate::bloom`])
+//! runs between motion blur and the color-blindness filter.
+//!
+//! Besides the built-in effects, any number of custom effects can be
+//! added with [`PostProcess::push_effect`]; they run in the order
+//! they were added, after the built-in ones.  A custom effect's
+//! shader is appended to the same prelude, so it defines `fs_main`
+//! and reads its parameters from `u_params` (set with
+//! [`PostProcess::set_effect_params`]).  A few ready-made ones are
+//! provided: [`VIGNETTE`], [`CHROMATIC_ABERRATION`], [`CRT`], and
+//! [`COLOR_GRADING`].
+
+use std::borrow::Cow;
+
+use crate::{
+    bloom::{BloomChain, BloomParams},
+    capture,
+    meshes::Camera3D,
+    WGPU,
+};
+
+/// How many `vec4<f32>` parameters each effect gets.
+const PARAM_COUNT: usize = 32;
+type Params = [[f32; 4]; PARAM_COUNT];
+/// How many built-in effects there are.
+const STAGE_COUNT: usize = 5;
+/// Bloom runs just before this stage.
+const BLOOM_STAGE: usize = 3;
+
+/// A vignette darkening the image towards its corners.
+/// `u_params[0]`: x = strength (0 to 1), y = the radius where
+/// darkening starts, z = the radius where it's strongest (as
+/// fractions of the distance from the center to a corner), and w = roundness
+/// (0 follows the screen's shape, 1 is circular).
+pub const VIGNETTE: &str = include_str!("vignette.wgsl");
+/// Chromatic aberration, which pulls the red and blue channels apart
+/// towards the edges of the image.  `u_params[0]`: x = how far apart
+/// the channels are at the image's corners, as a fraction of its size.
+pub const CHROMATIC_ABERRATION: &str = include_str!("chromatic_aberration.wgsl");
+/// An old CRT monitor: a bulging screen with scanlines and an
+/// aperture grille.  `u_params[0]`: x = curvature, y = scanline
+/// strength (0 to 1), z = grille strength (0 to 1), w = scanline
+/// count (or the image's height in pixels if it's 0).
+pub const CRT: &str = include_str!("crt.wgsl");
+/// Color grading with lift, gamma, and gain, then saturation, each
+/// given as an offset so that zero leaves colors as they are.
+/// `u_params[0]`: lift added to the shadows (rgb); `u_params[1]`:
+/// gamma, raising colors to `1/(1+gamma)` (rgb); `u_params[2]`: gain,
+/// multiplying colors by `1+gain` (rgb); `u_params[3]`: x =
+/// saturation, scaling it by `1+saturation`.
+pub const COLOR_GRADING: &str = include_str!("color_grading.wgsl");
+
+/// An opaque identifier for a custom effect added with [`PostProcess::push_effect`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct EffectId(usize);
+
+struct Effect {
+    label: String,
+    pipeline: wgpu::RenderPipeline,
+    params_buf: wgpu::Buffer,
+    // Index 0 reads the color texture, index 1 the scratch texture
+    bind_groups: [wgpu::BindGroup; 2],
+}
+
+// An effect added with `PostProcess::push_effect`
+struct CustomEffect {
+    id: EffectId,
+    label: String,
+    source: String,
+    params: Params,
+    enabled: bool,
+    // Created on demand, like the built-in effects
+    effect: Option<Effect>,
+    // Set when the parameters need to be uploaded
+    dirty: bool,
+}
+
+/// Screen-space ambient occlusion parameters.  Occlusion is
+/// estimated from the depth texture, so it only makes sense for
+/// scenes drawn with a perspective [`Camera3D`] (see
+/// [`PostProcess::set_camera`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ssao {
+    /// The world-space radius around each point which is checked for occluders
+    pub radius: f32,
+    /// How strongly occlusion darkens the scene, from 0.0 (not at all) to 1.0
+    pub intensity: f32,
+    /// How many depth samples are taken per pixel (at most [`Ssao::MAX_SAMPLES`]);
+    /// more samples are slower but less noisy
+    pub samples: u32,
+    /// A depth bias which avoids surfaces occluding themselves
+    pub bias: f32,
+}
+
+impl Ssao {
+    /// The largest supported number of samples per pixel.
+    pub const MAX_SAMPLES: u32 = 64;
+    fn params(&self, camera: &Camera3D) -> Params {
+        let mut params = [[0.0; 4]; PARAM_COUNT];
+        params[0] = [
+            self.radius,
+            self.intensity.clamp(0.0, 1.0),
+            self.samples.clamp(1, Self::MAX_SAMPLES) as f32,
+            self.bias,
+        ];
+        let proj = camera.projection();
+        params[1..5].copy_from_slice(&proj.cols.map(|c| *c.as_array()));
+        params[5..9].copy_from_slice(&proj.inversed().cols.map(|c| *c.as_array()));
+        params
+    }
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            intensity: 1.0,
+            samples: 16,
+            bias: 0.025,
+        }
+    }
+}
+
+/// What a [`DepthOfField`] effect keeps in focus.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    /// Points `distance` away from the camera are in focus, staying
+    /// sharp within `range` in front of or behind that distance and
+    /// becoming fully blurred at twice `range`.  This uses the depth
+    /// texture and the camera (see [`PostProcess::set_camera`]).
+    Depth { distance: f32, range: f32 },
+    /// A horizontal band centered at height `center` (from 0.0 at the
+    /// top of the screen to 1.0 at the bottom) is in focus, staying
+    /// sharp within `range` above and below it.  This gives
+    /// tilt-shift "miniature" looks and works without depth.
+    ScreenBand { center: f32, range: f32 },
+}
+
+/// Depth of field parameters: pixels out of focus are blurred over a
+/// disc whose radius grows up to `aperture` pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthOfField {
+    /// What's in focus
+    pub focus: Focus,
+    /// The largest blur radius, in pixels
+    pub aperture: f32,
+    /// How many color samples are taken for blurred pixels (at most
+    /// [`DepthOfField::MAX_SAMPLES`])
+    pub samples: u32,
+}
+
+impl DepthOfField {
+    /// The largest supported number of samples per pixel.
+    pub const MAX_SAMPLES: u32 = 64;
+    fn params(&self, camera: &Camera3D) -> Params {
+        let mut params = [[0.0; 4]; PARAM_COUNT];
+        let (mode, center, range) = match self.focus {
+            Focus::Depth { distance, range } => (0.0, distance, range),
+            Focus::ScreenBand { center, range } => (1.0, center, range),
+        };
+        params[0] = [mode, center, range, self.aperture.max(0.0)];
+        params[1][0] = self.samples.clamp(1, Self::MAX_SAMPLES) as f32;
+        let inv_proj = camera.projection().inversed();
+        params[2..6].copy_from_slice(&inv_proj.cols.map(|c| *c.as_array()));
+        params
+    }
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            focus: Focus::Depth {
+                distance: 10.0,
+                range: 5.0,
+            },
+            aperture: 8.0,
+            samples: 24,
+        }
+    }
+}
+
+/// Motion blur parameters.  Each pixel is blurred along its velocity,
+/// so this needs a velocity texture (see [`PostProcess::set_velocity`]);
+/// only meshes drawn into it are blurred.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionBlur {
+    /// How much of each frame's motion is blurred over, like a
+    /// camera's shutter time as a fraction of the frame time
+    pub strength: f32,
+    /// How many color samples are taken along each pixel's velocity
+    /// (at most [`MotionBlur::MAX_SAMPLES`])
+    pub samples: u32,
+    /// The longest blur, in pixels
+    pub max_length: f32,
+}
+
+impl MotionBlur {
+    /// The largest supported number of samples per pixel.
+    pub const MAX_SAMPLES: u32 = 32;
+    fn params(&self) -> Params {
+        let mut params = [[0.0; 4]; PARAM_COUNT];
+        params[0] = [
+            self.strength.max(0.0),
+            self.samples.clamp(1, Self::MAX_SAMPLES) as f32,
+            self.max_length.max(0.0),
+            0.0,
+        ];
+        params
+    }
+}
+
+impl Default for MotionBlur {
+    fn default() -> Self {
+        Self {
+            strength: 0.5,
+            samples: 8,
+            max_length: 32.0,
+        }
+    }
+}
+
+/// Bloom parameters.  Pixels brighter than the threshold glow, as do
+/// sprites with emissive layers (see
+/// [`crate::sprites::SpriteRenderer::set_emissive`]) when drawn by
+/// the built-in renderer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    /// How bright (in the largest of a pixel's color channels) a
+    /// pixel must be to glow
+    pub threshold: f32,
+    /// How gradually pixels start glowing below the threshold, as a
+    /// fraction of it; 0 is a hard cutoff
+    pub knee: f32,
+    /// How strongly the glow is added onto the scene; since every
+    /// level adds its own glow, more levels also make brighter glows
+    pub intensity: f32,
+    /// How far each level's glow is spread while upsampling, in texels
+    pub radius: f32,
+    /// How many times the image is halved in size while blurring (at
+    /// most [`Bloom::MAX_LEVELS`]); more levels make wider glows
+    pub levels: u32,
+}
+
+impl Bloom {
+    /// The largest supported number of levels.
+    pub const MAX_LEVELS: u32 = 8;
+    fn params(&self) -> BloomParams {
+        [[
+            self.threshold.max(0.0),
+            self.knee.clamp(0.0, 1.0),
+            self.intensity.max(0.0),
+            self.radius.max(0.0),
+        ]]
+    }
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            knee: 0.5,
+            intensity: 1.0,
+            radius: 1.0,
+            levels: 5,
+        }
+    }
+}
+
+/// The threshold pattern used for ordered dithering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DitherPattern {
+    /// A 2x2 Bayer matrix (very visible, chunky pattern)
+    Bayer2,
+    /// A 4x4 Bayer matrix
+    Bayer4,
+    /// An 8x8 Bayer matrix
+    Bayer8,
+    /// Interleaved gradient noise, a cheap approximation of blue
+    /// noise which avoids the cross-hatched look of Bayer matrices
+    GradientNoise,
+}
+
+/// Ordered dithering parameters, for retro limited-palette looks.
+/// Colors are either quantized to a number of levels per channel or
+/// snapped to the nearest color of a fixed palette.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dither {
+    /// The threshold pattern
+    pub pattern: DitherPattern,
+    /// How many values each color channel can take (e.g. 2 for
+    /// 1-bit color channels).  Ignored if `palette` is not empty.
+    pub levels: u32,
+    /// A fixed palette of up to [`Dither::MAX_PALETTE`] RGB colors;
+    /// if this is empty, `levels` is used instead.
+    pub palette: Vec<[f32; 3]>,
+}
+
+impl Dither {
+    /// The largest supported palette size.
+    pub const MAX_PALETTE: usize = 16;
+    /// Dithering which quantizes each color channel to `levels` values.
+    pub fn levels(pattern: DitherPattern, levels: u32) -> Self {
+        Self {
+            pattern,
+            levels,
+            palette: vec![],
+        }
+    }
+    /// Dithering onto a fixed palette (truncated to [`Dither::MAX_PALETTE`] colors).
+    pub fn palette(pattern: DitherPattern, palette: &[[f32; 3]]) -> Self {
+        Self {
+            pattern,
+            levels: 2,
+            palette: palette[..palette.len().min(Self::MAX_PALETTE)].to_vec(),
+        }
+    }
+    fn params(&self) -> Params {
+        let mut params = [[0.0; 4]; PARAM_COUNT];
+        let palette = &self.palette[..self.palette.len().min(Self::MAX_PALETTE)];
+        // Dither by about one quantization step
+        let steps = if palette.is_empty() {
+            self.levels
+        } else {
+            palette.len() as u32
+        };
+        let spread = 1.0 / (steps.max(2) - 1) as f32;
+        params[0] = [
+            self.levels as f32,
+            self.pattern as u32 as f32,
+            palette.len() as f32,
+            spread,
+        ];
+        for (param, color) in params[1..].iter_mut().zip(palette) {
+            *param = [color[0], color[1], color[2], 1.0];
+        }
+        params
+    }
+}
+
+/// A kind of color vision deficiency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDeficiency {
+    /// Missing long-wavelength (red) cones
+    Protanopia,
+    /// Missing medium-wavelength (green) cones
+    Deuteranopia,
+    /// Missing short-wavelength (blue) cones
+    Tritanopia,
+}
+
+impl ColorDeficiency {
+    // Machado et al. 2009's simulation matrices at full severity
+    fn simulation(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+    // Where daltonization moves the color information a viewer can't
+    // see: red-green losses into blue, blue losses into red and green
+    fn error_shift(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia | Self::Deuteranopia => {
+                [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]
+            }
+            Self::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+        }
+    }
+}
+
+/// What a [`ColorVision`] filter does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorVisionMode {
+    /// Shows the image as a viewer with the deficiency would see it,
+    /// e.g. for checking that a game's colors stay distinguishable
+    Simulate,
+    /// Daltonizes the image, shifting the colors a viewer with the
+    /// deficiency can't tell apart towards ones they can
+    Compensate,
+}
+
+/// Color-blindness accessibility filter parameters.  The filter is a
+/// single color matrix, so switching it at runtime is cheap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorVision {
+    pub deficiency: ColorDeficiency,
+    pub mode: ColorVisionMode,
+    /// How strongly the filter is applied, from 0 (not at all) to 1 (fully)
+    pub strength: f32,
+}
+
+impl ColorVision {
+    /// A full-strength filter simulating the given deficiency.
+    pub fn simulate(deficiency: ColorDeficiency) -> Self {
+        Self {
+            deficiency,
+            mode: ColorVisionMode::Simulate,
+            strength: 1.0,
+        }
+    }
+    /// A full-strength filter compensating for the given deficiency.
+    pub fn compensate(deficiency: ColorDeficiency) -> Self {
+        Self {
+            deficiency,
+            mode: ColorVisionMode::Compensate,
+            strength: 1.0,
+        }
+    }
+    /// Returns the filter's color matrix, as rows applied to linear RGB colors.
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        let sim = self.deficiency.simulation();
+        let identity = |r: usize, c: usize| if r == c { 1.0 } else { 0.0 };
+        let full: [[f32; 3]; 3] = match self.mode {
+            ColorVisionMode::Simulate => sim,
+            // c + shift * (c - sim * c)
+            ColorVisionMode::Compensate => {
+                let shift = self.deficiency.error_shift();
+                std::array::from_fn(|r| {
+                    std::array::from_fn(|c| {
+                        identity(r, c)
+                            + (0..3)
+                                .map(|k| shift[r][k] * (identity(k, c) - sim[k][c]))
+                                .sum::<f32>()
+                    })
+                })
+            }
+        };
+        let t = self.strength.clamp(0.0, 1.0);
+        std::array::from_fn(|r| {
+            std::array::from_fn(|c| identity(r, c) + (full[r][c] - identity(r, c)) * t)
+        })
+    }
+    fn params(&self) -> Params {
+        let mut params = [[0.0; 4]; PARAM_COUNT];
+        for (param, row) in params.iter_mut().zip(self.matrix()) {
+            *param = [row[0], row[1], row[2], 0.0];
+        }
+        params
+    }
+}
+
+/// A chain of fullscreen effects applied to the rendered image.
+pub struct PostProcess {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    color_format: wgpu::TextureFormat,
+    color_view: wgpu::TextureView,
+    scratch_texture: wgpu::Texture,
+    scratch_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    velocity_view: wgpu::TextureView,
+    // Extra light for bloom, if anything glows
+    glow_view: Option<wgpu::TextureView>,
+    // The camera used to reconstruct positions from depth
+    camera: Camera3D,
+    ssao: Option<Ssao>,
+    depth_of_field: Option<DepthOfField>,
+    motion_blur: Option<MotionBlur>,
+    bloom: Option<Bloom>,
+    color_vision: Option<ColorVision>,
+    dither: Option<Dither>,
+    // Created the first time bloom is enabled
+    bloom_chain: Option<BloomChain>,
+    // One per built-in stage, in the order they run (see `PostProcess::stages`)
+    effects: [Option<Effect>; STAGE_COUNT],
+    // Run in order after the built-in stages
+    custom: Vec<CustomEffect>,
+    next_custom_id: usize,
+    // Set when parameters change and need to be uploaded
+    dirty: bool,
+}
+
+impl PostProcess {
+    /// Creates a [`PostProcess`] stage operating on the given color
+    /// texture (which must support `RENDER_ATTACHMENT`,
+    /// `TEXTURE_BINDING`, and `COPY_DST`) and depth texture, if any.
+    pub fn new(gpu: &WGPU, color: &wgpu::Texture, depth: Option<&wgpu::Texture>) -> Self {
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("postprocess:bgl"),
+                    entries: &[
+                        // Effect parameters
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: wgpu::BufferSize::new(
+                                    std::mem::size_of::<Params>() as u64,
+                                ),
+                            },
+                            count: None,
+                        },
+                        // The previous stage's color
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        // The depth texture
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        // The velocity texture
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("postprocess:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("postprocess:color_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let (scratch_texture, scratch_view) = Self::create_scratch(gpu, color);
+        Self {
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            color_format: color.format(),
+            color_view: color.create_view(&wgpu::TextureViewDescriptor::default()),
+            scratch_texture,
+            scratch_view,
+            depth_view: Self::create_depth_view(gpu, depth),
+            velocity_view: Self::create_velocity_view(gpu, None),
+            glow_view: None,
+            camera: Camera3D {
+                translation: [0.0; 3],
+                near: 0.1,
+                far: 100.0,
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                aspect: 4.0 / 3.0,
+                fov: std::f32::consts::FRAC_PI_2,
+            },
+            ssao: None,
+            depth_of_field: None,
+            motion_blur: None,
+            bloom: None,
+            color_vision: None,
+            dither: None,
+            bloom_chain: None,
+            effects: Default::default(),
+            custom: Vec::new(),
+            next_custom_id: 0,
+            dirty: false,
+        }
+    }
+    fn create_scratch(gpu: &WGPU, color: &wgpu::Texture) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("postprocess:scratch"),
+            size: color.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: color.format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_depth_view(gpu: &WGPU, depth: Option<&wgpu::Texture>) -> wgpu::TextureView {
+        match depth {
+            Some(depth) => depth.create_view(&wgpu::TextureViewDescriptor {
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            }),
+            // Effects always get a depth texture; without one, give
+            // them a tiny placeholder
+            None => gpu
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("postprocess:placeholder_depth"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth32Float,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+    fn create_velocity_view(gpu: &WGPU, velocity: Option<&wgpu::Texture>) -> wgpu::TextureView {
+        match velocity {
+            Some(velocity) => velocity.create_view(&wgpu::TextureViewDescriptor::default()),
+            // A zero-initialized placeholder means nothing moves
+            None => gpu
+                .device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("postprocess:placeholder_velocity"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: crate::velocity::VELOCITY_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+    /// Replaces the color and depth textures this stage works on (for
+    /// example, because the render size or depth format has changed).
+    pub fn resize(&mut self, gpu: &WGPU, color: &wgpu::Texture, depth: Option<&wgpu::Texture>) {
+        self.color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+        (self.scratch_texture, self.scratch_view) = Self::create_scratch(gpu, color);
+        self.depth_view = Self::create_depth_view(gpu, depth);
+        if color.format() != self.color_format {
+            // pipelines target the old format; they'll be recreated on demand
+            self.color_format = color.format();
+            self.effects = Default::default();
+            for custom in self.custom.iter_mut() {
+                custom.effect = None;
+            }
+            self.bloom_chain = None;
+        }
+        self.rebind(gpu);
+    }
+    /// Sets the velocity texture read by motion blur (see
+    /// [`crate::velocity::VelocityPass`]), which should be the same
+    /// size as the color texture; `None` means nothing moves.
+    pub fn set_velocity(&mut self, gpu: &WGPU, velocity: Option<&wgpu::Texture>) {
+        self.velocity_view = Self::create_velocity_view(gpu, velocity);
+        self.rebind(gpu);
+    }
+    /// Sets a texture whose colors are added to the light bloom
+    /// spreads around (see [`crate::bloom::GlowPass`]), which should
+    /// be the same size as the color texture; `None` means only
+    /// pixels over the bloom threshold glow.
+    pub fn set_glow(&mut self, gpu: &WGPU, glow: Option<&wgpu::Texture>) {
+        self.glow_view = glow.map(|glow| glow.create_view(&wgpu::TextureViewDescriptor::default()));
+        self.rebind(gpu);
+    }
+    // Re-creates existing effects' bind groups after a texture changed
+    fn rebind(&mut self, gpu: &WGPU) {
+        let custom = self.custom.iter_mut().filter_map(|c| c.effect.as_mut());
+        for effect in self.effects.iter_mut().flatten().chain(custom) {
+            effect.bind_groups = Self::create_bind_groups(
+                gpu,
+                &self.bind_group_layout,
+                &self.sampler,
+                &effect.params_buf,
+                [&self.color_view, &self.scratch_view],
+                [&self.depth_view, &self.velocity_view],
+            );
+        }
+        self.resize_bloom(gpu);
+    }
+    // Re-creates the bloom chain's levels for the current textures and parameters
+    fn resize_bloom(&mut self, gpu: &WGPU) {
+        let (Some(chain), Some(bloom)) = (self.bloom_chain.as_mut(), self.bloom.as_ref()) else {
+            return;
+        };
+        let size = self.scratch_texture.size();
+        chain.resize(
+            gpu,
+            (size.width, size.height),
+            bloom.levels.min(Bloom::MAX_LEVELS),
+            [&self.color_view, &self.scratch_view],
+            self.glow_view.as_ref(),
+        );
+        self.dirty = true;
+    }
+    fn create_bind_groups(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params_buf: &wgpu::Buffer,
+        inputs: [&wgpu::TextureView; 2],
+        [depth_view, velocity_view]: [&wgpu::TextureView; 2],
+    ) -> [wgpu::BindGroup; 2] {
+        inputs.map(|input| {
+            gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("postprocess:bg"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(velocity_view),
+                    },
+                ],
+            })
+        })
+    }
+    fn create_effect(&self, gpu: &WGPU, label: &str, fragment_source: &str) -> Effect {
+        let source = format!("{}\n{}", include_str!("postprocess.wgsl"), fragment_source);
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+            });
+        let pipeline = gpu
+            .device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(self.color_format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        let params_buf = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_groups = Self::create_bind_groups(
+            gpu,
+            &self.bind_group_layout,
+            &self.sampler,
+            &params_buf,
+            [&self.color_view, &self.scratch_view],
+            [&self.depth_view, &self.velocity_view],
+        );
+        Effect {
+            label: label.to_string(),
+            pipeline,
+            params_buf,
+            bind_groups,
+        }
+    }
+    /// Sets the camera used to reconstruct 3D positions from the
+    /// depth texture; this should match the camera the scene's meshes
+    /// are drawn with.
+    pub fn set_camera(&mut self, camera: Camera3D) {
+        self.camera = camera;
+        self.dirty = true;
+    }
+    /// Enables screen-space ambient occlusion with the given
+    /// parameters, or disables it if `ssao` is `None`.  Ambient
+    /// occlusion runs first, darkening the scene's colors.
+    pub fn set_ssao(&mut self, ssao: Option<Ssao>) {
+        self.ssao = ssao;
+        self.dirty = true;
+    }
+    /// Returns the current ambient occlusion parameters, if it's enabled.
+    pub fn ssao(&self) -> Option<&Ssao> {
+        self.ssao.as_ref()
+    }
+    /// Enables depth of field with the given parameters, or disables
+    /// it if `depth_of_field` is `None`.  Depth of field runs after
+    /// ambient occlusion.
+    pub fn set_depth_of_field(&mut self, depth_of_field: Option<DepthOfField>) {
+        self.depth_of_field = depth_of_field;
+        self.dirty = true;
+    }
+    /// Returns the current depth of field parameters, if it's enabled.
+    pub fn depth_of_field(&self) -> Option<&DepthOfField> {
+        self.depth_of_field.as_ref()
+    }
+    /// Enables motion blur with the given parameters, or disables it
+    /// if `motion_blur` is `None`.  Motion blur runs after depth of
+    /// field and before dithering.
+    pub fn set_motion_blur(&mut self, motion_blur: Option<MotionBlur>) {
+        self.motion_blur = motion_blur;
+        self.dirty = true;
+    }
+    /// Returns the current motion blur parameters, if it's enabled.
+    pub fn motion_blur(&self) -> Option<&MotionBlur> {
+        self.motion_blur.as_ref()
+    }
+    /// Enables bloom with the given parameters, or disables it if
+    /// `bloom` is `None`.  Bloom runs after motion blur and before
+    /// the color-blindness filter.
+    pub fn set_bloom(&mut self, bloom: Option<Bloom>) {
+        let levels_changed = bloom.map(|b| b.levels) != self.bloom.map(|b| b.levels);
+        self.bloom = bloom;
+        if levels_changed {
+            // The chain gets new levels in prepare
+            if let Some(chain) = self.bloom_chain.as_mut() {
+                chain.clear_levels();
+            }
+        }
+        self.dirty = true;
+    }
+    /// Enables bloom with the given parameters.
+    pub fn enable_bloom(&mut self, bloom: Bloom) {
+        self.set_bloom(Some(bloom));
+    }
+    /// Disables bloom.
+    pub fn disable_bloom(&mut self) {
+        self.set_bloom(None);
+    }
+    /// Returns the current bloom parameters, if bloom is enabled.
+    pub fn bloom(&self) -> Option<&Bloom> {
+        self.bloom.as_ref()
+    }
+    /// Enables a color-blindness filter with the given parameters, or
+    /// disables it if `color_vision` is `None`.  The filter runs
+    /// after bloom and before dithering, so a dithering palette is
+    /// still respected.
+    pub fn set_color_vision(&mut self, color_vision: Option<ColorVision>) {
+        self.color_vision = color_vision;
+        self.dirty = true;
+    }
+    /// Returns the current color-blindness filter, if one is enabled.
+    pub fn color_vision(&self) -> Option<&ColorVision> {
+        self.color_vision.as_ref()
+    }
+    /// Enables ordered dithering with the given parameters, or
+    /// disables it if `dither` is `None`.  Dithering runs last of the
+    /// built-in effects, since it's meant to produce the final limited-color image.
+    pub fn set_dither(&mut self, dither: Option<Dither>) {
+        self.dither = dither;
+        self.dirty = true;
+    }
+    /// Returns the current dithering parameters, if dithering is enabled.
+    pub fn dither(&self) -> Option<&Dither> {
+        self.dither.as_ref()
+    }
+    /// Adds a custom effect which runs after the built-in effects and
+    /// any custom effects added before it.  `shader` is WGSL source
+    /// defining `fs_main(in:PostVertexOutput) -> @location(0)
+    /// vec4<f32>`, which is compiled after the declarations in
+    /// `postprocess.wgsl`: `u_params`, `t_color` and `s_color` (the
+    /// previous stage's output), `t_depth`, and `t_velocity`.  Its
+    /// parameters start out as zeroes.
+    ///
+    /// The shader is compiled the next time effects are rendered.
+    pub fn push_effect(&mut self, label: &str, shader: &str) -> EffectId {
+        let id = EffectId(self.next_custom_id);
+        self.next_custom_id += 1;
+        self.custom.push(CustomEffect {
+            id,
+            label: label.to_string(),
+            source: shader.to_string(),
+            params: [[0.0; 4]; PARAM_COUNT],
+            enabled: true,
+            effect: None,
+            dirty: true,
+        });
+        id
+    }
+    fn custom(&self, id: EffectId) -> &CustomEffect {
+        self.custom
+            .iter()
+            .find(|c| c.id == id)
+            .expect("No such post-processing effect")
+    }
+    fn custom_mut(&mut self, id: EffectId) -> &mut CustomEffect {
+        self.custom
+            .iter_mut()
+            .find(|c| c.id == id)
+            .expect("No such post-processing effect")
+    }
+    /// Sets the first few of a custom effect's parameters (its
+    /// `u_params` array), leaving the rest zero.  Panics if `id` was
+    /// removed or there are more than 32 parameters.
+    pub fn set_effect_params(&mut self, id: EffectId, params: &[[f32; 4]]) {
+        assert!(params.len() <= PARAM_COUNT, "Too many effect parameters");
+        let custom = self.custom_mut(id);
+        custom.params = [[0.0; 4]; PARAM_COUNT];
+        custom.params[..params.len()].copy_from_slice(params);
+        custom.dirty = true;
+    }
+    /// Returns a custom effect's parameters.  Panics if `id` was removed.
+    pub fn effect_params(&self, id: EffectId) -> &[[f32; 4]] {
+        &self.custom(id).params
+    }
+    /// Turns a custom effect on or off without removing it.  Panics
+    /// if `id` was removed.
+    pub fn set_effect_enabled(&mut self, id: EffectId, enabled: bool) {
+        self.custom_mut(id).enabled = enabled;
+    }
+    /// Returns whether a custom effect is on.  Panics if `id` was removed.
+    pub fn effect_enabled(&self, id: EffectId) -> bool {
+        self.custom(id).enabled
+    }
+    /// Removes a custom effect, returning false if it was already removed.
+    pub fn remove_effect(&mut self, id: EffectId) -> bool {
+        let count = self.custom.len();
+        self.custom.retain(|c| c.id != id);
+        self.custom.len() != count
+    }
+    /// Removes every custom effect.
+    pub fn clear_effects(&mut self) {
+        self.custom.clear();
+    }
+    /// Returns the custom effects in the order they run.
+    pub fn effects(&self) -> impl Iterator<Item = EffectId> + '_ {
+        self.custom.iter().map(|c| c.id)
+    }
+    /// Returns true if any effect is enabled.
+    pub fn is_active(&self) -> bool {
+        self.bloom.is_some()
+            || self.stages().iter().any(|stage| stage.0.is_some())
+            || self.custom.iter().any(|c| c.enabled)
+    }
+    /// The parameters (if enabled), label, and shader of each built-in stage in order.
+    fn stages(&self) -> [(Option<Params>, &'static str, &'static str); STAGE_COUNT] {
+        [
+            (
+                self.ssao.as_ref().map(|ssao| ssao.params(&self.camera)),
+                "postprocess:ssao",
+                include_str!("ssao.wgsl"),
+            ),
+            (
+                self.depth_of_field
+                    .as_ref()
+                    .map(|dof| dof.params(&self.camera)),
+                "postprocess:depth_of_field",
+                include_str!("dof.wgsl"),
+            ),
+            (
+                self.motion_blur.as_ref().map(MotionBlur::params),
+                "postprocess:motion_blur",
+                include_str!("motion_blur.wgsl"),
+            ),
+            (
+                self.color_vision.as_ref().map(ColorVision::params),
+                "postprocess:color_vision",
+                include_str!("color_vision.wgsl"),
+            ),
+            (
+                self.dither.as_ref().map(Dither::params),
+                "postprocess:dither",
+                include_str!("dither.wgsl"),
+            ),
+        ]
+    }
+    /// Creates any newly enabled effects and uploads changed parameters.
+    fn prepare(&mut self, gpu: &WGPU) {
+        if self.bloom.is_some() {
+            if self.bloom_chain.is_none() {
+                self.bloom_chain = Some(BloomChain::new(gpu, self.color_format));
+            }
+            if self.bloom_chain.as_ref().unwrap().level_count() == 0 {
+                self.resize_bloom(gpu);
+            }
+            if self.dirty {
+                let params = self.bloom.unwrap().params();
+                self.bloom_chain.as_ref().unwrap().set_params(gpu, &params);
+            }
+        }
+        for (slot, (params, label, source)) in self.stages().into_iter().enumerate() {
+            let Some(params) = params else {
+                continue;
+            };
+            let created = self.effects[slot].is_none();
+            if created {
+                self.effects[slot] = Some(self.create_effect(gpu, label, source));
+            }
+            if created || self.dirty {
+                gpu.queue().write_buffer(
+                    &self.effects[slot].as_ref().unwrap().params_buf,
+                    0,
+                    bytemuck::cast_slice(&params),
+                );
+            }
+        }
+        for index in 0..self.custom.len() {
+            if !self.custom[index].enabled {
+                continue;
+            }
+            if self.custom[index].effect.is_none() {
+                let custom = &self.custom[index];
+                let effect = self.create_effect(gpu, &custom.label, &custom.source);
+                self.custom[index].effect = Some(effect);
+                self.custom[index].dirty = true;
+            }
+            let custom = &mut self.custom[index];
+            if custom.dirty {
+                gpu.queue().write_buffer(
+                    &custom.effect.as_ref().unwrap().params_buf,
+                    0,
+                    bytemuck::cast_slice(&custom.params),
+                );
+                custom.dirty = false;
+            }
+        }
+        self.dirty = false;
+    }
+    // Draws one effect reading from the texture `current` refers to
+    // into the other one
+    fn draw_effect(&self, encoder: &mut wgpu::CommandEncoder, effect: &Effect, current: usize) {
+        let target = if current == 0 {
+            &self.scratch_view
+        } else {
+            &self.color_view
+        };
+        capture::begin_pass(&format!("postprocess:{}", effect.label));
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("postprocess"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        capture::set_pipeline(&mut rpass, &effect.pipeline, &effect.label);
+        capture::set_bind_group(
+            &mut rpass,
+            0,
+            &effect.bind_groups[current],
+            &[],
+            "postprocess:bind_groups",
+        );
+        capture::draw(&mut rpass, 0..6, 0..1);
+    }
+    /// Runs the enabled effects in order on `color`, which must be
+    /// the color texture this stage was created or last resized with.
+    /// The result ends up back in `color`.
+    pub fn render(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        color: &wgpu::Texture,
+    ) {
+        self.prepare(gpu);
+        let enabled = self.stages().map(|stage| stage.0.is_some());
+        // 0 means the latest result is in the color texture, 1 the scratch texture
+        let mut current = 0;
+        for (slot, (effect, enabled)) in self.effects.iter().zip(enabled).enumerate() {
+            let target = if current == 0 {
+                &self.scratch_view
+            } else {
+                &self.color_view
+            };
+            if slot == BLOOM_STAGE {
+                if let Some(chain) = self
+                    .bloom_chain
+                    .as_ref()
+                    .filter(|chain| self.bloom.is_some() && chain.level_count() > 0)
+                {
+                    chain.render(encoder, current, target);
+                    current = 1 - current;
+                }
+            }
+            let Some(effect) = effect.as_ref().filter(|_| enabled) else {
+                continue;
+            };
+            self.draw_effect(encoder, effect, current);
+            current = 1 - current;
+        }
+        for custom in self.custom.iter().filter(|c| c.enabled) {
+            self.draw_effect(encoder, custom.effect.as_ref().unwrap(), current);
+            current = 1 - current;
+        }
+        if current == 1 {
+            capture::copy("postprocess:scratch to color");
+            encoder.copy_texture_to_texture(
+                self.scratch_texture.as_image_copy(),
+                color.as_image_copy(),
+                color.size(),
+            );
+        }
+    }
+}
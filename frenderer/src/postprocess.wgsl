@@ -0,0 +1,49 @@
+// Shared declarations for fullscreen post-processing effects.  Each
+// effect's fragment shader is appended to this file and must define
+// `fs_main(in:PostVertexOutput) -> @location(0) vec4<f32>`.
+var<private> POST_VERTICES:array<vec4<f32>,6> = array<vec4<f32>,6>(
+    vec4<f32>(-1., -1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(1., 1., 0., 1.)
+);
+var<private> POST_TEX_COORDS:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    vec2<f32>(0., 1.),
+    vec2<f32>(1., 1.),
+    vec2<f32>(0., 0.),
+    vec2<f32>(0., 0.),
+    vec2<f32>(1., 1.),
+    vec2<f32>(1., 0.)
+);
+
+struct PostVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// Effect-specific parameters
+@group(0) @binding(0)
+var<uniform> u_params: array<vec4<f32>, 32>;
+// The output of the previous stage
+@group(0) @binding(1)
+var t_color: texture_2d<f32>;
+@group(0) @binding(2)
+var s_color: sampler;
+// The scene's depth buffer (a 1x1 placeholder in 2D-only mode), bound
+// as an unfilterable float texture so it can be loaded on GL too
+@group(0) @binding(3)
+var t_depth: texture_2d<f32>;
+// Each pixel's change in texture coordinates since the previous frame
+// (a 1x1 zero placeholder without a velocity pass)
+@group(0) @binding(4)
+var t_velocity: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> PostVertexOutput {
+  var out:PostVertexOutput;
+  out.clip_position = POST_VERTICES[in_vertex_index];
+  out.tex_coords = POST_TEX_COORDS[in_vertex_index];
+  return out;
+}
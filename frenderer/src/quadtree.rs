@@ -0,0 +1,250 @@
+//! A quadtree of sprite bounds for culling sprite groups in very
+//! large 2D worlds.
+//!
+//! A [`SpriteQuadtree`] tracks the world-space bounds of a sprite
+//! group's sprites by index.  Keep it up to date with
+//! [`SpriteQuadtree::set_sprite`] as sprites move, then ask it which
+//! ranges of the group are visible to a camera with
+//! [`SpriteQuadtree::visible_ranges`] and hand those to
+//! [`crate::Renderer::sprite_group_set_visible`] (or call
+//! [`crate::Renderer::sprite_group_cull`], which does both), so that
+//! only those ranges are drawn.  Since every range is a separate draw
+//! call, small gaps between visible sprites can be drawn through
+//! rather than split into more ranges.
+
+use crate::sprites::{Camera2D, Transform};
+use std::ops::Range;
+
+/// Nodes with more sprites than this are split, up to [`MAX_DEPTH`].
+const SPLIT_AT: usize = 16;
+/// How many times the root node can be subdivided.
+const MAX_DEPTH: u32 = 8;
+
+/// An axis-aligned rectangle in world space.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    /// The bottom-left corner
+    pub min: [f32; 2],
+    /// The top-right corner
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    /// The area of the world a camera sees.
+    pub fn of_camera(camera: &Camera2D) -> Self {
+        Self {
+            min: camera.screen_pos,
+            max: [
+                camera.screen_pos[0] + camera.screen_size[0],
+                camera.screen_pos[1] + camera.screen_size[1],
+            ],
+        }
+    }
+    /// The bounds of a sprite drawn with the given transform,
+    /// including its rotation.
+    pub fn of_sprite(trf: &Transform) -> Self {
+        let (sin, cos) = trf.rot.sin_cos();
+        let (w, h) = (trf.w as f32, trf.h as f32);
+        let half_w = (w * cos.abs() + h * sin.abs()) / 2.0;
+        let half_h = (w * sin.abs() + h * cos.abs()) / 2.0;
+        Self {
+            min: [trf.x - half_w, trf.y - half_h],
+            max: [trf.x + half_w, trf.y + half_h],
+        }
+    }
+    /// Returns true if the two rectangles overlap or touch.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min[0] <= other.max[0]
+            && other.min[0] <= self.max[0]
+            && self.min[1] <= other.max[1]
+            && other.min[1] <= self.max[1]
+    }
+    fn contains(&self, other: &Rect) -> bool {
+        self.min[0] <= other.min[0]
+            && self.min[1] <= other.min[1]
+            && other.max[0] <= self.max[0]
+            && other.max[1] <= self.max[1]
+    }
+    fn quadrants(&self) -> [Rect; 4] {
+        let mid = [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+        ];
+        [
+            Rect {
+                min: self.min,
+                max: mid,
+            },
+            Rect {
+                min: [mid[0], self.min[1]],
+                max: [self.max[0], mid[1]],
+            },
+            Rect {
+                min: [self.min[0], mid[1]],
+                max: [mid[0], self.max[1]],
+            },
+            Rect {
+                min: mid,
+                max: self.max,
+            },
+        ]
+    }
+}
+
+struct QuadNode {
+    bounds: Rect,
+    depth: u32,
+    // Index of the first of four consecutive child nodes
+    children: Option<usize>,
+    // Sprites which fit in this node but in none of its children
+    sprites: Vec<usize>,
+}
+
+/// A quadtree of sprite bounds (see the [module documentation](self)).
+pub struct SpriteQuadtree {
+    nodes: Vec<QuadNode>,
+    // The node each sprite is stored in and its bounds, if it's in the tree
+    sprites: Vec<Option<(usize, Rect)>>,
+}
+
+impl SpriteQuadtree {
+    /// Creates an empty quadtree covering the given area of the
+    /// world.  Sprites outside of it can still be added, but they
+    /// are tested one by one in every query.
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            nodes: vec![QuadNode {
+                bounds,
+                depth: 0,
+                children: None,
+                sprites: vec![],
+            }],
+            sprites: vec![],
+        }
+    }
+    /// Creates a quadtree covering the given area and adds each of
+    /// the given sprites (e.g. a sprite group's transforms) at its index.
+    pub fn from_sprites(bounds: Rect, trfs: &[Transform]) -> Self {
+        let mut tree = Self::new(bounds);
+        for (idx, trf) in trfs.iter().enumerate() {
+            tree.set_sprite(idx, trf);
+        }
+        tree
+    }
+    /// Adds the sprite at the given index, or moves it if it's
+    /// already in the tree.
+    pub fn set_sprite(&mut self, idx: usize, trf: &Transform) {
+        let rect = Rect::of_sprite(trf);
+        if let Some((node, old)) = self.sprites.get(idx).copied().flatten() {
+            if old == rect {
+                return;
+            }
+            self.unlink(node, idx);
+        }
+        if idx >= self.sprites.len() {
+            self.sprites.resize(idx + 1, None);
+        }
+        let node = self.insert(0, idx, rect);
+        self.sprites[idx] = Some((node, rect));
+    }
+    /// Removes the sprite at the given index from the tree, if it's there.
+    pub fn remove_sprite(&mut self, idx: usize) {
+        if let Some((node, _)) = self.sprites.get_mut(idx).and_then(Option::take) {
+            self.unlink(node, idx);
+        }
+    }
+    /// Removes every sprite at or after the given index, e.g. after
+    /// shrinking the sprite group.
+    pub fn truncate(&mut self, len: usize) {
+        for idx in len..self.sprites.len() {
+            self.remove_sprite(idx);
+        }
+        self.sprites.truncate(len);
+    }
+    /// Returns the indices of the sprites whose bounds overlap the
+    /// given area, in increasing order.
+    pub fn query(&self, area: &Rect) -> Vec<usize> {
+        let mut found = vec![];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            found.extend(
+                node.sprites.iter().copied().filter(|&idx| {
+                    self.sprites[idx].is_some_and(|(_, rect)| rect.intersects(area))
+                }),
+            );
+            if let Some(first) = node.children {
+                stack.extend(
+                    (first..first + 4).filter(|&child| self.nodes[child].bounds.intersects(area)),
+                );
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+    /// Returns the ranges of sprite indices visible to the given
+    /// camera, merging ranges separated by at most `max_gap` hidden
+    /// sprites.
+    pub fn visible_ranges(&self, camera: &Camera2D, max_gap: usize) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for idx in self.query(&Rect::of_camera(camera)) {
+            match ranges.last_mut() {
+                Some(range) if idx - range.end <= max_gap => range.end = idx + 1,
+                _ => ranges.push(idx..idx + 1),
+            }
+        }
+        ranges
+    }
+    // Stores a sprite in the deepest node that fully contains it,
+    // splitting nodes as they fill up, and returns that node
+    fn insert(&mut self, mut node: usize, idx: usize, rect: Rect) -> usize {
+        loop {
+            if self.nodes[node].children.is_none()
+                && self.nodes[node].sprites.len() >= SPLIT_AT
+                && self.nodes[node].depth < MAX_DEPTH
+            {
+                self.split(node);
+            }
+            let next = self.nodes[node].children.and_then(|first| {
+                (first..first + 4).find(|&child| self.nodes[child].bounds.contains(&rect))
+            });
+            match next {
+                Some(child) => node = child,
+                None => {
+                    self.nodes[node].sprites.push(idx);
+                    return node;
+                }
+            }
+        }
+    }
+    fn split(&mut self, node: usize) {
+        let first = self.nodes.len();
+        let depth = self.nodes[node].depth + 1;
+        for bounds in self.nodes[node].bounds.quadrants() {
+            self.nodes.push(QuadNode {
+                bounds,
+                depth,
+                children: None,
+                sprites: vec![],
+            });
+        }
+        self.nodes[node].children = Some(first);
+        // Move sprites down into the new children where they fit
+        for idx in std::mem::take(&mut self.nodes[node].sprites) {
+            let (_, rect) = self.sprites[idx].unwrap();
+            let new_node = match (first..first + 4).find(|&c| self.nodes[c].bounds.contains(&rect))
+            {
+                Some(child) => child,
+                None => node,
+            };
+            self.nodes[new_node].sprites.push(idx);
+            self.sprites[idx] = Some((new_node, rect));
+        }
+    }
+    fn unlink(&mut self, node: usize, idx: usize) {
+        let sprites = &mut self.nodes[node].sprites;
+        if let Some(pos) = sprites.iter().position(|&s| s == idx) {
+            sprites.swap_remove(pos);
+        }
+    }
+}
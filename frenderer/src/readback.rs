@@ -0,0 +1,139 @@
+//! Reading rendered images back from the GPU, for screenshots, photo
+//! modes, and golden-image tests.
+//!
+//! [`read_texture`] copies layer 0 of an 8-bit RGBA or BGRA texture
+//! (such as a [`crate::target::RenderTarget`]'s) into a buffer and
+//! returns an [`ImageQuery`] for the result, which arrives when the
+//! device is next polled.  [`crate::Renderer::read_frame`] and
+//! [`crate::Renderer::read_texture`] wait for it instead.  Either way
+//! the pixels come back as tightly packed RGBA8 rows from the top
+//! left, with the buffer's row padding removed.
+
+use std::sync::mpsc;
+
+use crate::WGPU;
+
+/// A pending read of an image.
+pub struct ImageQuery {
+    buffer: wgpu::Buffer,
+    ready: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    size: [u32; 2],
+    bytes_per_row: u32,
+    // Whether red and blue need swapping
+    bgra: bool,
+    result: Option<Option<Vec<u8>>>,
+}
+
+impl ImageQuery {
+    /// Returns the image as RGBA8 rows from the top left once the
+    /// read has finished, `Some(None)` if it failed, or `None` while
+    /// it's still in flight.  Like [`crate::pick::PickQuery::try_result`],
+    /// reads finish when the device is polled.
+    pub fn try_result(&mut self) -> Option<Option<&[u8]>> {
+        if self.result.is_none() {
+            match self.ready.try_recv() {
+                Ok(Ok(())) => {
+                    let pixels = {
+                        let data = self.buffer.slice(..).get_mapped_range();
+                        let row_len = self.size[0] as usize * 4;
+                        let mut pixels: Vec<u8> = data
+                            .chunks(self.bytes_per_row as usize)
+                            .take(self.size[1] as usize)
+                            .flat_map(|row| &row[..row_len])
+                            .copied()
+                            .collect();
+                        if self.bgra {
+                            pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+                        }
+                        pixels
+                    };
+                    self.buffer.unmap();
+                    self.result = Some(Some(pixels));
+                }
+                Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => self.result = Some(None),
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+        }
+        self.result.as_ref().map(Option::as_deref)
+    }
+    /// Blocks until the read has finished and returns the image, or
+    /// `None` if it failed.  The web can't block on the GPU, so there
+    /// this returns `None` unless the read had already finished.
+    pub fn wait(mut self, gpu: &WGPU) -> Option<Vec<u8>> {
+        if self.result.is_none() {
+            gpu.device().poll(wgpu::Maintain::Wait);
+        }
+        self.try_result()?;
+        self.result.flatten()
+    }
+    /// The width and height of the image being read, in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+}
+
+/// Starts reading back layer 0 of `texture` as of the most recently
+/// submitted commands.  Returns `None` if the texture's format isn't
+/// one of the 8-bit RGBA or BGRA formats, or if it wasn't created
+/// with [`wgpu::TextureUsages::COPY_SRC`].
+pub fn read_texture(gpu: &WGPU, texture: &wgpu::Texture) -> Option<ImageQuery> {
+    use wgpu::TextureFormat as F;
+    let bgra = match texture.format() {
+        F::Rgba8Unorm | F::Rgba8UnormSrgb => false,
+        F::Bgra8Unorm | F::Bgra8UnormSrgb => true,
+        _ => return None,
+    };
+    if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+        return None;
+    }
+    let size = [texture.width(), texture.height()];
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let bytes_per_row = (size[0] * 4).div_ceil(align) * align;
+    let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback:image"),
+        size: (bytes_per_row * size[1]) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = gpu
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback:image"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        },
+    );
+    gpu.queue().submit(Some(encoder.finish()));
+    let (sender, ready) = mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    Some(ImageQuery {
+        buffer,
+        ready,
+        size,
+        bytes_per_row,
+        bgra,
+        result: None,
+    })
+}
@@ -0,0 +1,761 @@
+//! Recording what an application does with a [`crate::Renderer`]
+//! over a number of frames and replaying it later, e.g. to reproduce
+//! a rendering bug reported on someone else's hardware.
+//!
+//! Start a recording with [`crate::Renderer::record_start`] and
+//! collect it with [`crate::Renderer::take_recording`] once its
+//! frames have been drawn.  Each frame of a [`Recording`] holds the
+//! [`Command`]s which bring the renderer from the previous frame's
+//! sprite groups, mesh groups, instances, and cameras to this
+//! frame's; they're found by comparing the renderer's contents each
+//! time it uploads data, so the application doesn't have to change
+//! how it drives the renderer.  Like a [`crate::scene::Scene`],
+//! textures and geometry aren't recorded, only the keys naming them,
+//! and a [`Replay`] loads them again with a
+//! [`crate::scene::SceneAssets`].  Other renderer settings (post
+//! effects, materials, lighting, and so on) aren't recorded.
+//!
+//! Recordings are stored as text in the same style as scenes, one
+//! record per line with floats written so they read back exactly, so
+//! replaying a recording on the same hardware draws the same frames.
+
+use std::io::{BufRead, Write};
+
+use crate::meshes::{Camera3D, FlatRenderer, MeshGroup, MeshRenderer, Transform3D};
+use crate::scene::{
+    read_camera2d, read_camera3d, read_instance, read_sprite, write_camera2d, write_camera3d,
+    write_instance, write_key, write_sprite, Fields, SceneAssets, SceneError, SceneGroup,
+};
+use crate::sprites::{Camera2D, SheetRegion, SpriteRenderer, Transform};
+use crate::Renderer;
+
+/// The first line of every recording file.
+const HEADER: &str = "frenderer recording 1";
+
+/// Which of the renderer's mesh renderers a command applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeshKind {
+    /// Textured meshes (see [`crate::meshes::MeshRenderer`])
+    Textured,
+    /// Flat-colored meshes (see [`crate::meshes::FlatRenderer`])
+    Flat,
+}
+
+impl MeshKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            MeshKind::Textured => "mesh",
+            MeshKind::Flat => "flat",
+        }
+    }
+}
+
+/// One change made to the renderer during a recorded frame.  Group
+/// indices are the ones the recorded application saw; a [`Replay`]
+/// maps them onto the groups it creates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// A sprite group was added
+    SpriteGroupAdd {
+        which: usize,
+        /// Names the group's spritesheet texture
+        texture: String,
+        camera: Camera2D,
+        transforms: Vec<Transform>,
+        regions: Vec<SheetRegion>,
+    },
+    /// A sprite group was removed
+    SpriteGroupRemove { which: usize },
+    /// A sprite group's camera changed
+    SpriteCamera { which: usize, camera: Camera2D },
+    /// A sprite group now has `len` sprites, and the ones from
+    /// `start` on were replaced by `transforms` and `regions`
+    Sprites {
+        which: usize,
+        len: usize,
+        start: usize,
+        transforms: Vec<Transform>,
+        regions: Vec<SheetRegion>,
+    },
+    /// A mesh group was added
+    MeshGroupAdd {
+        kind: MeshKind,
+        which: usize,
+        /// Names the group's geometry and its texture or colors
+        asset: String,
+        /// The instance transforms of each mesh in the group
+        instances: Vec<Vec<Transform3D>>,
+    },
+    /// A mesh group was removed
+    MeshGroupRemove { kind: MeshKind, which: usize },
+    /// A mesh now has `len` instances, and the ones from `start` on
+    /// were replaced by `transforms`
+    MeshInstances {
+        kind: MeshKind,
+        which: usize,
+        mesh: usize,
+        len: usize,
+        start: usize,
+        transforms: Vec<Transform3D>,
+    },
+    /// A mesh renderer's camera changed
+    MeshCamera { kind: MeshKind, camera: Camera3D },
+}
+
+/// A recorded sequence of frames.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub frames: Vec<Vec<Command>>,
+}
+
+impl Recording {
+    /// Writes the recording in frenderer's recording format.  Fails
+    /// if a key contains a line break.
+    pub fn write(&self, mut w: impl Write) -> std::io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        for frame in self.frames.iter() {
+            writeln!(w, "frame")?;
+            for command in frame.iter() {
+                write_command(&mut w, command)?;
+            }
+        }
+        Ok(())
+    }
+    /// Reads a recording written by [`Recording::write`].
+    pub fn read(r: impl BufRead) -> Result<Self, SceneError> {
+        let mut recording = Recording::default();
+        for (idx, line) in r.lines().enumerate() {
+            let line = line?;
+            let number = idx + 1;
+            if number == 1 {
+                if line.trim_end() != HEADER {
+                    return Err(SceneError::Parse {
+                        line: 1,
+                        message: "not a frenderer recording".to_string(),
+                    });
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (record, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            let mut fields = Fields {
+                line: number,
+                fields: rest.split_whitespace(),
+            };
+            if record == "frame" {
+                fields.finish()?;
+                recording.frames.push(vec![]);
+                continue;
+            }
+            let frame = recording
+                .frames
+                .last_mut()
+                .ok_or_else(|| fields.error(format!("{record} outside of a frame")))?;
+            let kind = if record.starts_with("flat_") {
+                MeshKind::Flat
+            } else {
+                MeshKind::Textured
+            };
+            match record {
+                "sprite_group_add" | "mesh_group_add" | "flat_group_add" => {
+                    let (which, key) = rest.split_once(' ').unwrap_or((rest, ""));
+                    let which = Fields {
+                        line: number,
+                        fields: which.split_whitespace(),
+                    }
+                    .next()?;
+                    frame.push(if record == "sprite_group_add" {
+                        Command::SpriteGroupAdd {
+                            which,
+                            texture: key.to_string(),
+                            camera: Camera2D {
+                                screen_pos: [0.0, 0.0],
+                                screen_size: [1.0, 1.0],
+                            },
+                            transforms: vec![],
+                            regions: vec![],
+                        }
+                    } else {
+                        Command::MeshGroupAdd {
+                            kind,
+                            which,
+                            asset: key.to_string(),
+                            instances: vec![],
+                        }
+                    });
+                }
+                "sprite_group_remove" => {
+                    let which = fields.next()?;
+                    fields.finish()?;
+                    frame.push(Command::SpriteGroupRemove { which });
+                }
+                "mesh_group_remove" | "flat_group_remove" => {
+                    let which = fields.next()?;
+                    fields.finish()?;
+                    frame.push(Command::MeshGroupRemove { kind, which });
+                }
+                "sprite_camera" => {
+                    let which = fields.next()?;
+                    let camera = read_camera2d(fields)?;
+                    frame.push(Command::SpriteCamera { which, camera });
+                }
+                "mesh_camera" | "flat_camera" => {
+                    let camera = read_camera3d(fields)?;
+                    frame.push(Command::MeshCamera { kind, camera });
+                }
+                "sprites" => {
+                    let [which, len, start] = fields.array()?;
+                    fields.finish()?;
+                    frame.push(Command::Sprites {
+                        which,
+                        len,
+                        start,
+                        transforms: vec![],
+                        regions: vec![],
+                    });
+                }
+                "mesh_instances" | "flat_instances" => {
+                    let [which, mesh, len, start] = fields.array()?;
+                    fields.finish()?;
+                    frame.push(Command::MeshInstances {
+                        kind,
+                        which,
+                        mesh,
+                        len,
+                        start,
+                        transforms: vec![],
+                    });
+                }
+                "camera" => match frame.last_mut() {
+                    Some(Command::SpriteGroupAdd { camera, .. }) => {
+                        *camera = read_camera2d(fields)?;
+                    }
+                    _ => return Err(fields.error("camera outside of a sprite group")),
+                },
+                "sprite" => match frame.last_mut() {
+                    Some(
+                        Command::SpriteGroupAdd {
+                            transforms,
+                            regions,
+                            ..
+                        }
+                        | Command::Sprites {
+                            transforms,
+                            regions,
+                            ..
+                        },
+                    ) => {
+                        let (transform, region) = read_sprite(fields)?;
+                        transforms.push(transform);
+                        regions.push(region);
+                    }
+                    _ => return Err(fields.error("sprite outside of a sprite group")),
+                },
+                "mesh" => match frame.last_mut() {
+                    Some(Command::MeshGroupAdd { instances, .. }) => {
+                        fields.finish()?;
+                        instances.push(vec![]);
+                    }
+                    _ => return Err(fields.error("mesh outside of a mesh group")),
+                },
+                "instance" => {
+                    let transforms = match frame.last_mut() {
+                        Some(Command::MeshGroupAdd { instances, .. }) => instances.last_mut(),
+                        Some(Command::MeshInstances { transforms, .. }) => Some(transforms),
+                        _ => None,
+                    }
+                    .ok_or_else(|| fields.error("instance outside of a mesh"))?;
+                    transforms.push(read_instance(fields)?);
+                }
+                _ => return Err(fields.error(format!("unknown record {record:?}"))),
+            }
+        }
+        Ok(recording)
+    }
+    /// Writes the recording to a file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+    /// Reads a recording from a file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, SceneError> {
+        Self::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+fn write_command(w: &mut impl Write, command: &Command) -> std::io::Result<()> {
+    match command {
+        Command::SpriteGroupAdd {
+            which,
+            texture,
+            camera,
+            transforms,
+            regions,
+        } => {
+            write_key(w, &format!("sprite_group_add {which}"), texture)?;
+            write_camera2d(w, camera)?;
+            for (t, r) in transforms.iter().zip(regions.iter()) {
+                write_sprite(w, t, r)?;
+            }
+        }
+        Command::SpriteGroupRemove { which } => writeln!(w, "sprite_group_remove {which}")?,
+        Command::SpriteCamera { which, camera } => {
+            let [cx, cy] = camera.screen_pos;
+            let [cw, ch] = camera.screen_size;
+            writeln!(w, "sprite_camera {which} {cx:?} {cy:?} {cw:?} {ch:?}")?;
+        }
+        Command::Sprites {
+            which,
+            len,
+            start,
+            transforms,
+            regions,
+        } => {
+            writeln!(w, "sprites {which} {len} {start}")?;
+            for (t, r) in transforms.iter().zip(regions.iter()) {
+                write_sprite(w, t, r)?;
+            }
+        }
+        Command::MeshGroupAdd {
+            kind,
+            which,
+            asset,
+            instances,
+        } => {
+            write_key(w, &format!("{}_group_add {which}", kind.prefix()), asset)?;
+            for mesh in instances.iter() {
+                writeln!(w, "mesh")?;
+                for t in mesh.iter() {
+                    write_instance(w, t)?;
+                }
+            }
+        }
+        Command::MeshGroupRemove { kind, which } => {
+            writeln!(w, "{}_group_remove {which}", kind.prefix())?;
+        }
+        Command::MeshInstances {
+            kind,
+            which,
+            mesh,
+            len,
+            start,
+            transforms,
+        } => {
+            writeln!(
+                w,
+                "{}_instances {which} {mesh} {len} {start}",
+                kind.prefix()
+            )?;
+            for t in transforms.iter() {
+                write_instance(w, t)?;
+            }
+        }
+        Command::MeshCamera { kind, camera } => {
+            write_camera3d(w, &format!("{}_camera", kind.prefix()), camera)?;
+        }
+    }
+    Ok(())
+}
+
+// The range of elements which changed between an old and new version
+// of a group's data, or None if nothing did
+fn changed_range(
+    old_len: usize,
+    new_len: usize,
+    differs: impl Fn(usize) -> bool,
+) -> Option<std::ops::Range<usize>> {
+    let common = old_len.min(new_len);
+    let start = (0..common).find(|&i| differs(i)).unwrap_or(common);
+    let end = if new_len > common {
+        new_len
+    } else {
+        (start..common)
+            .rev()
+            .find(|&i| differs(i))
+            .map_or(start, |i| i + 1)
+    };
+    (start < end || old_len != new_len).then_some(start..end)
+}
+
+type SpriteState = (Camera2D, Vec<Transform>, Vec<SheetRegion>);
+
+/// Compares a renderer's contents with what was last recorded.
+pub(crate) struct Recorder {
+    key: Box<dyn FnMut(SceneGroup) -> String>,
+    frames_left: usize,
+    recording: Recording,
+    // Commands noticed between frames, like removed groups
+    pending: Vec<Command>,
+    sprite_groups: Vec<Option<SpriteState>>,
+    // Indexed by MeshKind
+    mesh_groups: [Vec<Option<Vec<Vec<Transform3D>>>>; 2],
+    cameras: [Option<Camera3D>; 2],
+}
+
+impl Recorder {
+    pub(crate) fn new(frames: usize, key: Box<dyn FnMut(SceneGroup) -> String>) -> Self {
+        Self {
+            key,
+            frames_left: frames,
+            recording: Recording::default(),
+            pending: vec![],
+            sprite_groups: vec![],
+            mesh_groups: [vec![], vec![]],
+            cameras: [None, None],
+        }
+    }
+    pub(crate) fn is_finished(&self) -> bool {
+        self.frames_left == 0
+    }
+    pub(crate) fn finish(self) -> Recording {
+        self.recording
+    }
+    /// Notes that a sprite group is being removed, so a group added
+    /// later in its place is recorded as a new group.
+    pub(crate) fn removed_sprite_group(&mut self, which: usize) {
+        if let Some(slot) = self.sprite_groups.get_mut(which) {
+            if slot.take().is_some() {
+                self.pending.push(Command::SpriteGroupRemove { which });
+            }
+        }
+    }
+    /// Notes that a mesh group is being removed.
+    pub(crate) fn removed_mesh_group(&mut self, kind: MeshKind, which: MeshGroup) {
+        let which = which.index();
+        if let Some(slot) = self.mesh_groups[kind as usize].get_mut(which) {
+            if slot.take().is_some() {
+                self.pending.push(Command::MeshGroupRemove { kind, which });
+            }
+        }
+    }
+    /// Records the changes since the last frame as a new frame.
+    pub(crate) fn record_frame(
+        &mut self,
+        sprites: &SpriteRenderer,
+        meshes: &MeshRenderer,
+        flats: &FlatRenderer,
+    ) {
+        if self.is_finished() {
+            return;
+        }
+        let mut frame = std::mem::take(&mut self.pending);
+        for kind in [MeshKind::Textured, MeshKind::Flat] {
+            let camera = match kind {
+                MeshKind::Textured => meshes.camera(),
+                MeshKind::Flat => flats.camera(),
+            };
+            if self.cameras[kind as usize] != Some(camera) {
+                self.cameras[kind as usize] = Some(camera);
+                frame.push(Command::MeshCamera { kind, camera });
+            }
+        }
+        self.sprite_groups
+            .resize_with(sprites.sprite_group_count(), || None);
+        for (which, state) in self.sprite_groups.iter_mut().enumerate() {
+            if !sprites.has_sprite_group(which) {
+                if state.take().is_some() {
+                    frame.push(Command::SpriteGroupRemove { which });
+                }
+                continue;
+            }
+            let camera = sprites.camera(which);
+            let (transforms, regions) = sprites.get_sprites(which);
+            let Some((old_camera, old_transforms, old_regions)) = state.as_mut() else {
+                frame.push(Command::SpriteGroupAdd {
+                    which,
+                    texture: (self.key)(SceneGroup::Sprite(which)),
+                    camera,
+                    transforms: transforms.to_vec(),
+                    regions: regions.to_vec(),
+                });
+                *state = Some((camera, transforms.to_vec(), regions.to_vec()));
+                continue;
+            };
+            if *old_camera != camera {
+                *old_camera = camera;
+                frame.push(Command::SpriteCamera { which, camera });
+            }
+            let changed = changed_range(old_transforms.len(), transforms.len(), |i| {
+                old_transforms[i] != transforms[i] || old_regions[i] != regions[i]
+            });
+            if let Some(range) = changed {
+                frame.push(Command::Sprites {
+                    which,
+                    len: transforms.len(),
+                    start: range.start,
+                    transforms: transforms[range.clone()].to_vec(),
+                    regions: regions[range].to_vec(),
+                });
+                *old_transforms = transforms.to_vec();
+                *old_regions = regions.to_vec();
+            }
+        }
+        for kind in [MeshKind::Textured, MeshKind::Flat] {
+            let count = match kind {
+                MeshKind::Textured => meshes.mesh_group_count(),
+                MeshKind::Flat => flats.mesh_group_count(),
+            };
+            let states = &mut self.mesh_groups[kind as usize];
+            states.resize_with(count, || None);
+            for (which, state) in states.iter_mut().enumerate() {
+                let Some(instances) = mesh_instances(meshes, flats, kind, MeshGroup::from(which))
+                else {
+                    if state.take().is_some() {
+                        frame.push(Command::MeshGroupRemove { kind, which });
+                    }
+                    continue;
+                };
+                let Some(old) = state.as_mut() else {
+                    let instances: Vec<_> = instances.iter().map(|m| m.to_vec()).collect();
+                    let group = MeshGroup::from(which);
+                    frame.push(Command::MeshGroupAdd {
+                        kind,
+                        which,
+                        asset: (self.key)(match kind {
+                            MeshKind::Textured => SceneGroup::Mesh(group),
+                            MeshKind::Flat => SceneGroup::Flat(group),
+                        }),
+                        instances: instances.clone(),
+                    });
+                    *state = Some(instances);
+                    continue;
+                };
+                for (mesh, (old, new)) in old.iter_mut().zip(instances).enumerate() {
+                    if let Some(range) = changed_range(old.len(), new.len(), |i| old[i] != new[i]) {
+                        frame.push(Command::MeshInstances {
+                            kind,
+                            which,
+                            mesh,
+                            len: new.len(),
+                            start: range.start,
+                            transforms: new[range].to_vec(),
+                        });
+                        *old = new.to_vec();
+                    }
+                }
+            }
+        }
+        self.recording.frames.push(frame);
+        self.frames_left -= 1;
+    }
+}
+
+// The instances of each mesh of a group, if the group exists
+fn mesh_instances<'a>(
+    meshes: &'a MeshRenderer,
+    flats: &'a FlatRenderer,
+    kind: MeshKind,
+    which: MeshGroup,
+) -> Option<Vec<&'a [Transform3D]>> {
+    match kind {
+        MeshKind::Textured => meshes.has_mesh_group(which).then(|| {
+            (0..meshes.mesh_count(which))
+                .map(|mesh| meshes.get_meshes(which, mesh))
+                .collect()
+        }),
+        MeshKind::Flat => flats.has_mesh_group(which).then(|| {
+            (0..flats.mesh_count(which))
+                .map(|mesh| flats.get_meshes(which, mesh))
+                .collect()
+        }),
+    }
+}
+
+/// Plays a [`Recording`] back into a renderer one frame at a time.
+/// Groups are created with the renderer's usual methods, so a replay
+/// can run alongside other groups; the recorded group indices are
+/// mapped onto whichever indices the new groups get.
+pub struct Replay {
+    recording: Recording,
+    next_frame: usize,
+    sprite_groups: Vec<Option<usize>>,
+    // Indexed by MeshKind
+    mesh_groups: [Vec<Option<MeshGroup>>; 2],
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            next_frame: 0,
+            sprite_groups: vec![],
+            mesh_groups: [vec![], vec![]],
+        }
+    }
+    /// Returns the recording being replayed.
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+    /// Returns how many frames have been replayed so far.
+    pub fn frame(&self) -> usize {
+        self.next_frame
+    }
+    /// Returns true once every frame has been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.recording.frames.len()
+    }
+    /// Returns the renderer's index for a recorded sprite group, if
+    /// the replay has created it.
+    pub fn sprite_group(&self, recorded: usize) -> Option<usize> {
+        self.sprite_groups.get(recorded).copied().flatten()
+    }
+    /// Returns the renderer's index for a recorded mesh group, if the
+    /// replay has created it.
+    pub fn mesh_group(&self, kind: MeshKind, recorded: usize) -> Option<MeshGroup> {
+        self.mesh_groups[kind as usize]
+            .get(recorded)
+            .copied()
+            .flatten()
+    }
+    /// Applies the next frame's commands to `renderer`, loading
+    /// textures and geometry with `assets`; call
+    /// [`crate::Renderer::render`] afterwards to draw it.  Returns
+    /// `Ok(false)` without doing anything once the recording is over.
+    /// If an asset fails to load or a command refers to a group the
+    /// replay hasn't created, the error is returned and the rest of
+    /// the frame is skipped.
+    pub fn step(
+        &mut self,
+        renderer: &mut Renderer,
+        assets: &mut impl SceneAssets,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(frame) = self.recording.frames.get(self.next_frame) else {
+            return Ok(false);
+        };
+        self.next_frame += 1;
+        for command in frame.iter() {
+            match command {
+                Command::SpriteGroupAdd {
+                    which,
+                    texture,
+                    camera,
+                    transforms,
+                    regions,
+                } => {
+                    let tex = assets.sprite_texture(renderer, texture)?;
+                    let group = renderer.sprite_group_add(
+                        &tex,
+                        transforms.clone(),
+                        regions.clone(),
+                        *camera,
+                    );
+                    if self.sprite_groups.len() <= *which {
+                        self.sprite_groups.resize(which + 1, None);
+                    }
+                    self.sprite_groups[*which] = Some(group);
+                }
+                Command::SpriteGroupRemove { which } => {
+                    let group = Self::mapped(&self.sprite_groups, *which, "sprite")?;
+                    renderer.sprite_group_remove(group);
+                    self.sprite_groups[*which] = None;
+                }
+                Command::SpriteCamera { which, camera } => {
+                    let group = Self::mapped(&self.sprite_groups, *which, "sprite")?;
+                    renderer.sprite_group_set_camera(group, *camera);
+                }
+                Command::Sprites {
+                    which,
+                    len,
+                    start,
+                    transforms,
+                    regions,
+                } => {
+                    let group = Self::mapped(&self.sprite_groups, *which, "sprite")?;
+                    renderer.sprite_group_resize(group, *len);
+                    let (trfs, uvs) = renderer.sprites_mut(group, *start..start + transforms.len());
+                    trfs.copy_from_slice(transforms);
+                    uvs.copy_from_slice(regions);
+                }
+                Command::MeshGroupAdd {
+                    kind,
+                    which,
+                    asset,
+                    instances,
+                } => {
+                    let group = match kind {
+                        MeshKind::Textured => {
+                            let mut mesh = assets.mesh(renderer, asset)?;
+                            crate::scene::check_meshes(asset, instances, &mut mesh.meshes)?;
+                            renderer.mesh_group_add(
+                                &mesh.texture,
+                                mesh.vertices,
+                                mesh.indices,
+                                mesh.meshes,
+                            )
+                        }
+                        MeshKind::Flat => {
+                            let flat = assets.flat(renderer, asset)?;
+                            let mut meshes = flat.meshes;
+                            crate::scene::check_meshes(asset, instances, &mut meshes)?;
+                            renderer.flat_group_add(
+                                &flat.material_colors,
+                                flat.vertices,
+                                flat.indices,
+                                meshes,
+                            )
+                        }
+                    };
+                    for (mesh, transforms) in instances.iter().enumerate() {
+                        match kind {
+                            MeshKind::Textured => renderer.meshes_mut(group, mesh, ..),
+                            MeshKind::Flat => renderer.flats_mut(group, mesh, ..),
+                        }
+                        .copy_from_slice(transforms);
+                    }
+                    let groups = &mut self.mesh_groups[*kind as usize];
+                    if groups.len() <= *which {
+                        groups.resize(which + 1, None);
+                    }
+                    groups[*which] = Some(group);
+                }
+                Command::MeshGroupRemove { kind, which } => {
+                    let groups = &mut self.mesh_groups[*kind as usize];
+                    let group = Self::mapped(groups, *which, kind.prefix())?;
+                    match kind {
+                        MeshKind::Textured => renderer.mesh_group_remove(group),
+                        MeshKind::Flat => renderer.flat_group_remove(group),
+                    }
+                    groups[*which] = None;
+                }
+                Command::MeshInstances {
+                    kind,
+                    which,
+                    mesh,
+                    len,
+                    start,
+                    transforms,
+                } => {
+                    let groups = &mut self.mesh_groups[*kind as usize];
+                    let group = Self::mapped(groups, *which, kind.prefix())?;
+                    let range = *start..start + transforms.len();
+                    match kind {
+                        MeshKind::Textured => {
+                            renderer.mesh_instance_resize(group, *mesh, *len);
+                            renderer.meshes_mut(group, *mesh, range)
+                        }
+                        MeshKind::Flat => {
+                            renderer.flat_instance_resize(group, *mesh, *len);
+                            renderer.flats_mut(group, *mesh, range)
+                        }
+                    }
+                    .copy_from_slice(transforms);
+                }
+                Command::MeshCamera { kind, camera } => match kind {
+                    MeshKind::Textured => renderer.mesh_set_camera(*camera),
+                    MeshKind::Flat => renderer.flat_set_camera(*camera),
+                },
+            }
+        }
+        Ok(true)
+    }
+    fn mapped<T: Copy>(groups: &[Option<T>], which: usize, what: &str) -> Result<T, String> {
+        groups
+            .get(which)
+            .copied()
+            .flatten()
+            .ok_or_else(|| format!("The recording uses {what} group {which} before adding it"))
+    }
+}
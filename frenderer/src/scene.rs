@@ -0,0 +1,457 @@
+//! Saving and loading the contents of a [`crate::Renderer`]: its
+//! sprite groups, textured and flat mesh groups, instance transforms,
+//! and cameras.  Textures and mesh geometry aren't stored in a
+//! [`Scene`]; each group is saved with a key (e.g. a file path or a
+//! content hash) naming the asset it was made from, and when a scene
+//! is loaded a [`SceneAssets`] implementation turns those keys back
+//! into textures and geometry.
+//!
+//! Scenes are stored as UTF-8 text with one record per line, so
+//! they're easy to diff and check into version control.  Floats are
+//! written so that they read back exactly, which makes a saved scene
+//! a deterministic test fixture.
+
+use std::io::{BufRead, Write};
+
+use crate::meshes::{Camera3D, FlatVertex, MeshEntry, MeshGroup, Transform3D, Vertex};
+use crate::sprites::{Camera2D, SheetRegion, Transform};
+
+/// The first line of every scene file.
+const HEADER: &str = "frenderer scene 1";
+
+/// A saved description of a renderer's contents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub mesh_camera: Camera3D,
+    pub flat_camera: Camera3D,
+    pub sprite_groups: Vec<SpriteGroupScene>,
+    pub mesh_groups: Vec<MeshGroupScene>,
+    pub flat_groups: Vec<MeshGroupScene>,
+}
+
+/// A saved sprite group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteGroupScene {
+    /// Names the group's spritesheet texture
+    pub texture: String,
+    pub camera: Camera2D,
+    pub transforms: Vec<Transform>,
+    pub regions: Vec<SheetRegion>,
+}
+
+/// A saved textured or flat mesh group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshGroupScene {
+    /// Names the group's geometry and its texture or colors
+    pub asset: String,
+    /// The instance transforms of each mesh in the group
+    pub instances: Vec<Vec<Transform3D>>,
+}
+
+/// A group of a renderer, used to ask for the key it should be saved with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SceneGroup {
+    Sprite(usize),
+    Mesh(MeshGroup),
+    Flat(MeshGroup),
+}
+
+/// The geometry and texture of a textured mesh group, as passed to
+/// [`crate::Renderer::mesh_group_add`].  The instance counts of
+/// `meshes` are replaced by the saved ones.
+pub struct MeshAsset {
+    pub texture: wgpu::Texture,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub meshes: Vec<MeshEntry>,
+}
+
+/// The geometry and colors of a flat mesh group, as passed to
+/// [`crate::Renderer::flat_group_add`].  The instance counts of
+/// `meshes` are replaced by the saved ones.
+pub struct FlatAsset {
+    pub material_colors: Vec<[f32; 4]>,
+    pub vertices: Vec<FlatVertex>,
+    pub indices: Vec<u32>,
+    pub meshes: Vec<MeshEntry>,
+}
+
+/// Turns the keys a scene was saved with back into assets.
+pub trait SceneAssets {
+    /// Loads a spritesheet texture, e.g. with [`crate::Renderer::create_array_texture`].
+    fn sprite_texture(
+        &mut self,
+        renderer: &crate::Renderer,
+        key: &str,
+    ) -> Result<wgpu::Texture, Box<dyn std::error::Error>>;
+    /// Loads a textured mesh group's geometry and texture.
+    fn mesh(
+        &mut self,
+        renderer: &crate::Renderer,
+        key: &str,
+    ) -> Result<MeshAsset, Box<dyn std::error::Error>>;
+    /// Loads a flat mesh group's geometry and colors.
+    fn flat(
+        &mut self,
+        renderer: &crate::Renderer,
+        key: &str,
+    ) -> Result<FlatAsset, Box<dyn std::error::Error>>;
+}
+
+/// The groups created by [`crate::Renderer::scene_load`], in the
+/// order they appear in the [`Scene`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadedScene {
+    pub sprite_groups: Vec<usize>,
+    pub mesh_groups: Vec<MeshGroup>,
+    pub flat_groups: Vec<MeshGroup>,
+}
+
+/// Errors from reading a scene.
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    /// A line (counting from 1) couldn't be understood
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "Couldn't read scene: {err}"),
+            SceneError::Parse { line, message } => {
+                write!(f, "Invalid scene at line {line}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+pub(crate) fn write_camera3d(w: &mut impl Write, name: &str, c: &Camera3D) -> std::io::Result<()> {
+    let [tx, ty, tz] = c.translation;
+    let [rx, ry, rz, rw] = c.rotation;
+    writeln!(
+        w,
+        "{name} {tx:?} {ty:?} {tz:?} {:?} {:?} {rx:?} {ry:?} {rz:?} {rw:?} {:?} {:?}",
+        c.near, c.far, c.aspect, c.fov
+    )
+}
+
+pub(crate) fn write_key(w: &mut impl Write, record: &str, key: &str) -> std::io::Result<()> {
+    if key.contains(['\n', '\r']) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Scene keys can't contain line breaks: {key:?}"),
+        ));
+    }
+    writeln!(w, "{record} {key}")
+}
+
+pub(crate) fn write_camera2d(w: &mut impl Write, camera: &Camera2D) -> std::io::Result<()> {
+    let [cx, cy] = camera.screen_pos;
+    let [cw, ch] = camera.screen_size;
+    writeln!(w, "camera {cx:?} {cy:?} {cw:?} {ch:?}")
+}
+
+pub(crate) fn write_sprite(
+    w: &mut impl Write,
+    t: &Transform,
+    r: &SheetRegion,
+) -> std::io::Result<()> {
+    let [cr, cg, cb, ca] = r.colormod;
+    writeln!(
+        w,
+        "sprite {:?} {:?} {} {} {:?} {} {} {} {} {} {} {cr} {cg} {cb} {ca}",
+        t.x, t.y, t.w, t.h, t.rot, r.sheet, r.depth, r.x, r.y, r.w, r.h
+    )
+}
+
+pub(crate) fn write_instance(w: &mut impl Write, t: &Transform3D) -> std::io::Result<()> {
+    let [tx, ty, tz] = t.translation;
+    let [rx, ry, rz, rw] = t.rotation;
+    writeln!(
+        w,
+        "instance {tx:?} {ty:?} {tz:?} {:?} {rx:?} {ry:?} {rz:?} {rw:?}",
+        t.scale
+    )
+}
+
+fn write_meshes(w: &mut impl Write, record: &str, group: &MeshGroupScene) -> std::io::Result<()> {
+    write_key(w, record, &group.asset)?;
+    for mesh in group.instances.iter() {
+        writeln!(w, "mesh")?;
+        for t in mesh.iter() {
+            write_instance(w, t)?;
+        }
+    }
+    Ok(())
+}
+
+// Splits a line into fields and parses them on demand
+pub(crate) struct Fields<'a> {
+    pub(crate) line: usize,
+    pub(crate) fields: std::str::SplitWhitespace<'a>,
+}
+
+impl Fields<'_> {
+    pub(crate) fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::Parse {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+    pub(crate) fn next<T: std::str::FromStr>(&mut self) -> Result<T, SceneError> {
+        let field = self
+            .fields
+            .next()
+            .ok_or_else(|| self.error("missing field"))?;
+        field
+            .parse()
+            .map_err(|_| self.error(format!("invalid field {field:?}")))
+    }
+    pub(crate) fn array<T: std::str::FromStr + Default + Copy, const N: usize>(
+        &mut self,
+    ) -> Result<[T; N], SceneError> {
+        let mut out = [T::default(); N];
+        for x in out.iter_mut() {
+            *x = self.next()?;
+        }
+        Ok(out)
+    }
+    pub(crate) fn finish(mut self) -> Result<(), SceneError> {
+        match self.fields.next() {
+            Some(field) => Err(self.error(format!("unexpected field {field:?}"))),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn read_camera3d(mut fields: Fields) -> Result<Camera3D, SceneError> {
+    let [tx, ty, tz, near, far, rx, ry, rz, rw, aspect, fov] = fields.array()?;
+    fields.finish()?;
+    Ok(Camera3D {
+        translation: [tx, ty, tz],
+        near,
+        far,
+        rotation: [rx, ry, rz, rw],
+        aspect,
+        fov,
+    })
+}
+
+pub(crate) fn read_camera2d(mut fields: Fields) -> Result<Camera2D, SceneError> {
+    let [x, y, w, h] = fields.array()?;
+    fields.finish()?;
+    Ok(Camera2D {
+        screen_pos: [x, y],
+        screen_size: [w, h],
+    })
+}
+
+pub(crate) fn read_sprite(mut fields: Fields) -> Result<(Transform, SheetRegion), SceneError> {
+    let [x, y] = fields.array()?;
+    let [w, h] = fields.array()?;
+    let rot = fields.next()?;
+    let [sheet, depth, sx, sy] = fields.array()?;
+    let [sw, sh] = fields.array()?;
+    let colormod = fields.array()?;
+    fields.finish()?;
+    Ok((
+        Transform { w, h, x, y, rot },
+        SheetRegion {
+            sheet,
+            depth,
+            x: sx,
+            y: sy,
+            w: sw,
+            h: sh,
+            colormod,
+        },
+    ))
+}
+
+pub(crate) fn read_instance(mut fields: Fields) -> Result<Transform3D, SceneError> {
+    let [tx, ty, tz, scale, rx, ry, rz, rw] = fields.array()?;
+    fields.finish()?;
+    Ok(Transform3D {
+        translation: [tx, ty, tz],
+        scale,
+        rotation: [rx, ry, rz, rw],
+    })
+}
+
+/// Checks that an asset has as many meshes as were saved and sets
+/// their instance counts to the saved ones.
+pub(crate) fn check_meshes(
+    asset: &str,
+    instances: &[Vec<Transform3D>],
+    meshes: &mut [MeshEntry],
+) -> Result<(), String> {
+    if meshes.len() != instances.len() {
+        return Err(format!(
+            "Asset {:?} has {} meshes but the scene has {}",
+            asset,
+            meshes.len(),
+            instances.len()
+        ));
+    }
+    for (mesh, instances) in meshes.iter_mut().zip(instances.iter()) {
+        mesh.instance_count = instances.len() as u32;
+    }
+    Ok(())
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        let camera = Camera3D {
+            translation: [0.0; 3],
+            near: 0.1,
+            far: 100.0,
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            aspect: 4.0 / 3.0,
+            fov: std::f32::consts::FRAC_PI_2,
+        };
+        Self {
+            mesh_camera: camera,
+            flat_camera: camera,
+            sprite_groups: vec![],
+            mesh_groups: vec![],
+            flat_groups: vec![],
+        }
+    }
+}
+
+impl Scene {
+    /// Writes the scene in frenderer's scene format.  Fails if a key
+    /// contains a line break.
+    pub fn write(&self, mut w: impl Write) -> std::io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        write_camera3d(&mut w, "mesh_camera", &self.mesh_camera)?;
+        write_camera3d(&mut w, "flat_camera", &self.flat_camera)?;
+        for group in self.sprite_groups.iter() {
+            write_key(&mut w, "sprite_group", &group.texture)?;
+            write_camera2d(&mut w, &group.camera)?;
+            for (t, r) in group.transforms.iter().zip(group.regions.iter()) {
+                write_sprite(&mut w, t, r)?;
+            }
+        }
+        for group in self.mesh_groups.iter() {
+            write_meshes(&mut w, "mesh_group", group)?;
+        }
+        for group in self.flat_groups.iter() {
+            write_meshes(&mut w, "flat_group", group)?;
+        }
+        Ok(())
+    }
+    /// Reads a scene written by [`Scene::write`].
+    pub fn read(r: impl BufRead) -> Result<Self, SceneError> {
+        let mut scene = Scene::default();
+        // Which kind of mesh group instances are being read into
+        let mut mesh_groups: Option<&mut Vec<MeshGroupScene>> = None;
+        for (idx, line) in r.lines().enumerate() {
+            let line = line?;
+            let number = idx + 1;
+            if number == 1 {
+                if line.trim_end() != HEADER {
+                    return Err(SceneError::Parse {
+                        line: 1,
+                        message: "not a frenderer scene".to_string(),
+                    });
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (record, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            let fields = Fields {
+                line: number,
+                fields: rest.split_whitespace(),
+            };
+            match record {
+                "mesh_camera" => scene.mesh_camera = read_camera3d(fields)?,
+                "flat_camera" => scene.flat_camera = read_camera3d(fields)?,
+                "sprite_group" => {
+                    mesh_groups = None;
+                    scene.sprite_groups.push(SpriteGroupScene {
+                        texture: rest.to_string(),
+                        camera: Camera2D {
+                            screen_pos: [0.0, 0.0],
+                            screen_size: [1.0, 1.0],
+                        },
+                        transforms: vec![],
+                        regions: vec![],
+                    });
+                }
+                "camera" => {
+                    let group = scene
+                        .sprite_groups
+                        .last_mut()
+                        .filter(|_| mesh_groups.is_none())
+                        .ok_or_else(|| fields.error("camera outside of a sprite group"))?;
+                    group.camera = read_camera2d(fields)?;
+                }
+                "sprite" => {
+                    let group = scene
+                        .sprite_groups
+                        .last_mut()
+                        .filter(|_| mesh_groups.is_none())
+                        .ok_or_else(|| fields.error("sprite outside of a sprite group"))?;
+                    let (transform, region) = read_sprite(fields)?;
+                    group.transforms.push(transform);
+                    group.regions.push(region);
+                }
+                "mesh_group" | "flat_group" => {
+                    let groups = if record == "mesh_group" {
+                        &mut scene.mesh_groups
+                    } else {
+                        &mut scene.flat_groups
+                    };
+                    groups.push(MeshGroupScene {
+                        asset: rest.to_string(),
+                        instances: vec![],
+                    });
+                    mesh_groups = Some(groups);
+                }
+                "mesh" => {
+                    let group = mesh_groups
+                        .as_mut()
+                        .and_then(|groups| groups.last_mut())
+                        .ok_or_else(|| fields.error("mesh outside of a mesh group"))?;
+                    fields.finish()?;
+                    group.instances.push(vec![]);
+                }
+                "instance" => {
+                    let mesh = mesh_groups
+                        .as_mut()
+                        .and_then(|groups| groups.last_mut())
+                        .and_then(|group| group.instances.last_mut())
+                        .ok_or_else(|| fields.error("instance outside of a mesh"))?;
+                    mesh.push(read_instance(fields)?);
+                }
+                _ => return Err(fields.error(format!("unknown record {record:?}"))),
+            }
+        }
+        Ok(scene)
+    }
+    /// Writes the scene to a file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+    /// Reads a scene from a file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, SceneError> {
+        Self::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
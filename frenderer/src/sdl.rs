@@ -0,0 +1,85 @@
+//! The sdl2 feature lets frenderer draw into an SDL2 window, for
+//! games that use SDL2 for windowing, input, or audio instead of
+//! winit.  SDL2 keeps its own event loop, so there's no `Driver`
+//! for it: create the renderer with [`create_renderer`] (or
+//! [`crate::RendererBuilder::build_with_sdl_window`]), pass window
+//! events to [`SdlEvents::handle_sdl_event`], and call
+//! [`crate::clock::Clock::tick`] and [`Renderer::render`] once per
+//! iteration of your loop:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use frenderer::sdl::SdlEvents;
+//! let sdl = sdl2::init()?;
+//! let window = sdl.video()?.window("game", 1024, 768).resizable().build()?;
+//! // The window outlives the renderer
+//! let mut frend = unsafe { frenderer::sdl::create_renderer(&window, None).await }?;
+//! let mut clock = frenderer::clock::Clock::new(1.0 / 60.0, 0.0002, 5);
+//! let mut events = sdl.event_pump()?;
+//! 'game: loop {
+//!     for event in events.poll_iter() {
+//!         if let sdl2::event::Event::Quit { .. } = event {
+//!             break 'game;
+//!         }
+//!         frend.handle_sdl_event(&window, &event);
+//!     }
+//!     for _ in 0..clock.tick() {
+//!         // simulate
+//!     }
+//!     frend.render();
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Renderer, RendererBuilder};
+
+/// Creates a [`Renderer`] drawing into `window` at the given
+/// rendering resolution (the window's size if absent),
+/// with the default settings of [`RendererBuilder`].
+///
+/// # Safety
+///
+/// `window` must outlive the renderer, since its surface refers to
+/// the window.
+pub async unsafe fn create_renderer(
+    window: &sdl2::video::Window,
+    render_size: Option<(u32, u32)>,
+) -> Result<Renderer, Box<dyn std::error::Error>> {
+    let builder = match render_size {
+        Some((width, height)) => RendererBuilder::new().render_size(width, height),
+        None => RendererBuilder::new(),
+    };
+    builder.build_with_sdl_window(window).await
+}
+
+/// This extension trait keeps frenderer's surface in step with an
+/// SDL2 window.
+pub trait SdlEvents {
+    /// Call `handle_sdl_event` with each event SDL2 produces for
+    /// `window` so that the surface is resized along with the window.
+    fn handle_sdl_event(&mut self, window: &sdl2::video::Window, event: &sdl2::event::Event);
+}
+
+impl SdlEvents for Renderer {
+    fn handle_sdl_event(&mut self, window: &sdl2::video::Window, event: &sdl2::event::Event) {
+        use sdl2::event::{Event, WindowEvent};
+        if let Event::Window {
+            window_id,
+            win_event: WindowEvent::SizeChanged(..),
+            ..
+        } = event
+        {
+            if *window_id == window.id() {
+                let (width, height) = window.size();
+                self.resize_surface(width, height);
+            }
+        }
+    }
+}
+
+impl SdlEvents for crate::Immediate {
+    fn handle_sdl_event(&mut self, window: &sdl2::video::Window, event: &sdl2::event::Event) {
+        self.renderer.handle_sdl_event(window, event)
+    }
+}
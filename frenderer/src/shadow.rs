@@ -0,0 +1,468 @@
+//! Point lights and their cube shadow maps, and the sun's cascaded
+//! shadow maps.
+//!
+//! [`PointLight`]s light meshes per fragment, on top of their
+//! [`crate::meshes::FlatLighting`], with a smooth falloff to zero at
+//! each light's range.  Lights can also cast shadows: a
+//! [`PointShadows`] holds six square depth maps per light, one for
+//! each face of a cube around the light, which are drawn from the
+//! light's position by the mesh renderers' `render_point_shadow`
+//! methods and then sampled by the mesh shaders.  Both textured
+//! and flat meshes cast shadows, and both are lit wherever their
+//! vertices have normals.  [`crate::Renderer`] does all of this with
+//! [`crate::Renderer::point_lights_set`] and
+//! [`crate::Renderer::point_shadows_set`].
+//!
+//! The faces of every light are layers of one 2D array texture, in
+//! the order +x, -x, +y, -y, +z, -z, rather than a cube map array,
+//! since not every backend supports those.
+//!
+//! The directional light of [`crate::meshes::FlatLighting`] can cast
+//! shadows too: [`SunShadows`] splits the camera's view into
+//! cascades, each with its own orthographic depth map in one shadow
+//! atlas, which the mesh shaders sample with percentage-closer
+//! filtering.  Lit meshes lose the directional part of their
+//! lighting in shadow, and unlit textured meshes darken by
+//! [`ShadowConfig::shade`].  [`crate::Renderer::set_sun`] turns them
+//! on.
+
+use crate::{capture, meshes::Camera3D, WGPU};
+use ultraviolet::{Mat4, Vec3};
+
+/// How many point lights can light a scene at once.
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+// Six layers for each light, plus one so that GL doesn't take the
+// texture for a cube map array
+pub(crate) const SHADOW_LAYERS: u32 = MAX_POINT_LIGHTS as u32 * 6 + 1;
+
+/// The format of the shadow maps.
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// A light shining in every direction from a point.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PointLight {
+    /// The light's position in world space
+    pub position: [f32; 3],
+    /// The light's color at its position, as a linear RGB multiplier
+    pub color: [f32; 3],
+    /// How far the light reaches; it fades out completely at this distance
+    pub range: f32,
+    /// Whether the light casts shadows (when shadow maps are on)
+    pub shadows: bool,
+}
+
+impl PointLight {
+    /// A shadow-casting light with the given position, color, and range.
+    pub fn new(position: [f32; 3], color: [f32; 3], range: f32) -> Self {
+        Self {
+            position,
+            color,
+            range,
+            shadows: true,
+        }
+    }
+    /// The projection and view matrices of the cube faces around the
+    /// light, in the order +x, -x, +y, -y, +z, -z.
+    pub(crate) fn face_projection_views(&self) -> [Mat4; 6] {
+        let proj = ultraviolet::projection::rh_yup::perspective_wgpu_dx(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            self.range / 1000.0,
+            self.range,
+        );
+        let eye = Vec3::from(self.position);
+        [
+            (Vec3::unit_x(), Vec3::unit_y()),
+            (-Vec3::unit_x(), Vec3::unit_y()),
+            (Vec3::unit_y(), Vec3::unit_z()),
+            (-Vec3::unit_y(), Vec3::unit_z()),
+            (Vec3::unit_z(), Vec3::unit_y()),
+            (-Vec3::unit_z(), Vec3::unit_y()),
+        ]
+        .map(|(dir, up)| proj * Mat4::look_at(eye, eye + dir, up))
+    }
+}
+
+// One light as laid out in the shader
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightUniform {
+    position_range: [f32; 4],
+    // w is the first of the light's shadow map layers, or -1
+    color_shadow: [f32; 4],
+    faces: [[[f32; 4]; 4]; 6],
+}
+
+/// The point lights as laid out in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PointLightsUniform {
+    // The number of lights and the shadow map size
+    count_size: [u32; 4],
+    lights: [PointLightUniform; MAX_POINT_LIGHTS],
+}
+
+impl PointLightsUniform {
+    /// Lays out the given lights, giving each shadow-casting light
+    /// six shadow map layers if `shadow_size` (the size of the
+    /// shadow maps) is given.
+    pub(crate) fn new(lights: &[PointLight], shadow_size: Option<u32>) -> Self {
+        let mut uniform: Self = bytemuck::Zeroable::zeroed();
+        uniform.count_size = [lights.len() as u32, shadow_size.unwrap_or(1), 0, 0];
+        for (idx, (light, out)) in lights.iter().zip(&mut uniform.lights).enumerate() {
+            let [x, y, z] = light.position;
+            let [r, g, b] = light.color;
+            let layer = if light.shadows && shadow_size.is_some() {
+                (idx * 6) as f32
+            } else {
+                -1.0
+            };
+            out.position_range = [x, y, z, light.range];
+            out.color_shadow = [r, g, b, layer];
+            out.faces = light.face_projection_views().map(Into::into);
+        }
+        uniform
+    }
+}
+
+/// The shadow maps of up to [`MAX_POINT_LIGHTS`] point lights (see
+/// the [module documentation](self)).
+pub struct PointShadows {
+    size: u32,
+    texture: wgpu::Texture,
+    // Each face of each light, for drawing into
+    face_views: Vec<wgpu::TextureView>,
+}
+
+impl PointShadows {
+    /// Creates `size` by `size` shadow maps for every light.  They
+    /// take up `size * size * 4` bytes for each face of each of the
+    /// [`MAX_POINT_LIGHTS`] lights.
+    pub fn new(gpu: &WGPU, size: u32) -> Self {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow:point_maps"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: SHADOW_LAYERS,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let face_views = (0..MAX_POINT_LIGHTS as u32 * 6)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("shadow:point_face"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Self {
+            size,
+            texture,
+            face_views,
+        }
+    }
+    /// Returns the width and height of each shadow map.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    /// Returns the texture holding every shadow map, six layers per light.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Begins a render pass which clears the shadow map of one face
+    /// (from 0 to 5, in the order +x, -x, +y, -y, +z, -z) of the
+    /// given light; draw the shadow casters into it with the mesh
+    /// renderers' `render_point_shadow` methods.
+    pub fn begin_face_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+        light: usize,
+        face: usize,
+    ) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("shadow:point_face");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow:point_face"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.face_views[light * 6 + face],
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+/// How many cascades the sun's shadows can be split into.
+pub const MAX_CASCADES: usize = 4;
+
+/// How the sun casts shadows (see [`SunShadows`]).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShadowConfig {
+    /// The width and height of each cascade's shadow map, in texels
+    pub size: u32,
+    /// How many cascades the view is split into, from 1 to
+    /// [`MAX_CASCADES`]; nearer cascades cover less ground, so
+    /// shadows close to the camera are sharper
+    pub cascades: u32,
+    /// How far from the camera shadows reach, in world units;
+    /// everything beyond is unshadowed
+    pub distance: f32,
+    /// How far lit points are offset before they're compared with the
+    /// shadow map, in shadow map texels, to keep surfaces from
+    /// shadowing themselves; it's multiplied by one more than
+    /// [`ShadowConfig::pcf`] since wider filters reach further
+    pub bias: f32,
+    /// The radius of the filter softening shadow edges, in texels;
+    /// 0 samples a single texel, 1 a 3x3 grid, and so on
+    pub pcf: u32,
+    /// How much unlit textured meshes (those without normals, or
+    /// all of them while lighting is off) darken in shadow, from 0
+    /// (not at all) to 1 (black)
+    pub shade: f32,
+}
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            size: 1024,
+            cascades: 3,
+            distance: 50.0,
+            bias: 1.5,
+            pcf: 1,
+            shade: 0.5,
+        }
+    }
+}
+
+/// The sun's shadows as laid out in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SunUniform {
+    cascades: [[[f32; 4]; 4]; MAX_CASCADES],
+    // Each cascade's place in the atlas: x, y, w, h in texture coordinates
+    rects: [[f32; 4]; MAX_CASCADES],
+    // xyz: the direction towards the sun, w: the cascade count (0
+    // without shadows)
+    direction_count: [f32; 4],
+    // The width of a texel of each cascade in world units
+    texel_sizes: [f32; 4],
+    // x: the size of each cascade's map in texels, y: the bias,
+    // z: the filter radius, w: how much textured meshes darken
+    params: [f32; 4],
+}
+
+impl SunUniform {
+    /// The uniform of a scene without sun shadows.
+    pub(crate) fn off() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+}
+
+/// Shadows cast by the sun: a shadow atlas holding one square depth
+/// map per cascade, each drawn from the sun's direction over a slice
+/// of the camera's view and sampled with percentage-closer filtering
+/// by the mesh shaders.  Call [`SunShadows::update`] when the camera
+/// or sun moves, draw each cascade with the mesh renderers'
+/// `render_sun_shadow` methods in a pass begun with
+/// [`SunShadows::begin_pass`], and bind the atlas with their
+/// `set_sun_shadows` methods.  [`crate::Renderer::set_sun`] does all
+/// of this each frame.
+pub struct SunShadows {
+    config: ShadowConfig,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    direction: [f32; 3],
+    cascades: [Mat4; MAX_CASCADES],
+    texel_sizes: [f32; MAX_CASCADES],
+}
+
+impl SunShadows {
+    /// Creates the shadow atlas for the given configuration.  It
+    /// takes up `size * size * 4` bytes per cascade (rounded up to an
+    /// even number of cascades).  Panics if `config.cascades` isn't
+    /// between 1 and [`MAX_CASCADES`].
+    pub fn new(gpu: &WGPU, config: ShadowConfig) -> Self {
+        assert!(
+            (1..=MAX_CASCADES as u32).contains(&config.cascades),
+            "Sun shadows need 1 to {MAX_CASCADES} cascades, not {}",
+            config.cascades
+        );
+        let (columns, rows) = Self::grid(config.cascades);
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow:sun_atlas"),
+            size: wgpu::Extent3d {
+                width: config.size * columns,
+                height: config.size * rows,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            config,
+            texture,
+            view,
+            direction: [0.0, 1.0, 0.0],
+            cascades: [Mat4::identity(); MAX_CASCADES],
+            texel_sizes: [0.0; MAX_CASCADES],
+        }
+    }
+    // Cascades are laid out two to a row
+    fn grid(cascades: u32) -> (u32, u32) {
+        (cascades.min(2), cascades.div_ceil(2))
+    }
+    /// Returns the shadows' configuration.
+    pub fn config(&self) -> &ShadowConfig {
+        &self.config
+    }
+    /// Returns the shadow atlas.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Returns the direction towards the sun given to the last [`SunShadows::update`].
+    pub fn direction(&self) -> [f32; 3] {
+        self.direction
+    }
+    /// Fits the cascades to the given camera's view for a sun in the
+    /// given direction (towards the sun, in world space).  The view
+    /// out to [`ShadowConfig::distance`] is split so that nearer
+    /// cascades cover shorter stretches, and each cascade's map moves
+    /// in whole texels so that shadow edges don't shimmer as the
+    /// camera moves.
+    pub fn update(&mut self, direction: [f32; 3], camera: &Camera3D) {
+        self.direction = direction;
+        let to_sun = Vec3::from(direction).normalized();
+        let up = if to_sun.y.abs() > 0.99 {
+            Vec3::unit_z()
+        } else {
+            Vec3::unit_y()
+        };
+        let count = self.config.cascades as usize;
+        let near = camera.near;
+        let far = camera.far.min(near + self.config.distance);
+        // Halfway between evenly and logarithmically spaced splits
+        let split = |i: usize| {
+            let t = i as f32 / count as f32;
+            0.5 * (near + (far - near) * t) + 0.5 * (near * (far / near).powf(t))
+        };
+        let camera_to_world = camera.view().inversed();
+        let tan = (camera.fov * 0.5).tan();
+        let size = self.config.size as f32;
+        for cascade in 0..count {
+            let corners = [split(cascade), split(cascade + 1)].map(|z| {
+                let (h, w) = (z * tan, z * tan * camera.aspect);
+                [(-w, -h), (w, -h), (-w, h), (w, h)]
+                    .map(|(x, y)| camera_to_world.transform_point3(Vec3::new(x, y, -z)))
+            });
+            let corners = corners.concat();
+            let center = corners.iter().fold(Vec3::zero(), |sum, &c| sum + c) / 8.0;
+            let radius = corners
+                .iter()
+                .map(|&c| (c - center).mag())
+                .fold(0.0, f32::max);
+            // Rounded up so that the map's scale is steady as the camera turns
+            let radius = (radius * 16.0).ceil() / 16.0;
+            // Casters behind the slice, towards the sun, still cast
+            // shadows into it
+            let reach = radius + self.config.distance;
+            let view = Mat4::look_at(center + to_sun * reach, center, up);
+            let proj = ultraviolet::projection::rh_yup::orthographic_wgpu_dx(
+                -radius,
+                radius,
+                -radius,
+                radius,
+                0.0,
+                reach + radius,
+            );
+            let mut projection_view = proj * view;
+            // Snap the world origin to a texel
+            let origin = projection_view.transform_point3(Vec3::zero()) * (size * 0.5);
+            let offset =
+                (Vec3::new(origin.x.round(), origin.y.round(), origin.z) - origin) * (2.0 / size);
+            projection_view =
+                Mat4::from_translation(Vec3::new(offset.x, offset.y, 0.0)) * projection_view;
+            self.cascades[cascade] = projection_view;
+            self.texel_sizes[cascade] = 2.0 * radius / size;
+        }
+    }
+    /// The projection and view matrix of each cascade, as of the last
+    /// [`SunShadows::update`].
+    pub fn cascades(&self) -> &[Mat4] {
+        &self.cascades[..self.config.cascades as usize]
+    }
+    pub(crate) fn uniform(&self) -> SunUniform {
+        let (columns, rows) = Self::grid(self.config.cascades);
+        let [x, y, z] = Vec3::from(self.direction).normalized().into();
+        let mut uniform = SunUniform {
+            cascades: self.cascades.map(Into::into),
+            rects: [[0.0; 4]; MAX_CASCADES],
+            direction_count: [x, y, z, self.config.cascades as f32],
+            texel_sizes: self.texel_sizes,
+            params: [
+                self.config.size as f32,
+                self.config.bias,
+                self.config.pcf as f32,
+                self.config.shade,
+            ],
+        };
+        for (cascade, rect) in uniform.rects.iter_mut().enumerate() {
+            let (column, row) = (cascade as u32 % 2, cascade as u32 / 2);
+            *rect = [
+                column as f32 / columns as f32,
+                row as f32 / rows as f32,
+                1.0 / columns as f32,
+                1.0 / rows as f32,
+            ];
+        }
+        uniform
+    }
+    /// Begins a render pass which clears the whole atlas; draw each
+    /// cascade into it with [`SunShadows::set_cascade_viewport`] and
+    /// the mesh renderers' `render_sun_shadow` methods.
+    pub fn begin_pass<'s>(&'s self, encoder: &'s mut wgpu::CommandEncoder) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("shadow:sun");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow:sun"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        })
+    }
+    /// Restricts drawing to one cascade's part of the atlas.
+    pub fn set_cascade_viewport(&self, rpass: &mut wgpu::RenderPass, cascade: usize) {
+        let size = self.config.size as f32;
+        rpass.set_viewport(
+            (cascade % 2) as f32 * size,
+            (cascade / 2) as f32 * size,
+            size,
+            size,
+            0.0,
+            1.0,
+        );
+    }
+}
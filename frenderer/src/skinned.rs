@@ -0,0 +1,738 @@
+//! Skinned meshes: textured meshes whose vertices follow the bones
+//! of a skeleton, such as animated characters.
+//!
+//! Each [`SkinnedVertex`] names up to four bones (joints) of its
+//! group's skeleton and how much each one moves it.  Every instance
+//! of a skinned group has its own [`Transform3D`] and its own palette
+//! of [`BoneMatrix`]es, one per bone, which the vertex shader blends
+//! to place each vertex; so animating a character only means writing
+//! its bone matrices each frame (e.g. from an animation clip sampled
+//! on the CPU), not re-uploading its vertices.
+//!
+//! A bone matrix takes a vertex from the mesh's bind pose to where
+//! that bone has moved it, in model space: usually the bone's
+//! current model-space transform times the inverse of its bind-pose
+//! transform.  The identity matrix leaves vertices where they are.
+//!
+//! Bone palettes live in a storage buffer, so skinned meshes need a
+//! device which allows storage buffers in vertex shaders; creating a
+//! [`SkinnedMeshRenderer`] fails with a [`LimitError`] otherwise.  Skinned meshes are
+//! drawn unlit like [`crate::meshes::MeshRenderer`]'s, with its
+//! camera, but don't cast shadows or take part in picking, outlines,
+//! or velocity.
+
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    capture,
+    meshes::{Camera3D, Transform3D},
+    LimitError, WGPU,
+};
+
+/// How many bones can move each vertex.
+pub const MAX_INFLUENCES: usize = 4;
+
+/// A column-major 4x4 matrix moving vertices with one bone (see the
+/// [module documentation](self)).
+pub type BoneMatrix = [[f32; 4]; 4];
+
+/// The identity [`BoneMatrix`], which leaves vertices in the bind pose.
+pub const BONE_IDENTITY: BoneMatrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// A vertex for meshes in the [`SkinnedMeshRenderer`]: a textured
+/// vertex plus the bones which move it and their weights.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
+pub struct SkinnedVertex {
+    position: [f32; 3],
+    uv_which: [f32; 3],
+    joints: [u16; MAX_INFLUENCES],
+    weights: [f32; MAX_INFLUENCES],
+}
+impl SkinnedVertex {
+    pub const ZERO: Self = Self {
+        position: [0.0; 3],
+        uv_which: [0.0; 3],
+        joints: [0; MAX_INFLUENCES],
+        weights: [0.0; MAX_INFLUENCES],
+    };
+    /// Creates a vertex with the given position, UV coordinates, and
+    /// index into the texture array, moved by the given bones of its
+    /// group's skeleton in proportion to their weights.  Weights are
+    /// normalized when drawing; a vertex whose weights are all zero
+    /// stays in the bind pose.
+    pub fn new(
+        position: [f32; 3],
+        uv: [f32; 2],
+        which: u32,
+        joints: [u16; MAX_INFLUENCES],
+        weights: [f32; MAX_INFLUENCES],
+    ) -> Self {
+        Self {
+            position,
+            uv_which: [uv[0], uv[1], f32::from_bits(which)],
+            joints,
+            weights,
+        }
+    }
+    /// Returns the bones moving this vertex.
+    pub fn joints(&self) -> [u16; MAX_INFLUENCES] {
+        self.joints
+    }
+    /// Returns how much each of this vertex's bones moves it.
+    pub fn weights(&self) -> [f32; MAX_INFLUENCES] {
+        self.weights
+    }
+}
+
+// New instances' transforms: at the origin, unrotated, at unit scale
+const UNMOVED: Transform3D = Transform3D {
+    translation: [0.0; 3],
+    scale: 1.0,
+    rotation: [1.0, 0.0, 0.0, 0.0],
+};
+
+// The header before each group's bone matrices in its storage buffer;
+// this must match `Bones` in `skinned.wgsl`
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct BonesHeader {
+    // x = bones per instance
+    bone_count: [u32; 4],
+}
+
+struct SkinnedGroupData {
+    instances: Vec<Transform3D>,
+    // bone_count matrices per instance
+    bones: Vec<BoneMatrix>,
+    bone_count: usize,
+    // How many instances the buffers have room for
+    capacity: usize,
+    instance_buffer: wgpu::Buffer,
+    bone_buffer: wgpu::Buffer,
+    bone_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    index_count: u32,
+    bind_group: wgpu::BindGroup,
+    // Instances or bones changed since the last upload
+    dirty: bool,
+}
+
+/// Renders groups of skinned, textured 3D meshes, skinning them on the GPU.
+pub struct SkinnedMeshRenderer {
+    groups: Vec<Option<SkinnedGroupData>>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bone_bind_group_layout: wgpu::BindGroupLayout,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    camera: Camera3D,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl SkinnedMeshRenderer {
+    /// Creates a new `SkinnedMeshRenderer` meant to draw into the
+    /// given color target state with the given depth texture format
+    /// (or without depth testing if `depth_format` is `None`).
+    /// Panics if the device doesn't allow storage buffers in vertex
+    /// shaders (see [`SkinnedMeshRenderer::try_new`]).
+    pub fn new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        Self::try_new(gpu, color_target, depth_format).unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`SkinnedMeshRenderer::new`], but returns an error if the
+    /// device can't bind a storage buffer of bone matrices in its
+    /// vertex shaders.
+    pub fn try_new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Result<Self, LimitError> {
+        let limits = gpu.device().limits();
+        let min_bones =
+            (std::mem::size_of::<BonesHeader>() + std::mem::size_of::<BoneMatrix>()) as u64;
+        if limits.max_storage_buffers_per_shader_stage == 0 {
+            return Err(LimitError::StorageBindingSize {
+                buffer: "skinned:bones",
+                size: min_bones,
+                max: 0,
+            });
+        }
+        gpu.check_buffer("skinned:bones", min_bones, true)?;
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("skinned:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("skinned.wgsl"))),
+            });
+        let camera_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("skinned:camera_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("skinned:texture_bgl"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let bone_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("skinned:bones_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("skinned:pipeline_layout"),
+                    bind_group_layouts: &[
+                        &camera_bind_group_layout,
+                        &bind_group_layout,
+                        &bone_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+        let camera = Camera3D {
+            translation: [0.0; 3],
+            near: 0.1,
+            far: 100.0,
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            aspect: 1.0,
+            fov: std::f32::consts::FRAC_PI_2,
+        };
+        let camera_buffer = gpu
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("skinned:camera"),
+                contents: bytemuck::cast_slice(&camera.projection_view().as_array()[..]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let camera_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skinned:camera_bg"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let depth_format = depth_format.into();
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            color_target.clone(),
+            (depth_format, 1),
+        );
+        Ok(Self {
+            groups: vec![],
+            bind_group_layout,
+            bone_bind_group_layout,
+            camera_buffer,
+            camera_bind_group,
+            camera,
+            shader,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipeline,
+        })
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("skinned:pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<SkinnedVertex>() as u64,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                // position
+                                0 => Float32x3,
+                                // uv_which (we lie and say it's three floats)
+                                1 => Float32x3,
+                                // joints
+                                4 => Uint16x4,
+                                // weights
+                                5 => Float32x4,
+                            ],
+                        },
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Transform3D>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![
+                                // translation and scale
+                                2 => Float32x4,
+                                // rotation
+                                3 => Float32x4,
+                            ],
+                        },
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    /// Sets the camera all skinned groups are drawn with.
+    pub fn set_camera(&mut self, gpu: &WGPU, camera: Camera3D) {
+        self.camera = camera;
+        gpu.queue().write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&camera.projection_view().as_array()[..]),
+        );
+    }
+    /// Returns the camera all skinned groups are drawn with.
+    pub fn camera(&self) -> Camera3D {
+        self.camera
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipeline.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.color_target.clone(),
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass skinned
+    /// groups are drawn into, re-creating the pipeline.  The default
+    /// is 1 (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target this renderer draws into (e.g. for a
+    /// new color format), re-creating its pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Adds a skinned group drawing the triangles given by `indices`
+    /// into `vertices` with the given array texture, whose skeleton
+    /// has `bone_count` bones, with `instance_count` instances.
+    /// Instances start at the origin with unit scale and every bone
+    /// matrix the identity.  Indices are stored as 16-bit values on
+    /// the GPU if they all fit.  Returns the group's index.  Panics
+    /// if the group would exceed the device's limits.
+    pub fn add_group(
+        &mut self,
+        gpu: &WGPU,
+        texture: &wgpu::Texture,
+        vertices: &[SkinnedVertex],
+        indices: &[u32],
+        bone_count: usize,
+        instance_count: usize,
+    ) -> usize {
+        self.try_add_group(gpu, texture, vertices, indices, bone_count, instance_count)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`SkinnedMeshRenderer::add_group`], but returns an error
+    /// naming the exceeded limit if the texture has too many layers
+    /// or the group's buffers would be too large for the device.
+    pub fn try_add_group(
+        &mut self,
+        gpu: &WGPU,
+        texture: &wgpu::Texture,
+        vertices: &[SkinnedVertex],
+        indices: &[u32],
+        bone_count: usize,
+        instance_count: usize,
+    ) -> Result<usize, LimitError> {
+        gpu.check_texture_layers(texture)?;
+        let bone_count = bone_count.max(1);
+        gpu.check_buffer(
+            "skinned:vertices",
+            std::mem::size_of_val(vertices) as u64,
+            false,
+        )?;
+        gpu.check_buffer(
+            "skinned:indices",
+            std::mem::size_of_val(indices) as u64,
+            false,
+        )?;
+        Self::check_instances(gpu, bone_count, instance_count)?;
+        let vertex_buffer = gpu
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("skinned:vertices"),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        // Index buffers are padded to a multiple of four bytes
+        let (index_buffer, index_format) = if indices.iter().all(|&i| i <= u16::MAX as u32) {
+            let mut short: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+            if short.len() % 2 == 1 {
+                short.push(0);
+            }
+            (
+                gpu.device()
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("skinned:indices"),
+                        contents: bytemuck::cast_slice(&short),
+                        usage: wgpu::BufferUsages::INDEX,
+                    }),
+                wgpu::IndexFormat::Uint16,
+            )
+        } else {
+            (
+                gpu.device()
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("skinned:indices"),
+                        contents: bytemuck::cast_slice(indices),
+                        usage: wgpu::BufferUsages::INDEX,
+                    }),
+                wgpu::IndexFormat::Uint32,
+            )
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("skinned:texture_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            // Blends between mip levels, for textures that have them
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skinned:texture_bg"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        let (instance_buffer, bone_buffer, bone_bind_group) =
+            self.instance_buffers(gpu, bone_count, instance_count);
+        let group = SkinnedGroupData {
+            instances: vec![UNMOVED; instance_count],
+            bones: vec![BONE_IDENTITY; bone_count * instance_count],
+            bone_count,
+            capacity: instance_count,
+            instance_buffer,
+            bone_buffer,
+            bone_bind_group,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            index_count: indices.len() as u32,
+            bind_group,
+            dirty: true,
+        };
+        if let Some(which) = self.groups.iter().position(Option::is_none) {
+            self.groups[which] = Some(group);
+            Ok(which)
+        } else {
+            self.groups.push(Some(group));
+            Ok(self.groups.len() - 1)
+        }
+    }
+    fn bone_bytes(bone_count: usize, instance_count: usize) -> u64 {
+        (std::mem::size_of::<BonesHeader>()
+            + std::mem::size_of::<BoneMatrix>() * bone_count * instance_count.max(1)) as u64
+    }
+    fn check_instances(
+        gpu: &WGPU,
+        bone_count: usize,
+        instance_count: usize,
+    ) -> Result<(), LimitError> {
+        gpu.check_buffer(
+            "skinned:instances",
+            (std::mem::size_of::<Transform3D>() * instance_count.max(1)) as u64,
+            false,
+        )?;
+        gpu.check_buffer(
+            "skinned:bones",
+            Self::bone_bytes(bone_count, instance_count),
+            true,
+        )
+    }
+    // Creates instance and bone buffers with room for `capacity` instances
+    fn instance_buffers(
+        &self,
+        gpu: &WGPU,
+        bone_count: usize,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::Buffer, wgpu::BindGroup) {
+        let instance_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skinned:instances"),
+            size: (std::mem::size_of::<Transform3D>() * capacity.max(1)) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bone_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("skinned:bones"),
+            size: Self::bone_bytes(bone_count, capacity),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let header = BonesHeader {
+            bone_count: [bone_count as u32, 0, 0, 0],
+        };
+        gpu.queue()
+            .write_buffer(&bone_buffer, 0, bytemuck::bytes_of(&header));
+        let bone_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skinned:bones_bg"),
+            layout: &self.bone_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: bone_buffer.as_entire_binding(),
+            }],
+        });
+        (instance_buffer, bone_buffer, bone_bind_group)
+    }
+    /// Deletes a skinned group, leaving an empty slot behind (this might get recycled later).
+    pub fn remove_group(&mut self, which: usize) {
+        if let Some(group) = self.groups.get_mut(which) {
+            *group = None;
+        }
+    }
+    /// Returns the number of skinned groups (including placeholders for removed groups).
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+    /// Returns whether the given skinned group exists.
+    pub fn has_group(&self, which: usize) -> bool {
+        self.groups.get(which).is_some_and(Option::is_some)
+    }
+    fn group(&self, which: usize) -> &SkinnedGroupData {
+        self.groups[which]
+            .as_ref()
+            .expect("Skinned group not populated")
+    }
+    fn group_mut(&mut self, which: usize) -> &mut SkinnedGroupData {
+        self.groups[which]
+            .as_mut()
+            .expect("Skinned group not populated")
+    }
+    /// Returns how many bones each instance of the given group has.
+    /// Panics if the group is not populated.
+    pub fn bone_count(&self, which: usize) -> usize {
+        self.group(which).bone_count
+    }
+    /// Returns how many instances the given group has.  Panics if
+    /// the group is not populated.
+    pub fn instance_count(&self, which: usize) -> usize {
+        self.group(which).instances.len()
+    }
+    /// Changes how many instances the given group has; new instances
+    /// start at the origin in the bind pose.  Panics if the group is
+    /// not populated or its buffers would exceed the device's limits.
+    pub fn resize_group(&mut self, gpu: &WGPU, which: usize, len: usize) {
+        self.try_resize_group(gpu, which, len)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`SkinnedMeshRenderer::resize_group`], but returns an
+    /// error naming the exceeded limit instead of panicking if the
+    /// group's buffers wouldn't fit on this GPU.
+    pub fn try_resize_group(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        len: usize,
+    ) -> Result<(), LimitError> {
+        let (bone_count, capacity) = {
+            let group = self.group(which);
+            (group.bone_count, group.capacity)
+        };
+        if len > capacity {
+            Self::check_instances(gpu, bone_count, len)?;
+            let (instance_buffer, bone_buffer, bone_bind_group) =
+                self.instance_buffers(gpu, bone_count, len);
+            let group = self.group_mut(which);
+            group.instance_buffer = instance_buffer;
+            group.bone_buffer = bone_buffer;
+            group.bone_bind_group = bone_bind_group;
+            group.capacity = len;
+        }
+        let group = self.group_mut(which);
+        group.instances.resize(len, UNMOVED);
+        group.bones.resize(len * bone_count, BONE_IDENTITY);
+        group.dirty = true;
+        Ok(())
+    }
+    /// Gets the (mutable) transforms of every instance of the given
+    /// group; they're uploaded by the next [`SkinnedMeshRenderer::upload`].
+    /// Panics if the group is not populated.
+    pub fn instances_mut(&mut self, which: usize) -> &mut [Transform3D] {
+        let group = self.group_mut(which);
+        group.dirty = true;
+        &mut group.instances
+    }
+    /// Returns the transforms of every instance of the given group.
+    /// Panics if the group is not populated.
+    pub fn instances(&self, which: usize) -> &[Transform3D] {
+        &self.group(which).instances
+    }
+    /// Gets the (mutable) bone matrices of one instance of the given
+    /// group, one per bone; they're uploaded by the next
+    /// [`SkinnedMeshRenderer::upload`].  Panics if the group is not
+    /// populated or doesn't have that instance.
+    pub fn bones_mut(&mut self, which: usize, instance: usize) -> &mut [BoneMatrix] {
+        let group = self.group_mut(which);
+        assert!(
+            instance < group.instances.len(),
+            "Skinned instance out of bounds"
+        );
+        group.dirty = true;
+        let count = group.bone_count;
+        &mut group.bones[instance * count..(instance + 1) * count]
+    }
+    /// Returns the bone matrices of one instance of the given group.
+    /// Panics if the group is not populated or doesn't have that instance.
+    pub fn bones(&self, which: usize, instance: usize) -> &[BoneMatrix] {
+        let group = self.group(which);
+        assert!(
+            instance < group.instances.len(),
+            "Skinned instance out of bounds"
+        );
+        let count = group.bone_count;
+        &group.bones[instance * count..(instance + 1) * count]
+    }
+    /// Uploads the instances and bones of every group changed since
+    /// the last upload.
+    pub fn upload(&mut self, gpu: &WGPU) {
+        for group in self.groups.iter_mut().flatten() {
+            if !group.dirty {
+                continue;
+            }
+            group.dirty = false;
+            if group.instances.is_empty() {
+                continue;
+            }
+            gpu.queue().write_buffer(
+                &group.instance_buffer,
+                0,
+                bytemuck::cast_slice(&group.instances),
+            );
+            gpu.queue().write_buffer(
+                &group.bone_buffer,
+                std::mem::size_of::<BonesHeader>() as u64,
+                bytemuck::cast_slice(&group.bones),
+            );
+        }
+    }
+    /// Draws every skinned group into the given renderpass, which
+    /// should have been begun with the same color and depth formats
+    /// this renderer was created with.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "skinned:pipeline");
+        capture::set_bind_group(rpass, 0, &self.camera_bind_group, &[], "skinned:camera");
+        for group in self.groups.iter().flatten() {
+            if group.instances.is_empty() || group.index_count == 0 {
+                continue;
+            }
+            capture::set_bind_group(rpass, 1, &group.bind_group, &[], "skinned:texture");
+            capture::set_bind_group(rpass, 2, &group.bone_bind_group, &[], "skinned:bones");
+            capture::set_vertex_buffer(rpass, 0, &group.vertex_buffer, "skinned:vertices");
+            capture::set_vertex_buffer(rpass, 1, &group.instance_buffer, "skinned:instances");
+            capture::set_index_buffer(
+                rpass,
+                &group.index_buffer,
+                group.index_format,
+                "skinned:indices",
+            );
+            capture::draw_indexed(
+                rpass,
+                0..group.index_count,
+                0,
+                0..group.instances.len() as u32,
+            );
+        }
+    }
+}
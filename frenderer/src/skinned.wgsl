@@ -0,0 +1,105 @@
+// Skinned meshes (see skinned.rs): each vertex is moved by a weighted
+// blend of its instance's bone matrices before the instance transform.
+
+@group(0) @binding(0)
+var<uniform> projview: mat4x4<f32>;
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d_array<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+// Every instance's bone matrices, bone_count.x of them per instance
+struct Bones {
+  bone_count: vec4<u32>,
+  matrices: array<mat4x4<f32>>,
+}
+@group(2) @binding(0)
+var<storage, read> bones: Bones;
+
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) uv_which: vec3<f32>,
+  @location(4) joints: vec4<u32>,
+  @location(5) weights: vec4<f32>,
+}
+
+struct InstanceInput {
+  @location(2) translate_scale: vec4<f32>,
+  @location(3) rot: vec4<f32>,
+}
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) tex_coords: vec2<f32>,
+  @location(1) @interpolate(flat) tex_index: u32,
+}
+
+fn bone(instance:u32, joint:u32) -> mat4x4<f32> {
+  let count = bones.bone_count.x;
+  return bones.matrices[instance * count + min(joint, count - 1u)];
+}
+
+@vertex
+fn vs_main(vtx:VertexInput, inst:InstanceInput, @builtin(instance_index) instance:u32) -> VertexOutput {
+  let total = dot(vtx.weights, vec4(1.0));
+  var skinned = vec4(vtx.position, 1.0);
+  // Vertices without weights stay in the bind pose
+  if total > 0.0 {
+    let w = vtx.weights / total;
+    let skin = bone(instance, vtx.joints.x) * w.x
+      + bone(instance, vtx.joints.y) * w.y
+      + bone(instance, vtx.joints.z) * w.z
+      + bone(instance, vtx.joints.w) * w.w;
+    skinned = skin * skinned;
+  }
+  let model = mat_from_trs(inst.translate_scale.xyz, inst.rot, inst.translate_scale.w);
+  var out:VertexOutput;
+  out.clip_position = projview * model * skinned;
+  out.tex_coords = vtx.uv_which.xy;
+  out.tex_index = bitcast<u32>(vtx.uv_which.z);
+  return out;
+}
+
+@fragment
+fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+}
+
+// The same as in static_meshes.wgsl (see Transform3D::model_matrix)
+fn mat_from_trs(t:vec3<f32>, r:vec4<f32>, s:f32) -> mat4x4<f32> {
+  let trans =
+    mat4x4<f32>
+    (
+     vec4<f32>(1.0,0.0,0.0,0.0),
+     vec4<f32>(0.0,1.0,0.0,0.0),
+     vec4<f32>(0.0,0.0,1.0,0.0),
+     vec4<f32>(   t.xyz,   1.0),
+     );
+  let rot =
+    mat4x4<f32>
+    (
+     vec4<f32>(2.0*(r.x*r.x+r.y*r.y) - 1.0,
+               2.0*(r.y*r.z-r.x*r.w),
+               2.0*(r.y*r.w+r.x*r.z),
+               0.0),
+     vec4<f32>(2.0*(r.y*r.z+r.x*r.w),
+               2.0*(r.x*r.x+r.z*r.z) - 1.0,
+               2.0*(r.z*r.w-r.x*r.y),
+               0.0),
+     vec4<f32>(2.0*(r.y*r.w-r.x*r.z),
+               2.0*(r.z*r.w+r.x*r.y),
+               2.0*(r.x*r.x+r.w*r.w) - 1.0,
+               0.0),
+     vec4<f32>(0.0,0.0,0.0,1.0),
+     );
+  let scale =
+    mat4x4<f32>
+    (
+     vec4<f32>(  s,0.0,0.0,0.0),
+     vec4<f32>(0.0,  s,0.0,0.0),
+     vec4<f32>(0.0,0.0,  s,0.0),
+     vec4<f32>(0.0,0.0,0.0,1.0),
+     );
+  return trans*rot*scale;
+}
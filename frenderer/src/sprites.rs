@@ -3,16 +3,25 @@
 //! spritesheets and numerous sprites.  For efficiency, it's best to
 //! minimize the number of groups.
 
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, ops::Range, sync::Arc};
 
-use crate::WGPU;
+use crate::{
+    capture,
+    highlight::HighlightSet,
+    material::{GroupMaterial, Material},
+    outline::OutlineSet,
+    pick::PickTable,
+    BufferWriter, GroupSelection, GrowthPolicy, InstanceStorage, LimitError, WGPU,
+};
 use bytemuck::{Pod, Zeroable};
 
 /// A SheetRegion defines the visual appearance of a sprite: which spritesheet (of an array of spritesheets), its pixel region within the spritesheet, and its visual depth (larger meaning further away).
 #[repr(C)]
-#[derive(Clone, Copy, Zeroable, Pod, Debug, Default)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, Default, PartialEq)]
 pub struct SheetRegion {
-    /// Which array texture layer to use
+    /// Which array texture layer to use, in the low 13 bits; the
+    /// top three bits are flags which flip or rotate the sprite's
+    /// texture (see [`SheetRegion::with_flags`]).
     pub sheet: u16,
     /// How deep into the Z axis this sprite should be drawn; the range `0..u16::MAX` will be mapped onto `0.0..1.0`.
     pub depth: u16,
@@ -28,6 +37,17 @@ pub struct SheetRegion {
     pub colormod: [u8; 4],
 }
 
+/// The tint sprites start out with, which leaves them unchanged.  A
+/// sprite's tint (see [`SpriteRenderer::get_tints_mut`]) is RGBA
+/// bytes which multiply its color after its
+/// [`SheetRegion::colormod`]; its alpha fades the sprite out.
+pub const NO_TINT: [u8; 4] = [255; 4];
+
+/// How many sprites are drawn per chunk when instance data is held in
+/// uniform buffers; this must match `UNIFORM_CHUNK` in `sprites.wgsl`.
+/// 1024 16-byte elements is the 16KB minimum uniform binding size.
+const UNIFORM_CHUNK: usize = 1024;
+
 impl SheetRegion {
     pub const ZERO: Self = Self {
         sheet: 0,
@@ -38,6 +58,16 @@ impl SheetRegion {
         h: 0,
         colormod: [0; 4],
     };
+    /// Flag which mirrors the sprite's texture left to right.
+    pub const FLIP_X: u16 = 1 << 13;
+    /// Flag which mirrors the sprite's texture top to bottom.
+    pub const FLIP_Y: u16 = 1 << 14;
+    /// Flag which turns the sprite's texture a quarter turn
+    /// clockwise.  The region's width then runs along the sprite's
+    /// height and vice versa, so swap the [`Transform`]'s `w` and
+    /// `h` to keep its proportions.
+    pub const ROT_90: u16 = 1 << 15;
+    const FLAGS: u16 = Self::FLIP_X | Self::FLIP_Y | Self::ROT_90;
     /// Create a new [`SheetRegion`] with the given parameters.
     pub const fn new(sheet: u16, x: u16, y: u16, depth: u16, w: i16, h: i16) -> Self {
         Self {
@@ -54,13 +84,35 @@ impl SheetRegion {
     pub const fn rect(x: u16, y: u16, w: i16, h: i16) -> Self {
         Self::new(0, x, y, 0, w, h)
     }
-    /// Produce a new [`SheetRegion`] on a different spritesheet layer.
+    /// Produce a new [`SheetRegion`] on a different spritesheet
+    /// layer, keeping its flags.
     pub const fn with_sheet(self, which: u16) -> Self {
         Self {
-            sheet: which,
+            sheet: (which & !Self::FLAGS) | self.flags(),
+            ..self
+        }
+    }
+    /// The spritesheet layer this region is on, without its flags.
+    pub const fn layer(self) -> u16 {
+        self.sheet & !Self::FLAGS
+    }
+    /// Produce a new [`SheetRegion`] with the given combination of
+    /// [`SheetRegion::FLIP_X`], [`SheetRegion::FLIP_Y`], and
+    /// [`SheetRegion::ROT_90`] in place of its current flags.  The
+    /// sprite shader turns the texture first and then mirrors it, so
+    /// `FLIP_X` always mirrors the sprite left to right on screen
+    /// (e.g. to turn a character around) without touching the
+    /// region's coordinates or the sprite's transform.
+    pub const fn with_flags(self, flags: u16) -> Self {
+        Self {
+            sheet: self.layer() | (flags & Self::FLAGS),
             ..self
         }
     }
+    /// This region's flip and rotation flags.
+    pub const fn flags(self) -> u16 {
+        self.sheet & Self::FLAGS
+    }
     /// Produce a new [`SheetRegion`] drawn at a different depth level.
     pub const fn with_depth(self, depth: u16) -> Self {
         Self { depth, ..self }
@@ -94,7 +146,7 @@ impl SheetRegion {
 /// interpreted as the center of the object after translation.
 /// Rotations are in radians, counterclockwise about the center point.
 #[repr(C)]
-#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq)]
 pub struct Transform {
     /// The horizontal scale of the transform
     pub w: u16,
@@ -124,12 +176,26 @@ impl Transform {
     pub fn scale(&self) -> [f32; 2] {
         [self.w as f32, self.h as f32]
     }
+    /// Interpolates from this transform (at `alpha` 0) to `other`
+    /// (at 1), rotating the shorter way around.
+    pub fn lerp(&self, other: &Self, alpha: f32) -> Self {
+        use std::f32::consts::{PI, TAU};
+        let mix = |a: f32, b: f32| a + (b - a) * alpha;
+        let turn = (other.rot - self.rot + PI).rem_euclid(TAU) - PI;
+        Self {
+            w: mix(self.w as f32, other.w as f32).round() as u16,
+            h: mix(self.h as f32, other.h as f32).round() as u16,
+            x: mix(self.x, other.x),
+            y: mix(self.y, other.y),
+            rot: self.rot + turn * alpha,
+        }
+    }
 }
 
 /// Camera2D is a transform for a sprite layer, defining a scale
 /// followed by a translation.
 #[repr(C)]
-#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq)]
 pub struct Camera2D {
     /// The position of the camera in world space
     pub screen_pos: [f32; 2],
@@ -137,14 +203,279 @@ pub struct Camera2D {
     pub screen_size: [f32; 2],
 }
 
+impl Camera2D {
+    // Maps world space to clip space, the same way the sprite shader does
+    pub(crate) fn projection(&self) -> ultraviolet::Mat4 {
+        use ultraviolet::{Mat4, Vec4};
+        let [x, y] = self.screen_pos;
+        let [w, h] = self.screen_size;
+        Mat4::new(
+            Vec4::new(2.0 / w, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 2.0 / h, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 1.0, 0.0),
+            Vec4::new(-2.0 * x / w - 1.0, -2.0 * y / h - 1.0, 0.0, 1.0),
+        )
+    }
+}
+
+/// Which coordinate space a sprite group's [`Transform`]s are in (see
+/// [`SpriteRenderer::set_coordinate_mode`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CoordinateMode {
+    /// World units, mapped onto the screen by the group's [`Camera2D`]
+    #[default]
+    World,
+    /// Pixels of the render target, with the origin at the bottom
+    /// left; the group's camera is ignored
+    ScreenPixels,
+    /// Fractions of the render target from 0 to 1, with the origin at
+    /// the bottom left; the group's camera is ignored.  Since
+    /// [`Transform`] sizes are whole numbers, sprite sizes are
+    /// multiples of the whole target, which suits full-screen sprites
+    /// like fades and vignettes.
+    ScreenNormalized,
+}
+
+/// How a sprite group interacts with the depth buffer (see
+/// [`SpriteRenderer::set_depth_mode`]).  Has no effect on renderers
+/// without a depth buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DepthMode {
+    /// Whether sprites are hidden by whatever is already in front of
+    /// them in the depth buffer
+    pub test: bool,
+    /// Whether sprites hide whatever is drawn behind them afterwards
+    pub write: bool,
+}
+
+impl Default for DepthMode {
+    fn default() -> Self {
+        Self {
+            test: true,
+            write: true,
+        }
+    }
+}
+
+impl DepthMode {
+    /// Neither tested nor written, e.g. for UI drawn over everything else.
+    pub const OVERLAY: Self = Self {
+        test: false,
+        write: false,
+    };
+}
+
+/// How a sprite group's colors combine with what's drawn behind it
+/// (see [`SpriteRenderer::set_blend_mode`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Nearly transparent pixels of the spritesheet are cut out and
+    /// the rest are drawn solid, writing depth as usual.
+    #[default]
+    Cutout,
+    /// Sprites fade by their spritesheet's alpha and are blended over
+    /// what's behind them, e.g. for smoke or fades.  Each group's
+    /// sprites are drawn from farthest to nearest (by
+    /// [`SheetRegion::depth`]) and don't write depth.
+    Alpha,
+    /// Sprites' colors (times their alpha) are added to what's behind
+    /// them, e.g. for glows and sparks.  They don't write depth, and
+    /// since addition doesn't depend on order they aren't sorted.
+    Additive,
+}
+
+impl BlendMode {
+    // The color target used by pipelines for this mode
+    pub(crate) fn color_target(
+        self,
+        color_target: &wgpu::ColorTargetState,
+    ) -> wgpu::ColorTargetState {
+        let blend = match self {
+            BlendMode::Cutout => return color_target.clone(),
+            BlendMode::Alpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        };
+        wgpu::ColorTargetState {
+            blend: Some(blend),
+            ..color_target.clone()
+        }
+    }
+    // Blended sprites would hide whatever is drawn behind them later
+    // if they wrote depth
+    fn depth_mode(self, depth_mode: DepthMode) -> DepthMode {
+        DepthMode {
+            write: depth_mode.write && self == BlendMode::Cutout,
+            ..depth_mode
+        }
+    }
+    // Blended groups output premultiplied colors
+    fn fs_entries(self) -> (&'static str, &'static str) {
+        match self {
+            BlendMode::Cutout => ("fs_main", "fs_main_emissive"),
+            _ => ("fs_main_blended", "fs_main_emissive_blended"),
+        }
+    }
+}
+
+/// Debug views for diagnosing spritesheet and batching problems
+/// (see [`SpriteRenderer::set_debug_view`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    /// Sprites are drawn normally.
+    #[default]
+    Off,
+    /// Each sprite group is tinted with its own color, except that
+    /// groups batched together (see
+    /// [`SpriteRenderer::set_batch_limit`]) share their first
+    /// group's color; this shows how sprites are batched together.
+    GroupTint,
+    /// One layer of a group's spritesheet is drawn over everything
+    /// else, stretched to fill the target, with the sheet regions the
+    /// group's sprites use on that layer outlined.  Transparent parts
+    /// of the sheet are shown as a checkerboard.
+    Atlas { group: usize, layer: u16 },
+}
+
+struct DebugPipelines {
+    tint: wgpu::RenderPipeline,
+    atlas: wgpu::RenderPipeline,
+    rect: wgpu::RenderPipeline,
+}
+
+impl DebugPipelines {
+    fn new(sprites: &SpriteRenderer, gpu: &WGPU) -> Self {
+        // The atlas and its outlines are drawn over whatever is already there
+        let overlay_depth = sprites.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+        Self {
+            tint: SpriteRenderer::create_pipeline(
+                gpu,
+                &sprites.shader,
+                &sprites.outline_pipeline_layout,
+                sprites.instance_storage,
+                "fs_debug_tint",
+                sprites.color_target.clone(),
+                sprites.depth_format,
+                sprites.sample_count,
+            ),
+            atlas: SpriteRenderer::create_pipeline_with_depth(
+                gpu,
+                &sprites.shader,
+                &sprites.pipeline_layout,
+                sprites.instance_storage,
+                "fs_debug_atlas",
+                sprites.color_target.format.into(),
+                overlay_depth.clone(),
+                sprites.sample_count,
+            ),
+            rect: SpriteRenderer::create_pipeline_with_depth(
+                gpu,
+                &sprites.shader,
+                &sprites.outline_pipeline_layout,
+                sprites.instance_storage,
+                "fs_debug_rect",
+                sprites.color_target.format.into(),
+                overlay_depth,
+                sprites.sample_count,
+            ),
+        }
+    }
+}
+
 struct SpriteGroup {
     world_buffer: wgpu::Buffer,
     sheet_buffer: wgpu::Buffer,
+    tint_buffer: wgpu::Buffer,
     world_transforms: Vec<Transform>,
     sheet_regions: Vec<SheetRegion>,
+    tints: Vec<[u8; 4]>,
     camera: Camera2D,
     camera_buffer: wgpu::Buffer,
     tex_bind_group: wgpu::BindGroup,
+    // Kept for the atlas debug view
+    sheet_view: Arc<wgpu::TextureView>,
+    // The size of the spritesheet texture, which emissive layers must match
+    sheet_size: wgpu::Extent3d,
+    // Identifies the spritesheet texture, for batching; None for debug groups
+    sheet_id: Option<wgpu::Id<wgpu::Texture>>,
+    sprite_bind_group: wgpu::BindGroup,
+    outline: Option<OutlineSet>,
+    highlighted: HighlightSet,
+    emissive_bind_group: Option<wgpu::BindGroup>,
+    material: Option<GroupMaterial>,
+    depth_mode: DepthMode,
+    blend_mode: BlendMode,
+    // Groups are drawn in order of layer, then index
+    layer: i32,
+    // The visible sprites of an alpha-blended group, farthest first
+    sorted: Option<SortedSprites>,
+    // Whether sorted is out of date with the group's sprites
+    unsorted: bool,
+    coordinate_mode: CoordinateMode,
+    // Instances' worth of buffer space kept by reserve_sprite_group
+    reserved: usize,
+    // Like sprite_bind_group, but with each camera view's buffer in
+    // place of the group's camera
+    view_bind_groups: Vec<Option<wgpu::BindGroup>>,
+    // The only ranges of sprites to draw, if the group is culled
+    visible: Option<Vec<Range<usize>>>,
+}
+
+// The pipelines drawn with by groups using a non-default depth or
+// blend mode
+struct ModePipelines {
+    depth_mode: DepthMode,
+    blend_mode: BlendMode,
+    pipeline: wgpu::RenderPipeline,
+    emissive_main_pipeline: wgpu::RenderPipeline,
+}
+
+// A copy of some of a group's sprites in drawing order, with bind
+// groups for the group's camera and each camera view
+struct SortedSprites {
+    world_buffer: wgpu::Buffer,
+    sheet_buffer: wgpu::Buffer,
+    tint_buffer: wgpu::Buffer,
+    sprite_bind_group: wgpu::BindGroup,
+    view_bind_groups: Vec<Option<wgpu::BindGroup>>,
+    capacity: usize,
+    // The group's sprite drawn at each sorted index
+    order: Vec<usize>,
+}
+
+// An extra camera which sprite groups can be drawn with instead of
+// their own (see SpriteRenderer::add_camera_view)
+struct CameraView {
+    camera: Camera2D,
+    buffer: wgpu::Buffer,
+}
+
+// A run of small sprite groups sharing a spritesheet and camera,
+// drawn together from their own pair of instance buffers
+struct SpriteBatch {
+    // Member groups in drawing order, with where each one's sprites
+    // start in the batch's buffers
+    members: Vec<(usize, usize)>,
+    len: usize,
+    world_buffer: wgpu::Buffer,
+    sheet_buffer: wgpu::Buffer,
+    tint_buffer: wgpu::Buffer,
     sprite_bind_group: wgpu::BindGroup,
 }
 
@@ -154,21 +485,72 @@ struct SpriteGroup {
 /// its transform.  All groups render into the same depth
 /// buffer, so their outputs are interleaved.
 pub struct SpriteRenderer {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
     pipeline: wgpu::RenderPipeline,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    // The multisample count of the main pass
+    sample_count: u32,
+    // The render target size used by screen-space groups
+    screen_size: [f32; 2],
+    outline_pipeline_layout: wgpu::PipelineLayout,
+    outline_pipeline: wgpu::RenderPipeline,
+    outline_bind_group_layout: wgpu::BindGroupLayout,
+    // Only its color is used
+    highlight_color: OutlineSet,
+    debug_view: DebugView,
+    // Created the first time a debug view is turned on
+    debug_pipelines: Option<DebugPipelines>,
+    // One tint color per group, for DebugView::GroupTint
+    debug_tints: Vec<OutlineSet>,
+    // The sheet layer and region outlines shown by DebugView::Atlas
+    debug_atlas: Option<(SpriteGroup, OutlineSet)>,
+    pick_pipeline_layout: wgpu::PipelineLayout,
+    pick_pipeline: wgpu::RenderPipeline,
+    pick_bind_group_layout: wgpu::BindGroupLayout,
+    pick_table: PickTable,
+    // The first pick table entry of each group
+    pick_first_entries: Vec<usize>,
+    // Groups with emissive layers are drawn with these pipelines
+    emissive_bind_group_layout: wgpu::BindGroupLayout,
+    emissive_pipeline_layout: wgpu::PipelineLayout,
+    emissive_main_pipeline: wgpu::RenderPipeline,
+    emissive_pipeline: wgpu::RenderPipeline,
+    emissive_occluder_pipeline: wgpu::RenderPipeline,
+    mode_pipelines: Vec<ModePipelines>,
+    // Bound to the unused bind group index before a material's bindings
+    empty_bind_group_layout: wgpu::BindGroupLayout,
+    empty_bind_group: wgpu::BindGroup,
     sprite_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     groups: Vec<Option<SpriteGroup>>,
     free_groups: Vec<usize>,
-    use_storage: bool,
+    instance_storage: InstanceStorage,
+    growth: GrowthPolicy,
+    // Groups with at most this many sprites may be batched together
+    batch_limit: usize,
+    batches: Vec<SpriteBatch>,
+    // The batch, if any, each group is drawn in
+    group_batches: Vec<Option<usize>>,
+    camera_views: Vec<Option<CameraView>>,
+    // Set by crate::Renderer, which sorts alpha-blended groups once
+    // per upload through its staging belt
+    deferred_sorting: bool,
 }
 
 impl SpriteRenderer {
-    /// Create a new [`SpriteRenderer`] meant to draw into the given color target and with the given depth texture format.
+    /// Create a new [`SpriteRenderer`] meant to draw into the given
+    /// color target and with the given depth texture format.  If
+    /// `depth_format` is `None`, sprites are drawn without depth
+    /// testing in painter's order (group by group, by layer and then
+    /// index, and in order within each group).
     pub fn new(
         gpu: &WGPU,
         color_target: wgpu::ColorTargetState,
-        depth_format: wgpu::TextureFormat,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
     ) -> Self {
+        let depth_format = depth_format.into();
         let shader = gpu
             .device()
             .create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -227,49 +609,88 @@ impl SpriteRenderer {
             // No count, not a buffer array binding
             count: None,
         };
-        let use_storage = gpu.supports_storage();
-        let sprite_bind_group_layout = if use_storage {
-            gpu.device()
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: None,
-                    entries: &[
-                        camera_layout_entry,
-                        wgpu::BindGroupLayoutEntry {
-                            // This matches the binding in the shader
-                            binding: 1,
-                            // Available in vertex shader
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            // It's a buffer
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
+        let instance_storage = gpu.instance_storage();
+        let sprite_bind_group_layout = match instance_storage {
+            InstanceStorage::StorageBuffer => {
+                gpu.device()
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: None,
+                        entries: &[
+                            camera_layout_entry,
+                            wgpu::BindGroupLayoutEntry {
+                                // This matches the binding in the shader
+                                binding: 1,
+                                // Available in vertex shader
+                                visibility: wgpu::ShaderStages::VERTEX,
+                                // It's a buffer
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                // No count, not a buffer array binding
+                                count: None,
                             },
-                            // No count, not a buffer array binding
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            // This matches the binding in the shader
-                            binding: 2,
-                            // Available in vertex shader
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            // It's a buffer
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
+                            wgpu::BindGroupLayoutEntry {
+                                // This matches the binding in the shader
+                                binding: 2,
+                                // Available in vertex shader
+                                visibility: wgpu::ShaderStages::VERTEX,
+                                // It's a buffer
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                // No count, not a buffer array binding
+                                count: None,
                             },
-                            // No count, not a buffer array binding
-                            count: None,
-                        },
-                    ],
-                })
-        } else {
-            gpu.device()
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: None,
-                    entries: &[camera_layout_entry],
-                })
+                            // The tints
+                            wgpu::BindGroupLayoutEntry {
+                                binding: 5,
+                                visibility: wgpu::ShaderStages::VERTEX,
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                count: None,
+                            },
+                        ],
+                    })
+            }
+            InstanceStorage::VertexBuffer => {
+                gpu.device()
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: None,
+                        entries: &[camera_layout_entry],
+                    })
+            }
+            InstanceStorage::UniformChunks => {
+                // One chunk of each buffer is bound at a time using a
+                // dynamic offset.
+                let chunk_layout_entry = |binding, stride: usize| wgpu::BindGroupLayoutEntry {
+                    // These match the uniform bindings in the shader
+                    binding,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new((UNIFORM_CHUNK * stride) as u64),
+                    },
+                    count: None,
+                };
+                gpu.device()
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: None,
+                        entries: &[
+                            camera_layout_entry,
+                            chunk_layout_entry(3, std::mem::size_of::<Transform>()),
+                            chunk_layout_entry(4, std::mem::size_of::<SheetRegion>()),
+                            chunk_layout_entry(6, std::mem::size_of::<[u8; 4]>()),
+                        ],
+                    })
+            }
         };
         let pipeline_layout =
             gpu.device()
@@ -281,19 +702,290 @@ impl SpriteRenderer {
 
         assert_eq!(std::mem::size_of::<Transform>(), 4 * 4);
         assert_eq!(std::mem::size_of::<SheetRegion>(), 4 * 4);
-        let pipeline = gpu
-            .device()
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            instance_storage,
+            "fs_main",
+            color_target.clone(),
+            depth_format,
+            1,
+        );
+        let outline_bind_group_layout = crate::outline::color_bind_group_layout(gpu);
+        let outline_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &sprite_bind_group_layout,
+                        &texture_bind_group_layout,
+                        &outline_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+        let outline_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &outline_pipeline_layout,
+            instance_storage,
+            "fs_outline_mask",
+            crate::outline::mask_color_target(),
+            None,
+            1,
+        );
+        let pick_bind_group_layout = crate::pick::table_bind_group_layout(gpu);
+        let pick_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &sprite_bind_group_layout,
+                        &texture_bind_group_layout,
+                        &pick_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+        let pick_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pick_pipeline_layout,
+            instance_storage,
+            "fs_pick",
+            crate::pick::id_color_target(),
+            depth_format,
+            1,
+        );
+        let emissive_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    // The emissive layers are sampled with the sheet's sampler
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+        let emissive_pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &sprite_bind_group_layout,
+                        &texture_bind_group_layout,
+                        &emissive_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+        let emissive_main_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &emissive_pipeline_layout,
+            instance_storage,
+            "fs_main_emissive",
+            color_target.clone(),
+            depth_format,
+            1,
+        );
+        let emissive_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &emissive_pipeline_layout,
+            instance_storage,
+            "fs_emissive",
+            color_target.format.into(),
+            depth_format,
+            1,
+        );
+        let emissive_occluder_pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            instance_storage,
+            "fs_emissive_occluder",
+            color_target.format.into(),
+            depth_format,
+            1,
+        );
+        let (empty_bind_group_layout, empty_bind_group) = crate::material::empty_bind_group(gpu);
+        let highlight_color = OutlineSet::new(
+            gpu,
+            &outline_bind_group_layout,
+            crate::highlight::Highlight::default().color,
+            0..0,
+        );
+
+        Self {
+            shader,
+            pipeline_layout,
+            pipeline,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            screen_size: [1.0, 1.0],
+            outline_pipeline_layout,
+            outline_pipeline,
+            outline_bind_group_layout,
+            highlight_color,
+            debug_view: DebugView::Off,
+            debug_pipelines: None,
+            debug_tints: vec![],
+            debug_atlas: None,
+            pick_pipeline_layout,
+            pick_pipeline,
+            pick_bind_group_layout,
+            pick_table: PickTable::new(gpu),
+            pick_first_entries: vec![],
+            emissive_bind_group_layout,
+            emissive_pipeline_layout,
+            emissive_main_pipeline,
+            emissive_pipeline,
+            emissive_occluder_pipeline,
+            mode_pipelines: vec![],
+            empty_bind_group_layout,
+            empty_bind_group,
+            instance_storage,
+            free_groups: Vec::new(),
+            groups: Vec::with_capacity(4),
+            growth: GrowthPolicy::default(),
+            batch_limit: 64,
+            batches: vec![],
+            group_batches: vec![],
+            camera_views: vec![],
+            deferred_sorting: false,
+            sprite_bind_group_layout,
+            texture_bind_group_layout,
+        }
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        instance_storage: InstanceStorage,
+        fs_entry: &str,
+        color_target: wgpu::ColorTargetState,
+        depth_format: Option<wgpu::TextureFormat>,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline_with_depth(
+            gpu,
+            shader,
+            pipeline_layout,
+            instance_storage,
+            fs_entry,
+            color_target,
+            Self::depth_stencil(depth_format, DepthMode::default()),
+            sample_count,
+        )
+    }
+    fn depth_stencil(
+        depth_format: Option<wgpu::TextureFormat>,
+        depth_mode: DepthMode,
+    ) -> Option<wgpu::DepthStencilState> {
+        depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: depth_mode.write,
+            depth_compare: if depth_mode.test {
+                wgpu::CompareFunction::Less
+            } else {
+                wgpu::CompareFunction::Always
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    }
+    fn create_mode_pipelines(
+        &self,
+        gpu: &WGPU,
+        depth_mode: DepthMode,
+        blend_mode: BlendMode,
+    ) -> ModePipelines {
+        let depth_stencil =
+            Self::depth_stencil(self.depth_format, blend_mode.depth_mode(depth_mode));
+        let color_target = blend_mode.color_target(&self.color_target);
+        let (fs_main, fs_main_emissive) = blend_mode.fs_entries();
+        ModePipelines {
+            depth_mode,
+            blend_mode,
+            pipeline: Self::create_pipeline_with_depth(
+                gpu,
+                &self.shader,
+                &self.pipeline_layout,
+                self.instance_storage,
+                fs_main,
+                color_target.clone(),
+                depth_stencil.clone(),
+                self.sample_count,
+            ),
+            emissive_main_pipeline: Self::create_pipeline_with_depth(
+                gpu,
+                &self.shader,
+                &self.emissive_pipeline_layout,
+                self.instance_storage,
+                fs_main_emissive,
+                color_target,
+                depth_stencil,
+                self.sample_count,
+            ),
+        }
+    }
+    // The main and emissive main pipelines for a depth and blend mode
+    fn main_pipelines(
+        &self,
+        depth_mode: DepthMode,
+        blend_mode: BlendMode,
+    ) -> (&wgpu::RenderPipeline, &wgpu::RenderPipeline) {
+        self.mode_pipelines
+            .iter()
+            .find(|pipelines| {
+                pipelines.depth_mode == depth_mode && pipelines.blend_mode == blend_mode
+            })
+            .map(|pipelines| (&pipelines.pipeline, &pipelines.emissive_main_pipeline))
+            .unwrap_or((&self.pipeline, &self.emissive_main_pipeline))
+    }
+    // Makes sure the pipelines for a depth and blend mode exist
+    fn ensure_mode_pipelines(&mut self, gpu: &WGPU, depth_mode: DepthMode, blend_mode: BlendMode) {
+        if (depth_mode, blend_mode) != (DepthMode::default(), BlendMode::default())
+            && !self.mode_pipelines.iter().any(|pipelines| {
+                pipelines.depth_mode == depth_mode && pipelines.blend_mode == blend_mode
+            })
+        {
+            let pipelines = self.create_mode_pipelines(gpu, depth_mode, blend_mode);
+            self.mode_pipelines.push(pipelines);
+        }
+    }
+    // Pipelines drawn in the main pass use the renderer's sample
+    // count; pick, outline, and glow pipelines always use 1.
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline_with_depth(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        instance_storage: InstanceStorage,
+        fs_entry: &str,
+        color_target: wgpu::ColorTargetState,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: None,
-                layout: Some(&pipeline_layout),
+                layout: Some(pipeline_layout),
                 vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: if use_storage {
-                        "vs_storage_main"
-                    } else {
-                        "vs_vbuf_main"
+                    module: shader,
+                    entry_point: match instance_storage {
+                        InstanceStorage::StorageBuffer => "vs_storage_main",
+                        InstanceStorage::VertexBuffer => "vs_vbuf_main",
+                        InstanceStorage::UniformChunks => "vs_uniform_main",
                     },
-                    buffers: if use_storage {
+                    buffers: if instance_storage != InstanceStorage::VertexBuffer {
                         &[]
                     } else {
                         &[
@@ -321,12 +1013,22 @@ impl SpriteRenderer {
                                     shader_location: 1,
                                 }],
                             },
+                            // Tints are unpacked in the shader, like colormods
+                            wgpu::VertexBufferLayout {
+                                array_stride: std::mem::size_of::<[u8; 4]>() as u64,
+                                step_mode: wgpu::VertexStepMode::Instance,
+                                attributes: &[wgpu::VertexAttribute {
+                                    format: wgpu::VertexFormat::Uint32,
+                                    offset: 0,
+                                    shader_location: 2,
+                                }],
+                            },
                         ]
                     },
                 },
                 fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
+                    module: shader,
+                    entry_point: fs_entry,
                     targets: &[Some(color_target)],
                 }),
                 primitive: wgpu::PrimitiveState {
@@ -335,29 +1037,211 @@ impl SpriteRenderer {
                     cull_mode: Some(wgpu::Face::Back),
                     ..Default::default()
                 },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: depth_format,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState::default(),
+                depth_stencil,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
                 multiview: None,
-            });
-
-        Self {
-            pipeline,
-            use_storage,
-            free_groups: Vec::new(),
-            groups: Vec::with_capacity(4),
-            sprite_bind_group_layout,
-            texture_bind_group_layout,
+            })
+    }
+    /// Changes the multisample count of the render pass this renderer
+    /// draws into with [`SpriteRenderer::render`], re-creating its
+    /// pipelines.  The default is 1 (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target this renderer draws into (e.g. for a
+    /// new color format), re-creating its pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// The multisample count this renderer draws with.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    /// Changes the depth format this renderer draws with,
+    /// re-creating the pipeline.  `None` means sprites will be drawn
+    /// without a depth buffer, in painter's order.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.instance_storage,
+            "fs_main",
+            self.color_target.clone(),
+            depth_format,
+            self.sample_count,
+        );
+        self.pick_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pick_pipeline_layout,
+            self.instance_storage,
+            "fs_pick",
+            crate::pick::id_color_target(),
+            depth_format,
+            1,
+        );
+        self.emissive_main_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.emissive_pipeline_layout,
+            self.instance_storage,
+            "fs_main_emissive",
+            self.color_target.clone(),
+            depth_format,
+            self.sample_count,
+        );
+        self.emissive_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.emissive_pipeline_layout,
+            self.instance_storage,
+            "fs_emissive",
+            self.color_target.format.into(),
+            depth_format,
+            1,
+        );
+        self.emissive_occluder_pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.instance_storage,
+            "fs_emissive_occluder",
+            self.color_target.format.into(),
+            depth_format,
+            1,
+        );
+        if self.debug_pipelines.is_some() {
+            self.debug_pipelines = Some(DebugPipelines::new(self, gpu));
+        }
+        self.mode_pipelines = std::mem::take(&mut self.mode_pipelines)
+            .into_iter()
+            .map(|pipelines| {
+                self.create_mode_pipelines(gpu, pipelines.depth_mode, pipelines.blend_mode)
+            })
+            .collect();
+        for which in 0..self.groups.len() {
+            let Some(material) = self.groups[which]
+                .as_ref()
+                .and_then(|group| group.material.as_ref())
+                .map(|m| m.material.clone())
+            else {
+                continue;
+            };
+            self.set_material(gpu, which, Some(&material));
+        }
+    }
+    /// Returns the depth format this renderer draws with, if any.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_format
+    }
+    // Transform and SheetRegion are the same size, so this works for both
+    fn instance_buffer_len(&self, len: usize) -> usize {
+        match self.instance_storage {
+            // Round up to whole chunks so the last chunk can be bound in full
+            InstanceStorage::UniformChunks => len.div_ceil(UNIFORM_CHUNK).max(1) * UNIFORM_CHUNK,
+            _ => len,
         }
     }
+    fn instance_buffer_size(&self, len: usize) -> u64 {
+        (self.instance_buffer_len(len) * std::mem::size_of::<Transform>()) as u64
+    }
+    fn create_instance_buffer(&self, gpu: &WGPU, len: usize) -> wgpu::Buffer {
+        self.create_buffer(gpu, self.instance_buffer_size(len))
+    }
+    fn create_tint_buffer(&self, gpu: &WGPU, len: usize) -> wgpu::Buffer {
+        let size = self.instance_buffer_len(len) * std::mem::size_of::<[u8; 4]>();
+        // Storage buffers can't be empty
+        self.create_buffer(gpu, size.max(std::mem::size_of::<[u8; 4]>()) as u64)
+    }
+    fn create_buffer(&self, gpu: &WGPU, size: u64) -> wgpu::Buffer {
+        let usage = match self.instance_storage {
+            InstanceStorage::StorageBuffer => wgpu::BufferUsages::STORAGE,
+            InstanceStorage::VertexBuffer => wgpu::BufferUsages::VERTEX,
+            InstanceStorage::UniformChunks => wgpu::BufferUsages::UNIFORM,
+        };
+        gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    // Checks that instance buffers for `len` sprites fit in the device's limits
+    fn check_instances(&self, gpu: &WGPU, len: usize) -> Result<(), LimitError> {
+        gpu.check_buffer(
+            "sprite instance buffer",
+            self.instance_buffer_size(len),
+            self.instance_storage == InstanceStorage::StorageBuffer,
+        )
+    }
+    fn create_sprite_bind_group(
+        &self,
+        gpu: &WGPU,
+        camera_buffer: &wgpu::Buffer,
+        world_buffer: &wgpu::Buffer,
+        sheet_buffer: &wgpu::Buffer,
+        tint_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        fn chunk_binding(
+            binding: u32,
+            buffer: &wgpu::Buffer,
+            stride: usize,
+        ) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new((UNIFORM_CHUNK * stride) as u64),
+                }),
+            }
+        }
+        let camera_binding = wgpu::BindGroupEntry {
+            binding: 0,
+            resource: camera_buffer.as_entire_binding(),
+        };
+        let entries = match self.instance_storage {
+            InstanceStorage::StorageBuffer => vec![
+                camera_binding,
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: world_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sheet_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: tint_buffer.as_entire_binding(),
+                },
+            ],
+            InstanceStorage::VertexBuffer => vec![camera_binding],
+            InstanceStorage::UniformChunks => vec![
+                camera_binding,
+                chunk_binding(3, world_buffer, std::mem::size_of::<Transform>()),
+                chunk_binding(4, sheet_buffer, std::mem::size_of::<SheetRegion>()),
+                chunk_binding(6, tint_buffer, std::mem::size_of::<[u8; 4]>()),
+            ],
+        };
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.sprite_bind_group_layout,
+            entries: &entries,
+        })
+    }
     /// Create a new sprite group sized to fit `world_transforms` and
     /// `sheet_regions`, which should be the same length.  Returns the
-    /// sprite group index corresponding to this group.
+    /// sprite group index corresponding to this group.  Panics if
+    /// the group would exceed the device's limits (see
+    /// [`SpriteRenderer::try_add_sprite_group`]).
     pub fn add_sprite_group(
         &mut self,
         gpu: &WGPU,
@@ -366,9 +1250,27 @@ impl SpriteRenderer {
         sheet_regions: Vec<SheetRegion>,
         camera: Camera2D,
     ) -> usize {
+        self.try_add_sprite_group(gpu, tex, world_transforms, sheet_regions, camera)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`SpriteRenderer::add_sprite_group`], but returns an
+    /// error naming the exceeded limit if the texture has too many
+    /// layers or the group's instance buffers would be too large for
+    /// the device.
+    pub fn try_add_sprite_group(
+        &mut self,
+        gpu: &WGPU,
+        tex: &wgpu::Texture,
+        world_transforms: Vec<Transform>,
+        sheet_regions: Vec<SheetRegion>,
+        camera: Camera2D,
+    ) -> Result<usize, LimitError> {
+        trace_span!("sprites::add_group", len = world_transforms.len());
         if gpu.is_gl() && (tex.depth_or_array_layers() == 1 || tex.depth_or_array_layers() == 6) {
             panic!("Array textures with 1 or 6 layers aren't supported in webgl or other GL backends {:?}", tex);
         }
+        gpu.check_texture_layers(tex)?;
+        self.check_instances(gpu, world_transforms.len().max(sheet_regions.len()))?;
         let group_idx = if let Some(idx) = self.free_groups.pop() {
             idx
         } else {
@@ -384,6 +1286,28 @@ impl SpriteRenderer {
             },
             ..Default::default()
         });
+        let mut group = self.create_group(
+            gpu,
+            Arc::new(view_sprite),
+            tex.size(),
+            world_transforms,
+            sheet_regions,
+            camera,
+        );
+        group.sheet_id = Some(tex.global_id());
+        self.groups[group_idx] = Some(group);
+        self.rebatch(gpu);
+        Ok(group_idx)
+    }
+    fn create_group(
+        &self,
+        gpu: &WGPU,
+        view_sprite: Arc<wgpu::TextureView>,
+        sheet_size: wgpu::Extent3d,
+        world_transforms: Vec<Transform>,
+        sheet_regions: Vec<SheetRegion>,
+        camera: Camera2D,
+    ) -> SpriteGroup {
         let sampler_sprite = gpu
             .device()
             .create_sampler(&wgpu::SamplerDescriptor::default());
@@ -402,78 +1326,83 @@ impl SpriteRenderer {
                 },
             ],
         });
-        let buffer_world = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: world_transforms.len() as u64 * std::mem::size_of::<Transform>() as u64,
-            usage: if self.use_storage {
-                wgpu::BufferUsages::STORAGE
-            } else {
-                wgpu::BufferUsages::VERTEX
-            } | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let buffer_sheet = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: sheet_regions.len() as u64 * std::mem::size_of::<SheetRegion>() as u64,
-            usage: if self.use_storage {
-                wgpu::BufferUsages::STORAGE
-            } else {
-                wgpu::BufferUsages::VERTEX
-            } | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let buffer_world = self.create_instance_buffer(gpu, world_transforms.len());
+        let buffer_sheet = self.create_instance_buffer(gpu, sheet_regions.len());
+        let tints = vec![NO_TINT; world_transforms.len()];
+        let buffer_tint = self.create_tint_buffer(gpu, tints.len());
         let camera_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: std::mem::size_of::<Camera2D>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
-        let sprite_bind_group = if self.use_storage {
-            gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &self.sprite_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: camera_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: buffer_world.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: buffer_sheet.as_entire_binding(),
-                    },
-                ],
-            })
-        } else {
-            gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &self.sprite_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }],
-            })
-        };
+        let sprite_bind_group = self.create_sprite_bind_group(
+            gpu,
+            &camera_buffer,
+            &buffer_world,
+            &buffer_sheet,
+            &buffer_tint,
+        );
+        let view_bind_groups =
+            self.create_view_bind_groups(gpu, &buffer_world, &buffer_sheet, &buffer_tint);
         gpu.queue()
             .write_buffer(&buffer_world, 0, bytemuck::cast_slice(&world_transforms));
         gpu.queue()
             .write_buffer(&buffer_sheet, 0, bytemuck::cast_slice(&sheet_regions));
+        gpu.queue()
+            .write_buffer(&buffer_tint, 0, bytemuck::cast_slice(&tints));
         gpu.queue()
             .write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&camera));
-        self.groups[group_idx] = Some(SpriteGroup {
+        SpriteGroup {
             world_buffer: buffer_world,
             sheet_buffer: buffer_sheet,
+            tint_buffer: buffer_tint,
             world_transforms,
             sheet_regions,
+            tints,
             tex_bind_group,
+            sheet_view: view_sprite,
+            sheet_size,
+            sheet_id: None,
             sprite_bind_group,
             camera,
             camera_buffer,
-        });
-        group_idx
+            outline: None,
+            highlighted: HighlightSet::default(),
+            emissive_bind_group: None,
+            material: None,
+            depth_mode: DepthMode::default(),
+            blend_mode: BlendMode::default(),
+            layer: 0,
+            sorted: None,
+            unsorted: false,
+            coordinate_mode: CoordinateMode::World,
+            reserved: 0,
+            view_bind_groups,
+            visible: None,
+        }
+    }
+    fn create_view_bind_groups(
+        &self,
+        gpu: &WGPU,
+        world_buffer: &wgpu::Buffer,
+        sheet_buffer: &wgpu::Buffer,
+        tint_buffer: &wgpu::Buffer,
+    ) -> Vec<Option<wgpu::BindGroup>> {
+        self.camera_views
+            .iter()
+            .map(|view| {
+                view.as_ref().map(|view| {
+                    self.create_sprite_bind_group(
+                        gpu,
+                        &view.buffer,
+                        world_buffer,
+                        sheet_buffer,
+                        tint_buffer,
+                    )
+                })
+            })
+            .collect()
     }
     /// Returns the number of sprite groups (including placeholders for removed groups).
     pub fn sprite_group_count(&self) -> usize {
@@ -482,78 +1411,152 @@ impl SpriteRenderer {
     /// Deletes a sprite group, leaving an empty group slot behind (this might get recycled later).
     pub fn remove_sprite_group(&mut self, which: usize) {
         if self.groups[which].is_some() {
+            self.unbatch(which);
             self.groups[which] = None;
             self.free_groups.push(which);
         }
     }
+    /// Returns true if the given sprite group is populated (i.e. it
+    /// exists and hasn't been removed).
+    pub fn has_sprite_group(&self, which: usize) -> bool {
+        self.groups.get(which).is_some_and(|group| group.is_some())
+    }
     /// Reports the size of the given sprite group.  Panics if the given sprite group is not populated.
     pub fn sprite_group_size(&self, which: usize) -> usize {
         self.groups[which].as_ref().unwrap().world_transforms.len()
     }
     /// Resizes a sprite group.  If the new size is smaller, this is
-    /// very cheap; if it's larger than it's ever been before, it
-    /// might involve reallocating the [`Vec<Transform>`],
-    /// [`Vec<SheetRegion>`], or the GPU buffer used to draw sprites,
+    /// very cheap; if it's larger than the group's GPU buffers can
+    /// hold, it might involve reallocating the [`Vec<Transform>`],
+    /// [`Vec<SheetRegion>`], or the GPU buffers used to draw sprites,
     /// so it could be expensive.  If this happens, the buffer will
     /// also be uploaded to prevent garbage data from being used in
     /// the shader.  To avoid redundant work, resize upwards as few
-    /// times as possible.
+    /// times as possible, or reserve space with
+    /// [`SpriteRenderer::reserve_sprite_group`].  How much room
+    /// buffers get when they grow, and whether they shrink, is
+    /// decided by the renderer's [`GrowthPolicy`].
     ///
-    /// Panics if the given sprite group is not populated.
+    /// Panics if the given sprite group is not populated, or if its
+    /// buffers would exceed the device's limits (see
+    /// [`SpriteRenderer::try_resize_sprite_group`]).
     pub fn resize_sprite_group(&mut self, gpu: &WGPU, which: usize, len: usize) -> usize {
-        let group = &mut self.groups[which].as_mut().unwrap();
+        self.try_resize_sprite_group(gpu, which, len)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+    /// Like [`SpriteRenderer::resize_sprite_group`], but returns an
+    /// error naming the exceeded limit (leaving the group as it was)
+    /// if the group's instance buffers would be too large for the
+    /// device.  Panics if the given sprite group is not populated.
+    pub fn try_resize_sprite_group(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        len: usize,
+    ) -> Result<usize, LimitError> {
+        let group = self.groups[which].as_ref().unwrap();
         let old_len = group.world_transforms.len();
         if old_len == len {
-            return old_len;
+            return Ok(old_len);
         }
+        trace_span!("sprites::resize_group", which, old_len, len);
         assert_eq!(old_len, group.sheet_regions.len());
+        let capacity = self.sprite_group_capacity(which);
+        let new_capacity = self.growth.resized_capacity(len, capacity, group.reserved);
+        if new_capacity.is_some() {
+            self.check_instances(gpu, len)?;
+        }
+        // Don't let the growth policy's extra room push a buffer past the limits
+        let new_capacity = new_capacity.map(|capacity| match self.check_instances(gpu, capacity) {
+            Ok(()) => capacity,
+            Err(_) => len,
+        });
+        self.resize_sprite_group_buffers(gpu, which, len, new_capacity);
+        self.sort_sprites(gpu, which);
+        self.rebatch(gpu);
+        Ok(old_len)
+    }
+    /// Makes sure the given sprite group's GPU buffers have room for
+    /// at least `capacity` sprites, so that resizing the group up to
+    /// that size won't reallocate them.  Calling this right after
+    /// creating a group avoids reallocations as it fills up; the
+    /// space is kept even if the [`GrowthPolicy`] would shrink the
+    /// buffers.  Panics if the given sprite group is not populated
+    /// or if `capacity` sprites wouldn't fit in the device's limits.
+    pub fn reserve_sprite_group(&mut self, gpu: &WGPU, which: usize, capacity: usize) {
+        if let Err(err) = self.check_instances(gpu, capacity) {
+            panic!("{err}");
+        }
+        let group = self.groups[which].as_mut().unwrap();
+        group.reserved = capacity;
+        let len = group.world_transforms.len();
+        if capacity > self.sprite_group_capacity(which) {
+            self.resize_sprite_group_buffers(gpu, which, len, Some(capacity));
+        }
+    }
+    /// Returns how many sprites the given sprite group's GPU buffers
+    /// can hold without being reallocated.  Panics if the given
+    /// sprite group is not populated.
+    pub fn sprite_group_capacity(&self, which: usize) -> usize {
+        self.groups[which].as_ref().unwrap().world_buffer.size() as usize
+            / std::mem::size_of::<Transform>()
+    }
+    /// Sets how sprite groups' GPU buffers grow and shrink as they're resized.
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.growth = growth;
+    }
+    /// Returns how sprite groups' GPU buffers grow and shrink as they're resized.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth
+    }
+    fn resize_sprite_group_buffers(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        len: usize,
+        new_capacity: Option<usize>,
+    ) {
+        let group = self.groups[which].as_ref().unwrap();
+        // realloc buffer if needed, remake sprite_bind_group since it may refer to the buffers
+        let buffers = new_capacity.map(|capacity| {
+            let world_buffer = self.create_instance_buffer(gpu, capacity);
+            let sheet_buffer = self.create_instance_buffer(gpu, capacity);
+            let tint_buffer = self.create_tint_buffer(gpu, capacity);
+            let sprite_bind_group = self.create_sprite_bind_group(
+                gpu,
+                &group.camera_buffer,
+                &world_buffer,
+                &sheet_buffer,
+                &tint_buffer,
+            );
+            let view_bind_groups =
+                self.create_view_bind_groups(gpu, &world_buffer, &sheet_buffer, &tint_buffer);
+            (
+                world_buffer,
+                sheet_buffer,
+                tint_buffer,
+                sprite_bind_group,
+                view_bind_groups,
+            )
+        });
+        let group = self.groups[which].as_mut().unwrap();
         // shrink or grow sprite vecs
         group.world_transforms.resize(len, Transform::zeroed());
         group.sheet_regions.resize(len, SheetRegion::zeroed());
-        // realloc buffer if needed, remake sprite_bind_group if using storage buffers
-        let new_size = len * std::mem::size_of::<Transform>();
-        if new_size > group.world_buffer.size() as usize {
-            group.world_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: new_size as u64,
-                usage: if self.use_storage {
-                    wgpu::BufferUsages::STORAGE
-                } else {
-                    wgpu::BufferUsages::VERTEX
-                } | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-            group.sheet_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: new_size as u64,
-                usage: if self.use_storage {
-                    wgpu::BufferUsages::STORAGE
-                } else {
-                    wgpu::BufferUsages::VERTEX
-                } | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-            if self.use_storage {
-                group.sprite_bind_group =
-                    gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: None,
-                        layout: &self.sprite_bind_group_layout,
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: group.camera_buffer.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 1,
-                                resource: group.world_buffer.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 2,
-                                resource: group.sheet_buffer.as_entire_binding(),
-                            },
-                        ],
-                    });
-            };
+        group.tints.resize(len, NO_TINT);
+        if let Some((
+            world_buffer,
+            sheet_buffer,
+            tint_buffer,
+            sprite_bind_group,
+            view_bind_groups,
+        )) = buffers
+        {
+            group.world_buffer = world_buffer;
+            group.sheet_buffer = sheet_buffer;
+            group.tint_buffer = tint_buffer;
+            group.sprite_bind_group = sprite_bind_group;
+            group.view_bind_groups = view_bind_groups;
             gpu.queue().write_buffer(
                 &group.world_buffer,
                 0,
@@ -564,52 +1567,478 @@ impl SpriteRenderer {
                 0,
                 bytemuck::cast_slice(&group.sheet_regions),
             );
+            gpu.queue()
+                .write_buffer(&group.tint_buffer, 0, bytemuck::cast_slice(&group.tints));
         }
-        old_len
     }
     /// Set the given camera transform on all sprite groups.  Uploads to the GPU.
     pub fn set_camera_all(&mut self, gpu: &WGPU, camera: Camera2D) {
-        for sg_index in 0..self.groups.len() {
-            if self.groups[sg_index].is_some() {
-                self.set_camera(gpu, sg_index, camera);
-            }
+        for sg in self.groups.iter_mut().flatten() {
+            sg.camera = camera;
         }
+        self.write_cameras(gpu);
+        self.rebatch(gpu);
     }
     /// Set the given camera transform on a specific sprite group.  Uploads to the GPU.
     /// Panics if the given sprite group is not populated.
     pub fn set_camera(&mut self, gpu: &WGPU, which: usize, camera: Camera2D) {
-        let sg = &mut self.groups[which].as_mut().unwrap();
-        sg.camera = camera;
+        self.groups[which].as_mut().unwrap().camera = camera;
+        self.write_camera(gpu, which);
+        self.rebatch(gpu);
+    }
+    // The camera a group is actually drawn with
+    fn group_camera(&self, group: &SpriteGroup) -> Camera2D {
+        match group.coordinate_mode {
+            CoordinateMode::World => group.camera,
+            CoordinateMode::ScreenPixels => Camera2D {
+                screen_pos: [0.0, 0.0],
+                screen_size: self.screen_size,
+            },
+            CoordinateMode::ScreenNormalized => Camera2D {
+                screen_pos: [0.0, 0.0],
+                screen_size: [1.0, 1.0],
+            },
+        }
+    }
+    fn write_camera(&self, gpu: &WGPU, which: usize) {
+        let group = self.groups[which].as_ref().unwrap();
+        gpu.queue().write_buffer(
+            &group.camera_buffer,
+            0,
+            bytemuck::bytes_of(&self.group_camera(group)),
+        );
+    }
+    fn write_cameras(&self, gpu: &WGPU) {
+        for which in 0..self.groups.len() {
+            if self.groups[which].is_some() {
+                self.write_camera(gpu, which);
+            }
+        }
+    }
+    /// Sets which coordinate space a sprite group's transforms are in.
+    /// Groups in screen space ignore their camera (and any camera
+    /// view they are drawn with), so HUD elements don't need a
+    /// stand-in camera kept in sync with the render target's size.
+    /// Panics if the given sprite group is not populated.
+    pub fn set_coordinate_mode(&mut self, gpu: &WGPU, which: usize, mode: CoordinateMode) {
+        self.groups[which].as_mut().unwrap().coordinate_mode = mode;
+        self.write_camera(gpu, which);
+        self.rebatch(gpu);
+    }
+    /// Returns which coordinate space a sprite group's transforms are in.
+    /// Panics if the given sprite group is not populated.
+    pub fn coordinate_mode(&self, which: usize) -> CoordinateMode {
+        self.groups[which].as_ref().unwrap().coordinate_mode
+    }
+    /// Returns the camera a sprite group is actually drawn with: its
+    /// own camera in [`CoordinateMode::World`], or the camera its
+    /// screen-space coordinate mode stands for.  Panics if the given
+    /// sprite group is not populated.
+    pub fn effective_camera(&self, which: usize) -> Camera2D {
+        self.group_camera(self.groups[which].as_ref().unwrap())
+    }
+    /// Limits which of a sprite group's sprites are drawn to the
+    /// given ranges (e.g. from [`crate::quadtree::SpriteQuadtree`]),
+    /// or draws all of them again if `visible` is `None`.  Ranges
+    /// past the end of the group are ignored, and culled groups are
+    /// never batched.  Camera views (see
+    /// [`SpriteRenderer::add_camera_view`]) still draw every sprite.
+    /// Panics if the given sprite group is not populated.
+    pub fn set_visible_ranges(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        visible: Option<Vec<Range<usize>>>,
+    ) {
+        let group = self.groups[which].as_mut().unwrap();
+        let was_culled = group.visible.is_some();
+        group.visible = visible;
+        let is_culled = group.visible.is_some();
+        self.sort_sprites(gpu, which);
+        if was_culled != is_culled {
+            self.rebatch(gpu);
+        }
+    }
+    /// Returns the ranges of a sprite group drawn, if it's culled
+    /// (see [`SpriteRenderer::set_visible_ranges`]).  Panics if the
+    /// given sprite group is not populated.
+    pub fn visible_ranges(&self, which: usize) -> Option<&[Range<usize>]> {
+        self.groups[which].as_ref().unwrap().visible.as_deref()
+    }
+    /// Sets the size of the render target in pixels, which
+    /// [`CoordinateMode::ScreenPixels`] groups are laid out in.  Call
+    /// this whenever the target is resized; [`crate::Renderer`] does
+    /// so automatically.
+    pub fn set_screen_size(&mut self, gpu: &WGPU, screen_size: [f32; 2]) {
+        if self.screen_size == screen_size {
+            return;
+        }
+        self.screen_size = screen_size;
+        self.write_cameras(gpu);
+        self.rebatch(gpu);
+    }
+    /// Returns the render target size used by screen-space groups.
+    pub fn screen_size(&self) -> [f32; 2] {
+        self.screen_size
+    }
+    /// Adds a camera view: an extra camera which any sprite group can
+    /// be drawn with using [`SpriteRenderer::render_with_view`]
+    /// (e.g. for a minimap or split screen), without changing the
+    /// groups' own cameras.  Returns the view's index.
+    pub fn add_camera_view(&mut self, gpu: &WGPU, camera: Camera2D) -> usize {
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<Camera2D>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue()
+            .write_buffer(&buffer, 0, bytemuck::bytes_of(&camera));
+        let view = self
+            .camera_views
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.camera_views.len());
+        let bind_groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|group| {
+                group.as_ref().map(|group| {
+                    let sorted = group.sorted.as_ref().map(|sorted| {
+                        self.create_sprite_bind_group(
+                            gpu,
+                            &buffer,
+                            &sorted.world_buffer,
+                            &sorted.sheet_buffer,
+                            &sorted.tint_buffer,
+                        )
+                    });
+                    let bind_group = self.create_sprite_bind_group(
+                        gpu,
+                        &buffer,
+                        &group.world_buffer,
+                        &group.sheet_buffer,
+                        &group.tint_buffer,
+                    );
+                    (bind_group, sorted)
+                })
+            })
+            .collect();
+        fn set_view(
+            bind_groups: &mut Vec<Option<wgpu::BindGroup>>,
+            view: usize,
+            bind_group: wgpu::BindGroup,
+        ) {
+            if bind_groups.len() <= view {
+                bind_groups.resize_with(view + 1, || None);
+            }
+            bind_groups[view] = Some(bind_group);
+        }
+        for (group, bind_groups) in self.groups.iter_mut().zip(bind_groups) {
+            let (Some(group), Some((bind_group, sorted_bind_group))) = (group, bind_groups) else {
+                continue;
+            };
+            set_view(&mut group.view_bind_groups, view, bind_group);
+            if let (Some(sorted), Some(bind_group)) = (group.sorted.as_mut(), sorted_bind_group) {
+                set_view(&mut sorted.view_bind_groups, view, bind_group);
+            }
+        }
+        if view == self.camera_views.len() {
+            self.camera_views.push(None);
+        }
+        self.camera_views[view] = Some(CameraView { camera, buffer });
+        view
+    }
+    /// Changes the camera of a camera view.  Uploads to the GPU.
+    /// Panics if the view doesn't exist.
+    pub fn set_camera_view(&mut self, gpu: &WGPU, view: usize, camera: Camera2D) {
+        let view = self.camera_views[view].as_mut().unwrap();
+        view.camera = camera;
         gpu.queue()
-            .write_buffer(&sg.camera_buffer, 0, bytemuck::bytes_of(&sg.camera));
+            .write_buffer(&view.buffer, 0, bytemuck::bytes_of(&camera));
+    }
+    /// Returns the camera of a camera view.  Panics if the view
+    /// doesn't exist.
+    pub fn camera_view(&self, view: usize) -> Camera2D {
+        self.camera_views[view].as_ref().unwrap().camera
+    }
+    /// Deletes a camera view; its index may be reused by a later view.
+    pub fn remove_camera_view(&mut self, view: usize) {
+        self.camera_views[view] = None;
+        for group in self.groups.iter_mut().flatten() {
+            if let Some(bind_group) = group.view_bind_groups.get_mut(view) {
+                *bind_group = None;
+            }
+            if let Some(bind_group) = group
+                .sorted
+                .as_mut()
+                .and_then(|sorted| sorted.view_bind_groups.get_mut(view))
+            {
+                *bind_group = None;
+            }
+        }
+    }
+    /// Sets the largest number of sprites a group can have and still
+    /// be batched together with its neighbors (64 by default, or 0 to
+    /// turn batching off).  Consecutive sprite groups (in index
+    /// order, skipping empty and removed ones) which are this small,
+    /// share a spritesheet texture, camera, and layer, and have no emissive
+    /// layer or material are copied into shared instance buffers and
+    /// drawn with a single draw call by [`SpriteRenderer::render`],
+    /// so many tiny groups cost about as much as one bigger one.
+    /// Outlines, picking, and the other passes still draw groups one
+    /// at a time.
+    pub fn set_batch_limit(&mut self, gpu: &WGPU, limit: usize) {
+        self.batch_limit = limit;
+        self.rebatch(gpu);
+    }
+    /// Returns the largest number of sprites a group can have and still be batched.
+    pub fn batch_limit(&self) -> usize {
+        self.batch_limit
+    }
+    /// Returns how many batches of sprite groups are drawn together.
+    pub fn batch_count(&self) -> usize {
+        self.batches
+            .iter()
+            .filter(|batch| !batch.members.is_empty())
+            .count()
+    }
+    // Recomputes which groups are drawn together, keeping batches
+    // whose layout hasn't changed
+    fn rebatch(&mut self, gpu: &WGPU) {
+        let mut runs: Vec<Vec<(usize, usize)>> = vec![];
+        let mut prev: Option<&SpriteGroup> = None;
+        for (idx, group) in self.groups.iter().enumerate() {
+            let Some(group) = group else {
+                continue;
+            };
+            let len = group.world_transforms.len();
+            if len == 0 {
+                continue;
+            }
+            if len > self.batch_limit
+                || group.sheet_id.is_none()
+                || group.material.is_some()
+                || group.emissive_bind_group.is_some()
+                || group.visible.is_some()
+                || group.blend_mode == BlendMode::Alpha
+            {
+                prev = None;
+                continue;
+            }
+            let joins = prev.is_some_and(|prev| {
+                prev.sheet_id == group.sheet_id
+                    && self.group_camera(prev) == self.group_camera(group)
+                    && prev.depth_mode == group.depth_mode
+                    && prev.blend_mode == group.blend_mode
+                    && prev.layer == group.layer
+            });
+            // A run's combined buffers also have to fit in the device's limits
+            let end = runs.last().map(|run| {
+                let &(last, offset) = run.last().unwrap();
+                offset + self.groups[last].as_ref().unwrap().world_transforms.len()
+            });
+            match (runs.last_mut(), end) {
+                (Some(run), Some(end)) if joins && self.check_instances(gpu, end + len).is_ok() => {
+                    run.push((idx, end));
+                }
+                _ => runs.push(vec![(idx, 0)]),
+            }
+            prev = Some(group);
+        }
+        let mut old: Vec<_> = std::mem::take(&mut self.batches)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.group_batches = vec![None; self.groups.len()];
+        for members in runs.into_iter().filter(|run| run.len() > 1) {
+            let &(last, offset) = members.last().unwrap();
+            let len = offset + self.groups[last].as_ref().unwrap().world_transforms.len();
+            let kept = old.iter_mut().find_map(|batch| {
+                batch.take_if(|batch| batch.members == members && batch.len == len)
+            });
+            let batch = kept.unwrap_or_else(|| self.create_batch(gpu, members, len));
+            for &(which, _) in batch.members.iter() {
+                self.group_batches[which] = Some(self.batches.len());
+            }
+            self.batches.push(batch);
+        }
+    }
+    fn create_batch(&self, gpu: &WGPU, members: Vec<(usize, usize)>, len: usize) -> SpriteBatch {
+        let world_buffer = self.create_instance_buffer(gpu, len);
+        let sheet_buffer = self.create_instance_buffer(gpu, len);
+        let tint_buffer = self.create_tint_buffer(gpu, len);
+        // Members share a camera, so the first one's buffer stands in for all of them
+        let first = self.groups[members[0].0].as_ref().unwrap();
+        let sprite_bind_group = self.create_sprite_bind_group(
+            gpu,
+            &first.camera_buffer,
+            &world_buffer,
+            &sheet_buffer,
+            &tint_buffer,
+        );
+        for &(which, offset) in members.iter() {
+            let group = self.groups[which].as_ref().unwrap();
+            let tint_offset = (offset * std::mem::size_of::<[u8; 4]>()) as u64;
+            let offset = (offset * std::mem::size_of::<Transform>()) as u64;
+            gpu.queue().write_buffer(
+                &world_buffer,
+                offset,
+                bytemuck::cast_slice(&group.world_transforms),
+            );
+            gpu.queue().write_buffer(
+                &sheet_buffer,
+                offset,
+                bytemuck::cast_slice(&group.sheet_regions),
+            );
+            gpu.queue().write_buffer(
+                &tint_buffer,
+                tint_offset,
+                bytemuck::cast_slice(&group.tints),
+            );
+        }
+        SpriteBatch {
+            members,
+            len,
+            world_buffer,
+            sheet_buffer,
+            tint_buffer,
+            sprite_bind_group,
+        }
+    }
+    // Breaks up the batch a group is in, if any, so its other
+    // members are drawn on their own until the next rebatch
+    fn unbatch(&mut self, which: usize) {
+        let Some(batch) = self.group_batches.get(which).copied().flatten() else {
+            return;
+        };
+        for (member, _) in std::mem::take(&mut self.batches[batch].members) {
+            self.group_batches[member] = None;
+        }
+    }
+    // Returns the batch a group is drawn in, if any, and where its sprites start in it
+    fn group_batch(&self, which: usize) -> Option<(&SpriteBatch, usize)> {
+        let batch = &self.batches[self.group_batches.get(which).copied().flatten()?];
+        let &(_, offset) = batch.members.iter().find(|(member, _)| *member == which)?;
+        Some((batch, offset))
     }
     /// Send a range of stored sprite data for a particular group to the GPU.
     /// You must call this yourself after modifying sprite data.
     /// Panics if the given sprite group is not populated.
     pub fn upload_sprites(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+        self.upload_sprites_with(gpu, &mut BufferWriter::Queue(gpu.queue()), which, range)
+    }
+    /// Like [`Self::upload_sprites`], but writes the sprites with `writer`.
+    pub(crate) fn upload_sprites_with(
+        &mut self,
+        gpu: &WGPU,
+        writer: &mut BufferWriter,
+        which: usize,
+        range: Range<usize>,
+    ) {
         let range = crate::range(range, self.sprite_group_size(which));
-        self.upload_world_transforms(gpu, which, range.clone());
-        self.upload_sheet_regions(gpu, which, range);
+        self.write_world_transforms(writer, which, range.clone());
+        self.write_sheet_regions(writer, which, range.clone());
+        self.write_tints(writer, which, range);
+        self.sort_sprites(gpu, which);
     }
     /// Upload only position changes to the GPU.
     /// Panics if the given sprite group is not populated.
     pub fn upload_world_transforms(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+        self.write_world_transforms(&mut BufferWriter::Queue(gpu.queue()), which, range);
+        self.sort_sprites(gpu, which);
+    }
+    /// Upload only visual changes to the GPU.
+    /// Panics if the given sprite group is not populated.
+    pub fn upload_sheet_regions(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+        self.write_sheet_regions(&mut BufferWriter::Queue(gpu.queue()), which, range);
+        self.sort_sprites(gpu, which);
+    }
+    /// Upload only tint changes to the GPU.
+    /// Panics if the given sprite group is not populated.
+    pub fn upload_tints(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+        self.write_tints(&mut BufferWriter::Queue(gpu.queue()), which, range);
+        self.sort_sprites(gpu, which);
+    }
+    fn write_world_transforms(&self, writer: &mut BufferWriter, which: usize, range: Range<usize>) {
         let group = self.groups[which].as_ref().unwrap();
-        gpu.queue().write_buffer(
+        writer.write(
             &group.world_buffer,
             (range.start * std::mem::size_of::<Transform>()) as u64,
-            bytemuck::cast_slice(&group.world_transforms[range]),
+            bytemuck::cast_slice(&group.world_transforms[range.clone()]),
         );
+        if let Some((batch, offset)) = self.group_batch(which) {
+            writer.write(
+                &batch.world_buffer,
+                ((offset + range.start) * std::mem::size_of::<Transform>()) as u64,
+                bytemuck::cast_slice(&group.world_transforms[range]),
+            );
+        }
     }
-    /// Upload only visual changes to the GPU.
-    /// Panics if the given sprite group is not populated.
-    pub fn upload_sheet_regions(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+    /// Writes the given transforms to the GPU in place of all of a
+    /// sprite group's own, without changing the group's transforms
+    /// (e.g. for interpolated transforms which are only drawn).
+    /// Panics if the given sprite group is not populated or
+    /// `transforms` doesn't have one transform per sprite.
+    pub(crate) fn write_drawn_transforms(
+        &self,
+        writer: &mut BufferWriter,
+        which: usize,
+        transforms: &[Transform],
+    ) {
         let group = self.groups[which].as_ref().unwrap();
-        gpu.queue().write_buffer(
+        assert_eq!(transforms.len(), group.world_transforms.len());
+        writer.write(&group.world_buffer, 0, bytemuck::cast_slice(transforms));
+        if let Some((batch, offset)) = self.group_batch(which) {
+            writer.write(
+                &batch.world_buffer,
+                (offset * std::mem::size_of::<Transform>()) as u64,
+                bytemuck::cast_slice(transforms),
+            );
+        }
+        if let (BlendMode::Alpha, Some(sorted)) = (group.blend_mode, group.sorted.as_ref()) {
+            let sorted_transforms: Vec<_> = sorted.order.iter().map(|&i| transforms[i]).collect();
+            writer.write(
+                &sorted.world_buffer,
+                0,
+                bytemuck::cast_slice(&sorted_transforms),
+            );
+        }
+    }
+    fn write_sheet_regions(&self, writer: &mut BufferWriter, which: usize, range: Range<usize>) {
+        let group = self.groups[which].as_ref().unwrap();
+        writer.write(
             &group.sheet_buffer,
             (range.start * std::mem::size_of::<SheetRegion>()) as u64,
-            bytemuck::cast_slice(&group.sheet_regions[range]),
+            bytemuck::cast_slice(&group.sheet_regions[range.clone()]),
+        );
+        if let Some((batch, offset)) = self.group_batch(which) {
+            writer.write(
+                &batch.sheet_buffer,
+                ((offset + range.start) * std::mem::size_of::<SheetRegion>()) as u64,
+                bytemuck::cast_slice(&group.sheet_regions[range]),
+            );
+        }
+    }
+    fn write_tints(&self, writer: &mut BufferWriter, which: usize, range: Range<usize>) {
+        let group = self.groups[which].as_ref().unwrap();
+        writer.write(
+            &group.tint_buffer,
+            (range.start * std::mem::size_of::<[u8; 4]>()) as u64,
+            bytemuck::cast_slice(&group.tints[range.clone()]),
         );
+        if let Some((batch, offset)) = self.group_batch(which) {
+            writer.write(
+                &batch.tint_buffer,
+                ((offset + range.start) * std::mem::size_of::<[u8; 4]>()) as u64,
+                bytemuck::cast_slice(&group.tints[range]),
+            );
+        }
+    }
+    /// Returns the camera of the given sprite group.  Panics if the
+    /// given sprite group is not populated.
+    pub fn camera(&self, which: usize) -> Camera2D {
+        self.groups[which].as_ref().unwrap().camera
     }
     /// Get a read-only slice of a specified sprite group's world transforms and texture regions.
     /// Panics if the given sprite group is not populated.
@@ -623,8 +2052,514 @@ impl SpriteRenderer {
         let group = self.groups[which].as_mut().unwrap();
         (&mut group.world_transforms, &mut group.sheet_regions)
     }
-    /// Render the given range of sprite groups into the given pass.
-    pub fn render<'s, 'pass>(
+    /// Get a read-only slice of a specified sprite group's tints (see [`NO_TINT`]).
+    /// Panics if the given sprite group is not populated.
+    pub fn get_tints(&self, which: usize) -> &[[u8; 4]] {
+        &self.groups[which].as_ref().unwrap().tints
+    }
+    /// Get a mutable slice of a specified sprite group's tints (see
+    /// [`NO_TINT`]); upload them with [`SpriteRenderer::upload_tints`]
+    /// or [`SpriteRenderer::upload_sprites`].
+    /// Panics if the given sprite group is not populated.
+    pub fn get_tints_mut(&mut self, which: usize) -> &mut [[u8; 4]] {
+        &mut self.groups[which].as_mut().unwrap().tints
+    }
+    /// Outlines the given range of sprites in a sprite group with the
+    /// given color, or removes the group's outline if `color` is
+    /// `None`.  Only one range per group can be outlined at a time.
+    /// Outlines are drawn by [`SpriteRenderer::render_outline_mask`]
+    /// and [`crate::outline::OutlinePass`].
+    ///
+    /// Panics if the given sprite group is not populated.
+    pub fn set_outline(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        color: Option<[f32; 4]>,
+        range: impl std::ops::RangeBounds<usize>,
+    ) {
+        // sprites past the end of the group are ignored when drawing,
+        // so an unbounded range keeps covering the group as it grows
+        let range = crate::range(range, usize::MAX);
+        let group = self.groups[which].as_mut().unwrap();
+        match (color, group.outline.as_mut()) {
+            (None, _) => group.outline = None,
+            (Some(color), Some(outline)) => outline.update(gpu, color, range),
+            (Some(color), None) => {
+                group.outline = Some(OutlineSet::new(
+                    gpu,
+                    &self.outline_bind_group_layout,
+                    color,
+                    range,
+                ))
+            }
+        }
+    }
+    /// Returns true if any sprite group has an outline.
+    pub fn has_outlines(&self) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .any(|group| group.outline.is_some())
+    }
+    /// Highlights or unhighlights the given range of sprites in a
+    /// sprite group (see [`crate::highlight`]).  Highlighted sprites
+    /// are drawn by [`SpriteRenderer::render_highlight_mask`].
+    ///
+    /// Panics if the given sprite group is not populated.
+    pub fn set_highlighted(
+        &mut self,
+        which: usize,
+        range: impl std::ops::RangeBounds<usize>,
+        highlighted: bool,
+    ) {
+        let range = crate::range(range, usize::MAX);
+        self.groups[which]
+            .as_mut()
+            .unwrap()
+            .highlighted
+            .set(range, highlighted);
+    }
+    /// Returns true if the given sprite is highlighted.  Panics if
+    /// the given sprite group is not populated.
+    pub fn is_highlighted(&self, which: usize, index: usize) -> bool {
+        self.groups[which]
+            .as_ref()
+            .unwrap()
+            .highlighted
+            .contains(index)
+    }
+    /// Unhighlights every sprite in a sprite group.  Panics if the
+    /// given sprite group is not populated.
+    pub fn clear_highlighted(&mut self, which: usize) {
+        self.groups[which].as_mut().unwrap().highlighted.clear();
+    }
+    /// Returns true if any sprite is highlighted.
+    pub fn has_highlights(&self) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .any(|group| !group.highlighted.is_empty())
+    }
+    /// Sets the color highlighted sprites are drawn into the highlight mask with.
+    pub fn set_highlight_color(&mut self, gpu: &WGPU, color: [f32; 4]) {
+        self.highlight_color.update(gpu, color, 0..0);
+    }
+    /// Returns the color highlighted sprites are drawn into the highlight mask with.
+    pub fn highlight_color(&self) -> [f32; 4] {
+        self.highlight_color.color()
+    }
+    /// Turns a debug view on or off.  Debug views are drawn by
+    /// [`SpriteRenderer::render`]; call
+    /// [`SpriteRenderer::update_debug_view`] after adding groups or
+    /// changing sprites to keep the view up to date.
+    ///
+    /// Panics if the view shows a sprite group which is not populated.
+    pub fn set_debug_view(&mut self, gpu: &WGPU, view: DebugView) {
+        self.debug_view = view;
+        if view != DebugView::Off && self.debug_pipelines.is_none() {
+            self.debug_pipelines = Some(DebugPipelines::new(self, gpu));
+        }
+        self.update_debug_view(gpu);
+    }
+    /// Returns the current debug view.
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+    /// Refreshes the current debug view's group tints or sheet
+    /// region outlines from the sprite groups' current contents.
+    pub fn update_debug_view(&mut self, gpu: &WGPU) {
+        self.debug_atlas = None;
+        match self.debug_view {
+            DebugView::Off => self.debug_tints.clear(),
+            DebugView::GroupTint => {
+                while self.debug_tints.len() < self.groups.len() {
+                    // Spread hues around the color wheel by the golden ratio
+                    let hue = (self.debug_tints.len() as f32 * 0.618_034).fract() * 6.0;
+                    let channel =
+                        |offset: f32| (((hue + offset) % 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0);
+                    let color = [channel(0.0), channel(4.0), channel(2.0), 1.0];
+                    // Only the outline set's color is used here
+                    self.debug_tints.push(OutlineSet::new(
+                        gpu,
+                        &self.outline_bind_group_layout,
+                        color,
+                        0..0,
+                    ));
+                }
+            }
+            DebugView::Atlas { group, layer } => {
+                let sprites = self.groups[group].as_ref().unwrap();
+                let size = sprites.sheet_size;
+                let (w, h) = (size.width as f32, size.height as f32);
+                let mut trfs = vec![Transform {
+                    w: size.width as u16,
+                    h: size.height as u16,
+                    x: w / 2.0,
+                    y: h / 2.0,
+                    rot: 0.0,
+                }];
+                let mut uvs = vec![SheetRegion::new(
+                    layer,
+                    0,
+                    0,
+                    0,
+                    size.width as i16,
+                    size.height as i16,
+                )];
+                // Outline each region used on this layer with four thin sprites
+                for region in sprites.sheet_regions.iter() {
+                    if region.layer() != layer || region.w == 0 || region.h == 0 {
+                        continue;
+                    }
+                    let x0 = region.x as f32 + (region.w as f32).min(0.0);
+                    let y0 = region.y as f32 + (region.h as f32).min(0.0);
+                    let (rw, rh) = (region.w.unsigned_abs(), region.h.unsigned_abs());
+                    // Texture rows go down, but world y goes up
+                    let top = h - y0;
+                    let bottom = top - rh as f32;
+                    let (cx, cy) = (x0 + rw as f32 / 2.0, (top + bottom) / 2.0);
+                    trfs.extend(
+                        [
+                            (cx, top - 0.5, rw, 1),
+                            (cx, bottom + 0.5, rw, 1),
+                            (x0 + 0.5, cy, 1, rh),
+                            (x0 + rw as f32 - 0.5, cy, 1, rh),
+                        ]
+                        .map(|(x, y, w, h)| Transform {
+                            w,
+                            h,
+                            x,
+                            y,
+                            rot: 0.0,
+                        }),
+                    );
+                    uvs.extend([SheetRegion::ZERO; 4]);
+                }
+                let view = sprites.sheet_view.clone();
+                let atlas = self.create_group(
+                    gpu,
+                    view,
+                    size,
+                    trfs,
+                    uvs,
+                    Camera2D {
+                        screen_pos: [0.0, 0.0],
+                        screen_size: [w, h],
+                    },
+                );
+                let color = OutlineSet::new(
+                    gpu,
+                    &self.outline_bind_group_layout,
+                    [1.0, 0.0, 1.0, 1.0],
+                    0..0,
+                );
+                self.debug_atlas = Some((atlas, color));
+            }
+        }
+    }
+    /// Returns how many draw calls and sprites [`SpriteRenderer::render`]
+    /// issues when drawing every sprite group.
+    pub fn draw_counts(&self) -> (usize, usize) {
+        let draw_count = |len: usize| {
+            if self.instance_storage == InstanceStorage::UniformChunks {
+                len.div_ceil(UNIFORM_CHUNK)
+            } else {
+                1
+            }
+        };
+        let (draws, sprites) = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, group)| Some((idx, group.as_ref()?.world_transforms.len())))
+            .filter(|&(idx, len)| len > 0 && self.group_batch(idx).is_none())
+            .fold((0, 0), |(draws, sprites), (_, len)| {
+                (draws + draw_count(len), sprites + len)
+            });
+        self.batches
+            .iter()
+            .filter(|batch| !batch.members.is_empty())
+            .fold((draws, sprites), |(draws, sprites), batch| {
+                (draws + draw_count(batch.len), sprites + batch.len)
+            })
+    }
+    /// Gives a sprite group an emissive layer, or removes it if
+    /// `emissive` is `None`.  The emissive texture is laid out just
+    /// like the group's spritesheet texture (same size and number of
+    /// array layers): wherever a sprite is drawn, the emissive color
+    /// times its alpha is added to the sprite's color after color
+    /// modulation, and [`SpriteRenderer::render_emissive`] draws it
+    /// on its own, e.g. as the source of a bloom pass.
+    ///
+    /// Panics if the given sprite group is not populated or if the
+    /// emissive texture doesn't match the group's spritesheet.
+    pub fn set_emissive(&mut self, gpu: &WGPU, which: usize, emissive: Option<&wgpu::Texture>) {
+        let group = self.groups[which].as_mut().unwrap();
+        let Some(tex) = emissive else {
+            group.emissive_bind_group = None;
+            self.rebatch(gpu);
+            return;
+        };
+        assert_eq!(
+            tex.size(),
+            group.sheet_size,
+            "Emissive textures must match their group's spritesheet"
+        );
+        let view = tex.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        group.emissive_bind_group =
+            Some(gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.emissive_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            }));
+        self.rebatch(gpu);
+    }
+    /// Draws a sprite group with a material (see
+    /// [`crate::material`]), or with the renderer's usual shading if
+    /// `material` is `None`.  A material takes the place of the
+    /// group's emissive layer when it is drawn by
+    /// [`SpriteRenderer::render`].  Panics if the given sprite group
+    /// is not populated.
+    pub fn set_material(&mut self, gpu: &WGPU, which: usize, material: Option<&Material>) {
+        let material = material.map(|material| {
+            let shader = material.create_shader(
+                gpu,
+                include_str!("sprites.wgsl"),
+                include_str!("sprites_material.wgsl"),
+            );
+            let pipeline_layout = material.create_pipeline_layout(
+                gpu,
+                [
+                    &self.sprite_bind_group_layout,
+                    &self.texture_bind_group_layout,
+                    &self.empty_bind_group_layout,
+                ],
+            );
+            let group = self.groups[which].as_ref().unwrap();
+            let pipeline = Self::create_pipeline_with_depth(
+                gpu,
+                &shader,
+                &pipeline_layout,
+                self.instance_storage,
+                material.fragment_entry("fs_material"),
+                material.color_target(&group.blend_mode.color_target(&self.color_target)),
+                Self::depth_stencil(
+                    self.depth_format,
+                    group.blend_mode.depth_mode(group.depth_mode),
+                ),
+                self.sample_count,
+            );
+            GroupMaterial::new(material, pipeline)
+        });
+        self.groups[which].as_mut().unwrap().material = material;
+        self.rebatch(gpu);
+    }
+    /// Sets how a sprite group is depth tested and whether it writes
+    /// depth, e.g. so UI groups can ignore depth while world sprites
+    /// are hidden by 3D geometry.  Each depth mode in use gets its own
+    /// pipelines.  Panics if the given sprite group is not populated.
+    pub fn set_depth_mode(&mut self, gpu: &WGPU, which: usize, depth_mode: DepthMode) {
+        let group = self.groups[which].as_mut().unwrap();
+        if group.depth_mode == depth_mode {
+            return;
+        }
+        group.depth_mode = depth_mode;
+        let blend_mode = group.blend_mode;
+        let material = group.material.as_ref().map(|m| m.material.clone());
+        self.ensure_mode_pipelines(gpu, depth_mode, blend_mode);
+        if let Some(material) = material {
+            self.set_material(gpu, which, Some(&material));
+        }
+        self.rebatch(gpu);
+    }
+    /// Returns how a sprite group interacts with the depth buffer.
+    /// Panics if the given sprite group is not populated.
+    pub fn depth_mode(&self, which: usize) -> DepthMode {
+        self.groups[which].as_ref().unwrap().depth_mode
+    }
+    /// Sets how a sprite group's colors combine with what's behind
+    /// them; groups use [`BlendMode::Cutout`] until this is called,
+    /// usually right after adding them.  With a depth buffer, blended
+    /// groups are drawn after all the cutout groups being rendered
+    /// with them, so that they blend over whatever they're in front
+    /// of.  [`BlendMode::Alpha`] groups keep a second copy of their
+    /// visible sprites in drawing order, re-sorted whenever they're
+    /// uploaded (or once per [`crate::Renderer::do_uploads`], for a
+    /// [`crate::Renderer`]'s groups); camera views draw only those sprites too.  Panics
+    /// if the given sprite group is not populated.
+    pub fn set_blend_mode(&mut self, gpu: &WGPU, which: usize, blend_mode: BlendMode) {
+        let group = self.groups[which].as_mut().unwrap();
+        if group.blend_mode == blend_mode {
+            return;
+        }
+        group.blend_mode = blend_mode;
+        group.sorted = None;
+        group.unsorted = false;
+        let depth_mode = group.depth_mode;
+        let material = group.material.as_ref().map(|m| m.material.clone());
+        self.ensure_mode_pipelines(gpu, depth_mode, blend_mode);
+        if let Some(material) = material {
+            self.set_material(gpu, which, Some(&material));
+        }
+        self.sort_sprites(gpu, which);
+        self.rebatch(gpu);
+    }
+    /// Returns how a sprite group's colors combine with what's behind
+    /// them.  Panics if the given sprite group is not populated.
+    pub fn blend_mode(&self, which: usize) -> BlendMode {
+        self.groups[which].as_ref().unwrap().blend_mode
+    }
+    /// Puts a sprite group on a render layer (0 by default).  Groups
+    /// are drawn from the lowest layer to the highest, so e.g. a
+    /// background on layer -1 goes under gameplay on layer 0 and a HUD
+    /// on layer 1; groups on the same layer are drawn in index order.
+    /// With a depth buffer, depth testing still decides what's in
+    /// front, but alpha-blended groups are drawn after the cutout
+    /// groups of their layer.  Only groups on the same layer are
+    /// batched together.  Panics if the given sprite group is not
+    /// populated.
+    pub fn set_layer(&mut self, gpu: &WGPU, which: usize, layer: i32) {
+        let group = self.groups[which].as_mut().unwrap();
+        if group.layer == layer {
+            return;
+        }
+        group.layer = layer;
+        self.rebatch(gpu);
+    }
+    /// Returns a sprite group's render layer.  Panics if the given
+    /// sprite group is not populated.
+    pub fn layer(&self, which: usize) -> i32 {
+        self.groups[which].as_ref().unwrap().layer
+    }
+    // The visible sprites of a group from back to front, keeping the
+    // group's order among sprites at the same depth
+    fn sort_order(group: &SpriteGroup) -> Vec<usize> {
+        let len = group.world_transforms.len();
+        let mut order: Vec<usize> = match group.visible.as_ref() {
+            Some(visible) => visible
+                .iter()
+                .flat_map(|range| range.start.min(len)..range.end.min(len))
+                .collect(),
+            None => (0..len).collect(),
+        };
+        order.sort_by_key(|&i| std::cmp::Reverse(group.sheet_regions[i].depth));
+        order
+    }
+    // Marks an alpha-blended group's sorted buffers as out of date,
+    // sorting them right away unless a crate::Renderer will
+    fn sort_sprites(&mut self, gpu: &WGPU, which: usize) {
+        let group = self.groups[which].as_mut().unwrap();
+        if group.blend_mode != BlendMode::Alpha {
+            return;
+        }
+        group.unsorted = true;
+        if !self.deferred_sorting {
+            self.sort_group(gpu, &mut BufferWriter::Queue(gpu.queue()), which);
+        }
+    }
+    /// Leaves sorting alpha-blended groups to
+    /// [`SpriteRenderer::sort_unsorted_with`], so that a group changed
+    /// several times is only sorted once.
+    pub(crate) fn defer_sorting(&mut self) {
+        self.deferred_sorting = true;
+    }
+    /// Sorts the alpha-blended groups changed since they were last
+    /// sorted, writing them with `writer`, and returns how many bytes
+    /// were written.
+    pub(crate) fn sort_unsorted_with(&mut self, gpu: &WGPU, writer: &mut BufferWriter) -> usize {
+        let mut bytes = 0;
+        for which in 0..self.groups.len() {
+            if self.groups[which]
+                .as_ref()
+                .is_some_and(|group| group.unsorted)
+            {
+                bytes += self.sort_group(gpu, writer, which);
+            }
+        }
+        bytes
+    }
+    // Copies an alpha-blended group's visible sprites into its sorted
+    // buffers from farthest to nearest, returning how many bytes were
+    // written
+    fn sort_group(&mut self, gpu: &WGPU, writer: &mut BufferWriter, which: usize) -> usize {
+        let group = self.groups[which].as_ref().unwrap();
+        let order = Self::sort_order(group);
+        let world_transforms: Vec<_> = order.iter().map(|&i| group.world_transforms[i]).collect();
+        let sheet_regions: Vec<_> = order.iter().map(|&i| group.sheet_regions[i]).collect();
+        let tints: Vec<_> = order.iter().map(|&i| group.tints[i]).collect();
+        let sorted = match group.sorted.as_ref() {
+            Some(sorted) if sorted.capacity >= order.len() => None,
+            _ => {
+                let capacity = self.sprite_group_capacity(which).max(order.len());
+                let world_buffer = self.create_instance_buffer(gpu, capacity);
+                let sheet_buffer = self.create_instance_buffer(gpu, capacity);
+                let tint_buffer = self.create_tint_buffer(gpu, capacity);
+                Some(SortedSprites {
+                    sprite_bind_group: self.create_sprite_bind_group(
+                        gpu,
+                        &group.camera_buffer,
+                        &world_buffer,
+                        &sheet_buffer,
+                        &tint_buffer,
+                    ),
+                    view_bind_groups: self.create_view_bind_groups(
+                        gpu,
+                        &world_buffer,
+                        &sheet_buffer,
+                        &tint_buffer,
+                    ),
+                    world_buffer,
+                    sheet_buffer,
+                    tint_buffer,
+                    capacity,
+                    order: vec![],
+                })
+            }
+        };
+        let group = self.groups[which].as_mut().unwrap();
+        group.unsorted = false;
+        if sorted.is_some() {
+            group.sorted = sorted;
+        }
+        let sorted = group.sorted.as_mut().unwrap();
+        sorted.order = order;
+        writer.write(
+            &sorted.world_buffer,
+            0,
+            bytemuck::cast_slice(&world_transforms),
+        );
+        writer.write(
+            &sorted.sheet_buffer,
+            0,
+            bytemuck::cast_slice(&sheet_regions),
+        );
+        writer.write(&sorted.tint_buffer, 0, bytemuck::cast_slice(&tints));
+        std::mem::size_of_val(world_transforms.as_slice())
+            + std::mem::size_of_val(sheet_regions.as_slice())
+            + std::mem::size_of_val(tints.as_slice())
+    }
+    /// Returns true if any sprite group has an emissive layer.
+    pub fn has_emissive(&self) -> bool {
+        self.groups
+            .iter()
+            .flatten()
+            .any(|group| group.emissive_bind_group.is_some())
+    }
+    /// Draws only the emissive color of the given range of sprite
+    /// groups into the given pass, which should target a texture in
+    /// the same format as this renderer's color target and clear it
+    /// to black.  Sprites in groups without an emissive layer are
+    /// drawn black so they still hide glowing sprites behind them;
+    /// if the renderer uses a depth buffer, the pass needs its own
+    /// cleared depth buffer too.
+    pub fn render_emissive<'s, 'pass>(
         &'s self,
         rpass: &mut wgpu::RenderPass<'pass>,
         which: impl std::ops::RangeBounds<usize>,
@@ -634,24 +2569,483 @@ impl SpriteRenderer {
         if self.groups.is_empty() {
             return;
         }
-        rpass.set_pipeline(&self.pipeline);
         let which = crate::range(which, self.groups.len());
         for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
+            if let Some(emissive) = group.emissive_bind_group.as_ref() {
+                capture::set_pipeline(rpass, &self.emissive_pipeline, "sprites:emissive_pipeline");
+                capture::set_bind_group(rpass, 2, emissive, &[], "sprites:emissive");
+            } else {
+                capture::set_pipeline(
+                    rpass,
+                    &self.emissive_occluder_pipeline,
+                    "sprites:emissive_occluder_pipeline",
+                );
+            }
+            capture::set_bind_group(
+                rpass,
+                1,
+                &group.tex_bind_group,
+                &[],
+                "sprites:tex_bind_group",
+            );
+            self.draw_sprites(rpass, group, 0..group.world_transforms.len(), None);
+        }
+    }
+    /// Draws the outlined sprites of the given range of sprite groups
+    /// into the given pass, which should target an outline mask
+    /// texture (see [`crate::outline`]).
+    pub fn render_outline_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.outline_pipeline, "sprites:outline_pipeline");
+        let which = crate::range(which, self.groups.len());
+        for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
+            let Some(outline) = group.outline.as_ref() else {
+                continue;
+            };
+            capture::set_bind_group(
+                rpass,
+                1,
+                &group.tex_bind_group,
+                &[],
+                "sprites:tex_bind_group",
+            );
+            capture::set_bind_group(rpass, 2, &outline.bind_group, &[], "sprites:outline_color");
+            self.draw_sprites(
+                rpass,
+                group,
+                outline.clipped_instances(group.world_transforms.len()),
+                None,
+            );
+        }
+    }
+    /// Draws the highlighted sprites of the given range of sprite
+    /// groups into the given pass, which should target a highlight
+    /// mask texture (see [`crate::highlight`]).
+    pub fn render_highlight_mask<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.outline_pipeline, "sprites:outline_pipeline");
+        capture::set_bind_group(
+            rpass,
+            2,
+            &self.highlight_color.bind_group,
+            &[],
+            "sprites:highlight_color",
+        );
+        let which = crate::range(which, self.groups.len());
+        for group in self.groups[which].iter().filter_map(|o| o.as_ref()) {
+            if group.highlighted.is_empty() {
+                continue;
+            }
+            capture::set_bind_group(
+                rpass,
+                1,
+                &group.tex_bind_group,
+                &[],
+                "sprites:tex_bind_group",
+            );
+            for run in group.highlighted.clipped_runs(group.world_transforms.len()) {
+                self.draw_sprites(rpass, group, run, None);
+            }
+        }
+    }
+    /// Render the given range of sprite groups into the given pass.
+    pub fn render<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        let which = GroupSelection::Range(crate::range(which, self.groups.len()));
+        self.render_groups(rpass, &which, None)
+    }
+    /// Render the given range of sprite groups into the given pass
+    /// as seen through a camera view (see
+    /// [`SpriteRenderer::add_camera_view`]) instead of their own
+    /// cameras.  Groups are never batched together when drawn this
+    /// way, and the atlas debug view is not shown.  Panics if the
+    /// view doesn't exist.
+    pub fn render_with_view<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+        view: usize,
+    ) where
+        's: 'pass,
+    {
+        assert!(self.camera_views[view].is_some());
+        let which = GroupSelection::Range(crate::range(which, self.groups.len()));
+        self.render_groups(rpass, &which, Some(view))
+    }
+    /// Render the selected sprite groups into the given pass, as
+    /// seen through a camera view (see
+    /// [`SpriteRenderer::add_camera_view`]) if `view` is given.
+    /// Batched groups are only drawn as a batch when every member is
+    /// selected.  Panics if the view doesn't exist.
+    pub fn render_selected<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        view: Option<usize>,
+    ) where
+        's: 'pass,
+    {
+        if let Some(view) = view {
+            assert!(self.camera_views[view].is_some());
+        }
+        self.render_groups(rpass, which, view)
+    }
+    fn render_groups<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: &GroupSelection,
+        view: Option<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        let mut selected: Vec<_> = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| which.contains(i))
+            .filter_map(|(i, o)| Some((i, o.as_ref()?)))
+            .collect();
+        // Groups go in layer order.  With a depth buffer, blended
+        // groups go after the cutout groups of their layer they might
+        // be in front of; batches only hold groups of one layer and
+        // blend mode, so they stay together
+        let blended_last = self.depth_format.is_some();
+        selected.sort_by_key(|(_, group)| {
+            (
+                group.layer,
+                blended_last && group.blend_mode != BlendMode::Cutout,
+            )
+        });
+        for (idx, group) in selected {
             if group.world_transforms.is_empty() {
                 continue;
             }
-            if !self.use_storage {
-                rpass.set_vertex_buffer(0, group.world_buffer.slice(..));
-                rpass.set_vertex_buffer(1, group.sheet_buffer.slice(..));
+            // A batch is drawn along with its first member, as long as
+            // all of its members are being drawn with their own camera
+            let batch = self
+                .group_batch(idx)
+                .filter(|_| view.is_none())
+                .map(|(batch, _)| batch)
+                .filter(|batch| {
+                    batch
+                        .members
+                        .iter()
+                        .all(|&(member, _)| which.contains(member))
+                });
+            if batch.is_some_and(|batch| batch.members[0].0 != idx) {
+                continue;
+            }
+            match (self.debug_pipelines.as_ref(), self.debug_tints.get(idx)) {
+                (Some(debug), Some(tint)) if self.debug_view == DebugView::GroupTint => {
+                    capture::set_pipeline(rpass, &debug.tint, "sprites:debug_tint");
+                    capture::set_bind_group(rpass, 2, &tint.bind_group, &[], "sprites:debug_tint");
+                }
+                _ => {
+                    if let Some(material) = group.material.as_ref() {
+                        capture::set_pipeline(rpass, &material.pipeline, &material.capture_label);
+                        capture::set_bind_group(
+                            rpass,
+                            2,
+                            &self.empty_bind_group,
+                            &[],
+                            "sprites:empty_bind_group",
+                        );
+                        capture::set_bind_group(
+                            rpass,
+                            crate::material::MATERIAL_GROUP,
+                            material.material.bind_group(),
+                            &[],
+                            &material.capture_label,
+                        );
+                    } else if let Some(emissive) = group.emissive_bind_group.as_ref() {
+                        capture::set_pipeline(
+                            rpass,
+                            self.main_pipelines(group.depth_mode, group.blend_mode).1,
+                            "sprites:emissive_main_pipeline",
+                        );
+                        capture::set_bind_group(rpass, 2, emissive, &[], "sprites:emissive");
+                    } else {
+                        capture::set_pipeline(
+                            rpass,
+                            self.main_pipelines(group.depth_mode, group.blend_mode).0,
+                            "sprites:pipeline",
+                        );
+                    }
+                }
             }
-            rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
-            rpass.set_bind_group(1, &group.tex_bind_group, &[]);
-            // draw two triangles per sprite, and sprites-many sprites.
-            // this uses instanced drawing, but it would also be okay
-            // to draw 6 * sprites.len() vertices and use modular arithmetic
-            // to figure out which sprite we're drawing.
+            capture::set_bind_group(
+                rpass,
+                1,
+                &group.tex_bind_group,
+                &[],
+                "sprites:tex_bind_group",
+            );
             assert_eq!(group.world_transforms.len(), group.sheet_regions.len());
-            rpass.draw(0..6, 0..group.world_transforms.len() as u32);
+            if let Some(sorted) = group.sorted.as_ref() {
+                let bind_group =
+                    match view.filter(|_| group.coordinate_mode == CoordinateMode::World) {
+                        Some(view) => sorted.view_bind_groups[view].as_ref().unwrap(),
+                        None => &sorted.sprite_bind_group,
+                    };
+                self.draw_instances(
+                    rpass,
+                    (
+                        &sorted.world_buffer,
+                        &sorted.sheet_buffer,
+                        &sorted.tint_buffer,
+                        bind_group,
+                    ),
+                    0..sorted.order.len(),
+                    None,
+                );
+                continue;
+            }
+            match batch {
+                Some(batch) => self.draw_instances(
+                    rpass,
+                    (
+                        &batch.world_buffer,
+                        &batch.sheet_buffer,
+                        &batch.tint_buffer,
+                        &batch.sprite_bind_group,
+                    ),
+                    0..batch.len,
+                    None,
+                ),
+                None => match view.filter(|_| group.coordinate_mode == CoordinateMode::World) {
+                    Some(view) => self.draw_instances(
+                        rpass,
+                        (
+                            &group.world_buffer,
+                            &group.sheet_buffer,
+                            &group.tint_buffer,
+                            group.view_bind_groups[view].as_ref().unwrap(),
+                        ),
+                        0..group.world_transforms.len(),
+                        None,
+                    ),
+                    None => match group.visible.as_ref() {
+                        Some(visible) => {
+                            let len = group.world_transforms.len();
+                            for range in visible {
+                                let range = range.start.min(len)..range.end.min(len);
+                                if !range.is_empty() {
+                                    self.draw_sprites(rpass, group, range, None);
+                                }
+                            }
+                        }
+                        None => {
+                            self.draw_sprites(rpass, group, 0..group.world_transforms.len(), None)
+                        }
+                    },
+                },
+            }
+        }
+        let atlas_group = match self.debug_view {
+            DebugView::Atlas { group, .. } if view.is_none() => group,
+            _ => usize::MAX,
+        };
+        if let (Some(debug), Some((atlas, color)), true) = (
+            self.debug_pipelines.as_ref(),
+            self.debug_atlas.as_ref(),
+            which.contains(atlas_group),
+        ) {
+            capture::set_bind_group(
+                rpass,
+                1,
+                &atlas.tex_bind_group,
+                &[],
+                "sprites:debug_atlas_texture",
+            );
+            capture::set_pipeline(rpass, &debug.atlas, "sprites:debug_atlas");
+            self.draw_sprites(rpass, atlas, 0..1, None);
+            capture::set_pipeline(rpass, &debug.rect, "sprites:debug_rect");
+            capture::set_bind_group(rpass, 2, &color.bind_group, &[], "sprites:debug_rect_color");
+            self.draw_sprites(rpass, atlas, 1..atlas.world_transforms.len(), None);
+        }
+    }
+    /// Updates the pick table used by [`SpriteRenderer::render_pick`];
+    /// call this before drawing IDs whenever sprite groups have been
+    /// added, removed, or resized.
+    pub fn prepare_pick(&mut self, gpu: &WGPU) {
+        let mut entries = vec![];
+        self.pick_first_entries.clear();
+        for (which, group) in self.groups.iter().enumerate() {
+            self.pick_first_entries.push(entries.len());
+            let Some(group) = group else {
+                continue;
+            };
+            // uniform chunks are drawn with chunk-relative instance indices
+            let chunks = if self.instance_storage == InstanceStorage::UniformChunks {
+                group.world_transforms.len().div_ceil(UNIFORM_CHUNK)
+            } else {
+                1
+            };
+            entries.extend((0..chunks).map(|chunk| {
+                [
+                    crate::pick::KIND_SPRITE,
+                    which as u32,
+                    0,
+                    (chunk * UNIFORM_CHUNK) as u32,
+                ]
+            }));
+        }
+        self.pick_table
+            .write(gpu, &self.pick_bind_group_layout, &entries);
+    }
+    /// Draws the object IDs of the given range of sprite groups into
+    /// the given pass, which should target an object ID texture (see
+    /// [`crate::pick`]).  Call [`SpriteRenderer::prepare_pick`] first.
+    pub fn render_pick<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        which: impl std::ops::RangeBounds<usize>,
+    ) where
+        's: 'pass,
+    {
+        if self.groups.is_empty() {
+            return;
+        }
+        capture::set_pipeline(rpass, &self.pick_pipeline, "sprites:pick_pipeline");
+        let which = crate::range(which, self.groups.len());
+        // In layer order, so the topmost sprite's ID is the one kept
+        let mut selected: Vec<_> = self.groups[which.clone()]
+            .iter()
+            .zip(&self.pick_first_entries[which])
+            .filter_map(|(group, first_entry)| Some((group.as_ref()?, first_entry)))
+            .collect();
+        selected.sort_by_key(|(group, _)| group.layer);
+        for (group, first_entry) in selected {
+            capture::set_bind_group(
+                rpass,
+                1,
+                &group.tex_bind_group,
+                &[],
+                "sprites:tex_bind_group",
+            );
+            self.draw_sprites(
+                rpass,
+                group,
+                0..group.world_transforms.len(),
+                Some((&self.pick_table, *first_entry)),
+            );
+        }
+    }
+    /// Binds a group's instance data and draws the given range of its
+    /// sprites with whatever pipeline is set.  When drawing IDs,
+    /// `pick` gives the pick table and the group's first entry.
+    fn draw_sprites<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        group: &'s SpriteGroup,
+        range: Range<usize>,
+        pick: Option<(&'s PickTable, usize)>,
+    ) where
+        's: 'pass,
+    {
+        self.draw_instances(
+            rpass,
+            (
+                &group.world_buffer,
+                &group.sheet_buffer,
+                &group.tint_buffer,
+                &group.sprite_bind_group,
+            ),
+            range,
+            pick,
+        );
+    }
+    /// Like [`SpriteRenderer::draw_sprites`], but for any world
+    /// transform buffer, sheet region buffer, tint buffer, and sprite
+    /// bind group.
+    fn draw_instances<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        (world_buffer, sheet_buffer, tint_buffer, sprite_bind_group): (
+            &'s wgpu::Buffer,
+            &'s wgpu::Buffer,
+            &'s wgpu::Buffer,
+            &'s wgpu::BindGroup,
+        ),
+        range: Range<usize>,
+        pick: Option<(&'s PickTable, usize)>,
+    ) where
+        's: 'pass,
+    {
+        if range.is_empty() {
+            return;
+        }
+        if self.instance_storage == InstanceStorage::UniformChunks {
+            // draw one chunk of sprites at a time, moving the
+            // uniform bindings along the buffers as we go
+            let mut start = range.start;
+            while start < range.end {
+                let chunk_start = start - start % UNIFORM_CHUNK;
+                let end = range.end.min(chunk_start + UNIFORM_CHUNK);
+                let offset = (chunk_start * std::mem::size_of::<Transform>()) as u32;
+                let tint_offset = (chunk_start * std::mem::size_of::<[u8; 4]>()) as u32;
+                capture::set_bind_group(
+                    rpass,
+                    0,
+                    sprite_bind_group,
+                    &[offset, offset, tint_offset],
+                    "sprites:sprite_bind_group",
+                );
+                if let Some((table, first_entry)) = pick {
+                    table.bind(rpass, 2, first_entry + chunk_start / UNIFORM_CHUNK);
+                }
+                capture::draw(
+                    rpass,
+                    0..6,
+                    (start - chunk_start) as u32..(end - chunk_start) as u32,
+                );
+                start = end;
+            }
+            return;
+        }
+        if self.instance_storage == InstanceStorage::VertexBuffer {
+            capture::set_vertex_buffer(rpass, 0, world_buffer, "sprites:world_buffer");
+            capture::set_vertex_buffer(rpass, 1, sheet_buffer, "sprites:sheet_buffer");
+            capture::set_vertex_buffer(rpass, 2, tint_buffer, "sprites:tint_buffer");
+        }
+        capture::set_bind_group(
+            rpass,
+            0,
+            sprite_bind_group,
+            &[],
+            "sprites:sprite_bind_group",
+        );
+        if let Some((table, first_entry)) = pick {
+            table.bind(rpass, 2, first_entry);
         }
+        // draw two triangles per sprite, and sprites-many sprites.
+        // this uses instanced drawing, but it would also be okay
+        // to draw 6 * sprites.len() vertices and use modular arithmetic
+        // to figure out which sprite we're drawing.
+        capture::draw(rpass, 0..6, range.start as u32..range.end as u32);
     }
 }
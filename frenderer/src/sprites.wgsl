@@ -27,29 +27,48 @@ var<uniform> camera: Camera;
 var<storage, read> s_world: array<vec4<f32>>;
 @group(0) @binding(2)
 var<storage, read> s_sheet: array<UVData>;
+// When storage buffers aren't available, instances are drawn in
+// chunks of UNIFORM_CHUNK using these uniform bindings instead.
+const UNIFORM_CHUNK:u32 = 1024u;
+@group(0) @binding(3)
+var<uniform> u_world: array<vec4<f32>, UNIFORM_CHUNK>;
+@group(0) @binding(4)
+var<uniform> u_sheet: array<vec4<u32>, UNIFORM_CHUNK>;
+// Each sprite's tint is four u8s, like colormod
+@group(0) @binding(5)
+var<storage, read> s_tint: array<u32>;
+@group(0) @binding(6)
+var<uniform> u_tint: array<vec4<u32>, 256>; // UNIFORM_CHUNK / 4
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) @interpolate(flat) tex_index: u32,
-    @location(2) colormod: vec4<f32>
+    @location(2) colormod: vec4<f32>,
+    // Which sprite this is, for object ID rendering
+    @location(3) @interpolate(flat) instance: u32,
+    // Multiplies the sprite's color, after colormod
+    @location(4) tint: vec4<f32>
 }
 
+// The bytes of SheetRegion::colormod are packed little-endian, so red is the low byte
 fn u32_to_vec4(in:u32) -> vec4<f32> {
-  let r = (in >> 24u) & 0x000000FFu;
-  let g = (in >> 16u) & 0x000000FFu;
-  let b = (in >>  8u) & 0x000000FFu;
-  let a = in & 0x000000FFu;
+  let r = in & 0x000000FFu;
+  let g = (in >>  8u) & 0x000000FFu;
+  let b = (in >> 16u) & 0x000000FFu;
+  let a = (in >> 24u) & 0x000000FFu;
   return vec4(f32(r)/255.0, f32(g)/255.0, f32(b)/255.0, f32(a)/255.0);
 }
 
-fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, norm_vert:vec2<f32>) -> VertexOutput {
+fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, tint:u32, norm_vert:vec2<f32>, instance:u32) -> VertexOutput {
   let center:vec2<f32> = trf.yz;
   let size_bits:u32 = bitcast<u32>(trf.x);
   let size:vec2<f32> = vec2(f32(size_bits & 0x0000FFFFu),
                             f32((size_bits & 0xFFFF0000u) >> 16u)
                             );
-  let tex_layer = uvs.sheet_depth & 0x0000FFFFu;
+  // The top three bits of the layer are SheetRegion's flip and rotation flags
+  let tex_layer = uvs.sheet_depth & 0x00001FFFu;
+  let flags = (uvs.sheet_depth >> 13u) & 7u;
   let tex_depth = (uvs.sheet_depth & 0xFFFF0000u) >> 16u;
   let tex_size:vec2<u32> = textureDimensions(t_diffuse);
   let rot:f32 = trf.w;
@@ -70,10 +89,20 @@ fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, norm_vert:vec2<f32>) -> VertexOutpu
   let tex_uvwh:vec2<f32> = unpack2x16snorm(uvs.wh)*32767.0;
   let tex_corner = vec2(tex_uvxy.x / f32(tex_size.x), tex_uvxy.y / f32(tex_size.y));
   let tex_uv_size = vec2(tex_uvwh.x / f32(tex_size.x), tex_uvwh.y / f32(tex_size.y));
-  let norm_uv = vec2(norm_vert.x+0.5, 1.0-(norm_vert.y+0.5));
+  var norm_uv = vec2(norm_vert.x+0.5, 1.0-(norm_vert.y+0.5));
+  // Mirror on screen, then turn the texture a quarter turn clockwise
+  if (flags & 1u) != 0u {
+    norm_uv.x = 1.0 - norm_uv.x;
+  }
+  if (flags & 2u) != 0u {
+    norm_uv.y = 1.0 - norm_uv.y;
+  }
+  if (flags & 4u) != 0u {
+    norm_uv = vec2(norm_uv.y, 1.0 - norm_uv.x);
+  }
   let colormod = u32_to_vec4(uvs.colormod);
   // Larger y = smaller depth = closer to screen
-  return VertexOutput(ndc_pos+vec4(0.0, 0.0, f32(tex_depth)/65535.0, 0.0), tex_corner + norm_uv*tex_uv_size, tex_layer, colormod);
+  return VertexOutput(ndc_pos+vec4(0.0, 0.0, f32(tex_depth)/65535.0, 0.0), tex_corner + norm_uv*tex_uv_size, tex_layer, colormod, instance, u32_to_vec4(tint));
 }
 
 @vertex
@@ -81,7 +110,7 @@ fn vs_storage_main(@builtin(vertex_index) in_vertex_index: u32, @builtin(instanc
   // We'll just look up the vertex data in those constant arrays
   let trf = s_world[sprite_index];
   let uvs = s_sheet[sprite_index];
-  return sprite_to_vert(trf, uvs, VERTICES[in_vertex_index]);
+  return sprite_to_vert(trf, uvs, s_tint[sprite_index], VERTICES[in_vertex_index], sprite_index);
 }
 
 @vertex
@@ -90,14 +119,21 @@ fn vs_storage_noinstance_main(@builtin(vertex_index) in_vertex_index: u32) -> Ve
     let vertex_index:u32 = in_vertex_index % u32(6);
     let trf = s_world[sprite_index];
     let uvs = s_sheet[sprite_index];
-    return sprite_to_vert(trf, uvs, VERTICES[vertex_index]);
+    return sprite_to_vert(trf, uvs, s_tint[sprite_index], VERTICES[vertex_index], sprite_index);
 }
 
 @vertex
-fn vs_vbuf_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) trf:vec4<f32>, @location(1) sheet_region:vec4<u32>) -> VertexOutput {
-  return sprite_to_vert(trf, UVData(sheet_region.x, sheet_region.y, sheet_region.z, sheet_region.w), VERTICES[in_vertex_index]);
+fn vs_vbuf_main(@builtin(vertex_index) in_vertex_index: u32, @builtin(instance_index) sprite_index:u32, @location(0) trf:vec4<f32>, @location(1) sheet_region:vec4<u32>, @location(2) tint:u32) -> VertexOutput {
+  return sprite_to_vert(trf, UVData(sheet_region.x, sheet_region.y, sheet_region.z, sheet_region.w), tint, VERTICES[in_vertex_index], sprite_index);
 }
 
+@vertex
+fn vs_uniform_main(@builtin(vertex_index) in_vertex_index: u32, @builtin(instance_index) sprite_index:u32) -> VertexOutput {
+  let trf = u_world[sprite_index];
+  let sheet_region = u_sheet[sprite_index];
+  let tint = u_tint[sprite_index / 4u][sprite_index % 4u];
+  return sprite_to_vert(trf, UVData(sheet_region.x, sheet_region.y, sheet_region.z, sheet_region.w), tint, VERTICES[in_vertex_index], sprite_index);
+}
 
 // Now our fragment shader needs two "global" inputs to be bound:
 // A texture...
@@ -112,9 +148,110 @@ var s_diffuse: sampler;
 @fragment
 fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture.
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    // Fully faded sprites are left out of the depth buffer too
+    if color.w < 0.05 || in.tint.w == 0.0 { discard; }
+    // mod color by in.colormod, then tint it
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz;
+    return vec4<f32>(out_color.xyz, in.tint.w);
+}
+
+// Blended groups fade by the sheet's alpha as well as the tint's and
+// output premultiplied colors
+@fragment
+fn fs_main_blended(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let alpha = color.w * in.tint.w;
+    if alpha == 0.0 { discard; }
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz;
+    return vec4<f32>(out_color * alpha, alpha);
+}
+
+// Outline masks are drawn with this color wherever a sprite is visible
+@group(2) @binding(0)
+var<uniform> outline_color: vec4<f32>;
+
+@fragment
+fn fs_outline_mask(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 { discard; }
+    return outline_color;
+}
+
+// Object IDs are built from the current draw's pick table entry:
+// kind, group, mesh (unused), and a base added to the instance index
+@group(2) @binding(0)
+var<uniform> pick_entry: vec4<u32>;
+
+@fragment
+fn fs_pick(in:VertexOutput) -> @location(0) vec4<u32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 { discard; }
+    return vec4<u32>(pick_entry.xyz, u32(i32(in.instance) + bitcast<i32>(pick_entry.w)));
+}
+
+// Groups with an emissive layer sample it with the same coordinates
+// as their sheets; its color (times its alpha) is added unlit
+@group(2) @binding(0)
+var t_emissive: texture_2d_array<f32>;
+
+@fragment
+fn fs_main_emissive(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 || in.tint.w == 0.0 { discard; }
+    let glow:vec4<f32> = textureSample(t_emissive, s_diffuse, in.tex_coords, in.tex_index);
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz + glow.xyz * glow.w;
+    return vec4<f32>(out_color.xyz, in.tint.w);
+}
+
+@fragment
+fn fs_main_emissive_blended(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let alpha = color.w * in.tint.w;
+    if alpha == 0.0 { discard; }
+    let glow:vec4<f32> = textureSample(t_emissive, s_diffuse, in.tex_coords, in.tex_index);
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz + glow.xyz * glow.w;
+    return vec4<f32>(out_color * alpha, alpha);
+}
+
+// The emissive pass draws only the glow of each visible sprite...
+@fragment
+fn fs_emissive(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 { discard; }
+    let glow:vec4<f32> = textureSample(t_emissive, s_diffuse, in.tex_coords, in.tex_index);
+    return vec4<f32>(glow.xyz * glow.w, 1.0);
+}
+
+// ...and sprites without an emissive layer are drawn black so they
+// still hide whatever glows behind them
+@fragment
+fn fs_emissive_occluder(in:VertexOutput) -> @location(0) vec4<f32> {
     let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
     if color.w < 0.05 { discard; }
-    // mod color by in.colormod
-    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w);
-    return vec4<f32>(out_color.xyz, 1.0);
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
+
+// Debug views (see DebugView in sprites.rs).  Group tints reuse the
+// outline color binding.
+@fragment
+fn fs_debug_tint(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 { discard; }
+    let base = mix(color.xyz, in.colormod.xyz, in.colormod.w);
+    return vec4<f32>(mix(base, outline_color.xyz, 0.6), 1.0);
+}
+
+// Whole sheet layers are shown over a checkerboard so transparent texels are visible
+@fragment
+fn fs_debug_atlas(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let cell = vec2<u32>(in.clip_position.xy / 8.0);
+    let checker = select(0.25, 0.45, (cell.x + cell.y) % 2u == 0u);
+    return vec4<f32>(mix(vec3<f32>(checker), color.xyz, color.w), 1.0);
+}
+
+@fragment
+fn fs_debug_rect(in:VertexOutput) -> @location(0) vec4<f32> {
+    return outline_color;
 }
@@ -0,0 +1,9 @@
+// Sprite groups with a material are drawn with this entry point,
+// which is compiled along with the material's `material` function
+@fragment
+fn fs_material(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    if color.w < 0.05 || in.tint.w == 0.0 { discard; }
+    let out_color = mix(color.xyz, in.colormod.xyz, in.colormod.w) * in.tint.xyz;
+    return material(vec4<f32>(out_color, color.w * in.tint.w), in.tex_coords, in.clip_position);
+}
@@ -0,0 +1,359 @@
+//! Reading sprite sheet metadata exported by Aseprite and
+//! TexturePacker (with the `spritesheet` feature).
+//!
+//! [`SpriteSheet::from_aseprite_json`] reads the JSON file Aseprite
+//! writes next to an exported sprite sheet, and
+//! [`SpriteSheet::from_texturepacker_json`] reads TexturePacker's
+//! "JSON (Hash)" and "JSON (Array)" atlases (and the Phaser and PixiJS
+//! variants of them).  Either way, the result is a [`SpriteSheet`]
+//! with:
+//!
+//! - One [`SpriteFrame`] per exported frame, in the file's order,
+//!   whose [`SheetRegion`] covers the frame's pixels in the image and
+//!   which can be looked up by name with [`SpriteSheet::region`].
+//! - One [`AnimationTag`] per Aseprite frame tag, or per entry of a
+//!   TexturePacker atlas's `animations` table, which
+//!   [`SpriteSheet::animation`] turns into a [`SpriteAnimation`].
+//!
+//! The regions use array texture layer 0; call
+//! [`SpriteSheet::set_sheet`] if the image is loaded into another
+//! layer.  Frames TexturePacker rotated to pack them tighter are
+//! marked [`SpriteFrame::rotated`], and their regions cover the
+//! rotated pixels as they are in the image.
+
+use std::collections::HashMap;
+
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::animation::{AnimationFrame, LoopMode, SpriteAnimation};
+use crate::sprites::SheetRegion;
+
+/// An error reading sprite sheet metadata.
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    /// The file isn't JSON, or isn't laid out like a sprite sheet
+    Json(serde_json::Error),
+    /// A frame's rectangle doesn't fit in a [`SheetRegion`]
+    FrameTooLarge { frame: String },
+    /// An animation tag refers to a frame that isn't in the sheet
+    MissingFrame { tag: String },
+}
+
+impl std::fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpriteSheetError::Json(err) => write!(f, "Couldn't read sprite sheet: {err}"),
+            SpriteSheetError::FrameTooLarge { frame } => write!(
+                f,
+                "Sprite sheet frame {frame} is too large or too far from the origin"
+            ),
+            SpriteSheetError::MissingFrame { tag } => write!(
+                f,
+                "Sprite sheet animation {tag} refers to a frame that isn't in the sheet"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpriteSheetError {}
+
+impl From<serde_json::Error> for SpriteSheetError {
+    fn from(err: serde_json::Error) -> Self {
+        SpriteSheetError::Json(err)
+    }
+}
+
+/// One named frame of a [`SpriteSheet`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteFrame {
+    /// The frame's name (for Aseprite, usually the file name and
+    /// frame number)
+    pub name: String,
+    /// Where the frame's pixels are in the image
+    pub region: SheetRegion,
+    /// True if the exporter rotated the frame 90 degrees clockwise
+    /// to pack it, so that `region` is as tall as the frame is wide
+    pub rotated: bool,
+    /// How long the frame is shown, in seconds, if the file says
+    pub duration: Option<f32>,
+    /// Where the top left corner of `region` is in the untrimmed
+    /// frame, if the exporter trimmed off transparent edges
+    pub offset: [u16; 2],
+    /// The width and height of the untrimmed frame
+    pub source_size: [u16; 2],
+}
+
+/// A named animation of a [`SpriteSheet`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnimationTag {
+    pub name: String,
+    /// Indices into [`SpriteSheet::frames`] in the order they're
+    /// played (so backwards for Aseprite's reverse directions)
+    pub frames: Vec<usize>,
+    /// What happens after the last frame
+    pub loop_mode: LoopMode,
+    /// How many times Aseprite should play the animation, if the tag
+    /// says; a count of 1 makes `loop_mode` [`LoopMode::Once`], but
+    /// other counts are left for the game to enforce
+    pub repeat: Option<u32>,
+}
+
+/// Frame rectangles and animations read from a sprite sheet's
+/// metadata (see the [module documentation](self)).
+#[derive(Clone, Debug, Default)]
+pub struct SpriteSheet {
+    image: Option<String>,
+    size: Option<[u32; 2]>,
+    frames: Vec<SpriteFrame>,
+    names: HashMap<String, usize>,
+    tags: Vec<AnimationTag>,
+}
+
+impl SpriteSheet {
+    /// Reads the JSON metadata Aseprite exports with a sprite sheet,
+    /// with frames either as a hash or as an array.  Frame durations
+    /// and frame tags are kept; layers and slices are ignored.
+    pub fn from_aseprite_json(bytes: &[u8]) -> Result<Self, SpriteSheetError> {
+        Self::from_json(bytes)
+    }
+    /// Reads a TexturePacker JSON atlas, with frames either as a hash
+    /// or as an array.  A PixiJS-style `animations` table mapping
+    /// names to lists of frame names becomes the sheet's tags.
+    pub fn from_texturepacker_json(bytes: &[u8]) -> Result<Self, SpriteSheetError> {
+        Self::from_json(bytes)
+    }
+    // Both formats descend from the same layout, so one reader handles
+    // either; each just leaves the other's extra fields out
+    fn from_json(bytes: &[u8]) -> Result<Self, SpriteSheetError> {
+        let raw: RawSheet = serde_json::from_slice(bytes)?;
+        let mut sheet = Self {
+            image: raw.meta.image,
+            size: raw.meta.size.map(|size| [size.w, size.h]),
+            ..Self::default()
+        };
+        for (name, frame) in raw.frames.0 {
+            let name = name.or(frame.filename).unwrap_or_default();
+            let too_large = || SpriteSheetError::FrameTooLarge {
+                frame: name.clone(),
+            };
+            // A rotated frame's rectangle has its unrotated size
+            let (w, h) = if frame.rotated {
+                (frame.frame.h, frame.frame.w)
+            } else {
+                (frame.frame.w, frame.frame.h)
+            };
+            let region = SheetRegion::new(
+                0,
+                u16::try_from(frame.frame.x).map_err(|_| too_large())?,
+                u16::try_from(frame.frame.y).map_err(|_| too_large())?,
+                0,
+                i16::try_from(w).map_err(|_| too_large())?,
+                i16::try_from(h).map_err(|_| too_large())?,
+            );
+            let offset = frame
+                .sprite_source_size
+                .map(|rect| [rect.x, rect.y])
+                .unwrap_or_default();
+            let source_size = frame
+                .source_size
+                .map(|size| [size.w, size.h])
+                .unwrap_or([frame.frame.w, frame.frame.h]);
+            let fit = |value: u32| u16::try_from(value).map_err(|_| too_large());
+            let frame = SpriteFrame {
+                region,
+                rotated: frame.rotated,
+                duration: frame.duration.map(|ms| ms / 1000.0),
+                offset: [fit(offset[0])?, fit(offset[1])?],
+                source_size: [fit(source_size[0])?, fit(source_size[1])?],
+                name,
+            };
+            sheet
+                .names
+                .entry(frame.name.clone())
+                .or_insert(sheet.frames.len());
+            sheet.frames.push(frame);
+        }
+        for tag in raw.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= sheet.frames.len() {
+                return Err(SpriteSheetError::MissingFrame { tag: tag.name });
+            }
+            let repeat = tag.repeat.as_deref().and_then(|r| r.parse().ok());
+            let (frames, loop_mode): (Vec<usize>, _) = match tag.direction.as_str() {
+                "reverse" => ((tag.from..=tag.to).rev().collect(), LoopMode::Loop),
+                "pingpong" => ((tag.from..=tag.to).collect(), LoopMode::PingPong),
+                "pingpong_reverse" => ((tag.from..=tag.to).rev().collect(), LoopMode::PingPong),
+                _ => ((tag.from..=tag.to).collect(), LoopMode::Loop),
+            };
+            sheet.tags.push(AnimationTag {
+                name: tag.name,
+                frames,
+                loop_mode: if repeat == Some(1) {
+                    LoopMode::Once
+                } else {
+                    loop_mode
+                },
+                repeat,
+            });
+        }
+        for (name, frame_names) in raw.animations.0 {
+            let name = name.unwrap_or_default();
+            let frames = frame_names
+                .iter()
+                .map(|frame| sheet.names.get(frame).copied())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| SpriteSheetError::MissingFrame { tag: name.clone() })?;
+            sheet.tags.push(AnimationTag {
+                name,
+                frames,
+                loop_mode: LoopMode::Loop,
+                repeat: None,
+            });
+        }
+        Ok(sheet)
+    }
+    /// Returns the file name of the sheet's image, if the metadata says.
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+    /// Returns the width and height of the sheet's image in pixels,
+    /// if the metadata says.
+    pub fn size(&self) -> Option<[u32; 2]> {
+        self.size
+    }
+    /// Returns the frames, in the order they appear in the file.
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+    /// Returns the frame with the given name, if there is one.
+    pub fn frame(&self, name: &str) -> Option<&SpriteFrame> {
+        self.names.get(name).map(|&index| &self.frames[index])
+    }
+    /// Returns the region of the frame with the given name, if there
+    /// is one.
+    pub fn region(&self, name: &str) -> Option<SheetRegion> {
+        self.frame(name).map(|frame| frame.region)
+    }
+    /// Returns the animation tags.
+    pub fn tags(&self) -> &[AnimationTag] {
+        &self.tags
+    }
+    /// Returns the animation tag with the given name, if there is one.
+    pub fn tag(&self, name: &str) -> Option<&AnimationTag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+    /// Sets the array texture layer of every frame's region.
+    pub fn set_sheet(&mut self, sheet: u16) {
+        for frame in self.frames.iter_mut() {
+            frame.region.sheet = sheet;
+        }
+    }
+    /// Creates an animation playing the frames of the tag with the
+    /// given name, if there is one.  Frames without their own
+    /// duration (e.g. all of TexturePacker's) are shown for
+    /// `default_duration` seconds.
+    pub fn animation(&self, tag: &str, default_duration: f32) -> Option<SpriteAnimation> {
+        let tag = self.tag(tag)?;
+        let frames = tag
+            .frames
+            .iter()
+            .map(|&index| AnimationFrame {
+                region: self.frames[index].region,
+                duration: self.frames[index].duration.unwrap_or(default_duration),
+            })
+            .collect();
+        Some(SpriteAnimation::new(frames, tag.loop_mode))
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSheet {
+    frames: Ordered<RawFrame>,
+    #[serde(default)]
+    meta: RawMeta,
+    #[serde(default)]
+    animations: Ordered<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrame {
+    filename: Option<String>,
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<RawRect>,
+    source_size: Option<RawSize>,
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct RawRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct RawSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMeta {
+    image: Option<String>,
+    size: Option<RawSize>,
+    #[serde(default)]
+    frame_tags: Vec<RawTag>,
+}
+
+#[derive(Deserialize)]
+struct RawTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    repeat: Option<String>,
+}
+
+// The entries of a JSON object in the order they're written, since
+// tags refer to frames by position; or the elements of a JSON array,
+// without names.
+struct Ordered<T>(Vec<(Option<String>, T)>);
+
+impl<T> Default for Ordered<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Ordered<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedVisitor<T>(std::marker::PhantomData<T>);
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for OrderedVisitor<T> {
+            type Value = Ordered<T>;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an object or an array")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some((name, value)) = map.next_entry::<String, T>()? {
+                    entries.push((Some(name), value));
+                }
+                Ok(Ordered(entries))
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(value) = seq.next_element::<T>()? {
+                    entries.push((None, value));
+                }
+                Ok(Ordered(entries))
+            }
+        }
+        deserializer.deserialize_any(OrderedVisitor(std::marker::PhantomData))
+    }
+}
@@ -0,0 +1,81 @@
+// u_params[0]: x = radius, y = intensity, z = sample count, w = bias
+// u_params[1..5]: projection matrix columns
+// u_params[5..9]: inverse projection matrix columns
+
+fn proj() -> mat4x4<f32> {
+  return mat4x4<f32>(u_params[1], u_params[2], u_params[3], u_params[4]);
+}
+fn inv_proj() -> mat4x4<f32> {
+  return mat4x4<f32>(u_params[5], u_params[6], u_params[7], u_params[8]);
+}
+
+// The view-space position of the depth texel at pixel p
+fn view_pos(p:vec2<i32>) -> vec3<f32> {
+  let size = vec2<i32>(textureDimensions(t_depth));
+  let pc = clamp(p, vec2<i32>(0), size - vec2<i32>(1));
+  let depth = textureLoad(t_depth, pc, 0).r;
+  let uv = (vec2<f32>(pc) + 0.5) / vec2<f32>(size);
+  let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+  let v = inv_proj() * ndc;
+  return v.xyz / v.w;
+}
+
+fn hash(p:vec2<f32>) -> f32 {
+  return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureLoad(t_color, vec2<i32>(in.clip_position.xy), 0);
+  let depth_size = vec2<f32>(textureDimensions(t_depth));
+  // Without a real depth texture there's nothing to do
+  if any(textureDimensions(t_depth) != textureDimensions(t_color)) {
+    return color;
+  }
+  let p = vec2<i32>(in.tex_coords * depth_size);
+  if textureLoad(t_depth, p, 0).r >= 1.0 {
+    // Nothing was drawn here
+    return color;
+  }
+  let pos = view_pos(p);
+  // Reconstruct the normal from neighboring depths, using whichever
+  // side is closer to avoid smearing normals across edges
+  let right = view_pos(p + vec2<i32>(1, 0)) - pos;
+  let left = pos - view_pos(p - vec2<i32>(1, 0));
+  let down = view_pos(p + vec2<i32>(0, 1)) - pos;
+  let up = pos - view_pos(p - vec2<i32>(0, 1));
+  let dx = select(right, left, abs(left.z) < abs(right.z));
+  let dy = select(down, up, abs(up.z) < abs(down.z));
+  var normal = normalize(cross(dy, dx));
+  if dot(normal, -pos) < 0.0 {
+    normal = -normal;
+  }
+  // An orthonormal basis around the normal, randomly rotated per pixel
+  let angle = hash(in.clip_position.xy) * 6.2831853;
+  let seed = vec3<f32>(cos(angle), sin(angle), 0.0);
+  let tangent = normalize(seed - normal * dot(seed, normal) + vec3<f32>(0.0, 0.0, 1e-4));
+  let bitangent = cross(normal, tangent);
+  let radius = u_params[0].x;
+  let bias = u_params[0].w;
+  let count = u32(u_params[0].z);
+  var occlusion = 0.0;
+  for (var i = 0u; i < count; i++) {
+    // Spread samples over the hemisphere, closer to the point for low i
+    let t = (f32(i) + 0.5) / f32(count);
+    let phi = f32(i) * 2.3999632;
+    let z = sqrt(1.0 - t);
+    let r = sqrt(t);
+    let dir = tangent * (cos(phi) * r) + bitangent * (sin(phi) * r) + normal * z;
+    let scale = mix(0.1, 1.0, t * t);
+    let sample_pos = pos + dir * radius * scale;
+    let clip = proj() * vec4<f32>(sample_pos, 1.0);
+    let ndc = clip.xy / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let scene = view_pos(vec2<i32>(uv * depth_size));
+    // Only count occluders within the radius
+    let range = smoothstep(0.0, 1.0, radius / max(abs(pos.z - scene.z), 1e-4));
+    occlusion += select(0.0, 1.0, scene.z >= sample_pos.z + bias) * range;
+  }
+  let ao = 1.0 - occlusion / f32(count) * u_params[0].y;
+  return vec4<f32>(color.xyz * ao, color.w);
+}
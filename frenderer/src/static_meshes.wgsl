@@ -1,12 +1,63 @@
+// Lighting applies to meshes with normals; light_dir.w is 1.0 when
+// it's on.  light_color.w and sky.w are the strength and shininess of
+// textured meshes' highlights.
+struct Camera {
+  projview: mat4x4<f32>,
+  light_dir: vec4<f32>,
+  light_color: vec4<f32>,
+  sky: vec4<f32>,
+  ground: vec4<f32>,
+}
 @group(0) @binding(0)
-var<uniform> projview: mat4x4<f32>;
+var<uniform> camera: Camera;
+
+// Point lights also only apply to meshes with normals.  color_shadow.w is
+// the first of the light's six shadow map layers (one per cube face,
+// drawn with the matrices in `faces`), or negative without shadows.
+struct PointLight {
+  position_range: vec4<f32>,
+  color_shadow: vec4<f32>,
+  faces: array<mat4x4<f32>, 6>,
+}
+struct PointLights {
+  // The number of lights and the shadow map size
+  count_size: vec4<u32>,
+  lights: array<PointLight, 4>,
+}
+@group(0) @binding(1)
+var<uniform> point_lights: PointLights;
+@group(0) @binding(2)
+var point_shadow_maps: texture_depth_2d_array;
+@group(0) @binding(3)
+var point_shadow_sampler: sampler_comparison;
+
+// The sun's shadows (see `crate::shadow::SunShadows`), drawn from the
+// light direction in `camera`.  Each cascade has a matrix and a
+// rectangle of the atlas; direction_count.w is 0 without shadows.
+struct Sun {
+  cascades: array<mat4x4<f32>, 4>,
+  rects: array<vec4<f32>, 4>,
+  direction_count: vec4<f32>,
+  texel_sizes: vec4<f32>,
+  // The map size in texels, bias, filter radius, and textured shade
+  params: vec4<f32>,
+}
+@group(0) @binding(4)
+var<uniform> sun: Sun;
+@group(0) @binding(5)
+var sun_map: texture_depth_2d;
 
 struct VertexInput {
   @location(0) position: vec3<f32>,
-  @location(1) uv_which: vec3<f32>
+  @location(1) uv_which: vec3<f32>,
+  @location(6) normal: vec3<f32>,
+  @location(7) lightmap_uv: vec2<f32>,
+  @location(8) tangent: vec4<f32>,
 }
 struct FlatVertexInput {
   @location(0) position_which: vec4<f32>,
+  @location(1) color: vec4<f32>,
+  @location(6) normal: vec3<f32>,
 }
 
 struct InstanceInput {
@@ -18,33 +69,255 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) @interpolate(flat) tex_index: u32,
+    // Which instance this is, for object ID rendering
+    @location(2) @interpolate(flat) instance: u32,
+    @location(3) lightmap_uv: vec2<f32>,
+    // For lighting and the sun's shadows, in world space; the
+    // normal is zero if the vertex has none
+    @location(4) world_position: vec3<f32>,
+    @location(5) world_normal: vec3<f32>,
+    @location(6) to_eye: vec3<f32>,
+    // For normal maps; zero if the vertex has no tangent
+    @location(7) world_tangent: vec4<f32>,
 }
 
 struct FlatVertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) @interpolate(flat) mat_index: u32,
+    @location(1) @interpolate(flat) instance: u32,
+    // Multiplied with the material color
+    @location(2) color: vec4<f32>,
+    // For point lights, in world space; the normal is zero if the
+    // vertex has none
+    @location(3) world_position: vec3<f32>,
+    @location(4) world_normal: vec3<f32>,
+    // The vertex color before lighting
+    @location(5) albedo: vec4<f32>,
+    // The hemisphere and directional lighting
+    @location(6) light: vec3<f32>,
+    // The directional part of `light`, which is shadowed by the sun
+    @location(7) sun: vec3<f32>,
 }
 
-@vertex
-fn vs_main(vtx:VertexInput, inst:InstanceInput) -> VertexOutput {
+// When instance-rate vertex buffers aren't available, instances are
+// drawn in chunks of 512 using this uniform binding instead.  Each
+// instance takes up two vec4s.
+@group(2) @binding(0)
+var<uniform> u_instances: array<vec4<f32>, 1024>;
+
+fn uniform_instance(index:u32) -> InstanceInput {
+  return InstanceInput(u_instances[index*2u], u_instances[index*2u+1u]);
+}
+
+fn mesh_vert(vtx:VertexInput, inst:InstanceInput, instance:u32) -> VertexOutput {
   var out:VertexOutput;
   let model = mat_from_trs(inst.translate_scale.xyz, inst.rot, inst.translate_scale.w);
   let transformed = model * vec4(vtx.position,1.0);
-  out.clip_position = projview * transformed;
+  out.clip_position = camera.projview * transformed;
   out.tex_coords = vtx.uv_which.xy;
   out.tex_index = bitcast<u32>(vtx.uv_which.z);
+  out.instance = instance;
+  out.lightmap_uv = vtx.lightmap_uv;
+  out.world_position = transformed.xyz;
+  out.world_normal = vec3(0.0);
+  if dot(vtx.normal, vtx.normal) > 0.0 {
+    out.world_normal = normalize((model * vec4(vtx.normal, 0.0)).xyz);
+  }
+  out.to_eye = to_eye(transformed.xyz);
+  out.world_tangent = vec4(0.0);
+  if dot(vtx.tangent.xyz, vtx.tangent.xyz) > 0.0 {
+    out.world_tangent = vec4(normalize((model * vec4(vtx.tangent.xyz, 0.0)).xyz), vtx.tangent.w);
+  }
   return out;
 }
-@vertex
-fn vs_flat_main(vtx:FlatVertexInput, inst:InstanceInput) -> FlatVertexOutput {
+fn flat_vert(vtx:FlatVertexInput, inst:InstanceInput, instance:u32) -> FlatVertexOutput {
   var out:FlatVertexOutput;
   let model = mat_from_trs(inst.translate_scale.xyz, inst.rot, inst.translate_scale.w);
   let transformed = model * vec4(vtx.position_which.xyz,1.0);
-  out.clip_position = projview * transformed;
+  out.clip_position = camera.projview * transformed;
   out.mat_index = bitcast<u32>(vtx.position_which.w);
+  out.instance = instance;
+  out.light = lighting(model, vtx.normal);
+  out.sun = sunlight(model, vtx.normal);
+  out.color = vtx.color * vec4(out.light, 1.0);
+  out.world_position = transformed.xyz;
+  out.world_normal = vec3(0.0);
+  if dot(vtx.normal, vtx.normal) > 0.0 {
+    out.world_normal = normalize((model * vec4(vtx.normal, 0.0)).xyz);
+  }
+  out.albedo = vtx.color;
   return out;
 }
 
+// Hemisphere plus directional lighting, computed per vertex
+fn lighting(model:mat4x4<f32>, normal:vec3<f32>) -> vec3<f32> {
+  if camera.light_dir.w == 0.0 || dot(normal, normal) == 0.0 {
+    return vec3(1.0);
+  }
+  let n = normalize((model * vec4(normal, 0.0)).xyz);
+  let ambient = mix(camera.ground.rgb, camera.sky.rgb, n.y * 0.5 + 0.5);
+  return ambient + sunlight(model, normal);
+}
+// The directional part of `lighting`
+fn sunlight(model:mat4x4<f32>, normal:vec3<f32>) -> vec3<f32> {
+  if camera.light_dir.w == 0.0 || dot(normal, normal) == 0.0 {
+    return vec3(0.0);
+  }
+  let n = normalize((model * vec4(normal, 0.0)).xyz);
+  return camera.light_color.rgb * max(dot(n, camera.light_dir.xyz), 0.0);
+}
+
+// The direction from a position towards the camera.  The camera is
+// where projview's clip x, y, and w are all zero, which is a
+// direction rather than a point for orthographic projections.
+fn to_eye(pos:vec3<f32>) -> vec3<f32> {
+  let m = transpose(camera.projview);
+  let eye = cross4(m[0], m[1], m[3]);
+  if abs(eye.w) < 1e-6 {
+    // Pointing the way depth decreases
+    return eye.xyz * -sign(dot(m[2].xyz, eye.xyz));
+  }
+  return eye.xyz / eye.w - pos;
+}
+// A vector perpendicular to the three given ones
+fn cross4(a:vec4<f32>, b:vec4<f32>, c:vec4<f32>) -> vec4<f32> {
+  return vec4(
+    dot(a.yzw, cross(b.yzw, c.yzw)),
+    -dot(a.xzw, cross(b.xzw, c.xzw)),
+    dot(a.xyw, cross(b.xyw, c.xyw)),
+    -dot(a.xyz, cross(b.xyz, c.xyz)),
+  );
+}
+
+// Light split into the part multiplied with a surface's color and
+// the highlights added on top
+struct Lighting {
+  diffuse: vec3<f32>,
+  specular: vec3<f32>,
+}
+// The Blinn-Phong highlight of a light from to_light seen from
+// to_eye (both normalized), given a strength and shininess
+fn blinn_phong(normal:vec3<f32>, to_light:vec3<f32>, to_eye:vec3<f32>, specular:vec2<f32>) -> f32 {
+  if specular.x <= 0.0 || dot(normal, to_light) <= 0.0 {
+    return 0.0;
+  }
+  let half_dir = normalize(to_light + to_eye);
+  return specular.x * pow(max(dot(normal, half_dir), 0.0), specular.y);
+}
+
+// Point lighting, computed per fragment.  Shadow maps are sampled in
+// uniform control flow, so unlit fragments are zeroed at the end.
+// Flat meshes pass a zero specular strength.
+fn point_lighting(pos:vec3<f32>, normal:vec3<f32>, to_eye:vec3<f32>, specular:vec2<f32>) -> Lighting {
+  var total = Lighting(vec3(0.0), vec3(0.0));
+  for (var i = 0u; i < point_lights.count_size.x; i++) {
+    let reach = point_reach(i, pos, normal);
+    let lambert = max(dot(normal, reach.dir), 0.0);
+    total.diffuse += reach.light * lambert;
+    total.specular += reach.light * blinn_phong(normal, reach.dir, to_eye, specular);
+  }
+  let lit = dot(normal, normal) > 0.0;
+  return Lighting(select(vec3(0.0), total.diffuse, lit), select(vec3(0.0), total.specular, lit));
+}
+// The light of point light i which reaches a position, after
+// falloff and shadows, and the direction towards it
+struct PointReach {
+  dir: vec3<f32>,
+  light: vec3<f32>,
+}
+fn point_reach(i:u32, pos:vec3<f32>, normal:vec3<f32>) -> PointReach {
+  let light = point_lights.lights[i];
+  let to_light = light.position_range.xyz - pos;
+  let dist = length(to_light);
+  let falloff = clamp(1.0 - dist / light.position_range.w, 0.0, 1.0);
+  var shadow = 1.0;
+  if light.color_shadow.w >= 0.0 {
+    // Offset by about a shadow map texel along the normal to avoid acne
+    let texel = 2.0 * dist / f32(point_lights.count_size.y);
+    shadow = point_shadow(i, pos + normal * texel * 1.5);
+  }
+  return PointReach(to_light / max(dist, 0.0001), light.color_shadow.rgb * (falloff * falloff * shadow));
+}
+// How much of the given light reaches a position, from its shadow maps
+fn point_shadow(light:u32, pos:vec3<f32>) -> f32 {
+  let d = pos - point_lights.lights[light].position_range.xyz;
+  let a = abs(d);
+  // The cube face the position falls on: +x, -x, +y, -y, +z, or -z
+  let face = select(
+    select(select(5u, 4u, d.z > 0.0), select(3u, 2u, d.y > 0.0), a.y >= a.z),
+    select(1u, 0u, d.x > 0.0),
+    a.x >= a.y && a.x >= a.z
+  );
+  let clip = point_lights.lights[light].faces[face] * vec4(pos, 1.0);
+  let ndc = clip.xyz / clip.w;
+  let uv = ndc.xy * vec2(0.5, -0.5) + 0.5;
+  let layer = i32(point_lights.lights[light].color_shadow.w) + i32(face);
+  return textureSampleCompare(point_shadow_maps, point_shadow_sampler, uv, layer, ndc.z);
+}
+// How much sunlight reaches a position, from the first cascade
+// covering it.  The position is offset along `offset_dir` by the
+// bias, scaled to that cascade's texels and widened with the filter
+// since its outer taps compare against further-off depths.
+fn sun_shadow(pos:vec3<f32>, offset_dir:vec3<f32>) -> f32 {
+  let count = u32(sun.direction_count.w);
+  let bias = sun.params.y * (1.0 + sun.params.z);
+  for (var c = 0u; c < count; c++) {
+    let biased = pos + offset_dir * (bias * sun.texel_sizes[c]);
+    let clip = sun.cascades[c] * vec4(biased, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if all(abs(ndc.xy) <= vec2(1.0)) && ndc.z >= 0.0 && ndc.z <= 1.0 {
+      return sun_pcf(c, ndc);
+    }
+  }
+  return 1.0;
+}
+// Averages a square of comparisons around ndc in one cascade's part
+// of the atlas, keeping taps inside it
+fn sun_pcf(cascade:u32, ndc:vec3<f32>) -> f32 {
+  let rect = sun.rects[cascade];
+  let texel = 1.0 / sun.params.x;
+  let uv = ndc.xy * vec2(0.5, -0.5) + 0.5;
+  let radius = i32(sun.params.z);
+  var total = 0.0;
+  for (var y = -radius; y <= radius; y++) {
+    for (var x = -radius; x <= radius; x++) {
+      let tap = clamp(uv + vec2(f32(x), f32(y)) * texel, vec2(texel * 0.5), vec2(1.0 - texel * 0.5));
+      total += textureSampleCompareLevel(sun_map, point_shadow_sampler, rect.xy + tap * rect.zw, ndc.z);
+    }
+  }
+  let side = f32(2 * radius + 1);
+  return total / (side * side);
+}
+// The sunlight a flat mesh fragment loses to shadow
+fn flat_sun_shadowed(in:FlatVertexOutput) -> vec3<f32> {
+  let has_normal = dot(in.world_normal, in.world_normal) > 0.0;
+  let offset_dir = select(sun.direction_count.xyz, in.world_normal, has_normal);
+  return in.sun * (1.0 - sun_shadow(in.world_position, offset_dir));
+}
+// A flat mesh fragment's color before its material color
+fn flat_color(in:FlatVertexOutput) -> vec4<f32> {
+  let point = point_lighting(in.world_position, in.world_normal, vec3(0.0), vec2(0.0)).diffuse;
+  let light = in.albedo.rgb * (point - flat_sun_shadowed(in));
+  return in.color + vec4(light, 0.0);
+}
+
+@vertex
+fn vs_main(vtx:VertexInput, inst:InstanceInput, @builtin(instance_index) instance:u32) -> VertexOutput {
+  return mesh_vert(vtx, inst, instance);
+}
+@vertex
+fn vs_flat_main(vtx:FlatVertexInput, inst:InstanceInput, @builtin(instance_index) instance:u32) -> FlatVertexOutput {
+  return flat_vert(vtx, inst, instance);
+}
+@vertex
+fn vs_uniform_main(vtx:VertexInput, @builtin(instance_index) instance:u32) -> VertexOutput {
+  return mesh_vert(vtx, uniform_instance(instance), instance);
+}
+@vertex
+fn vs_flat_uniform_main(vtx:FlatVertexInput, @builtin(instance_index) instance:u32) -> FlatVertexOutput {
+  return flat_vert(vtx, uniform_instance(instance), instance);
+}
+
 fn mat_from_trs(t:vec3<f32>, r:vec4<f32>, s:f32) -> mat4x4<f32> {
   let trans =
     mat4x4<f32>
@@ -90,12 +363,96 @@ var t_diffuse: texture_2d_array<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 // Both are in the same binding group here since they go together naturally.
+// The group's lightmap is multiplied in (it's white if there isn't one).
+@group(1) @binding(2)
+var t_lightmap: texture_2d<f32>;
+@group(1) @binding(3)
+var s_lightmap: sampler;
+// Which layer holds each albedo layer's normal map and
+// metallic-roughness map, plus one (or 0 for none); srgb.x is 1 if
+// the texture's format is sRGB.
+struct TextureLayers {
+  srgb: vec4<u32>,
+  normal_maps: array<vec4<u32>, 64>,
+  metallic_roughness: array<vec4<u32>, 64>,
+}
+@group(1) @binding(4)
+var<uniform> texture_layers: TextureLayers;
+
+// Samples a map of a textured mesh fragment's albedo layer, which
+// is in layer entry - 1, converting it back to linear values if the
+// texture is sRGB.  It's sampled in uniform control flow even if
+// entry is 0 (for none).
+fn sample_map(in:VertexOutput, entry:u32) -> vec3<f32> {
+    let mapped = textureSample(t_diffuse, s_diffuse, in.tex_coords, max(entry, 1u) - 1u).rgb;
+    if texture_layers.srgb.x != 0u {
+        return linear_to_srgb(mapped);
+    }
+    return mapped;
+}
+
+// A textured mesh fragment's normal, perturbed by its normal map if
+// it has one, or zero if it has none.  The normal map is sampled in
+// uniform control flow and then ignored if it's not there.
+fn textured_normal(in:VertexOutput) -> vec3<f32> {
+    var entry = 0u;
+    if in.tex_index < 256u {
+        entry = texture_layers.normal_maps[in.tex_index / 4u][in.tex_index % 4u];
+    }
+    let mapped = sample_map(in, entry);
+    if dot(in.world_normal, in.world_normal) == 0.0 {
+        return vec3(0.0);
+    }
+    let normal = normalize(in.world_normal);
+    if entry == 0u || dot(in.world_tangent.xyz, in.world_tangent.xyz) == 0.0 {
+        return normal;
+    }
+    // Interpolation may have bent the tangent away from the normal
+    let tangent = normalize(in.world_tangent.xyz - normal * dot(normal, in.world_tangent.xyz));
+    let bitangent = cross(normal, tangent) * in.world_tangent.w;
+    let n = mapped * 2.0 - 1.0;
+    return normalize(tangent * n.x + bitangent * n.y + normal * n.z);
+}
+fn linear_to_srgb(c:vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3(0.0031308));
+}
+// A textured mesh fragment's hemisphere and directional light, or
+// just the sun's shade if it isn't lit
+fn textured_lighting(in:VertexOutput, normal:vec3<f32>) -> Lighting {
+    let lit = camera.light_dir.w != 0.0 && dot(normal, normal) > 0.0;
+    let l = camera.light_dir.xyz;
+    let shadow = sun_shadow(in.world_position, select(sun.direction_count.xyz, normal, lit));
+    if !lit {
+        return Lighting(vec3(mix(1.0, shadow, sun.params.w)), vec3(0.0));
+    }
+    let ambient = mix(camera.ground.rgb, camera.sky.rgb, normal.y * 0.5 + 0.5);
+    let direct = camera.light_color.rgb * shadow;
+    let specular = vec2(camera.light_color.w, camera.sky.w);
+    let highlight = blinn_phong(normal, l, normalize(in.to_eye), specular);
+    return Lighting(ambient + direct * max(dot(normal, l), 0.0), direct * highlight);
+}
+fn textured_point_lighting(in:VertexOutput, normal:vec3<f32>) -> Lighting {
+    let specular = vec2(camera.light_color.w, camera.sky.w);
+    return point_lighting(in.world_position, normal, normalize(in.to_eye), specular);
+}
+// The lightmap only scales the hemisphere and directional light
+fn textured_color(in:VertexOutput) -> vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let lightmap = textureSample(t_lightmap, s_lightmap, in.lightmap_uv).rgb;
+    let normal = textured_normal(in);
+    let light = textured_lighting(in, normal);
+    let point = textured_point_lighting(in, normal);
+    let diffuse = color.rgb * (lightmap * light.diffuse + point.diffuse);
+    return vec4(diffuse + light.specular + point.specular, color.a);
+}
 
 // Our fragment shader takes an interpolated `VertexOutput` as input now
 @fragment
 fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture.
-    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let color:vec4<f32> = textured_color(in);
     // if color.w < 0.2 { discard; }
     return color;
 }
@@ -109,6 +466,118 @@ var<uniform> mat_diffuse: array<vec4<f32>, 256>;
 @fragment
 fn fs_flat_main(in:FlatVertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture
-    let color:vec4<f32> = mat_diffuse[in.mat_index];
+    let color:vec4<f32> = mat_diffuse[in.mat_index] * flat_color(in);
     return color;
 }
+
+// The G-buffer targets of the deferred shading path (see
+// `crate::deferred`).  Point lights are added when the G-buffer is
+// resolved, in proportion to material.a.
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) material: vec4<f32>,
+}
+
+// Highlights aren't kept in the G-buffer, so textured meshes lose them
+@fragment
+fn fs_gbuffer(in:VertexOutput) -> GBufferOutput {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let lightmap = textureSample(t_lightmap, s_lightmap, in.lightmap_uv).rgb;
+    let normal = textured_normal(in);
+    let light = lightmap * textured_lighting(in, normal).diffuse;
+    let lit = select(0.0, 1.0, dot(normal, normal) > 0.0);
+    return GBufferOutput(color, vec4(normal, 1.0), vec4(light, lit));
+}
+@fragment
+fn fs_flat_gbuffer(in:FlatVertexOutput) -> GBufferOutput {
+    let albedo = mat_diffuse[in.mat_index] * in.albedo;
+    let light = in.light - flat_sun_shadowed(in);
+    return GBufferOutput(albedo, vec4(in.world_normal, 1.0), vec4(light, 1.0));
+}
+
+// Outline masks are drawn with this color wherever a mesh is visible.
+@group(1) @binding(0)
+var<uniform> outline_color: vec4<f32>;
+
+@fragment
+fn fs_outline_mask(in:VertexOutput) -> @location(0) vec4<f32> {
+    return outline_color;
+}
+@fragment
+fn fs_flat_outline_mask(in:FlatVertexOutput) -> @location(0) vec4<f32> {
+    return outline_color;
+}
+
+// Object IDs are built from the current draw's pick table entry:
+// kind, group, mesh, and a base added to the instance index
+@group(1) @binding(0)
+var<uniform> pick_entry: vec4<u32>;
+
+fn pick_id(instance:u32) -> vec4<u32> {
+    return vec4<u32>(pick_entry.xyz, u32(i32(instance) + bitcast<i32>(pick_entry.w)));
+}
+@fragment
+fn fs_pick(in:VertexOutput) -> @location(0) vec4<u32> {
+    return pick_id(in.instance);
+}
+@fragment
+fn fs_flat_pick(in:FlatVertexOutput) -> @location(0) vec4<u32> {
+    return pick_id(in.instance);
+}
+
+// Velocities compare each vertex's position under this frame's
+// camera and transforms with last frame's
+@group(1) @binding(0)
+var<uniform> prev_projview: mat4x4<f32>;
+// Last frame's instances, chunked like `u_instances`
+@group(3) @binding(0)
+var<uniform> u_prev_instances: array<vec4<f32>, 1024>;
+
+struct PrevInstanceInput {
+  @location(4) translate_scale: vec4<f32>,
+  @location(5) rot: vec4<f32>,
+}
+
+struct VelocityOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) current: vec4<f32>,
+    @location(1) previous: vec4<f32>,
+}
+
+fn velocity_vert(position:vec3<f32>, inst:InstanceInput, prev:InstanceInput) -> VelocityOutput {
+  var out:VelocityOutput;
+  let model = mat_from_trs(inst.translate_scale.xyz, inst.rot, inst.translate_scale.w);
+  let prev_model = mat_from_trs(prev.translate_scale.xyz, prev.rot, prev.translate_scale.w);
+  out.current = camera.projview * model * vec4(position, 1.0);
+  out.previous = prev_projview * prev_model * vec4(position, 1.0);
+  out.clip_position = out.current;
+  return out;
+}
+
+@vertex
+fn vs_velocity(vtx:VertexInput, inst:InstanceInput, prev:PrevInstanceInput) -> VelocityOutput {
+  return velocity_vert(vtx.position, inst, InstanceInput(prev.translate_scale, prev.rot));
+}
+@vertex
+fn vs_flat_velocity(vtx:FlatVertexInput, inst:InstanceInput, prev:PrevInstanceInput) -> VelocityOutput {
+  return velocity_vert(vtx.position_which.xyz, inst, InstanceInput(prev.translate_scale, prev.rot));
+}
+@vertex
+fn vs_velocity_uniform(vtx:VertexInput, @builtin(instance_index) instance:u32) -> VelocityOutput {
+  let prev = InstanceInput(u_prev_instances[instance*2u], u_prev_instances[instance*2u+1u]);
+  return velocity_vert(vtx.position, uniform_instance(instance), prev);
+}
+@vertex
+fn vs_flat_velocity_uniform(vtx:FlatVertexInput, @builtin(instance_index) instance:u32) -> VelocityOutput {
+  let prev = InstanceInput(u_prev_instances[instance*2u], u_prev_instances[instance*2u+1u]);
+  return velocity_vert(vtx.position_which.xyz, uniform_instance(instance), prev);
+}
+
+// The change in texture coordinates (y down) since last frame
+@fragment
+fn fs_velocity(in:VelocityOutput) -> @location(0) vec2<f32> {
+    let current = in.current.xy / in.current.w;
+    let previous = in.previous.xy / in.previous.w;
+    return (current - previous) * vec2<f32>(0.5, -0.5);
+}
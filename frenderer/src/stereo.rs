@@ -0,0 +1,378 @@
+//! Stereoscopic rendering of the 3D scene.
+//!
+//! In stereo mode, meshes are drawn twice per frame through a pair of
+//! camera views (see [`crate::CameraViews`]) offset to either side of
+//! the mesh camera, each into its own eye texture owned by a
+//! [`StereoPass`].  The pass then combines the two images onto the
+//! color target, either as a red-cyan anaglyph for colored glasses or
+//! squeezed side by side for 3D displays.  [`crate::Renderer`] does
+//! all of this when stereo is turned on with
+//! [`crate::Renderer::stereo_set`]; sprites are drawn afterwards on
+//! top of the combined image, as a flat overlay.
+
+use std::borrow::Cow;
+
+use crate::{capture, meshes::Camera3D, msaa::MsaaTargets, WGPU};
+
+/// How the two eyes' images are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StereoMode {
+    /// The left eye's red channel and the right eye's green and blue
+    /// channels, for red-cyan glasses
+    #[default]
+    Anaglyph,
+    /// The left eye's image squeezed into the left half of the
+    /// target and the right eye's into the right half
+    SideBySide,
+}
+
+/// One of the two eyes of a stereo pair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Parameters for stereo rendering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub mode: StereoMode,
+    /// The distance between the two eye cameras in world units
+    pub eye_separation: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            mode: StereoMode::default(),
+            eye_separation: 0.1,
+        }
+    }
+}
+
+impl Stereo {
+    /// Returns the left and right eye cameras for a given camera,
+    /// moved half the eye separation to either side along the
+    /// camera's own x axis.
+    pub fn eye_cameras(&self, camera: &Camera3D) -> [Camera3D; 2] {
+        let right = ultraviolet::Rotor3::from_quaternion_array(camera.rotation)
+            * ultraviolet::Vec3::unit_x()
+            * (self.eye_separation / 2.0);
+        let center = ultraviolet::Vec3::from(camera.translation);
+        [center - right, center + right].map(|translation| Camera3D {
+            translation: translation.into(),
+            ..*camera
+        })
+    }
+}
+
+/// Draws the eye textures of a stereo pair onto a color target.
+pub struct StereoPass {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    params_buf: wgpu::Buffer,
+    stereo: Stereo,
+    format: wgpu::TextureFormat,
+    eyes: [(wgpu::Texture, wgpu::TextureView); 2],
+    // Both eyes are drawn into this and resolved into their textures
+    // when multisampling
+    msaa: Option<MsaaTargets>,
+}
+
+impl StereoPass {
+    /// Creates a [`StereoPass`] with a pair of `width` by `height` eye
+    /// textures, which draws into color targets of the given format.
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        stereo: Stereo,
+    ) -> Self {
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("stereo:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("stereo.wgsl"))),
+            });
+        let eye_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("stereo:bgl"),
+                    entries: &[
+                        // Parameters
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        // The left and right eye textures
+                        eye_entry(2),
+                        eye_entry(3),
+                    ],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("stereo:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let pipeline = Self::create_pipeline(gpu, &shader, &pipeline_layout, format, 1);
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("stereo:sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buf = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("stereo:params"),
+            size: std::mem::size_of::<[u32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let eyes = [
+            Self::create_eye(gpu, width, height, format),
+            Self::create_eye(gpu, width, height, format),
+        ];
+        let bind_group =
+            Self::create_bind_group(gpu, &bind_group_layout, &params_buf, &sampler, &eyes);
+        let mut pass = Self {
+            shader,
+            pipeline_layout,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            params_buf,
+            stereo,
+            format,
+            eyes,
+            msaa: None,
+        };
+        pass.set_stereo(gpu, stereo);
+        pass
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("stereo:pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    fn create_eye(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("stereo:eye"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_bind_group(
+        gpu: &WGPU,
+        layout: &wgpu::BindGroupLayout,
+        params_buf: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        eyes: &[(wgpu::Texture, wgpu::TextureView); 2],
+    ) -> wgpu::BindGroup {
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("stereo:bg"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&eyes[0].1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&eyes[1].1),
+                },
+            ],
+        })
+    }
+    /// Re-creates the eye textures at a new size (for example,
+    /// because the color target has changed size).
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        self.eyes = [
+            Self::create_eye(gpu, width, height, self.format),
+            Self::create_eye(gpu, width, height, self.format),
+        ];
+        self.bind_group = Self::create_bind_group(
+            gpu,
+            &self.bind_group_layout,
+            &self.params_buf,
+            &self.sampler,
+            &self.eyes,
+        );
+        self.set_sample_count(gpu, self.sample_count());
+    }
+    /// Changes the number of samples per pixel the eyes are drawn
+    /// with, and of the render pass [`StereoPass::render`] draws
+    /// into.  The eye passes' depth texture and the pipelines drawn
+    /// into them must have the same sample count.  The default is 1
+    /// (no multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        let size = self.eyes[0].0.size();
+        self.msaa = (sample_count > 1).then(|| {
+            MsaaTargets::new(
+                gpu,
+                size.width,
+                size.height,
+                self.format,
+                None,
+                sample_count,
+            )
+        });
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.format,
+            sample_count,
+        );
+    }
+    /// Returns the number of samples per pixel the eyes are drawn with.
+    pub fn sample_count(&self) -> u32 {
+        self.msaa.as_ref().map_or(1, MsaaTargets::sample_count)
+    }
+    /// Returns the stereo parameters.
+    pub fn stereo(&self) -> &Stereo {
+        &self.stereo
+    }
+    /// Changes the stereo parameters.
+    pub fn set_stereo(&mut self, gpu: &WGPU, stereo: Stereo) {
+        self.stereo = stereo;
+        let mode = match stereo.mode {
+            StereoMode::Anaglyph => 0_u32,
+            StereoMode::SideBySide => 1,
+        };
+        gpu.queue()
+            .write_buffer(&self.params_buf, 0, bytemuck::cast_slice(&[mode, 0, 0, 0]));
+    }
+    /// Returns the texture one eye's image is drawn into.
+    pub fn eye_texture(&self, eye: Eye) -> &wgpu::Texture {
+        &self.eyes[eye as usize].0
+    }
+    /// Begins a render pass which clears an eye texture and the given
+    /// depth texture (which should be the same size as the eye
+    /// textures); draw that eye's view of the scene into it, e.g.
+    /// with the mesh renderers' `render_with_view` methods.
+    pub fn begin_eye_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+        eye: Eye,
+        depth_view: Option<&'s wgpu::TextureView>,
+    ) -> wgpu::RenderPass<'s> {
+        let label = match eye {
+            Eye::Left => "stereo:left",
+            Eye::Right => "stereo:right",
+        };
+        capture::begin_pass(label);
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self
+                    .msaa
+                    .as_ref()
+                    .map_or(&self.eyes[eye as usize].1, MsaaTargets::color_view),
+                resolve_target: self.msaa.as_ref().map(|_| &self.eyes[eye as usize].1),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: depth_view.map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            ..Default::default()
+        })
+    }
+    /// Draws the combined eye images onto the given renderpass,
+    /// covering the whole target.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "stereo:pipeline");
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "stereo:bind_group");
+        capture::draw(rpass, 0..6, 0..1);
+    }
+}
@@ -0,0 +1,41 @@
+var<private> VERTICES:array<vec4<f32>,6> = array<vec4<f32>,6>(
+    vec4<f32>(-1., -1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(-1., 1., 0., 1.),
+    vec4<f32>(1., -1., 0., 1.),
+    vec4<f32>(1., 1., 0., 1.)
+);
+
+// x is the mode (0 for anaglyph, 1 for side by side); the rest is padding
+@group(0) @binding(0)
+var<uniform> u_params: vec4<u32>;
+@group(0) @binding(1)
+var s_eye: sampler;
+@group(0) @binding(2)
+var t_left: texture_2d<f32>;
+@group(0) @binding(3)
+var t_right: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let pos = VERTICES[in_vertex_index];
+    return VertexOutput(pos, vec2<f32>(pos.x + 1.0, 1.0 - pos.y) / 2.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // sample both eyes up front, since sampling needs uniform control flow
+    let squeezed = vec2<f32>(fract(in.uv.x * 2.0), in.uv.y);
+    let left = textureSample(t_left, s_eye, select(in.uv, squeezed, u_params.x == 1u));
+    let right = textureSample(t_right, s_eye, select(in.uv, squeezed, u_params.x == 1u));
+    if u_params.x == 1u {
+        return select(right, left, in.uv.x < 0.5);
+    }
+    return vec4<f32>(left.r, right.g, right.b, max(left.a, right.a));
+}
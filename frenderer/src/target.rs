@@ -0,0 +1,128 @@
+//! Offscreen render targets, for drawing the scene into a texture
+//! which sprite and mesh groups can then draw from.
+//!
+//! A [`RenderTarget`] owns a color texture and, optionally, a depth
+//! texture.  [`crate::Renderer::render_to`] draws a frame into it just
+//! as [`crate::Renderer::render_to_view`] would into a host's view,
+//! and [`RenderTarget::texture`] can be passed to e.g.
+//! [`crate::Renderer::sprite_group_add`] or
+//! [`crate::Renderer::mesh_group_add`] like any other array texture,
+//! with the image in layer 0.  This is how minimaps, security camera
+//! screens, and portals are drawn: move the cameras, render into the
+//! target, put them back, and render as usual.  A group drawing from
+//! a target shows what was last rendered into it, so a target can
+//! even show itself from the frame before.
+//!
+//! The scene is drawn at the renderer's render resolution and
+//! stretched over the target, so for pixel-perfect low-resolution
+//! rendering, make the render resolution (see
+//! [`crate::Renderer::resize_render`]) the target's size and draw the
+//! target scaled up by a whole number.
+
+use crate::{
+    embed::{ViewTarget, ViewTargetError},
+    WGPU,
+};
+
+/// A color texture, and optionally a depth texture, to render into
+/// and then draw from (see the [module documentation](self)).
+pub struct RenderTarget {
+    color: wgpu::Texture,
+    // Layer 0 of the color texture
+    color_view: wgpu::TextureView,
+    depth: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl RenderTarget {
+    /// Creates a `width` by `height` target with a color texture of
+    /// `color_format` and, unless `depth_format` is `None`, a depth
+    /// texture of `depth_format`.  Returns an error if either format
+    /// can't be rendered to.
+    pub fn new(
+        gpu: &WGPU,
+        (width, height): (u32, u32),
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Result<Self, ViewTargetError> {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let color = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("target:color"),
+            size: wgpu::Extent3d {
+                // GL treats one-layer array textures as plain 2D
+                // textures (see crate::Renderer::create_array_texture)
+                depth_or_array_layers: if gpu.is_gl() { 2 } else { 1 },
+                ..size
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: color_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let color_view = color.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("target:color_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        let depth = depth_format.map(|format| {
+            let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+                label: Some("target:depth"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        });
+        let target = Self {
+            color,
+            color_view,
+            depth,
+        };
+        target.view_target().validate(gpu)?;
+        Ok(target)
+    }
+    /// Returns the color texture, to use as a spritesheet or mesh
+    /// texture; the rendered image is in layer 0.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.color
+    }
+    /// Returns a view of the rendered image.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+    /// Returns the depth texture, if there is one.
+    pub fn depth_texture(&self) -> Option<&wgpu::Texture> {
+        self.depth.as_ref().map(|(texture, _)| texture)
+    }
+    /// Returns the width and height of the target in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.color.width(), self.color.height())
+    }
+    /// Returns the target as a [`ViewTarget`], e.g. for
+    /// [`crate::Renderer::render_to_view`].
+    pub fn view_target(&self) -> ViewTarget<'_> {
+        ViewTarget {
+            color: &self.color_view,
+            color_format: self.color.format(),
+            depth: self
+                .depth
+                .as_ref()
+                .map(|(texture, view)| (view, texture.format())),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Text drawn from TrueType and OpenType fonts (requires the `text`
+//! feature).  A [`TextRenderer`] owns a sprite group whose texture is
+//! a glyph atlas: glyphs are rasterized into the atlas the first time
+//! they're drawn at a given size, and each frame's strings become
+//! sprites in the group.  This saves baking a bitmap font ahead of
+//! time (compare [`crate::bitfont::BitFont`]) and keeps text crisp at
+//! any size.
+//!
+//! Like other sprites, glyphs are alpha-tested rather than blended,
+//! so their edges are hard; draw text at the sizes you'll show it
+//! rather than scaling the group's camera.
+//!
+//! ```ignore
+//! let mut text = TextRenderer::new(&mut renderer, font_bytes, 1024, camera)?;
+//! // every frame:
+//! text.draw_text(&format!("Score: {score}"), [8.0, 592.0], 24.0, [255, 255, 255, 255], 0);
+//! text.flush(&mut renderer);
+//! ```
+
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont};
+
+use crate::sprites::{Camera2D, SheetRegion, Transform};
+use crate::Renderer;
+
+/// Space left between glyphs in the atlas so sampling doesn't bleed
+const PADDING: u32 = 1;
+
+/// Why a [`TextRenderer`] couldn't be created.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextError {
+    /// The font data couldn't be parsed
+    InvalidFont,
+    /// The atlas size was zero or too large for a sheet region
+    AtlasSize(u32),
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::InvalidFont => {
+                write!(f, "Font data is not a valid TrueType or OpenType font")
+            }
+            TextError::AtlasSize(size) => write!(f, "Can't make a {size}x{size} glyph atlas"),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+// A glyph at a whole-pixel size
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct GlyphKey {
+    id: GlyphId,
+    size: u16,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GlyphEntry {
+    // The offset of the bitmap's top-left corner from the pen
+    // position on the baseline, y down as in the font
+    offset: [f32; 2],
+    size: [u32; 2],
+    // Where the bitmap is in the atlas, if it's been packed since the
+    // atlas was last reset
+    atlas_pos: Option<(u16, u16)>,
+}
+
+// A glyph queued for drawing this frame
+#[derive(Clone, Copy, Debug)]
+struct Quad {
+    key: GlyphKey,
+    trf: Transform,
+    colormod: [u8; 4],
+    depth: u16,
+}
+
+/// Rasterizes a font into a glyph atlas and draws strings with it as
+/// a sprite group.  Queue up strings with [`TextRenderer::draw_text`]
+/// and call [`TextRenderer::flush`] once per frame before rendering.
+pub struct TextRenderer {
+    font: FontVec,
+    group: usize,
+    texture: wgpu::Texture,
+    atlas: Vec<u8>,
+    atlas_size: u32,
+    glyphs: HashMap<GlyphKey, GlyphEntry>,
+    // Shelf packing state: the next free spot and the current shelf's height
+    cursor: (u32, u32),
+    shelf_height: u32,
+    // The region of the atlas changed since the last upload, as (min, max) corners
+    dirty: Option<((u32, u32), (u32, u32))>,
+    quads: Vec<Quad>,
+}
+
+impl TextRenderer {
+    /// Parses `font_data` (the contents of a TTF or OTF file) and
+    /// creates a sprite group with the given camera to draw it,
+    /// backed by an `atlas_size` by `atlas_size` glyph atlas.  If a
+    /// frame needs more glyphs than fit in the atlas, it is cleared
+    /// and refilled with just that frame's glyphs.
+    pub fn new(
+        renderer: &mut Renderer,
+        font_data: Vec<u8>,
+        atlas_size: u32,
+        camera: Camera2D,
+    ) -> Result<Self, TextError> {
+        let font = FontVec::try_from_vec(font_data).map_err(|_| TextError::InvalidFont)?;
+        if atlas_size == 0 || atlas_size > u16::MAX as u32 {
+            return Err(TextError::AtlasSize(atlas_size));
+        }
+        let atlas = vec![0; (atlas_size * atlas_size * 4) as usize];
+        let texture = renderer.create_array_texture(
+            &[&atlas],
+            wgpu::TextureFormat::Rgba8Unorm,
+            (atlas_size, atlas_size),
+            Some("text:atlas"),
+        );
+        let group = renderer.sprite_group_add(
+            &texture,
+            vec![Transform::ZERO],
+            vec![SheetRegion::ZERO],
+            camera,
+        );
+        Ok(Self {
+            font,
+            group,
+            texture,
+            atlas,
+            atlas_size,
+            glyphs: HashMap::new(),
+            cursor: (PADDING, PADDING),
+            shelf_height: 0,
+            dirty: None,
+            quads: vec![],
+        })
+    }
+    /// Returns the index of the sprite group text is drawn with, e.g.
+    /// to change its camera.
+    pub fn sprite_group(&self) -> usize {
+        self.group
+    }
+    /// Returns the glyph atlas texture.
+    pub fn atlas_texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Returns the distance between the tops of consecutive lines of
+    /// text drawn at `size` pixels.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let scaled = self.font.as_scaled(Self::scale(size));
+        scaled.height() + scaled.line_gap()
+    }
+    /// Returns the width and height of the box `text` would cover if
+    /// drawn at `size` pixels.
+    pub fn measure(&self, text: &str, size: f32) -> [f32; 2] {
+        let scaled = self.font.as_scaled(Self::scale(size));
+        let line_height = scaled.height() + scaled.line_gap();
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for line in text.split('\n') {
+            let mut x = 0.0;
+            let mut prev = None;
+            for ch in line.chars() {
+                let id = scaled.glyph_id(ch);
+                if let Some(prev) = prev {
+                    x += scaled.kern(prev, id);
+                }
+                x += scaled.h_advance(id);
+                prev = Some(id);
+            }
+            width = width.max(x);
+            lines += 1;
+        }
+        [width, line_height * (lines - 1) as f32 + scaled.height()]
+    }
+    /// Queues `text` to be drawn at `size` pixels (the height from
+    /// the font's descender to its ascender, rounded to a whole
+    /// pixel) with its top-left corner at `screen_pos`.  Glyphs are
+    /// white, so `colormod` (see [`SheetRegion::colormod`]) with full
+    /// alpha gives them a solid color.  Newlines start new lines
+    /// below the first.  Returns the bottom-right corner of the text.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        screen_pos: [f32; 2],
+        size: f32,
+        colormod: [u8; 4],
+        depth: u16,
+    ) -> [f32; 2] {
+        let scale = Self::scale(size);
+        let key_size = scale.y as u16;
+        let (ascent, height, line_height) = {
+            let scaled = self.font.as_scaled(scale);
+            (
+                scaled.ascent(),
+                scaled.height(),
+                scaled.height() + scaled.line_gap(),
+            )
+        };
+        let mut right = screen_pos[0];
+        let mut top = screen_pos[1];
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                top -= line_height;
+            }
+            // Like other 2D coordinates in frenderer, y increases upwards
+            let baseline = top - ascent;
+            let mut x = screen_pos[0];
+            let mut prev = None;
+            for ch in line.chars() {
+                let id = self.font.glyph_id(ch);
+                let scaled = self.font.as_scaled(scale);
+                if let Some(prev) = prev {
+                    x += scaled.kern(prev, id);
+                }
+                let advance = scaled.h_advance(id);
+                prev = Some(id);
+                let key = GlyphKey { id, size: key_size };
+                let entry = self.entry(key);
+                if entry.size[0] > 0 && entry.size[1] > 0 {
+                    let (w, h) = (entry.size[0] as f32, entry.size[1] as f32);
+                    self.quads.push(Quad {
+                        key,
+                        trf: Transform {
+                            w: entry.size[0] as u16,
+                            h: entry.size[1] as u16,
+                            x: x + entry.offset[0] + w / 2.0,
+                            y: baseline - entry.offset[1] - h / 2.0,
+                            rot: 0.0,
+                        },
+                        colormod,
+                        depth,
+                    });
+                }
+                x += advance;
+            }
+            right = right.max(x);
+        }
+        [right, top - height]
+    }
+    /// Drops all queued text without drawing it.
+    pub fn clear(&mut self) {
+        self.quads.clear();
+    }
+    /// Rasterizes any new glyphs into the atlas, uploads the changed
+    /// part of it, and writes the queued text into the sprite group,
+    /// resizing it to fit.  The queue is then emptied, so text is
+    /// drawn again each frame it's queued.
+    pub fn flush(&mut self, renderer: &mut Renderer) {
+        if !self.pack_quads() {
+            // This frame's glyphs alone might fit in a fresh atlas
+            self.reset_atlas();
+            if !self.pack_quads() {
+                log::warn!(
+                    "Glyph atlas is too small for this frame's text; some glyphs won't be drawn"
+                );
+            }
+        }
+        if let Some(((x0, y0), (x1, y1))) = self.dirty.take() {
+            let stride = (self.atlas_size * 4) as usize;
+            let mut data = Vec::with_capacity(((x1 - x0) * (y1 - y0) * 4) as usize);
+            for y in y0..y1 {
+                let row = y as usize * stride;
+                data.extend_from_slice(&self.atlas[row + x0 as usize * 4..row + x1 as usize * 4]);
+            }
+            renderer.update_texture_region(&self.texture, 0, (x0, y0), (x1 - x0, y1 - y0), &data);
+        }
+        // Keep one (empty) sprite around so the group is never empty
+        let len = self.quads.len().max(1);
+        renderer.sprite_group_resize(self.group, len);
+        let (trfs, uvs) = renderer.sprites_mut(self.group, ..);
+        trfs.fill(Transform::ZERO);
+        uvs.fill(SheetRegion::ZERO);
+        for (quad, (trf, uv)) in self.quads.iter().zip(trfs.iter_mut().zip(uvs.iter_mut())) {
+            let entry = &self.glyphs[&quad.key];
+            let Some((x, y)) = entry.atlas_pos else {
+                continue;
+            };
+            *trf = quad.trf;
+            *uv = SheetRegion::new(
+                0,
+                x,
+                y,
+                quad.depth,
+                entry.size[0] as i16,
+                entry.size[1] as i16,
+            )
+            .with_colormod(quad.colormod);
+        }
+        self.quads.clear();
+    }
+    fn scale(size: f32) -> PxScale {
+        PxScale::from(size.round().clamp(1.0, u16::MAX as f32))
+    }
+    // Measures a glyph, without packing it
+    fn entry(&mut self, key: GlyphKey) -> GlyphEntry {
+        let font = &self.font;
+        *self.glyphs.entry(key).or_insert_with(|| {
+            let glyph = key.id.with_scale(PxScale::from(key.size as f32));
+            match font.outline_glyph(glyph) {
+                Some(outlined) => {
+                    let bounds = outlined.px_bounds();
+                    GlyphEntry {
+                        offset: [bounds.min.x, bounds.min.y],
+                        size: [bounds.width() as u32, bounds.height() as u32],
+                        atlas_pos: None,
+                    }
+                }
+                // Spaces and the like have nothing to draw
+                None => GlyphEntry {
+                    offset: [0.0, 0.0],
+                    size: [0, 0],
+                    atlas_pos: None,
+                },
+            }
+        })
+    }
+    // Makes sure every queued glyph is in the atlas; returns false if
+    // one didn't fit.
+    fn pack_quads(&mut self) -> bool {
+        let mut fit = true;
+        for i in 0..self.quads.len() {
+            let key = self.quads[i].key;
+            if self.glyphs[&key].atlas_pos.is_none() {
+                fit &= self.pack(key);
+            }
+        }
+        fit
+    }
+    fn pack(&mut self, key: GlyphKey) -> bool {
+        let [w, h] = self.glyphs[&key].size;
+        if self.cursor.0 + w + PADDING > self.atlas_size {
+            // Start a new shelf
+            self.cursor = (PADDING, self.cursor.1 + self.shelf_height + PADDING);
+            self.shelf_height = 0;
+        }
+        if self.cursor.0 + w + PADDING > self.atlas_size
+            || self.cursor.1 + h + PADDING > self.atlas_size
+        {
+            return false;
+        }
+        let (x0, y0) = self.cursor;
+        let glyph = key.id.with_scale(PxScale::from(key.size as f32));
+        if let Some(outlined) = self.font.outline_glyph(glyph) {
+            let stride = self.atlas_size as usize * 4;
+            let atlas = &mut self.atlas;
+            outlined.draw(|x, y, coverage| {
+                if x < w && y < h {
+                    let idx = (y0 + y) as usize * stride + (x0 + x) as usize * 4;
+                    atlas[idx..idx + 4].copy_from_slice(&[
+                        255,
+                        255,
+                        255,
+                        (coverage.clamp(0.0, 1.0) * 255.0) as u8,
+                    ]);
+                }
+            });
+        }
+        self.glyphs.get_mut(&key).unwrap().atlas_pos = Some((x0 as u16, y0 as u16));
+        self.cursor.0 += w + PADDING;
+        self.shelf_height = self.shelf_height.max(h);
+        let (min, max) = self.dirty.unwrap_or(((x0, y0), (x0 + w, y0 + h)));
+        self.dirty = Some((
+            (min.0.min(x0), min.1.min(y0)),
+            (max.0.max(x0 + w), max.1.max(y0 + h)),
+        ));
+        true
+    }
+    // Forgets where every glyph is, so the atlas can be refilled from scratch
+    fn reset_atlas(&mut self) {
+        for entry in self.glyphs.values_mut() {
+            entry.atlas_pos = None;
+        }
+        self.atlas.fill(0);
+        self.cursor = (PADDING, PADDING);
+        self.shelf_height = 0;
+        self.dirty = Some(((0, 0), (self.atlas_size, self.atlas_size)));
+    }
+}
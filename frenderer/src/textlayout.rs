@@ -0,0 +1,327 @@
+//! Multi-line text layout on top of [`crate::bitfont::BitFont`]:
+//! word wrapping, alignment, line spacing, and runs of differently
+//! colored or sized text.  A [`TextLayout`] is computed once from
+//! some [`TextRun`]s and [`LayoutOptions`], can be measured (e.g. to
+//! size a dialog box around it), and is then drawn into sprites with
+//! [`TextLayout::draw`].
+
+use std::ops::RangeBounds;
+
+use crate::bitfont::BitFont;
+use crate::sprites::{SheetRegion, Transform};
+
+/// How lines are aligned within the layout's width.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// The appearance of a run of text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Color modulation applied to the run's glyphs, as in [`SheetRegion::colormod`]
+    pub colormod: [u8; 4],
+    /// A multiplier on [`LayoutOptions::char_height`]
+    pub scale: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            colormod: [0; 4],
+            scale: 1.0,
+        }
+    }
+}
+
+/// A piece of text drawn with a single [`TextStyle`].
+#[derive(Clone, Copy, Debug)]
+pub struct TextRun<'a> {
+    pub text: &'a str,
+    pub style: TextStyle,
+}
+
+impl<'a> TextRun<'a> {
+    /// Creates a run of text in the default style.
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            style: TextStyle::default(),
+        }
+    }
+    /// Produce a new [`TextRun`] with different color modulation.
+    pub fn with_colormod(self, colormod: [u8; 4]) -> Self {
+        Self {
+            style: TextStyle {
+                colormod,
+                ..self.style
+            },
+            ..self
+        }
+    }
+    /// Produce a new [`TextRun`] with a different size multiplier.
+    pub fn with_scale(self, scale: f32) -> Self {
+        Self {
+            style: TextStyle {
+                scale,
+                ..self.style
+            },
+            ..self
+        }
+    }
+}
+
+/// Parameters for laying out text.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutOptions {
+    /// The height of a character in a run with scale 1.0
+    pub char_height: f32,
+    /// Lines are wrapped between words to fit this width; words
+    /// wider than it are broken between characters.  `None` means
+    /// lines only end at newlines.
+    pub max_width: Option<f32>,
+    /// How lines are aligned within `max_width` (or within the widest line)
+    pub align: Align,
+    /// The distance between the tops of consecutive lines, as a multiple of each line's height
+    pub line_spacing: f32,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            char_height: 16.0,
+            max_width: None,
+            align: Align::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// One positioned character of a [`TextLayout`].
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    pub ch: char,
+    /// The top-left corner of the glyph relative to the layout's
+    /// top-left corner; like other 2D coordinates in frenderer, y
+    /// increases upwards, so lines below the first have negative y.
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+    pub colormod: [u8; 4],
+}
+
+/// The measurements of one line of a [`TextLayout`].
+#[derive(Clone, Debug)]
+pub struct Line {
+    /// The indices of this line's glyphs in [`TextLayout::glyphs`]
+    pub glyphs: std::ops::Range<usize>,
+    /// The left edge of the line after alignment
+    pub x: f32,
+    /// The top of the line (zero or negative)
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Text which has been broken into lines and positioned.
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    glyphs: Vec<Glyph>,
+    lines: Vec<Line>,
+    size: [f32; 2],
+}
+
+// A character waiting to be placed on a line
+#[derive(Clone, Copy)]
+struct Pending {
+    ch: char,
+    width: f32,
+    height: f32,
+    colormod: [u8; 4],
+}
+
+impl TextLayout {
+    /// Lays out the given runs of text with a font's character
+    /// proportions.  Whitespace separates words and takes up space
+    /// but isn't drawn; spaces where a line wraps are dropped.
+    /// Characters are checked against the font when the layout is
+    /// drawn, not here.
+    pub fn new<B: RangeBounds<char>>(
+        font: &BitFont<B>,
+        runs: &[TextRun],
+        options: &LayoutOptions,
+    ) -> Self {
+        let aspect = font.char_aspect();
+        let max_width = options.max_width.unwrap_or(f32::INFINITY);
+        let mut layout = Self::default();
+        let mut line: Vec<Pending> = vec![];
+        let mut line_width = 0.0;
+        let mut word: Vec<Pending> = vec![];
+        let mut word_width = 0.0;
+        let mut spaces: Vec<Pending> = vec![];
+        let mut spaces_width = 0.0;
+        // The height of a line with nothing on it
+        let mut empty_height = options.char_height;
+        let chars = runs.iter().flat_map(|run| {
+            run.text.chars().map(|ch| {
+                let height = options.char_height * run.style.scale;
+                Pending {
+                    ch,
+                    width: height * aspect,
+                    height,
+                    colormod: run.style.colormod,
+                }
+            })
+        });
+        // A final newline flushes the last word and line
+        for c in chars.chain(std::iter::once(Pending {
+            ch: '\n',
+            width: 0.0,
+            height: 0.0,
+            colormod: [0; 4],
+        })) {
+            if !c.ch.is_whitespace() {
+                word_width += c.width;
+                word.push(c);
+                continue;
+            }
+            // The word is over, so put it on this line or the next
+            if !word.is_empty() {
+                if !line.is_empty() && line_width + spaces_width + word_width > max_width {
+                    layout.finish_line(&mut line, &mut line_width, empty_height, options);
+                    spaces.clear();
+                    spaces_width = 0.0;
+                }
+                line.append(&mut spaces);
+                line_width += spaces_width;
+                spaces_width = 0.0;
+                for w in word.drain(..) {
+                    // Break up words that don't fit on a line by themselves
+                    if !line.is_empty() && line_width + w.width > max_width {
+                        layout.finish_line(&mut line, &mut line_width, empty_height, options);
+                    }
+                    line_width += w.width;
+                    line.push(w);
+                }
+                word_width = 0.0;
+            }
+            if c.ch == '\n' {
+                spaces.clear();
+                spaces_width = 0.0;
+                layout.finish_line(&mut line, &mut line_width, empty_height, options);
+                empty_height = c.height;
+            } else {
+                spaces_width += c.width;
+                spaces.push(c);
+            }
+        }
+        let width = options
+            .max_width
+            .unwrap_or_else(|| layout.lines.iter().map(|l| l.width).fold(0.0, f32::max));
+        for line in layout.lines.iter_mut() {
+            let offset = match options.align {
+                Align::Left => 0.0,
+                Align::Center => (width - line.width) / 2.0,
+                Align::Right => width - line.width,
+            };
+            line.x += offset;
+            for g in layout.glyphs[line.glyphs.clone()].iter_mut() {
+                g.pos[0] += offset;
+            }
+        }
+        let left = layout.lines.iter().map(|l| l.x).fold(0.0, f32::min);
+        let right = layout
+            .lines
+            .iter()
+            .map(|l| l.x + l.width)
+            .fold(0.0, f32::max);
+        let bottom = layout.lines.last().map(|l| l.height - l.y).unwrap_or(0.0);
+        layout.size = [right - left, bottom];
+        layout
+    }
+    fn finish_line(
+        &mut self,
+        line: &mut Vec<Pending>,
+        line_width: &mut f32,
+        empty_height: f32,
+        options: &LayoutOptions,
+    ) {
+        let height = line.iter().map(|c| c.height).fold(0.0, f32::max);
+        let height = if line.is_empty() {
+            empty_height
+        } else {
+            height
+        };
+        let y = self
+            .lines
+            .last()
+            .map(|l| l.y - l.height * options.line_spacing)
+            .unwrap_or(0.0);
+        let start = self.glyphs.len();
+        let mut x = 0.0;
+        for c in line.drain(..) {
+            if !c.ch.is_whitespace() {
+                // Glyphs of different sizes share a bottom edge
+                self.glyphs.push(Glyph {
+                    ch: c.ch,
+                    pos: [x, y - (height - c.height)],
+                    size: [c.width, c.height],
+                    colormod: c.colormod,
+                });
+            }
+            x += c.width;
+        }
+        self.lines.push(Line {
+            glyphs: start..self.glyphs.len(),
+            x: 0.0,
+            y,
+            width: *line_width,
+            height,
+        });
+        *line_width = 0.0;
+    }
+    /// Returns the positioned characters of the layout (not including whitespace).
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+    /// Returns the lines of the layout.
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+    /// Returns the width and height of the text's bounding box.
+    pub fn size(&self) -> [f32; 2] {
+        self.size
+    }
+    /// Returns how many sprites [`TextLayout::draw`] needs.
+    pub fn sprite_count(&self) -> usize {
+        self.glyphs.len()
+    }
+    /// Draws the layout with its top-left corner at `screen_pos`,
+    /// filling the first [`TextLayout::sprite_count`] elements of
+    /// `trfs` and `uvs`.  Returns how many sprites were used.
+    /// Panics if any character is not within the font's character
+    /// range or if the slices are too short.
+    pub fn draw<B: RangeBounds<char>>(
+        &self,
+        font: &BitFont<B>,
+        trfs: &mut [Transform],
+        uvs: &mut [SheetRegion],
+        screen_pos: [f32; 2],
+        depth: u16,
+    ) -> usize {
+        assert!(trfs.len() >= self.glyphs.len() && uvs.len() >= self.glyphs.len());
+        for (g, (trf, uv)) in self.glyphs.iter().zip(trfs.iter_mut().zip(uvs.iter_mut())) {
+            *trf = Transform {
+                w: g.size[0] as u16,
+                h: g.size[1] as u16,
+                x: screen_pos[0] + g.pos[0] + g.size[0] / 2.0,
+                y: screen_pos[1] + g.pos[1] - g.size[1] / 2.0,
+                rot: 0.0,
+            };
+            *uv = font.char_region(g.ch, depth).with_colormod(g.colormod);
+        }
+        self.glyphs.len()
+    }
+}
@@ -0,0 +1,560 @@
+//! Tile maps: large grids of same-sized tiles drawn from an array
+//! texture, such as the terrain of a top-down or side-scrolling game.
+//!
+//! Drawing a map as a sprite group means keeping a sprite (and its
+//! upload) for every tile.  A [`TileMapRenderer`] instead keeps the
+//! map's tile indices in a texture and splits the map into square
+//! chunks of [`CHUNK_SIZE`] tiles.  Each visible chunk is drawn as a
+//! single quad whose fragment shader looks up the tile under each
+//! pixel, so scrolling costs nothing and changing a tile only uploads
+//! two bytes.
+//!
+//! Like sprites, tile maps use a [`Camera2D`] and y increases
+//! upwards, but rows of tiles are numbered from the top: tile `(0,
+//! 0)` is the top-left tile of the map, whose top-left corner is at
+//! the map's origin.  Each layer of the tileset texture is one tile,
+//! so tile index `i` draws layer `i`; [`EMPTY`] tiles aren't drawn.
+
+use std::borrow::Cow;
+
+use crate::{capture, sprites::Camera2D, LimitError, WGPU};
+
+/// How many tiles wide and tall each chunk of a map is.
+pub const CHUNK_SIZE: u32 = 32;
+
+/// The tile index of tiles which aren't drawn.
+pub const EMPTY: u16 = u16::MAX;
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy)]
+struct TileMapUniforms {
+    camera: Camera2D,
+    // xy = the map's origin, zw = the size of a tile in world units
+    origin_tile_size: [f32; 4],
+    // x = depth
+    params: [f32; 4],
+}
+
+/// Draws a grid of tiles from a tileset array texture, one chunk at a time.
+pub struct TileMapRenderer {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_target: wgpu::ColorTargetState,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // Each chunk's first tile and size, at `chunk_stride` byte offsets
+    chunk_bind_group: wgpu::BindGroup,
+    chunk_stride: u32,
+    tile_texture: wgpu::Texture,
+    tiles: Vec<u16>,
+    size: (u32, u32),
+    camera: Camera2D,
+    origin: [f32; 2],
+    tile_size: [f32; 2],
+    depth: u16,
+}
+
+impl TileMapRenderer {
+    /// Creates a `width` by `height` tile map, initially all
+    /// [`EMPTY`], drawing tiles of `tile_size` world units from the
+    /// given tileset (an array texture with one tile per layer) into
+    /// the given color target state with the given depth texture
+    /// format (or without depth testing if `depth_format` is `None`).
+    /// Panics if the map is too large for the device.
+    pub fn new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+        tileset: &wgpu::Texture,
+        tile_size: [f32; 2],
+        (width, height): (u32, u32),
+        camera: Camera2D,
+    ) -> Self {
+        match Self::try_new(
+            gpu,
+            color_target,
+            depth_format,
+            tileset,
+            tile_size,
+            (width, height),
+            camera,
+        ) {
+            Ok(map) => map,
+            Err(err) => panic!("{err}"),
+        }
+    }
+    /// Like [`TileMapRenderer::new`], but returns an error naming the
+    /// exceeded device limit instead of panicking if the map wouldn't
+    /// fit on this GPU.
+    pub fn try_new(
+        gpu: &WGPU,
+        color_target: wgpu::ColorTargetState,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+        tileset: &wgpu::Texture,
+        tile_size: [f32; 2],
+        (width, height): (u32, u32),
+        camera: Camera2D,
+    ) -> Result<Self, LimitError> {
+        let limits = gpu.device().limits();
+        let max = limits.max_texture_dimension_2d;
+        if width.max(height) > max {
+            return Err(LimitError::TextureSize {
+                size: width.max(height),
+                max,
+            });
+        }
+        let (width, height) = (width.max(1), height.max(1));
+        let chunk_stride = limits.min_uniform_buffer_offset_alignment;
+        let chunk_count = width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE);
+        let chunk_bytes = chunk_count as u64 * chunk_stride as u64;
+        if chunk_bytes > limits.max_buffer_size {
+            return Err(LimitError::BufferSize {
+                buffer: "tiles:chunks",
+                size: chunk_bytes,
+                max: limits.max_buffer_size,
+            });
+        }
+        let shader = gpu
+            .device()
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("tiles:shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("tiles.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("tiles:bgl"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                    TileMapUniforms,
+                                >(
+                                )
+                                    as u64),
+                            },
+                            count: None,
+                        },
+                        // The map's tile indices
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Uint,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        // The tileset
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let chunk_bind_group_layout =
+            gpu.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("tiles:chunk_bgl"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<[u32; 4]>() as u64,
+                            ),
+                        },
+                        count: None,
+                    }],
+                });
+        let pipeline_layout =
+            gpu.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("tiles:pipeline_layout"),
+                    bind_group_layouts: &[&bind_group_layout, &chunk_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let depth_format = depth_format.into();
+        let pipeline = Self::create_pipeline(
+            gpu,
+            &shader,
+            &pipeline_layout,
+            color_target.clone(),
+            (depth_format, 1),
+        );
+        let uniform_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tiles:uniforms"),
+            size: std::mem::size_of::<TileMapUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let tile_texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("tiles:indices"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R16Uint,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let tiles = vec![EMPTY; (width * height) as usize];
+        gpu.queue().write_texture(
+            tile_texture.as_image_copy(),
+            bytemuck::cast_slice(&tiles),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 2),
+                rows_per_image: Some(height),
+            },
+            tile_texture.size(),
+        );
+        let sampler = gpu.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("tiles:sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tiles:bg"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &tile_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&tileset.create_view(
+                        &wgpu::TextureViewDescriptor {
+                            dimension: Some(wgpu::TextureViewDimension::D2Array),
+                            ..Default::default()
+                        },
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        // Chunks never change, so their entries are written once
+        let mut chunks = vec![0_u8; chunk_bytes as usize];
+        let chunks_wide = width.div_ceil(CHUNK_SIZE);
+        for (i, entry) in chunks.chunks_mut(chunk_stride as usize).enumerate() {
+            let x = (i as u32 % chunks_wide) * CHUNK_SIZE;
+            let y = (i as u32 / chunks_wide) * CHUNK_SIZE;
+            let chunk = [x, y, CHUNK_SIZE.min(width - x), CHUNK_SIZE.min(height - y)];
+            entry[..16].copy_from_slice(bytemuck::cast_slice(&chunk));
+        }
+        let chunk_buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tiles:chunks"),
+            size: chunk_bytes,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue().write_buffer(&chunk_buffer, 0, &chunks);
+        let chunk_bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tiles:chunk_bg"),
+            layout: &chunk_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &chunk_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<[u32; 4]>() as u64),
+                }),
+            }],
+        });
+        let ret = Self {
+            shader,
+            pipeline_layout,
+            color_target,
+            depth_format,
+            sample_count: 1,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            chunk_bind_group,
+            chunk_stride,
+            tile_texture,
+            tiles,
+            size: (width, height),
+            camera,
+            origin: [0.0, 0.0],
+            tile_size,
+            depth: 0,
+        };
+        ret.upload(gpu);
+        Ok(ret)
+    }
+    fn create_pipeline(
+        gpu: &WGPU,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        color_target: wgpu::ColorTargetState,
+        (depth_format, sample_count): (Option<wgpu::TextureFormat>, u32),
+    ) -> wgpu::RenderPipeline {
+        gpu.device()
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("tiles:pipeline"),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Tiles are depth tested and written like sprites
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+    }
+    fn upload(&self, gpu: &WGPU) {
+        let uniforms = TileMapUniforms {
+            camera: self.camera,
+            origin_tile_size: [
+                self.origin[0],
+                self.origin[1],
+                self.tile_size[0],
+                self.tile_size[1],
+            ],
+            params: [self.depth as f32 / u16::MAX as f32, 0.0, 0.0, 0.0],
+        };
+        gpu.queue()
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    /// Returns the map's width and height in tiles.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+    /// Returns the index of the tile at column `x` and row `y`.
+    /// Panics if the tile is outside the map.
+    pub fn tile(&self, x: u32, y: u32) -> u16 {
+        assert!(x < self.size.0 && y < self.size.1, "Tile out of bounds");
+        self.tiles[(y * self.size.0 + x) as usize]
+    }
+    /// Returns every tile index, row by row from the top.
+    pub fn tiles(&self) -> &[u16] {
+        &self.tiles
+    }
+    /// Changes the tile at column `x` and row `y`.  Uploads to the
+    /// GPU.  Panics if the tile is outside the map.
+    pub fn set_tile(&mut self, gpu: &WGPU, x: u32, y: u32, tile: u16) {
+        self.set_tiles(gpu, (x, y), 1, &[tile]);
+    }
+    /// Overwrites a rectangle of tiles `width` tiles wide whose
+    /// top-left tile is at column `x` and row `y` with `tiles`, which
+    /// holds the rectangle's rows from the top.  Uploads to the GPU.
+    /// Panics if the rectangle doesn't fit in the map or `tiles`
+    /// doesn't hold a whole number of rows.
+    pub fn set_tiles(&mut self, gpu: &WGPU, (x, y): (u32, u32), width: u32, tiles: &[u16]) {
+        if tiles.is_empty() {
+            return;
+        }
+        assert!(
+            width > 0 && tiles.len() % width as usize == 0,
+            "Tiles must be whole rows of the given width"
+        );
+        let height = (tiles.len() / width as usize) as u32;
+        assert!(
+            x + width <= self.size.0 && y + height <= self.size.1,
+            "Tiles out of bounds"
+        );
+        for (row, src) in tiles.chunks(width as usize).enumerate() {
+            let start = ((y + row as u32) * self.size.0 + x) as usize;
+            self.tiles[start..start + width as usize].copy_from_slice(src);
+        }
+        gpu.queue().write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.tile_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(tiles),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 2),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    /// Returns the camera the map is drawn with.
+    pub fn camera(&self) -> Camera2D {
+        self.camera
+    }
+    /// Sets the camera the map is drawn with.  Uploads to the GPU.
+    pub fn set_camera(&mut self, gpu: &WGPU, camera: Camera2D) {
+        self.camera = camera;
+        self.upload(gpu);
+    }
+    /// Returns the world position of the map's top-left corner.
+    pub fn origin(&self) -> [f32; 2] {
+        self.origin
+    }
+    /// Moves the map's top-left corner to `origin`.  Uploads to the GPU.
+    pub fn set_origin(&mut self, gpu: &WGPU, origin: [f32; 2]) {
+        self.origin = origin;
+        self.upload(gpu);
+    }
+    /// Returns the size of a tile in world units.
+    pub fn tile_size(&self) -> [f32; 2] {
+        self.tile_size
+    }
+    /// Changes the size of a tile in world units.  Uploads to the GPU.
+    pub fn set_tile_size(&mut self, gpu: &WGPU, tile_size: [f32; 2]) {
+        self.tile_size = tile_size;
+        self.upload(gpu);
+    }
+    /// Returns the depth the map is drawn at (see [`crate::sprites::SheetRegion::depth`]).
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+    /// Changes the depth the map is drawn at, e.g. to put it behind
+    /// or in front of sprites.  Uploads to the GPU.
+    pub fn set_depth(&mut self, gpu: &WGPU, depth: u16) {
+        self.depth = depth;
+        self.upload(gpu);
+    }
+    /// Returns the map coordinates of the tile containing the world
+    /// position `pos`, if it's on the map.
+    pub fn tile_at(&self, pos: [f32; 2]) -> Option<(u32, u32)> {
+        let x = ((pos[0] - self.origin[0]) / self.tile_size[0]).floor();
+        let y = ((self.origin[1] - pos[1]) / self.tile_size[1]).floor();
+        (x >= 0.0 && y >= 0.0 && x < self.size.0 as f32 && y < self.size.1 as f32)
+            .then_some((x as u32, y as u32))
+    }
+    /// Changes the depth format this renderer draws with, re-creating the pipeline.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        self.depth_format = depth_format;
+        self.pipeline = Self::create_pipeline(
+            gpu,
+            &self.shader,
+            &self.pipeline_layout,
+            self.color_target.clone(),
+            (depth_format, self.sample_count),
+        );
+    }
+    /// Changes the multisample count of the render pass the map is
+    /// drawn into, re-creating the pipeline.  The default is 1 (no
+    /// multisampling).
+    pub fn set_sample_count(&mut self, gpu: &WGPU, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Changes the color target the map is drawn into (e.g. for a
+    /// new color format), re-creating the pipelines.
+    pub fn set_color_target(&mut self, gpu: &WGPU, color_target: wgpu::ColorTargetState) {
+        self.color_target = color_target;
+        self.set_depth_format(gpu, self.depth_format);
+    }
+    /// Returns the indices of the chunks the camera can see, in the
+    /// order they're drawn.
+    pub fn visible_chunks(&self) -> Vec<u32> {
+        let [tw, th] = self.tile_size;
+        if tw <= 0.0 || th <= 0.0 {
+            return vec![];
+        }
+        let [sx, sy] = self.camera.screen_pos;
+        let [sw, sh] = self.camera.screen_size;
+        let (left, right) = (sx.min(sx + sw), sx.max(sx + sw));
+        let (bottom, top) = (sy.min(sy + sh), sy.max(sy + sh));
+        let chunk_w = tw * CHUNK_SIZE as f32;
+        let chunk_h = th * CHUNK_SIZE as f32;
+        let chunks_wide = self.size.0.div_ceil(CHUNK_SIZE);
+        let chunks_tall = self.size.1.div_ceil(CHUNK_SIZE);
+        let range = |lo: f32, hi: f32, count: u32| {
+            let lo = lo.floor().max(0.0);
+            let hi = hi.ceil().min(count as f32);
+            lo as u32..(hi.max(lo)) as u32
+        };
+        // Rows count down from the origin
+        let cols = range(
+            (left - self.origin[0]) / chunk_w,
+            (right - self.origin[0]) / chunk_w,
+            chunks_wide,
+        );
+        let rows = range(
+            (self.origin[1] - top) / chunk_h,
+            (self.origin[1] - bottom) / chunk_h,
+            chunks_tall,
+        );
+        rows.flat_map(|row| cols.clone().map(move |col| row * chunks_wide + col))
+            .collect()
+    }
+    /// Draws the visible chunks of the map into the given renderpass,
+    /// which should have been begun with the same color and depth
+    /// formats this renderer was created with.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        capture::set_pipeline(rpass, &self.pipeline, "tiles:pipeline");
+        capture::set_bind_group(rpass, 0, &self.bind_group, &[], "tiles:bind_group");
+        for chunk in self.visible_chunks() {
+            capture::set_bind_group(
+                rpass,
+                1,
+                &self.chunk_bind_group,
+                &[chunk * self.chunk_stride],
+                "tiles:chunk_bind_group",
+            );
+            capture::draw(rpass, 0..6, 0..1);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Draws one chunk of a tile map as a quad, looking up the tile under
+// each pixel in the map's index texture.
+
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    // Corners of the chunk in tiles from its top-left: top left, top
+    // right, bottom left; then bottom left, top right, bottom right.
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(1.0, 1.0)
+);
+
+struct TileMap {
+    screen_pos: vec2<f32>,
+    screen_size: vec2<f32>,
+    origin: vec2<f32>,
+    tile_size: vec2<f32>,
+    // x = depth
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> map: TileMap;
+@group(0) @binding(1)
+var t_tiles: texture_2d<u32>;
+@group(0) @binding(2)
+var t_tileset: texture_2d_array<f32>;
+@group(0) @binding(3)
+var s_tileset: sampler;
+// xy = the chunk's top-left tile, zw = its size in tiles
+@group(1) @binding(0)
+var<uniform> chunk: vec4<u32>;
+
+// EMPTY in tiles.rs
+const EMPTY:u32 = 65535u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // The position in tiles from the map's top-left corner
+    @location(0) tile_pos: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+  let tile_pos = vec2<f32>(chunk.xy) + VERTICES[in_vertex_index] * vec2<f32>(chunk.zw);
+  // Rows go down from the origin
+  let world_pos = map.origin + vec2<f32>(tile_pos.x, -tile_pos.y) * map.tile_size;
+  let box_pos = (world_pos - map.screen_pos) / (map.screen_size * 0.5);
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(box_pos - vec2<f32>(1.0, 1.0), map.params.x, 1.0);
+  out.tile_pos = tile_pos;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let size = vec2<i32>(textureDimensions(t_tiles));
+  let tile = clamp(vec2<i32>(floor(in.tile_pos)), vec2<i32>(0), size - 1);
+  let index = textureLoad(t_tiles, tile, 0).r;
+  if index == EMPTY { discard; }
+  let uv = in.tile_pos - vec2<f32>(tile);
+  let color = textureSampleLevel(t_tileset, s_tileset, uv, index, 0.0);
+  if color.w < 0.05 { discard; }
+  return color;
+}
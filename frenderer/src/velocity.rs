@@ -0,0 +1,152 @@
+//! Per-pixel motion vectors for motion blur and temporal effects.
+//!
+//! The mesh renderers can draw their instances a second time into a
+//! velocity texture (see
+//! [`crate::meshes::MeshRenderer::render_velocity`]), writing how far
+//! each visible point moved on screen since the previous frame, in
+//! texture coordinates.  Motion comes both from the camera and from
+//! instance transforms: each renderer keeps a copy of the previous
+//! frame's instance data on the GPU, which
+//! [`crate::meshes::MeshRenderer::finish_velocity`] refreshes at the
+//! end of each frame.  Like the ID pass in [`crate::pick`], the
+//! velocity pass has its own depth buffer.  Sprites aren't drawn into
+//! it, so they never have any velocity.
+
+use crate::{capture, WGPU};
+
+/// The texture format of velocity textures.
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// The color target state for drawing into a velocity texture.
+pub(crate) fn velocity_color_target() -> wgpu::ColorTargetState {
+    VELOCITY_FORMAT.into()
+}
+
+/// Owns a velocity texture and the depth texture used while drawing into it.
+pub struct VelocityPass {
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    depth_texture: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl VelocityPass {
+    /// Creates a [`VelocityPass`] with `width` by `height` velocity
+    /// and depth textures.  `depth_format` should match the depth
+    /// format of the renderers drawing into it (`None` means no depth
+    /// testing).
+    pub fn new(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        depth_format: impl Into<Option<wgpu::TextureFormat>>,
+    ) -> Self {
+        let (texture, texture_view) = Self::create_texture(
+            gpu,
+            "velocity:texture",
+            width,
+            height,
+            VELOCITY_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let depth_texture = depth_format
+            .into()
+            .map(|format| Self::create_depth_texture(gpu, width, height, format));
+        Self {
+            texture,
+            texture_view,
+            depth_texture,
+        }
+    }
+    fn create_texture(
+        gpu: &WGPU,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+    fn create_depth_texture(
+        gpu: &WGPU,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        Self::create_texture(
+            gpu,
+            "velocity:depth",
+            width,
+            height,
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    }
+    /// Re-creates the velocity and depth textures at a new size.
+    pub fn resize(&mut self, gpu: &WGPU, width: u32, height: u32) {
+        let format = self.depth_format();
+        *self = Self::new(gpu, width, height, format);
+    }
+    /// Changes the depth format used while drawing velocities, re-creating the depth texture.
+    pub fn set_depth_format(&mut self, gpu: &WGPU, depth_format: Option<wgpu::TextureFormat>) {
+        let size = self.texture.size();
+        self.depth_texture = depth_format
+            .map(|format| Self::create_depth_texture(gpu, size.width, size.height, format));
+    }
+    /// Returns the depth format used while drawing velocities, if any.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_texture.as_ref().map(|(t, _)| t.format())
+    }
+    /// Returns the velocity texture, e.g. to bind it in a temporal
+    /// antialiasing pass.  Each texel holds the change in texture
+    /// coordinates of the point drawn there since the previous frame.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    /// Begins a render pass which clears the velocity texture to zero
+    /// and clears the depth texture; draw into it with the mesh
+    /// renderers' `render_velocity` methods.
+    pub fn begin_render_pass<'s>(
+        &'s self,
+        encoder: &'s mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'s> {
+        capture::begin_pass("velocity");
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("velocity"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth_texture.as_ref().map(|(_, view)| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            ..Default::default()
+        })
+    }
+}
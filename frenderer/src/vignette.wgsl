@@ -0,0 +1,15 @@
+// u_params[0]: x = strength, y = inner radius, z = outer radius, w = roundness
+
+@fragment
+fn fs_main(in:PostVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureLoad(t_color, vec2<i32>(in.clip_position.xy), 0);
+  let size = vec2<f32>(textureDimensions(t_color));
+  // Stretch towards a circle as roundness goes to 1
+  let aspect = mix(vec2<f32>(1.0), size / max(size.x, size.y), u_params[0].w);
+  let offset = (in.tex_coords - 0.5) * aspect;
+  let corner = length(0.5 * aspect);
+  let d = length(offset) / corner;
+  let outer = max(u_params[0].z, u_params[0].y + 0.0001);
+  let darken = smoothstep(u_params[0].y, outer, d) * clamp(u_params[0].x, 0.0, 1.0);
+  return vec4<f32>(color.xyz * (1.0 - darken), color.w);
+}
@@ -0,0 +1,264 @@
+//! Rendering from a web worker.  A page can hand its canvas over to a
+//! worker with `transferControlToOffscreen()`; frenderer running in
+//! the worker then draws into the resulting `OffscreenCanvas`, which
+//! keeps the main thread free for DOM work and input handling.
+//!
+//! [`crate::Renderer::with_offscreen_canvas`] creates a renderer for
+//! an `OffscreenCanvas` directly.  [`WebWorkerRuntime`] plays the
+//! role of [`crate::Driver`] inside a worker: it waits for the canvas
+//! to arrive in a message, initializes frenderer, and then runs a
+//! `requestAnimationFrame` loop, passing messages and simulation
+//! steps to a handler.  The main thread's side of this looks
+//! something like:
+//!
+//! ```js
+//! const offscreen = canvas.transferControlToOffscreen();
+//! worker.postMessage({ canvas: offscreen }, [offscreen]);
+//! new ResizeObserver(() => worker.postMessage({
+//!   resize: [canvas.clientWidth, canvas.clientHeight],
+//! })).observe(canvas);
+//! // Any other messages are passed along to the handler, e.g. input:
+//! window.addEventListener("keydown", (e) => worker.postMessage({ key: e.code }));
+//! ```
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent, OffscreenCanvas};
+
+use crate::{clock::Clock, Renderer};
+
+impl Renderer {
+    /// Creates a [Renderer] drawing into an `OffscreenCanvas` (for
+    /// example, one transferred to a web worker), along with its own
+    /// [`wgpu::Instance`] (see [`crate::WGPU::detect_instance`]) and
+    /// [crate::gpu::WGPU].  The rendering resolution is (`width`,
+    /// `height`), and the surface takes the canvas's current size.
+    pub async fn with_offscreen_canvas(
+        width: u32,
+        height: u32,
+        canvas: OffscreenCanvas,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let instance = crate::WGPU::detect_instance().await;
+        let (surf_width, surf_height) = (canvas.width(), canvas.height());
+        let surface = instance.create_surface(wgpu::SurfaceTarget::OffscreenCanvas(canvas))?;
+        Self::with_surface(
+            width,
+            height,
+            surf_width,
+            surf_height,
+            instance,
+            Some(surface),
+        )
+        .await
+    }
+}
+
+/// Errors starting a [`WebWorkerRuntime`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkerError {
+    /// The runtime was started outside of a dedicated web worker
+    NotInWorker,
+    /// A JavaScript exception, as a debug string
+    Js(String),
+}
+impl std::fmt::Display for WorkerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <Self as std::fmt::Debug>::fmt(self, f)
+    }
+}
+impl std::error::Error for WorkerError {}
+
+/// Something the game running in a [`WebWorkerRuntime`] should respond to.
+pub enum WorkerEvent {
+    /// The game should simulate time forward by the given number of steps and then render.
+    Run(usize),
+    /// The main thread sent a `resize: [width, height]` message.  The
+    /// renderer's surface has already been resized.
+    Resize(u32, u32),
+    /// The main thread sent some other message, with this data.
+    Message(JsValue),
+}
+
+/// WebWorkerRuntime takes over a dedicated web worker's message
+/// handler and animation frames, and creates a [`Renderer`] once the
+/// main thread sends it an `OffscreenCanvas` (either by itself or as
+/// the `canvas` field of a message).
+pub struct WebWorkerRuntime {
+    render_size: Option<(u32, u32)>,
+    clock: Clock,
+}
+
+impl WebWorkerRuntime {
+    /// Create a [`WebWorkerRuntime`] with the given render target
+    /// size (if absent, will use the canvas's size instead) and the
+    /// clock used to count simulation steps.
+    pub fn new(render_size: Option<(u32, u32)>, clock: Clock) -> Self {
+        Self { render_size, clock }
+    }
+    /// Sets up logging and starts handling messages and animation
+    /// frames, then returns; the worker's event loop keeps the
+    /// runtime going from there.  Once the renderer is initialized,
+    /// `init_cb` is called to produce an application state object
+    /// which is passed to `handler` along with the renderer for every
+    /// [`WorkerEvent`].  Messages which arrive before then are
+    /// delivered right after `init_cb` is called.
+    pub fn run<U: 'static>(
+        self,
+        init_cb: impl FnOnce(&mut Renderer) -> U + 'static,
+        handler: impl FnMut(WorkerEvent, &mut Renderer, &mut U) + 'static,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        console_log::init_with_level(log::Level::Warn)?;
+        let scope: DedicatedWorkerGlobalScope = js_sys::global()
+            .dyn_into()
+            .map_err(|_| WorkerError::NotInWorker)?;
+        let Self { render_size, clock } = self;
+        let runtime = Rc::new(RefCell::new(Runtime {
+            state: RuntimeState::WaitingForCanvas,
+            render_size,
+            clock,
+            init_cb: Some(init_cb),
+            handler,
+            queued: vec![],
+        }));
+        let onmessage = {
+            let runtime = Rc::clone(&runtime);
+            Closure::<dyn FnMut(MessageEvent)>::new(move |evt: MessageEvent| {
+                runtime.borrow_mut().message(evt.data())
+            })
+        };
+        scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        // The handler lives as long as the worker does
+        onmessage.forget();
+        // The frame callback reschedules itself through this cell
+        let frame: Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>> = Rc::new(RefCell::new(None));
+        let next_frame = Rc::clone(&frame);
+        let frame_scope = scope.clone();
+        *frame.borrow_mut() = Some(Closure::new(move |_time: f64| {
+            runtime.borrow_mut().frame();
+            frame_scope
+                .request_animation_frame(
+                    next_frame
+                        .borrow()
+                        .as_ref()
+                        .unwrap()
+                        .as_ref()
+                        .unchecked_ref(),
+                )
+                .unwrap();
+        }));
+        scope
+            .request_animation_frame(frame.borrow().as_ref().unwrap().as_ref().unchecked_ref())
+            .map_err(|err| WorkerError::Js(format!("{err:?}")))?;
+        Ok(())
+    }
+}
+
+enum RuntimeState<U> {
+    WaitingForCanvas,
+    #[allow(clippy::type_complexity)]
+    PollingFuture(Pin<Box<dyn Future<Output = Result<Renderer, Box<dyn std::error::Error>>>>>),
+    Running(Renderer, U),
+}
+
+struct Runtime<U, I, H> {
+    state: RuntimeState<U>,
+    render_size: Option<(u32, u32)>,
+    clock: Clock,
+    init_cb: Option<I>,
+    handler: H,
+    // Messages that arrived before the renderer was ready
+    queued: Vec<JsValue>,
+}
+
+impl<U, I, H> Runtime<U, I, H>
+where
+    I: FnOnce(&mut Renderer) -> U,
+    H: FnMut(WorkerEvent, &mut Renderer, &mut U),
+{
+    fn message(&mut self, data: JsValue) {
+        match &mut self.state {
+            RuntimeState::Running(frend, userdata) => {
+                dispatch(&mut self.handler, data, frend, userdata)
+            }
+            RuntimeState::WaitingForCanvas => match offscreen_canvas(&data) {
+                Some(canvas) => {
+                    let (w, h) = self
+                        .render_size
+                        .unwrap_or((canvas.width(), canvas.height()));
+                    self.state = RuntimeState::PollingFuture(Box::pin(
+                        Renderer::with_offscreen_canvas(w, h, canvas),
+                    ));
+                }
+                None => self.queued.push(data),
+            },
+            RuntimeState::PollingFuture(_) => self.queued.push(data),
+        }
+    }
+    fn frame(&mut self) {
+        let ready = match &mut self.state {
+            RuntimeState::PollingFuture(future) => {
+                let mut cx = Context::from_waker(Waker::noop());
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(frend) => Some(frend),
+                    Poll::Pending => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(frend) = ready {
+            let mut frend = frend.unwrap();
+            let mut userdata = self.init_cb.take().unwrap()(&mut frend);
+            for data in std::mem::take(&mut self.queued) {
+                dispatch(&mut self.handler, data, &mut frend, &mut userdata);
+            }
+            self.clock.set_now(crate::clock::Instant::now());
+            self.state = RuntimeState::Running(frend, userdata);
+        }
+        if let RuntimeState::Running(frend, userdata) = &mut self.state {
+            let steps = self.clock.tick();
+            (self.handler)(WorkerEvent::Run(steps), frend, userdata);
+        }
+    }
+}
+
+fn dispatch<U>(
+    handler: &mut impl FnMut(WorkerEvent, &mut Renderer, &mut U),
+    data: JsValue,
+    frend: &mut Renderer,
+    userdata: &mut U,
+) {
+    let event = match resize_message(&data) {
+        Some((w, h)) => {
+            frend.resize_surface(w, h);
+            WorkerEvent::Resize(w, h)
+        }
+        None => WorkerEvent::Message(data),
+    };
+    handler(event, frend, userdata);
+}
+
+fn offscreen_canvas(data: &JsValue) -> Option<OffscreenCanvas> {
+    if let Some(canvas) = data.dyn_ref::<OffscreenCanvas>() {
+        return Some(canvas.clone());
+    }
+    js_sys::Reflect::get(data, &JsValue::from_str("canvas"))
+        .ok()?
+        .dyn_into()
+        .ok()
+}
+
+fn resize_message(data: &JsValue) -> Option<(u32, u32)> {
+    let size: js_sys::Array = js_sys::Reflect::get(data, &JsValue::from_str("resize"))
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    Some((size.get(0).as_f64()? as u32, size.get(1).as_f64()? as u32))
+}